@@ -21,6 +21,8 @@ pub enum Token {
     EEq,
     EIf,
     EEndIf,
+    // `}{`, ends the true block of an `EIf` and starts its false block.
+    EElse,
     ELsl,
     ELsr,
     ERor,
@@ -44,6 +46,9 @@ pub enum Token {
     EBreak,
     EClear,
     EDup,
+    ESwap,
+    EPick,
+    ENum,
     ETrap,
     // Invalid
     EInvalid,
@@ -113,7 +118,7 @@ impl Token {
 
     pub fn is_arity_zero(&self) -> bool {
         match *self {
-            Token::EDump | Token::ENop | Token::EEndIf => true,
+            Token::EDump | Token::ENop | Token::EEndIf | Token::EElse => true,
             _ => false,
         }
     }
@@ -148,6 +153,18 @@ impl Token {
         }
     }
 
+    pub fn is_flag(&self) -> bool {
+        match *self {
+            Token::IZero(_) |
+            Token::ICarry(_) |
+            Token::IParity(_) |
+            Token::IOverflow(_) |
+            Token::ISign(_) |
+            Token::IBorrow(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn updates_result(&self) -> bool {
         // If it an operator
         if self.is_binary() || self.is_unary() || self.is_arity_zero() {
@@ -203,6 +220,7 @@ impl Tokenize for Tokenizer {
                     Token::EEq],
 
                     "}" => vec![Token::EEndIf],
+                    "}{" => vec![Token::EElse],
 
                     "|" => vec![Token::EOr],
                     "|=" => vec![Token::PCopy(1), Token::EOr, Token::PPop(1),
@@ -388,6 +406,9 @@ impl Tokenize for Tokenizer {
                     "BREAK" => vec![Token::EBreak],
                     "CLEAR" => vec![Token::EClear],
                     "DUP" => vec![Token::EDup],
+                    "SWAP" => vec![Token::ESwap],
+                    "PICK" => vec![Token::EPick],
+                    "NUM" => vec![Token::ENum],
                     "TRAP" => vec![Token::ETrap],
                     _   => {
             // Handle internal vars
@@ -446,6 +467,14 @@ mod test {
         assert_eq!(op[0], Tokenizer::tokenize("+")[0]);
     }
 
+    #[test]
+    fn esil_else() {
+        let tokens = Tokenizer::tokenize("zf,?{,1,rax,=,}{,2,rax,=,}");
+        assert_eq!(Token::EIf, tokens[1]);
+        assert_eq!(Token::EElse, tokens[5]);
+        assert_eq!(Token::EEndIf, tokens[9]);
+    }
+
     #[test]
     fn negative_int() {
         assert_eq!(Token::EConstant(0xFFFFFFFFFFFFFFFF),
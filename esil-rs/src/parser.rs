@@ -15,6 +15,7 @@ use std::collections::{HashMap, VecDeque};
 pub enum ParserError {
     InvalidPop,
     InvalidDup,
+    InvalidPick,
     TooMany(Token),
     InvalidOpcode,
     InsufficientOperands,
@@ -33,6 +34,7 @@ impl ToString for ParserError {
             ParserError::InsufficientOperands => "Insufficient operands!".to_string(),
             ParserError::Unimplemented => "Unimplemented".to_string(),
             ParserError::InvalidDup => "Invalid use of EDup!".to_string(),
+            ParserError::InvalidPick => "Invalid use of EPick!".to_string(),
             ParserError::UnknownOperandSize => "Unknown operand size".to_string(),
         }
     }
@@ -61,6 +63,9 @@ pub struct Parser {
     pub eold_: Option<Token>,
     pub ecur: Option<Token>,
     pub lastsz: Option<Token>,
+    // If set, the flags (`$z`, `$c`, `$b`, `$p`, `$o` and `$s`) are returned to the consumer as
+    // operands instead of being expanded, leaving their computation to the consumer.
+    pub pass_flags: bool,
 }
 
 pub trait Parse {
@@ -106,6 +111,15 @@ impl Parse for Parser {
 
         while let Some(token) = self.tokens.as_mut().unwrap().pop_front() {
             match token {
+                // Flags left to the consumer.
+                Token::IZero(_) |
+                Token::ICarry(_) |
+                Token::IParity(_) |
+                Token::IOverflow(_) |
+                Token::ISign(_) |
+                Token::IBorrow(_) if self.pass_flags => {
+                    self.push(token);
+                }
                 // Esil Internal Vars
                 Token::IZero(_) |
                 Token::ICarry(_) |
@@ -168,6 +182,33 @@ impl Parse for Parser {
                     };
                     self.push(top);
                 }
+                Token::ESwap => {
+                    let len = self.stack.len();
+                    if len < 2 {
+                        return Err(ParserError::InsufficientOperands);
+                    }
+                    self.stack.swap(len - 1, len - 2);
+                }
+                Token::EPick => {
+                    // Copy the n-th element from the top of the esil stack, counting from 1:
+                    // `1,PICK` is `DUP`.
+                    let n = match self.stack.pop() {
+                        Some(Token::EConstant(n)) => n as usize,
+                        _ => return Err(ParserError::InvalidPick),
+                    };
+                    let len = self.stack.len();
+                    if n == 0 || n > len {
+                        return Err(ParserError::InvalidPick);
+                    }
+                    let picked = self.stack[len - n].clone();
+                    self.push(picked);
+                }
+                // Operands are always evaluated by the consumer, there is nothing to convert.
+                Token::ENum => {
+                    if self.stack.is_empty() {
+                        return Err(ParserError::InsufficientOperands);
+                    }
+                }
                 // Invalid. Let the Evaluator decide what to do with it.
                 // Esil Opcodes. Return to the Evaluator.
                 _ => {
@@ -231,6 +272,7 @@ impl Parse for Parser {
                     }
                     Some(Token::EConstant(_)) => self.default_size,
                     Some(Token::EEntry(_, n)) => n.unwrap_or(self.default_size),
+                    Some(ref t) if t.is_flag() => 1,
                     _ => unreachable!(),
                 }));
             } else {
@@ -258,6 +300,7 @@ impl Parser {
             eold_: None,
             ecur: None,
             lastsz: None,
+            pass_flags: false,
         }
     }
 
@@ -533,6 +576,17 @@ mod test {
         assert_eq!(expected, expression);
     }
 
+    #[test]
+    fn parser_pass_flags() {
+        let mut parser = Parser::init(None, None);
+        parser.pass_flags = true;
+        let token = parser.parse::<_, Tokenizer>("$z,zf,=").unwrap().unwrap();
+        assert_eq!(token, Token::EEq);
+        let (lhs, rhs) = parser.fetch_operands(&token).unwrap();
+        assert_eq!(lhs, Some(Token::EIdentifier("zf".to_owned())));
+        assert_eq!(rhs, Some(Token::IZero(64)));
+    }
+
     #[test]
     fn parser_of() {
         // of = ((((~eold ^ eold_) & (enew ^ eold)) >> (lastsz - 1)) & 1) == 1
@@ -721,6 +775,25 @@ mod test {
         assert_eq!(parser.skip_esil_set, 1);
     }
 
+    #[test]
+    fn parser_stack_ops() {
+        assert_eq!("(EEq  rax, (EAdd  rbx, rbx))", construct!("rbx,DUP,+,rax,="));
+        assert_eq!("(EEq  rax, (ESub  rbx, rcx))", construct!("rbx,rcx,SWAP,-,rax,="));
+        assert_eq!("(EEq  rax, (EAdd  rbx, rbx))", construct!("rbx,1,PICK,+,rax,="));
+        assert_eq!("(EEq  rax, (EAdd  rcx, rcx))", construct!("rcx,rbx,2,PICK,SWAP,POP,+,rax,="));
+        assert_eq!("(EEq  rax, rbx)", construct!("rbx,NUM,rax,="));
+    }
+
+    #[test]
+    fn parser_invalid_pick() {
+        for esil in &["rbx,0,PICK,rax,=", "rcx,rbx,3,PICK,rax,="] {
+            match ExpressionConstructor::run(esil, None) {
+                Err(ParserError::InvalidPick) => {}
+                r => panic!("{}: {:?}", esil, r),
+            }
+        }
+    }
+
     #[test]
     fn parser_follow_false() {
         // TODO
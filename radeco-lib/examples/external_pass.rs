@@ -0,0 +1,108 @@
+// Example of a pass modifying the SSA from outside of radeco-lib
+
+extern crate r2papi;
+extern crate radeco_lib;
+extern crate serde_json;
+
+use r2papi::structs::LRegInfo;
+use radeco_lib::frontend::radeco_containers::RadecoFunction;
+use radeco_lib::middle::ir_reader;
+use radeco_lib::middle::ir_writer;
+use radeco_lib::middle::regfile::SubRegisterFile;
+use radeco_lib::middle::ssa::prelude::*;
+
+use std::fs;
+use std::sync::Arc;
+
+const REGISTER_PROFILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/test_files/x86_register_profile.json"
+);
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const MASK_SSA_TXT: &str = "\
+define-fun sym.mask(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Unknown64 = %1 & #xffffffff;
+        [@0x001004.0001] %4: $Unknown64 = %1 + #x1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %3;
+        $mem = %2;
+}
+";
+
+/// Replaces the masks `x & 0xffffffff` with `ZeroExt64(Narrow32(x))`.
+fn narrow_masks(ssa: &mut SSAStorage) -> usize {
+    let masks = ssa
+        .blocks()
+        .into_iter()
+        .flat_map(|b| ssa.exprs_in(b))
+        .filter(|&n| {
+            ssa.opcode(n) == Some(MOpcode::OpAnd)
+                && ssa.operands_of(n).len() == 2
+                && ssa.constant(ssa.operands_of(n)[1]) == Some(0xffff_ffff)
+        })
+        .collect::<Vec<_>>();
+    for &mask in &masks {
+        let block = ssa.block_for(mask).unwrap();
+        let addr = ssa.address(mask).unwrap();
+        let value = ssa.operands_of(mask)[0];
+
+        let vt32 = ValueInfo::new_scalar(WidthSpec::new_known(32));
+        let narrow = ssa.insert_op(MOpcode::OpNarrow(32), vt32, None).unwrap();
+        ssa.op_use(narrow, 0, value);
+        ssa.insert_into_block(narrow, block, addr);
+        propagate_registers(ssa, narrow);
+
+        let vt64 = ValueInfo::new_scalar(WidthSpec::new_known(64));
+        let ext = ssa.insert_op(MOpcode::OpZeroExt(64), vt64, None).unwrap();
+        ssa.op_use(ext, 0, narrow);
+        ssa.insert_into_block(ext, block, addr);
+        propagate_registers(ssa, ext);
+
+        // Moves the uses of the mask to the extension, and removes the mask.
+        ssa.replace_value(mask, ext);
+    }
+    masks.len()
+}
+
+/// Removes the arithmetic operations whose result is never used.
+fn remove_unused(ssa: &mut SSAStorage) -> usize {
+    let unused = ssa
+        .blocks()
+        .into_iter()
+        .flat_map(|b| ssa.exprs_in(b))
+        .filter(|&n| match ssa.opcode(n) {
+            Some(MOpcode::OpAdd) | Some(MOpcode::OpSub) | Some(MOpcode::OpAnd) => {
+                ssa.uses_of(n).is_empty()
+            }
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    for &node in &unused {
+        ssa.remove_value(node);
+    }
+    unused.len()
+}
+
+fn main() {
+    let s = fs::read_to_string(REGISTER_PROFILE).expect("Failed to read the register profile");
+    let reg_profile: LRegInfo = serde_json::from_str(&s).unwrap();
+    let regfile = Arc::new(SubRegisterFile::new(&reg_profile).unwrap());
+    let mut rfn = RadecoFunction::default();
+    *rfn.ssa_mut() = ir_reader::parse_il(MASK_SSA_TXT, regfile);
+
+    let narrowed = narrow_masks(rfn.ssa_mut());
+    let removed = remove_unused(rfn.ssa_mut());
+    println!("{} masks narrowed, {} values removed", narrowed, removed);
+    assert!(verify(rfn.ssa()).is_ok(), "The SSA is inconsistent");
+
+    let mut il = String::new();
+    ir_writer::emit_il(&mut il, Some("sym.mask".to_owned()), rfn.ssa()).unwrap();
+    println!("{}", il);
+}
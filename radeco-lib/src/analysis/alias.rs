@@ -0,0 +1,373 @@
+//! Flow-insensitive points-to analysis, for memory disambiguation.
+//!
+//! Every value of a function is given the set of abstract locations it may point into: the stack
+//! frame of the function, the global memory, the memory returned by an allocator (e.g. `malloc`)
+//! at a given call site, or an unknown location. The sets are propagated through copies, phis and
+//! additions/subtractions of a constant, regardless of the order of the instructions. Anything
+//! else, e.g. the arguments of the function, the results of the calls or the values loaded from
+//! memory, may point anywhere: the unknown location aliases every other one.
+//!
+//! Two loads/stores whose addresses point into disjoint sets of locations can't access the same
+//! memory, e.g. a stack slot and a global. The sets are recorded in every function (see
+//! `RadecoFunction::alias_sets`), where `SpillForward` uses them to look past the stores which
+//! can't overwrite the loaded memory.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::frontend::radeco_containers::{FunctionKind, RadecoModule};
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{NodeType, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+
+use std::any::Any;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+const NAME: &str = "pointsto";
+const REQUIRES: &[AnalyzerKind] = &[AnalyzerKind::CallSiteFixer];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::PointsTo,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// libc functions returning a pointer to newly allocated memory.
+pub const ALLOCATORS: &[&str] = &["calloc", "malloc", "realloc", "strdup", "strndup"];
+
+/// Returns `true` if `name`, possibly decorated by r2 (e.g. `sym.imp.malloc`), is a known
+/// allocator.
+pub fn is_allocator(name: &str) -> bool {
+    let name = name.rsplit('.').next().unwrap_or(name);
+    ALLOCATORS.contains(&name)
+}
+
+/// An abstract memory location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Location {
+    /// The stack frame of the function.
+    Stack,
+    /// The memory at constant addresses, e.g. the globals.
+    Global,
+    /// The memory returned by the allocator called at the given call site.
+    Heap(u64),
+    /// Any memory, including all the other locations.
+    Unknown,
+}
+
+/// The locations the loads and stores of a function may access.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AliasSets {
+    /// Frame base registers.
+    bases: Vec<String>,
+    /// Call sites of the allocators, with the register holding the returned pointer.
+    allocations: HashMap<u64, String>,
+    /// Locations the address of every load/store may point into.
+    accesses: HashMap<NodeIndex, BTreeSet<Location>>,
+}
+
+impl AliasSets {
+    /// Creates empty `AliasSets`, which consider the given registers (typically the ones aliased
+    /// by `BP` and `SP`) as pointers to the stack frame.
+    pub fn new(bp_name: Option<String>, sp_name: Option<String>) -> Self {
+        AliasSets {
+            bases: bp_name.into_iter().chain(sp_name).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Records that the call at `csite` returns in `reg` a pointer to newly allocated memory.
+    pub fn add_allocation(&mut self, csite: u64, reg: String) {
+        self.allocations.insert(csite, reg);
+    }
+
+    /// Returns the locations the load/store `node` may access, or `None` if `node` wasn't a
+    /// load/store of the SSA given to the last `update`.
+    pub fn locations(&self, node: NodeIndex) -> Option<&BTreeSet<Location>> {
+        self.accesses.get(&node)
+    }
+
+    /// Returns `false` if the loads/stores `a` and `b` can't access the same memory.
+    pub fn may_alias(&self, a: NodeIndex, b: NodeIndex) -> bool {
+        match (self.locations(a), self.locations(b)) {
+            (Some(la), Some(lb)) => {
+                la.contains(&Location::Unknown)
+                    || lb.contains(&Location::Unknown)
+                    || la.intersection(lb).next().is_some()
+            }
+            _ => true,
+        }
+    }
+
+    /// Computes the locations of the loads and stores of `ssa`. It has to be called again
+    /// whenever the SSA changes, as the locations are indexed by node.
+    pub fn update(&mut self, ssa: &SSAStorage) {
+        let heap = self.allocated(ssa);
+        let values = ssa.values();
+        let mut points_to: HashMap<NodeIndex, BTreeSet<Location>> = HashMap::new();
+        loop {
+            let mut changed = false;
+            for &value in &values {
+                let locs = match heap.get(&value) {
+                    Some(&csite) => Some(Location::Heap(csite)).into_iter().collect(),
+                    None => self.transfer(ssa, value, &points_to),
+                };
+                let entry = points_to.entry(value).or_insert_with(BTreeSet::new);
+                let len = entry.len();
+                entry.extend(locs);
+                changed |= entry.len() != len;
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        self.accesses = values
+            .into_iter()
+            .filter_map(|n| match ssa.opcode(n) {
+                Some(MOpcode::OpLoad) | Some(MOpcode::OpStore) => {
+                    let addr = *ssa.operands_of(n).get(1)?;
+                    Some((n, points_to.get(&addr).cloned().unwrap_or_default()))
+                }
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Returns the values holding the pointers returned by the allocators, with the address of
+    /// their call site.
+    fn allocated(&self, ssa: &SSAStorage) -> HashMap<NodeIndex, u64> {
+        let mut heap = HashMap::new();
+        for node in ssa.blocks().into_iter().flat_map(|b| ssa.exprs_in(b)) {
+            if ssa.opcode(node) != Some(MOpcode::OpCall) {
+                continue;
+            }
+            let csite = match ssa.address(node) {
+                Some(addr) => addr.address,
+                None => continue,
+            };
+            let reg = self
+                .allocations
+                .get(&csite)
+                .and_then(|reg| ssa.regfile.register_id_by_name(reg));
+            if let Some((ret, _)) = reg.and_then(|r| utils::call_rets(node, ssa).get(r).cloned()) {
+                heap.insert(ret, csite);
+            }
+        }
+        heap
+    }
+
+    /// Locations `value` may point into, given the ones of its operands.
+    fn transfer(
+        &self,
+        ssa: &SSAStorage,
+        value: NodeIndex,
+        points_to: &HashMap<NodeIndex, BTreeSet<Location>>,
+    ) -> BTreeSet<Location> {
+        let of = |n: NodeIndex| points_to.get(&n).cloned().unwrap_or_default();
+        if ssa.constant(value).is_some() {
+            return Some(Location::Global).into_iter().collect();
+        }
+        let ops = ssa.operands_of(value);
+        match ssa.node_data(value).map(|nd| nd.nt) {
+            Ok(NodeType::Argument(ref reg)) if self.bases.contains(reg) => {
+                Some(Location::Stack).into_iter().collect()
+            }
+            Ok(NodeType::Phi) => ops.into_iter().flat_map(&of).collect(),
+            Ok(NodeType::Op(MOpcode::OpMov))
+            | Ok(NodeType::Op(MOpcode::OpZeroExt(_)))
+            | Ok(NodeType::Op(MOpcode::OpSignExt(_))) => {
+                ops.into_iter().take(1).flat_map(&of).collect()
+            }
+            Ok(NodeType::Op(MOpcode::OpAdd)) if ops.len() == 2 => {
+                match (ssa.constant(ops[0]), ssa.constant(ops[1])) {
+                    (None, Some(_)) => of(ops[0]),
+                    (Some(_), None) => of(ops[1]),
+                    _ => ops.into_iter().flat_map(&of).collect(),
+                }
+            }
+            Ok(NodeType::Op(MOpcode::OpSub))
+                if ops.len() == 2 && ssa.constant(ops[1]).is_some() =>
+            {
+                of(ops[0])
+            }
+            _ => Some(Location::Unknown).into_iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PointsTo {
+    /// Addresses of the known allocators, with the register holding the returned pointer
+    allocators: HashMap<u64, Option<String>>,
+}
+
+impl PointsTo {
+    pub fn new() -> Self {
+        PointsTo {
+            allocators: HashMap::new(),
+        }
+    }
+
+    fn find_allocators(&mut self, rmod: &RadecoModule) {
+        let ret_of = |offset: u64| {
+            rmod.functions
+                .get(&offset)
+                .and_then(|rfn| rfn.callconv.as_ref())
+                .and_then(|cc| cc.ret.clone())
+        };
+        let imports = rmod
+            .imports
+            .values()
+            .filter(|imp| is_allocator(&imp.name))
+            .map(|imp| imp.plt);
+        let functions = rmod
+            .functions
+            .values()
+            .filter(|rfn| rfn.kind != FunctionKind::Local && is_allocator(&rfn.name))
+            .map(|rfn| rfn.offset);
+        let offsets = imports.chain(functions).collect::<HashSet<_>>();
+        self.allocators = offsets.into_iter().map(|o| (o, ret_of(o))).collect();
+    }
+}
+
+impl Analyzer for PointsTo {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for PointsTo {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        self.find_allocators(rmod);
+        for rfn in rmod.functions.values_mut() {
+            if rfn.kind != FunctionKind::Local {
+                continue;
+            }
+            let mut sets = {
+                let regfile = &rfn.ssa().regfile;
+                let bp_name = regfile.get_name_by_alias(&"BP".to_string());
+                let sp_name = regfile.get_name_by_alias(&"SP".to_string());
+                AliasSets::new(bp_name.map(|s| s.to_owned()), sp_name.map(|s| s.to_owned()))
+            };
+            // Without a known return register, the allocated memory is unknown.
+            let own_ret = rfn.callconv.as_ref().and_then(|cc| cc.ret.clone());
+            let ssa = rfn.ssa();
+            for node in ssa.blocks().into_iter().flat_map(|b| ssa.exprs_in(b)) {
+                if ssa.opcode(node) != Some(MOpcode::OpCall) {
+                    continue;
+                }
+                let target = utils::call_info(node, ssa).and_then(|ci| ssa.constant(ci.target));
+                let ret = match target.and_then(|t| self.allocators.get(&t)) {
+                    Some(ret) => ret.clone().or_else(|| own_ret.clone()),
+                    None => continue,
+                };
+                if let (Some(addr), Some(reg)) = (ssa.address(node), ret) {
+                    sets.add_allocation(addr.address, reg);
+                }
+            }
+            sets.update(ssa);
+            rfn.set_alias_sets(sets);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::{all, FuncAnalyzer};
+    use crate::analysis::spill::SpillForward;
+    use crate::frontend::radeco_containers::RadecoFunction;
+    use crate::utils::test_support::{load_at, register_file};
+
+    // The global at 0x601000 is reloaded after a store to `rbp - 0x8` and one through `rsi`.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const GLOBAL_SSA_TXT: &str = "\
+define-fun sym.global(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Unknown64 = $rdi;
+        %3: $Unknown64 = $rsi;
+        %4: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %5: $Unknown0 = Store(%4, #x601000, %2);
+        [@0x001008.0001] %6: $Unknown64 = %1 - #x8;
+        [@0x001008.0002] %7: $Unknown0 = Store(%5, %6, %3);
+        [@0x00100c.0001] %8: $Unknown64 = Load(%7, #x601000);
+        [@0x00100e.0001] %9: $Unknown0 = Store(%7, %3, %2);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %8;
+        $mem = %9;
+}
+";
+
+    fn find_op(rfn: &RadecoFunction, opc: MOpcode) -> Vec<NodeIndex> {
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&n| ssa.opcode(n) == Some(opc.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn stack_store_does_not_alias_global() {
+        let rfn = load_at(GLOBAL_SSA_TXT, 0x1000, &register_file());
+
+        let mut rmod = RadecoModule::default();
+        rmod.functions.insert(0x1000, rfn);
+        let mut points_to = PointsTo::new();
+        points_to.analyze(&mut rmod, None::<fn(_) -> _>);
+
+        let rfn = rmod.functions.get_mut(&0x1000).unwrap();
+        let stores = find_op(rfn, MOpcode::OpStore);
+        let load = find_op(rfn, MOpcode::OpLoad)[0];
+        {
+            let sets = rfn.alias_sets().expect("No alias sets");
+            let locations = |n| {
+                sets.locations(n)
+                    .unwrap()
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(locations(stores[0]), vec![Location::Global]);
+            assert_eq!(locations(stores[1]), vec![Location::Stack]);
+            assert_eq!(locations(stores[2]), vec![Location::Unknown]);
+            assert_eq!(locations(load), vec![Location::Global]);
+            assert!(!sets.may_alias(stores[1], load));
+            assert!(sets.may_alias(stores[2], load));
+            assert!(sets.may_alias(stores[2], stores[1]));
+        }
+
+        // The load of the global is forwarded past the store to the stack.
+        let mut spill_forward = SpillForward::new(Some("rbp".to_owned()), Some("rsp".to_owned()));
+        spill_forward.analyze(rfn, Some(all));
+        assert!(find_op(rfn, MOpcode::OpLoad).is_empty());
+        let ssa = rfn.ssa();
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        assert!(ssa
+            .operands_of(exit)
+            .into_iter()
+            .filter_map(|n| ssa.argument(n))
+            .any(|reg| reg == "rdi"));
+    }
+}
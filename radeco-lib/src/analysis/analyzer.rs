@@ -1,13 +1,22 @@
 use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::From;
 use std::fmt::Debug;
 
+use petgraph::algo::tarjan_scc;
 use petgraph::graph::NodeIndex;
+use petgraph::Graph;
 
 use crate::analysis::cse::cse;
-use crate::analysis::functions::{fix_ssa_opcalls, infer_regusage};
-use crate::analysis::interproc::interproc;
-use crate::analysis::{arithmetic, copy_propagation, dce, inst_combine, sccp};
+use crate::analysis::functions::{
+    canary, devirtualize, fix_ssa_opcalls, infer_regusage, purity, signatures,
+};
+use crate::analysis::interproc::{argprop, interproc};
+use crate::analysis::{
+    alias, arithmetic, bool_ext, byteswap, copy_propagation, dce, flag_fold, globals, inst_combine,
+    intrinsics, jump_table, mem_phi, rodata, sccp, simplify, simplify_flags, spill, stackvars,
+    uninit,
+};
 use crate::frontend::radeco_containers::{RadecoFunction, RadecoModule};
 
 /// This trait provides access to extra informations generated during the analysis pass.
@@ -18,15 +27,34 @@ pub trait AnalyzerResult: Any + Debug {
 /// Kind of `Analyzer`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AnalyzerKind {
+    ArgConstProp,
     Arithmetic,
+    BoolExt,
+    ByteSwap,
     CallSiteFixer,
+    CallTypes,
     Combiner,
     CopyPropagation,
     CSE,
     DCE,
+    Devirtualizer,
+    FlagFold,
+    GlobalVars,
     Inferer,
     InterProc,
+    Intrinsics,
+    JumpTables,
+    MemPhiElim,
+    PointsTo,
+    Purity,
+    RodataFold,
     SCCP,
+    Simplify,
+    SimplifyFlags,
+    SpillForward,
+    StackCanary,
+    StackVars,
+    UninitReads,
 }
 
 /// A struct providing information about an analyzer.
@@ -38,7 +66,7 @@ pub struct AnalyzerInfo {
     /// The type of this `Analyzer`.
     pub kind: AnalyzerKind,
 
-    /// A list of `Analyzer`s that should be run before running this one.
+    /// A list of `Analyzer`s that should be run before running this one, see `schedule`.
     ///
     /// # Note
     /// These analyzers are **not guaranteed** to be called before this one.
@@ -62,15 +90,34 @@ pub struct AnalyzerInfo {
 impl From<AnalyzerKind> for &'static AnalyzerInfo {
     fn from(kind: AnalyzerKind) -> &'static AnalyzerInfo {
         match kind {
+            AnalyzerKind::ArgConstProp => &argprop::INFO,
             AnalyzerKind::Arithmetic => &arithmetic::INFO,
+            AnalyzerKind::BoolExt => &bool_ext::INFO,
+            AnalyzerKind::ByteSwap => &byteswap::INFO,
             AnalyzerKind::CallSiteFixer => &fix_ssa_opcalls::INFO,
+            AnalyzerKind::CallTypes => &signatures::INFO,
             AnalyzerKind::Combiner => &inst_combine::INFO,
             AnalyzerKind::CopyPropagation => &copy_propagation::INFO,
             AnalyzerKind::CSE => &cse::INFO,
             AnalyzerKind::DCE => &dce::INFO,
+            AnalyzerKind::Devirtualizer => &devirtualize::INFO,
+            AnalyzerKind::FlagFold => &flag_fold::INFO,
+            AnalyzerKind::GlobalVars => &globals::INFO,
             AnalyzerKind::Inferer => &infer_regusage::INFO,
             AnalyzerKind::InterProc => &interproc::INFO,
+            AnalyzerKind::Intrinsics => &intrinsics::INFO,
+            AnalyzerKind::JumpTables => &jump_table::INFO,
+            AnalyzerKind::MemPhiElim => &mem_phi::INFO,
+            AnalyzerKind::PointsTo => &alias::INFO,
+            AnalyzerKind::Purity => &purity::INFO,
+            AnalyzerKind::RodataFold => &rodata::INFO,
             AnalyzerKind::SCCP => &sccp::INFO,
+            AnalyzerKind::Simplify => &simplify::INFO,
+            AnalyzerKind::SimplifyFlags => &simplify_flags::INFO,
+            AnalyzerKind::SpillForward => &spill::INFO,
+            AnalyzerKind::StackCanary => &canary::INFO,
+            AnalyzerKind::StackVars => &stackvars::INFO,
+            AnalyzerKind::UninitReads => &uninit::INFO,
         }
     }
 }
@@ -79,6 +126,16 @@ impl From<AnalyzerKind> for &'static AnalyzerInfo {
 pub trait Analyzer: Any + Debug {
     fn info(&self) -> &'static AnalyzerInfo;
     fn as_any(&self) -> &dyn Any;
+
+    /// The name of this `Analyzer`, see `AnalyzerInfo::name`.
+    fn name(&self) -> &'static str {
+        self.info().name
+    }
+
+    /// The `Analyzer`s to run before this one, see `AnalyzerInfo::requires`.
+    fn dependencies(&self) -> &'static [AnalyzerKind] {
+        self.info().requires
+    }
 }
 
 /// An atomic change to the IR.
@@ -137,6 +194,12 @@ pub trait FuncAnalyzer: Analyzer {
         func: &mut RadecoFunction,
         policy: Option<T>,
     ) -> Option<Box<dyn AnalyzerResult>>;
+
+    /// Number of internal iterations needed by the last call to `analyze` to reach a fixed point.
+    /// It is `None` for `Analyzer`s which do not iterate internally.
+    fn iterations_used(&self) -> Option<u32> {
+        None
+    }
 }
 
 /// An `Analyzer` that takes a module.
@@ -158,19 +221,94 @@ pub trait ModuleAnalyzer: Analyzer {
 pub fn all_func_analyzers() -> Vec<AnalyzerKind> {
     vec![
         AnalyzerKind::Arithmetic,
+        AnalyzerKind::BoolExt,
+        AnalyzerKind::ByteSwap,
         AnalyzerKind::Combiner,
         AnalyzerKind::CopyPropagation,
         AnalyzerKind::CSE,
         AnalyzerKind::DCE,
+        AnalyzerKind::FlagFold,
+        AnalyzerKind::Intrinsics,
+        AnalyzerKind::MemPhiElim,
         AnalyzerKind::SCCP,
+        AnalyzerKind::Simplify,
+        AnalyzerKind::SimplifyFlags,
+        AnalyzerKind::SpillForward,
     ]
 }
 
 /// Get all the available `ModuleAnalyzer`s
 pub fn all_module_analyzers() -> Vec<AnalyzerKind> {
     vec![
+        AnalyzerKind::ArgConstProp,
         AnalyzerKind::CallSiteFixer,
+        AnalyzerKind::CallTypes,
+        AnalyzerKind::Devirtualizer,
+        AnalyzerKind::GlobalVars,
         AnalyzerKind::Inferer,
         AnalyzerKind::InterProc,
+        AnalyzerKind::JumpTables,
+        AnalyzerKind::PointsTo,
+        AnalyzerKind::Purity,
+        AnalyzerKind::RodataFold,
+        AnalyzerKind::StackCanary,
     ]
 }
+
+/// Get a list of the `FuncAnalyzer`s which are run once, after the ones of `all_func_analyzers`
+/// simplified the IR.
+pub fn final_func_analyzers() -> Vec<AnalyzerKind> {
+    vec![AnalyzerKind::StackVars, AnalyzerKind::UninitReads]
+}
+
+/// Returns `true` if the `Analyzer` of `kind` is a `FuncAnalyzer`, see `all_func_analyzers` and
+/// `final_func_analyzers`.
+pub fn is_func_analyzer(kind: AnalyzerKind) -> bool {
+    all_func_analyzers().contains(&kind) || final_func_analyzers().contains(&kind)
+}
+
+/// Returns the kind of the `Analyzer` named `name`, see `AnalyzerInfo::name`.
+pub fn kind_by_name(name: &str) -> Option<AnalyzerKind> {
+    all_func_analyzers()
+        .into_iter()
+        .chain(all_module_analyzers())
+        .chain(final_func_analyzers())
+        .find(|&kind| <&AnalyzerInfo>::from(kind).name == name)
+}
+
+/// Returns `analyzers` along with the ones they require, transitively, in the order to run them:
+/// each `Analyzer` comes after the ones it requires. The ones requiring each other are in an
+/// arbitrary order.
+pub fn schedule(analyzers: &[AnalyzerKind]) -> Vec<AnalyzerKind> {
+    // Build the dependency graph.
+    let mut graph = Graph::<AnalyzerKind, ()>::new();
+    let mut kind2id = HashMap::new();
+    let mut expanded = HashSet::new();
+    let mut worklist = analyzers.iter().cloned().collect::<VecDeque<_>>();
+
+    while let Some(analyzer) = worklist.pop_front() {
+        if !expanded.insert(analyzer) {
+            continue;
+        }
+        let n = *kind2id
+            .entry(analyzer)
+            .or_insert_with(|| graph.add_node(analyzer));
+        let info: &'static AnalyzerInfo = From::from(analyzer);
+
+        for dep in info.requires {
+            let d = *kind2id.entry(*dep).or_insert_with(|| graph.add_node(*dep));
+            graph.add_edge(d, n, ());
+            worklist.push_back(*dep);
+        }
+    }
+
+    // Compute the reverse topological sorting using the Tarjan's algorithm. If SCCs are present
+    // we can safely run the analyzers within the same SCC in arbitrary order because the `requires`
+    // property is not garanteed to be respected.
+    tarjan_scc(&graph)
+        .into_iter()
+        .flatten()
+        .map(|id| graph[id])
+        .rev()
+        .collect()
+}
@@ -0,0 +1,305 @@
+//! Looks through the extensions of the booleans tested by conditional jumps.
+//!
+//! Comparisons yield 1-bit values, which the code often widens before using them, e.g. `setl al;
+//! movzx eax, al; test eax, eax; jne`. The boolean is then tested through a zero extension into a
+//! subregister, the merge of the subregister into its register, a narrowing, another zero
+//! extension and a `test`. This pass recognizes the selectors of the conditional blocks which
+//! test such a widened boolean `b` and replaces them with `b` itself:
+//!
+//!  * `Narrow1(x)` becomes `b`;
+//!  * `x == 0` becomes `!b`.
+//!
+//! `x` is the widened boolean, that is any combination of `ZeroExt(x)`, `Narrow(x)`, `x & x`,
+//! `x & c` (with the lowest bit of `c` set), `x + 0`, `x - 0` and of the merge `(r & c) | x` of `x`
+//! into a register, whose upper bits are dropped by a narrowing (see `widened_bool`). Negations of
+//! the above (e.g. `jne`) are kept as `OpNot` of `b`. As in `FlagFold`, only the selector is
+//! replaced: the widened boolean survives if it is used elsewhere, e.g. stored to memory, and is
+//! cleaned up by `DCE` otherwise.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+};
+use crate::analysis::flag_fold::{mask, split_const, width_of};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::{MOpcode, WidthSpec};
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+
+/// A `Change` which replaces the selector of `block` with the boolean `value` it tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoolTest {
+    /// The conditional block.
+    pub block: NodeIndex,
+    /// The current selector of `block`.
+    pub selector: NodeIndex,
+    /// The 1-bit value tested by the selector.
+    pub value: NodeIndex,
+    /// Whether `value` has to be negated.
+    pub negated: bool,
+}
+
+impl Change for BoolTest {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+const NAME: &str = "bool_ext";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::BoolExt,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+/// Returns the 1-bit value `b` such that the lowest `bits` bits of `node` are the zero extension
+/// of `b`, looking through the idioms described in the module documentation.
+pub fn widened_bool(ssa: &SSAStorage, node: NodeIndex, bits: u16) -> Option<NodeIndex> {
+    if bits == 0 {
+        return None;
+    }
+    let width = width_of(ssa, node)?;
+    if width == 1 {
+        return Some(node);
+    }
+    let ops = ssa.operands_of(node);
+    match ssa.opcode(node)? {
+        MOpcode::OpMov if ops.len() == 1 => widened_bool(ssa, ops[0], bits),
+        MOpcode::OpZeroExt(_) if ops.len() == 1 => {
+            let inner = width_of(ssa, ops[0])?;
+            widened_bool(ssa, ops[0], bits.min(inner))
+        }
+        MOpcode::OpNarrow(n) if ops.len() == 1 => widened_bool(ssa, ops[0], bits.min(n)),
+        MOpcode::OpAnd if ops.len() == 2 && ops[0] == ops[1] => widened_bool(ssa, ops[0], bits),
+        MOpcode::OpAnd => match split_const(ssa, node) {
+            Some((x, c)) if c & 1 == 1 => widened_bool(ssa, x, bits),
+            _ => None,
+        },
+        MOpcode::OpAdd | MOpcode::OpOr => match split_const(ssa, node) {
+            Some((x, 0)) => widened_bool(ssa, x, bits),
+            _ if ops.len() == 2 => {
+                // The merge of `x` into a register, `(r & c)` being zero in the lowest bits.
+                let cleared = |n: NodeIndex| {
+                    ssa.opcode(n) == Some(MOpcode::OpAnd)
+                        && split_const(ssa, n).map_or(false, |(_, c)| c & mask(bits) == 0)
+                };
+                if cleared(ops[0]) {
+                    widened_bool(ssa, ops[1], bits)
+                } else if cleared(ops[1]) {
+                    widened_bool(ssa, ops[0], bits)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        },
+        MOpcode::OpSub if ops.len() == 2 && ssa.constant(ops[1]) == Some(0) => {
+            widened_bool(ssa, ops[0], bits)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct BoolExt {}
+
+impl BoolExt {
+    pub fn new() -> Self {
+        BoolExt {}
+    }
+
+    /// Matches the tests described in the module documentation, looking through `OpNot` and
+    /// `OpMov`. Returns (value, negated).
+    fn match_test(ssa: &SSAStorage, node: NodeIndex, negated: bool) -> Option<(NodeIndex, bool)> {
+        let ops = ssa.operands_of(node);
+        match ssa.opcode(node) {
+            Some(MOpcode::OpNot) if ops.len() == 1 => BoolExt::match_test(ssa, ops[0], !negated),
+            Some(MOpcode::OpMov) if ops.len() == 1 => BoolExt::match_test(ssa, ops[0], negated),
+            // Already 1-bit wide, so `widened_bool` would stop at the narrowing itself.
+            Some(MOpcode::OpNarrow(1)) if ops.len() == 1 => {
+                widened_bool(ssa, ops[0], 1).map(|value| (value, negated))
+            }
+            Some(MOpcode::OpEq) => match split_const(ssa, node) {
+                Some((x, 0)) => {
+                    let value = widened_bool(ssa, x, width_of(ssa, x)?)?;
+                    Some((value, !negated))
+                }
+                _ => None,
+            },
+            _ => widened_bool(ssa, node, 1).map(|value| (value, negated)),
+        }
+    }
+
+    fn gather(ssa: &SSAStorage) -> Vec<BoolTest> {
+        ssa.blocks()
+            .into_iter()
+            .filter_map(|block| {
+                let selector = ssa.selector_in(block)?;
+                let (value, negated) = BoolExt::match_test(ssa, selector, false)?;
+                // Skip the selectors which already are `value` or `!value`.
+                let done = if negated {
+                    ssa.opcode(selector) == Some(MOpcode::OpNot)
+                        && ssa.operands_of(selector) == vec![value]
+                } else {
+                    selector == value
+                };
+                if done {
+                    return None;
+                }
+                Some(BoolTest {
+                    block: block,
+                    selector: selector,
+                    value: value,
+                    negated: negated,
+                })
+            })
+            .collect()
+    }
+
+    fn apply(ssa: &mut SSAStorage, test: BoolTest) -> Option<NodeIndex> {
+        let new_selector = if test.negated {
+            let addr = ssa.address(test.selector)?;
+            let vt = ValueInfo::new_scalar(WidthSpec::new_known(1));
+            let not = ssa.insert_op(MOpcode::OpNot, vt, None)?;
+            ssa.op_use(not, 0, test.value);
+            ssa.insert_into_block(not, test.block, addr);
+            not
+        } else {
+            test.value
+        };
+        // Only the selector edge is moved, other users of the widened boolean keep it alive.
+        ssa.remove_edges_between(test.block, test.selector);
+        ssa.set_selector(new_selector, test.block);
+        Some(new_selector)
+    }
+}
+
+impl Analyzer for BoolExt {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for BoolExt {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        func: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        let ssa = func.ssa_mut();
+
+        for test in BoolExt::gather(ssa) {
+            match policy(Box::new(test.clone())) {
+                Action::Apply => {
+                    radeco_trace!("bool_ext|{:?}", test);
+                    if BoolExt::apply(ssa, test).is_none() {
+                        radeco_err!("Failed to replace the selector");
+                    }
+                }
+                Action::Skip => (),
+                Action::Abort => break,
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::utils::test_support::load;
+
+    // setl al; movzx eax, al; test eax, eax; jne 0x100e
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SETL_JNE_SSA_TXT: &str = "\
+define-fun sym.setl_jne(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown64 = $rax;
+        %4: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %5: $Unknown1 = %1 < %2;
+        [@0x001000.0002] %6: $Unknown64 = %3 & #xffffffffffffff00;
+        [@0x001000.0003] %7: $Unknown64 = ZeroExt64(%5);
+        [@0x001000.0004] %8: $Unknown64 = %6 | %7;
+        [@0x001003.0001] %9: $Unknown8 = Narrow8(%8);
+        [@0x001003.0002] %10: $Unknown32 = ZeroExt32(%9);
+        [@0x001003.0003] %11: $Unknown64 = ZeroExt64(%10);
+        [@0x001006.0001] %12: $Unknown32 = %10 & %10;
+        [@0x001006.0002] %13: $Unknown1 = %12 == #x0;
+        [@0x001006.0003] %14: $Unknown1 = !%13;
+        JMP IF %14 0x00100e.0000 ELSE 0x00100c.0000
+    bb_0x00100c.0000(sz 0x2):
+        RETURN
+    bb_0x00100e.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %11;
+        $mem = %4;
+}
+";
+
+    fn selector(rfn: &RadecoFunction) -> NodeIndex {
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .filter_map(|b| ssa.selector_in(b))
+            .next()
+            .unwrap()
+    }
+
+    #[test]
+    fn setl_movzx_jne_is_lt() {
+        let mut rfn = load(SETL_JNE_SSA_TXT);
+        let mut bool_ext = BoolExt::new();
+        bool_ext.analyze(&mut rfn, Some(all));
+        let selector = selector(&rfn);
+        let ssa = rfn.ssa();
+        // `!(x == 0)` is the comparison itself, without the extensions.
+        assert_eq!(ssa.opcode(selector), Some(MOpcode::OpLt));
+        // The extension written to `rax` is kept.
+        let exit_regs = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        assert!(ssa
+            .operands_of(exit_regs)
+            .into_iter()
+            .any(|n| ssa.opcode(n) == Some(MOpcode::OpZeroExt(64))));
+        assert!(BoolExt::gather(ssa).is_empty());
+    }
+
+    #[test]
+    fn narrow1_is_lt() {
+        let il = SETL_JNE_SSA_TXT.replace(
+            "\
+        [@0x001006.0001] %12: $Unknown32 = %10 & %10;
+        [@0x001006.0002] %13: $Unknown1 = %12 == #x0;
+        [@0x001006.0003] %14: $Unknown1 = !%13;
+        JMP IF %14",
+            "\
+        [@0x001006.0001] %12: $Unknown1 = Narrow1(%10);
+        JMP IF %12",
+        );
+        let mut rfn = load(&il);
+        let mut bool_ext = BoolExt::new();
+        bool_ext.analyze(&mut rfn, Some(all));
+        let selector = selector(&rfn);
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(selector), Some(MOpcode::OpLt));
+        assert!(BoolExt::gather(ssa).is_empty());
+    }
+}
@@ -0,0 +1,344 @@
+//! Folds byte swap idioms into `OpByteSwap`.
+//!
+//! `bswap`, `htonl` and friends are usually compiled into an or of shifted and masked copies of
+//! the same value, e.g. for 32 bits:
+//!
+//! ```text
+//! (x << 24) | ((x << 8) & 0xff0000) | ((x >> 8) & 0xff00) | (x >> 24)
+//! ```
+//!
+//! This pass looks at every tree of `OpOr`s whose leaves are `x`, shifted by whole bytes and
+//! masked with whole bytes (before or after the shift). If together the leaves move every byte of
+//! `x` to its mirrored position, and nothing else, the root of the tree is replaced with a single
+//! `OpByteSwap` of `x`. Swaps of 2, 4 and 8 bytes are recognized; anything else is left alone.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+};
+use crate::analysis::flag_fold::{split_const, width_of};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::{self, MOpcode};
+use crate::middle::ssa::ssa_traits::{SSAMod, SSAWalk, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+
+/// A `Change` which replaces `root` with the byte swap of `value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldByteSwap {
+    /// The root of the tree of `OpOr`s.
+    pub root: NodeIndex,
+    /// The value whose bytes are swapped.
+    pub value: NodeIndex,
+    /// Width of `value` and of the swap.
+    pub width: u16,
+}
+
+impl Change for FoldByteSwap {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+const NAME: &str = "byteswap";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::ByteSwap,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+/// A leaf of the tree: `value` shifted left by `shift` bits (right if negative), then masked
+/// with `mask`.
+#[derive(Debug, Clone, Copy)]
+struct Term {
+    value: NodeIndex,
+    shift: i32,
+    mask: u64,
+}
+
+#[derive(Debug)]
+pub struct ByteSwap {}
+
+impl ByteSwap {
+    pub fn new() -> ByteSwap {
+        ByteSwap {}
+    }
+
+    /// Matches `value << shift`, `value >> shift` or `value` itself.
+    fn match_shift(ssa: &SSAStorage, node: NodeIndex) -> (NodeIndex, i32) {
+        let ops = ssa.operands_of(node);
+        let dir = match ssa.opcode(node) {
+            Some(MOpcode::OpLsl) => 1,
+            Some(MOpcode::OpLsr) => -1,
+            _ => return (node, 0),
+        };
+        match (ops.len(), ops.get(1).and_then(|&s| ssa.constant(s))) {
+            (2, Some(s)) if s < 64 => (ops[0], dir * s as i32),
+            _ => (node, 0),
+        }
+    }
+
+    /// Matches a leaf of the tree, at width `width`.
+    fn match_term(ssa: &SSAStorage, node: NodeIndex, width: u16) -> Term {
+        let full = ir::truncate_const(u64::max_value(), width);
+        let shifted = |mask: u64, shift: i32| {
+            if shift >= 0 {
+                (mask << shift) & full
+            } else {
+                mask >> -shift
+            }
+        };
+
+        // Mask after the shift.
+        let (inner, mask) = match ssa.opcode(node) {
+            Some(MOpcode::OpAnd) => split_const(ssa, node).unwrap_or((node, full)),
+            _ => (node, full),
+        };
+        let (value, shift) = ByteSwap::match_shift(ssa, inner);
+        if value != inner {
+            // Mask before the shift.
+            if let (Some(MOpcode::OpAnd), Some((v, m))) =
+                (ssa.opcode(value), split_const(ssa, value))
+            {
+                return Term {
+                    value: v,
+                    shift: shift,
+                    mask: mask & shifted(m, shift),
+                };
+            }
+        }
+        Term {
+            value: value,
+            shift: shift,
+            mask: mask & shifted(full, shift),
+        }
+    }
+
+    /// Collects the leaves of the tree of `OpOr`s rooted at `root`.
+    fn leaves(ssa: &SSAStorage, root: NodeIndex) -> Vec<NodeIndex> {
+        let mut leaves = Vec::new();
+        let mut worklist = vec![root];
+        while let Some(node) = worklist.pop() {
+            if ssa.opcode(node) == Some(MOpcode::OpOr) {
+                worklist.extend(ssa.operands_of(node));
+            } else {
+                leaves.push(node);
+            }
+        }
+        leaves
+    }
+
+    /// Returns (value, width) if `root` swaps the bytes of value.
+    fn match_swap(ssa: &SSAStorage, root: NodeIndex) -> Option<(NodeIndex, u16)> {
+        let width = width_of(ssa, root)?;
+        if width != 16 && width != 32 && width != 64 {
+            return None;
+        }
+        let bytes = i32::from(width / 8);
+
+        let terms = ByteSwap::leaves(ssa, root)
+            .into_iter()
+            .map(|leaf| ByteSwap::match_term(ssa, leaf, width))
+            .collect::<Vec<_>>();
+        let value = terms.first()?.value;
+        if width_of(ssa, value) != Some(width) {
+            return None;
+        }
+
+        // Source byte of every byte of the result.
+        let mut sources = vec![None; bytes as usize];
+        for term in &terms {
+            if term.value != value || term.shift % 8 != 0 {
+                return None;
+            }
+            for dst in 0..bytes {
+                match (term.mask >> (dst * 8)) & 0xff {
+                    0 => continue,
+                    0xff => {}
+                    // Bytes are moved whole.
+                    _ => return None,
+                }
+                let src = dst - term.shift / 8;
+                if sources[dst as usize].is_some() {
+                    return None;
+                }
+                sources[dst as usize] = Some(src);
+            }
+        }
+
+        let swapped = sources
+            .iter()
+            .enumerate()
+            .all(|(dst, &src)| src == Some(bytes - 1 - dst as i32));
+        if swapped {
+            Some((value, width))
+        } else {
+            None
+        }
+    }
+
+    fn gather_folds(ssa: &SSAStorage) -> Vec<FoldByteSwap> {
+        ssa.inorder_walk()
+            .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpOr))
+            .filter_map(|root| {
+                let (value, width) = ByteSwap::match_swap(ssa, root)?;
+                Some(FoldByteSwap {
+                    root: root,
+                    value: value,
+                    width: width,
+                })
+            })
+            .collect()
+    }
+
+    fn apply(ssa: &mut SSAStorage, fold: FoldByteSwap) -> Option<NodeIndex> {
+        let block = ssa.block_for(fold.root)?;
+        let addr = ssa.address(fold.root)?;
+        let vt = ssa.node_data(fold.root).ok()?.vt;
+        let swap = ssa.insert_op(MOpcode::OpByteSwap(fold.width), vt, None)?;
+        ssa.op_use(swap, 0, fold.value);
+        ssa.insert_into_block(swap, block, addr);
+        ssa.replace_value(fold.root, swap);
+        Some(swap)
+    }
+}
+
+impl Analyzer for ByteSwap {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for ByteSwap {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        func: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        let ssa = func.ssa_mut();
+
+        for fold in ByteSwap::gather_folds(ssa) {
+            // A previous fold may have replaced this tree.
+            if ssa.opcode(fold.root) != Some(MOpcode::OpOr) {
+                continue;
+            }
+            match policy(Box::new(fold)) {
+                Action::Apply => {
+                    radeco_trace!("byteswap|{:?}", fold);
+                    if ByteSwap::apply(ssa, fold).is_none() {
+                        radeco_err!("Failed to fold the byte swap");
+                    }
+                }
+                Action::Skip => (),
+                Action::Abort => break,
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::utils::test_support::load;
+
+    // (x << 24) | ((x << 8) & 0xff0000) | ((x >> 8) & 0xff00) | (x >> 24)
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const BSWAP32_SSA_TXT: &str = "\
+define-fun sym.bswap32(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %3: $Unknown32 = Narrow32(%1);
+        [@0x001000.0002] %4: $Unknown32 = %3 << #x18;
+        [@0x001000.0003] %5: $Unknown32 = %3 << #x8;
+        [@0x001000.0004] %6: $Unknown32 = %5 & #xff0000;
+        [@0x001000.0005] %7: $Unknown32 = %3 >> #x8;
+        [@0x001000.0006] %8: $Unknown32 = %7 & #xff00;
+        [@0x001000.0007] %9: $Unknown32 = %3 >> #x18;
+        [@0x001000.0008] %10: $Unknown32 = %4 | %6;
+        [@0x001000.0009] %11: $Unknown32 = %10 | %8;
+        [@0x001000.000a] %12: $Unknown32 = %11 | %9;
+        [@0x001000.000b] %13: $Unknown64 = ZeroExt64(%12);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %13;
+        $mem = %2;
+}
+";
+
+    // The middle bytes are not swapped.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const PARTIAL_SSA_TXT: &str = "\
+define-fun sym.partial(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %3: $Unknown32 = Narrow32(%1);
+        [@0x001000.0002] %4: $Unknown32 = %3 << #x18;
+        [@0x001000.0003] %5: $Unknown32 = %3 & #xffff00;
+        [@0x001000.0004] %6: $Unknown32 = %3 >> #x18;
+        [@0x001000.0005] %7: $Unknown32 = %4 | %5;
+        [@0x001000.0006] %8: $Unknown32 = %7 | %6;
+        [@0x001000.0007] %9: $Unknown64 = ZeroExt64(%8);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %9;
+        $mem = %2;
+}
+";
+
+    fn byte_swaps(rfn: &RadecoFunction) -> Vec<NodeIndex> {
+        let ssa = rfn.ssa();
+        ssa.inorder_walk()
+            .filter(|&n| match ssa.opcode(n) {
+                Some(MOpcode::OpByteSwap(_)) => true,
+                _ => false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bswap32() {
+        let mut rfn = load(BSWAP32_SSA_TXT);
+        ByteSwap::new().analyze(&mut rfn, Some(all));
+
+        let swaps = byte_swaps(&rfn);
+        assert_eq!(swaps.len(), 1);
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(swaps[0]), Some(MOpcode::OpByteSwap(32)));
+        let uses = ssa.uses_of(swaps[0]);
+        assert_eq!(uses.len(), 1);
+        assert_eq!(ssa.opcode(uses[0]), Some(MOpcode::OpZeroExt(64)));
+    }
+
+    #[test]
+    fn partial_swap_untouched() {
+        let mut rfn = load(PARTIAL_SSA_TXT);
+        ByteSwap::new().analyze(&mut rfn, Some(all));
+
+        assert!(byte_swaps(&rfn).is_empty());
+    }
+
+    #[test]
+    fn const_byte_swap() {
+        assert_eq!(ir::byte_swap(0x11223344, 32), 0x44332211);
+        assert_eq!(ir::byte_swap(0xaabb, 16), 0xbbaa);
+        assert_eq!(ir::byte_swap(0x0102030405060708, 64), 0x0807060504030201);
+    }
+}
@@ -54,15 +54,19 @@ where
     fn get_priority(&self, node_data: NodeData) -> (u16, u64) {
         match node_data.nt {
             NodeType::Undefined => (PUNDEFINED, 0),
-            NodeType::Comment(_) => (PCOMMENT, 0),
+            NodeType::Comment(_) | NodeType::Argument(_) => (PCOMMENT, 0),
             NodeType::Phi => (PPHI, 0),
             NodeType::Op(opc) => (
                 opc.idx() + OP_BASE,
                 match opc {
                     MOpcode::OpConst(num) => num,
-                    MOpcode::OpNarrow(num) | MOpcode::OpZeroExt(num) | MOpcode::OpSignExt(num) => {
-                        num as u64
-                    }
+                    MOpcode::OpNarrow(num)
+                    | MOpcode::OpZeroExt(num)
+                    | MOpcode::OpSignExt(num)
+                    | MOpcode::OpByteSwap(num)
+                    | MOpcode::OpRepMovs(num)
+                    | MOpcode::OpRepScas(num)
+                    | MOpcode::OpRepStos(num) => num as u64,
                     _ => 0,
                 },
             ),
@@ -124,7 +128,15 @@ where
                 MOpcode::OpNop => {
                     return self.return_value(Ordering::Equal, op1, op2);
                 }
-                MOpcode::OpCall | MOpcode::OpLoad | MOpcode::OpStore | MOpcode::OpITE => {
+                MOpcode::OpCall
+                | MOpcode::OpLoad
+                | MOpcode::OpStore
+                | MOpcode::OpITE
+                | MOpcode::OpRepMovs(_)
+                | MOpcode::OpRepScas(_)
+                | MOpcode::OpRepStos(_)
+                | MOpcode::OpTrap
+                | MOpcode::OpUnsupported(_) => {
                     let addr1 = self.ssa.address(op1).expect("No address information found");
                     let addr2 = self.ssa.address(op2).expect("No address information found");
                     return self.return_value(addr1.cmp(&addr2), op1, op2);
@@ -132,7 +144,10 @@ where
                 MOpcode::OpConst(_) => {
                     return self.return_value(priority1.1.cmp(&priority2.1), op1, op2);
                 }
-                MOpcode::OpSignExt(_) | MOpcode::OpZeroExt(_) | MOpcode::OpNarrow(_) => {
+                MOpcode::OpSignExt(_)
+                | MOpcode::OpZeroExt(_)
+                | MOpcode::OpNarrow(_)
+                | MOpcode::OpByteSwap(_) => {
                     if priority1.1.cmp(&priority2.1) != Ordering::Equal {
                         return self.return_value(priority1.1.cmp(&priority2.1), op1, op2);
                     } else {
@@ -8,9 +8,13 @@
 //! Dead code elimination
 //!
 //! Removes SSA nodes that are not used by any other node.
-//! The algorithm will not consider whether the uses keeping a node alive
-//! are in code that is actually executed or not. For a better analysis
-//! look at `analysis::constant_propagation`.
+//! Operations with side effects (see `MOpcode::has_sideeffects`), such as
+//! stores and calls, are always kept along with their operands.
+//! By default the algorithm will not consider whether the uses keeping a node
+//! alive are in code that is actually executed or not. Use
+//! `DCE::remove_unreachable` to also remove the blocks which cannot be
+//! reached from the entry. For a better analysis look at
+//! `analysis::constant_propagation`.
 
 use crate::analysis::analyzer::{
     Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer, RemoveValue,
@@ -22,10 +26,12 @@ use crate::middle::ssa::ssa_traits::{NodeType, SSAExtra, SSAMod, SSA};
 use crate::middle::ssa::ssastorage::SSAStorage;
 
 use std::any::Any;
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 
 #[derive(Debug)]
-pub struct DCE {}
+pub struct DCE {
+    remove_unreachable: bool,
+}
 
 const NAME: &str = "dce";
 const REQUIRES: &[AnalyzerKind] = &[];
@@ -39,7 +45,51 @@ pub const INFO: AnalyzerInfo = AnalyzerInfo {
 
 impl DCE {
     pub fn new() -> Self {
-        DCE {}
+        DCE {
+            remove_unreachable: false,
+        }
+    }
+
+    /// Also remove the blocks that cannot be reached from the entry node, along with the nodes
+    /// they contain.
+    pub fn remove_unreachable(mut self) -> Self {
+        self.remove_unreachable = true;
+        self
+    }
+
+    // Removes the blocks not reachable from the entry node.
+    fn sweep_unreachable<T: FnMut(Box<dyn Change>) -> Action>(
+        &self,
+        ssa: &mut SSAStorage,
+        policy: &mut T,
+    ) -> bool {
+        let entry = entry_node_err!(ssa);
+        let exit = exit_node_err!(ssa);
+        let mut reachable = HashSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(entry);
+        while let Some(block) = queue.pop_front() {
+            if reachable.insert(block) {
+                queue.extend(ssa.succs_of(block));
+            }
+        }
+
+        for block in ssa.blocks() {
+            if reachable.contains(&block) || block == exit {
+                continue;
+            }
+            match policy(Box::new(RemoveValue(block))) {
+                Action::Apply => {
+                    radeco_trace!("dce_rm_unreachable|{:?}", block);
+                    ssa.remove_block(block);
+                }
+                Action::Skip => (),
+                Action::Abort => {
+                    return false;
+                }
+            };
+        }
+        true
     }
 
     // Marks node for removal. This method does not remove nodes.
@@ -143,12 +193,75 @@ impl FuncAnalyzer for DCE {
         rfn: &mut RadecoFunction,
         policy: Option<T>,
     ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        if self.remove_unreachable && !self.sweep_unreachable(rfn.ssa_mut(), &mut policy) {
+            return None;
+        }
         self.mark(rfn.ssa_mut());
-        self.sweep(
-            rfn.ssa_mut(),
-            policy.expect("A policy function must be provided"),
-        );
+        self.sweep(rfn.ssa_mut(), policy);
 
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ir::MOpcode;
+    use crate::utils::test_support::load;
+
+    // The result of the store is not used: the memory after the call is still %3.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const STORE_SSA_TXT: &str = "\
+define-fun sym.store(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown64 = %1 + %2;
+        [@0x001000.0002] %5: $Unknown0 = Store(%3, %1, %2);
+        JMP IF %1 0x001008.0000 ELSE 0x001010.0000
+    bb_0x001008.0000(sz 0x2):
+        RETURN
+    bb_0x001010.0000(sz 0x2):
+        RETURN
+    bb_0x001020.0000(sz 0x4):
+        [@0x001020.0001] %6: $Unknown0 = Store(%3, %2, %1);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %3;
+}
+";
+
+    fn count(rfn: &RadecoFunction, opcode: MOpcode) -> usize {
+        let ssa = rfn.ssa();
+        ssa.values()
+            .into_iter()
+            .filter(|&n| ssa.opcode(n) == Some(opcode.clone()))
+            .count()
+    }
+
+    #[test]
+    fn keeps_sideeffects() {
+        let mut rfn = load(STORE_SSA_TXT);
+        assert_eq!(count(&rfn, MOpcode::OpAdd), 1);
+        DCE::new().analyze(&mut rfn, Some(all));
+        assert_eq!(count(&rfn, MOpcode::OpAdd), 0);
+        assert_eq!(count(&rfn, MOpcode::OpStore), 2);
+    }
+
+    #[test]
+    fn removes_unreachable() {
+        let mut kept = load(STORE_SSA_TXT);
+        DCE::new().analyze(&mut kept, Some(all));
+        let mut pruned = load(STORE_SSA_TXT);
+        DCE::new()
+            .remove_unreachable()
+            .analyze(&mut pruned, Some(all));
+        assert_eq!(pruned.ssa().blocks().len(), kept.ssa().blocks().len() - 1);
+        assert_eq!(count(&pruned, MOpcode::OpStore), 1);
+    }
+}
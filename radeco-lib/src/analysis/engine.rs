@@ -1,61 +1,289 @@
 //! This module implements the logic needed to apply the proper analysis passes to simplify the IR.
 
-use petgraph::algo::tarjan_scc;
-use petgraph::Graph;
-
 use rayon::prelude::*;
 
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::time::{Duration, Instant};
 
+use crate::analysis::alias::PointsTo;
 use crate::analysis::analyzer;
 use crate::analysis::analyzer::{
     Action, AnalyzerInfo, AnalyzerKind, Change, FuncAnalyzer, ModuleAnalyzer,
 };
 use crate::analysis::arithmetic::{ArithChange, Arithmetic};
+use crate::analysis::bool_ext::BoolExt;
+use crate::analysis::byteswap::ByteSwap;
 use crate::analysis::copy_propagation::CopyPropagation;
 use crate::analysis::cse::cse::CSE;
 use crate::analysis::cse::ssasort::Sorter;
 use crate::analysis::dce::DCE;
+use crate::analysis::flag_fold::FlagFold;
+use crate::analysis::functions::canary::StackCanary;
+use crate::analysis::functions::devirtualize::Devirtualizer;
 use crate::analysis::functions::fix_ssa_opcalls::CallSiteFixer;
 use crate::analysis::functions::infer_regusage::Inferer;
+use crate::analysis::functions::purity::Purity;
+use crate::analysis::functions::signatures::{CallTypes, SignatureDb};
+use crate::analysis::globals::GlobalVars;
 use crate::analysis::inst_combine::Combiner;
+use crate::analysis::interproc::argprop::ArgConstProp;
 use crate::analysis::interproc::fixcall::CallFixer;
+use crate::analysis::intrinsics::Intrinsics;
+use crate::analysis::jump_table::JumpTables;
+use crate::analysis::mem_phi::MemPhiElim;
+use crate::analysis::rodata::RodataFold;
 use crate::analysis::sccp::SCCP;
+use crate::analysis::simplify::Simplify;
+use crate::analysis::simplify_flags::SimplifyFlags;
+use crate::analysis::spill::SpillForward;
+use crate::analysis::stackvars::StackVars;
+use crate::analysis::uninit::UninitReads;
+use crate::analysis::vrange::ValueRanges;
+use crate::error::RadecoError;
 use crate::frontend::radeco_containers::{FunctionKind, RadecoFunction, RadecoModule};
 use crate::middle::regfile::SubRegisterFile;
 
-fn sort_by_requires(analyzers: &Vec<AnalyzerKind>) -> impl Iterator<Item = AnalyzerKind> {
-    // Build the dependency graph.
-    let mut graph = Graph::<AnalyzerKind, ()>::new();
-    let mut kind2id = HashMap::new();
+/// Runs the `FuncAnalyzer` of `kind` once on `rfn`, returning the number of internal iterations
+/// it reports. The frame bases `bp_name` and `sp_name` are used to recognize the stack accesses.
+/// Nothing is run for the other `Analyzer`s.
+fn run_func_analyzer<T: FnMut(Box<dyn Change>) -> Action>(
+    kind: AnalyzerKind,
+    rfn: &mut RadecoFunction,
+    policy: T,
+    bp_name: &Option<String>,
+    sp_name: &Option<String>,
+) -> Option<u32> {
+    match kind {
+        AnalyzerKind::Arithmetic => {
+            let mut arithmetic = Arithmetic::new();
+            arithmetic.analyze(rfn, Some(policy));
+            arithmetic.iterations_used()
+        }
+        AnalyzerKind::BoolExt => {
+            let mut bool_ext = BoolExt::new();
+            bool_ext.analyze(rfn, Some(policy));
+            bool_ext.iterations_used()
+        }
+        AnalyzerKind::ByteSwap => {
+            let mut byteswap = ByteSwap::new();
+            byteswap.analyze(rfn, Some(policy));
+            byteswap.iterations_used()
+        }
+        AnalyzerKind::Combiner => {
+            let mut combiner = Combiner::new();
+            combiner.analyze(rfn, Some(policy));
+            combiner.iterations_used()
+        }
+        AnalyzerKind::CopyPropagation => {
+            let mut copy_propagation = CopyPropagation::new();
+            copy_propagation.analyze(rfn, Some(policy));
+            copy_propagation.iterations_used()
+        }
+        AnalyzerKind::CSE => {
+            let mut cse = CSE::new();
+            cse.analyze(rfn, Some(policy));
+            cse.iterations_used()
+        }
+        AnalyzerKind::DCE => {
+            let mut dce = DCE::new();
+            dce.analyze(rfn, Some(policy));
+            dce.iterations_used()
+        }
+        AnalyzerKind::FlagFold => {
+            let mut flag_fold = FlagFold::new();
+            flag_fold.analyze(rfn, Some(policy));
+            flag_fold.iterations_used()
+        }
+        AnalyzerKind::Intrinsics => {
+            let mut intrinsics = Intrinsics::new();
+            intrinsics.analyze(rfn, Some(policy));
+            intrinsics.iterations_used()
+        }
+        AnalyzerKind::MemPhiElim => {
+            let mut mem_phi_elim = MemPhiElim::new();
+            mem_phi_elim.analyze(rfn, Some(policy));
+            mem_phi_elim.iterations_used()
+        }
+        AnalyzerKind::SCCP => {
+            let mut sccp = SCCP::new();
+            sccp.analyze(rfn, Some(policy));
+            sccp.iterations_used()
+        }
+        AnalyzerKind::Simplify => {
+            let mut simplify = Simplify::new();
+            simplify.analyze(rfn, Some(policy));
+            simplify.iterations_used()
+        }
+        AnalyzerKind::SimplifyFlags => {
+            let mut simplify_flags = SimplifyFlags::new();
+            simplify_flags.analyze(rfn, Some(policy));
+            simplify_flags.iterations_used()
+        }
+        AnalyzerKind::SpillForward => {
+            let mut spill_forward = SpillForward::new(bp_name.clone(), sp_name.clone());
+            spill_forward.analyze(rfn, Some(policy));
+            spill_forward.iterations_used()
+        }
+        AnalyzerKind::StackVars => {
+            let mut stackvars = StackVars::new(bp_name.clone(), sp_name.clone());
+            stackvars.analyze(rfn, Some(policy));
+            stackvars.iterations_used()
+        }
+        AnalyzerKind::UninitReads => {
+            let mut uninit = UninitReads::new(bp_name.clone(), sp_name.clone());
+            uninit.analyze(rfn, Some(policy));
+            uninit.iterations_used()
+        }
+        _ => None,
+    }
+}
+
+/// Returns the names of the frame bases of `rfn`, its base pointer and stack pointer.
+fn frame_bases(rfn: &RadecoFunction) -> (Option<String>, Option<String>) {
+    let regfile = &rfn.ssa().regfile;
+    let bp_name = regfile.get_name_by_alias(&"BP".to_string());
+    let sp_name = regfile.get_name_by_alias(&"SP".to_string());
+    (bp_name.map(|s| s.to_owned()), sp_name.map(|s| s.to_owned()))
+}
+
+pub trait EngineResult: Any + Debug {}
+
+/// Statistics about a single `FuncAnalyzer` collected while running `RadecoEngine::run_func`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassStats {
+    /// The analyzer these statistics refer to.
+    pub kind: AnalyzerKind,
+    /// Number of times the analyzer has been run.
+    pub iterations: u32,
+    /// Total number of `Change`s applied by the analyzer.
+    pub changes: u32,
+    /// It is `true` if the last run of the analyzer did not apply any `Change`.
+    pub converged: bool,
+    /// Internal iterations of the analyzer summed over all its runs, if the analyzer reports
+    /// them (see `FuncAnalyzer::iterations_used`).
+    pub inner_iterations: Option<u32>,
+    /// Wall-clock time spent running the analyzer.
+    pub time: Duration,
+}
+
+impl PassStats {
+    fn new(kind: AnalyzerKind) -> PassStats {
+        PassStats {
+            kind: kind,
+            iterations: 0,
+            changes: 0,
+            converged: false,
+            inner_iterations: None,
+            time: Duration::default(),
+        }
+    }
+}
+
+/// Result of `RadecoEngine::run_func`, telling whether the analyzers reached a fixed point
+/// within the allowed number of iterations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvergenceReport {
+    /// The iteration cap the engine was run with.
+    pub max_iteration: u32,
+    /// Number of iterations actually performed.
+    pub iterations: u32,
+    /// It is `true` if a whole iteration completed without changing the IR.
+    pub converged: bool,
+    /// It is `true` if the engine gave up on the function once its time budget ran out.
+    pub timed_out: bool,
+    /// Per-analyzer statistics, in the order the analyzers were run.
+    pub passes: Vec<PassStats>,
+    /// Wall-clock time spent analyzing the function.
+    pub time: Duration,
+}
 
-    for analyzer in analyzers {
-        let n = kind2id
-            .entry(*analyzer)
-            .or_insert_with(|| graph.add_node(*analyzer))
-            .clone();
-        let info: &'static AnalyzerInfo = From::from(*analyzer);
+impl ConvergenceReport {
+    /// Returns `true` if the engine stopped because it ran out of iterations.
+    pub fn hit_cap(&self) -> bool {
+        !self.converged
+    }
 
-        for dep in info.requires {
-            let d = kind2id
-                .entry(*dep)
-                .or_insert_with(|| graph.add_node(*dep))
-                .clone();
-            graph.add_edge(d, n, ());
+    /// Builds a warning for `fn_name` if the iteration cap was hit or the time budget ran out,
+    /// `None` otherwise.
+    pub fn cap_warning(&self, fn_name: &str) -> Option<String> {
+        if self.timed_out {
+            return Some(format!(
+                "Analysis of {} timed out after {} iterations ({:.3}s), the output may be \
+                 unreliable",
+                fn_name,
+                self.iterations,
+                self.time.as_secs_f64()
+            ));
         }
+        if !self.hit_cap() {
+            return None;
+        }
+        let unstable = self
+            .passes
+            .iter()
+            .filter(|p| !p.converged)
+            .map(|p| format!("{:?}", p.kind))
+            .collect::<Vec<_>>();
+        Some(format!(
+            "Analysis of {} did not converge within {} iterations (still changing: {}), \
+             the output may be unreliable",
+            fn_name,
+            self.max_iteration,
+            unstable.join(", ")
+        ))
     }
+}
 
-    // Compute the reverse topological sorting using the Tarjan's algorithm. If SCCs are present
-    // we can safely run the analyzers within the same SCC in arbitrary order because the `requires`
-    // property is not garanteed to be respected.
-    let sccs = tarjan_scc(&graph);
+impl EngineResult for ConvergenceReport {}
 
-    sccs.into_iter().flatten().map(move |id| graph[id]).rev()
+/// Wall-clock timings collected from the `ConvergenceReport`s of several functions.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// Time spent in each analyzer, summed over all the recorded functions.
+    pub passes: HashMap<AnalyzerKind, Duration>,
+    /// Time spent analyzing each function.
+    pub functions: HashMap<String, Duration>,
 }
 
-pub trait EngineResult: Any + Debug {}
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub fn record(&mut self, fn_name: &str, report: &ConvergenceReport) {
+        for pass in &report.passes {
+            *self.passes.entry(pass.kind).or_default() += pass.time;
+        }
+        *self.functions.entry(fn_name.to_owned()).or_default() += report.time;
+    }
+
+    /// Renders the timings of the analyzers and of the functions, slowest first.
+    pub fn table(&self) -> String {
+        fn rows(title: &str, mut times: Vec<(String, Duration)>) -> String {
+            times.sort_by(|a, b| b.1.cmp(&a.1));
+            let mut table = format!("{:<40} {:>12}\n", title, "time (ms)");
+            for (name, time) in times {
+                let ms = time.as_secs_f64() * 1000.0;
+                table.push_str(&format!("{:<40} {:>12.3}\n", name, ms));
+            }
+            table
+        }
+
+        let passes = self
+            .passes
+            .iter()
+            .map(|(kind, time)| (format!("{:?}", kind), *time))
+            .collect();
+        let functions = self
+            .functions
+            .iter()
+            .map(|(name, time)| (name.clone(), *time))
+            .collect();
+        format!("{}\n{}", rows("pass", passes), rows("function", functions))
+    }
+}
 
 pub trait Engine: Any + Debug {
     fn run_module(
@@ -70,56 +298,58 @@ pub trait Engine: Any + Debug {
 #[derive(Debug)]
 pub struct RadecoEngine {
     max_iteration: u32,
+    collapse_canaries: bool,
+    fn_timeout: Option<Duration>,
+    signatures: SignatureDb,
 }
 
 impl RadecoEngine {
     pub fn new(max_iteration: u32) -> Self {
         RadecoEngine {
             max_iteration: max_iteration,
+            collapse_canaries: false,
+            fn_timeout: None,
+            signatures: SignatureDb::default(),
         }
     }
-}
-
-impl Engine for RadecoEngine {
-    fn run_module(
-        &self,
-        rmod: &mut RadecoModule,
-        regfile: &SubRegisterFile,
-    ) -> Option<Box<dyn EngineResult>> {
-        radeco_trace!("run_module");
-
-        // Analyze preserved for all functions.
-        {
-            let bp_name = regfile.get_name_by_alias(&"BP".to_string());
-            let bp_name = bp_name.map(|s| s.to_owned());
-            let sp_name = regfile.get_name_by_alias(&"SP".to_string());
-            let sp_name = sp_name.map(|s| s.to_owned());
-            let mut callfixer = CallFixer::new(rmod, bp_name, sp_name);
-            callfixer.rounded_analysis();
-        }
 
-        // Fix call sites
-        let mut call_site_fixer = CallSiteFixer::new();
-        call_site_fixer.analyze(rmod, None::<fn(_) -> _>);
-
-        // Infer calling conventions
-        let mut inferer = Inferer::new((*regfile).clone());
-        inferer.analyze(rmod, None::<fn(_) -> _>);
+    /// Sets whether the stack canary checks are removed from the functions by `run_module`, see
+    /// `StackCanary`. They are kept by default.
+    pub fn collapse_canaries(mut self, collapse: bool) -> Self {
+        self.collapse_canaries = collapse;
+        self
+    }
 
-        rmod.functions.par_iter_mut().for_each(|(_, rfn)| {
-            self.run_func(rfn);
-        });
+    /// Sets the wall-clock time `report_func` may spend on a function. The budget is checked
+    /// before every analyzer; once it is exceeded, the remaining ones are not run and the function
+    /// is recorded as timed out (see `RadecoFunction::timed_out`). There is no limit by default.
+    pub fn fn_timeout(mut self, budget: Duration) -> Self {
+        self.fn_timeout = Some(budget);
+        self
+    }
 
-        None
+    /// Sets the signatures used by `run_module` to type the values returned by the calls, see
+    /// `CallTypes`. The builtin ones of the common libc functions are used by default.
+    pub fn signatures(mut self, db: SignatureDb) -> Self {
+        self.signatures = db;
+        self
     }
 
-    fn run_func(&self, rfn: &mut RadecoFunction) -> Option<Box<dyn EngineResult>> {
+    /// Same as `Engine::run_func`, but returns the `ConvergenceReport` unboxed. It is `None` for
+    /// functions without code, e.g. imports, and for the ones which already timed out.
+    pub fn report_func(&self, rfn: &mut RadecoFunction) -> Option<ConvergenceReport> {
         radeco_trace!("run_func: {}", rfn.name);
+        let start = Instant::now();
 
         // There is no code for imported/relocated functions.
         if rfn.kind != FunctionKind::Local {
             return None;
         }
+        // The SSA of a function which timed out may be incomplete.
+        if rfn.timed_out() {
+            radeco_warn!("Skipping {}: timed out", rfn.name);
+            return None;
+        }
 
         // Try to convert the condition codes to relational operators. This should be done before
         // all the other passes.
@@ -145,57 +375,307 @@ impl Engine for RadecoEngine {
             sorter.run();
         }
 
-        let mut analyzers = sort_by_requires(&analyzer::all_func_analyzers());
+        let analyzers = analyzer::schedule(&analyzer::all_func_analyzers());
+        let mut report = ConvergenceReport {
+            max_iteration: self.max_iteration,
+            iterations: 0,
+            converged: false,
+            timed_out: false,
+            passes: analyzers.iter().map(|a| PassStats::new(*a)).collect(),
+            time: Duration::default(),
+        };
+
+        // The frame bases, used to recognize the stack accesses.
+        let (bp_name, sp_name) = frame_bases(rfn);
 
         // Run iteratively all the available analyzers until a stable point or the maximum
         // number of iterations is reached.
         for _ in 0..self.max_iteration {
+            report.iterations += 1;
             let mut stable = true;
 
             // Build and run the analyzers.
-            while let Some(analyzer) = analyzers.next() {
+            for (analyzer, stats) in analyzers.iter().zip(report.passes.iter_mut()) {
+                if self.fn_timeout.map_or(false, |t| start.elapsed() >= t) {
+                    report.timed_out = true;
+                    break;
+                }
                 radeco_trace!("running analyzer: {:?}", analyzer);
                 // If the policy is called then there is still something to change, thus this is
                 // not a stable point.
+                let mut changes = 0;
+                let pass_start = Instant::now();
                 let policy = |_| {
-                    stable = false;
+                    changes += 1;
                     Action::Apply
                 };
 
-                match analyzer {
-                    AnalyzerKind::Arithmetic => {
-                        let mut arithmetic = Arithmetic::new();
-                        arithmetic.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::Combiner => {
-                        let mut combiner = Combiner::new();
-                        combiner.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::CopyPropagation => {
-                        let mut copy_propagation = CopyPropagation::new();
-                        copy_propagation.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::CSE => {
-                        let mut cse = CSE::new();
-                        cse.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::DCE => {
-                        let mut dce = DCE::new();
-                        dce.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::SCCP => {
-                        let mut sccp = SCCP::new();
-                        sccp.analyze(rfn, Some(policy));
-                    }
-                    _ => (),
+                let inner = run_func_analyzer(*analyzer, rfn, policy, &bp_name, &sp_name);
+
+                stats.time += pass_start.elapsed();
+                stats.iterations += 1;
+                stats.changes += changes;
+                stats.converged = changes == 0;
+                if let Some(n) = inner {
+                    stats.inner_iterations = Some(stats.inner_iterations.unwrap_or(0) + n);
                 }
+                stable &= changes == 0;
             }
 
+            if report.timed_out {
+                rfn.set_timed_out(true);
+                report.time = start.elapsed();
+                return Some(report);
+            }
             if stable {
+                report.converged = true;
                 break;
             }
         }
 
+        // Recover the stack variables once the IR has been simplified.
+        let mut stackvars = StackVars::new(bp_name.clone(), sp_name.clone());
+        stackvars.analyze(rfn, None::<fn(_) -> _>);
+        let mut uninit = UninitReads::new(bp_name, sp_name);
+        uninit.analyze(rfn, None::<fn(_) -> _>);
+
+        let ranges = ValueRanges::new(rfn.ssa());
+        rfn.set_value_ranges(ranges);
+
+        report.time = start.elapsed();
+        Some(report)
+    }
+
+    /// Runs the `FuncAnalyzer` of `kind` once on `rfn`, after the ones it requires (see
+    /// `analyzer::schedule`), applying all their changes. Returns their statistics in the order
+    /// they were run. Fails if one of them is not a `FuncAnalyzer`, or if `rfn` has no code.
+    pub fn run_pass(
+        &self,
+        rfn: &mut RadecoFunction,
+        kind: AnalyzerKind,
+    ) -> Result<Vec<PassStats>, RadecoError> {
+        if rfn.kind != FunctionKind::Local {
+            return Err(RadecoError::Analysis(format!("{} has no code", rfn.name)));
+        }
+        let analyzers = analyzer::schedule(&[kind]);
+        if let Some(&other) = analyzers.iter().find(|&&a| !analyzer::is_func_analyzer(a)) {
+            let info: &'static AnalyzerInfo = From::from(other);
+            return Err(RadecoError::Analysis(format!(
+                "{} is not a function analyzer",
+                info.name
+            )));
+        }
+
+        let (bp_name, sp_name) = frame_bases(rfn);
+        let mut passes = Vec::new();
+        for analyzer in analyzers {
+            let mut stats = PassStats::new(analyzer);
+            let mut changes = 0;
+            let pass_start = Instant::now();
+            let policy = |_| {
+                changes += 1;
+                Action::Apply
+            };
+            stats.inner_iterations = run_func_analyzer(analyzer, rfn, policy, &bp_name, &sp_name);
+            stats.time = pass_start.elapsed();
+            stats.iterations = 1;
+            stats.changes = changes;
+            stats.converged = changes == 0;
+            passes.push(stats);
+        }
+        Ok(passes)
+    }
+}
+
+impl Engine for RadecoEngine {
+    fn run_module(
+        &self,
+        rmod: &mut RadecoModule,
+        regfile: &SubRegisterFile,
+    ) -> Option<Box<dyn EngineResult>> {
+        radeco_trace!("run_module");
+
+        // Analyze preserved for all functions.
+        {
+            let bp_name = regfile.get_name_by_alias(&"BP".to_string());
+            let bp_name = bp_name.map(|s| s.to_owned());
+            let sp_name = regfile.get_name_by_alias(&"SP".to_string());
+            let sp_name = sp_name.map(|s| s.to_owned());
+            let mut callfixer = CallFixer::new(rmod, bp_name, sp_name);
+            callfixer.rounded_analysis();
+        }
+
+        // Fix call sites
+        let mut call_site_fixer = CallSiteFixer::new();
+        call_site_fixer.analyze(rmod, None::<fn(_) -> _>);
+
+        // Infer calling conventions
+        let mut inferer = Inferer::new((*regfile).clone());
+        inferer.analyze(rmod, None::<fn(_) -> _>);
+
+        // Record which calls leave memory and registers untouched
+        let mut purity = Purity::new();
+        purity.analyze(rmod, None::<fn(_) -> _>);
+
+        // Type the values returned by the known library functions.
+        let mut call_types = CallTypes::new(self.signatures.clone());
+        call_types.analyze(rmod, None::<fn(_) -> _>);
+
+        // Replace the loads of constants from read-only sections, for SCCP to fold them further.
+        let mut rodata = RodataFold::new();
+        rodata.analyze(rmod, None::<fn(_) -> _>);
+
+        // Drop the stack canary checks before the functions are simplified.
+        if self.collapse_canaries {
+            let mut canary = StackCanary::with_collapse(true);
+            canary.analyze(rmod, None::<fn(_) -> _>);
+        }
+
+        // Tell apart the memory accessed by the loads and stores, for the load forwarding.
+        let mut points_to = PointsTo::new();
+        points_to.analyze(rmod, None::<fn(_) -> _>);
+
+        rmod.functions.par_iter_mut().for_each(|(_, rfn)| {
+            if let Some(_warning) = self
+                .report_func(rfn)
+                .and_then(|report| report.cap_warning(&rfn.name))
+            {
+                radeco_warn!(_warning);
+            }
+        });
+
+        // Resolve the jumps through the tables of the switches bounded by the simplified checks.
+        let mut jump_tables = JumpTables::new();
+        jump_tables.analyze(rmod, None::<fn(_) -> _>);
+
+        // Fold the arguments which are always passed the same constant.
+        let mut argprop = ArgConstProp::new();
+        argprop.analyze(rmod, None::<fn(_) -> _>);
+
+        // Indirect calls may have been resolved by the passes above.
+        let mut devirtualizer = Devirtualizer::new();
+        devirtualizer.analyze(rmod, None::<fn(_) -> _>);
+
+        // Name the globals accessed through the constant addresses left by the simplifications.
+        let mut globals = GlobalVars::new();
+        globals.analyze(rmod, None::<fn(_) -> _>);
+
         None
     }
+
+    fn run_func(&self, rfn: &mut RadecoFunction) -> Option<Box<dyn EngineResult>> {
+        self.report_func(rfn)
+            .map(|report| Box::new(report) as Box<dyn EngineResult>)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_support::load;
+
+    // `%3` and `%4` are dead, so DCE always has something to remove in the first iteration.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const DEAD_SSA_TXT: &str = "\
+define-fun sym.dead(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x2;
+        [@0x001004.0001] %4: $Unknown64 = %3 * #x3;
+        [@0x001004.0002] %5: $Unknown64 = %1 + #x1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %5;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn warns_when_cap_is_hit() {
+        let mut rfn = load(DEAD_SSA_TXT);
+        let report = RadecoEngine::new(1).report_func(&mut rfn).unwrap();
+        assert_eq!(report.iterations, 1);
+        assert!(report.hit_cap());
+        let dce = report
+            .passes
+            .iter()
+            .find(|p| p.kind == AnalyzerKind::DCE)
+            .unwrap();
+        assert!(!dce.converged);
+        assert!(dce.changes > 0);
+        assert!(report.cap_warning("sym.dead").unwrap().contains("DCE"));
+    }
+
+    #[test]
+    fn no_warning_on_fixed_point() {
+        let mut rfn = load(DEAD_SSA_TXT);
+        let report = RadecoEngine::new(10).report_func(&mut rfn).unwrap();
+        assert!(report.converged);
+        assert!(report.iterations > 1);
+        assert!(report.passes.iter().all(|p| p.converged));
+        assert_eq!(report.cap_warning("sym.dead"), None);
+    }
+
+    #[test]
+    fn profile_records_passes() {
+        let mut rfn = load(DEAD_SSA_TXT);
+        let report = RadecoEngine::new(10).report_func(&mut rfn).unwrap();
+        let mut profile = Profile::new();
+        profile.record("sym.dead", &report);
+
+        for kind in &[
+            AnalyzerKind::DCE,
+            AnalyzerKind::SCCP,
+            AnalyzerKind::CopyPropagation,
+        ] {
+            assert!(profile.passes.contains_key(kind));
+        }
+        assert!(profile.passes[&AnalyzerKind::DCE] <= report.time);
+        assert_eq!(profile.functions["sym.dead"], report.time);
+        let table = profile.table();
+        assert!(table.contains("DCE"));
+        assert!(table.contains("sym.dead"));
+    }
+
+    #[test]
+    fn schedule_runs_requirements_first() {
+        // `Intrinsics` matches the loop conditions as the comparisons `FlagFold` makes of the
+        // flags.
+        assert_eq!(
+            analyzer::schedule(&[AnalyzerKind::Intrinsics]),
+            vec![AnalyzerKind::FlagFold, AnalyzerKind::Intrinsics]
+        );
+        // `Purity` reads the register usage of the callees, which `Inferer` computes.
+        assert_eq!(
+            analyzer::schedule(&[AnalyzerKind::Purity]),
+            vec![AnalyzerKind::Inferer, AnalyzerKind::Purity]
+        );
+        // Every analyzer comes after the ones it requires.
+        let all = analyzer::schedule(&analyzer::all_func_analyzers());
+        assert_eq!(all.len(), analyzer::all_func_analyzers().len());
+        let position = |kind| all.iter().position(|&a| a == kind).unwrap();
+        assert!(position(AnalyzerKind::FlagFold) < position(AnalyzerKind::Intrinsics));
+    }
+
+    #[test]
+    fn run_pass_with_requirement() {
+        let mut rfn = load(DEAD_SSA_TXT);
+        let engine = RadecoEngine::new(10);
+        let passes = engine.run_pass(&mut rfn, AnalyzerKind::Intrinsics).unwrap();
+        let kinds = passes.iter().map(|p| p.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![AnalyzerKind::FlagFold, AnalyzerKind::Intrinsics]
+        );
+        assert!(passes.iter().all(|p| p.iterations == 1));
+
+        let passes = engine.run_pass(&mut rfn, AnalyzerKind::DCE).unwrap();
+        assert_eq!(passes.len(), 1);
+        assert!(passes[0].changes > 0);
+        // The module analyzers are not run on a single function.
+        assert!(engine.run_pass(&mut rfn, AnalyzerKind::Purity).is_err());
+    }
 }
@@ -0,0 +1,436 @@
+//! Folds flag computations into comparisons.
+//!
+//! ESIL models the flags explicitly, so a `cmp a, b; jcc` pair ends up as an `OpSub` feeding the
+//! extraction of one or more flags, which in turn feed the selector of the conditional block.
+//! This pass recognizes the following idioms and replaces the selector with a single comparison:
+//!
+//!  * `zf`: `Narrow1((a - b) ^ 1)`, with the subtraction and the xor at the same width, or
+//!    `(a - b) == 0` becomes `a == b`;
+//!  * `cf`: `a < (a - b)` (borrow) becomes `a < b`;
+//!  * `sf`: `(a - 0) >> (w - 1)` becomes `a > 0x7f..f`.
+//!
+//! Negations of the above (e.g. `jne`) are kept as `OpNot` of the comparison. Only the selector
+//! is replaced: the flag nodes are left alone, so they survive if they are used elsewhere and
+//! are cleaned up by `DCE` otherwise.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::{MOpcode, WidthSpec};
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+
+/// An operand of the folded comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CondOperand {
+    /// An existing value.
+    Value(NodeIndex),
+    /// A constant which has to be created, with its width.
+    Const(u64, u16),
+}
+
+/// A `Change` which replaces the selector of `block` with `lhs <opcode> rhs`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoldCondition {
+    /// The conditional block.
+    pub block: NodeIndex,
+    /// The current selector of `block`.
+    pub selector: NodeIndex,
+    /// The comparison, one of `OpEq`, `OpLt` and `OpGt`.
+    pub opcode: MOpcode,
+    pub lhs: CondOperand,
+    pub rhs: CondOperand,
+    /// Whether the comparison has to be negated.
+    pub negated: bool,
+}
+
+impl Change for FoldCondition {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+const NAME: &str = "flag_fold";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::FlagFold,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+#[derive(Debug)]
+pub struct FlagFold {}
+
+/// Width of the value `node`, if it is known.
+pub fn width_of(ssa: &SSAStorage, node: NodeIndex) -> Option<u16> {
+    ssa.node_data(node)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width())
+}
+
+/// The lowest `width` bits set.
+pub fn mask(width: u16) -> u64 {
+    if width >= 64 {
+        u64::max_value()
+    } else {
+        (1 << width) - 1
+    }
+}
+
+/// Operands of `node`, if it is a binary `opcode`.
+pub fn binop(ssa: &SSAStorage, node: NodeIndex, opcode: MOpcode) -> Option<(NodeIndex, NodeIndex)> {
+    let ops = ssa.operands_of(node);
+    if ssa.opcode(node) == Some(opcode) && ops.len() == 2 {
+        Some((ops[0], ops[1]))
+    } else {
+        None
+    }
+}
+
+/// Splits the operands of a commutative binary `node` into (value, constant).
+pub fn split_const(ssa: &SSAStorage, node: NodeIndex) -> Option<(NodeIndex, u64)> {
+    let ops = ssa.operands_of(node);
+    if ops.len() != 2 {
+        return None;
+    }
+    match (ssa.constant(ops[0]), ssa.constant(ops[1])) {
+        (None, Some(c)) => Some((ops[0], c)),
+        (Some(c), None) => Some((ops[1], c)),
+        _ => None,
+    }
+}
+
+/// Strips an `OpAnd` with a mask covering the whole width of the masked value.
+pub fn peel_mask(ssa: &SSAStorage, node: NodeIndex) -> NodeIndex {
+    if ssa.opcode(node) != Some(MOpcode::OpAnd) {
+        return node;
+    }
+    match split_const(ssa, node) {
+        Some((x, c)) if width_of(ssa, x).map(mask) == Some(c) => x,
+        _ => node,
+    }
+}
+
+/// Matches `a - b`, possibly masked.
+fn as_sub(ssa: &SSAStorage, node: NodeIndex) -> Option<(NodeIndex, NodeIndex)> {
+    let node = peel_mask(ssa, node);
+    if ssa.opcode(node) != Some(MOpcode::OpSub) {
+        return None;
+    }
+    let ops = ssa.operands_of(node);
+    if ops.len() == 2 {
+        Some((ops[0], ops[1]))
+    } else {
+        None
+    }
+}
+
+impl FlagFold {
+    pub fn new() -> Self {
+        FlagFold {}
+    }
+
+    /// Matches the flag idioms described in the module documentation, looking through
+    /// `OpNot`, `OpMov` and `OpNarrow(1)`. Returns (opcode, lhs, rhs, negated).
+    fn match_flag(
+        ssa: &SSAStorage,
+        node: NodeIndex,
+        negated: bool,
+    ) -> Option<(MOpcode, CondOperand, CondOperand, bool)> {
+        let ops = ssa.operands_of(node);
+        match ssa.opcode(node)? {
+            MOpcode::OpNot if ops.len() == 1 => FlagFold::match_flag(ssa, ops[0], !negated),
+            MOpcode::OpMov | MOpcode::OpNarrow(1) if ops.len() == 1 => {
+                FlagFold::match_flag(ssa, ops[0], negated)
+            }
+            // zf
+            MOpcode::OpXor => match split_const(ssa, node) {
+                Some((x, 1)) => {
+                    let (a, b) = as_sub(ssa, x)?;
+                    // Below the width of the subtraction, `a - b` may be 0 while `a != b`.
+                    let sub_width = width_of(ssa, peel_mask(ssa, x))?;
+                    if width_of(ssa, node) != Some(sub_width) {
+                        return None;
+                    }
+                    Some((
+                        MOpcode::OpEq,
+                        CondOperand::Value(a),
+                        CondOperand::Value(b),
+                        negated,
+                    ))
+                }
+                _ => None,
+            },
+            MOpcode::OpEq => match split_const(ssa, node) {
+                Some((x, 0)) => {
+                    let (a, b) = as_sub(ssa, x)?;
+                    Some((
+                        MOpcode::OpEq,
+                        CondOperand::Value(a),
+                        CondOperand::Value(b),
+                        negated,
+                    ))
+                }
+                _ => None,
+            },
+            // cf
+            MOpcode::OpLt if ops.len() == 2 => {
+                let a = peel_mask(ssa, ops[0]);
+                let (a_, b) = as_sub(ssa, ops[1])?;
+                if a != a_ {
+                    return None;
+                }
+                Some((
+                    MOpcode::OpLt,
+                    CondOperand::Value(a),
+                    CondOperand::Value(b),
+                    negated,
+                ))
+            }
+            // sf
+            MOpcode::OpLsr if ops.len() == 2 => {
+                let w = width_of(ssa, ops[0])?;
+                if w == 0 || ssa.constant(ops[1]) != Some(u64::from(w) - 1) {
+                    return None;
+                }
+                let (a, b) = as_sub(ssa, ops[0])?;
+                if ssa.constant(b) != Some(0) {
+                    return None;
+                }
+                Some((
+                    MOpcode::OpGt,
+                    CondOperand::Value(a),
+                    CondOperand::Const(mask(w) >> 1, w),
+                    negated,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn gather_folds(ssa: &SSAStorage) -> Vec<FoldCondition> {
+        ssa.blocks()
+            .into_iter()
+            .filter_map(|block| {
+                let selector = ssa.selector_in(block)?;
+                let (opcode, lhs, rhs, negated) = FlagFold::match_flag(ssa, selector, false)?;
+                Some(FoldCondition {
+                    block: block,
+                    selector: selector,
+                    opcode: opcode,
+                    lhs: lhs,
+                    rhs: rhs,
+                    negated: negated,
+                })
+            })
+            .collect()
+    }
+
+    fn operand(ssa: &mut SSAStorage, op: CondOperand) -> Option<NodeIndex> {
+        match op {
+            CondOperand::Value(n) => Some(n),
+            CondOperand::Const(c, w) => ssa.insert_const(c, Some(w)),
+        }
+    }
+
+    fn apply(ssa: &mut SSAStorage, fold: FoldCondition) -> Option<NodeIndex> {
+        let addr = ssa.address(fold.selector)?;
+        let vt = ValueInfo::new_scalar(WidthSpec::new_known(1));
+        let lhs = FlagFold::operand(ssa, fold.lhs)?;
+        let rhs = FlagFold::operand(ssa, fold.rhs)?;
+        let cmp = ssa.insert_op(fold.opcode, vt, None)?;
+        ssa.op_use(cmp, 0, lhs);
+        ssa.op_use(cmp, 1, rhs);
+        ssa.insert_into_block(cmp, fold.block, addr);
+        let new_selector = if fold.negated {
+            let not = ssa.insert_op(MOpcode::OpNot, vt, None)?;
+            ssa.op_use(not, 0, cmp);
+            ssa.insert_into_block(not, fold.block, addr);
+            not
+        } else {
+            cmp
+        };
+        // Only the selector edge is moved, other users of the flag keep it alive.
+        ssa.remove_edges_between(fold.block, fold.selector);
+        ssa.set_selector(new_selector, fold.block);
+        Some(new_selector)
+    }
+}
+
+impl Analyzer for FlagFold {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for FlagFold {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        func: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        let ssa = func.ssa_mut();
+
+        for fold in FlagFold::gather_folds(ssa) {
+            match policy(Box::new(fold.clone())) {
+                Action::Apply => {
+                    radeco_trace!("flag_fold|{:?}", fold);
+                    if FlagFold::apply(ssa, fold).is_none() {
+                        radeco_err!("Failed to fold the selector");
+                    }
+                }
+                Action::Skip => (),
+                Action::Abort => break,
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::utils::test_support::{entry_reg, load};
+
+    // cmp rdi, rsi; je 0x1006
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CMP_JE_SSA_TXT: &str = "\
+define-fun sym.cmp_je(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %4: $Unknown64 = %1 - %2;
+        [@0x001000.0002] %5: $Unknown64 = %4 ^ #x1;
+        [@0x001000.0003] %6: $Unknown1 = Narrow1(%5);
+        JMP IF %6 0x001006.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x2):
+        RETURN
+    bb_0x001006.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $zf = %6;
+        $mem = %3;
+}
+";
+
+    // cmp rdi, rsi; jb 0x1006
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CMP_JB_SSA_TXT: &str = "\
+define-fun sym.cmp_jb(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %4: $Unknown64 = %1 - %2;
+        [@0x001000.0002] %5: $Unknown1 = %1 < %4;
+        JMP IF %5 0x001006.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x2):
+        RETURN
+    bb_0x001006.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %3;
+}
+";
+
+    // cmp rdi, rsi with zf computed from the low byte of the difference only.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const NARROW_ZF_SSA_TXT: &str = "\
+define-fun sym.narrow_zf(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %4: $Unknown64 = %1 - %2;
+        [@0x001000.0002] %5: $Unknown8 = %4 ^ #x1;
+        [@0x001000.0003] %6: $Unknown1 = Narrow1(%5);
+        JMP IF %6 0x001006.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x2):
+        RETURN
+    bb_0x001006.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %3;
+}
+";
+
+    fn fold(il: &str) -> (RadecoFunction, NodeIndex) {
+        let mut rfn = load(il);
+        let mut flag_fold = FlagFold::new();
+        flag_fold.analyze(&mut rfn, Some(all));
+        let selector = {
+            let ssa = rfn.ssa();
+            ssa.blocks()
+                .into_iter()
+                .filter_map(|b| ssa.selector_in(b))
+                .next()
+                .unwrap()
+        };
+        (rfn, selector)
+    }
+
+    #[test]
+    fn cmp_je_is_eq() {
+        let (rfn, selector) = fold(CMP_JE_SSA_TXT);
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(selector), Some(MOpcode::OpEq));
+        assert_eq!(
+            ssa.operands_of(selector),
+            vec![entry_reg(ssa, "rdi"), entry_reg(ssa, "rsi")]
+        );
+        // zf is still part of the final register state.
+        let exit_regs = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        assert!(ssa
+            .operands_of(exit_regs)
+            .into_iter()
+            .any(|n| ssa.opcode(n) == Some(MOpcode::OpNarrow(1))));
+    }
+
+    #[test]
+    fn cmp_jb_is_lt() {
+        let (rfn, selector) = fold(CMP_JB_SSA_TXT);
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(selector), Some(MOpcode::OpLt));
+        assert_eq!(
+            ssa.operands_of(selector),
+            vec![entry_reg(ssa, "rdi"), entry_reg(ssa, "rsi")]
+        );
+    }
+
+    #[test]
+    fn folded_selector_is_stable() {
+        let mut rfn = load(CMP_JE_SSA_TXT);
+        let mut flag_fold = FlagFold::new();
+        flag_fold.analyze(&mut rfn, Some(all));
+        assert!(FlagFold::gather_folds(rfn.ssa()).is_empty());
+    }
+
+    #[test]
+    fn narrowed_zf_is_not_folded() {
+        let (rfn, selector) = fold(NARROW_ZF_SSA_TXT);
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(selector), Some(MOpcode::OpNarrow(1)));
+    }
+}
@@ -0,0 +1,443 @@
+//! Recognizes the stack canary checks inserted by the compiler, and optionally collapses them.
+//!
+//! A stack protected function saves the canary, read from the thread-local storage, in its frame
+//! on entry. Before returning, it reads the canary again, compares it against the saved copy, and
+//! calls `__stack_chk_fail` if they differ:
+//!
+//! ```text
+//! mov rax, qword fs:[0x28]
+//! mov qword [rbp - 8], rax
+//! ...
+//! mov rdx, qword [rbp - 8]
+//! xor rdx, qword fs:[0x28]
+//! je ok
+//! call sym.imp.__stack_chk_fail
+//! ```
+//!
+//! A check is only recognized when all of these are found: a store of the canary, a block whose
+//! selector is computed from the canary, and a successor of this block which does nothing but
+//! call the fail function. Collapsing a check removes the call to the fail function along with
+//! its branch and the store of the canary, and leaves a comment on the block making the check.
+//! The comparison is left dead for `DCE` to remove.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::frontend::radeco_containers::RadecoModule;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::ssa_traits::{SSAExtra, SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+use std::collections::HashSet;
+
+const NAME: &str = "stackcanary";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::StackCanary,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Name of the function called when the canary was overwritten.
+pub const FAIL_FN: &str = "__stack_chk_fail";
+
+/// Comment left on the blocks whose canary check was collapsed.
+pub const CHECK_COMMENT: &str = "stack canary check";
+
+/// Segment registers and offsets the canary is read from: `fs:0x28` on x86-64 and `gs:0x14` on
+/// x86.
+const CANARY_SLOTS: &[(&str, u64)] = &[
+    ("fs", 0x28),
+    ("fs_base", 0x28),
+    ("gs", 0x14),
+    ("gs_base", 0x14),
+];
+
+/// A recognized canary check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Check {
+    /// Block comparing the canary against the saved copy
+    block: NodeIndex,
+    /// Block calling the fail function
+    fail_block: NodeIndex,
+}
+
+#[derive(Debug)]
+pub struct StackCanary {
+    collapse: bool,
+    /// (function offset, block making the check) for every check found
+    found: Vec<(u64, NodeIndex)>,
+}
+
+impl StackCanary {
+    /// Only records the checks, leaving the IR untouched.
+    pub fn new() -> Self {
+        StackCanary::with_collapse(false)
+    }
+
+    pub fn with_collapse(collapse: bool) -> Self {
+        StackCanary {
+            collapse,
+            found: Vec::new(),
+        }
+    }
+
+    /// Checks found, as (function offset, block making the check). When collapsing, the blocks
+    /// are the ones annotated with `CHECK_COMMENT`.
+    pub fn found(&self) -> &[(u64, NodeIndex)] {
+        &self.found
+    }
+}
+
+impl Analyzer for StackCanary {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for StackCanary {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let fail_fns: HashSet<u64> = rmod
+            .functions
+            .iter()
+            .map(|(&offset, rfn)| (offset, &*rfn.name))
+            .chain(rmod.imports.iter().map(|(&plt, imp)| (plt, &*imp.name)))
+            .filter(|&(_, name)| name.contains(FAIL_FN))
+            .map(|(offset, _)| offset)
+            .collect();
+        if fail_fns.is_empty() {
+            return None;
+        }
+
+        for (&offset, rfn) in rmod.functions.iter_mut() {
+            let (store, checks) = match find_checks(rfn.ssa(), &fail_fns) {
+                Some(found) => found,
+                None => continue,
+            };
+            for check in &checks {
+                radeco_trace!("stackcanary|{}: {:?}", rfn.name, check);
+                if self.collapse {
+                    collapse(rfn.ssa_mut(), check);
+                }
+                self.found.push((offset, check.block));
+            }
+            // Every epilogue checks the same saved canary, so memory flows around its store once
+            // all of them are collapsed.
+            if self.collapse && !checks.is_empty() {
+                let ssa = rfn.ssa_mut();
+                let mem = ssa.operands_of(store)[0];
+                ssa.replace_value(store, mem);
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns the store saving the canary along with the checks made against it, or `None` if the
+/// canary isn't saved.
+fn find_checks(ssa: &SSAStorage, fail_fns: &HashSet<u64>) -> Option<(NodeIndex, Vec<Check>)> {
+    let loads = ssa
+        .values()
+        .into_iter()
+        .filter(|&n| is_canary_load(ssa, n))
+        .collect::<HashSet<_>>();
+    // Without a saved canary, there is nothing to compare against.
+    let store = ssa.values().into_iter().find(|&n| {
+        ssa.opcode(n) == Some(MOpcode::OpStore)
+            && ssa
+                .operands_of(n)
+                .get(2)
+                .map_or(false, |v| loads.contains(v))
+    });
+    let store = store?;
+
+    let exit = ssa.exit_node();
+    let mut checks = Vec::new();
+    for fail_block in ssa.blocks() {
+        if !calls_fail_only(ssa, fail_block, fail_fns)
+            || ssa
+                .succs_of(fail_block)
+                .into_iter()
+                .any(|s| Some(s) != exit)
+        {
+            continue;
+        }
+        let block = match ssa.preds_of(fail_block).as_slice() {
+            &[block] => block,
+            _ => continue,
+        };
+        let compared = ssa
+            .selector_in(block)
+            .map_or(false, |sel| compares_canary(ssa, sel, &loads));
+        if ssa.succs_of(block).len() == 2 && compared {
+            checks.push(Check { block, fail_block });
+        }
+    }
+    Some((store, checks))
+}
+
+/// Returns `true` if `node` loads the canary from the thread-local storage.
+fn is_canary_load(ssa: &SSAStorage, node: NodeIndex) -> bool {
+    if ssa.opcode(node) != Some(MOpcode::OpLoad) {
+        return false;
+    }
+    let addr = match ssa.operands_of(node).get(1) {
+        Some(&addr) if ssa.opcode(addr) == Some(MOpcode::OpAdd) => addr,
+        _ => return false,
+    };
+    let operands = ssa.operands_of(addr);
+    let (segment, offset) = match operands.iter().position(|&n| ssa.constant(n).is_some()) {
+        Some(i) if operands.len() == 2 => (operands[1 - i], ssa.constant(operands[i])),
+        _ => return false,
+    };
+    let names = ssa.registers(segment);
+    CANARY_SLOTS
+        .iter()
+        .any(|&(reg, off)| offset == Some(off) && names.iter().any(|n| n == reg))
+}
+
+/// Returns `true` if the only side effect of `block` is a call to a fail function, whose results
+/// are not used outside of the block.
+fn calls_fail_only(ssa: &SSAStorage, block: NodeIndex, fail_fns: &HashSet<u64>) -> bool {
+    let exprs = ssa.exprs_in(block);
+    let mut calls = 0;
+    for &expr in &exprs {
+        match ssa.opcode(expr) {
+            Some(MOpcode::OpCall) => {
+                let target = ssa.operands_of(expr).first().and_then(|&t| ssa.constant(t));
+                if !target.map_or(false, |t| fail_fns.contains(&t)) {
+                    return false;
+                }
+                calls += 1;
+                // The results of the call hang off of it, outside of the block.
+                let rets = utils::call_rets(expr, ssa);
+                if rets.iter().any(|(_, &(ret, _))| escapes(ssa, ret, &exprs)) {
+                    return false;
+                }
+                continue;
+            }
+            Some(ref op) if op.has_sideeffects() => return false,
+            _ => {}
+        }
+        if escapes(ssa, expr, &exprs) {
+            return false;
+        }
+    }
+    calls == 1
+}
+
+/// Returns `true` if `value` is used outside of `exprs`.
+fn escapes(ssa: &SSAStorage, value: NodeIndex, exprs: &[NodeIndex]) -> bool {
+    ssa.uses_of(value).into_iter().any(|u| !exprs.contains(&u))
+}
+
+/// Returns `true` if the selector `sel` is a comparison of one of the canary `loads`.
+fn compares_canary(ssa: &SSAStorage, sel: NodeIndex, loads: &HashSet<NodeIndex>) -> bool {
+    let mut compared = false;
+    let mut reaches_load = false;
+    let mut seen = HashSet::new();
+    let mut stack = vec![sel];
+    while let Some(node) = stack.pop() {
+        if !seen.insert(node) {
+            continue;
+        }
+        if loads.contains(&node) {
+            reaches_load = true;
+            continue;
+        }
+        match ssa.opcode(node) {
+            Some(MOpcode::OpEq) | Some(MOpcode::OpXor) | Some(MOpcode::OpSub) => compared = true,
+            // The canary is not read through memory or calls.
+            Some(MOpcode::OpLoad) | Some(MOpcode::OpCall) | None => continue,
+            _ => {}
+        }
+        stack.extend(ssa.operands_of(node));
+    }
+    compared && reaches_load
+}
+
+fn collapse(ssa: &mut SSAStorage, check: &Check) {
+    let edge = ssa
+        .outgoing_edges(check.block)
+        .into_iter()
+        .map(|(edge, _)| edge)
+        .find(|&edge| ssa.edge_info(edge).map(|e| e.target) == Some(check.fail_block));
+    if let Some(edge) = edge {
+        ssa.remove_data_edge(edge);
+    }
+    ssa.remove_block(check.fail_block);
+    ssa.set_comment(&check.block, CHECK_COMMENT.to_owned());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::imports::ImportInfo;
+    use crate::utils::test_support::{load_at, register_file};
+    use std::borrow::Cow;
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CANARY_SSA_TXT: &str = "\
+define-fun sym.protected(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Unknown16 = $fs;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %4: $Unknown64 = %2 + #x28;
+        [@0x001000.0002] %5: $Unknown64 = Load(%3, %4);
+        [@0x001004.0001] %6: $Unknown64 = %1 - #x8;
+        [@0x001004.0002] %7: $Unknown0 = Store(%3, %6, %5);
+        [@0x001008.0001] %8: $Unknown64 = Load(%7, %6);
+        [@0x00100C.0001] %9: $Unknown64 = Load(%7, %4);
+        [@0x00100C.0002] %10: $Unknown64 = %8 ^ %9;
+        [@0x00100C.0003] %11: $Unknown1 = %10 == #x0;
+        JMP IF %11 0x001014.0000 ELSE 0x001010.0000
+    bb_0x001010.0000(sz 0x4):
+        [@0x001010.0001] (%12: $Unknown0 = $mem) = CALL #x1650($mem=%7);
+        UNREACHABLE
+    bb_0x001014.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rbp = %1;
+        $mem = %7;
+}
+";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const TWO_EPILOGUES_SSA_TXT: &str = "\
+define-fun sym.protected(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Unknown16 = $fs;
+        %3: $Unknown0 = $mem;
+        %4: $Unknown64 = $rdi;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %5: $Unknown64 = %2 + #x28;
+        [@0x001000.0002] %6: $Unknown64 = Load(%3, %5);
+        [@0x001004.0001] %7: $Unknown64 = %1 - #x8;
+        [@0x001004.0002] %8: $Unknown0 = Store(%3, %7, %6);
+        [@0x001008.0001] %9: $Unknown1 = %4 == #x0;
+        JMP IF %9 0x001020.0000 ELSE 0x001010.0000
+    bb_0x001010.0000(sz 0x10):
+        [@0x001010.0001] %10: $Unknown64 = Load(%8, %7);
+        [@0x001014.0001] %11: $Unknown64 = Load(%8, %5);
+        [@0x001014.0002] %12: $Unknown64 = %10 ^ %11;
+        [@0x001014.0003] %13: $Unknown1 = %12 == #x0;
+        JMP IF %13 0x001034.0000 ELSE 0x001030.0000
+    bb_0x001020.0000(sz 0x10):
+        [@0x001020.0001] %14: $Unknown64 = Load(%8, %7);
+        [@0x001024.0001] %15: $Unknown64 = Load(%8, %5);
+        [@0x001024.0002] %16: $Unknown64 = %14 ^ %15;
+        [@0x001024.0003] %17: $Unknown1 = %16 == #x0;
+        JMP IF %17 0x00103C.0000 ELSE 0x001038.0000
+    bb_0x001030.0000(sz 0x4):
+        [@0x001030.0001] (%18: $Unknown0 = $mem) = CALL #x1650($mem=%8);
+        UNREACHABLE
+    bb_0x001034.0000(sz 0x1):
+        RETURN
+    bb_0x001038.0000(sz 0x4):
+        [@0x001038.0001] (%19: $Unknown0 = $mem) = CALL #x1650($mem=%8);
+        UNREACHABLE
+    bb_0x00103C.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rbp = %1;
+        $mem = %8;
+}
+";
+
+    fn load(il: &str) -> RadecoModule {
+        let rfn = load_at(il, 0x1000, &register_file());
+
+        let mut rmod = RadecoModule::default();
+        rmod.functions.insert(0x1000, rfn);
+        let name = Cow::from("sym.imp.__stack_chk_fail");
+        rmod.imports
+            .insert(0x1650, ImportInfo::new_stub(0x1650, name));
+        rmod
+    }
+
+    fn has_call(ssa: &SSAStorage) -> bool {
+        ssa.values()
+            .into_iter()
+            .any(|n| ssa.opcode(n) == Some(MOpcode::OpCall))
+    }
+
+    #[test]
+    fn collapse_canary() {
+        let mut rmod = load(CANARY_SSA_TXT);
+        let mut canary = StackCanary::with_collapse(true);
+        canary.analyze(&mut rmod, None::<fn(_) -> _>);
+        assert_eq!(canary.found().len(), 1);
+
+        let ssa = rmod.functions[&0x1000].ssa();
+        let (_, block) = canary.found()[0];
+        assert!(!has_call(ssa));
+        assert_eq!(ssa.succs_of(block).len(), 1);
+        assert_eq!(ssa.comments(&block), Some(CHECK_COMMENT.to_owned()));
+        // The canary isn't stored anymore.
+        assert!(ssa
+            .values()
+            .into_iter()
+            .all(|n| ssa.opcode(n) != Some(MOpcode::OpStore)));
+    }
+
+    #[test]
+    fn collapse_two_epilogues() {
+        let mut rmod = load(TWO_EPILOGUES_SSA_TXT);
+        let mut canary = StackCanary::with_collapse(true);
+        canary.analyze(&mut rmod, None::<fn(_) -> _>);
+        assert_eq!(canary.found().len(), 2);
+
+        let ssa = rmod.functions[&0x1000].ssa();
+        assert!(!has_call(ssa));
+        for &(_, block) in canary.found() {
+            assert_eq!(ssa.succs_of(block).len(), 1);
+            assert_eq!(ssa.comments(&block), Some(CHECK_COMMENT.to_owned()));
+        }
+        assert!(ssa
+            .values()
+            .into_iter()
+            .all(|n| ssa.opcode(n) != Some(MOpcode::OpStore)));
+    }
+
+    #[test]
+    fn detect_only() {
+        let mut rmod = load(CANARY_SSA_TXT);
+        let mut canary = StackCanary::new();
+        canary.analyze(&mut rmod, None::<fn(_) -> _>);
+        assert_eq!(canary.found().len(), 1);
+        assert!(has_call(rmod.functions[&0x1000].ssa()));
+    }
+
+    #[test]
+    fn partial_match_is_kept() {
+        // The check is against `fs:0x30`, which isn't the canary.
+        let il = CANARY_SSA_TXT.replace("%2 + #x28", "%2 + #x30");
+        let mut rmod = load(&il);
+        let mut canary = StackCanary::with_collapse(true);
+        canary.analyze(&mut rmod, None::<fn(_) -> _>);
+        assert!(canary.found().is_empty());
+        assert!(has_call(rmod.functions[&0x1000].ssa()));
+    }
+}
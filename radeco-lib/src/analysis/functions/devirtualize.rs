@@ -0,0 +1,206 @@
+//! Promotes indirect calls with a known target to direct calls.
+//!
+//! Indirect calls are lifted as [`OpCall`] nodes whose "target" operand is the value the call
+//! goes through, rather than a comment naming the callee. Once constant propagation has resolved
+//! that value to the address of a function of the module, the corresponding edge is added to
+//! [the callgraph]. Calls whose target is still unknown are left untouched.
+//!
+//! [`OpCall`]: ir::MOpcode::OpCall
+//! [the callgraph]: RadecoModule::callgraph
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::frontend::llanalyzer;
+use crate::frontend::radeco_containers::*;
+use crate::middle::ir;
+use crate::middle::ssa::ssa_traits::*;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+const NAME: &str = "devirtualizer";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::Devirtualizer,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+#[derive(Debug)]
+pub struct Devirtualizer;
+
+impl Devirtualizer {
+    pub fn new() -> Self {
+        Devirtualizer
+    }
+}
+
+impl Analyzer for Devirtualizer {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for Devirtualizer {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        // Function offset -> node in the callgraph. Imports are keyed by their PLT entry.
+        let cg_nodes: HashMap<u64, NodeIndex> = rmod
+            .callgraph
+            .node_indices()
+            .map(|n| (rmod.callgraph[n], n))
+            .collect();
+
+        for rfn in rmod.functions.values_mut() {
+            let caller = match cg_nodes.get(&rfn.offset) {
+                Some(&caller) => caller,
+                None => continue,
+            };
+            // Callsites already in the callgraph, e.g. the direct calls, or calls promoted by a
+            // previous run.
+            let linked = rmod
+                .callgraph
+                .callees(caller)
+                .map(|(csite, _)| csite)
+                .collect::<HashSet<_>>();
+            let mut promoted = Vec::new();
+            {
+                let ssa = rfn.ssa();
+                let calls = ssa
+                    .inorder_walk()
+                    .filter(|&n| ssa.opcode(n) == Some(ir::MOpcode::OpCall))
+                    .collect::<Vec<_>>();
+                for node in calls {
+                    if ssa
+                        .address(node)
+                        .map_or(true, |a| linked.contains(&a.address))
+                    {
+                        continue;
+                    }
+                    let (_target, callee) = match call_target(ssa, node)
+                        .and_then(|t| cg_nodes.get(&t).map(|&callee| (t, callee)))
+                    {
+                        Some(resolved) => resolved,
+                        None => continue,
+                    };
+                    radeco_trace!("devirtualizing call {:?} to {:#X}", node, _target);
+                    promoted.push((node, callee));
+                }
+            }
+
+            if promoted.is_empty() {
+                continue;
+            }
+            let mut csites = llanalyzer::analyze_callsite_initial(rfn);
+            for (node, callee) in promoted {
+                let csite = rfn.ssa().address(node).map(|a| a.address);
+                if let Some(cctx) = csite.and_then(|a| csites.remove(&a)) {
+                    rmod.callgraph.add_edge(caller, callee, cctx);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns the constant address a call goes to, if it is known.
+fn call_target(ssa: &SSAStorage, call_node: <SSAStorage as SSA>::ValueRef) -> Option<u64> {
+    let target = ssa
+        .sparse_operands_of(call_node)
+        .iter()
+        .find(|x| x.0 == 0)?
+        .1;
+    ssa.constant_value(target)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::{all, FuncAnalyzer};
+    use crate::analysis::sccp::SCCP;
+    use crate::middle::ir::{MAddress, MOpcode};
+    use crate::utils::test_support::{entry_reg, load_at, register_file};
+
+    // mov rdx, 0x1ff0; add rdx, 0x10; call rdx; call rax
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLER_SSA_TXT: &str = "\
+define-fun sym.caller(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rax;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %3: $Unknown64 = #x1ff0 + #x10;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdx = %3;
+        $mem = %2;
+}
+";
+
+    fn insert_call(
+        ssa: &mut SSAStorage,
+        block: NodeIndex,
+        target: NodeIndex,
+        mem: NodeIndex,
+        at: u64,
+    ) -> NodeIndex {
+        let call = ssa.insert_op(MOpcode::OpCall, scalar!(64), None).unwrap();
+        ssa.op_use(call, 0, target);
+        ssa.op_use(call, 1, mem);
+        ssa.insert_into_block(call, block, MAddress::new(at, 0));
+        call
+    }
+
+    #[test]
+    fn constant_target_becomes_direct() {
+        let mut caller = load_at(CALLER_SSA_TXT, 0x1000, &register_file());
+        let (resolved, _) = {
+            let ssa = caller.ssa_mut();
+            let add = ssa
+                .inorder_walk()
+                .find(|&n| ssa.opcode(n) == Some(MOpcode::OpAdd))
+                .unwrap();
+            let block = ssa.block_for(add).unwrap();
+            let (rax, mem) = (entry_reg(ssa, "rax"), entry_reg(ssa, "mem"));
+            (
+                insert_call(ssa, block, add, mem, 0x1005),
+                insert_call(ssa, block, rax, mem, 0x100a),
+            )
+        };
+        SCCP::new().analyze(&mut caller, Some(all));
+
+        let mut callee = RadecoFunction::default();
+        callee.offset = 0x2000;
+
+        let mut rmod = RadecoModule::default();
+        let caller_id = rmod.callgraph.add_node(caller.offset);
+        let callee_id = rmod.callgraph.add_node(callee.offset);
+        rmod.functions.insert(caller.offset, caller);
+        rmod.functions.insert(callee.offset, callee);
+
+        Devirtualizer::new().analyze(&mut rmod, None::<fn(_) -> _>);
+
+        let callees = rmod.callgraph.callees(caller_id).collect::<Vec<_>>();
+        assert_eq!(callees, vec![(0x1005, callee_id)]);
+        let cctx = &rmod.callgraph[rmod.callgraph.find_edge(caller_id, callee_id).unwrap()];
+        assert_eq!(cctx.csite_node, resolved);
+
+        // The callsite is linked once.
+        Devirtualizer::new().analyze(&mut rmod, None::<fn(_) -> _>);
+        assert_eq!(rmod.callgraph.callees(caller_id).count(), 1);
+    }
+}
@@ -84,6 +84,26 @@ impl ModuleAnalyzer for Inferer {
             }
         }
 
+        // the callconvs supplied by the user are trusted as well
+        for (&fn_addr, rfn) in &mut rmod.functions {
+            if !rfn.has_custom_callconv() {
+                continue;
+            }
+            let regusage = rfn.callconv.as_ref().and_then(|cc| {
+                self.reginfo.custom_callconv_to_register_usage(
+                    cc,
+                    &*rfn.callconv_name,
+                    rfn.clobbers(),
+                )
+            });
+            if let Some(ru) = regusage {
+                rfn.regusage = ru;
+                new_analyzed.push(fn_addr);
+            } else {
+                radeco_warn!("Unknown register in the callconv of {}", rfn.name);
+            }
+        }
+
         for func in new_analyzed {
             self.analyzed.insert(func);
         }
@@ -230,3 +250,92 @@ fn direct_call_info(
     let callinfo = utils::call_info(call_node, ssa)?;
     Some((ssa.constant(callinfo.target)?, callinfo.register_args))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_support::{load_at, register_file};
+    use r2papi::structs::LCCInfo;
+
+    // Reads `rdi`, although its calling convention says otherwise.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLEE_SSA_TXT: &str = "\
+define-fun sym.stub(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $r8;
+        %3: $Unknown0 = $mem;
+    bb_0x002000.0000(sz 0x4):
+        [@0x002000.0001] %4: $Unknown64 = %1 + %2;
+        RETURN
+    exit-node:
+    final-register-state:
+        $r10 = %4;
+        $mem = %3;
+}
+";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLER_SSA_TXT: &str = "\
+define-fun sym.caller(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $r8;
+        %3: $Unknown64 = $r9;
+        %4: $Unknown64 = $rbx;
+        %5: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x5):
+        [@0x001000.0001] (%6: $Unknown64 = $r10, %7: $Unknown64 = $rbx, %8: $Unknown0 = $mem) = CALL #x2000($rdi=%1, $r8=%2, $r9=%3, $rbx=%4, $mem=%5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $rbx = %7;
+        $mem = %8;
+}
+";
+
+    #[test]
+    fn custom_callconv_at_callsite() {
+        let regfile = register_file();
+
+        let mut callconv = LCCInfo::default();
+        callconv.args = Some(vec!["r8".to_owned(), "r9".to_owned()]);
+        callconv.ret = Some("r10".to_owned());
+        let mut callee = load_at(CALLEE_SSA_TXT, 0x2000, &regfile);
+        callee.set_calling_convention(callconv);
+        callee.set_clobbers(vec!["r11".to_owned()]);
+
+        let mut rmod = RadecoModule::default();
+        rmod.callgraph.add_node(0x2000);
+        rmod.callgraph.add_node(0x1000);
+        rmod.functions.insert(0x2000, callee);
+        let caller = load_at(CALLER_SSA_TXT, 0x1000, &regfile);
+        rmod.functions.insert(0x1000, caller);
+
+        let mut inferer = Inferer::new((*regfile).clone());
+        inferer.analyze(&mut rmod, None::<fn(_) -> _>);
+
+        let ssa = rmod.functions[&0x1000].ssa();
+        let call = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(ir::MOpcode::OpCall))
+            .unwrap();
+        let info = utils::call_info(call, ssa).unwrap();
+        let mut args = info
+            .register_args
+            .iter()
+            .filter_map(|(regid, _)| regfile.get_name(regid))
+            .collect::<Vec<_>>();
+        args.sort();
+        assert_eq!(args, vec!["r8", "r9"]);
+
+        // `rbx` isn't clobbered, so it keeps its value across the call.
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        assert!(ssa
+            .operands_of(exit)
+            .into_iter()
+            .filter_map(|n| ssa.argument(n))
+            .any(|reg| reg == "rbx"));
+    }
+}
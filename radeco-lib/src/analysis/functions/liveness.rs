@@ -0,0 +1,171 @@
+//! Computes the registers that are live at the entry of a function.
+//!
+//! A register is live at the entry of a function if the value it holds on entry may be read before
+//! the register is written, which makes the live registers a sound approximation of the register
+//! arguments of the function.
+//!
+//! Liveness is propagated backwards over the SSA, through expressions and phis, from the values
+//! that are read: the operands of operations with side effects, the selectors of blocks, the
+//! values of dynamic actions' register states and the values returned in the exit register
+//! state. A register whose value on entry is only passed through to the exit register state, as
+//! for a callee-saved register, is not live.
+//!
+//! A call only reads the registers its callee reads, according to the register usage of the callee
+//! at the call site (see [`RadecoFunction::callee_usage`]). Calls without one are assumed to read
+//! every register. In particular, memory is always live across a call, unless the usage of the
+//! callee proves that it isn't read.
+
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::MOpcode;
+use crate::middle::regfile::RegisterId;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::NodeData;
+use crate::middle::ssa::utils;
+
+use petgraph::graph::NodeIndex;
+
+use std::collections::HashSet;
+
+/// Returns the registers, including memory, that are live at the entry of `rfn`. Returns `None`
+/// if the SSA of the function has no entry or exit register state.
+pub fn live_in(rfn: &RadecoFunction) -> Option<HashSet<RegisterId>> {
+    let ssa = rfn.ssa();
+    let entry_regs = ssa.registers_in(ssa.entry_node()?)?;
+    let exit_regs = ssa.registers_in(ssa.exit_node()?)?;
+    let entry_state = utils::register_state_info(entry_regs, ssa);
+
+    let mut worklist = Vec::new();
+    for node in ssa.g.node_indices() {
+        match ssa.g[node] {
+            NodeData::Op(MOpcode::OpCall, _) => worklist.extend(call_reads(rfn, node)),
+            NodeData::Op(ref op, _) if op.has_sideeffects() => worklist.push(node),
+            NodeData::RegisterState if node != entry_regs && node != exit_regs => {
+                worklist.extend(ssa.operands_of(node))
+            }
+            _ if ssa.is_selector(node) => worklist.push(node),
+            _ => {}
+        }
+    }
+
+    // The value of a register on exit is read by the caller, unless it is the value the same
+    // register had on entry.
+    for (regid, &(value, _)) in utils::register_state_info(exit_regs, ssa).iter() {
+        let on_entry = entry_state.get(regid).map(|&(node, _)| node);
+        let mut phis = HashSet::new();
+        let mut stack = vec![value];
+        while let Some(node) = stack.pop() {
+            if Some(node) == on_entry {
+                continue;
+            }
+            if !ssa.is_phi(node) {
+                worklist.push(node);
+            } else if phis.insert(node) {
+                stack.extend(ssa.operands_of(node));
+            }
+        }
+    }
+
+    let mut live = HashSet::new();
+    while let Some(node) = worklist.pop() {
+        if !live.insert(node) {
+            continue;
+        }
+        // The operands of a call are handled above, the result of a call doesn't make all of them
+        // live.
+        if ssa.is_phi(node) || (ssa.is_expr(node) && ssa.opcode(node) != Some(MOpcode::OpCall)) {
+            worklist.extend(ssa.operands_of(node));
+        }
+    }
+
+    Some(
+        entry_state
+            .iter()
+            .filter(|&(_, &(value, _))| live.contains(&value))
+            .map(|(regid, _)| regid)
+            .collect(),
+    )
+}
+
+/// Returns the operands of `call` it reads: its target, and the value of the registers its callee
+/// reads.
+fn call_reads(rfn: &RadecoFunction, call: NodeIndex) -> Vec<NodeIndex> {
+    let ssa = rfn.ssa();
+    let info = match utils::call_info(call, ssa) {
+        Some(info) => info,
+        None => return ssa.operands_of(call),
+    };
+    let usage = ssa
+        .address(call)
+        .and_then(|addr| rfn.callee_usage(addr.address));
+
+    let mut reads = vec![info.target];
+    reads.extend(
+        info.register_args
+            .iter()
+            .filter(|&(regid, _)| usage.map_or(true, |usage| usage.is_read(regid)))
+            .map(|(_, &node)| node),
+    );
+    reads
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::radeco_containers::{BindingType, VarBinding};
+    use crate::utils::test_support::{load_at, register_file};
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const LIVE_SSA_TXT: &str = "\
+define-fun sym.live(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown64 = $rbx;
+        %4: $Unknown64 = $rbp;
+        %5: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %6: $Unknown64 = %2 + #x1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $rbx = #x5;
+        $rbp = %4;
+        $rdi = %1;
+        $mem = %5;
+}
+";
+
+    #[test]
+    fn live_in_test() {
+        let regfile = register_file();
+        let mut rfn = load_at(LIVE_SSA_TXT, 0, &regfile);
+
+        // `rsi` is read, `rbx` is written first, and `rdi` and `rbp` are only passed through.
+        let reg = |name| regfile.register_id_by_name(name).unwrap();
+        let live = live_in(&rfn).unwrap();
+        assert!(live.contains(&reg("rsi")));
+        for name in &["rbx", "rdi", "rbp"] {
+            assert!(!live.contains(&reg(name)));
+        }
+        assert!(!live.contains(&regfile.mem_id()));
+
+        // Only the argument binding of `rsi` is kept.
+        let entry = rfn.ssa().registers_in(rfn.ssa().entry_node().unwrap());
+        let entry_state = utils::register_state_info(entry.unwrap(), rfn.ssa());
+        for (i, &name) in ["rdi", "rsi"].iter().enumerate() {
+            let (value, _) = entry_state[reg(name)];
+            rfn.bindings_mut().push(VarBinding::new(
+                BindingType::RegisterArgument(i),
+                String::new(),
+                Some(name.to_owned()),
+                value,
+                Some(reg(name).to_u8() as u64),
+            ));
+        }
+        rfn.mark_args();
+        let names = rfn.bindings().iter().map(|b| b.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["rsi"]);
+    }
+}
@@ -1,2 +1,7 @@
+pub mod canary;
+pub mod devirtualize;
 pub mod fix_ssa_opcalls;
 pub mod infer_regusage;
+pub mod liveness;
+pub mod purity;
+pub mod signatures;
@@ -0,0 +1,216 @@
+//! Summarizes the side effects of the calls in every function.
+//!
+//! A function is considered *pure* if it never writes to memory. Imports are looked up in a table
+//! of well-known libc functions, while the purity of local functions is inferred: a function is
+//! pure if it contains no stores and calls pure functions only.
+//!
+//! The register usage of the callee of every call site is then recorded in the caller (see
+//! [`RadecoFunction::callee_usage`]), with memory marked as preserved when the callee is pure.
+//! Later passes (e.g. `SCCP`) use it to let values survive across calls. Calls without a recorded
+//! usage are still assumed to read and clobber everything.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::frontend::radeco_containers::*;
+use crate::middle::ir;
+use crate::middle::regfile::RegisterUsage;
+use crate::middle::ssa::ssa_traits::*;
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+const NAME: &str = "purity";
+const REQUIRES: &[AnalyzerKind] = &[AnalyzerKind::Inferer];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::Purity,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// libc functions which never write to memory.
+pub const PURE_LIBC: &[&str] = &[
+    "abs", "atoi", "atol", "atoll", "getenv", "isalnum", "isalpha", "isdigit", "islower",
+    "isprint", "isspace", "isupper", "isxdigit", "labs", "memchr", "memcmp", "strchr", "strcmp",
+    "strcspn", "strlen", "strncmp", "strnlen", "strpbrk", "strrchr", "strspn", "strstr", "tolower",
+    "toupper",
+];
+
+/// Returns `true` if `name`, possibly decorated by r2 (e.g. `sym.imp.strlen`), is a known pure
+/// libc function.
+pub fn is_known_pure(name: &str) -> bool {
+    let name = name.rsplit('.').next().unwrap_or(name);
+    PURE_LIBC.contains(&name)
+}
+
+#[derive(Debug)]
+pub struct Purity {
+    /// Addresses of the functions found to be pure
+    pure: HashSet<u64>,
+}
+
+impl Purity {
+    pub fn new() -> Self {
+        Purity {
+            pure: HashSet::new(),
+        }
+    }
+
+    /// Returns `true` if the function at `offset` was found to be pure.
+    pub fn is_pure(&self, offset: u64) -> bool {
+        self.pure.contains(&offset)
+    }
+
+    fn infer(&mut self, rmod: &RadecoModule) {
+        let callees: HashMap<u64, Vec<u64>> = rmod
+            .callgraph
+            .node_indices()
+            .map(|n| {
+                let targets = rmod.callgraph.callees(n).map(|(_, t)| rmod.callgraph[t]);
+                (rmod.callgraph[n], targets.collect())
+            })
+            .collect();
+
+        self.pure = rmod
+            .imports
+            .values()
+            .filter(|imp| is_known_pure(&imp.name))
+            .map(|imp| imp.plt)
+            .collect();
+        let mut candidates = HashSet::new();
+        for rfn in rmod.functions.values() {
+            match rfn.kind {
+                FunctionKind::Local if only_direct_effects(rfn, &callees) => {
+                    candidates.insert(rfn.offset);
+                }
+                FunctionKind::Local => (),
+                _ => {
+                    if is_known_pure(&rfn.name) {
+                        self.pure.insert(rfn.offset);
+                    }
+                }
+            }
+        }
+
+        // Optimistically assume every candidate is pure, then drop the ones calling impure
+        // functions until nothing changes.
+        self.pure.extend(&candidates);
+        loop {
+            let impure = candidates
+                .iter()
+                .cloned()
+                .filter(|f| {
+                    let targets = callees.get(f).map(|t| t.as_slice()).unwrap_or(&[]);
+                    targets.iter().any(|t| !self.pure.contains(t))
+                })
+                .collect::<Vec<_>>();
+            if impure.is_empty() {
+                break;
+            }
+            for f in impure {
+                candidates.remove(&f);
+                self.pure.remove(&f);
+            }
+        }
+    }
+
+    fn usage_of(&self, rmod: &RadecoModule, offset: u64) -> Option<RegisterUsage> {
+        let rfn = rmod.functions.get(&offset);
+        let mut usage = match rfn.map(|rfn| &rfn.regusage) {
+            Some(ru) if ru.is_mutable() => ru.clone(),
+            _ if self.is_pure(offset) => rfn?.ssa().regfile.new_register_usage(),
+            _ => return None,
+        };
+        if self.is_pure(offset) {
+            usage.set_preserved(rfn?.ssa().regfile.mem_id());
+        }
+        Some(usage)
+    }
+}
+
+impl Analyzer for Purity {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for Purity {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        self.infer(rmod);
+
+        let mut usages = Vec::new();
+        for n in rmod.callgraph.node_indices() {
+            for (csite, callee) in rmod.callgraph.callees(n) {
+                if let Some(usage) = self.usage_of(rmod, rmod.callgraph[callee]) {
+                    usages.push((rmod.callgraph[n], csite, usage));
+                }
+            }
+        }
+        for (caller, csite, usage) in usages {
+            if let Some(rfn) = rmod.functions.get_mut(&caller) {
+                rfn.set_callee_usage(csite, usage);
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns `true` if `rfn` doesn't store to memory and every call it makes is in the callgraph.
+fn only_direct_effects(rfn: &RadecoFunction, callees: &HashMap<u64, Vec<u64>>) -> bool {
+    if !callees.contains_key(&rfn.offset) {
+        return false;
+    }
+    let ssa = rfn.ssa();
+    let mut n_calls = 0;
+    for n in ssa.inorder_walk() {
+        match ssa.opcode(n) {
+            Some(ir::MOpcode::OpStore) => return false,
+            Some(ir::MOpcode::OpCall) | Some(ir::MOpcode::OpCustom(_)) => n_calls += 1,
+            _ => (),
+        }
+    }
+    n_calls == callees[&rfn.offset].len()
+}
+
+/// Replaces the values clobbered by `call_node` with their value before the call, for all the
+/// registers (and memory) `usage` says are preserved by the callee.
+pub fn bridge_preserved(
+    ssa: &mut SSAStorage,
+    call_node: <SSAStorage as SSA>::ValueRef,
+    usage: &RegisterUsage,
+) {
+    let args = match utils::call_info(call_node, ssa) {
+        Some(callinfo) => callinfo.register_args,
+        None => return,
+    };
+    for (regid, (ret, _)) in utils::call_rets(call_node, ssa) {
+        if let (true, Some(&before)) = (usage.is_preserved(regid), args.get(regid)) {
+            ssa.replace_value(ret, before);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn known_pure_names() {
+        assert!(is_known_pure("strlen"));
+        assert!(is_known_pure("sym.imp.strlen"));
+        assert!(!is_known_pure("sym.imp.memcpy"));
+        assert!(!is_known_pure("sym.strlen_wrapper"));
+    }
+}
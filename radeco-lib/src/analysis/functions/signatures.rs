@@ -0,0 +1,321 @@
+//! Types of the values returned by well-known library functions.
+//!
+//! The `SignatureDb` maps the name of a function (e.g. `malloc`) to the type of the value it
+//! returns: whether it is a pointer and how wide it is. A builtin table covers the most common libc
+//! functions, and more signatures can be loaded from a JSON object keyed by function name:
+//!
+//! ```json
+//! { "malloc": { "pointer": true }, "atoi": { "width": 32 } }
+//! ```
+//!
+//! `CallTypes` annotates the `ValueInfo` of the value returned by every call accordingly, for the
+//! reference marking and the C backend. Pointers without an explicit width, and the values
+//! returned by unknown functions, are as wide as a machine word.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::frontend::radeco_containers::{FunctionKind, RadecoModule};
+use crate::middle::ir::{MOpcode, WidthSpec};
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{ValueInfo, ValueType, SSA};
+use crate::middle::ssa::utils;
+
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use std::any::Any;
+use std::collections::HashMap;
+
+const NAME: &str = "calltypes";
+const REQUIRES: &[AnalyzerKind] = &[AnalyzerKind::CallSiteFixer];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::CallTypes,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// libc functions returning a pointer.
+const POINTER_LIBC: &[&str] = &[
+    "calloc", "fdopen", "fgets", "fopen", "getenv", "malloc", "memchr", "memcpy", "memmove",
+    "memset", "mmap", "realloc", "strcat", "strchr", "strcpy", "strdup", "strncat", "strncpy",
+    "strndup", "strrchr", "strstr",
+];
+
+/// libc functions returning a `size_t`.
+const SIZE_LIBC: &[&str] = &["fread", "fwrite", "strcspn", "strlen", "strnlen", "strspn"];
+
+/// libc functions returning an `int`.
+const INT_LIBC: &[&str] = &[
+    "atoi", "close", "fclose", "fprintf", "memcmp", "open", "printf", "puts", "snprintf",
+    "sprintf", "strcmp", "strncmp", "tolower", "toupper",
+];
+
+/// Type of the value returned by a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Signature {
+    /// It is `true` if the returned value is a pointer.
+    #[serde(default)]
+    pub pointer: bool,
+    /// Width of the returned value in bits, `None` for a machine word.
+    #[serde(default)]
+    pub width: Option<u16>,
+}
+
+impl Signature {
+    /// `ValueInfo` of the returned value, on an architecture with words of `word_size` bits.
+    pub fn value_info(&self, word_size: Option<u16>) -> ValueInfo {
+        let vty = if self.pointer {
+            ValueType::Reference
+        } else {
+            ValueType::Scalar
+        };
+        match self.width.or(word_size) {
+            Some(width) => ValueInfo::new(vty, WidthSpec::Known(width)),
+            None => ValueInfo::new(vty, WidthSpec::Unknown),
+        }
+    }
+}
+
+/// Signatures of the known functions, by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureDb {
+    signatures: HashMap<String, Signature>,
+}
+
+impl Default for SignatureDb {
+    /// The builtin signatures of the common libc functions.
+    fn default() -> SignatureDb {
+        let entries = POINTER_LIBC
+            .iter()
+            .map(|name| (name, true, None))
+            .chain(SIZE_LIBC.iter().map(|name| (name, false, None)))
+            .chain(INT_LIBC.iter().map(|name| (name, false, Some(32))));
+        let signatures = entries
+            .map(|(name, pointer, width)| {
+                let sig = Signature {
+                    pointer: pointer,
+                    width: width,
+                };
+                (name.to_string(), sig)
+            })
+            .collect();
+        SignatureDb {
+            signatures: signatures,
+        }
+    }
+}
+
+impl SignatureDb {
+    /// A database without any signature.
+    pub fn empty() -> SignatureDb {
+        SignatureDb {
+            signatures: HashMap::new(),
+        }
+    }
+
+    /// Adds the signatures of the JSON object `json`, replacing the known ones with the same name.
+    pub fn load(&mut self, json: &str) -> Result<(), serde_json::Error> {
+        let signatures: HashMap<String, Signature> = serde_json::from_str(json)?;
+        self.signatures.extend(signatures);
+        Ok(())
+    }
+
+    pub fn insert(&mut self, name: String, sig: Signature) {
+        self.signatures.insert(name, sig);
+    }
+
+    /// Signature of the function `name`, possibly decorated by r2 (e.g. `sym.imp.malloc`).
+    pub fn get(&self, name: &str) -> Option<&Signature> {
+        self.signatures.get(name).or_else(|| {
+            let name = name.rsplit('.').next().unwrap_or(name);
+            self.signatures.get(name)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+}
+
+#[derive(Debug)]
+pub struct CallTypes {
+    db: SignatureDb,
+}
+
+impl CallTypes {
+    pub fn new(db: SignatureDb) -> Self {
+        CallTypes { db: db }
+    }
+
+    /// Names of the functions which may be called, with the register holding their return value.
+    fn callees(rmod: &RadecoModule) -> HashMap<u64, (String, Option<String>)> {
+        let ret_of = |offset: u64| {
+            rmod.functions
+                .get(&offset)
+                .and_then(|rfn| rfn.callconv.as_ref())
+                .and_then(|cc| cc.ret.clone())
+        };
+        let imports = rmod
+            .imports
+            .values()
+            .map(|imp| (imp.plt, imp.name.to_string()));
+        let functions = rmod
+            .functions
+            .values()
+            .filter(|rfn| rfn.kind != FunctionKind::Local)
+            .map(|rfn| (rfn.offset, rfn.name.to_string()));
+        imports
+            .chain(functions)
+            .map(|(offset, name)| (offset, (name, ret_of(offset))))
+            .collect()
+    }
+}
+
+impl Analyzer for CallTypes {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for CallTypes {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let callees = Self::callees(rmod);
+        for rfn in rmod.functions.values_mut() {
+            if rfn.kind != FunctionKind::Local {
+                continue;
+            }
+            // The callees without a known return register are assumed to share the one of the
+            // caller.
+            let own_ret = rfn.callconv.as_ref().and_then(|cc| cc.ret.clone());
+            let mut annotations = Vec::new();
+            {
+                let ssa = rfn.ssa();
+                let word_size = ssa.regfile.word_size();
+                for node in ssa.blocks().into_iter().flat_map(|b| ssa.exprs_in(b)) {
+                    if ssa.opcode(node) != Some(MOpcode::OpCall) {
+                        continue;
+                    }
+                    let target = utils::call_info(node, ssa).and_then(|ci| ssa.constant(ci.target));
+                    let callee = target.and_then(|t| callees.get(&t));
+                    let ret = callee
+                        .and_then(|&(_, ref ret)| ret.clone())
+                        .or_else(|| own_ret.clone())
+                        .and_then(|reg| ssa.regfile.register_id_by_name(&reg));
+                    let ret = match ret.and_then(|r| utils::call_rets(node, ssa).get(r).cloned()) {
+                        Some((ret, _)) => ret,
+                        None => continue,
+                    };
+                    let sig = callee.and_then(|&(ref name, _)| self.db.get(name));
+                    let vi = match sig {
+                        Some(sig) => sig.value_info(word_size),
+                        // Only the width of the values returned by unknown functions is known.
+                        None => {
+                            let vty = ssa.node_data(ret).map(|nd| *nd.vt.value_type());
+                            let mut vi = Signature::default().value_info(word_size);
+                            vi.set_value_type(vty.unwrap_or(ValueType::Unresolved));
+                            vi
+                        }
+                    };
+                    annotations.push((ret, vi));
+                }
+            }
+            let ssa = rfn.ssa_mut();
+            for (ret, vi) in annotations {
+                if let Some(nd) = ssa.g.node_weight_mut(ret) {
+                    nd.set_valueinfo(vi);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::imports::ImportInfo;
+    use crate::frontend::radeco_containers::RadecoFunction;
+    use crate::utils::test_support::{load_at, register_file};
+    use petgraph::graph::NodeIndex;
+    use std::borrow::Cow;
+
+    // `malloc` is called at 0x1004 and an unknown function at 0x100c.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLS_SSA_TXT: &str = "\
+define-fun sym.calls(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001004.0000] (%3: $Unknown64 = $rax, %4: $Unknown0 = $mem) = CALL #x400($rdi=%1, $mem=%2);
+        [@0x00100c.0000] (%5: $Unknown64 = $rax, %6: $Unknown0 = $mem) = CALL #x500($rdi=%3, $mem=%4);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %5;
+        $mem = %6;
+}
+";
+
+    fn ret_of(rfn: &RadecoFunction, csite: u64) -> NodeIndex {
+        let ssa = rfn.ssa();
+        let call = ssa
+            .blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .find(|&n| {
+                ssa.opcode(n) == Some(MOpcode::OpCall)
+                    && ssa.address(n).map(|a| a.address) == Some(csite)
+            })
+            .unwrap();
+        let rax = ssa.regfile.register_id_by_name("rax").unwrap();
+        utils::call_rets(call, ssa).get(rax).unwrap().0
+    }
+
+    #[test]
+    fn loaded_signatures() {
+        let mut db = SignatureDb::empty();
+        db.load(r#"{ "fopen": { "pointer": true }, "atoi": { "width": 32 } }"#)
+            .unwrap();
+        assert_eq!(db.len(), 2);
+        assert!(db.get("sym.imp.fopen").unwrap().pointer);
+        assert_eq!(db.get("atoi").unwrap().width, Some(32));
+        assert!(db.get("malloc").is_none());
+        assert!(db.load("[]").is_err());
+    }
+
+    #[test]
+    fn malloc_returns_pointer() {
+        let mut rfn = load_at(CALLS_SSA_TXT, 0x1000, &register_file());
+        rfn.callconv = Some(Default::default());
+        rfn.callconv.as_mut().unwrap().ret = Some("rax".to_owned());
+
+        let mut rmod = RadecoModule::default();
+        rmod.functions.insert(0x1000, rfn);
+        rmod.imports.insert(
+            0x400,
+            ImportInfo::new_stub(0x400, Cow::from("sym.imp.malloc")),
+        );
+        let mut call_types = CallTypes::new(SignatureDb::default());
+        call_types.analyze(&mut rmod, None::<fn(_) -> _>);
+
+        let rfn = &rmod.functions[&0x1000];
+        let vt = |csite| rfn.ssa().node_data(ret_of(rfn, csite)).unwrap().vt;
+        assert!(vt(0x1004).is_reference());
+        assert_eq!(vt(0x1004).width().get_width(), Some(64));
+        assert!(!vt(0x100c).is_reference());
+        assert_eq!(vt(0x100c).width().get_width(), Some(64));
+    }
+}
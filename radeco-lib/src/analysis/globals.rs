@@ -0,0 +1,375 @@
+//! Global variable recovery.
+//!
+//! The accesses to `.data`/`.bss` go through constant addresses, which the backend prints as
+//! `*(0x601040)`. This pass groups the `OpLoad`/`OpStore`s whose address is a constant inside a
+//! writable, non-executable section by address, and records a global variable for every group in
+//! the `RadecoModule` (see `RadecoModule::globals`). Globals are named after the data symbols or
+//! the flags of the `Source` at their address (e.g. `obj.counter` becomes `g_counter`), otherwise
+//! a `g_<address>` name is synthesized.
+//!
+//! An address `base + index * stride` (or `base + (index << n)`) whose `base` is such a constant
+//! is an indexing of the array starting at `base`, which records the stride of the global.
+//!
+//! The constant addresses are bound to their global in every function (see
+//! `RadecoFunction::bind_local`), so that the backend prints `g_counter` instead of
+//! `*(0x601040)`, and `*(g_table + i * 4)` for the arrays. Constants which are also used as plain
+//! values, e.g. passed as a pointer, are left unbound.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::analysis::flag_fold::width_of;
+use crate::frontend::radeco_containers::{flag_names, BindingType, RadecoModule, VarBinding};
+use crate::frontend::radeco_source::Source;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+use r2papi::structs::LSymbolType;
+use serde::{Deserialize, Serialize};
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const NAME: &str = "globals";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::GlobalVars,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// A variable living in a data section of the module.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalVar {
+    /// Address of the first byte of the variable.
+    pub address: u64,
+    /// Size in bytes of the widest access, or of an element for the arrays.
+    pub size: u64,
+    /// Distance in bytes between two elements, if the variable is indexed as an array.
+    pub stride: Option<u64>,
+    /// The binding associated to this variable.
+    pub binding: VarBinding,
+}
+
+/// A single `OpLoad`/`OpStore` to a global.
+#[derive(Debug, Clone, Copy)]
+struct GlobalAccess {
+    /// The constant address of the global.
+    base: NodeIndex,
+    address: u64,
+    /// Width in bytes.
+    width: u64,
+    /// Stride of the indexing, if the access is `base + index * stride`.
+    stride: Option<u64>,
+}
+
+/// Returns `true` if `address` is in a section which is known to be writable and not executable,
+/// e.g. `.data` or `.bss`.
+pub fn is_data(source: &dyn Source, address: u64) -> bool {
+    source
+        .section_of(address)
+        .ok()
+        .and_then(|s| s.flags)
+        .map_or(false, |f| f.contains('w') && !f.contains('x'))
+}
+
+/// Name of the global named `raw` by a symbol or a flag, e.g. `g_counter` for `obj.counter`.
+fn global_name(raw: &str) -> Option<String> {
+    let name = raw.rsplit('.').next().unwrap_or(raw);
+    let valid = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match name.chars().next() {
+        Some(c) if valid && !c.is_ascii_digit() => {
+            if name.starts_with("g_") {
+                Some(name.to_owned())
+            } else {
+                Some(format!("g_{}", name))
+            }
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct GlobalVars {}
+
+impl GlobalVars {
+    pub fn new() -> Self {
+        GlobalVars {}
+    }
+
+    /// Returns the stride of `node` if it is `index * stride` or `index << n`.
+    fn stride_of(ssa: &SSAStorage, node: NodeIndex) -> Option<u64> {
+        let ops = ssa.operands_of(node);
+        if ops.len() != 2 {
+            return None;
+        }
+        let stride = match ssa.opcode(node)? {
+            MOpcode::OpMul => ssa.constant(ops[1]).or_else(|| ssa.constant(ops[0]))?,
+            MOpcode::OpLsl => 1 << ssa.constant(ops[1]).filter(|&n| n < 64)?,
+            _ => return None,
+        };
+        Some(stride).filter(|&s| s > 0)
+    }
+
+    /// Matches the address `addr` of an access of `width` bytes against the forms described in
+    /// the module documentation.
+    fn match_address(
+        ssa: &SSAStorage,
+        source: &dyn Source,
+        addr: NodeIndex,
+        width: u64,
+    ) -> Option<GlobalAccess> {
+        if let Some(address) = ssa.constant(addr) {
+            if !is_data(source, address) {
+                return None;
+            }
+            return Some(GlobalAccess {
+                base: addr,
+                address: address,
+                width: width,
+                stride: None,
+            });
+        }
+        if ssa.opcode(addr) != Some(MOpcode::OpAdd) {
+            return None;
+        }
+        let ops = ssa.operands_of(addr);
+        if ops.len() != 2 {
+            return None;
+        }
+        let (base, index) = match (ssa.constant(ops[0]), ssa.constant(ops[1])) {
+            (Some(_), None) => (ops[0], ops[1]),
+            (None, Some(_)) => (ops[1], ops[0]),
+            _ => return None,
+        };
+        let address = ssa.constant(base)?;
+        let stride = GlobalVars::stride_of(ssa, index)?;
+        if !is_data(source, address) {
+            return None;
+        }
+        Some(GlobalAccess {
+            base: base,
+            address: address,
+            width: width,
+            stride: Some(stride),
+        })
+    }
+
+    fn gather_accesses(ssa: &SSAStorage, source: &dyn Source) -> Vec<GlobalAccess> {
+        let mut accesses = Vec::new();
+        for node in ssa.values() {
+            let ops = ssa.operands_of(node);
+            let width = match ssa.opcode(node) {
+                Some(MOpcode::OpLoad) if ops.len() == 2 => width_of(ssa, node),
+                Some(MOpcode::OpStore) if ops.len() == 3 => width_of(ssa, ops[2]),
+                _ => continue,
+            };
+            let width = u64::from(width.unwrap_or(0) / 8);
+            accesses.extend(GlobalVars::match_address(ssa, source, ops[1], width));
+        }
+        accesses
+    }
+
+    /// Returns `true` if every use of the constant `base` is an access to its global, either
+    /// directly (`indexed` is `false`) or through an indexing (`indexed` is `true`).
+    fn only_addresses(ssa: &SSAStorage, base: NodeIndex, indexed: bool) -> bool {
+        let is_address = |user: NodeIndex, addr: NodeIndex| match ssa.opcode(user) {
+            Some(MOpcode::OpLoad) => ssa.operands_of(user).get(1) == Some(&addr),
+            Some(MOpcode::OpStore) => {
+                let ops = ssa.operands_of(user);
+                ops.len() == 3 && ops[1] == addr && ops[2] != addr
+            }
+            _ => false,
+        };
+        let uses = ssa.uses_of(base);
+        !uses.is_empty()
+            && uses.into_iter().all(|user| {
+                if indexed {
+                    ssa.opcode(user) == Some(MOpcode::OpAdd)
+                        && !ssa.uses_of(user).is_empty()
+                        && ssa.uses_of(user).into_iter().all(|u| is_address(u, user))
+                } else {
+                    is_address(user, base)
+                }
+            })
+    }
+
+    /// Names of the data symbols of `source`, falling back to its flags.
+    fn names(source: &dyn Source) -> HashMap<u64, String> {
+        let flags = source.flags().unwrap_or_default();
+        let mut names = flag_names(&flags)
+            .into_iter()
+            .filter(|(_, name)| !name.starts_with("section.") && !name.starts_with("segment."))
+            .collect::<HashMap<_, _>>();
+        for symbol in source.symbols().unwrap_or_default() {
+            if let Some(LSymbolType::Func) = symbol.stype {
+                continue;
+            }
+            if let (Some(address), Some(name)) = (symbol.vaddr, symbol.name) {
+                names.insert(address, name);
+            }
+        }
+        names
+    }
+}
+
+impl Analyzer for GlobalVars {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for GlobalVars {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let source = rmod.source.clone()?;
+        let accesses = rmod
+            .functions
+            .iter()
+            .map(|(&offset, rfn)| (offset, GlobalVars::gather_accesses(rfn.ssa(), &*source)))
+            .collect::<Vec<_>>();
+
+        // Group the accesses by address.
+        let names = GlobalVars::names(&*source);
+        let mut globals: BTreeMap<u64, GlobalVar> = BTreeMap::new();
+        for access in accesses.iter().flat_map(|(_, a)| a) {
+            let global = globals.entry(access.address).or_insert_with(|| {
+                let name = names
+                    .get(&access.address)
+                    .and_then(|raw| global_name(raw))
+                    .unwrap_or_else(|| format!("g_{:x}", access.address));
+                GlobalVar {
+                    address: access.address,
+                    size: 0,
+                    stride: None,
+                    binding: VarBinding::new(
+                        BindingType::Global(access.address),
+                        String::new(),
+                        Some(name),
+                        NodeIndex::end(),
+                        None,
+                    ),
+                }
+            });
+            global.size = global.size.max(access.width);
+            global.stride = global.stride.or(access.stride);
+        }
+
+        for (offset, accesses) in accesses {
+            let rfn = match rmod.functions.get_mut(&offset) {
+                Some(rfn) => rfn,
+                None => continue,
+            };
+            let mut bound = HashSet::new();
+            for access in accesses {
+                let global = &globals[&access.address];
+                let indexed = global.stride.is_some();
+                if access.stride.is_some() != indexed || !bound.insert(access.base) {
+                    continue;
+                }
+                if !GlobalVars::only_addresses(rfn.ssa(), access.base, indexed) {
+                    continue;
+                }
+                radeco_trace!(
+                    "globals|{:#x}: {:#x} -> {}",
+                    offset,
+                    access.address,
+                    global.binding.name()
+                );
+                rfn.bind_local(access.base, global.binding.clone());
+            }
+        }
+
+        rmod.set_globals(globals);
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ssa::cfg_traits::CFG;
+    use crate::utils::test_support::{load_at, register_file, StubSource};
+    use std::rc::Rc;
+
+    /// Source with a `.data` section at 0x601000 holding `obj.counter` at 0x601040.
+    fn data_source() -> StubSource {
+        StubSource::new()
+            .with_flags(
+                r#"[{"name":"section..data","offset":6295552,"size":256},
+                    {"name":"obj.counter","offset":6295616,"size":4}]"#,
+            )
+            .with_sections(
+                r#"[{"flags":"-rw-","name":".data","paddr":4096,"size":256,"vaddr":6295552,"vsize":256}]"#,
+            )
+    }
+
+    // mov eax, dword [0x601040]; mov ecx, dword [0x601040]
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const COUNTER_SSA_TXT: &str = "\
+define-fun sym.counter(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %2: $Unknown32 = Load(%1, #x601040);
+        [@0x001006.0001] %3: $Unknown32 = Load(%1, #x601040);
+        RETURN
+    exit-node:
+    final-register-state:
+        $eax = %2;
+        $ecx = %3;
+        $mem = %1;
+}
+";
+
+    #[test]
+    fn loads_share_one_global() {
+        let rfn = load_at(COUNTER_SSA_TXT, 0x1000, &register_file());
+
+        let mut rmod = RadecoModule::default();
+        rmod.functions.insert(0x1000, rfn);
+        rmod.source = Some(Rc::new(data_source()));
+
+        let mut globals = GlobalVars::new();
+        globals.analyze(&mut rmod, None::<fn(_) -> _>);
+
+        assert_eq!(rmod.globals().len(), 1);
+        let global = &rmod.globals()[&0x601040];
+        assert_eq!(global.binding.name(), "g_counter");
+        assert_eq!(global.size, 4);
+        assert_eq!(global.stride, None);
+
+        let rfn = &rmod.functions[&0x1000];
+        let bindings = rfn
+            .bindings()
+            .iter()
+            .filter(|vb| vb.btype == BindingType::Global(0x601040))
+            .count();
+        assert_eq!(bindings, 1);
+        // Both loads print the global instead of dereferencing its address.
+        let ssa = rfn.ssa();
+        let loads = ssa
+            .blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpLoad))
+            .collect::<Vec<_>>();
+        assert_eq!(loads.len(), 2);
+        for load in loads {
+            let addr = ssa.operands_of(load)[1];
+            let bound = rfn.local_at(addr, false).unwrap();
+            assert_eq!(bound[0].name(), "g_counter");
+        }
+    }
+}
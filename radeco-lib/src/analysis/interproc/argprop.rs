@@ -0,0 +1,281 @@
+//! Propagates constant arguments across the call graph.
+//!
+//! When every callsite of a function passes the same constant for one of its arguments, as linked
+//! by `CallContextInfo::map`, the argument is replaced by that constant inside the callee and
+//! `SCCP` is run again on it. The constants found this way may in turn be passed to the callees of
+//! the callee, so the propagation is repeated, up to a bounded depth. The bound also keeps the
+//! propagation from going around recursive functions forever.
+
+use crate::analysis::analyzer::{
+    all, Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+    ModuleAnalyzer,
+};
+use crate::analysis::sccp::SCCP;
+use crate::frontend::radeco_containers::{FunctionKind, RadecoModule};
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+const NAME: &str = "argconstprop";
+const REQUIRES: &[AnalyzerKind] = &[AnalyzerKind::Inferer];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::ArgConstProp,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Default number of times the constants are pushed one level down the call graph.
+pub const DEFAULT_DEPTH: u32 = 3;
+
+#[derive(Debug)]
+pub struct ArgConstProp {
+    max_depth: u32,
+    /// (function offset, argument node, constant) for every propagated argument
+    propagated: Vec<(u64, NodeIndex, u64)>,
+}
+
+impl ArgConstProp {
+    pub fn new() -> Self {
+        ArgConstProp::with_depth(DEFAULT_DEPTH)
+    }
+
+    pub fn with_depth(max_depth: u32) -> Self {
+        ArgConstProp {
+            max_depth,
+            propagated: Vec::new(),
+        }
+    }
+
+    /// Arguments replaced by a constant, as (function offset, argument node, constant).
+    pub fn propagated(&self) -> &[(u64, NodeIndex, u64)] {
+        &self.propagated
+    }
+
+    /// Finds the arguments, not propagated yet, for which every callsite passes the same constant.
+    fn constant_args(&self, rmod: &RadecoModule) -> Vec<(u64, NodeIndex, u64)> {
+        let cg_nodes: HashMap<u64, NodeIndex> = rmod
+            .callgraph
+            .node_indices()
+            .map(|n| (rmod.callgraph[n], n))
+            .collect();
+        let done: HashSet<(u64, NodeIndex)> = self
+            .propagated
+            .iter()
+            .map(|&(offset, arg, _)| (offset, arg))
+            .collect();
+
+        let mut found = Vec::new();
+        for (&offset, rfn) in &rmod.functions {
+            if rfn.kind != FunctionKind::Local {
+                continue;
+            }
+            let callsites = match cg_nodes.get(&offset) {
+                Some(&n) => rmod
+                    .callgraph
+                    .edges_directed(n, Direction::Incoming)
+                    .collect::<Vec<_>>(),
+                None => continue,
+            };
+            if callsites.is_empty() {
+                continue;
+            }
+
+            for arg in rfn.bindings().iter().filter(|b| b.btype.is_argument()) {
+                if done.contains(&(offset, arg.idx)) {
+                    continue;
+                }
+                let passed = callsites
+                    .iter()
+                    .map(|e| {
+                        let caller = rmod.functions.get(&rmod.callgraph[e.source()])?;
+                        let &(value, _) = e.weight().map.iter().find(|m| m.1 == arg.idx)?;
+                        caller.ssa().constant(value)
+                    })
+                    .collect::<Option<Vec<_>>>();
+                match passed {
+                    Some(ref values) if values.windows(2).all(|w| w[0] == w[1]) => {
+                        found.push((offset, arg.idx, values[0]));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Makes every user of the argument `arg` use the constant `value` instead, except for the entry
+/// register state which keeps describing the incoming registers.
+fn propagate(ssa: &mut SSAStorage, arg: NodeIndex, value: u64) {
+    let entry_regs = ssa.entry_node().and_then(|entry| ssa.registers_in(entry));
+    let width = ssa
+        .node_data(arg)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width());
+    let cnode = match ssa.insert_const(value, width) {
+        Some(cnode) => cnode,
+        None => return,
+    };
+
+    for user in ssa.uses_of(arg) {
+        if Some(user) == entry_regs {
+            continue;
+        }
+        let indices = ssa
+            .sparse_operands_of(user)
+            .into_iter()
+            .filter(|&(_, operand)| operand == arg)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        ssa.op_unuse(user, arg);
+        for i in indices {
+            ssa.op_use(user, i, cnode);
+        }
+    }
+}
+
+impl Analyzer for ArgConstProp {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for ArgConstProp {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        for _ in 0..self.max_depth {
+            let found = self.constant_args(rmod);
+            if found.is_empty() {
+                break;
+            }
+
+            let mut changed = HashSet::new();
+            for &(offset, arg, value) in &found {
+                if let Some(rfn) = rmod.functions.get_mut(&offset) {
+                    radeco_trace!("argconstprop|{}: {:?} = {:#x}", rfn.name, arg, value);
+                    propagate(rfn.ssa_mut(), arg, value);
+                    changed.insert(offset);
+                }
+            }
+            self.propagated.extend(found);
+
+            // Fold the constants inside the callees, so that they can be passed further down.
+            for offset in changed {
+                if let Some(rfn) = rmod.functions.get_mut(&offset) {
+                    SCCP::new().analyze(rfn, Some(all));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::radeco_containers::{BindingType, CallContextInfo, VarBinding};
+    use crate::middle::ir::MOpcode;
+    use crate::middle::ssa::ssa_traits::SSAWalk;
+    use crate::utils::test_support::{entry_reg, load_at, register_file};
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLEE_SSA_TXT: &str = "\
+define-fun sym.callee(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x002000.0000(sz 0x4):
+        [@0x002000.0001] %3: $Unknown64 = %1 + #x2;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %3;
+        $mem = %2;
+}
+";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLER_SSA_TXT: &str = "\
+define-fun sym.caller(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] (%3: $Unknown0 = $mem) = CALL #x2000($rdi=#x1, $mem=%2);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %3;
+}
+";
+
+    #[test]
+    fn single_caller_constant() {
+        let regfile = register_file();
+
+        let mut callee = load_at(CALLEE_SSA_TXT, 0x2000, &regfile);
+        let rdi = entry_reg(callee.ssa(), "rdi");
+        let ridx = regfile.register_id_by_name("rdi").unwrap().to_u8() as u64;
+        callee.bindings_mut().push(VarBinding::new(
+            BindingType::RegisterArgument(0),
+            String::new(),
+            Some("rdi".to_owned()),
+            rdi,
+            Some(ridx),
+        ));
+
+        let caller = load_at(CALLER_SSA_TXT, 0x1000, &regfile);
+        let call = caller
+            .ssa()
+            .inorder_walk()
+            .find(|&n| caller.ssa().opcode(n) == Some(MOpcode::OpCall))
+            .unwrap();
+        let one = caller
+            .ssa()
+            .operands_of(call)
+            .into_iter()
+            .find(|&n| caller.ssa().constant(n) == Some(1))
+            .unwrap();
+
+        let mut rmod = RadecoModule::default();
+        let callee_id = rmod.callgraph.add_node(0x2000);
+        let caller_id = rmod.callgraph.add_node(0x1000);
+        let cctx = CallContextInfo {
+            map: vec![(one, rdi)],
+            csite_node: call,
+            csite: 0x1001,
+        };
+        rmod.callgraph.add_edge(caller_id, callee_id, cctx);
+        rmod.functions.insert(0x2000, callee);
+        rmod.functions.insert(0x1000, caller);
+
+        let mut argprop = ArgConstProp::new();
+        argprop.analyze(&mut rmod, None::<fn(_) -> _>);
+        assert_eq!(argprop.propagated(), &[(0x2000, rdi, 1)]);
+
+        // `rdi + 2` folds to 3.
+        let ssa = rmod.functions[&0x2000].ssa();
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        assert!(ssa
+            .operands_of(exit)
+            .into_iter()
+            .any(|n| ssa.constant(n) == Some(3)));
+    }
+}
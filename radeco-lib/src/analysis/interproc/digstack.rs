@@ -61,8 +61,8 @@ pub fn backward_analysis(ssa: &SSAStorage, sp_name: String) -> HashMap<LValueRef
             }
         }
 
-        // The node maybe a comment, for call statements made it
-        if ssa.comment(node).is_some() {
+        // The node maybe a comment, for call statements made it, or the SP on entry
+        if ssa.comment(node).is_some() || ssa.is_argument(node) {
             continue;
         }
 
@@ -139,7 +139,7 @@ fn generic_frontward_analysis(
 
         let nodes = ssa.operands_of(reg_state);
         for node in &nodes {
-            if ssa.comment(*node) != Some(sp_name.clone()) {
+            if ssa.argument(*node) != Some(sp_name.clone()) {
                 continue;
             }
             stack_offset.insert(*node, 0);
@@ -223,6 +223,7 @@ fn generic_frontward_analysis(
             }
             if ssa.opcode(args[opcode_arg as usize]).is_some()
                 || ssa.comment(args[opcode_arg as usize]).is_some()
+                || ssa.is_argument(args[opcode_arg as usize])
                 || (ssa.is_phi(args[opcode_arg as usize]) && is_global)
             {
                 if let Some(MOpcode::OpConst(num)) = ssa.opcode(args[const_arg as usize]) {
@@ -284,18 +285,15 @@ mod test {
     use crate::frontend::radeco_containers::RadecoFunction;
     use crate::frontend::ssaconstructor::SSAConstruct;
     use crate::middle::regfile::SubRegisterFile;
+    use crate::utils::test_support::reg_profile;
 
-    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
     const BIN_LS_INSTRUCTIONS: &'static str = "test_files/bin_ls_instructions.json";
     const CT1_INSTRUCTIONS: &'static str = "test_files/ct1_instructions.json";
 
     #[test]
     fn bin_ls_test() {
         let instructions: LFunctionInfo;
-        let mut register_profile = File::open(REGISTER_PROFILE).unwrap();
-        let mut s = String::new();
-        register_profile.read_to_string(&mut s).unwrap();
-        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let reg_profile = reg_profile();
         let mut instruction_file = File::open(BIN_LS_INSTRUCTIONS).unwrap();
         let mut s = String::new();
         instruction_file.read_to_string(&mut s).unwrap();
@@ -303,9 +301,11 @@ mod test {
         let mut rfn = RadecoFunction::default();
 
         {
-            let regfile = SubRegisterFile::new(&reg_profile);
+            let regfile = SubRegisterFile::new(&reg_profile).unwrap();
             let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor
+                .run(instructions.ops.unwrap().as_slice())
+                .unwrap();
         }
 
         let mut dce = DCE::new();
@@ -319,10 +319,7 @@ mod test {
     #[test]
     fn ct1_test() {
         let instructions: LFunctionInfo;
-        let mut register_profile = File::open(REGISTER_PROFILE).unwrap();
-        let mut s = String::new();
-        register_profile.read_to_string(&mut s).unwrap();
-        let reg_profile = serde_json::from_str(&*s).unwrap();
+        let reg_profile = reg_profile();
         let mut instruction_file = File::open(CT1_INSTRUCTIONS).unwrap();
         let mut s = String::new();
         instruction_file.read_to_string(&mut s).unwrap();
@@ -330,9 +327,11 @@ mod test {
         let mut rfn = RadecoFunction::default();
 
         {
-            let regfile = SubRegisterFile::new(&reg_profile);
+            let regfile = SubRegisterFile::new(&reg_profile).unwrap();
             let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor
+                .run(instructions.ops.unwrap().as_slice())
+                .unwrap();
         }
 
         let mut dce = DCE::new();
@@ -387,7 +387,7 @@ impl<'a> CallFixer<'a> {
         let reg_state = registers_in_err!(ssa, entry_node_err!(ssa));
         let nodes = ssa.operands_of(reg_state);
         for node in &nodes {
-            if ssa.comment(*node).is_none() {
+            if !ssa.is_argument(*node) {
                 continue;
             }
             if ssa.registers(*node).is_empty() {
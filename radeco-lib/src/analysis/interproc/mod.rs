@@ -5,6 +5,7 @@
 // This file may not be copied, modified, or distributed
 // except according to those terms.
 
+pub mod argprop;
 mod digstack;
 pub mod fixcall;
 pub mod interproc;
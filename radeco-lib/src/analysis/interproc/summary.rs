@@ -75,7 +75,7 @@ impl InterProcAnalysis for CallSummary {
                 for (i, r) in ssa.operands_of(rs).iter().enumerate() {
                     let (insert_r, insert_m) = if let Ok(ref data) = ssa.node_data(*r) {
                         match data.nt {
-                            NodeType::Comment(_) => (false, false),
+                            NodeType::Comment(_) | NodeType::Argument(_) => (false, false),
                             NodeType::Op(MOpcode::OpLoad) => (locals.contains(&i), true),
                             _ => (true, true),
                         }
@@ -0,0 +1,624 @@
+//! Recognizes the loops compilers inline for `memcpy`, `memset` and `strlen`.
+//!
+//! Small memory and string operations are often compiled to a loop over bytes. This pass looks for
+//! the innermost loops made of a single block, using the `LoopForest`, whose only effects are:
+//!
+//!  * `memcpy`: a byte loaded from `src + i` stored at `dst + i`;
+//!  * `memset`: a byte defined outside the loop stored at `dst + i`;
+//!  * `strlen`: a byte loaded from `s + i`, the loop ending on the first `0`.
+//!
+//! Here `i` is an induction variable, i.e. a phi of the header incremented by one at every
+//! iteration, and the addresses are either such a phi or the sum of a value defined outside the
+//! loop and a phi starting from `0`. The stores are chained through the memory phi of the header,
+//! so that the loop is the only definition of memory in the loop. `memcpy` and `memset` loops have
+//! to end once an induction variable reaches a bound defined outside the loop, which gives the
+//! number of bytes.
+//!
+//! Such a loop is replaced by an `OpCustom` node named after the function (see `Intrinsic`),
+//! whose operands are the memory state before the loop and the arguments of the function. It
+//! defines the memory state after `memcpy` and `memset`, and the length of the string for
+//! `strlen`. The values of the induction variables after the loop are computed from the number of
+//! iterations and the block falls through to the exit of the loop.
+//!
+//! Only exact matches are replaced: the loop is left alone if it has any other side effect, any
+//! other phi, or if a value other than the memory and the induction variables is used after it.
+//! The loops are assumed to run at least once, as the compiler checks the bound beforehand.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+};
+use crate::analysis::flag_fold::{binop, split_const, width_of};
+use crate::analysis::loops::{Loop, LoopForest};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::{MAddress, MOpcode, WidthSpec};
+use crate::middle::phiplacement::UNCOND_EDGE;
+use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+
+const FALSE_EDGE: u8 = 0;
+const TRUE_EDGE: u8 = 1;
+
+/// A library function whose inlined loop is recognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intrinsic {
+    /// `memcpy(dst, src, n)`
+    Memcpy,
+    /// `memset(dst, value, n)`
+    Memset,
+    /// `strlen(s)`
+    Strlen,
+}
+
+impl Intrinsic {
+    /// Name of the function, also the name of the `OpCustom` standing for it.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Intrinsic::Memcpy => "memcpy",
+            Intrinsic::Memset => "memset",
+            Intrinsic::Strlen => "strlen",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Intrinsic> {
+        match name {
+            "memcpy" => Some(Intrinsic::Memcpy),
+            "memset" => Some(Intrinsic::Memset),
+            "strlen" => Some(Intrinsic::Strlen),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the intrinsic `node` stands for, if any.
+pub fn intrinsic_of(ssa: &SSAStorage, node: NodeIndex) -> Option<Intrinsic> {
+    match ssa.opcode(node) {
+        Some(MOpcode::OpCustom(ref name)) => Intrinsic::from_name(name),
+        _ => None,
+    }
+}
+
+/// A phi of the header incremented by one at every iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Induction {
+    pub phi: NodeIndex,
+    /// Value before the loop
+    pub init: NodeIndex,
+    /// Value at the end of an iteration, `phi + 1`
+    pub next: NodeIndex,
+}
+
+/// A `Change` which replaces the loop `block` with a call of `intrinsic`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlinedLoop {
+    /// The only block of the loop.
+    pub block: NodeIndex,
+    /// The block following the loop.
+    pub exit: NodeIndex,
+    pub intrinsic: Intrinsic,
+    /// The memory state before the loop.
+    pub memory: NodeIndex,
+    /// The memory state at the end of an iteration, for `memcpy` and `memset`.
+    pub memory_out: Option<NodeIndex>,
+    /// Arguments of the function, without the number of bytes.
+    pub args: Vec<NodeIndex>,
+    /// For `memcpy` and `memset`, (bound, init) of the induction variable ending the loop. The
+    /// number of bytes is `bound - init`.
+    pub count: Option<(NodeIndex, NodeIndex)>,
+    pub inductions: Vec<Induction>,
+    /// Address of the first memory access of the loop.
+    pub at: MAddress,
+}
+
+impl Change for InlinedLoop {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+const NAME: &str = "intrinsics";
+const REQUIRES: &[AnalyzerKind] = &[AnalyzerKind::FlagFold];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::Intrinsics,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+#[derive(Debug)]
+pub struct Intrinsics {}
+
+/// A loop made of `block` alone.
+struct LoopBlock<'a> {
+    ssa: &'a SSAStorage,
+    block: NodeIndex,
+    inductions: Vec<Induction>,
+}
+
+impl<'a> LoopBlock<'a> {
+    /// Whether `node` is computed in the loop. Constants are not part of any block.
+    fn inside(&self, node: NodeIndex) -> bool {
+        self.ssa.constant(node).is_none() && self.ssa.block_for(node) == Some(self.block)
+    }
+
+    fn induction_of(&self, phi: NodeIndex) -> Option<Induction> {
+        self.inductions.iter().find(|iv| iv.phi == phi).cloned()
+    }
+
+    fn induction_with_next(&self, next: NodeIndex) -> Option<Induction> {
+        self.inductions.iter().find(|iv| iv.next == next).cloned()
+    }
+
+    /// Value of the byte address `addr` at the first iteration: `addr` is either an induction
+    /// variable or the sum of a value defined outside the loop and one starting from `0`.
+    fn start_of(&self, addr: NodeIndex) -> Option<NodeIndex> {
+        if let Some(iv) = self.induction_of(addr) {
+            return Some(iv.init);
+        }
+        let (x, y) = binop(self.ssa, addr, MOpcode::OpAdd)?;
+        [(x, y), (y, x)].iter().find_map(|&(base, idx)| {
+            let iv = self.induction_of(idx)?;
+            if !self.inside(base) && self.ssa.constant(iv.init) == Some(0) {
+                Some(base)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Matches the condition `node` of staying in the loop (or of leaving it, if `stay` is
+    /// false) against an induction variable reaching a bound. Returns (bound, init).
+    fn match_count(&self, node: NodeIndex, stay: bool) -> Option<(NodeIndex, NodeIndex)> {
+        let ssa = self.ssa;
+        let ops = ssa.operands_of(node);
+        let bounded = |next: NodeIndex, bound: NodeIndex| {
+            let iv = self.induction_with_next(next)?;
+            if self.inside(bound) {
+                None
+            } else {
+                Some((bound, iv.init))
+            }
+        };
+        match ssa.opcode(node)? {
+            MOpcode::OpNot if ops.len() == 1 => self.match_count(ops[0], !stay),
+            MOpcode::OpMov | MOpcode::OpNarrow(1) if ops.len() == 1 => {
+                self.match_count(ops[0], stay)
+            }
+            // Leaves once `next == bound`.
+            MOpcode::OpEq if !stay && ops.len() == 2 => {
+                bounded(ops[0], ops[1]).or_else(|| bounded(ops[1], ops[0]))
+            }
+            // Stays while `next < bound`.
+            MOpcode::OpLt if stay && ops.len() == 2 => bounded(ops[0], ops[1]),
+            MOpcode::OpGt if stay && ops.len() == 2 => bounded(ops[1], ops[0]),
+            _ => None,
+        }
+    }
+
+    /// Matches the condition `node` of staying in the loop (or of leaving it, if `stay` is
+    /// false) against `byte` being `0`, possibly tested as `byte & byte`.
+    fn match_nul(&self, node: NodeIndex, byte: NodeIndex, stay: bool) -> bool {
+        let ssa = self.ssa;
+        let ops = ssa.operands_of(node);
+        match ssa.opcode(node) {
+            Some(MOpcode::OpNot) if ops.len() == 1 => self.match_nul(ops[0], byte, !stay),
+            Some(MOpcode::OpMov) | Some(MOpcode::OpNarrow(1)) if ops.len() == 1 => {
+                self.match_nul(ops[0], byte, stay)
+            }
+            Some(MOpcode::OpEq) if !stay => match split_const(ssa, node) {
+                Some((x, 0)) => x == byte || binop(ssa, x, MOpcode::OpAnd) == Some((byte, byte)),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl Intrinsics {
+    pub fn new() -> Self {
+        Intrinsics {}
+    }
+
+    fn match_loop(ssa: &SSAStorage, lp: &Loop<NodeIndex>) -> Option<InlinedLoop> {
+        if lp.body.len() != 1 || !lp.children.is_empty() {
+            return None;
+        }
+        let block = lp.header;
+        if ssa.preds_of(block).len() != 2 {
+            return None;
+        }
+        let (mut stay, mut exit) = (None, None);
+        for (edge, ety) in ssa.outgoing_edges(block) {
+            let target = ssa.g.edge_endpoints(edge).map(|(_, t)| t)?;
+            match (target == block, ety) {
+                (true, TRUE_EDGE) => stay = Some(true),
+                (true, FALSE_EDGE) => stay = Some(false),
+                (false, TRUE_EDGE) | (false, FALSE_EDGE) => exit = Some(target),
+                _ => return None,
+            }
+        }
+        let (stay, exit) = (stay?, exit?);
+        let selector = ssa.selector_in(block)?;
+
+        let mut lb = LoopBlock {
+            ssa: ssa,
+            block: block,
+            inductions: Vec::new(),
+        };
+        let mut memory_phi = None;
+        for phi in ssa.phis_in(block) {
+            let ops = ssa.operands_of(phi);
+            if ops.len() != 2 {
+                return None;
+            }
+            let (init, next) = match (lb.inside(ops[0]), lb.inside(ops[1])) {
+                (false, true) => (ops[0], ops[1]),
+                (true, false) => (ops[1], ops[0]),
+                _ => return None,
+            };
+            if width_of(ssa, phi) == Some(0) && memory_phi.is_none() {
+                memory_phi = Some((phi, init, next));
+            } else if ssa.opcode(next) == Some(MOpcode::OpAdd)
+                && split_const(ssa, next) == Some((phi, 1))
+            {
+                lb.inductions.push(Induction {
+                    phi: phi,
+                    init: init,
+                    next: next,
+                });
+            } else {
+                return None;
+            }
+        }
+
+        let exprs = ssa.exprs_in(block);
+        let with_opcode = |opcode: MOpcode| {
+            exprs
+                .iter()
+                .cloned()
+                .filter(|&n| ssa.opcode(n) == Some(opcode.clone()))
+                .collect::<Vec<_>>()
+        };
+        let (loads, stores) = (with_opcode(MOpcode::OpLoad), with_opcode(MOpcode::OpStore));
+        let effects = exprs
+            .iter()
+            .filter(|&&n| ssa.opcode(n).map_or(false, |op| op.has_sideeffects()))
+            .count();
+        if effects != stores.len() {
+            return None;
+        }
+        let is_byte = |n: NodeIndex| width_of(ssa, n) == Some(8);
+
+        let (intrinsic, memory, memory_out, args, count, at) = match (&*loads, &*stores) {
+            (&[load], &[store]) => {
+                let (phi, init, next) = memory_phi?;
+                let (l, s) = (ssa.operands_of(load), ssa.operands_of(store));
+                if l.len() != 2 || s.len() != 3 || l[0] != phi || s[0] != phi || s[2] != load {
+                    return None;
+                }
+                if next != store || !is_byte(load) {
+                    return None;
+                }
+                let args = vec![lb.start_of(s[1])?, lb.start_of(l[1])?];
+                let count = lb.match_count(selector, stay)?;
+                let at = ssa.address(load)?;
+                (Intrinsic::Memcpy, init, Some(store), args, Some(count), at)
+            }
+            (&[], &[store]) => {
+                let (phi, init, next) = memory_phi?;
+                let s = ssa.operands_of(store);
+                if s.len() != 3 || s[0] != phi || next != store {
+                    return None;
+                }
+                if lb.inside(s[2]) || !is_byte(s[2]) {
+                    return None;
+                }
+                let args = vec![lb.start_of(s[1])?, s[2]];
+                let count = lb.match_count(selector, stay)?;
+                let at = ssa.address(store)?;
+                (Intrinsic::Memset, init, Some(store), args, Some(count), at)
+            }
+            (&[load], &[]) => {
+                let l = ssa.operands_of(load);
+                if memory_phi.is_some() || l.len() != 2 || lb.inside(l[0]) || !is_byte(load) {
+                    return None;
+                }
+                if !lb.match_nul(selector, load, stay) {
+                    return None;
+                }
+                let args = vec![lb.start_of(l[1])?];
+                let at = ssa.address(load)?;
+                (Intrinsic::Strlen, l[0], None, args, None, at)
+            }
+            _ => return None,
+        };
+
+        // Only the memory and the induction variables may be used after the loop.
+        let used_after = |n: NodeIndex| ssa.uses_of(n).into_iter().any(|u| !lb.inside(u));
+        let live_out = |n: NodeIndex| {
+            Some(n) == memory_out || lb.inductions.iter().any(|iv| iv.phi == n || iv.next == n)
+        };
+        let mut nodes = ssa.phis_in(block).into_iter().chain(exprs.iter().cloned());
+        if nodes.any(|n| lb.inside(n) && used_after(n) && !live_out(n)) {
+            return None;
+        }
+
+        Some(InlinedLoop {
+            block: block,
+            exit: exit,
+            intrinsic: intrinsic,
+            memory: memory,
+            memory_out: memory_out,
+            args: args,
+            count: count,
+            inductions: lb.inductions,
+            at: at,
+        })
+    }
+
+    fn gather(ssa: &SSAStorage) -> Vec<InlinedLoop> {
+        let forest = LoopForest::new(ssa);
+        forest
+            .loops()
+            .iter()
+            .filter_map(|lp| Intrinsics::match_loop(ssa, lp))
+            .collect()
+    }
+
+    /// Inserts `opcode` of `operands`, with the type `vt`, into the block of `il`.
+    fn insert(
+        ssa: &mut SSAStorage,
+        il: &InlinedLoop,
+        opcode: MOpcode,
+        vt: ValueInfo,
+        operands: &[NodeIndex],
+    ) -> Option<NodeIndex> {
+        let node = ssa.insert_op(opcode, vt, None)?;
+        for (i, &op) in operands.iter().enumerate() {
+            ssa.op_use(node, i as u8, op);
+        }
+        ssa.insert_into_block(node, il.block, il.at);
+        Some(node)
+    }
+
+    fn apply(ssa: &mut SSAStorage, il: InlinedLoop) -> Option<NodeIndex> {
+        let block = il.block;
+        let old_nodes = ssa
+            .phis_in(block)
+            .into_iter()
+            .chain(ssa.exprs_in(block))
+            .filter(|&n| ssa.constant(n).is_none())
+            .collect::<Vec<_>>();
+        let used_after = |ssa: &SSAStorage, n: NodeIndex| {
+            ssa.uses_of(n)
+                .into_iter()
+                .any(|u| ssa.block_for(u) != Some(block))
+        };
+        let scalar = |w: Option<u16>| ValueInfo::new_scalar(WidthSpec::new_known(w.unwrap_or(64)));
+
+        let mut operands = vec![il.memory];
+        operands.extend(il.args.iter().cloned());
+        let n = match il.count {
+            Some((bound, init)) if ssa.constant(init) == Some(0) => Some(bound),
+            Some((bound, init)) => {
+                let vt = scalar(width_of(ssa, bound));
+                let n = Intrinsics::insert(ssa, &il, MOpcode::OpSub, vt, &[bound, init])?;
+                Some(n)
+            }
+            None => None,
+        };
+        operands.extend(n);
+        let vt = match il.memory_out {
+            Some(_) => ssa.node_data(il.memory).ok()?.vt,
+            None => scalar(width_of(ssa, il.args[0])),
+        };
+        let opcode = MOpcode::OpCustom(il.intrinsic.name().to_owned());
+        let call = Intrinsics::insert(ssa, &il, opcode, vt, &operands)?;
+
+        if let Some(memory_out) = il.memory_out {
+            ssa.replace_value(memory_out, call);
+        }
+        for iv in &il.inductions {
+            if !used_after(ssa, iv.phi) && !used_after(ssa, iv.next) {
+                continue;
+            }
+            let vt = scalar(width_of(ssa, iv.phi));
+            let one = ssa.insert_const(1, vt.width().get_width())?;
+            let init_is_zero = ssa.constant(iv.init) == Some(0);
+            // Values of the induction variable at the start and at the end of the last iteration.
+            let (last, after) = match (il.count, n) {
+                (Some((bound, init)), Some(n)) => {
+                    let after = if init == iv.init {
+                        bound
+                    } else if init_is_zero {
+                        n
+                    } else {
+                        Intrinsics::insert(ssa, &il, MOpcode::OpAdd, vt, &[iv.init, n])?
+                    };
+                    let last = Intrinsics::insert(ssa, &il, MOpcode::OpSub, vt, &[after, one])?;
+                    (last, after)
+                }
+                // The last iteration of `strlen` loads the terminating `0`.
+                _ => {
+                    let last = if init_is_zero {
+                        call
+                    } else {
+                        Intrinsics::insert(ssa, &il, MOpcode::OpAdd, vt, &[iv.init, call])?
+                    };
+                    let after = Intrinsics::insert(ssa, &il, MOpcode::OpAdd, vt, &[last, one])?;
+                    (last, after)
+                }
+            };
+            ssa.replace_value(iv.phi, last);
+            ssa.replace_value(iv.next, after);
+        }
+
+        // The block now falls through to the exit of the loop.
+        if let Some(selector) = ssa.selector_in(block) {
+            ssa.remove_edges_between(block, selector);
+        }
+        for (edge, _) in ssa.outgoing_edges(block) {
+            ssa.remove_control_edge(edge);
+        }
+        ssa.insert_control_edge(block, il.exit, UNCOND_EDGE);
+        for node in old_nodes {
+            ssa.remove_value(node);
+        }
+        Some(call)
+    }
+}
+
+impl Analyzer for Intrinsics {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for Intrinsics {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        func: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        let ssa = func.ssa_mut();
+
+        for il in Intrinsics::gather(ssa) {
+            match policy(Box::new(il.clone())) {
+                Action::Apply => {
+                    radeco_trace!("intrinsics|{:?}", il);
+                    if Intrinsics::apply(ssa, il).is_none() {
+                        radeco_err!("Failed to replace the loop");
+                    }
+                }
+                Action::Skip => (),
+                Action::Abort => break,
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ssa::utils;
+    use crate::utils::test_support::load;
+
+    // for (rcx = 0; rcx != rdx; rcx++) rdi[rcx] = rsi[rcx];
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const COPY_SSA_TXT: &str = "\
+define-fun sym.copy(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown64 = $rdx;
+        %4: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        %5: $Unknown64 = Phi(#x0, %11);
+        %6: $Unknown0 = Phi(%4, %10);
+        [@0x001000.0001] %7: $Unknown64 = %2 + %5;
+        [@0x001000.0002] %8: $Unknown8 = Load(%6, %7);
+        [@0x001004.0001] %9: $Unknown64 = %1 + %5;
+        [@0x001004.0002] %10: $Unknown0 = Store(%6, %9, %8);
+        [@0x001007.0001] %11: $Unknown64 = %5 + #x1;
+        [@0x00100a.0001] %12: $Unknown1 = %11 == %3;
+        JMP IF %12 0x001010.0000 ELSE 0x001000.0000
+    bb_0x001010.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rcx = %11;
+        $mem = %10;
+}
+";
+
+    // Same as above, but the loaded byte is also returned.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const COPY_LIVE_OUT_SSA_TXT: &str = "\
+define-fun sym.copy_live_out(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown64 = $rdx;
+        %4: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        %5: $Unknown64 = Phi(#x0, %11);
+        %6: $Unknown0 = Phi(%4, %10);
+        [@0x001000.0001] %7: $Unknown64 = %2 + %5;
+        [@0x001000.0002] %8: $Unknown8 = Load(%6, %7);
+        [@0x001004.0001] %9: $Unknown64 = %1 + %5;
+        [@0x001004.0002] %10: $Unknown0 = Store(%6, %9, %8);
+        [@0x001007.0001] %11: $Unknown64 = %5 + #x1;
+        [@0x00100a.0001] %12: $Unknown1 = %11 == %3;
+        JMP IF %12 0x001010.0000 ELSE 0x001000.0000
+    bb_0x001010.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $al = %8;
+        $mem = %10;
+}
+";
+
+    fn reg_value(ssa: &SSAStorage, block: NodeIndex, reg: &str) -> NodeIndex {
+        let regid = match reg {
+            "mem" => ssa.regfile.mem_id(),
+            _ => ssa.regfile.register_id_by_name(reg).unwrap(),
+        };
+        let state = ssa.registers_in(block).unwrap();
+        utils::register_state_info(state, ssa)[regid].0
+    }
+
+    #[test]
+    fn byte_copy_is_memcpy() {
+        let mut rfn = load(COPY_SSA_TXT);
+        Intrinsics::new().analyze(&mut rfn, Some(all));
+        let ssa = rfn.ssa();
+
+        let entry_reg = |reg| reg_value(ssa, ssa.entry_node().unwrap(), reg);
+        let exit_reg = |reg| reg_value(ssa, ssa.exit_node().unwrap(), reg);
+
+        let memcpy = exit_reg("mem");
+        assert_eq!(intrinsic_of(ssa, memcpy), Some(Intrinsic::Memcpy));
+        assert_eq!(
+            ssa.operands_of(memcpy),
+            vec![
+                entry_reg("mem"),
+                entry_reg("rdi"),
+                entry_reg("rsi"),
+                entry_reg("rdx")
+            ]
+        );
+        // The counter ends up at the number of bytes.
+        assert_eq!(exit_reg("rcx"), entry_reg("rdx"));
+
+        // The loop is gone.
+        let block = ssa.block_for(memcpy).unwrap();
+        assert!(ssa.phis_in(block).is_empty());
+        assert!(ssa.selector_in(block).is_none());
+        assert!(!ssa.succs_of(block).contains(&block));
+        assert!(ssa
+            .exprs_in(block)
+            .into_iter()
+            .all(|n| ssa.opcode(n) != Some(MOpcode::OpLoad)
+                && ssa.opcode(n) != Some(MOpcode::OpStore)));
+    }
+
+    #[test]
+    fn live_out_byte_is_kept() {
+        let rfn = load(COPY_LIVE_OUT_SSA_TXT);
+        assert!(Intrinsics::gather(rfn.ssa()).is_empty());
+    }
+}
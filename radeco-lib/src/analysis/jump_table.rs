@@ -0,0 +1,425 @@
+//! Resolves the indirect jumps through the jump table of a `switch`.
+//!
+//! The targets of an indirect jump aren't known to `SSAConstruct`, so the block ending with one
+//! only branches to a placeholder block (see `phiplacement::is_unexplored`), or straight to the
+//! exit for the IR read by `ir_reader`. A jump to `Load(mem, table + index * size)`, where `table`
+//! is a constant address in a read-only section and `size` the size of the loaded entries, is
+//! resolved from the range of `index` in the block of the jump (see `ValueRanges::range_in`).
+//! When the bounds check of the switch restricts `index` to `[lo, hi]`, exactly the entries of
+//! these `hi - lo + 1` indices are read, since the data following the table is not part of it,
+//! and the placeholder is replaced by an edge to the block starting at every target.
+//!
+//! The other indices branch away at the bounds check, to the default case of the switch. That
+//! block, the other side of the nearest branch dominating the jump, is recorded with the table.
+//!
+//! A jump whose index is unbounded, i.e. may take more than `MAX_CASES` values, or with a target
+//! that doesn't start a block of the function, is left unresolved.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::analysis::flag_fold::width_of;
+use crate::analysis::loops::LoopForest;
+use crate::analysis::rodata::{self, Endian};
+use crate::analysis::vrange::{Interval, ValueRanges};
+use crate::frontend::radeco_containers::RadecoModule;
+use crate::frontend::radeco_source::Source;
+use crate::middle::ir::MOpcode;
+use crate::middle::phiplacement;
+use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::{EdgeIndex, NodeIndex};
+
+use std::any::Any;
+use std::collections::HashMap;
+
+const NAME: &str = "jumptables";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::JumpTables,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Largest number of entries read from a table. An index with a wider range is unbounded.
+pub const MAX_CASES: u64 = 1024;
+
+/// Type of the edges to the cases, as for the placeholder they replace.
+const UNCOND_EDGE: u8 = 2;
+
+/// A jump table whose targets are resolved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JumpTable {
+    /// Offset of the function
+    pub function: u64,
+    /// Address of the entry of index 0
+    pub table: u64,
+    /// Range of the index at the jump
+    pub index: Interval,
+    /// Target of every index of the range, in order
+    pub cases: Vec<u64>,
+    /// Start of the block the bounds check branches to for the other indices
+    pub default: Option<u64>,
+}
+
+/// Jump resolved in a function, before its edges are inserted.
+struct Switch {
+    block: NodeIndex,
+    placeholder: EdgeIndex,
+    targets: Vec<NodeIndex>,
+    table: JumpTable,
+}
+
+#[derive(Debug)]
+pub struct JumpTables {
+    endian: Endian,
+    tables: Vec<JumpTable>,
+}
+
+impl JumpTables {
+    /// Reads the entries of the tables as little endian values.
+    pub fn new() -> Self {
+        JumpTables::with_endian(Endian::Little)
+    }
+
+    pub fn with_endian(endian: Endian) -> Self {
+        JumpTables {
+            endian,
+            tables: Vec::new(),
+        }
+    }
+
+    /// Jump tables resolved so far.
+    pub fn tables(&self) -> &[JumpTable] {
+        &self.tables
+    }
+
+    fn resolve(
+        &self,
+        source: &dyn Source,
+        ssa: &SSAStorage,
+        ranges: &ValueRanges,
+        forest: &LoopForest<NodeIndex>,
+        block: NodeIndex,
+    ) -> Option<Switch> {
+        let target = ssa.selector_in(block)?;
+        let placeholder = match ssa.outgoing_edges(block).as_slice() {
+            &[(edge, _)] => edge,
+            _ => return None,
+        };
+        let succ = ssa.g.edge_endpoints(placeholder)?.1;
+        let unexplored = ssa
+            .starting_address(succ)
+            .map_or(false, phiplacement::is_unexplored);
+        if !unexplored && ssa.exit_node() != Some(succ) {
+            return None;
+        }
+
+        let (table, index, size) = table_load(ssa, target)?;
+        let range = ranges.range_in(index, block)?;
+        if range.hi - range.lo >= MAX_CASES {
+            radeco_trace!("jumptables|{:?}: unbounded index {:?}", block, range);
+            return None;
+        }
+        let first = table.checked_add(range.lo.checked_mul(size)?)?;
+        let len = (range.hi - range.lo + 1) * size;
+        if !rodata::is_read_only(source, first, len) {
+            return None;
+        }
+        let bytes = source.read_bytes(first, len as usize).ok()?;
+        if bytes.len() as u64 != len {
+            return None;
+        }
+        let cases = bytes
+            .chunks(size as usize)
+            .map(|entry| self.endian.value_of(entry))
+            .collect::<Vec<_>>();
+
+        let starts = ssa
+            .blocks()
+            .into_iter()
+            .filter_map(|b| {
+                let start = ssa.starting_address(b)?;
+                if start.offset == 0 {
+                    Some((start.address, b))
+                } else {
+                    None
+                }
+            })
+            .collect::<HashMap<_, _>>();
+        let mut targets = Vec::new();
+        for case in &cases {
+            let target = *starts.get(case)?;
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+
+        let default = default_case(ssa, forest, block)
+            .and_then(|b| ssa.starting_address(b))
+            .map(|start| start.address);
+        Some(Switch {
+            block,
+            placeholder,
+            targets,
+            table: JumpTable {
+                function: 0,
+                table,
+                index: range,
+                cases,
+                default,
+            },
+        })
+    }
+}
+
+/// Returns the constant address of the table, the index and the size of the entries, if `target`
+/// is loaded from `table + index * size`.
+fn table_load(ssa: &SSAStorage, target: NodeIndex) -> Option<(u64, NodeIndex, u64)> {
+    if ssa.opcode(target) != Some(MOpcode::OpLoad) {
+        return None;
+    }
+    let size = match width_of(ssa, target)? {
+        32 => 4,
+        64 => 8,
+        _ => return None,
+    };
+    let addr = *ssa.operands_of(target).get(1)?;
+    if ssa.opcode(addr) != Some(MOpcode::OpAdd) {
+        return None;
+    }
+    let ops = ssa.operands_of(addr);
+    let (table, offset) = match (ssa.constant(*ops.get(0)?), ssa.constant(*ops.get(1)?)) {
+        (Some(table), None) => (table, ops[1]),
+        (None, Some(table)) => (table, ops[0]),
+        _ => return None,
+    };
+    match scaled_index(ssa, offset)? {
+        (index, scale) if scale == size => Some((table, index, size)),
+        _ => None,
+    }
+}
+
+/// Splits `offset` into `index * scale`, from a multiplication or a left shift by a constant.
+fn scaled_index(ssa: &SSAStorage, offset: NodeIndex) -> Option<(NodeIndex, u64)> {
+    let ops = ssa.operands_of(offset);
+    if ops.len() != 2 {
+        return None;
+    }
+    match (
+        ssa.opcode(offset)?,
+        ssa.constant(ops[0]),
+        ssa.constant(ops[1]),
+    ) {
+        (MOpcode::OpMul, None, Some(scale)) => Some((ops[0], scale)),
+        (MOpcode::OpMul, Some(scale), None) => Some((ops[1], scale)),
+        (MOpcode::OpLsl, None, Some(shift)) if shift < 64 => Some((ops[0], 1 << shift)),
+        _ => None,
+    }
+}
+
+/// The side of the nearest branch dominating `block` which doesn't lead to `block`.
+fn default_case(
+    ssa: &SSAStorage,
+    forest: &LoopForest<NodeIndex>,
+    block: NodeIndex,
+) -> Option<NodeIndex> {
+    let mut current = block;
+    loop {
+        let dom = forest.idom(current).filter(|&dom| dom != current)?;
+        if let Some(branches) = ssa.conditional_blocks(dom) {
+            let (t, f) = (branches.true_side, branches.false_side);
+            match (forest.dominates(t, block), forest.dominates(f, block)) {
+                (true, false) => return Some(f),
+                (false, true) => return Some(t),
+                _ => {}
+            }
+        }
+        current = dom;
+    }
+}
+
+impl Analyzer for JumpTables {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for JumpTables {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let source = rmod.source.clone()?;
+        for (&offset, rfn) in rmod.functions.iter_mut() {
+            let switches = {
+                let ssa = rfn.ssa();
+                let ranges = rfn
+                    .value_ranges()
+                    .cloned()
+                    .unwrap_or_else(|| ValueRanges::new(ssa));
+                let forest = LoopForest::new(ssa);
+                ssa.blocks()
+                    .into_iter()
+                    .filter_map(|b| self.resolve(&*source, ssa, &ranges, &forest, b))
+                    .collect::<Vec<_>>()
+            };
+            if switches.is_empty() {
+                continue;
+            }
+
+            {
+                let ssa = rfn.ssa_mut();
+                for switch in switches {
+                    radeco_trace!(
+                        "jumptables|{:?}: {} cases at {:#x}",
+                        switch.block,
+                        switch.table.cases.len(),
+                        switch.table.table
+                    );
+                    for &target in &switch.targets {
+                        ssa.insert_control_edge(switch.block, target, UNCOND_EDGE);
+                    }
+                    let succ = ssa.g.edge_endpoints(switch.placeholder).map(|(_, s)| s);
+                    ssa.remove_control_edge(switch.placeholder);
+                    // The placeholder only stands for the targets of this jump.
+                    if let Some(succ) = succ.filter(|&s| Some(s) != ssa.exit_node()) {
+                        ssa.remove_block(succ);
+                    }
+                    self.tables.push(JumpTable {
+                        function: offset,
+                        ..switch.table
+                    });
+                }
+            }
+            let ranges = ValueRanges::new(rfn.ssa());
+            rfn.set_value_ranges(ranges);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_support::{load_at, register_file, StubSource};
+    use std::rc::Rc;
+
+    const TABLE: [u64; 4] = [0x1010, 0x1020, 0x1030, 0xdead_beef];
+
+    /// Source with a `.rodata` section at 0x400100 holding a table of 4 entries, the last one
+    /// being past the end of the switch.
+    fn table_source() -> StubSource {
+        let bytes = TABLE
+            .iter()
+            .flat_map(|entry| (0..8).map(move |i| (entry >> (8 * i)) as u8))
+            .collect();
+        StubSource::new()
+            .with_sections(
+                r#"[{"flags":"-r--","name":".rodata","paddr":256,"size":32,"vaddr":4194560,"vsize":32}]"#,
+            )
+            .with_memory(0x400100, bytes)
+    }
+
+    // switch (rdi) { case 0: case 1: case 2: } with the default case at 0x1040.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SWITCH_SSA_TXT: &str = "\
+define-fun sym.switch(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown1 = %1 > #x2;
+        JMP IF %3 0x001040.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0xc):
+        [@0x001004.0001] %4: $Unknown64 = %1 * #x8;
+        [@0x001004.0002] %5: $Unknown64 = %4 + #x400100;
+        [@0x001004.0003] %6: $Unknown64 = Load(%2, %5);
+        JMP TO %6
+    bb_0x001010.0000(sz 0x1):
+        RETURN
+    bb_0x001020.0000(sz 0x1):
+        RETURN
+    bb_0x001030.0000(sz 0x1):
+        RETURN
+    bb_0x001040.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %1;
+        $mem = %2;
+}
+";
+
+    fn module(il: &str) -> RadecoModule {
+        let rfn = load_at(il, 0x1000, &register_file());
+
+        let mut rmod = RadecoModule::default();
+        rmod.functions.insert(0x1000, rfn);
+        rmod.source = Some(Rc::new(table_source()));
+        rmod
+    }
+
+    fn block_at(ssa: &SSAStorage, address: u64) -> NodeIndex {
+        ssa.blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b).map(|a| a.address) == Some(address))
+            .unwrap()
+    }
+
+    #[test]
+    fn bounded_switch() {
+        let mut rmod = module(SWITCH_SSA_TXT);
+        let mut jump_tables = JumpTables::new();
+        jump_tables.analyze(&mut rmod, None::<fn(_) -> _>);
+
+        // Only the 3 entries of the indices in `[0, 2]` are read.
+        assert_eq!(
+            jump_tables.tables(),
+            &[JumpTable {
+                function: 0x1000,
+                table: 0x400100,
+                index: Interval::new(0, 2),
+                cases: vec![0x1010, 0x1020, 0x1030],
+                default: Some(0x1040),
+            }]
+        );
+
+        let ssa = rmod.functions[&0x1000].ssa();
+        let jump = block_at(ssa, 0x1004);
+        let mut succs = ssa
+            .succs_of(jump)
+            .into_iter()
+            .map(|b| ssa.starting_address(b).unwrap().address)
+            .collect::<Vec<_>>();
+        succs.sort();
+        assert_eq!(succs, vec![0x1010, 0x1020, 0x1030]);
+    }
+
+    #[test]
+    fn unbounded_index_is_unresolved() {
+        // Without the bounds check, the index may take any value.
+        let il = SWITCH_SSA_TXT.replace(
+            "JMP IF %3 0x001040.0000 ELSE 0x001004.0000",
+            "JMP 0x001004.0000",
+        );
+        let mut rmod = module(&il);
+        let mut jump_tables = JumpTables::new();
+        jump_tables.analyze(&mut rmod, None::<fn(_) -> _>);
+
+        assert!(jump_tables.tables().is_empty());
+        let ssa = rmod.functions[&0x1000].ssa();
+        let jump = block_at(ssa, 0x1004);
+        assert_eq!(ssa.succs_of(jump), vec![ssa.exit_node().unwrap()]);
+    }
+}
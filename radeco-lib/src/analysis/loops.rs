@@ -0,0 +1,379 @@
+//! Loop detection over the control flow graph.
+//!
+//! Computes the dominators of the blocks of a `CFG` and, from them, its natural loops: a back
+//! edge is an edge whose target (the header) dominates its source (the latch), and the loop of a
+//! header is made of the blocks which reach one of its latches without going through the header.
+//! Loops sharing a header are merged, and the loops are nested into a forest according to their
+//! bodies.
+//!
+//! An edge going back to a block on the current DFS path whose target does not dominate its
+//! source is the entry of a loop with more than one entry, i.e. the CFG is irreducible. Such
+//! edges are reported by `LoopForest::irreducible_edges` and do not form loops.
+
+use crate::middle::ssa::cfg_traits::CFG;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+#[derive(Clone, Debug)]
+pub struct Loop<B> {
+    /// Block dominating every block of the loop, target of its back edges
+    pub header: B,
+    /// Sources of the back edges of the loop
+    pub latches: Vec<B>,
+    /// Blocks of the loop, including the header and the blocks of the nested loops
+    pub body: HashSet<B>,
+    /// Index in the forest of the innermost loop containing this one
+    pub parent: Option<usize>,
+    /// Indices in the forest of the loops directly nested in this one
+    pub children: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LoopForest<B> {
+    loops: Vec<Loop<B>>,
+    irreducible: Vec<(B, B)>,
+    idom: HashMap<B, B>,
+    rpo: Vec<B>,
+}
+
+impl<B> LoopForest<B>
+where
+    B: Copy + Eq + Hash + Debug,
+{
+    /// Computes the loops of `cfg`. Blocks unreachable from the entry node are ignored.
+    pub fn new<T: CFG<ActionRef = B>>(cfg: &T) -> LoopForest<B> {
+        let mut forest = LoopForest {
+            loops: Vec::new(),
+            irreducible: Vec::new(),
+            idom: HashMap::new(),
+            rpo: Vec::new(),
+        };
+        let entry = match cfg.entry_node() {
+            Some(entry) => entry,
+            None => return forest,
+        };
+
+        let (postorder, retreating) = dfs(cfg, entry);
+        forest.idom = dominators(cfg, entry, &postorder);
+
+        forest.rpo = postorder.iter().rev().cloned().collect();
+        let rpo_index = forest
+            .rpo
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| (b, i))
+            .collect::<HashMap<_, _>>();
+
+        // Back edges, grouped by header.
+        let mut latches: HashMap<B, Vec<B>> = HashMap::new();
+        for (latch, header) in retreating {
+            if forest.dominates(header, latch) {
+                latches.entry(header).or_insert_with(Vec::new).push(latch);
+            } else {
+                forest.irreducible.push((latch, header));
+            }
+        }
+
+        let mut headers = latches.keys().cloned().collect::<Vec<_>>();
+        headers.sort_by_key(|h| rpo_index[h]);
+        for header in headers {
+            let latches = latches.remove(&header).unwrap_or_default();
+            let body = natural_loop(cfg, header, &latches, &rpo_index);
+            forest.loops.push(Loop {
+                header,
+                latches,
+                body,
+                parent: None,
+                children: Vec::new(),
+            });
+        }
+
+        // Bodies of loops are either disjoint or nested, the parent of a loop is the smallest
+        // one containing its header.
+        for i in 0..forest.loops.len() {
+            let header = forest.loops[i].header;
+            let parent = (0..forest.loops.len())
+                .filter(|&j| j != i && forest.loops[j].body.contains(&header))
+                .min_by_key(|&j| forest.loops[j].body.len());
+            forest.loops[i].parent = parent;
+            if let Some(parent) = parent {
+                forest.loops[parent].children.push(i);
+            }
+        }
+
+        forest
+    }
+
+    /// All the loops of the CFG, ordered by the reverse postorder of their headers. Outer loops
+    /// come before the loops nested in them.
+    pub fn loops(&self) -> &[Loop<B>] {
+        &self.loops
+    }
+
+    /// Indices of the outermost loops.
+    pub fn roots(&self) -> Vec<usize> {
+        (0..self.loops.len())
+            .filter(|&i| self.loops[i].parent.is_none())
+            .collect()
+    }
+
+    /// Index of the innermost loop containing `block`, if any.
+    pub fn innermost_loop(&self, block: B) -> Option<usize> {
+        (0..self.loops.len())
+            .filter(|&i| self.loops[i].body.contains(&block))
+            .min_by_key(|&i| self.loops[i].body.len())
+    }
+
+    /// Returns `false` if the CFG has a loop with more than one entry.
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible.is_empty()
+    }
+
+    /// Edges, as `(source, target)`, entering a loop with more than one entry. These are not
+    /// back edges, as their target does not dominate their source.
+    pub fn irreducible_edges(&self) -> &[(B, B)] {
+        &self.irreducible
+    }
+
+    /// Blocks reachable from the entry node, in reverse postorder.
+    pub fn reverse_postorder(&self) -> &[B] {
+        &self.rpo
+    }
+
+    /// Immediate dominator of `block`. The entry node is its own immediate dominator.
+    pub fn idom(&self, block: B) -> Option<B> {
+        self.idom.get(&block).cloned()
+    }
+
+    /// Returns `true` if every path from the entry node to `b` goes through `a`.
+    pub fn dominates(&self, a: B, b: B) -> bool {
+        let mut current = b;
+        loop {
+            if current == a {
+                return true;
+            }
+            match self.idom.get(&current) {
+                Some(&idom) if idom != current => current = idom,
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// Iterative DFS from `entry`. Returns the blocks in postorder and the edges going back to a
+/// block on the current path, as `(source, target)`.
+fn dfs<T: CFG>(
+    cfg: &T,
+    entry: T::ActionRef,
+) -> (Vec<T::ActionRef>, Vec<(T::ActionRef, T::ActionRef)>) {
+    let mut postorder = Vec::new();
+    let mut retreating = Vec::new();
+    let mut visited = HashSet::new();
+    let mut on_path = HashSet::new();
+    let mut stack = vec![(entry, cfg.succs_of(entry), 0)];
+    visited.insert(entry);
+    on_path.insert(entry);
+
+    while let Some(&mut (block, ref succs, ref mut next)) = stack.last_mut() {
+        if let Some(&succ) = succs.get(*next) {
+            *next += 1;
+            if on_path.contains(&succ) {
+                retreating.push((block, succ));
+            } else if visited.insert(succ) {
+                on_path.insert(succ);
+                stack.push((succ, cfg.succs_of(succ), 0));
+            }
+        } else {
+            on_path.remove(&block);
+            postorder.push(block);
+            stack.pop();
+        }
+    }
+
+    (postorder, retreating)
+}
+
+/// Immediate dominators of the blocks in `postorder`, as computed by "A Simple, Fast Dominance
+/// Algorithm" (Cooper, Harvey and Kennedy).
+fn dominators<T: CFG>(
+    cfg: &T,
+    entry: T::ActionRef,
+    postorder: &[T::ActionRef],
+) -> HashMap<T::ActionRef, T::ActionRef> {
+    let po_index = postorder
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| (b, i))
+        .collect::<HashMap<_, _>>();
+    let mut idom = HashMap::new();
+    idom.insert(entry, entry);
+
+    let intersect =
+        |idom: &HashMap<T::ActionRef, T::ActionRef>, mut a: T::ActionRef, mut b: T::ActionRef| {
+            while a != b {
+                while po_index[&a] < po_index[&b] {
+                    a = idom[&a];
+                }
+                while po_index[&b] < po_index[&a] {
+                    b = idom[&b];
+                }
+            }
+            a
+        };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block in postorder.iter().rev().filter(|&&b| b != entry) {
+            let new_idom = cfg
+                .preds_of(block)
+                .into_iter()
+                .filter(|p| idom.contains_key(p))
+                .fold(None, |acc, p| match acc {
+                    None => Some(p),
+                    Some(acc) => Some(intersect(&idom, acc, p)),
+                });
+            if let Some(new_idom) = new_idom {
+                if idom.get(&block) != Some(&new_idom) {
+                    idom.insert(block, new_idom);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    idom
+}
+
+/// Blocks of the natural loop of `header`: the header and every block reaching one of `latches`
+/// without going through it.
+fn natural_loop<T: CFG>(
+    cfg: &T,
+    header: T::ActionRef,
+    latches: &[T::ActionRef],
+    reachable: &HashMap<T::ActionRef, usize>,
+) -> HashSet<T::ActionRef> {
+    let mut body = HashSet::new();
+    body.insert(header);
+    let mut worklist = latches.to_vec();
+    while let Some(block) = worklist.pop() {
+        if body.insert(block) {
+            worklist.extend(
+                cfg.preds_of(block)
+                    .into_iter()
+                    .filter(|p| reachable.contains_key(p)),
+            );
+        }
+    }
+    body
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir::MAddress;
+    use crate::middle::ssa::cfg_traits::CFGMod;
+    use crate::middle::ssa::ssastorage::SSAStorage;
+    use petgraph::graph::NodeIndex;
+
+    /// Builds a CFG with a block per address, the first one being the entry, and the given edges
+    /// between the blocks at these addresses.
+    fn cfg(addresses: &[u64], edges: &[(u64, u64)]) -> (SSAStorage, HashMap<u64, NodeIndex>) {
+        let mut ssa = SSAStorage::new();
+        let blocks = addresses
+            .iter()
+            .map(|&a| (a, ssa.insert_block(MAddress::new(a, 0)).unwrap()))
+            .collect::<HashMap<_, _>>();
+        ssa.set_entry_node(blocks[&addresses[0]]);
+        for (i, &(src, dst)) in edges.iter().enumerate() {
+            // Conditional edges are numbered in order, unconditional ones are `2`.
+            let branches = edges.iter().filter(|e| e.0 == src).count();
+            let index = if branches > 1 {
+                edges[..i].iter().filter(|e| e.0 == src).count() as u8
+            } else {
+                2
+            };
+            ssa.insert_control_edge(blocks[&src], blocks[&dst], index);
+        }
+        (ssa, blocks)
+    }
+
+    #[test]
+    fn while_loop() {
+        // 0x0: entry; 0x10: while (cond) { 0x20: body } 0x30: exit
+        let (ssa, b) = cfg(
+            &[0x0, 0x10, 0x20, 0x30],
+            &[(0x0, 0x10), (0x10, 0x20), (0x10, 0x30), (0x20, 0x10)],
+        );
+        let forest = LoopForest::new(&ssa);
+
+        assert!(forest.is_reducible());
+        assert_eq!(forest.loops().len(), 1);
+        let l = &forest.loops()[0];
+        assert_eq!(l.header, b[&0x10]);
+        assert_eq!(l.latches, vec![b[&0x20]]);
+        assert_eq!(l.body, [b[&0x10], b[&0x20]].iter().cloned().collect());
+        assert_eq!(forest.roots(), vec![0]);
+        assert_eq!(forest.innermost_loop(b[&0x20]), Some(0));
+        assert_eq!(forest.innermost_loop(b[&0x30]), None);
+
+        assert_eq!(forest.idom(b[&0x30]), Some(b[&0x10]));
+        assert!(forest.dominates(b[&0x10], b[&0x20]));
+        assert!(!forest.dominates(b[&0x20], b[&0x30]));
+    }
+
+    #[test]
+    fn nested_loops() {
+        // 0x10: outer header; 0x20: inner header; 0x30: inner latch; 0x40: outer latch
+        let (ssa, b) = cfg(
+            &[0x0, 0x10, 0x20, 0x30, 0x40, 0x50],
+            &[
+                (0x0, 0x10),
+                (0x10, 0x20),
+                (0x10, 0x50),
+                (0x20, 0x30),
+                (0x20, 0x40),
+                (0x30, 0x20),
+                (0x40, 0x10),
+            ],
+        );
+        let forest = LoopForest::new(&ssa);
+
+        assert!(forest.is_reducible());
+        let loops = forest.loops();
+        assert_eq!(loops.len(), 2);
+        assert_eq!(loops[0].header, b[&0x10]);
+        assert_eq!(loops[0].body.len(), 4);
+        assert_eq!(loops[0].children, vec![1]);
+        assert_eq!(loops[1].header, b[&0x20]);
+        assert_eq!(
+            loops[1].body,
+            [b[&0x20], b[&0x30]].iter().cloned().collect()
+        );
+        assert_eq!(loops[1].parent, Some(0));
+        assert_eq!(forest.roots(), vec![0]);
+        assert_eq!(forest.innermost_loop(b[&0x30]), Some(1));
+        assert_eq!(forest.innermost_loop(b[&0x40]), Some(0));
+    }
+
+    #[test]
+    fn irreducible_two_entry_loop() {
+        // The cycle between 0x10 and 0x20 can be entered through either block.
+        let (ssa, b) = cfg(
+            &[0x0, 0x10, 0x20],
+            &[(0x0, 0x10), (0x0, 0x20), (0x10, 0x20), (0x20, 0x10)],
+        );
+        let forest = LoopForest::new(&ssa);
+
+        assert!(!forest.is_reducible());
+        assert!(forest.loops().is_empty());
+        let edges = forest.irreducible_edges();
+        assert_eq!(edges.len(), 1);
+        let cycle = [(b[&0x10], b[&0x20]), (b[&0x20], b[&0x10])];
+        assert!(cycle.contains(&edges[0]));
+        assert_eq!(forest.idom(b[&0x10]), Some(b[&0x0]));
+        assert_eq!(forest.idom(b[&0x20]), Some(b[&0x0]));
+    }
+}
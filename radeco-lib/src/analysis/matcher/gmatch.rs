@@ -223,10 +223,11 @@ where
                     MOpcode::OpStore => "OpStore".to_owned(),
                     MOpcode::OpNarrow(w) => format!("OpNarrow{}", w),
                     MOpcode::OpZeroExt(w) => format!("OpZeroExt{}", w),
+                    MOpcode::OpByteSwap(w) => format!("OpByteSwap{}", w),
                     MOpcode::OpCall => "OpCall".to_owned(),
                     _ => unreachable!(),
                 });
-            } else if let NodeType::Comment(s) = node_data.nt {
+            } else if let NodeType::Comment(s) | NodeType::Argument(s) = node_data.nt {
                 result.push_str(&s);
             }
         }
@@ -357,6 +358,10 @@ where
             Some(MOpcode::OpZeroExt(
                 u16::from_str_radix(&t[7..], 10).expect("Invalid decimal integer"),
             ))
+        } else if t.starts_with("OpByteSwap") {
+            Some(MOpcode::OpByteSwap(
+                u16::from_str_radix(&t[10..], 10).expect("Invalid decimal integer"),
+            ))
         } else {
             match t {
                 "OpAdd" => Some(MOpcode::OpAdd),
@@ -0,0 +1,216 @@
+//! Removes the phis of memory states which merge a single memory state.
+//!
+//! The memory is a single SSA variable, so every join point gets a memory phi as soon as its
+//! predecessors reach it through different nodes, even when none of the paths wrote memory since
+//! their common dominator. These nodes can be phis themselves (e.g. of a loop which doesn't store
+//! anything), or values left behind by other passes, like the stores removed by `SpillForward`.
+//!
+//! The memory phis are followed through their operands which are memory phis as well. If they
+//! only lead to one other memory state, every phi on the way is that state: the phi is replaced
+//! with it. The phis of the registers are left alone.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+    ReplaceValue,
+};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSAWalk, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+use std::collections::HashSet;
+
+const NAME: &str = "memphielim";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::MemPhiElim,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+#[derive(Debug)]
+pub struct MemPhiElim {}
+
+/// Returns `true` if `node` is a phi of memory states, which are 0 bits wide.
+fn is_mem_phi(ssa: &SSAStorage, node: NodeIndex) -> bool {
+    ssa.is_phi(node)
+        && ssa
+            .node_data(node)
+            .map_or(false, |nd| nd.vt.width().get_width() == Some(0))
+}
+
+impl MemPhiElim {
+    pub fn new() -> MemPhiElim {
+        MemPhiElim {}
+    }
+
+    /// Returns the only memory state merged by `phi`, through other memory phis, if any.
+    fn merged_state(ssa: &SSAStorage, phi: NodeIndex) -> Option<NodeIndex> {
+        let mut state = None;
+        let mut visited = HashSet::new();
+        let mut worklist = vec![phi];
+        while let Some(node) = worklist.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+            for op in ssa.operands_of(node) {
+                if is_mem_phi(ssa, op) {
+                    worklist.push(op);
+                } else if state.map_or(false, |s| s != op) {
+                    return None;
+                } else {
+                    state = Some(op);
+                }
+            }
+        }
+        state
+    }
+
+    fn gather_phis(ssa: &SSAStorage) -> Vec<NodeIndex> {
+        ssa.inorder_walk().filter(|&n| is_mem_phi(ssa, n)).collect()
+    }
+}
+
+impl Analyzer for MemPhiElim {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for MemPhiElim {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        func: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        let ssa = func.ssa_mut();
+
+        for phi in MemPhiElim::gather_phis(ssa) {
+            // A previous replacement may have removed this phi.
+            if !is_mem_phi(ssa, phi) {
+                continue;
+            }
+            let state = match MemPhiElim::merged_state(ssa, phi) {
+                Some(state) => state,
+                None => continue,
+            };
+            match policy(Box::new(ReplaceValue(state, phi))) {
+                Action::Apply => {
+                    radeco_trace!("memphielim|{:?} -> {:?}", phi, state);
+                    ssa.replace_value(phi, state);
+                }
+                Action::Skip => (),
+                Action::Abort => break,
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ir::MOpcode;
+    use crate::middle::ssa::cfg_traits::CFG;
+    use crate::utils::test_support::load;
+
+    // if (rdi == rsi) { rax = rdi - rsi; }, the memory reaches the join through a phi of its own
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const NO_STORE_SSA_TXT: &str = "\
+define-fun sym.no_store(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown1 = %1 == %2;
+        JMP IF %4 0x001008.0000 ELSE 0x001010.0000
+    bb_0x001008.0000(sz 0x8):
+        %5: $Unknown0 = Phi(%3);
+        [@0x001008.0001] %6: $Unknown64 = %1 - %2;
+        JMP 0x001010.0000
+    bb_0x001010.0000(sz 0x1):
+        %7: $Unknown64 = Phi(%6, %1);
+        %8: $Unknown0 = Phi(%5, %3);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $mem = %8;
+}
+";
+
+    // Same, but the memory is written when rdi == rsi.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const STORE_SSA_TXT: &str = "\
+define-fun sym.store(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown1 = %1 == %2;
+        JMP IF %4 0x001008.0000 ELSE 0x001010.0000
+    bb_0x001008.0000(sz 0x8):
+        [@0x001008.0001] %5: $Unknown0 = Store(%3, %1, %2);
+        JMP 0x001010.0000
+    bb_0x001010.0000(sz 0x1):
+        %6: $Unknown0 = Phi(%5, %3);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %6;
+}
+";
+
+    /// Returns the memory phis of the block at `address`.
+    fn mem_phis_at(rfn: &RadecoFunction, address: u64) -> Vec<NodeIndex> {
+        let ssa = rfn.ssa();
+        let block = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b).map(|a| a.address) == Some(address))
+            .unwrap();
+        ssa.phis_in(block)
+            .into_iter()
+            .filter(|&phi| is_mem_phi(ssa, phi))
+            .collect()
+    }
+
+    #[test]
+    fn unwritten_memory_join() {
+        let mut rfn = load(NO_STORE_SSA_TXT);
+        assert_eq!(mem_phis_at(&rfn, 0x1010).len(), 1);
+        MemPhiElim::new().analyze(&mut rfn, Some(all));
+
+        assert!(mem_phis_at(&rfn, 0x1008).is_empty());
+        assert!(mem_phis_at(&rfn, 0x1010).is_empty());
+        // The phi of rax is kept.
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.inorder_walk().filter(|&n| ssa.is_phi(n)).count(), 1);
+    }
+
+    #[test]
+    fn written_memory_join() {
+        let mut rfn = load(STORE_SSA_TXT);
+        MemPhiElim::new().analyze(&mut rfn, Some(all));
+
+        let phis = mem_phis_at(&rfn, 0x1010);
+        assert_eq!(phis.len(), 1);
+        let ssa = rfn.ssa();
+        assert!(ssa
+            .operands_of(phis[0])
+            .iter()
+            .any(|&op| ssa.opcode(op) == Some(MOpcode::OpStore)));
+    }
+}
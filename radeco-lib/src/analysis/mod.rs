@@ -7,6 +7,7 @@
 
 //! Module that implements analysis and optimizations on radeco IR.
 
+pub mod alias;
 pub mod analyzer;
 pub mod engine;
 
@@ -28,12 +29,28 @@ pub mod matcher {
 }
 
 pub mod arithmetic;
+pub mod bool_ext;
+pub mod byteswap;
 pub mod constraint_set;
 pub mod copy_propagation;
+pub mod flag_fold;
 pub mod functions;
+pub mod globals;
 pub mod inst_combine;
 pub mod interproc;
+pub mod intrinsics;
+pub mod jump_table;
+pub mod loops;
 pub mod mask2narrow;
+pub mod mem_phi;
 pub mod reference_marking;
+pub mod rodata;
+pub mod simplify;
+pub mod simplify_flags;
+pub mod spill;
+pub mod stackvars;
 pub mod tie;
+pub mod uninit;
+pub mod variables;
+pub mod vrange;
 pub mod vsa;
@@ -88,7 +88,11 @@ impl ReferenceMarker {
                             // Setup a union constraint
                             self.cs.add_union(idx, operands.as_slice());
                             // Check if they of the operands are comments
-                            comment_nodes.extend(operands.iter().filter(|&&x| ssa.is_comment(x)));
+                            comment_nodes.extend(
+                                operands
+                                    .iter()
+                                    .filter(|&&x| ssa.is_comment(x) || ssa.is_argument(x)),
+                            );
                             for (cidx, cval) in operands
                                 .iter()
                                 .filter_map(|&x| ssa.constant_value(x).map(|v| (x, v)))
@@ -145,11 +149,18 @@ impl ReferenceMarker {
                             // later on.
                             // In case of load, nothing can be said about the returned value.
                         }
-                        &MOpcode::OpCustom(_) | &MOpcode::OpInvalid | &MOpcode::OpNop => {
+                        &MOpcode::OpCustom(_)
+                        | &MOpcode::OpUnsupported(_)
+                        | &MOpcode::OpInvalid
+                        | &MOpcode::OpNop => {
                             // Can't say anything about these operands
                             let operands = ssa.operands_of(idx);
                             self.cs.add_union(idx, operands.as_slice());
-                            comment_nodes.extend(operands.iter().filter(|&&x| ssa.is_comment(x)));
+                            comment_nodes.extend(
+                                operands
+                                    .iter()
+                                    .filter(|&&x| ssa.is_comment(x) || ssa.is_argument(x)),
+                            );
                         }
                         _ => {
                             // Nothing to do
@@ -163,7 +174,11 @@ impl ReferenceMarker {
                     // The phi node should also be equal to all its operands.
                     operands.push(idx);
                     self.cs.add_equivalence_assertion(operands.as_slice());
-                    comment_nodes.extend(operands.iter().filter(|&&x| ssa.is_comment(x)));
+                    comment_nodes.extend(
+                        operands
+                            .iter()
+                            .filter(|&&x| ssa.is_comment(x) || ssa.is_argument(x)),
+                    );
                 }
                 _ => {
                     // This is unreachable!
@@ -175,7 +190,7 @@ impl ReferenceMarker {
         for idx in comment_nodes {
             let nd = ssa.node_data(idx);
             match nd.unwrap().nt {
-                NodeType::Comment(ref comm) => {
+                NodeType::Comment(ref comm) | NodeType::Argument(ref comm) => {
                     // Generate equality constraint if the comment is the stack pointer
                     if let Some(sp_reg) = self.regfile.alias_info.get("SP") {
                         if comm == sp_reg {
@@ -0,0 +1,233 @@
+//! Folds the loads from constant addresses of read-only sections.
+//!
+//! The bytes of a section which is mapped without write permission, e.g. `.rodata`, can't change
+//! while the program runs, so a load from a constant address inside such a section always loads
+//! the bytes the `Source` has at that address. The load is replaced by a constant of its width,
+//! which `SCCP` can fold further.
+//!
+//! Loads from writable sections, or from sections whose permissions are unknown, are never
+//! folded.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::frontend::radeco_containers::RadecoModule;
+use crate::frontend::radeco_source::Source;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+
+const NAME: &str = "rodatafold";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::RodataFold,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Byte order of the values in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    /// Assembles `bytes`, read from memory, into a value.
+    pub fn value_of(self, bytes: &[u8]) -> u64 {
+        let fold = |value, &byte| (value << 8) | u64::from(byte);
+        match self {
+            Endian::Little => bytes.iter().rev().fold(0, fold),
+            Endian::Big => bytes.iter().fold(0, fold),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RodataFold {
+    endian: Endian,
+    /// (function offset, address, value) for every folded load
+    folded: Vec<(u64, u64, u64)>,
+}
+
+impl RodataFold {
+    /// Folds the loads as little endian values.
+    pub fn new() -> Self {
+        RodataFold::with_endian(Endian::Little)
+    }
+
+    pub fn with_endian(endian: Endian) -> Self {
+        RodataFold {
+            endian,
+            folded: Vec::new(),
+        }
+    }
+
+    /// Loads replaced by a constant, as (function offset, address, value).
+    pub fn folded(&self) -> &[(u64, u64, u64)] {
+        &self.folded
+    }
+}
+
+/// Returns `true` if the `len` bytes at `address` are all in sections which are known not to be
+/// writable.
+pub fn is_read_only(source: &dyn Source, address: u64, len: u64) -> bool {
+    let last = match address.checked_add(len - 1) {
+        Some(last) => last,
+        None => return false,
+    };
+    [address, last].iter().all(|&addr| {
+        source
+            .section_of(addr)
+            .ok()
+            .and_then(|s| s.flags)
+            .map_or(false, |f| f.contains('r') && !f.contains('w'))
+    })
+}
+
+/// Returns the constant address and the width, in bits, of the load `node`.
+fn constant_load(ssa: &SSAStorage, node: NodeIndex) -> Option<(u64, u16)> {
+    if ssa.opcode(node) != Some(MOpcode::OpLoad) {
+        return None;
+    }
+    let address = ssa.constant(*ssa.operands_of(node).get(1)?)?;
+    match ssa.node_data(node).ok()?.vt.width().get_width()? {
+        width @ 8 | width @ 16 | width @ 32 | width @ 64 => Some((address, width)),
+        _ => None,
+    }
+}
+
+impl Analyzer for RodataFold {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for RodataFold {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let source = rmod.source.clone()?;
+        for (&offset, rfn) in rmod.functions.iter_mut() {
+            let ssa = rfn.ssa_mut();
+            let loads = ssa
+                .values()
+                .into_iter()
+                .filter_map(|n| constant_load(ssa, n).map(|(addr, width)| (n, addr, width)))
+                .collect::<Vec<_>>();
+            for (load, address, width) in loads {
+                let len = u64::from(width / 8);
+                if !is_read_only(&*source, address, len) {
+                    continue;
+                }
+                let bytes = match source.read_bytes(address, len as usize) {
+                    Ok(bytes) => bytes,
+                    Err(_) => continue,
+                };
+                if bytes.len() as u64 != len {
+                    continue;
+                }
+                let value = self.endian.value_of(&bytes);
+                if let Some(cnode) = ssa.insert_const(value, Some(width)) {
+                    radeco_trace!("rodatafold|{:?}: [{:#x}] = {:#x}", load, address, value);
+                    ssa.replace_value(load, cnode);
+                    self.folded.push((offset, address, value));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ssa::cfg_traits::CFG;
+    use crate::utils::test_support::{load_at, register_file, StubSource};
+    use std::rc::Rc;
+
+    const BYTES: [u8; 8] = [0x44, 0x33, 0x22, 0x11, 0x88, 0x77, 0x66, 0x55];
+
+    /// Source with a `.rodata` section at 0x400100 and a `.data` section at 0x601000, both holding
+    /// `BYTES`.
+    fn section_source() -> StubSource {
+        StubSource::new()
+            .with_sections(
+                r#"[{"flags":"-r--","name":".rodata","paddr":256,"size":8,"vaddr":4194560,"vsize":8},
+                    {"flags":"-rw-","name":".data","paddr":4096,"size":8,"vaddr":6295552,"vsize":8}]"#,
+            )
+            .with_memory(0x400100, BYTES.to_vec())
+            .with_memory(0x601000, BYTES.to_vec())
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const LOADS_SSA_TXT: &str = "\
+define-fun sym.loads(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %2: $Unknown32 = Load(%1, #x400100);
+        [@0x001004.0001] %3: $Unknown16 = Load(%1, #x400104);
+        [@0x001008.0001] %4: $Unknown32 = Load(%1, #x601000);
+        RETURN
+    exit-node:
+    final-register-state:
+        $eax = %2;
+        $cx = %3;
+        $edx = %4;
+        $mem = %1;
+}
+";
+
+    #[test]
+    fn fold_rodata_loads() {
+        let rfn = load_at(LOADS_SSA_TXT, 0x1000, &register_file());
+
+        let mut rmod = RadecoModule::default();
+        rmod.functions.insert(0x1000, rfn);
+        rmod.source = Some(Rc::new(section_source()));
+
+        let mut fold = RodataFold::new();
+        fold.analyze(&mut rmod, None::<fn(_) -> _>);
+        assert_eq!(
+            fold.folded(),
+            &[(0x1000, 0x400100, 0x1122_3344), (0x1000, 0x400104, 0x7788)]
+        );
+
+        let ssa = rmod.functions[&0x1000].ssa();
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let operands = ssa.operands_of(exit);
+        let consts = operands
+            .iter()
+            .filter_map(|&n| ssa.constant(n))
+            .collect::<Vec<_>>();
+        assert!(consts.contains(&0x1122_3344));
+        assert!(consts.contains(&0x7788));
+        // The load from `.data` is kept.
+        let loads = operands
+            .iter()
+            .filter(|&&n| ssa.opcode(n) == Some(MOpcode::OpLoad))
+            .count();
+        assert_eq!(loads, 1);
+    }
+
+    #[test]
+    fn endianness() {
+        let bytes = [0x11, 0x22, 0x33, 0x44];
+        assert_eq!(Endian::Little.value_of(&bytes), 0x4433_2211);
+        assert_eq!(Endian::Big.value_of(&bytes), 0x1122_3344);
+    }
+}
@@ -15,17 +15,19 @@
 use crate::analysis::analyzer::{
     Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
 };
+use crate::analysis::functions::purity;
 use crate::frontend::radeco_containers::RadecoFunction;
-use crate::middle::ir::{MArity, MOpcode, WidthSpec};
+use crate::middle::ir::{self, MArity, MOpcode, WidthSpec};
 use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
 use crate::middle::ssa::graph_traits::{ConditionInfo, Graph};
 use crate::middle::ssa::ssa_traits::{NodeData, NodeType, ValueInfo, ValueType};
-use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssa_traits::{SSAMod, SSAWalk, SSA};
 use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
 
 use std::any::Any;
 use std::collections::{HashMap, VecDeque};
-use std::u64;
+use std::{u64, usize};
 
 #[macro_export]
 macro_rules! node_data_from_g {
@@ -103,6 +105,9 @@ pub struct SCCP {
     cfg_worklist: VecDeque<<SSAStorage as CFG>::CFEdgeRef>,
     executable: HashMap<<SSAStorage as CFG>::CFEdgeRef, bool>,
     expr_val: HashMap<<SSAStorage as SSA>::ValueRef, LatticeValue>,
+    /// Index of every reachable block in the reverse postorder of the CFG
+    rpo_index: HashMap<<SSAStorage as CFG>::ActionRef, usize>,
+    iterations: u32,
 }
 
 impl SCCP {
@@ -112,6 +117,26 @@ impl SCCP {
             cfg_worklist: VecDeque::new(),
             executable: HashMap::new(),
             expr_val: HashMap::new(),
+            rpo_index: HashMap::new(),
+            iterations: 0,
+        }
+    }
+
+    /// Lets the values preserved by the callees, according to the usages recorded in `rfn`,
+    /// flow across the calls.
+    fn bridge_calls(&self, rfn: &mut RadecoFunction) {
+        let calls = {
+            let ssa = rfn.ssa();
+            ssa.inorder_walk()
+                .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpCall))
+                .filter_map(|n| {
+                    let csite = ssa.address(n)?.address;
+                    Some((n, rfn.callee_usage(csite)?.clone()))
+                })
+                .collect::<Vec<_>>()
+        };
+        for (call, usage) in calls {
+            purity::bridge_preserved(rfn.ssa_mut(), call, &usage);
         }
     }
 
@@ -120,7 +145,6 @@ impl SCCP {
     }
 
     fn visit_phi(&mut self, g: &SSAStorage, i: &<SSAStorage as SSA>::ValueRef) -> LatticeValue {
-        let operands = g.operands_of(*i);
         let mut phi_val = self.get_value(g, i);
 
         // If "overdefined" return it.
@@ -130,9 +154,8 @@ impl SCCP {
 
         let invalid_block = g.invalid_action().expect("Invalid Action is not defind");
         let parent_block = g.block_for(*i).unwrap_or(invalid_block);
-        for op in &operands {
-            let operand_block = g.block_for(*op).unwrap_or(invalid_block);
-            let op_val = self.get_value(g, op);
+        for (op, preds) in utils::phi_sources(*i, g) {
+            let op_val = self.get_value(g, &op);
 
             if op_val.is_undefined() {
                 continue;
@@ -144,13 +167,25 @@ impl SCCP {
             // Only operand which could be executable will be considered.
             // Even these operands are overdefined.
 
-            let edge = g.find_edges_between(operand_block, parent_block);
-            if edge.len() == 0 {
+            // The operand flows in along the edges from its predecessors, which aren't the block
+            // defining it when it is defined further up, e.g. in a loop closed by a jump block.
+            // Without known predecessors, it is the defining block.
+            let sources = if preds.is_empty() {
+                vec![g.block_for(op).unwrap_or(invalid_block)]
+            } else {
+                preds
+            };
+            let edges = sources
+                .into_iter()
+                .flat_map(|pred| g.find_edges_between(pred, parent_block))
+                .collect::<Vec<_>>();
+            if edges.is_empty() {
                 continue;
             }
-            assert_eq!(edge.len(), 1);
-            if !self.is_executable(&edge[0])
-                && edge[0] != g.invalid_edge().expect("Invalid Edge is not defined")
+            let invalid_edge = g.invalid_edge().expect("Invalid Edge is not defined");
+            if !edges
+                .iter()
+                .any(|edge| self.is_executable(edge) || *edge == invalid_edge)
             {
                 continue;
             }
@@ -230,6 +265,7 @@ impl SCCP {
             }
             MOpcode::OpMov => const_val as u64,
             MOpcode::OpNot => !const_val as u64,
+            MOpcode::OpByteSwap(width) => ir::byte_swap(const_val, width),
             MOpcode::OpCall => {
                 return LatticeValue::Bottom;
             }
@@ -298,10 +334,12 @@ impl SCCP {
                     (u64::MAX - rhs_val + lhs_val + 1) as u64
                 }
             }
-            MOpcode::OpMul => {
-                // TODO: how to handle integer overflow.
-                lhs_val * rhs_val
-            }
+            // Wraps around on overflow, like the machine multiplication does.
+            MOpcode::OpMul => lhs_val.wrapping_mul(rhs_val),
+            // A division by zero traps instead of producing a value, so there is nothing to fold
+            // it to. It is overdefined, which leaves the operation in place. The division is
+            // unsigned, so it can't overflow otherwise.
+            MOpcode::OpDiv | MOpcode::OpMod if rhs_val == 0 => return LatticeValue::Bottom,
             MOpcode::OpDiv => lhs_val / rhs_val,
             MOpcode::OpMod => lhs_val % rhs_val,
             MOpcode::OpAnd => lhs_val & rhs_val,
@@ -330,9 +368,15 @@ impl SCCP {
         _i: &<SSAStorage as SSA>::ValueRef,
         opcode: MOpcode,
     ) -> LatticeValue {
-        // Do not reason about stores.
+        // Do not reason about stores, nor about the string operations, traps or the ESIL which
+        // couldn't be lifted.
         match opcode {
-            MOpcode::OpStore => return LatticeValue::Bottom,
+            MOpcode::OpStore
+            | MOpcode::OpRepMovs(_)
+            | MOpcode::OpRepScas(_)
+            | MOpcode::OpRepStos(_)
+            | MOpcode::OpTrap
+            | MOpcode::OpUnsupported(_) => return LatticeValue::Bottom,
             _ => unimplemented!(),
         }
     }
@@ -447,6 +491,17 @@ impl SCCP {
     fn cfgwl_push(&mut self, i: &<SSAStorage as CFG>::CFEdgeRef) {
         self.cfg_worklist.push_back(*i);
     }
+
+    /// Pops the pending edge whose target comes first in reverse postorder, so that the blocks
+    /// are visited after their predecessors, except along back edges.
+    fn cfgwl_pop(&mut self, g: &SSAStorage) -> Option<<SSAStorage as CFG>::CFEdgeRef> {
+        let next = (0..self.cfg_worklist.len()).min_by_key(|&i| {
+            g.edge_info(self.cfg_worklist[i])
+                .and_then(|info| self.rpo_index.get(&info.target).cloned())
+                .unwrap_or(usize::MAX)
+        })?;
+        self.cfg_worklist.remove(next)
+    }
 }
 
 impl Analyzer for SCCP {
@@ -464,7 +519,14 @@ impl FuncAnalyzer for SCCP {
         rfn: &mut RadecoFunction,
         _policy: Option<T>,
     ) -> Option<Box<dyn AnalyzerResult>> {
+        self.bridge_calls(rfn);
         let mut g = rfn.ssa_mut();
+        self.rpo_index = g
+            .reverse_postorder()
+            .into_iter()
+            .enumerate()
+            .map(|(i, block)| (block, i))
+            .collect();
 
         {
             let entry_node = entry_node_err!(g);
@@ -478,7 +540,8 @@ impl FuncAnalyzer for SCCP {
         }
 
         while !self.ssa_worklist.is_empty() || !self.cfg_worklist.is_empty() {
-            while let Some(edge) = self.cfg_worklist.pop_front() {
+            self.iterations += 1;
+            while let Some(edge) = self.cfgwl_pop(&g) {
                 if !self.is_executable(&edge) {
                     self.mark_executable(&edge);
                     let block = g
@@ -584,11 +647,108 @@ impl FuncAnalyzer for SCCP {
 
         None
     }
+
+    fn iterations_used(&self) -> Option<u32> {
+        Some(self.iterations)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{meet, LatticeValue};
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ssa::utils;
+    use crate::utils::test_support::load;
+    use petgraph::graph::NodeIndex;
+
+    // mov rbx, 0x30; call 0x2000; lea rax, [rbx + 1]
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALL_SSA_TXT: &str = "\
+define-fun sym.caller(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %3: $Unknown64 = #x10 + #x20;
+        [@0x001004.0000] (%4: $Unknown64 = $rbx, %5: $Unknown0 = $mem) = CALL #x2000($rdi=%1, $rbx=%3, $mem=%2);
+        [@0x001009.0001] %6: $Unknown64 = %4 + #x1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $mem = %5;
+}
+";
+
+    fn exit_value(ssa: &SSAStorage, reg: &str) -> NodeIndex {
+        let regid = match reg {
+            "mem" => ssa.regfile.mem_id(),
+            _ => ssa.regfile.register_id_by_name(reg).unwrap(),
+        };
+        let exit_state = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        utils::register_state_info(exit_state, ssa)[regid].0
+    }
+
+    #[test]
+    fn clobbered_by_default() {
+        let mut rfn = load(CALL_SSA_TXT);
+        SCCP::new().analyze(&mut rfn, Some(all));
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.constant_value(exit_value(ssa, "rax")), None);
+    }
+
+    #[test]
+    fn survives_pure_call() {
+        let mut rfn = load(CALL_SSA_TXT);
+        let (mem, usage) = {
+            let ssa = rfn.ssa();
+            let mut usage = ssa.regfile.new_register_usage();
+            usage.set_preserved(ssa.regfile.register_id_by_name("rbx").unwrap());
+            usage.set_preserved(ssa.regfile.mem_id());
+            let entry_state = ssa.registers_in(ssa.entry_node().unwrap()).unwrap();
+            let mem = utils::register_state_info(entry_state, ssa)[ssa.regfile.mem_id()].0;
+            (mem, usage)
+        };
+        rfn.set_callee_usage(0x1004, usage);
+        SCCP::new().analyze(&mut rfn, Some(all));
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.constant_value(exit_value(ssa, "rax")), Some(0x31));
+        assert_eq!(exit_value(ssa, "mem"), mem);
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const DIV_SSA_TXT: &str = "\
+define-fun sym.div(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %2: $Unknown64 = #x10 / #x0;
+        [@0x001004.0001] %3: $Unknown64 = #x10 % #x0;
+        [@0x001008.0001] %4: $Unknown64 = #xffffffffffffffff * #x2;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %2;
+        $rdx = %3;
+        $rcx = %4;
+        $mem = %1;
+}
+";
+
+    #[test]
+    fn division_by_zero_unfolded() {
+        let mut rfn = load(DIV_SSA_TXT);
+        SCCP::new().analyze(&mut rfn, Some(all));
+        let ssa = rfn.ssa();
+        let (div, rem) = (exit_value(ssa, "rax"), exit_value(ssa, "rdx"));
+        assert_eq!(ssa.constant_value(div), None);
+        assert_eq!(ssa.opcode(div), Some(MOpcode::OpDiv));
+        assert_eq!(ssa.constant_value(rem), None);
+        assert_eq!(ssa.opcode(rem), Some(MOpcode::OpMod));
+        // The multiplication wraps around.
+        let mul = exit_value(ssa, "rcx");
+        assert_eq!(ssa.constant_value(mul), Some(0xffff_ffff_ffff_fffe));
+    }
 
     #[test]
     fn test_meet() {
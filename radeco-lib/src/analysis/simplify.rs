@@ -0,0 +1,407 @@
+//! Peephole simplification of algebraic identities.
+//!
+//! Rewrites operations which are known to yield one of their operands, or zero, regardless of
+//! the value of the other operand:
+//!
+//! | Operation            | Simplified to |
+//! |----------------------|---------------|
+//! | `x + 0`, `x - 0`     | `x`           |
+//! | `x * 1`              | `x`           |
+//! | `x * 0`, `x & 0`     | `0`           |
+//! | `x & x`, `x | x`     | `x`           |
+//! | `x ^ x`              | `0`           |
+//! | `x << 0`, `x >> 0`   | `x`           |
+//!
+//! An operation is only replaced by one of its operands if both have the same width, and only
+//! replaced by zero if its width is known.
+//!
+//! Chains of casts are collapsed into a single cast of the innermost value `x`, of `w` bits:
+//!
+//! | Operation                   | Simplified to                                            |
+//! |-----------------------------|----------------------------------------------------------|
+//! | `Narrow(n)(ZeroExt(m)(x))`  | `x` if `w == n`, else `ZeroExt(n)(x)` or `Narrow(n)(x)`  |
+//! | `Narrow(n)(SignExt(m)(x))`  | `x` if `w == n`, else `SignExt(n)(x)` or `Narrow(n)(x)`  |
+//! | `Narrow(n)(Narrow(m)(x))`   | `Narrow(n)(x)`                                           |
+//! | `ZeroExt(n)(ZeroExt(m)(x))` | `ZeroExt(n)(x)`                                          |
+//! | `SignExt(n)(SignExt(m)(x))` | `SignExt(n)(x)`                                          |
+//! | `SignExt(n)(ZeroExt(m)(x))` | `ZeroExt(n)(x)` if `w < m`                               |
+//!
+//! An extension of a narrowed value is kept as is, since the narrowing may have dropped bits.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::{MOpcode, WidthSpec};
+use crate::middle::ssa::ssa_traits::*;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use std::any::Any;
+use std::cmp::Ordering;
+
+type SSAValue = <SSAStorage as SSA>::ValueRef;
+
+/// The value an operation is simplified to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Simplified {
+    /// One of the operands of the operation.
+    Operand(SSAValue),
+    /// A zero of the same width as the operation.
+    Zero,
+    /// A single cast, with the given opcode, of a value.
+    Cast(MOpcode, SSAValue),
+}
+
+#[derive(Debug)]
+pub struct SimplifyChange {
+    /// The simplified operation.
+    pub node: SSAValue,
+    /// The value replacing `node`.
+    pub simplified: Simplified,
+}
+
+impl Change for SimplifyChange {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+const NAME: &str = "simplify";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::Simplify,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+#[derive(Debug)]
+pub struct Simplify;
+
+impl Simplify {
+    pub fn new() -> Self {
+        Simplify
+    }
+}
+
+impl Analyzer for Simplify {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for Simplify {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        func: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let ssa = func.ssa_mut();
+        let mut policy = policy.expect("A policy function must be provided");
+        for node in ssa.inorder_walk() {
+            let (simplified, width) = match simplify(ssa, node) {
+                Some(res) => res,
+                None => continue,
+            };
+
+            let change = SimplifyChange {
+                node,
+                simplified: simplified.clone(),
+            };
+            match policy(Box::new(change)) {
+                Action::Apply => {}
+                Action::Skip => continue,
+                Action::Abort => break,
+            }
+
+            radeco_trace!("simplify|{:?} ==> {:?}", node, simplified);
+            let repl_node = match simplified {
+                Simplified::Operand(operand) => operand,
+                Simplified::Zero => match ssa.insert_const(0, Some(width)) {
+                    Some(zero) => zero,
+                    None => continue,
+                },
+                Simplified::Cast(opcode, operand) => {
+                    // Rewrite the outer cast in place, the inner one is left to DCE.
+                    for inner in ssa.operands_of(node) {
+                        ssa.op_unuse(node, inner);
+                    }
+                    ssa.op_use(node, 0, operand);
+                    ssa.set_opcode(node, opcode);
+                    continue;
+                }
+            };
+            ssa.replace_value(node, repl_node);
+        }
+
+        None
+    }
+}
+
+/// Returns the value `node` can be replaced with, along with the width of `node`.
+fn simplify(ssa: &SSAStorage, node: SSAValue) -> Option<(Simplified, u16)> {
+    let opcode = ssa.opcode(node)?;
+    let width = ssa.node_data(node).ok()?.vt.width().get_width()?;
+    let operands = ssa.operands_of(node);
+    let simplified = match operands.len() {
+        1 => simplify_cast(ssa, &opcode, operands[0])?,
+        2 => simplify_binop(ssa, &opcode, operands[0], operands[1])?,
+        _ => return None,
+    };
+
+    // Do not change the width of the value.
+    if let Simplified::Operand(operand) = simplified {
+        let operand_width = ssa.node_data(operand).ok()?.vt.width().clone();
+        if operand_width != WidthSpec::Known(width) {
+            return None;
+        }
+    }
+
+    Some((simplified, width))
+}
+
+fn simplify_binop(
+    ssa: &SSAStorage,
+    opcode: &MOpcode,
+    lhs: SSAValue,
+    rhs: SSAValue,
+) -> Option<Simplified> {
+    let (lc, rc) = (ssa.constant(lhs), ssa.constant(rhs));
+    let simplified = match *opcode {
+        MOpcode::OpAdd if rc == Some(0) => Simplified::Operand(lhs),
+        MOpcode::OpAdd if lc == Some(0) => Simplified::Operand(rhs),
+        MOpcode::OpSub if rc == Some(0) => Simplified::Operand(lhs),
+        MOpcode::OpMul | MOpcode::OpAnd if lc == Some(0) || rc == Some(0) => Simplified::Zero,
+        MOpcode::OpMul if rc == Some(1) => Simplified::Operand(lhs),
+        MOpcode::OpMul if lc == Some(1) => Simplified::Operand(rhs),
+        MOpcode::OpAnd | MOpcode::OpOr if lhs == rhs => Simplified::Operand(lhs),
+        MOpcode::OpXor if lhs == rhs => Simplified::Zero,
+        MOpcode::OpLsl | MOpcode::OpLsr if rc == Some(0) => Simplified::Operand(lhs),
+        _ => return None,
+    };
+    Some(simplified)
+}
+
+/// Collapses the cast `opcode` of another cast, `inner`, into a single cast.
+fn simplify_cast(ssa: &SSAStorage, opcode: &MOpcode, inner: SSAValue) -> Option<Simplified> {
+    let inner_opcode = ssa.opcode(inner)?;
+    let x = *ssa.operands_of(inner).first()?;
+    let x_width = ssa.node_data(x).ok()?.vt.width().get_width()?;
+
+    let simplified = match (opcode, inner_opcode) {
+        // The low `n` bits of an extended value are the ones of the value itself.
+        (&MOpcode::OpNarrow(n), MOpcode::OpZeroExt(_)) => match x_width.cmp(&n) {
+            Ordering::Equal => Simplified::Operand(x),
+            Ordering::Less => Simplified::Cast(MOpcode::OpZeroExt(n), x),
+            Ordering::Greater => Simplified::Cast(MOpcode::OpNarrow(n), x),
+        },
+        (&MOpcode::OpNarrow(n), MOpcode::OpSignExt(_)) => match x_width.cmp(&n) {
+            Ordering::Equal => Simplified::Operand(x),
+            Ordering::Less => Simplified::Cast(MOpcode::OpSignExt(n), x),
+            Ordering::Greater => Simplified::Cast(MOpcode::OpNarrow(n), x),
+        },
+        (&MOpcode::OpNarrow(n), MOpcode::OpNarrow(_)) => Simplified::Cast(MOpcode::OpNarrow(n), x),
+        (&MOpcode::OpZeroExt(n), MOpcode::OpZeroExt(_)) => {
+            Simplified::Cast(MOpcode::OpZeroExt(n), x)
+        }
+        (&MOpcode::OpSignExt(n), MOpcode::OpSignExt(_)) => {
+            Simplified::Cast(MOpcode::OpSignExt(n), x)
+        }
+        // The sign bit of a value which was actually widened by a zero extension is clear.
+        (&MOpcode::OpSignExt(n), MOpcode::OpZeroExt(m)) if x_width < m => {
+            Simplified::Cast(MOpcode::OpZeroExt(n), x)
+        }
+        _ => return None,
+    };
+    Some(simplified)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ssa::cfg_traits::CFG;
+    use crate::utils::test_support::load;
+
+    /// Runs `Simplify` over `expr` and returns the resulting value of `rax`.
+    fn simplify_il(expr: &str, width: u16) -> (RadecoFunction, SSAValue) {
+        let il = format!(
+            "\
+define-fun sym.simplify(unknown) -> unknown {{
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown32 = Narrow32(%1);
+        [@0x001000.0002] %4: $Unknown{} = {};
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $mem = %2;
+}}
+",
+            width, expr
+        );
+        let mut rfn = load(&il);
+
+        Simplify::new().analyze(&mut rfn, Some(all));
+
+        let rax = {
+            let ssa = rfn.ssa();
+            let rax_id = ssa.regfile.register_id_by_name("rax").unwrap();
+            let regs = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+            ssa.sparse_operands_of(regs)
+                .into_iter()
+                .find(|&(idx, _)| idx == rax_id.to_u8())
+                .unwrap()
+                .1
+        };
+        (rfn, rax)
+    }
+
+    #[derive(Debug)]
+    enum Expected {
+        Reg(&'static str),
+        Const(u64),
+        Op(MOpcode),
+    }
+
+    #[test]
+    fn identities() {
+        let table = [
+            ("%1 + #x0", 64, Expected::Reg("rdi")),
+            ("#x0 + %1", 64, Expected::Reg("rdi")),
+            ("%1 - #x0", 64, Expected::Reg("rdi")),
+            ("%1 * #x1", 64, Expected::Reg("rdi")),
+            ("#x1 * %1", 64, Expected::Reg("rdi")),
+            ("%1 * #x0", 64, Expected::Const(0)),
+            ("%1 & %1", 64, Expected::Reg("rdi")),
+            ("%1 | %1", 64, Expected::Reg("rdi")),
+            ("%1 ^ %1", 64, Expected::Const(0)),
+            ("%1 & #x0", 64, Expected::Const(0)),
+            ("%1 << #x0", 64, Expected::Reg("rdi")),
+            ("%1 >> #x0", 64, Expected::Reg("rdi")),
+            ("#x0 - %1", 64, Expected::Op(MOpcode::OpSub)),
+            ("%1 * #x2", 64, Expected::Op(MOpcode::OpMul)),
+            ("%1 ^ %3", 64, Expected::Op(MOpcode::OpXor)),
+            ("%3 * #x1", 32, Expected::Op(MOpcode::OpNarrow(32))),
+            // The operand is narrower than the result.
+            ("%3 * #x1", 64, Expected::Op(MOpcode::OpMul)),
+        ];
+
+        for (expr, width, expected) in table.iter() {
+            let (rfn, rax) = simplify_il(expr, *width);
+            let ssa = rfn.ssa();
+            match *expected {
+                Expected::Reg(reg) => {
+                    assert_eq!(
+                        ssa.argument(rax).as_ref().map(|s| s.as_str()),
+                        Some(reg),
+                        "{}",
+                        expr
+                    );
+                }
+                Expected::Const(c) => {
+                    assert_eq!(ssa.constant(rax), Some(c), "{}", expr);
+                    assert_eq!(
+                        ssa.node_data(rax).unwrap().vt.width(),
+                        &WidthSpec::Known(*width),
+                        "{}",
+                        expr
+                    );
+                }
+                Expected::Op(ref opcode) => {
+                    assert_eq!(ssa.opcode(rax).as_ref(), Some(opcode), "{}", expr);
+                }
+            }
+        }
+    }
+
+    /// Runs `Simplify` over the cast of a cast, `outer(inner)`, where `%3` is the low byte of
+    /// `rdi`. Returns the function and the resulting value of `rax`.
+    fn simplify_casts(inner: (&str, u16), outer: (&str, u16)) -> (RadecoFunction, SSAValue) {
+        let il = format!(
+            "\
+define-fun sym.casts(unknown) -> unknown {{
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown8 = Narrow8(%1);
+        [@0x001000.0002] %4: $Unknown{} = {};
+        [@0x001000.0003] %5: $Unknown{} = {}(%4);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %5;
+        $mem = %2;
+}}
+",
+            inner.1, inner.0, outer.1, outer.0
+        );
+        let mut rfn = load(&il);
+
+        Simplify::new().analyze(&mut rfn, Some(all));
+
+        let rax = {
+            let ssa = rfn.ssa();
+            let rax_id = ssa.regfile.register_id_by_name("rax").unwrap();
+            let regs = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+            ssa.sparse_operands_of(regs)
+                .into_iter()
+                .find(|&(idx, _)| idx == rax_id.to_u8())
+                .unwrap()
+                .1
+        };
+        (rfn, rax)
+    }
+
+    #[test]
+    fn cast_chains() {
+        // Names a value as in the IL of `simplify_casts`.
+        let describe = |ssa: &SSAStorage, x: SSAValue| match ssa.argument(x) {
+            Some(reg) => reg,
+            None => format!("%{}", ssa.address(x).unwrap().offset + 2),
+        };
+
+        let (zext, sext, narrow) = (MOpcode::OpZeroExt, MOpcode::OpSignExt, MOpcode::OpNarrow);
+        // (inner cast, width, outer cast, width, collapsed opcode, collapsed operand)
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let table = [
+            ("ZeroExt32(%3)", 32, "Narrow8", 8, None, "%3"),
+            ("SignExt32(%3)", 32, "Narrow8", 8, None, "%3"),
+            ("ZeroExt32(%3)", 32, "Narrow16", 16, Some(zext(16)), "%3"),
+            ("SignExt32(%3)", 32, "Narrow16", 16, Some(sext(16)), "%3"),
+            ("ZeroExt128(%1)", 128, "Narrow32", 32, Some(narrow(32)), "rdi"),
+            ("Narrow32(%1)", 32, "Narrow8", 8, Some(narrow(8)), "rdi"),
+            ("ZeroExt32(%3)", 32, "ZeroExt64", 64, Some(zext(64)), "%3"),
+            ("SignExt32(%3)", 32, "SignExt64", 64, Some(sext(64)), "%3"),
+            ("ZeroExt32(%3)", 32, "SignExt64", 64, Some(zext(64)), "%3"),
+            // The sign extension may set the high bits.
+            ("SignExt32(%3)", 32, "ZeroExt64", 64, Some(zext(64)), "%4"),
+            // The narrowing drops bits of `rdi`.
+            ("Narrow16(%1)", 16, "ZeroExt64", 64, Some(zext(64)), "%4"),
+        ];
+
+        for &(inner, inner_width, outer, outer_width, ref opcode, operand) in table.iter() {
+            let (rfn, rax) = simplify_casts((inner, inner_width), (outer, outer_width));
+            let ssa = rfn.ssa();
+            let case = format!("{}({})", outer, inner);
+            let collapsed = match *opcode {
+                None => rax,
+                Some(ref opcode) => {
+                    assert_eq!(ssa.opcode(rax).as_ref(), Some(opcode), "{}", case);
+                    let operands = ssa.operands_of(rax);
+                    assert_eq!(operands.len(), 1, "{}", case);
+                    operands[0]
+                }
+            };
+            assert_eq!(describe(ssa, collapsed), operand, "{}", case);
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! Raises the sign and overflow flags tested by conditional jumps to signed predicates.
+//!
+//! The ESIL of x86 recomputes every flag after an arithmetic instruction. `DCE` removes the ones
+//! which are never tested, but the tested ones are still bit twiddling of the operands and of the
+//! result. This pass recognizes the following idioms in the selector of a conditional block and
+//! replaces it with the predicate they compute. As there is no signed comparison in the IR,
+//! `x <s 0` is written as `x > 0x7f..f`:
+//!
+//!  * `sf`: `Narrow1(r >> (w - 1))` becomes `r <s 0`, `test r, r` (`r & r`) being seen as `r`;
+//!  * `of`: `Narrow1(((~a ^ b) & ((a + b) ^ a)) >> (w - 1))` becomes the signed overflow of
+//!    `a + b`, that is `(a <s 0) == (b <s 0) && !((a + b) <s 0 == (a <s 0))`.
+//!
+//! Negations of the above (e.g. `jno`) are kept as `OpNot` of the predicate. Flags computed in
+//! any other way are left as they are. As in `FlagFold`, only the selector is replaced: the flag
+//! nodes survive if they are used elsewhere and are cleaned up by `DCE` otherwise.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+};
+use crate::analysis::flag_fold::{binop, mask, peel_mask, width_of};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::{MOpcode, WidthSpec};
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+
+/// A predicate on the sign of values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagPredicate {
+    /// `value <s 0`
+    Negative(NodeIndex),
+    /// The signed addition of `lhs` and `rhs`, whose result is `sum`, overflows.
+    AddOverflow {
+        lhs: NodeIndex,
+        rhs: NodeIndex,
+        sum: NodeIndex,
+    },
+}
+
+/// A `Change` which replaces the selector of `block` with `predicate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimplifyFlag {
+    /// The conditional block.
+    pub block: NodeIndex,
+    /// The current selector of `block`.
+    pub selector: NodeIndex,
+    pub predicate: FlagPredicate,
+    /// Width of the values the predicate is about.
+    pub width: u16,
+    /// Whether the predicate has to be negated.
+    pub negated: bool,
+}
+
+impl Change for SimplifyFlag {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+const NAME: &str = "simplify_flags";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::SimplifyFlags,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+#[derive(Debug)]
+pub struct SimplifyFlags {}
+
+/// Whether `node` has the shape of the `of` computation, i.e. it is an `OpAnd` of two `OpXor`.
+fn is_overflow_like(ssa: &SSAStorage, node: NodeIndex) -> bool {
+    match binop(ssa, node, MOpcode::OpAnd) {
+        Some((x, y)) => {
+            ssa.opcode(x) == Some(MOpcode::OpXor) && ssa.opcode(y) == Some(MOpcode::OpXor)
+        }
+        None => false,
+    }
+}
+
+impl SimplifyFlags {
+    pub fn new() -> Self {
+        SimplifyFlags {}
+    }
+
+    /// Matches `(~a ^ b) & ((a + b) ^ a)`, in any order of the commutative operations.
+    fn match_overflow(ssa: &SSAStorage, node: NodeIndex) -> Option<FlagPredicate> {
+        let (x, y) = binop(ssa, node, MOpcode::OpAnd)?;
+        SimplifyFlags::match_overflow_terms(ssa, x, y)
+            .or_else(|| SimplifyFlags::match_overflow_terms(ssa, y, x))
+    }
+
+    /// Matches `signs` against `~a ^ b` and `result` against `(a + b) ^ a`.
+    fn match_overflow_terms(
+        ssa: &SSAStorage,
+        signs: NodeIndex,
+        result: NodeIndex,
+    ) -> Option<FlagPredicate> {
+        let (x, y) = binop(ssa, signs, MOpcode::OpXor)?;
+        let (not_a, b) = if ssa.opcode(x) == Some(MOpcode::OpNot) {
+            (x, y)
+        } else {
+            (y, x)
+        };
+        let ops = ssa.operands_of(not_a);
+        if ssa.opcode(not_a) != Some(MOpcode::OpNot) || ops.len() != 1 {
+            return None;
+        }
+        let a = ops[0];
+        let sum = match binop(ssa, result, MOpcode::OpXor)? {
+            (sum, a_) if a_ == a => sum,
+            (a_, sum) if a_ == a => sum,
+            _ => return None,
+        };
+        match binop(ssa, peel_mask(ssa, sum), MOpcode::OpAdd)? {
+            (a_, b_) if (a_, b_) == (a, b) || (b_, a_) == (a, b) => {
+                Some(FlagPredicate::AddOverflow {
+                    lhs: a,
+                    rhs: b,
+                    sum: sum,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Matches the flag idioms described in the module documentation, looking through
+    /// `OpNot`, `OpMov` and `OpNarrow(1)`. Returns (predicate, width, negated).
+    fn match_flag(
+        ssa: &SSAStorage,
+        node: NodeIndex,
+        negated: bool,
+    ) -> Option<(FlagPredicate, u16, bool)> {
+        let ops = ssa.operands_of(node);
+        match ssa.opcode(node)? {
+            MOpcode::OpNot if ops.len() == 1 => SimplifyFlags::match_flag(ssa, ops[0], !negated),
+            MOpcode::OpMov | MOpcode::OpNarrow(1) if ops.len() == 1 => {
+                SimplifyFlags::match_flag(ssa, ops[0], negated)
+            }
+            MOpcode::OpLsr if ops.len() == 2 => {
+                let w = width_of(ssa, ops[0])?;
+                if w == 0 || ssa.constant(ops[1]) != Some(u64::from(w) - 1) {
+                    return None;
+                }
+                let predicate = if is_overflow_like(ssa, ops[0]) {
+                    SimplifyFlags::match_overflow(ssa, ops[0])?
+                } else {
+                    match binop(ssa, ops[0], MOpcode::OpAnd) {
+                        Some((x, y)) if x == y => FlagPredicate::Negative(x),
+                        _ => FlagPredicate::Negative(ops[0]),
+                    }
+                };
+                Some((predicate, w, negated))
+            }
+            _ => None,
+        }
+    }
+
+    fn gather(ssa: &SSAStorage) -> Vec<SimplifyFlag> {
+        ssa.blocks()
+            .into_iter()
+            .filter_map(|block| {
+                let selector = ssa.selector_in(block)?;
+                let (predicate, width, negated) = SimplifyFlags::match_flag(ssa, selector, false)?;
+                Some(SimplifyFlag {
+                    block: block,
+                    selector: selector,
+                    predicate: predicate,
+                    width: width,
+                    negated: negated,
+                })
+            })
+            .collect()
+    }
+
+    /// Inserts the one bit wide `opcode` of `operands` into `block`.
+    fn insert(
+        ssa: &mut SSAStorage,
+        simp: &SimplifyFlag,
+        opcode: MOpcode,
+        operands: &[NodeIndex],
+    ) -> Option<NodeIndex> {
+        let addr = ssa.address(simp.selector)?;
+        let vt = ValueInfo::new_scalar(WidthSpec::new_known(1));
+        let node = ssa.insert_op(opcode, vt, None)?;
+        for (i, &op) in operands.iter().enumerate() {
+            ssa.op_use(node, i as u8, op);
+        }
+        ssa.insert_into_block(node, simp.block, addr);
+        Some(node)
+    }
+
+    fn apply(ssa: &mut SSAStorage, simp: SimplifyFlag) -> Option<NodeIndex> {
+        let max = ssa.insert_const(mask(simp.width) >> 1, Some(simp.width))?;
+        let predicate = match simp.predicate {
+            FlagPredicate::Negative(value) => {
+                SimplifyFlags::insert(ssa, &simp, MOpcode::OpGt, &[value, max])?
+            }
+            FlagPredicate::AddOverflow { lhs, rhs, sum } => {
+                let lhs = SimplifyFlags::insert(ssa, &simp, MOpcode::OpGt, &[lhs, max])?;
+                let rhs = SimplifyFlags::insert(ssa, &simp, MOpcode::OpGt, &[rhs, max])?;
+                let sum = SimplifyFlags::insert(ssa, &simp, MOpcode::OpGt, &[sum, max])?;
+                let same = SimplifyFlags::insert(ssa, &simp, MOpcode::OpEq, &[lhs, rhs])?;
+                let kept = SimplifyFlags::insert(ssa, &simp, MOpcode::OpEq, &[sum, lhs])?;
+                let flipped = SimplifyFlags::insert(ssa, &simp, MOpcode::OpNot, &[kept])?;
+                SimplifyFlags::insert(ssa, &simp, MOpcode::OpAnd, &[same, flipped])?
+            }
+        };
+        let new_selector = if simp.negated {
+            SimplifyFlags::insert(ssa, &simp, MOpcode::OpNot, &[predicate])?
+        } else {
+            predicate
+        };
+        // Only the selector edge is moved, other users of the flag keep it alive.
+        ssa.remove_edges_between(simp.block, simp.selector);
+        ssa.set_selector(new_selector, simp.block);
+        Some(new_selector)
+    }
+}
+
+impl Analyzer for SimplifyFlags {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for SimplifyFlags {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        func: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        let ssa = func.ssa_mut();
+
+        for simp in SimplifyFlags::gather(ssa) {
+            match policy(Box::new(simp.clone())) {
+                Action::Apply => {
+                    radeco_trace!("simplify_flags|{:?}", simp);
+                    if SimplifyFlags::apply(ssa, simp).is_none() {
+                        radeco_err!("Failed to simplify the selector");
+                    }
+                }
+                Action::Skip => (),
+                Action::Abort => break,
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::utils::test_support::{entry_reg, load};
+
+    // add rdi, rsi; jo 0x1007
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const ADD_JO_SSA_TXT: &str = "\
+define-fun sym.add_jo(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x5):
+        [@0x001000.0001] %4: $Unknown64 = %1 + %2;
+        [@0x001000.0002] %5: $Unknown64 = !%1;
+        [@0x001000.0003] %6: $Unknown64 = %5 ^ %2;
+        [@0x001000.0004] %7: $Unknown64 = %4 ^ %1;
+        [@0x001000.0005] %8: $Unknown64 = %6 & %7;
+        [@0x001000.0006] %9: $Unknown64 = %8 >> #x3f;
+        [@0x001000.0007] %10: $Unknown1 = Narrow1(%9);
+        JMP IF %10 0x001007.0000 ELSE 0x001005.0000
+    bb_0x001005.0000(sz 0x2):
+        RETURN
+    bb_0x001007.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %4;
+        $mem = %3;
+}
+";
+
+    // sub rdi, rsi; jo 0x1007
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SUB_JO_SSA_TXT: &str = "\
+define-fun sym.sub_jo(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x5):
+        [@0x001000.0001] %4: $Unknown64 = %1 - %2;
+        [@0x001000.0002] %5: $Unknown64 = !%1;
+        [@0x001000.0003] %6: $Unknown64 = %5 ^ %2;
+        [@0x001000.0004] %7: $Unknown64 = %4 ^ %1;
+        [@0x001000.0005] %8: $Unknown64 = %6 & %7;
+        [@0x001000.0006] %9: $Unknown64 = %8 >> #x3f;
+        [@0x001000.0007] %10: $Unknown1 = Narrow1(%9);
+        JMP IF %10 0x001007.0000 ELSE 0x001005.0000
+    bb_0x001005.0000(sz 0x2):
+        RETURN
+    bb_0x001007.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %4;
+        $mem = %3;
+}
+";
+
+    // test rdi, rdi; js 0x1005
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const TEST_JS_SSA_TXT: &str = "\
+define-fun sym.test_js(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x3):
+        [@0x001000.0001] %3: $Unknown64 = %1 & %1;
+        [@0x001000.0002] %4: $Unknown64 = %3 >> #x3f;
+        [@0x001000.0003] %5: $Unknown1 = Narrow1(%4);
+        JMP IF %5 0x001005.0000 ELSE 0x001003.0000
+    bb_0x001003.0000(sz 0x2):
+        RETURN
+    bb_0x001005.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $sf = %5;
+        $mem = %2;
+}
+";
+
+    // test rdi, rdi; jns 0x1005
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const TEST_JNS_SSA_TXT: &str = "\
+define-fun sym.test_jns(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x3):
+        [@0x001000.0001] %3: $Unknown64 = %1 & %1;
+        [@0x001000.0002] %4: $Unknown64 = %3 >> #x3f;
+        [@0x001000.0003] %5: $Unknown1 = Narrow1(%4);
+        [@0x001000.0004] %6: $Unknown1 = !%5;
+        JMP IF %6 0x001005.0000 ELSE 0x001003.0000
+    bb_0x001003.0000(sz 0x2):
+        RETURN
+    bb_0x001005.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %2;
+}
+";
+
+    fn simplify(il: &str) -> (RadecoFunction, NodeIndex) {
+        let mut rfn = load(il);
+        let mut simplify_flags = SimplifyFlags::new();
+        simplify_flags.analyze(&mut rfn, Some(all));
+        let selector = {
+            let ssa = rfn.ssa();
+            ssa.blocks()
+                .into_iter()
+                .filter_map(|b| ssa.selector_in(b))
+                .next()
+                .unwrap()
+        };
+        (rfn, selector)
+    }
+
+    /// Asserts that `node` is `value <s 0`, returning `value`.
+    fn negative_value(ssa: &SSAStorage, node: NodeIndex) -> NodeIndex {
+        assert_eq!(ssa.opcode(node), Some(MOpcode::OpGt));
+        let ops = ssa.operands_of(node);
+        assert_eq!(ssa.constant(ops[1]), Some(0x7fff_ffff_ffff_ffff));
+        ops[0]
+    }
+
+    #[test]
+    fn add_jo_is_signed_overflow() {
+        let (rfn, selector) = simplify(ADD_JO_SSA_TXT);
+        let ssa = rfn.ssa();
+        let (rdi, rsi) = (entry_reg(ssa, "rdi"), entry_reg(ssa, "rsi"));
+        assert_eq!(ssa.opcode(selector), Some(MOpcode::OpAnd));
+        let ops = ssa.operands_of(selector);
+
+        assert_eq!(ssa.opcode(ops[0]), Some(MOpcode::OpEq));
+        let same = ssa.operands_of(ops[0]);
+        assert_eq!(negative_value(ssa, same[0]), rdi);
+        assert_eq!(negative_value(ssa, same[1]), rsi);
+
+        assert_eq!(ssa.opcode(ops[1]), Some(MOpcode::OpNot));
+        let kept = ssa.operands_of(ssa.operands_of(ops[1])[0]);
+        let sum = negative_value(ssa, kept[0]);
+        assert_eq!(ssa.opcode(sum), Some(MOpcode::OpAdd));
+        assert_eq!(kept[1], same[0]);
+    }
+
+    #[test]
+    fn sub_jo_is_kept() {
+        let rfn = load(SUB_JO_SSA_TXT);
+        assert!(SimplifyFlags::gather(rfn.ssa()).is_empty());
+    }
+
+    #[test]
+    fn test_js_is_negative() {
+        let (rfn, selector) = simplify(TEST_JS_SSA_TXT);
+        let ssa = rfn.ssa();
+        assert_eq!(negative_value(ssa, selector), entry_reg(ssa, "rdi"));
+        // sf is still part of the final register state.
+        let exit_regs = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        assert!(ssa
+            .operands_of(exit_regs)
+            .into_iter()
+            .any(|n| ssa.opcode(n) == Some(MOpcode::OpNarrow(1))));
+    }
+
+    #[test]
+    fn test_jns_is_not_negative() {
+        let (rfn, selector) = simplify(TEST_JNS_SSA_TXT);
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(selector), Some(MOpcode::OpNot));
+        let negative = ssa.operands_of(selector)[0];
+        assert_eq!(negative_value(ssa, negative), entry_reg(ssa, "rdi"));
+    }
+}
@@ -0,0 +1,609 @@
+//! Forwards the reloads of spilled values.
+//!
+//! Unoptimized code keeps its values in stack slots: a register is stored (spilled) to a
+//! `rbp`-relative slot and loaded back (reloaded) every time it is needed. A load of a slot whose
+//! memory state comes, through stores to disjoint slots of the same frame base, from a store of
+//! the same width to the same slot always loads the stored value, so it is replaced by that value.
+//! The loads from constant addresses, e.g. of globals, are forwarded the same way.
+//!
+//! A load reading only part of the stored slot, e.g. the low 4 bytes of a spilled 8-byte register,
+//! is replaced by the bits it reads from the stored value, narrowed (and shifted right first if
+//! the load doesn't start at the slot, the accesses being little-endian). A load which only
+//! partially overlaps the stored slot can't be forwarded; it is kept and reported in the
+//! `SpillForwardResult`.
+//!
+//! Once its reloads are forwarded, a store to a local slot (below `rbp`) which no load, call or
+//! other memory user can observe anymore is removed, and `DCE` drops its address computation.
+//!
+//! The stack accesses are the ones recognized by `StackVars`. Two accesses are only compared when
+//! their addresses are computed from the same value, or are both constant. Any other store is
+//! considered to alias the reloaded slot, unless the alias sets of the function (see
+//! `analysis::alias`) prove that they access different locations.
+
+use crate::analysis::alias::AliasSets;
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+    RemoveValue, ReplaceValue,
+};
+use crate::analysis::stackvars::StackVars;
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils::propagate_registers;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+const NAME: &str = "spillforward";
+// The stack accesses are recognized by a `StackVars` of its own, nothing has to run before.
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::SpillForward,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+/// The memory accessed by a load or a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slot {
+    /// The value the address is computed from, `None` for a constant address.
+    base: Option<NodeIndex>,
+    /// Offset of the first byte from `base`, or the address itself.
+    offset: i64,
+    /// Width of the access in bytes.
+    width: u64,
+    /// It is `true` for the locals, which are gone once the function returns.
+    local: bool,
+}
+
+impl Slot {
+    fn overlaps(&self, other: &Slot) -> bool {
+        self.offset < other.offset + other.width as i64
+            && other.offset < self.offset + self.width as i64
+    }
+
+    fn covers(&self, other: &Slot) -> bool {
+        self.offset <= other.offset
+            && self.offset + self.width as i64 >= other.offset + other.width as i64
+    }
+}
+
+/// The slots of the loads and stores of a function, indexed by node.
+type Slots = HashMap<NodeIndex, Slot>;
+
+/// What a load is replaced with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Forward {
+    /// The stored value, as a whole.
+    Value(NodeIndex),
+    /// The `width` bits of the stored value `value` starting at bit `shift`.
+    Extract {
+        value: NodeIndex,
+        shift: u64,
+        width: u16,
+    },
+}
+
+/// A load which only partially overlaps the slot of the store its memory state comes from, and
+/// isn't forwarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialOverlap {
+    pub load: NodeIndex,
+    pub store: NodeIndex,
+}
+
+/// Result of `SpillForward`.
+#[derive(Debug, Clone, Default)]
+pub struct SpillForwardResult {
+    /// The loads left in place as they only partially overlap a stored slot.
+    pub partial_overlaps: Vec<PartialOverlap>,
+}
+
+impl AnalyzerResult for SpillForwardResult {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct SpillForward {
+    bp_name: Option<String>,
+    stackvars: StackVars,
+}
+
+impl SpillForward {
+    /// Creates a new `SpillForward` which considers the given registers (typically the ones
+    /// aliased by `BP` and `SP`) as frame bases, see `StackVars::new`.
+    pub fn new(bp_name: Option<String>, sp_name: Option<String>) -> Self {
+        SpillForward {
+            stackvars: StackVars::new(bp_name.clone(), sp_name),
+            bp_name: bp_name,
+        }
+    }
+
+    /// Collects the stack accesses and the accesses to constant addresses of `ssa`.
+    fn gather_slots(&self, ssa: &SSAStorage) -> Slots {
+        let mut slots = self
+            .stackvars
+            .gather_accesses(ssa)
+            .into_iter()
+            .map(|(base, access)| {
+                let slot = Slot {
+                    base: Some(base_of(ssa, access.addr)),
+                    offset: access.offset,
+                    width: access.width,
+                    local: self.bp_name.as_ref() == Some(&base)
+                        && access.offset + access.width as i64 <= 0,
+                };
+                (access.node, slot)
+            })
+            .collect::<Slots>();
+        for node in ssa.blocks().into_iter().flat_map(|b| ssa.exprs_in(b)) {
+            let ops = ssa.operands_of(node);
+            let width = match ssa.opcode(node) {
+                Some(MOpcode::OpLoad) if ops.len() == 2 => ssa.node_data(node),
+                Some(MOpcode::OpStore) if ops.len() == 3 => ssa.node_data(ops[2]),
+                _ => continue,
+            }
+            .ok()
+            .and_then(|nd| nd.vt.width().get_width());
+            if let (Some(addr), Some(width)) = (ssa.constant(ops[1]), width) {
+                let slot = Slot {
+                    base: None,
+                    offset: addr as i64,
+                    width: (u64::from(width) + 7) / 8,
+                    local: false,
+                };
+                slots.insert(node, slot);
+            }
+        }
+        slots
+    }
+
+    /// Returns what the load `load` reloads, if it can be found. A load partially overlapping the
+    /// stored slot is added to `partial_overlaps`.
+    fn spilled_value(
+        ssa: &SSAStorage,
+        slots: &Slots,
+        aliases: Option<&AliasSets>,
+        load: NodeIndex,
+        partial_overlaps: &mut Vec<PartialOverlap>,
+    ) -> Option<Forward> {
+        let reload = *slots.get(&load)?;
+        let width = *ssa.node_data(load).ok()?.vt.width();
+        let mut mem = *ssa.operands_of(load).get(0)?;
+        loop {
+            if ssa.opcode(mem) != Some(MOpcode::OpStore) {
+                return None;
+            }
+            let ops = ssa.operands_of(mem);
+            match slots.get(&mem) {
+                Some(spill) if spill.base == reload.base => {
+                    if spill.covers(&reload) {
+                        let value = ops[2];
+                        let value_width = *ssa.node_data(value).ok()?.vt.width();
+                        if spill.offset == reload.offset && value_width == width {
+                            return Some(Forward::Value(value));
+                        }
+                        let shift = (reload.offset - spill.offset) as u64 * 8;
+                        return match (value_width.get_width(), width.get_width()) {
+                            (Some(vw), Some(w)) if shift + u64::from(w) <= u64::from(vw) => {
+                                Some(Forward::Extract {
+                                    value: value,
+                                    shift: shift,
+                                    width: w,
+                                })
+                            }
+                            _ => None,
+                        };
+                    }
+                    if spill.overlaps(&reload) {
+                        partial_overlaps.push(PartialOverlap {
+                            load: load,
+                            store: mem,
+                        });
+                        return None;
+                    }
+                }
+                _ if !may_alias(aliases, mem, load) => (),
+                _ => return None,
+            }
+            mem = ops[0];
+        }
+    }
+
+    /// Inserts the extraction of `width` bits at bit `shift` of `value` next to `load`, and
+    /// returns it.
+    fn extract(
+        ssa: &mut SSAStorage,
+        load: NodeIndex,
+        value: NodeIndex,
+        shift: u64,
+        width: u16,
+    ) -> Option<NodeIndex> {
+        let block = ssa.block_for(load)?;
+        let addr = ssa.address(load)?;
+        let vt = ssa.node_data(load).ok()?.vt;
+        let shifted = if shift > 0 {
+            let value_vt = ssa.node_data(value).ok()?.vt;
+            let amount = ssa.insert_const(shift, None)?;
+            let shifted = ssa.insert_op(MOpcode::OpLsr, value_vt, None)?;
+            ssa.op_use(shifted, 0, value);
+            ssa.op_use(shifted, 1, amount);
+            ssa.insert_into_block(shifted, block, addr);
+            propagate_registers(ssa, shifted);
+            shifted
+        } else {
+            value
+        };
+        let narrowed = ssa.insert_op(MOpcode::OpNarrow(width), vt, None)?;
+        ssa.op_use(narrowed, 0, shifted);
+        ssa.insert_into_block(narrowed, block, addr);
+        propagate_registers(ssa, narrowed);
+        Some(narrowed)
+    }
+
+    /// Returns `true` if the slot written by the stack store `store` may be read after it.
+    fn is_observed(
+        ssa: &SSAStorage,
+        slots: &Slots,
+        aliases: Option<&AliasSets>,
+        store: NodeIndex,
+    ) -> bool {
+        let spill = slots[&store];
+        let exit = ssa.exit_node().and_then(|e| ssa.registers_in(e));
+
+        let mut visited = HashSet::new();
+        let mut queue = vec![store];
+        while let Some(mem) = queue.pop() {
+            if !visited.insert(mem) {
+                continue;
+            }
+            for user in ssa.uses_of(mem) {
+                if Some(user) == exit {
+                    if spill.local {
+                        continue;
+                    }
+                    return true;
+                }
+                if ssa.is_phi(user) {
+                    queue.push(user);
+                    continue;
+                }
+                let slot = slots.get(&user).filter(|s| s.base == spill.base);
+                match ssa.opcode(user) {
+                    Some(MOpcode::OpLoad) => match slot {
+                        Some(s) if !s.overlaps(&spill) => (),
+                        None if !may_alias(aliases, user, store) => (),
+                        _ => return true,
+                    },
+                    Some(MOpcode::OpStore) if ssa.operands_of(user).get(0) == Some(&mem) => {
+                        match slot {
+                            // Overwritten as a whole.
+                            Some(s) if s.covers(&spill) => (),
+                            _ => queue.push(user),
+                        }
+                    }
+                    _ => return true,
+                }
+            }
+        }
+        false
+    }
+}
+
+fn may_alias(aliases: Option<&AliasSets>, a: NodeIndex, b: NodeIndex) -> bool {
+    aliases.map_or(true, |aliases| aliases.may_alias(a, b))
+}
+
+/// Returns the non-constant operand of the address `addr`, as matched by `StackVars`.
+fn base_of(ssa: &SSAStorage, addr: NodeIndex) -> NodeIndex {
+    let ops = ssa.operands_of(addr);
+    match ssa.opcode(addr) {
+        Some(MOpcode::OpAdd) | Some(MOpcode::OpSub)
+            if ops.len() == 2 && ssa.constant(ops[1]).is_some() =>
+        {
+            ops[0]
+        }
+        Some(MOpcode::OpAdd) if ops.len() == 2 && ssa.constant(ops[0]).is_some() => ops[1],
+        _ => addr,
+    }
+}
+
+impl Analyzer for SpillForward {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for SpillForward {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rfn: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        // The alias sets are indexed by node, so they go stale as the SSA is simplified.
+        let aliases = rfn.alias_sets().cloned().map(|mut aliases| {
+            aliases.update(rfn.ssa());
+            aliases
+        });
+        let mut result = SpillForwardResult::default();
+        let (slots, reloads) = {
+            let ssa = rfn.ssa();
+            let slots = self.gather_slots(ssa);
+            let mut loads = slots
+                .keys()
+                .cloned()
+                .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpLoad))
+                .collect::<Vec<_>>();
+            loads.sort();
+            let reloads = loads
+                .into_iter()
+                .filter_map(|n| {
+                    let partial_overlaps = &mut result.partial_overlaps;
+                    SpillForward::spilled_value(ssa, &slots, aliases.as_ref(), n, partial_overlaps)
+                        .map(|v| (n, v))
+                })
+                .collect::<Vec<_>>();
+            (slots, reloads)
+        };
+
+        let ssa = rfn.ssa_mut();
+        for (load, forward) in reloads {
+            let stored = match forward {
+                Forward::Value(value) | Forward::Extract { value, .. } => value,
+            };
+            match policy(Box::new(ReplaceValue(stored, load))) {
+                Action::Apply => {
+                    let value = match forward {
+                        Forward::Value(value) => Some(value),
+                        Forward::Extract {
+                            value,
+                            shift,
+                            width,
+                        } => SpillForward::extract(ssa, load, value, shift, width),
+                    };
+                    match value {
+                        Some(value) => {
+                            radeco_trace!("spillforward|{:?} -> {:?}", load, value);
+                            ssa.replace_value(load, value);
+                        }
+                        None => radeco_err!("Failed to extract the value of {:?}", load),
+                    }
+                }
+                Action::Skip => (),
+                Action::Abort => return None,
+            }
+        }
+
+        let mut stores = slots
+            .iter()
+            .filter(|&(&n, s)| s.base.is_some() && ssa.opcode(n) == Some(MOpcode::OpStore))
+            .map(|(&n, _)| n)
+            .collect::<Vec<_>>();
+        stores.sort();
+        for store in stores {
+            if SpillForward::is_observed(ssa, &slots, aliases.as_ref(), store) {
+                continue;
+            }
+            match policy(Box::new(RemoveValue(store))) {
+                Action::Apply => {
+                    radeco_trace!("spillforward|removed {:?}", store);
+                    let mem = ssa.operands_of(store)[0];
+                    ssa.replace_value(store, mem);
+                }
+                Action::Skip => (),
+                Action::Abort => return None,
+            }
+        }
+
+        Some(Box::new(result))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::utils::test_support::load;
+
+    // `rdi` is spilled to `rbp - 0x8` and reloaded after a store to `rbp - 0x10`.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SPILL_SSA_TXT: &str = "\
+define-fun sym.spill(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Unknown64 = $rdi;
+        %3: $Unknown64 = $rsi;
+        %4: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %5: $Unknown64 = %1 - #x8;
+        [@0x001000.0002] %6: $Unknown0 = Store(%4, %5, %2);
+        [@0x001004.0001] %7: $Unknown64 = %1 - #x10;
+        [@0x001004.0002] %8: $Unknown0 = Store(%6, %7, %3);
+        [@0x001008.0001] %9: $Unknown64 = %1 - #x8;
+        [@0x001008.0002] %10: $Unknown64 = Load(%8, %9);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %10;
+        $mem = %8;
+}
+";
+
+    // Same as above, but the slot may be overwritten through `rsi` before the reload.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const ALIASED_SSA_TXT: &str = "\
+define-fun sym.aliased(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Unknown64 = $rdi;
+        %3: $Unknown64 = $rsi;
+        %4: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %5: $Unknown64 = %1 - #x8;
+        [@0x001000.0002] %6: $Unknown0 = Store(%4, %5, %2);
+        [@0x001004.0001] %7: $Unknown0 = Store(%6, %3, %2);
+        [@0x001008.0001] %8: $Unknown64 = %1 - #x8;
+        [@0x001008.0002] %9: $Unknown64 = Load(%7, %8);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %9;
+        $mem = %7;
+}
+";
+
+    // `rdi` is spilled to `rbp - 0x8`, and its low 4 bytes are reloaded.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const TRUNCATED_SSA_TXT: &str = "\
+define-fun sym.truncated(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Unknown64 = $rdi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown64 = %1 - #x8;
+        [@0x001000.0002] %5: $Unknown0 = Store(%3, %4, %2);
+        [@0x001004.0001] %6: $Unknown64 = %1 - #x8;
+        [@0x001004.0002] %7: $Unknown32 = Load(%5, %6);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $mem = %5;
+}
+";
+
+    // `edi` is spilled to `rbp - 0x8`, and 8 bytes are reloaded from there.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const WIDENED_SSA_TXT: &str = "\
+define-fun sym.widened(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Unknown64 = $rdi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown64 = %1 - #x8;
+        [@0x001000.0002] %5: $Unknown32 = Narrow32(%2);
+        [@0x001000.0003] %6: $Unknown0 = Store(%3, %4, %5);
+        [@0x001004.0001] %7: $Unknown64 = %1 - #x8;
+        [@0x001004.0002] %8: $Unknown64 = Load(%6, %7);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %8;
+        $mem = %6;
+}
+";
+
+    fn count_op(rfn: &RadecoFunction, opc: MOpcode) -> usize {
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&n| ssa.opcode(n) == Some(opc.clone()))
+            .count()
+    }
+
+    /// Returns the registers whose entry value is in the final register state.
+    fn exit_arguments(rfn: &RadecoFunction) -> Vec<String> {
+        let ssa = rfn.ssa();
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        ssa.operands_of(exit)
+            .into_iter()
+            .filter_map(|n| ssa.argument(n))
+            .collect()
+    }
+
+    /// Returns the only operation `opc` of `rfn`.
+    fn only_op(rfn: &RadecoFunction, opc: MOpcode) -> NodeIndex {
+        let ssa = rfn.ssa();
+        let nodes = ssa
+            .blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&n| ssa.opcode(n) == Some(opc.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 1);
+        nodes[0]
+    }
+
+    fn spill_forward(rfn: &mut RadecoFunction) -> SpillForwardResult {
+        let mut spill_forward = SpillForward::new(Some("rbp".to_owned()), Some("rsp".to_owned()));
+        let result = spill_forward.analyze(rfn, Some(all)).unwrap();
+        result
+            .as_any()
+            .downcast_ref::<SpillForwardResult>()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn reload_forwarded() {
+        let mut rfn = load(SPILL_SSA_TXT);
+        let result = spill_forward(&mut rfn);
+
+        assert!(result.partial_overlaps.is_empty());
+        assert_eq!(count_op(&rfn, MOpcode::OpLoad), 0);
+        assert!(exit_arguments(&rfn).contains(&"rdi".to_owned()));
+        // Nothing reads the locals anymore.
+        assert_eq!(count_op(&rfn, MOpcode::OpStore), 0);
+    }
+
+    #[test]
+    fn narrower_reload_truncated() {
+        let mut rfn = load(TRUNCATED_SSA_TXT);
+        spill_forward(&mut rfn);
+
+        assert_eq!(count_op(&rfn, MOpcode::OpLoad), 0);
+        let ssa = rfn.ssa();
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        // `rax` is the low half of `rdi`.
+        let rax = ssa
+            .operands_of(exit)
+            .into_iter()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpNarrow(32)))
+            .expect("No truncation");
+        let narrowed = ssa.operands_of(rax);
+        assert_eq!(ssa.argument(narrowed[0]), Some("rdi".to_owned()));
+        assert!(ssa.registers(rax).contains(&"rdi".to_owned()));
+    }
+
+    #[test]
+    fn wider_reload_kept() {
+        let mut rfn = load(WIDENED_SSA_TXT);
+        let result = spill_forward(&mut rfn);
+
+        // The upper 4 bytes are not written by the store.
+        let load = only_op(&rfn, MOpcode::OpLoad);
+        let store = only_op(&rfn, MOpcode::OpStore);
+        assert_eq!(
+            result.partial_overlaps,
+            vec![PartialOverlap {
+                load: load,
+                store: store,
+            }]
+        );
+    }
+
+    #[test]
+    fn aliasing_store_keeps_reload() {
+        let mut rfn = load(ALIASED_SSA_TXT);
+        spill_forward(&mut rfn);
+
+        assert_eq!(count_op(&rfn, MOpcode::OpLoad), 1);
+        assert_eq!(count_op(&rfn, MOpcode::OpStore), 2);
+        assert!(!exit_arguments(&rfn).contains(&"rdi".to_owned()));
+    }
+}
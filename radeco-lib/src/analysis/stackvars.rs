@@ -0,0 +1,349 @@
+//! Stack variable recovery
+//!
+//! Identifies memory accesses whose address is `framebase ± constant` and groups them into local
+//! variables. Accesses overlapping the same stack slot (e.g. a 4-byte and an 8-byte access at
+//! `rbp - 0x10`) belong to the same variable. Variables are named after the locals provided by
+//! the `Source` (see `Source::locals_of`) when available, otherwise a `var_N`/`arg_N` name is
+//! synthesized from the offset.
+//!
+//! The address computation of every access is bound to its variable in the `RadecoFunction`, so
+//! that the backend prints `var_10` instead of `*(rbp - 0x10)`.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+};
+use crate::frontend::radeco_containers::{BindingType, RadecoFunction, VarBinding};
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+use std::collections::HashMap;
+
+/// A local variable living on the stack.
+#[derive(Debug, Clone)]
+pub struct StackVar {
+    /// Name of the frame base register the variable is addressed from.
+    pub base: String,
+    /// Offset of the first byte of the variable from `base`.
+    pub offset: i64,
+    /// Size in bytes, i.e. the extent of the widest combination of overlapping accesses.
+    pub size: u64,
+    /// The binding associated to this variable.
+    pub binding: VarBinding,
+}
+
+/// A single `OpLoad`/`OpStore` to a stack slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackAccess {
+    /// The `OpLoad`/`OpStore` node.
+    pub node: NodeIndex,
+    /// The address operand of `node`.
+    pub addr: NodeIndex,
+    /// Offset of the access from the frame base.
+    pub offset: i64,
+    /// Width of the access in bytes.
+    pub width: u64,
+    /// Index of the accessed variable in `StackVarsResult::vars`.
+    pub var: usize,
+}
+
+/// Result of `StackVars`.
+#[derive(Debug, Clone, Default)]
+pub struct StackVarsResult {
+    /// Recovered variables, sorted by base register and offset.
+    pub vars: Vec<StackVar>,
+    /// Accesses to the variables, indexed by their `OpLoad`/`OpStore` node.
+    pub accesses: HashMap<NodeIndex, StackAccess>,
+}
+
+impl StackVarsResult {
+    /// Returns the variable accessed by the `OpLoad`/`OpStore` `node`.
+    pub fn var_of(&self, node: NodeIndex) -> Option<&StackVar> {
+        self.accesses.get(&node).map(|a| &self.vars[a.var])
+    }
+}
+
+impl AnalyzerResult for StackVarsResult {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct StackVars {
+    bases: Vec<String>,
+}
+
+const NAME: &str = "stackvars";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::StackVars,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+impl StackVars {
+    /// Creates a new `StackVars` which considers the given registers (typically the ones
+    /// aliased by `BP` and `SP`) as frame bases.
+    pub fn new(bp_name: Option<String>, sp_name: Option<String>) -> Self {
+        StackVars {
+            bases: bp_name.into_iter().chain(sp_name).collect(),
+        }
+    }
+
+    /// Returns the frame base register `node` holds, if any. Apart from the values explicitly
+    /// tagged with a base register, only the entry values of the base registers are considered.
+    fn frame_base(&self, ssa: &SSAStorage, node: NodeIndex) -> Option<String> {
+        if let Some(reg) = ssa
+            .registers(node)
+            .into_iter()
+            .find(|r| self.bases.contains(r))
+        {
+            return Some(reg);
+        }
+        let reg = ssa.argument(node).filter(|c| self.bases.contains(c))?;
+        let entry_regs = ssa.registers_in(ssa.entry_node()?)?;
+        if ssa.operands_of(entry_regs).contains(&node) {
+            Some(reg)
+        } else {
+            None
+        }
+    }
+
+    /// Matches `addr` against `base`, `base + c` and `base - c`.
+    fn frame_offset(&self, ssa: &SSAStorage, addr: NodeIndex) -> Option<(String, i64)> {
+        if let Some(base) = self.frame_base(ssa, addr) {
+            return Some((base, 0));
+        }
+        let ops = ssa.operands_of(addr);
+        if ops.len() != 2 {
+            return None;
+        }
+        match ssa.opcode(addr)? {
+            MOpcode::OpAdd => {
+                if let (Some(base), Some(c)) = (self.frame_base(ssa, ops[0]), ssa.constant(ops[1]))
+                {
+                    Some((base, c as i64))
+                } else if let (Some(c), Some(base)) =
+                    (ssa.constant(ops[0]), self.frame_base(ssa, ops[1]))
+                {
+                    Some((base, c as i64))
+                } else {
+                    None
+                }
+            }
+            MOpcode::OpSub => {
+                let base = self.frame_base(ssa, ops[0])?;
+                let c = ssa.constant(ops[1])?;
+                Some((base, (c as i64).wrapping_neg()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Collects all the stack accesses of `ssa`, without variable assigned.
+    pub fn gather_accesses(&self, ssa: &SSAStorage) -> Vec<(String, StackAccess)> {
+        let mut accesses = Vec::new();
+        for node in ssa.blocks().into_iter().flat_map(|b| ssa.exprs_in(b)) {
+            let ops = ssa.operands_of(node);
+            let width = match ssa.opcode(node) {
+                Some(MOpcode::OpLoad) if ops.len() == 2 => ssa.node_data(node),
+                Some(MOpcode::OpStore) if ops.len() == 3 => ssa.node_data(ops[2]),
+                _ => continue,
+            }
+            .ok()
+            .and_then(|nd| nd.vt.width().get_width())
+            .map(|w| (u64::from(w) + 7) / 8)
+            .unwrap_or(1)
+            .max(1);
+            if let Some((base, offset)) = self.frame_offset(ssa, ops[1]) {
+                accesses.push((
+                    base,
+                    StackAccess {
+                        node: node,
+                        addr: ops[1],
+                        offset: offset,
+                        width: width,
+                        var: 0,
+                    },
+                ));
+            }
+        }
+        accesses
+    }
+
+    /// Looks for a known local of `rfn` lying in `[offset, offset + size)` from `base`,
+    /// preferring the one starting exactly at `offset`.
+    fn known_local(rfn: &RadecoFunction, base: &str, offset: i64, size: u64) -> Option<VarBinding> {
+        let mut candidates = rfn
+            .locals()
+            .into_iter()
+            .filter_map(|vb| match vb.btype {
+                BindingType::RegisterLocal(ref reg, o)
+                    if reg == base && o >= offset && o < offset + size as i64 =>
+                {
+                    Some((o, vb.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|c| c.0);
+        candidates.into_iter().next().map(|c| c.1)
+    }
+
+    fn synthesize(base: &str, offset: i64) -> VarBinding {
+        let name = if offset < 0 {
+            format!("var_{:x}", offset.wrapping_neg())
+        } else {
+            format!("arg_{:x}", offset)
+        };
+        VarBinding::new(
+            BindingType::RegisterLocal(base.to_owned(), offset),
+            String::new(),
+            Some(name),
+            NodeIndex::end(),
+            None,
+        )
+    }
+}
+
+impl Analyzer for StackVars {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for StackVars {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rfn: &mut RadecoFunction,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut accesses = self.gather_accesses(rfn.ssa());
+        // Widest access first, so that the slot of a group covers all the narrower accesses
+        // starting at the same offset.
+        accesses.sort_by(|a, b| (&a.0, a.1.offset, b.1.width).cmp(&(&b.0, b.1.offset, a.1.width)));
+
+        // Group overlapping accesses into (base, start, end, accesses).
+        let mut groups: Vec<(String, i64, i64, Vec<StackAccess>)> = Vec::new();
+        for (base, access) in accesses {
+            let end = access.offset + access.width as i64;
+            if let Some(last) = groups.last_mut() {
+                if last.0 == base && access.offset < last.2 {
+                    last.2 = last.2.max(end);
+                    last.3.push(access);
+                    continue;
+                }
+            }
+            groups.push((base, access.offset, end, vec![access]));
+        }
+
+        let mut result = StackVarsResult::default();
+        for (base, start, end, group) in groups {
+            let size = (end - start) as u64;
+            let binding = StackVars::known_local(rfn, &base, start, size)
+                .unwrap_or_else(|| StackVars::synthesize(&base, start));
+            radeco_trace!(
+                "stackvars|{}{:+} ({} bytes) -> {}",
+                base,
+                start,
+                size,
+                binding.name()
+            );
+            let var = result.vars.len();
+            for mut access in group {
+                access.var = var;
+                rfn.bind_local(access.addr, binding.clone());
+                result.accesses.insert(access.node, access);
+            }
+            result.vars.push(StackVar {
+                base: base,
+                offset: start,
+                size: size,
+                binding: binding,
+            });
+        }
+
+        Some(Box::new(result))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_support::load;
+
+    // An 8-byte store and a 4-byte load to `rbp - 0x10`, through different address nodes.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SAME_SLOT_SSA_TXT: &str = "\
+define-fun sym.slot(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Unknown64 = $rdi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown64 = %1 - #x10;
+        [@0x001000.0002] %5: $Unknown0 = Store(%3, %4, %2);
+        [@0x001004.0001] %6: $Unknown64 = %1 + #xfffffffffffffff0;
+        [@0x001004.0002] %7: $Unknown32 = Load(%5, %6);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $mem = %5;
+}
+";
+
+    fn find_op(rfn: &RadecoFunction, opc: MOpcode) -> NodeIndex {
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .find(|&n| ssa.opcode(n) == Some(opc.clone()))
+            .unwrap()
+    }
+
+    #[test]
+    fn same_slot_different_widths() {
+        let mut rfn = load(SAME_SLOT_SSA_TXT);
+        let mut stackvars = StackVars::new(Some("rbp".to_owned()), Some("rsp".to_owned()));
+        let result = stackvars
+            .analyze(&mut rfn, None::<fn(_) -> _>)
+            .expect("No result");
+        let result = result
+            .as_any()
+            .downcast_ref::<StackVarsResult>()
+            .expect("Unexpected result");
+
+        assert_eq!(result.vars.len(), 1);
+        let var = &result.vars[0];
+        assert_eq!(var.base, "rbp");
+        assert_eq!(var.offset, -0x10);
+        assert_eq!(var.size, 8);
+        assert_eq!(var.binding.name(), "var_10");
+
+        let store = find_op(&rfn, MOpcode::OpStore);
+        let load = find_op(&rfn, MOpcode::OpLoad);
+        assert_eq!(result.accesses.len(), 2);
+        assert_eq!(result.accesses[&store].width, 8);
+        assert_eq!(result.accesses[&load].width, 4);
+        assert_eq!(result.var_of(store).unwrap().binding.name(), "var_10");
+        assert_eq!(result.var_of(load).unwrap().binding.name(), "var_10");
+
+        // Both address computations print as the variable.
+        for access in result.accesses.values() {
+            let bindings = rfn.local_at(access.addr, false).expect("Unbound address");
+            assert_eq!(bindings[0].name(), "var_10");
+        }
+        assert_eq!(rfn.locals().len(), 1);
+    }
+}
@@ -0,0 +1,200 @@
+//! Detection of the reads of uninitialized locals.
+//!
+//! Builds on the stack accesses recovered by `StackVars`: for every `OpLoad` of a local, i.e. a
+//! stack slot below the frame base, the memory chain of the load is walked back through the
+//! stores and the memory phis. If the memory state at the entry of the function is reached without
+//! crossing a store overlapping the slot, there is a path on which the slot is read before being
+//! written, and the load is flagged (see `RadecoFunction::uninit_reads`).
+//!
+//! The slots at non-negative offsets (the saved frame pointer, the return address and the
+//! arguments passed on the stack) are initialized by the caller, so their loads are never flagged.
+//! The walk conservatively stops at anything else writing memory, e.g. the calls, as well as at the
+//! stores to the stack addressed from another frame base, which may overlap the slot. Stores to
+//! addresses which are not on the stack are looked past.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+};
+use crate::analysis::stackvars::{StackAccess, StackVars};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+const NAME: &str = "uninit";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::UninitReads,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+#[derive(Debug)]
+pub struct UninitReads {
+    stackvars: StackVars,
+}
+
+impl UninitReads {
+    /// Creates a new `UninitReads` which considers the given registers (typically the ones
+    /// aliased by `BP` and `SP`) as frame bases, see `StackVars::new`.
+    pub fn new(bp_name: Option<String>, sp_name: Option<String>) -> Self {
+        UninitReads {
+            stackvars: StackVars::new(bp_name, sp_name),
+        }
+    }
+
+    /// Returns `true` if the memory state `mem` may be reached from the entry of the function
+    /// without writing any byte of the slot `[access.offset, access.offset + access.width)` from
+    /// `base`.
+    fn reaches_entry(
+        ssa: &SSAStorage,
+        mem: NodeIndex,
+        base: &str,
+        access: &StackAccess,
+        stores: &HashMap<NodeIndex, (String, StackAccess)>,
+        entry_values: &HashSet<NodeIndex>,
+    ) -> bool {
+        let start = access.offset;
+        let end = access.offset + access.width as i64;
+        let mut visited = HashSet::new();
+        let mut worklist = vec![mem];
+        while let Some(node) = worklist.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+            if entry_values.contains(&node) {
+                return true;
+            }
+            if ssa.is_phi(node) {
+                worklist.extend(ssa.operands_of(node));
+                continue;
+            }
+            if ssa.opcode(node) != Some(MOpcode::OpStore) {
+                continue;
+            }
+            let ops = ssa.operands_of(node);
+            match stores.get(&node) {
+                Some(&(ref b, ref store)) if b == base => {
+                    let overlaps = store.offset < end && start < store.offset + store.width as i64;
+                    if !overlaps {
+                        worklist.push(ops[0]);
+                    }
+                }
+                Some(_) => {}
+                None => worklist.extend(ops.first().cloned()),
+            }
+        }
+        false
+    }
+}
+
+impl Analyzer for UninitReads {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for UninitReads {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rfn: &mut RadecoFunction,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut uninit = Vec::new();
+        {
+            let ssa = rfn.ssa();
+            let entry_values = ssa
+                .entry_node()
+                .and_then(|entry| ssa.registers_in(entry))
+                .map(|regs| ssa.operands_of(regs).into_iter().collect::<HashSet<_>>())
+                .unwrap_or_default();
+            let (loads, stores): (Vec<_>, Vec<_>) = self
+                .stackvars
+                .gather_accesses(ssa)
+                .into_iter()
+                .partition(|(_, a)| ssa.opcode(a.node) == Some(MOpcode::OpLoad));
+            let stores = stores
+                .into_iter()
+                .map(|(base, a)| (a.node, (base, a)))
+                .collect::<HashMap<_, _>>();
+
+            for (base, load) in loads.into_iter().filter(|(_, a)| a.offset < 0) {
+                let mem = ssa.operands_of(load.node)[0];
+                if UninitReads::reaches_entry(ssa, mem, &base, &load, &stores, &entry_values) {
+                    radeco_trace!("uninit|{:?} reads {}{:+}", load.node, base, load.offset);
+                    uninit.push(load.node);
+                }
+            }
+        }
+        uninit.sort();
+        rfn.set_uninit_reads(uninit);
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_support::load;
+
+    // `rsp - 0x10` is written, then `rsp - 0x8` is read before being written, then `rsp - 0x10`
+    // and the stack argument at `rsp + 0x8` are read.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const LOCALS_SSA_TXT: &str = "\
+define-fun sym.locals(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rsp;
+        %2: $Unknown64 = $rdi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %4: $Unknown64 = %1 - #x10;
+        [@0x001000.0002] %5: $Unknown0 = Store(%3, %4, %2);
+        [@0x001004.0001] %6: $Unknown64 = %1 - #x8;
+        [@0x001004.0002] %7: $Unknown64 = Load(%5, %6);
+        [@0x001008.0001] %8: $Unknown64 = Load(%5, %4);
+        [@0x00100c.0001] %9: $Unknown64 = %1 + #x8;
+        [@0x00100c.0002] %10: $Unknown64 = Load(%5, %9);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $rcx = %8;
+        $rdx = %10;
+        $mem = %5;
+}
+";
+
+    /// Returns the `OpLoad`s of `rfn`, in order.
+    fn loads(rfn: &RadecoFunction) -> Vec<NodeIndex> {
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpLoad))
+            .collect()
+    }
+
+    #[test]
+    fn read_before_write() {
+        let mut rfn = load(LOCALS_SSA_TXT);
+        let mut uninit = UninitReads::new(Some("rbp".to_owned()), Some("rsp".to_owned()));
+        uninit.analyze(&mut rfn, None::<fn(_) -> _>);
+
+        let loads = loads(&rfn);
+        assert_eq!(loads.len(), 3);
+        // Only the read of `rsp - 0x8` is flagged: `rsp - 0x10` is written before being read,
+        // and the stack argument is initialized by the caller.
+        assert_eq!(rfn.uninit_reads(), &[loads[0]]);
+    }
+}
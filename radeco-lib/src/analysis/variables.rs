@@ -0,0 +1,130 @@
+//! Names the variables of a function, for display.
+//!
+//! The SSA gives every definition its own value, so a variable which is assigned in several
+//! blocks shows up as several values tied together by phis. The values flowing into the same phi,
+//! directly or through other phis and `OpMov`s, are grouped into one variable with a union-find.
+//! Every value of a group is annotated with the name of its variable (see `SSAExtra::variable`),
+//! e.g. `v3`, which `ir_writer::emit_il_with_variables` prints in place of the number of the
+//! value.
+//!
+//! Unlike `CSE`, nothing is merged or removed from the SSA: the variables only name the values.
+
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAExtra, SSAWalk, SSA};
+use crate::middle::ssa::ssastorage::{NodeData, SSAStorage};
+use crate::middle::ssa::utils;
+
+use petgraph::graph::NodeIndex;
+use petgraph::unionfind::UnionFind;
+use petgraph::visit::NodeIndexable;
+
+use std::collections::HashMap;
+
+/// Annotates every value of `ssa` with the name of its variable. The variables are numbered in
+/// the order their first value is emitted by `ir_writer`, so the names are stable as long as the
+/// SSA doesn't change. Returns the number of variables.
+pub fn name_variables(ssa: &mut SSAStorage) -> usize {
+    let mut values = ssa
+        .entry_node()
+        .and_then(|entry| ssa.registers_in(entry))
+        .map(|regs| {
+            utils::register_state_info(regs, ssa)
+                .iter()
+                .map(|(_, &(value, _))| value)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    values.extend(ssa.inorder_walk().filter(|&n| is_value(ssa, n)));
+
+    let mut webs = UnionFind::new(ssa.g.node_bound());
+    for &value in &values {
+        let joined = if ssa.is_phi(value) || ssa.opcode(value) == Some(MOpcode::OpMov) {
+            ssa.operands_of(value)
+        } else {
+            continue;
+        };
+        for operand in joined.into_iter().filter(|&n| is_value(ssa, n)) {
+            webs.union(value.index(), operand.index());
+        }
+    }
+
+    let mut names: HashMap<usize, String> = HashMap::new();
+    for value in values {
+        let next = names.len();
+        let name = names
+            .entry(webs.find(value.index()))
+            .or_insert_with(|| format!("v{}", next))
+            .clone();
+        ssa.set_variable(&value, name);
+    }
+    names.len()
+}
+
+/// Returns `true` if `node` is a value which may be held by a variable: the constants and the
+/// calls, whose results are separate values, are not.
+fn is_value(ssa: &SSAStorage, node: NodeIndex) -> bool {
+    match ssa.g.node_weight(node) {
+        Some(&NodeData::Op(MOpcode::OpConst(_), _)) | Some(&NodeData::Op(MOpcode::OpCall, _)) => {
+            false
+        }
+        Some(&NodeData::Op(..))
+        | Some(&NodeData::Phi(..))
+        | Some(&NodeData::Comment(..))
+        | Some(&NodeData::Argument(..)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir_writer;
+    use crate::utils::test_support::load_ssa;
+
+    // `rax` is set in `bb_0x001000`, doubled in `bb_0x001004` and merged in `bb_0x001008`.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const PHI_WEB_SSA_TXT: &str = "\
+define-fun sym.web(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        [@0x001000.0002] %4: $Unknown1 = %1 == #x0;
+        JMP IF %4 0x001004.0000 ELSE 0x001008.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] %5: $Unknown64 = %3 * #x2;
+        JMP 0x001008.0000
+    bb_0x001008.0000(sz 0x1):
+        %6: $Unknown64 = Phi(%3, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $rdi = %1;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn phi_web_named_once() {
+        let mut ssa = load_ssa(PHI_WEB_SSA_TXT);
+
+        // `rdi`, `mem`, the web of `rax` and the condition.
+        assert_eq!(name_variables(&mut ssa), 4);
+        let phi = ssa.inorder_walk().find(|&n| ssa.is_phi(n)).unwrap();
+        let var = ssa.variable(&phi).unwrap();
+
+        let mut il = String::new();
+        ir_writer::emit_il_with_variables(&mut il, None, &ssa).unwrap();
+        let defs = il
+            .lines()
+            .filter(|l| l.contains(&format!("{}: $Unknown64 = ", var)))
+            .count();
+        assert_eq!(defs, 3);
+        assert!(il.contains(&format!("$rax = {};", var)));
+        // The value of `rdi` the web is computed from keeps a name of its own.
+        assert!(!il.contains(&format!("{}: $Unknown64 = $rdi", var)));
+    }
+}
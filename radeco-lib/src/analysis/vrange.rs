@@ -0,0 +1,419 @@
+//! Value range analysis over the SSA.
+//!
+//! Computes, for every value of a function, an unsigned interval containing all the values it may
+//! take. Constants, additions, subtractions and masks are evaluated on the intervals of their
+//! operands, and a comparison with a constant selecting a branch refines the interval of the
+//! compared value in the blocks dominated by that branch. Phis join the intervals of their
+//! operands.
+//!
+//! Joins at the blocks targeted by a back edge are widened to the full range of the value, so that
+//! the analysis terminates on loops. A few narrowing passes then recover the bounds given by the
+//! conditions of the loops, e.g. a counter compared with `16` gets the range `[0, 16]` at the
+//! header of its loop.
+
+use crate::analysis::loops::LoopForest;
+use crate::middle::ir::{self, MOpcode};
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::u64;
+
+/// Number of passes recomputing the ranges without widening once a fixed point is reached.
+const NARROWING_PASSES: usize = 2;
+
+/// Inclusive interval of unsigned values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interval {
+    pub lo: u64,
+    pub hi: u64,
+}
+
+impl Interval {
+    pub fn new(lo: u64, hi: u64) -> Interval {
+        assert!(lo <= hi, "Empty interval [{:#x}, {:#x}]", lo, hi);
+        Interval { lo, hi }
+    }
+
+    pub fn constant(value: u64) -> Interval {
+        Interval::new(value, value)
+    }
+
+    /// Every value representable in `width` bits.
+    pub fn full(width: u16) -> Interval {
+        Interval::new(0, ir::truncate_const(u64::MAX, width))
+    }
+
+    pub fn contains(&self, value: u64) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.lo == self.hi
+    }
+
+    /// Smallest interval containing both `self` and `other`.
+    pub fn join(&self, other: &Interval) -> Interval {
+        Interval::new(cmp::min(self.lo, other.lo), cmp::max(self.hi, other.hi))
+    }
+
+    /// Intersection of `self` and `other`, `None` if they are disjoint.
+    pub fn meet(&self, other: &Interval) -> Option<Interval> {
+        let lo = cmp::max(self.lo, other.lo);
+        let hi = cmp::min(self.hi, other.hi);
+        if lo <= hi {
+            Some(Interval::new(lo, hi))
+        } else {
+            None
+        }
+    }
+
+    /// Moves the bounds of `self` which `next` extends to the bounds of `full`.
+    fn widen(&self, next: &Interval, full: &Interval) -> Interval {
+        Interval::new(
+            if next.lo < self.lo { full.lo } else { self.lo },
+            if next.hi > self.hi { full.hi } else { self.hi },
+        )
+    }
+}
+
+/// Ranges of the values of a function, see the module documentation.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ValueRanges {
+    ranges: HashMap<NodeIndex, Interval>,
+    /// Ranges given, in each block, by the branches dominating it
+    facts: HashMap<NodeIndex, HashMap<NodeIndex, Interval>>,
+}
+
+impl ValueRanges {
+    pub fn new(ssa: &SSAStorage) -> ValueRanges {
+        let forest = LoopForest::new(ssa);
+
+        // Values in blocks reachable from the entry, dominators first.
+        let order = forest
+            .reverse_postorder()
+            .iter()
+            .flat_map(|&block| {
+                ssa.phis_in(block)
+                    .into_iter()
+                    .chain(ssa.exprs_in(block))
+                    .map(move |n| (n, block))
+            })
+            .collect::<Vec<_>>();
+
+        // Every cycle of values goes through a phi of a block targeted by a back edge, or by an
+        // edge entering an irreducible loop.
+        let targets = forest
+            .loops()
+            .iter()
+            .map(|l| l.header)
+            .chain(forest.irreducible_edges().iter().map(|&(_, h)| h))
+            .collect::<HashSet<_>>();
+        let widened = targets
+            .into_iter()
+            .flat_map(|block| ssa.phis_in(block))
+            .collect();
+
+        let mut analysis = RangeAnalysis {
+            ssa,
+            pending: ssa
+                .blocks()
+                .into_iter()
+                .flat_map(|block| ssa.phis_in(block).into_iter().chain(ssa.exprs_in(block)))
+                .collect(),
+            widened,
+            facts: branch_facts(ssa, &forest),
+            ranges: HashMap::new(),
+        };
+        analysis.run(&order);
+
+        ValueRanges {
+            ranges: analysis.ranges,
+            facts: analysis.facts,
+        }
+    }
+
+    /// Range of `node`, `None` if it is not reachable from the entry of the function.
+    pub fn range_of(&self, node: NodeIndex) -> Option<Interval> {
+        self.ranges.get(&node).cloned()
+    }
+
+    /// Range of `node` as seen from `block`, narrowed by the branches dominating `block`, e.g. the
+    /// bounds check of a switch. Unlike `range_of`, the values defined outside of the blocks, such
+    /// as the arguments, are bounded by the branches as well. `None` if nothing is known.
+    pub fn range_in(&self, node: NodeIndex, block: NodeIndex) -> Option<Interval> {
+        let fact = self.facts.get(&block).and_then(|facts| facts.get(&node));
+        match (self.range_of(node), fact) {
+            (Some(range), Some(fact)) => Some(fact.meet(&range).unwrap_or(range)),
+            (range, fact) => range.or_else(|| fact.cloned()),
+        }
+    }
+}
+
+struct RangeAnalysis<'a> {
+    ssa: &'a SSAStorage,
+    /// Values whose range is computed by the analysis
+    pending: HashSet<NodeIndex>,
+    /// Phis whose joins are widened
+    widened: HashSet<NodeIndex>,
+    /// Ranges given, in each block, by the branches dominating it
+    facts: HashMap<NodeIndex, HashMap<NodeIndex, Interval>>,
+    ranges: HashMap<NodeIndex, Interval>,
+}
+
+impl<'a> RangeAnalysis<'a> {
+    fn run(&mut self, order: &[(NodeIndex, NodeIndex)]) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(node, block) in order {
+                let next = match self.eval(node, block) {
+                    Some(next) => next,
+                    None => continue,
+                };
+                let range = match self.ranges.get(&node) {
+                    Some(old) if self.widened.contains(&node) => {
+                        old.widen(&old.join(&next), &Interval::full(width_of(self.ssa, node)))
+                    }
+                    Some(old) => old.join(&next),
+                    None => next,
+                };
+                changed |= self.ranges.insert(node, range) != Some(range);
+            }
+        }
+
+        for _ in 0..NARROWING_PASSES {
+            for &(node, block) in order {
+                let narrowed = self
+                    .eval(node, block)
+                    .and_then(|next| self.ranges.get(&node)?.meet(&next));
+                if let Some(range) = narrowed {
+                    self.ranges.insert(node, range);
+                }
+            }
+        }
+    }
+
+    /// Range of the operand `value`, as seen from `block`. `None` if it is not computed yet.
+    fn operand(&self, value: NodeIndex, block: Option<NodeIndex>) -> Option<Interval> {
+        let range = if let Some(c) = self.ssa.constant(value) {
+            Interval::constant(c)
+        } else if let Some(&range) = self.ranges.get(&value) {
+            range
+        } else if self.pending.contains(&value) {
+            return None;
+        } else {
+            Interval::full(width_of(self.ssa, value))
+        };
+        let fact = block
+            .and_then(|b| self.facts.get(&b))
+            .and_then(|facts| facts.get(&value));
+        Some(fact.and_then(|f| f.meet(&range)).unwrap_or(range))
+    }
+
+    fn eval(&self, node: NodeIndex, block: NodeIndex) -> Option<Interval> {
+        let full = Interval::full(width_of(self.ssa, node));
+        if self.ssa.is_phi(node) {
+            return self
+                .ssa
+                .operands_of(node)
+                .into_iter()
+                .filter_map(|op| self.operand(op, None))
+                .fold(None, |acc: Option<Interval>, r| {
+                    Some(acc.map_or(r, |acc| acc.join(&r)))
+                })
+                .map(|r| r.meet(&full).unwrap_or(full));
+        }
+
+        let opcode = match self.ssa.opcode(node) {
+            Some(opcode) => opcode,
+            None => return Some(full),
+        };
+        let ops = self
+            .ssa
+            .operands_of(node)
+            .into_iter()
+            .map(|op| self.operand(op, Some(block)))
+            .collect::<Option<Vec<_>>>()?;
+        let range = match (opcode, ops.as_slice()) {
+            (MOpcode::OpConst(c), _) => Interval::constant(ir::truncate_const(c, 64)),
+            (MOpcode::OpAdd, &[a, b]) => match a.hi.checked_add(b.hi) {
+                Some(hi) => Interval::new(a.lo + b.lo, hi),
+                None => full,
+            },
+            (MOpcode::OpSub, &[a, b]) if a.lo >= b.hi => Interval::new(a.lo - b.hi, a.hi - b.lo),
+            (MOpcode::OpAnd, &[a, b]) if a.is_constant() && b.is_constant() => {
+                Interval::constant(a.lo & b.lo)
+            }
+            // Masking keeps the value below both operands.
+            (MOpcode::OpAnd, &[a, b]) => Interval::new(0, cmp::min(a.hi, b.hi)),
+            (MOpcode::OpMov, &[a])
+            | (MOpcode::OpNarrow(_), &[a])
+            | (MOpcode::OpZeroExt(_), &[a]) => a,
+            (MOpcode::OpEq, _) | (MOpcode::OpLt, _) | (MOpcode::OpGt, _) => Interval::new(0, 1),
+            _ => full,
+        };
+        // Values overflowing the width of the node wrap around.
+        Some(if range.hi <= full.hi { range } else { full })
+    }
+}
+
+fn width_of(ssa: &SSAStorage, node: NodeIndex) -> u16 {
+    ssa.node_data(node)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width())
+        .unwrap_or(64)
+}
+
+/// Ranges known in each block reachable from the entry, from the comparisons selecting the
+/// branches that dominate it.
+fn branch_facts(
+    ssa: &SSAStorage,
+    forest: &LoopForest<NodeIndex>,
+) -> HashMap<NodeIndex, HashMap<NodeIndex, Interval>> {
+    let mut facts: HashMap<NodeIndex, HashMap<NodeIndex, Interval>> = HashMap::new();
+    for &block in forest.reverse_postorder() {
+        let mut known = forest
+            .idom(block)
+            .filter(|&dom| dom != block)
+            .and_then(|dom| facts.get(&dom))
+            .cloned()
+            .unwrap_or_default();
+        // Only the branch entering a block with a single predecessor holds in the whole block.
+        if let &[pred] = &ssa.preds_of(block)[..] {
+            if let Some((value, range)) = branch_fact(ssa, pred, block) {
+                let range = known
+                    .get(&value)
+                    .and_then(|r| r.meet(&range))
+                    .unwrap_or(range);
+                known.insert(value, range);
+            }
+        }
+        facts.insert(block, known);
+    }
+    facts
+}
+
+/// Range of the value compared with a constant by the selector of `pred`, when `pred` branches to
+/// `block`.
+fn branch_fact(
+    ssa: &SSAStorage,
+    pred: NodeIndex,
+    block: NodeIndex,
+) -> Option<(NodeIndex, Interval)> {
+    let branches = ssa.conditional_blocks(pred)?;
+    let taken = match (branches.true_side == block, branches.false_side == block) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => return None,
+    };
+    let cond = ssa.selector_in(pred)?;
+    let ops = ssa.operands_of(cond);
+    if ops.len() != 2 {
+        return None;
+    }
+    // Normalize to `value <op> c`.
+    let (value, opcode, c) = match (
+        ssa.opcode(cond)?,
+        ssa.constant(ops[0]),
+        ssa.constant(ops[1]),
+    ) {
+        (opcode, None, Some(c)) => (ops[0], opcode, c),
+        (MOpcode::OpLt, Some(c), None) => (ops[1], MOpcode::OpGt, c),
+        (MOpcode::OpGt, Some(c), None) => (ops[1], MOpcode::OpLt, c),
+        (MOpcode::OpEq, Some(c), None) => (ops[1], MOpcode::OpEq, c),
+        _ => return None,
+    };
+    let range = match (opcode, taken) {
+        (MOpcode::OpLt, true) if c > 0 => Interval::new(0, c - 1),
+        (MOpcode::OpLt, false) => Interval::new(c, u64::MAX),
+        (MOpcode::OpGt, true) if c < u64::MAX => Interval::new(c + 1, u64::MAX),
+        (MOpcode::OpGt, false) => Interval::new(0, c),
+        (MOpcode::OpEq, true) => Interval::constant(c),
+        _ => return None,
+    };
+    Some((value, range))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ssa::ssa_traits::SSAWalk;
+    use crate::utils::test_support::load_ssa;
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const MASK_SSA_TXT: &str = "\
+define-fun sym.mask(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = %1 & #xf;
+        [@0x001000.0002] %4: $Unknown64 = %3 + #x2;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $mem = %2;
+}
+";
+
+    // for (i = 0; i < 16; i++) {}
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const LOOP_SSA_TXT: &str = "\
+define-fun sym.count(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = #x0;
+        JMP 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        %4: $Unknown64 = Phi(%3, %6);
+        [@0x001004.0001] %5: $Unknown1 = %4 < #x10;
+        JMP IF %5 0x001008.0000 ELSE 0x00100c.0000
+    bb_0x001008.0000(sz 0x4):
+        [@0x001008.0001] %6: $Unknown64 = %4 + #x1;
+        JMP 0x001004.0000
+    bb_0x00100c.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $mem = %2;
+}
+";
+
+    fn nodes_with(ssa: &SSAStorage, opcode: MOpcode) -> Vec<NodeIndex> {
+        ssa.inorder_walk()
+            .filter(|&n| ssa.opcode(n) == Some(opcode.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn masked_value() {
+        let ssa = load_ssa(MASK_SSA_TXT);
+        let ranges = ValueRanges::new(&ssa);
+
+        let and = nodes_with(&ssa, MOpcode::OpAnd)[0];
+        assert_eq!(ranges.range_of(and), Some(Interval::new(0, 0xf)));
+        let add = nodes_with(&ssa, MOpcode::OpAdd)[0];
+        assert_eq!(ranges.range_of(add), Some(Interval::new(2, 0x11)));
+    }
+
+    #[test]
+    fn loop_counter() {
+        let ssa = load_ssa(LOOP_SSA_TXT);
+        let ranges = ValueRanges::new(&ssa);
+
+        let phi = ssa.inorder_walk().find(|&n| ssa.is_phi(n)).unwrap();
+        // Widened at the header, then bounded again by the loop condition.
+        assert_eq!(ranges.range_of(phi), Some(Interval::new(0, 0x10)));
+        let inc = nodes_with(&ssa, MOpcode::OpAdd)[0];
+        assert_eq!(ranges.range_of(inc), Some(Interval::new(1, 0x10)));
+    }
+}
@@ -0,0 +1,232 @@
+//! Emits ESIL back from the SSA of a basic block.
+//!
+//! Re-emitting ESIL from the SSA allows to check the translation of ESIL to SSA differentially:
+//! emulating the ESIL of the instructions of a block and the ESIL emitted from its SSA has to give
+//! the same registers and memory.
+//!
+//! Every value written to a register or stored to memory is emitted as the whole tree of
+//! operations computing it, in the order of the addresses of the block. The values defined outside
+//! of the block, e.g. the registers on entry of the function or the phis, are read back from the
+//! register holding them, which assumes that the register isn't overwritten by the block before
+//! the value is read. The control flow leaving the block isn't emitted.
+//!
+//! Only the opcodes with an ESIL counterpart are supported. The emitter fails with an
+//! `EmitError` rather than emitting ESIL with a different meaning.
+
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+use std::error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmitError {
+    /// The operation has no ESIL counterpart
+    Unsupported(NodeIndex, MOpcode),
+    /// The value is defined outside of the block, but no register is known to hold it
+    Unnamed(NodeIndex),
+}
+
+impl fmt::Display for EmitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmitError::Unsupported(node, ref op) => {
+                write!(f, "{:?} at {:?} can't be emitted as ESIL", op, node)
+            }
+            EmitError::Unnamed(node) => write!(f, "No register holds the value {:?}", node),
+        }
+    }
+}
+
+impl error::Error for EmitError {}
+
+/// Emits the ESIL of the register writes and the stores of `block`.
+pub fn emit_block(ssa: &SSAStorage, block: NodeIndex) -> Result<String, EmitError> {
+    let mut tokens = Vec::new();
+    for node in ssa.exprs_in(block) {
+        match ssa.opcode(node) {
+            Some(MOpcode::OpStore) => {
+                let operands = ssa.operands_of(node);
+                emit_into(ssa, block, operands[2], &mut tokens)?;
+                emit_into(ssa, block, operands[1], &mut tokens)?;
+                let bytes = access_size(ssa, node)
+                    .or_else(|| access_size(ssa, operands[2]))
+                    .ok_or(EmitError::Unsupported(node, MOpcode::OpStore))?;
+                tokens.push(format!("=[{}]", bytes));
+            }
+            Some(MOpcode::OpCall) => return Err(EmitError::Unsupported(node, MOpcode::OpCall)),
+            _ => {
+                for reg in written_registers(ssa, block, node) {
+                    emit_into(ssa, block, node, &mut tokens)?;
+                    tokens.push(reg);
+                    tokens.push("=".to_owned());
+                }
+            }
+        }
+    }
+    Ok(tokens.join(","))
+}
+
+/// Emits the ESIL computing `node`, an expression of `block`, which leaves its value on the top of
+/// the stack.
+pub fn emit_value(
+    ssa: &SSAStorage,
+    block: NodeIndex,
+    node: NodeIndex,
+) -> Result<String, EmitError> {
+    let mut tokens = Vec::new();
+    emit_into(ssa, block, node, &mut tokens)?;
+    Ok(tokens.join(","))
+}
+
+fn emit_into(
+    ssa: &SSAStorage,
+    block: NodeIndex,
+    node: NodeIndex,
+    tokens: &mut Vec<String>,
+) -> Result<(), EmitError> {
+    if let Some(value) = ssa.constant(node) {
+        tokens.push(format!("{:#x}", value));
+        return Ok(());
+    }
+    if !ssa.is_expr(node) || ssa.block_for(node) != Some(block) {
+        let reg = ssa.registers(node).into_iter().next();
+        tokens.push(reg.ok_or(EmitError::Unnamed(node))?);
+        return Ok(());
+    }
+
+    let opcode = ssa.opcode(node).unwrap_or(MOpcode::OpInvalid);
+    let operands = ssa.operands_of(node);
+    match (&opcode, esil_op(&opcode), operands.as_slice()) {
+        (MOpcode::OpLoad, _, &[_, addr]) => {
+            emit_into(ssa, block, addr, tokens)?;
+            let bytes = access_size(ssa, node).ok_or(EmitError::Unsupported(node, opcode))?;
+            tokens.push(format!("[{}]", bytes));
+        }
+        (&MOpcode::OpNarrow(width), _, &[value]) => {
+            emit_into(ssa, block, value, tokens)?;
+            if width < 64 {
+                tokens.push(format!("{:#x}", (1u64 << width) - 1));
+                tokens.push("&".to_owned());
+            }
+        }
+        // ESIL values are unsigned, so they are already zero extended.
+        (MOpcode::OpZeroExt(_), _, &[value]) => emit_into(ssa, block, value, tokens)?,
+        // A move, e.g. of a constant written to a register, is its operand.
+        (MOpcode::OpMov, _, &[value]) => emit_into(ssa, block, value, tokens)?,
+        (_, Some(op), &[value]) => {
+            emit_into(ssa, block, value, tokens)?;
+            tokens.push(op.to_owned());
+        }
+        // The left hand side is the operand on the top of the stack.
+        (_, Some(op), &[lhs, rhs]) => {
+            emit_into(ssa, block, rhs, tokens)?;
+            emit_into(ssa, block, lhs, tokens)?;
+            tokens.push(op.to_owned());
+        }
+        _ => return Err(EmitError::Unsupported(node, opcode)),
+    }
+    Ok(())
+}
+
+/// ESIL operator of the arithmetic and logic operations, as translated by `SSAConstruct`.
+fn esil_op(op: &MOpcode) -> Option<&'static str> {
+    Some(match *op {
+        MOpcode::OpAdd => "+",
+        MOpcode::OpSub => "-",
+        MOpcode::OpMul => "*",
+        MOpcode::OpDiv => "/",
+        MOpcode::OpMod => "%",
+        MOpcode::OpAnd => "&",
+        MOpcode::OpOr => "|",
+        MOpcode::OpXor => "^",
+        MOpcode::OpNot => "!",
+        MOpcode::OpLsl => "<<",
+        MOpcode::OpLsr => ">>",
+        MOpcode::OpRol => "<<<",
+        MOpcode::OpRor => ">>>",
+        MOpcode::OpLt => "<",
+        MOpcode::OpGt => ">",
+        _ => return None,
+    })
+}
+
+/// Size in bytes of the memory access of `node`, from its width.
+fn access_size(ssa: &SSAStorage, node: NodeIndex) -> Option<u16> {
+    let width = ssa.node_data(node).ok()?.vt.width().get_width()?;
+    match width {
+        8 | 16 | 32 | 64 => Some(width / 8),
+        _ => None,
+    }
+}
+
+/// Registers the expression `node` of `block` is written to. These are the whole registers, as
+/// wide as `node`, which are recorded to hold it, if it is used outside of the block. The
+/// intermediate values the register names are propagated to are left out this way.
+fn written_registers(ssa: &SSAStorage, block: NodeIndex, node: NodeIndex) -> Vec<String> {
+    let live_out = ssa
+        .uses_of(node)
+        .into_iter()
+        .any(|user| !ssa.is_expr(user) || ssa.block_for(user) != Some(block));
+    if !live_out {
+        return Vec::new();
+    }
+    let width = ssa
+        .node_data(node)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width());
+    ssa.registers(node)
+        .into_iter()
+        .filter(|reg| {
+            let reg_width = ssa
+                .regfile
+                .register_id_by_name(reg)
+                .and_then(|id| ssa.regfile.get_width(id));
+            reg_width.is_some() && reg_width == width.map(u64::from)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::radeco_containers::RadecoFunction;
+    use crate::frontend::ssaconstructor::SSAConstruct;
+    use crate::middle::ssa::ssa_traits::SSAWalk;
+    use crate::utils::test_support::reg_profile;
+    use esil::lexer::{Tokenize, Tokenizer};
+    use r2papi::structs::LOpInfo;
+    use serde_json;
+
+    #[test]
+    fn add_round_trip() {
+        let reg_profile = reg_profile();
+        // lea rax, [rbx + 0x10]; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"0x10,rbx,+,rax,=","offset":4096,"opcode":"lea rax, [rbx + 0x10]","type":"lea","size":4},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4100,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.offset = 0x1000;
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let add = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpAdd))
+            .unwrap();
+        let block = ssa.block_for(add).unwrap();
+        let emitted = emit_value(ssa, block, add).unwrap();
+        assert_eq!(
+            Tokenizer::tokenize(&emitted),
+            Tokenizer::tokenize("0x10,rbx,+")
+        );
+        let emitted = emit_block(ssa, block).unwrap();
+        assert!(emitted.starts_with("0x10,rbx,+,rax,="));
+    }
+}
@@ -0,0 +1,496 @@
+//! Concrete interpreter of the SSA of a function, to check what it does on given inputs.
+//!
+//! Starting from the values of the registers of the entry state, the blocks are run one after
+//! the other, following the branches taken, until the function returns. A phi takes the operand
+//! which was computed last, i.e. the one flowing in from the block run before it. Memory is a
+//! sparse map of bytes, which are 0 until written, and is accessed in little-endian.
+//!
+//! Only the integer operations are run. A value which can't be computed is left unknown, and the
+//! execution only stops if it is needed to go on, e.g. as the condition of a branch. Reaching a
+//! call stops the execution as well, since there is no model of what the callee does.
+
+use crate::middle::ir::{self, MOpcode};
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+
+use petgraph::graph::NodeIndex;
+
+use std::collections::HashMap;
+use std::{error, fmt};
+
+const FALSE_EDGE: u8 = 0;
+const TRUE_EDGE: u8 = 1;
+
+/// Reason for the execution to stop before the function returns.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InterpError {
+    /// The call at `address` was reached. Its callee needs a model to go on.
+    NeedsModel { address: u64, callee: String },
+    /// The value of `node`, at `address`, is needed but it can't be computed.
+    Unknown { address: u64, node: NodeIndex },
+    /// The operation at `address` isn't supported.
+    Unsupported { address: u64, opcode: MOpcode },
+    /// The block at `address` has no successor to go on with, e.g. it ends with an indirect
+    /// jump.
+    NoSuccessor(u64),
+    /// The given number of blocks were run without returning.
+    StepLimit(usize),
+}
+
+impl fmt::Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &InterpError::NeedsModel {
+                address,
+                ref callee,
+            } => write!(f, "Call to {} at {:#x} needs model", callee, address),
+            &InterpError::Unknown { address, node } => {
+                write!(f, "Value of {:?} at {:#x} is unknown", node, address)
+            }
+            &InterpError::Unsupported {
+                address,
+                ref opcode,
+            } => write!(f, "{} at {:#x} is not supported", opcode, address),
+            &InterpError::NoSuccessor(address) => {
+                write!(f, "No successor to go on with after {:#x}", address)
+            }
+            &InterpError::StepLimit(steps) => write!(f, "Did not return in {} blocks", steps),
+        }
+    }
+}
+
+impl error::Error for InterpError {}
+
+/// Byte-addressed memory, whose bytes are 0 until written.
+#[derive(Clone, Debug, Default)]
+pub struct Memory {
+    bytes: HashMap<u64, u8>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory::default()
+    }
+
+    /// Reads `size` bytes (at most 8) at `address`, in little-endian.
+    pub fn read(&self, address: u64, size: u16) -> u64 {
+        (0..u64::from(size.min(8))).rev().fold(0, |value, i| {
+            let byte = self.bytes.get(&address.wrapping_add(i)).cloned();
+            (value << 8) | u64::from(byte.unwrap_or(0))
+        })
+    }
+
+    /// Writes the lowest `size` bytes (at most 8) of `value` at `address`, in little-endian.
+    pub fn write(&mut self, address: u64, size: u16, value: u64) {
+        for i in 0..u64::from(size.min(8)) {
+            let byte = (value >> (8 * i)) as u8;
+            self.bytes.insert(address.wrapping_add(i), byte);
+        }
+    }
+}
+
+/// State of the function once it returned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Emulation {
+    /// Registers of the exit state, by name, `None` if the value is unknown.
+    pub registers: Vec<(String, Option<u64>)>,
+    /// Number of blocks run
+    pub steps: usize,
+}
+
+pub struct Interpreter<'a> {
+    ssa: &'a SSAStorage,
+    pub memory: Memory,
+    max_steps: usize,
+    values: HashMap<NodeIndex, u64>,
+    // Step at which each value was computed last, to choose the operand of phis.
+    defined: HashMap<NodeIndex, usize>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Interpreter of `ssa`, which gives up after running `max_steps` blocks.
+    pub fn new(ssa: &'a SSAStorage, max_steps: usize) -> Interpreter<'a> {
+        Interpreter {
+            ssa: ssa,
+            memory: Memory::new(),
+            max_steps: max_steps,
+            values: HashMap::new(),
+            defined: HashMap::new(),
+        }
+    }
+
+    /// Runs the function until it returns. The registers of the entry state take their value
+    /// from `registers`, by name, or are 0.
+    pub fn run(&mut self, registers: &HashMap<String, u64>) -> Result<Emulation, InterpError> {
+        let ssa = self.ssa;
+        let (entry, exit) = match (ssa.entry_node(), ssa.exit_node()) {
+            (Some(entry), Some(exit)) => (entry, exit),
+            _ => return Err(InterpError::NoSuccessor(0)),
+        };
+        self.values.clear();
+        self.defined.clear();
+        for (name, node) in self.register_state(entry) {
+            let value = registers.get(&name).cloned().unwrap_or(0);
+            let value = ir::truncate_const(value, self.width(node));
+            self.values.insert(node, value);
+        }
+
+        let mut steps = 0;
+        let mut prev = entry;
+        let mut block = self.successor(entry)?;
+        while block != exit {
+            if steps == self.max_steps {
+                return Err(InterpError::StepLimit(steps));
+            }
+            steps += 1;
+            self.run_phis(block, prev, steps);
+            for node in ssa.exprs_in(block) {
+                if let Some(value) = self.eval(node)? {
+                    self.values.insert(node, value);
+                    self.defined.insert(node, steps);
+                } else {
+                    self.values.remove(&node);
+                }
+            }
+            prev = block;
+            block = self.successor(block)?;
+        }
+
+        let registers = self
+            .register_state(exit)
+            .into_iter()
+            .filter(|&(_, node)| self.width(node) > 0)
+            .map(|(name, node)| (name, self.value(node)))
+            .collect();
+        Ok(Emulation {
+            registers: registers,
+            steps: steps,
+        })
+    }
+
+    /// Values of the register state of `block`, by register name.
+    fn register_state(&self, block: NodeIndex) -> Vec<(String, NodeIndex)> {
+        let state = match self.ssa.registers_in(block) {
+            Some(state) => state,
+            None => return Vec::new(),
+        };
+        let regfile = &self.ssa.regfile;
+        utils::register_state_info(state, self.ssa)
+            .into_iter()
+            .map(|(id, (node, _))| (regfile.get_name(id).unwrap_or("mem").to_owned(), node))
+            .collect()
+    }
+
+    fn width(&self, node: NodeIndex) -> u16 {
+        self.ssa
+            .node_data(node)
+            .ok()
+            .and_then(|nd| nd.vt.width().get_width())
+            .unwrap_or(64)
+    }
+
+    fn address(&self, node: NodeIndex) -> u64 {
+        self.ssa.address(node).map_or(0, |a| a.address)
+    }
+
+    fn value(&self, node: NodeIndex) -> Option<u64> {
+        self.ssa
+            .constant(node)
+            .or_else(|| self.values.get(&node).cloned())
+    }
+
+    fn needed(&self, node: NodeIndex, user: NodeIndex) -> Result<u64, InterpError> {
+        self.value(node).ok_or_else(|| InterpError::Unknown {
+            address: self.address(user),
+            node: node,
+        })
+    }
+
+    /// Sets the phis of `block`, entered from `prev`, all at once.
+    fn run_phis(&mut self, block: NodeIndex, prev: NodeIndex, step: usize) {
+        let phis = self
+            .ssa
+            .phis_in(block)
+            .into_iter()
+            .map(|phi| (phi, self.phi_value(phi, prev)))
+            .collect::<Vec<_>>();
+        for (phi, value) in phis {
+            match value {
+                Some(value) => {
+                    self.values.insert(phi, value);
+                    self.defined.insert(phi, step);
+                }
+                None => {
+                    self.values.remove(&phi);
+                }
+            }
+        }
+    }
+
+    fn phi_value(&self, phi: NodeIndex, prev: NodeIndex) -> Option<u64> {
+        let known = self
+            .ssa
+            .operands_of(phi)
+            .into_iter()
+            .filter(|&op| self.value(op).is_some())
+            .map(|op| (op, self.defined.get(&op).cloned().unwrap_or(0)))
+            .collect::<Vec<_>>();
+        let last = known.iter().map(|&(_, step)| step).max()?;
+        let candidates = known
+            .into_iter()
+            .filter(|&(_, step)| step == last)
+            .map(|(op, _)| op)
+            .collect::<Vec<_>>();
+        // The values defined before the function runs, e.g. constants, are told apart by the
+        // predecessor they flow in from.
+        let op = if candidates.len() == 1 {
+            candidates[0]
+        } else {
+            utils::phi_sources(phi, self.ssa)
+                .into_iter()
+                .find(|&(op, ref preds)| candidates.contains(&op) && preds.contains(&prev))
+                .map(|(op, _)| op)?
+        };
+        let value = self.value(op)?;
+        Some(ir::truncate_const(value, self.width(phi)))
+    }
+
+    /// The block run after `block`.
+    fn successor(&self, block: NodeIndex) -> Result<NodeIndex, InterpError> {
+        let ssa = self.ssa;
+        let edges = ssa.outgoing_edges(block);
+        let edge_type = match ssa.selector_in(block) {
+            Some(selector) => {
+                let cond = self.needed(selector, selector)?;
+                Some(if cond != 0 { TRUE_EDGE } else { FALSE_EDGE })
+            }
+            None => None,
+        };
+        let mut targets = edges
+            .into_iter()
+            .filter(|&(_, ety)| edge_type.map_or(true, |t| t == ety))
+            .filter_map(|(edge, _)| ssa.g.edge_endpoints(edge).map(|(_, t)| t))
+            .collect::<Vec<_>>();
+        targets.sort();
+        targets.dedup();
+        match &*targets {
+            &[target] => Ok(target),
+            _ => Err(InterpError::NoSuccessor(
+                ssa.starting_address(block).map_or(0, |a| a.address),
+            )),
+        }
+    }
+
+    /// Runs the operation `node`, returning its value if it is known.
+    fn eval(&mut self, node: NodeIndex) -> Result<Option<u64>, InterpError> {
+        let ssa = self.ssa;
+        let opcode = match ssa.opcode(node) {
+            Some(opcode) => opcode,
+            None => return Ok(None),
+        };
+        let address = self.address(node);
+        let operands = ssa.operands_of(node);
+        let w = self.width(node);
+        let ops = operands
+            .iter()
+            .map(|&op| self.value(op).map(|v| (v, self.width(op))))
+            .collect::<Vec<_>>();
+        // Operand `i`, truncated to its width.
+        let op = |i: usize| {
+            ops.get(i)
+                .cloned()
+                .and_then(|o| o)
+                .map(|(v, width)| ir::truncate_const(v, width))
+        };
+
+        let value = match opcode {
+            MOpcode::OpCall => {
+                let callee = operands
+                    .get(0)
+                    .and_then(|&target| {
+                        ssa.constant(target)
+                            .map(|c| format!("{:#x}", c))
+                            .or_else(|| ssa.comment(target))
+                    })
+                    .unwrap_or_else(|| "unknown".to_owned());
+                return Err(InterpError::NeedsModel {
+                    address: address,
+                    callee: callee,
+                });
+            }
+            MOpcode::OpStore => {
+                let (target, value) = match (operands.get(1), operands.get(2)) {
+                    (Some(&target), Some(&value)) => (target, value),
+                    _ => return Err(InterpError::Unknown { address, node }),
+                };
+                let target = self.needed(target, node)?;
+                let size = match w {
+                    0 => self.width(value) / 8,
+                    w => w / 8,
+                };
+                let value = self.needed(value, node)?;
+                self.memory.write(target, size, value);
+                // Memory itself has no value.
+                Some(0)
+            }
+            MOpcode::OpLoad => op(1).map(|target| self.memory.read(target, w / 8)),
+            MOpcode::OpITE => match op(0) {
+                Some(0) => op(2),
+                Some(_) => op(1),
+                None => None,
+            },
+            MOpcode::OpMov | MOpcode::OpNarrow(_) | MOpcode::OpZeroExt(_) => op(0),
+            MOpcode::OpSignExt(_) => ops
+                .get(0)
+                .cloned()
+                .and_then(|o| o)
+                .map(|(v, width)| ir::signed_const(v, width) as u64),
+            MOpcode::OpNot | MOpcode::OpByteSwap(_) => op(0).and_then(|v| opcode.eval_unop(v)),
+            MOpcode::OpRol | MOpcode::OpRor if w > 0 && w <= 64 => match (op(0), op(1)) {
+                (Some(v), Some(amount)) => Some(rotate(&opcode, v, amount, w)),
+                _ => None,
+            },
+            MOpcode::OpLsl | MOpcode::OpLsr => match (op(0), op(1)) {
+                (Some(_), Some(amount)) if amount >= 64 => Some(0),
+                (Some(v), Some(amount)) => opcode.eval_binop(v, amount),
+                _ => None,
+            },
+            MOpcode::OpAdd
+            | MOpcode::OpSub
+            | MOpcode::OpMul
+            | MOpcode::OpDiv
+            | MOpcode::OpMod
+            | MOpcode::OpAnd
+            | MOpcode::OpOr
+            | MOpcode::OpXor
+            | MOpcode::OpEq
+            | MOpcode::OpGt
+            | MOpcode::OpLt => match (op(0), op(1)) {
+                (Some(_), Some(0)) if opcode == MOpcode::OpDiv || opcode == MOpcode::OpMod => {
+                    return Err(InterpError::Unsupported { address, opcode });
+                }
+                (Some(lhs), Some(rhs)) => opcode.eval_binop(lhs, rhs),
+                _ => None,
+            },
+            MOpcode::OpNop | MOpcode::OpConst(_) => None,
+            _ => return Err(InterpError::Unsupported { address, opcode }),
+        };
+        Ok(value.map(|v| ir::truncate_const(v, w)))
+    }
+}
+
+/// `value` of `width` bits rotated by `amount`.
+fn rotate(opcode: &MOpcode, value: u64, amount: u64, width: u16) -> u64 {
+    let width = u64::from(width);
+    let amount = amount % width;
+    if amount == 0 {
+        return value;
+    }
+    let (hi, lo) = if *opcode == MOpcode::OpRol {
+        (value << amount, value >> (width - amount))
+    } else {
+        (value << (width - amount), value >> amount)
+    };
+    ir::truncate_const(hi | lo, width as u16)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_support::load_ssa;
+
+    // rax = rdi + rsi, or 0 if it is 0x10
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const ADD_SSA_TXT: &str = "\
+define-fun sym.add(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown64 = $rsp;
+        %4: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %5: $Unknown64 = %1 + %2;
+        [@0x001004.0001] %6: $Unknown64 = %3 - #x8;
+        [@0x001004.0002] %7: $Unknown0 = Store(%4, %6, %5);
+        [@0x001004.0003] %8: $Unknown64 = Load(%7, %6);
+        [@0x001004.0004] %9: $Unknown1 = %8 == #x10;
+        JMP IF %9 0x001010.0000 ELSE 0x001008.0000
+    bb_0x001008.0000(sz 0x4):
+        JMP 0x001014.0000
+    bb_0x001010.0000(sz 0x4):
+        [@0x001010.0001] %10: $Unknown64 = %8 & #x0;
+        JMP 0x001014.0000
+    bb_0x001014.0000(sz 0x1):
+        %11: $Unknown64 = Phi(%8, %10);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %11;
+        $rsp = %3;
+        $mem = %7;
+}
+";
+
+    // Calls `0x2000`
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALL_SSA_TXT: &str = "\
+define-fun sym.call(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001008.0001] (%3: $Unknown0 = $mem) = CALL #x2000($rdi=%1, $mem=%2);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %3;
+}
+";
+
+    fn registers(regs: &[(&str, u64)]) -> HashMap<String, u64> {
+        regs.iter().map(|&(r, v)| (r.to_owned(), v)).collect()
+    }
+
+    #[test]
+    fn add_and_return() {
+        let ssa = load_ssa(ADD_SSA_TXT);
+        let rax = |res: &Emulation| {
+            res.registers
+                .iter()
+                .find(|&&(ref name, _)| name == "rax")
+                .and_then(|&(_, value)| value)
+        };
+
+        let mut interp = Interpreter::new(&ssa, 16);
+        let res = interp
+            .run(&registers(&[("rdi", 3), ("rsi", 5), ("rsp", 0x7000)]))
+            .unwrap();
+        assert_eq!(rax(&res), Some(8));
+        assert_eq!(res.steps, 3);
+        // The sum was spilled to the stack.
+        assert_eq!(interp.memory.read(0x6ff8, 8), 8);
+        assert!(res.registers.iter().all(|&(ref name, _)| name != "mem"));
+
+        let res = interp.run(&registers(&[("rdi", 0xc), ("rsi", 4)])).unwrap();
+        assert_eq!(rax(&res), Some(0));
+
+        // Not enough steps to reach the return.
+        let mut interp = Interpreter::new(&ssa, 2);
+        assert_eq!(interp.run(&registers(&[])), Err(InterpError::StepLimit(2)));
+    }
+
+    #[test]
+    fn call_needs_model() {
+        let ssa = load_ssa(CALL_SSA_TXT);
+        let mut interp = Interpreter::new(&ssa, 16);
+        match interp.run(&HashMap::new()) {
+            Err(InterpError::NeedsModel { address, callee }) => {
+                assert_eq!(address, 0x1008);
+                assert_eq!(callee, "0x2000");
+            }
+            res => panic!("Expected the call to stop the execution, got {:?}", res),
+        }
+    }
+}
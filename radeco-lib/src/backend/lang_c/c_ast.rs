@@ -44,6 +44,11 @@ impl Ty {
         base = BTy::Array(Box::new(base), elements);
         Ty::new(base, signed, long)
     }
+
+    /// Fixed width integer, e.g. `uint8_t` or `int32_t`.
+    pub fn new_fixed(width: u16, signed: bool) -> Ty {
+        Ty::new(BTy::IntN(width), signed, 0)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -54,6 +59,8 @@ pub enum BTy {
     Char,
     Double,
     Void,
+    /// Integer of the given width in bits, as in `<stdint.h>`.
+    IntN(u16),
     /// Pointer to type `BTy`.
     Ptr(Box<BTy>),
     /// Array of type `BTy` and length.
@@ -62,6 +69,11 @@ pub enum BTy {
 
 impl fmt::Display for Ty {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let BTy::IntN(width) = self.base_type {
+            let prefix = if self.signed { "" } else { "u" };
+            return write!(f, "{}int{}_t", prefix, width);
+        }
+
         let mut result = format!("{}", self.base_type);
 
         if self.long == 1 {
@@ -86,6 +98,7 @@ impl fmt::Display for BTy {
             BTy::Char => "char".to_owned(),
             BTy::Double => "double".to_owned(),
             BTy::Void => "void".to_owned(),
+            BTy::IntN(width) => format!("int{}_t", width),
             BTy::Ptr(ref bty) => format!("*{}", bty),
             BTy::Array(ref bty, count) => format!("{}[{}]", bty, count - 1),
         };
@@ -123,9 +136,15 @@ pub enum Expr {
     Add,
     //Add field `SignExt`, `ZeroExt`, `Narrow`
     Cast(usize),
+    // Cast to a C type, e.g. `(uint8_t)x`
+    TyCast(Ty),
+    // Byte swap of a value of the given width, e.g. `__builtin_bswap32`
+    ByteSwap(usize),
     Sub,
     Mul,
     DeRef,
+    // Dereference through a pointer to a C type, e.g. `*(uint32_t *)(p)`
+    TyDeRef(Ty),
     Div,
     Mod,
     Shr,
@@ -140,6 +159,8 @@ pub enum Expr {
     Lt,
     LtEq,
     Eq,
+    // Call of the named function with the operands as arguments, e.g. returned by a tail call
+    Call(String),
 }
 
 #[derive(Clone, Debug)]
@@ -383,6 +404,7 @@ impl CAST {
                     match self.ast.node_weight(arg) {
                         Some(&CASTNode::Var(ref v)) => v.clone(),
                         Some(&CASTNode::Constant(_, ref c)) => c.clone(),
+                        Some(&CASTNode::ExpressionNode(_)) => self.emit_c(&arg, 0, true),
                         _ => "unknown".to_string(),
                     }
                 } else {
@@ -404,6 +426,7 @@ impl CAST {
             match self.ast.node_weight(_value) {
                 Some(&CASTNode::Var(ref v)) => v.clone(),
                 Some(&CASTNode::Constant(_, ref c)) => c.clone(),
+                Some(&CASTNode::ExpressionNode(_)) => self.emit_c(&_value, 0, true),
                 _ => "unknown".to_string(),
             }
         } else {
@@ -572,6 +595,14 @@ impl CAST {
                         format_with_indent(&op_str[0], indent),
                         &size.to_string()
                     ),
+                    Expr::TyCast(ref ty) => {
+                        format!("{}(({}){})", format_with_indent("", indent), ty, &op_str[0])
+                    }
+                    Expr::ByteSwap(size) => format!(
+                        "__builtin_bswap{}({})",
+                        size,
+                        format_with_indent(&op_str[0], indent)
+                    ),
                     Expr::Sub => format!(
                         "({} - {})",
                         format_with_indent(&op_str[0], indent),
@@ -583,6 +614,12 @@ impl CAST {
                         &op_str[1]
                     ),
                     Expr::DeRef => format!("{}*({})", format_with_indent("", indent), &op_str[0]),
+                    Expr::TyDeRef(ref ty) => format!(
+                        "{}*({} *)({})",
+                        format_with_indent("", indent),
+                        ty,
+                        &op_str[0]
+                    ),
                     Expr::Div => format!(
                         "({} / {})",
                         format_with_indent(&op_str[0], indent),
@@ -645,6 +682,11 @@ impl CAST {
                         format_with_indent(&op_str[0], indent),
                         &op_str[1]
                     ),
+                    Expr::Call(ref name) => format!(
+                        "{}({})",
+                        format_with_indent(name, indent),
+                        op_str.join(", ")
+                    ),
                 }
             }
             CASTNode::Var(ref ident) => ident.clone(),
@@ -99,6 +99,8 @@ pub struct CCFG {
     label_map: HashMap<CCFGRef, String>,
     /// Debug information retrieved from SSA
     debug_info: HashMap<CCFGRef, String>,
+    /// Comments emitted along with the actions
+    comments: HashMap<CCFGRef, String>,
 }
 
 /// Returns nodes which is connected with given type of edge
@@ -125,9 +127,20 @@ impl CCFG {
             exprs: Vec::new(),
             label_map: HashMap::new(),
             debug_info: HashMap::new(),
+            comments: HashMap::new(),
         }
     }
 
+    /// Attach a comment to the given action, printed after its statement. The comments attached
+    /// to the same action are joined with `; `.
+    pub fn comment_at(&mut self, node: CCFGRef, comment: &str) {
+        let comment = match self.comments.get(&node) {
+            Some(c) => format!("{}; {}", c, comment),
+            None => comment.to_string(),
+        };
+        self.comments.insert(node, comment);
+    }
+
     /// Append a string for given node
     pub fn debug_info_at(&mut self, node: CCFGRef, comment: String) {
         let s = if let Some(c) = self.debug_info.get(&node).cloned() {
@@ -182,9 +195,18 @@ impl CCFG {
     }
 
     pub fn deref(&mut self, operand: CCFGRef) -> CCFGRef {
+        self.deref_with(operand, c_ast::Expr::DeRef)
+    }
+
+    /// Like `deref`, but `operand` is dereferenced as a pointer to `ty`.
+    pub fn deref_as(&mut self, operand: CCFGRef, ty: Ty) -> CCFGRef {
+        self.deref_with(operand, c_ast::Expr::TyDeRef(ty))
+    }
+
+    fn deref_with(&mut self, operand: CCFGRef, deref: c_ast::Expr) -> CCFGRef {
         let node = self
             .g
-            .add_node(CCFGNode::Value(ValueNode::Expression(c_ast::Expr::DeRef)));
+            .add_node(CCFGNode::Value(ValueNode::Expression(deref)));
         let _ = self
             .g
             .add_edge(node, operand, CCFGEdge::Value(ValueEdge::DeRef));
@@ -903,7 +925,7 @@ impl<'a> CASTConverter<'a> {
 
     pub fn to_c_ast_single(&mut self, current_node: CCFGRef) -> Result<CASTRef, &'static str> {
         let idx = self.cfg.g.node_weight(current_node).cloned();
-        match idx {
+        let ast_node = match idx {
             Some(CCFGNode::Action(ActionNode::Assignment)) => {
                 self.to_c_ast_assignment(current_node)
             }
@@ -921,7 +943,11 @@ impl<'a> CASTConverter<'a> {
                 radeco_err!("Unreachable node {:?}", idx);
                 unreachable!()
             }
+        };
+        if let (&Ok(node), Some(comment)) = (&ast_node, self.cfg.comments.get(&current_node)) {
+            self.ast.comment_at(node, &format!(" {}", comment));
         }
+        ast_node
     }
 
     fn to_c_ast_assignment(&mut self, node: CCFGRef) -> Result<CASTRef, &'static str> {
@@ -939,10 +965,10 @@ impl<'a> CASTConverter<'a> {
         }
     }
 
-    fn to_c_ast_call(&mut self, node: CCFGRef, name: &str) -> Result<CASTRef, &'static str> {
+    fn to_c_ast_call(&mut self, call: CCFGRef, name: &str) -> Result<CASTRef, &'static str> {
         let args = self
             .cfg
-            .args_call(node)
+            .args_call(call)
             .unwrap_or(Vec::new())
             .into_iter()
             .map(|arg| {
@@ -955,7 +981,7 @@ impl<'a> CASTConverter<'a> {
             .collect();
         let ret_node_opt = self
             .cfg
-            .func_val(node)
+            .func_val(call)
             .and_then(|x| self.node_map.get(&x).map(|a| *a));
         let mut node = self.ast.call_func(name, args);
         if let Some(ret_node) = ret_node_opt {
@@ -7,13 +7,19 @@
 use super::c_ast;
 use super::c_ast::Ty;
 use super::c_cfg::{CCFGRef, CCFG};
-use crate::frontend::radeco_containers::RadecoFunction;
-use crate::middle::ir::{MAddress, MOpcode};
+use crate::analysis::intrinsics::Intrinsic;
+use crate::frontend::radeco_containers::{
+    self, BindingType, CallContextInfo, RadecoFunction, RadecoModule,
+};
+use crate::middle::ir::{ConstFormat, MAddress, MOpcode};
 use crate::middle::ssa::cfg_traits::CFG;
-use crate::middle::ssa::ssa_traits::{SSAWalk, SSA};
+use crate::middle::ssa::ssa_traits::{SSAExtra, SSAWalk, SSA};
 use crate::middle::ssa::ssastorage::{NodeData, SSAStorage};
 use crate::middle::ssa::utils;
 use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use r2papi::structs::LSectionInfo;
 use std::collections::{HashMap, HashSet};
 
 fn is_debug() -> bool {
@@ -25,15 +31,118 @@ pub fn recover_c_cfg(
     rfn: &RadecoFunction,
     fname_map: &HashMap<u64, String>,
     strings: &HashMap<u64, String>,
+) -> CCFG {
+    recover_c_cfg_with(rfn, fname_map, strings, RenderOptions::default())
+}
+
+/// Options controlling how the values of the recovered CCFG are rendered.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderOptions<'a> {
+    /// Format of the constants.
+    pub const_format: ConstFormat,
+    /// Constants pointing into one of these sections are labeled with its name, e.g.
+    /// `0x601040 /* .data */`. Constants annotated with a flag (see
+    /// `RadecoFunction::annotate_flags`) are labeled with the flag instead.
+    pub sections: &'a [LSectionInfo],
+    /// Render explicit C types: width changes become casts such as `(uint8_t)x`, and memory is
+    /// accessed through pointers to the accessed width, e.g. `*(uint32_t *)(p)`.
+    pub c_types: bool,
+    /// Call sites resolved to their callee. Their arguments are printed following the signature
+    /// of the callee, while the other calls are annotated with the registers they clobber.
+    pub calls: &'a [ResolvedCall<'a>],
+}
+
+/// A call site together with the function it calls.
+#[derive(Clone, Copy, Debug)]
+pub struct ResolvedCall<'a> {
+    pub context: &'a CallContextInfo,
+    pub callee: &'a RadecoFunction,
+}
+
+/// Resolves the call sites of `rfn` to their callee, as recorded in the call graph of `rmod`.
+pub fn resolve_calls<'a>(rmod: &'a RadecoModule, rfn: &RadecoFunction) -> Vec<ResolvedCall<'a>> {
+    if rmod.callgraph.node_weight(rfn.cgid()).is_none() {
+        return Vec::new();
+    }
+    rmod.callgraph
+        .edges_directed(rfn.cgid(), Direction::Outgoing)
+        .filter_map(|e| {
+            let callee = rmod.functions.get(&rmod.callgraph[e.target()])?;
+            Some(ResolvedCall {
+                context: e.weight(),
+                callee,
+            })
+        })
+        .collect()
+}
+
+/// Values of the caller bound to the signature of the callee at a resolved call site.
+#[derive(Clone, Debug)]
+struct CallBinding {
+    /// Values passed as the arguments, in order.
+    args: Vec<SSARef>,
+    /// Value of the register holding the return value after the call.
+    ret: Option<SSARef>,
+}
+
+impl CallBinding {
+    fn new(call: &ResolvedCall, ssa: &SSAStorage) -> CallBinding {
+        let bindings = call.callee.bindings();
+        // Stack arguments come after the register arguments.
+        let mut args = call
+            .context
+            .map
+            .iter()
+            .filter_map(|&(value, param)| {
+                let binding = bindings.iter().find(|b| b.idx == param)?;
+                match binding.btype {
+                    BindingType::RegisterArgument(i) => Some(((0, i), value)),
+                    BindingType::StackArgument(i) => Some(((1, i), value)),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        args.sort_by_key(|a| a.0);
+        let ret = bindings
+            .iter()
+            .find(|b| b.btype.is_return())
+            .and_then(|b| b.ridx)
+            .and_then(|ridx| {
+                utils::call_rets(call.context.csite_node, ssa)
+                    .into_iter()
+                    .find(|&(idx, _)| idx.to_u8() as u64 == ridx)
+                    .map(|(_, (node, _))| node)
+            });
+        CallBinding {
+            args: args.into_iter().map(|(_, v)| v).collect(),
+            ret,
+        }
+    }
+}
+
+/// Like `recover_c_cfg`, but values are rendered according to `options`.
+pub fn recover_c_cfg_with(
+    rfn: &RadecoFunction,
+    fname_map: &HashMap<u64, String>,
+    strings: &HashMap<u64, String>,
+    options: RenderOptions,
 ) -> CCFG {
     let mut builder = CCFGBuilder::new(rfn, fname_map);
-    let data_graph = CCFGDataMap::recover_data(rfn, &mut builder.cfg, strings);
+    let data_graph = CCFGDataMap::recover_data(rfn, &mut builder.cfg, strings, options);
     builder.datamap = data_graph;
     builder.cfg_from_ssa();
     builder.insert_jumps();
     builder.cfg
 }
 
+// Name of the function in the header, the flag at its offset if it was annotated with one.
+fn function_name(rfn: &RadecoFunction) -> String {
+    let ssa = rfn.ssa();
+    ssa.entry_node()
+        .and_then(|entry| ssa.flags(&entry))
+        .unwrap_or_else(|| rfn.name.to_string())
+}
+
 fn ret_value_string(rfn: &RadecoFunction) -> Option<String> {
     let ret_reg_opt = rfn.callconv.clone();
     if ret_reg_opt.is_none() {
@@ -42,6 +151,24 @@ fn ret_value_string(rfn: &RadecoFunction) -> Option<String> {
     ret_reg_opt.unwrap().ret
 }
 
+/// Name of the call standing for a string operation, after the compiler intrinsics of the same
+/// instructions, e.g. `__movsb(dst, src, count)`.
+fn string_op_name(op: &MOpcode) -> String {
+    let (name, width) = match *op {
+        MOpcode::OpRepMovs(w) => ("movs", w),
+        MOpcode::OpRepScas(w) => ("scas", w),
+        MOpcode::OpRepStos(w) => ("stos", w),
+        _ => return String::new(),
+    };
+    let suffix = match width {
+        8 => "b",
+        16 => "w",
+        32 => "d",
+        _ => "q",
+    };
+    format!("__{}{}", name, suffix)
+}
+
 type SSARef = NodeIndex;
 // CCFGBuilder constructs CCFG from RadecoFunction
 struct CCFGBuilder<'a> {
@@ -53,11 +180,13 @@ struct CCFGBuilder<'a> {
     fname_map: &'a HashMap<u64, String>,
     action_map: HashMap<SSARef, CCFGRef>,
     datamap: CCFGDataMap<'a>,
+    // Call returned by the blocks ending in a tail call, see `handle_return`
+    tail_calls: HashMap<SSARef, CCFGRef>,
 }
 
 impl<'a> CCFGBuilder<'a> {
     fn new(rfn: &'a RadecoFunction, fname_map: &'a HashMap<u64, String>) -> CCFGBuilder<'a> {
-        let cfg = CCFG::new(rfn.name.as_ref());
+        let cfg = CCFG::new(&function_name(rfn));
         CCFGBuilder {
             last_action: cfg.entry,
             cfg: cfg,
@@ -66,6 +195,7 @@ impl<'a> CCFGBuilder<'a> {
             fname_map: fname_map,
             action_map: HashMap::new(),
             datamap: CCFGDataMap::new(rfn),
+            tail_calls: HashMap::new(),
         }
     }
 
@@ -125,7 +255,17 @@ impl<'a> CCFGBuilder<'a> {
         args.into_iter().map(|(_, n)| n).collect()
     }
 
-    fn call_action(&mut self, call_node: SSARef) -> CCFGRef {
+    // Names of the registers clobbered by a call, i.e. the ones it defines.
+    fn clobbered_regs(&self, call_node: SSARef) -> Vec<String> {
+        utils::call_rets(call_node, self.ssa)
+            .into_iter()
+            .filter_map(|(idx, _)| self.ssa.regfile.get_name(idx))
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    // Name of the callee of a call, along with its arguments and the variable it returns into.
+    fn call_parts(&self, call_node: SSARef) -> (String, Vec<CCFGRef>, Option<CCFGRef>) {
         let call_info = utils::call_info(call_node, self.ssa).expect("This should not be `None`");
         let callee_node = call_info.target;
         let func_name = {
@@ -139,8 +279,15 @@ impl<'a> CCFGBuilder<'a> {
                 "unknown".to_string()
             }
         };
-        let args = self
-            .args_inorder(call_node)
+        let binding = self.datamap.calls.get(&call_node).cloned();
+        let (arg_nodes, ret_val_node) = match binding {
+            Some(ref b) => (
+                b.args.clone(),
+                b.ret.and_then(|n| self.datamap.var_map.get(&n).cloned()),
+            ),
+            None => (self.args_inorder(call_node), self.return_node(call_node)),
+        };
+        let args = arg_nodes
             .into_iter()
             .map(|n| {
                 self.datamap
@@ -150,10 +297,50 @@ impl<'a> CCFGBuilder<'a> {
                     .unwrap_or(self.cfg.unknown)
             })
             .collect::<Vec<_>>();
-        let ret_val_node = self.return_node(call_node);
+        (func_name, args, ret_val_node)
+    }
+
+    fn is_tail_call(&self, call_node: SSARef) -> bool {
+        self.ssa
+            .address(call_node)
+            .map_or(false, |a| self.rfn.tail_calls().contains_key(&a.address))
+    }
+
+    fn call_action(&mut self, call_node: SSARef) -> CCFGRef {
+        let (func_name, args, ret_val_node) = self.call_parts(call_node);
         self.last_action =
             self.cfg
                 .call_func(&func_name, args.as_slice(), self.last_action, ret_val_node);
+        if !self.datamap.calls.contains_key(&call_node) {
+            let clobbered = self.clobbered_regs(call_node);
+            if !clobbered.is_empty() {
+                let comment = format!("unresolved call, clobbers {}", clobbered.join(", "));
+                self.cfg.comment_at(self.last_action, &comment);
+            }
+        }
+        self.last_action
+    }
+
+    /// Renders the `OpCustom` standing for an inlined `memcpy`, `memset` or `strlen` as a call.
+    fn intrinsic_action(&mut self, node: SSARef, name: &str) -> CCFGRef {
+        // The first operand is the memory state.
+        let args = self
+            .ssa
+            .operands_of(node)
+            .into_iter()
+            .skip(1)
+            .map(|n| {
+                self.datamap
+                    .var_map
+                    .get(&n)
+                    .cloned()
+                    .unwrap_or(self.cfg.unknown)
+            })
+            .collect::<Vec<_>>();
+        let ret_val_node = self.datamap.var_map.get(&node).cloned();
+        self.last_action =
+            self.cfg
+                .call_func(name, args.as_slice(), self.last_action, ret_val_node);
         self.last_action
     }
 
@@ -169,6 +356,15 @@ impl<'a> CCFGBuilder<'a> {
         let op = self.ssa.opcode(node).unwrap_or(MOpcode::OpInvalid);
         radeco_trace!("CCFGBuilder::recover {:?} @ {:?}", op, node);
         match op {
+            MOpcode::OpCall if self.is_tail_call(node) => {
+                // Rendered as the value returned by its block, see `handle_return`.
+                let (func_name, args, _) = self.call_parts(node);
+                let call = self.cfg.expr(&args, c_ast::Expr::Call(func_name));
+                if let Some(block) = self.ssa.block_for(node) {
+                    self.tail_calls.insert(block, call);
+                }
+                self.last_action
+            }
             MOpcode::OpCall => {
                 let ret = self.call_action(node);
                 if is_debug() {
@@ -223,6 +419,12 @@ impl<'a> CCFGBuilder<'a> {
                 }
                 ret
             }
+            MOpcode::OpCustom(ref name) => self.intrinsic_action(node, name),
+            MOpcode::OpRepMovs(_) | MOpcode::OpRepScas(_) | MOpcode::OpRepStos(_) => {
+                let name = string_op_name(&op);
+                self.intrinsic_action(node, &name)
+            }
+            MOpcode::OpTrap => self.intrinsic_action(node, "__builtin_trap"),
             _ => unreachable!(),
         }
     }
@@ -232,6 +434,9 @@ impl<'a> CCFGBuilder<'a> {
         match op {
             MOpcode::OpCall | MOpcode::OpStore => true,
             MOpcode::OpLoad if self.datamap.is_used_by_call_store(node) => true,
+            MOpcode::OpCustom(ref name) => Intrinsic::from_name(name).is_some(),
+            MOpcode::OpRepMovs(_) | MOpcode::OpRepScas(_) | MOpcode::OpRepStos(_) => true,
+            MOpcode::OpTrap => true,
             _ => false,
         }
     }
@@ -326,14 +531,16 @@ impl<'a> CCFGBuilder<'a> {
         }
     }
 
-    fn handle_return(&mut self, block: SSARef) {
+    fn handle_return(&mut self, block: SSARef, prev_block: SSARef) {
         let prev = self
             .action_map
             .get(&block)
             .and_then(|&n| self.cfg.preds_of(n).first().cloned())
             .expect("This should not be `None`");
-        // TODO specify return value if it exists
-        self.cfg.add_return(None, prev);
+        // A block ending in a tail call returns the value of the call.
+        // TODO specify the return value of the other blocks if it exists
+        let value = self.tail_calls.get(&prev_block).cloned();
+        self.cfg.add_return(value, prev);
     }
 
     fn insert_jump(&mut self, cur_block: SSARef, prev_block: SSARef) {
@@ -342,7 +549,7 @@ impl<'a> CCFGBuilder<'a> {
                 // TODO
                 radeco_trace!("CCFGBuilder::insert_jump INDIRET JMP");
             } else if self.ssa.exit_node().map_or(false, |en| en == succ) {
-                self.handle_return(cur_block);
+                self.handle_return(cur_block, prev_block);
             } else {
                 self.handle_goto(cur_block, succ);
             }
@@ -389,14 +596,41 @@ impl<'a> CCFGBuilder<'a> {
     }
 
     fn cfg_from_ssa(&mut self) {
+        // Comments of the values (see `RadecoFunction::apply_annotations`) which are folded into
+        // an expression go to the next statement of their block, or to its last one.
+        let mut pending = Vec::new();
+        let mut block_action = self.last_action;
         for node in self.ssa.inorder_walk() {
             if self.is_recover_action(node) {
+                let prev = self.last_action;
                 let n = self.recover_action(node);
                 self.action_map.insert(node, n);
+                pending.extend(self.ssa.comments(&node));
+                if n != prev {
+                    self.flush_comments(n, &mut pending);
+                }
             } else if self.ssa.is_action(node) {
+                if self.last_action != block_action {
+                    let last = self.last_action;
+                    self.flush_comments(last, &mut pending);
+                }
+                pending.clear();
                 let n = self.basic_block();
                 self.action_map.insert(node, n);
-            };
+                block_action = n;
+            } else {
+                pending.extend(self.ssa.comments(&node));
+            }
+        }
+        if self.last_action != block_action {
+            let last = self.last_action;
+            self.flush_comments(last, &mut pending);
+        }
+    }
+
+    fn flush_comments(&mut self, action: CCFGRef, pending: &mut Vec<String>) {
+        for comment in pending.drain(..) {
+            self.cfg.comment_at(action, &comment);
         }
     }
 }
@@ -410,8 +644,11 @@ struct CCFGDataMap<'a> {
     // a map from the name of register to node data
     pub reg_map: HashMap<String, CCFGRef>,
     pub const_nodes: HashSet<SSARef>,
+    // Arguments and return value of the resolved call sites, by call node
+    calls: HashMap<SSARef, CallBinding>,
     seen: HashSet<SSARef>,
     regvar_version: u32,
+    render: RenderOptions<'a>,
 }
 
 impl<'a> CCFGDataMap<'a> {
@@ -422,8 +659,10 @@ impl<'a> CCFGDataMap<'a> {
             var_map: HashMap::new(),
             reg_map: HashMap::new(),
             const_nodes: HashSet::new(),
+            calls: HashMap::new(),
             seen: HashSet::new(),
             regvar_version: 0,
+            render: RenderOptions::default(),
         }
     }
 
@@ -431,8 +670,15 @@ impl<'a> CCFGDataMap<'a> {
         rfn: &'a RadecoFunction,
         cfg: &mut CCFG,
         strings: &'a HashMap<u64, String>,
+        render: RenderOptions<'a>,
     ) -> Self {
         let mut s = Self::new(rfn);
+        s.render = render;
+        s.calls = render
+            .calls
+            .iter()
+            .map(|c| (c.context.csite_node, CallBinding::new(c, s.ssa)))
+            .collect();
         s.prepare_consts(cfg, strings);
         s.prepare_regs(cfg);
         for node in s.ssa.inorder_walk() {
@@ -477,10 +723,50 @@ impl<'a> CCFGDataMap<'a> {
         self.var_map.insert(ret_node, expr_node);
     }
 
-    fn deref(&self, node: SSARef, cfg: &mut CCFG) -> CCFGRef {
+    /// Dereferences `node`, for an access of `width` bits.
+    fn deref(&self, node: SSARef, width: Option<u16>, cfg: &mut CCFG) -> CCFGRef {
         radeco_trace!("DeRef {:?}", node);
         let n = self.var_map.get(&node).cloned().unwrap_or(cfg.unknown);
-        cfg.deref(n)
+        let ty = width
+            .and_then(|w| Self::fixed_ty(w, false))
+            .filter(|_| self.render.c_types);
+        match ty {
+            Some(ty) => cfg.deref_as(n, ty),
+            None => cfg.deref(n),
+        }
+    }
+
+    fn width_of(&self, node: SSARef) -> Option<u16> {
+        self.ssa
+            .node_data(node)
+            .ok()
+            .and_then(|nd| nd.vt.width().get_width())
+    }
+
+    /// C integer type of `width` bits, if there is one.
+    fn fixed_ty(width: u16, signed: bool) -> Option<Ty> {
+        match width {
+            8 | 16 | 32 | 64 => Some(Ty::new_fixed(width, signed)),
+            _ => None,
+        }
+    }
+
+    /// With `c_types`, returns the casts `node` is rendered as, innermost first. Narrowing and
+    /// zero extension are unsigned casts to the new width, while sign extension first has to
+    /// reinterpret its operand as signed.
+    fn casts_of(&self, node: SSARef) -> Option<Vec<Ty>> {
+        if !self.render.c_types {
+            return None;
+        }
+        match self.ssa.opcode(node)? {
+            MOpcode::OpNarrow(w) | MOpcode::OpZeroExt(w) => Some(vec![Self::fixed_ty(w, false)?]),
+            MOpcode::OpSignExt(w) => {
+                let operand = *self.ssa.operands_of(node).first()?;
+                let from = Self::fixed_ty(self.width_of(operand)?, true)?;
+                Some(vec![from, Self::fixed_ty(w, true)?])
+            }
+            _ => None,
+        }
     }
 
     fn handle_phi(&mut self, node: SSARef) {
@@ -526,6 +812,7 @@ impl<'a> CCFGDataMap<'a> {
             MOpcode::OpSignExt(size) => Some(c_ast::Expr::Cast(*size as usize)),
             // TODO Add `ZeroExt`
             MOpcode::OpZeroExt(size) => Some(c_ast::Expr::Cast(*size as usize)),
+            MOpcode::OpByteSwap(size) => Some(c_ast::Expr::ByteSwap(*size as usize)),
             _ => None,
         }
     }
@@ -575,6 +862,18 @@ impl<'a> CCFGDataMap<'a> {
             "CCFGBuilder::update_values opcode: {:?}",
             self.ssa.opcode(ret_node)
         );
+        if let Some(casts) = self.casts_of(ret_node) {
+            let mut casts = casts.into_iter();
+            if let Some(ty) = casts.next() {
+                self.handle(ret_node, ops, c_ast::Expr::TyCast(ty), cfg);
+            }
+            for ty in casts {
+                let inner = self.var_map[&ret_node];
+                let outer = cfg.expr(&[inner], c_ast::Expr::TyCast(ty));
+                self.var_map.insert(ret_node, outer);
+            }
+            return;
+        }
         let (exp_opt, def_ops) = self.def_of(ret_node);
         if let Some(exp) = exp_opt {
             self.handle(ret_node, def_ops, exp, cfg);
@@ -584,7 +883,7 @@ impl<'a> CCFGDataMap<'a> {
                     debug_assert!(ops.len() == 3);
                     // Variables do not need Deref
                     if self.rfn.local_at(ops[1], true).is_none() {
-                        self.deref(ops[1], cfg);
+                        self.deref(ops[1], self.width_of(ops[2]), cfg);
                     }
                 }
                 MOpcode::OpLoad => {
@@ -595,7 +894,7 @@ impl<'a> CCFGDataMap<'a> {
                     } else if self.is_used_by_call_store(ret_node) {
                         self.add_regvar(ret_node, cfg);
                     } else {
-                        let derefed = self.deref(ops[1], cfg);
+                        let derefed = self.deref(ops[1], self.width_of(ret_node), cfg);
                         self.var_map.insert(ret_node, derefed);
                     }
                 }
@@ -605,6 +904,20 @@ impl<'a> CCFGDataMap<'a> {
                     let cfg_node = *self.var_map.get(&ops[0]).expect("This can not be `None`");
                     self.var_map.insert(ret_node, cfg_node);
                 }
+                // `strlen` returns the length, `memcpy` and `memset` only define memory.
+                MOpcode::OpCustom(ref name) if Intrinsic::from_name(name).is_some() => {
+                    if self.width_of(ret_node) != Some(0) {
+                        self.add_regvar(ret_node, cfg);
+                    }
+                }
+                // `repne scas` counts the elements read, `rep movs` and `rep stos` only define
+                // memory.
+                MOpcode::OpRepScas(_) => {
+                    self.add_regvar(ret_node, cfg);
+                }
+                MOpcode::OpRepMovs(_) | MOpcode::OpRepStos(_) => {}
+                // A trap defines nothing, and neither does the ESIL which couldn't be lifted.
+                MOpcode::OpTrap | MOpcode::OpUnsupported(_) => {}
                 _ => unreachable!(),
             }
         }
@@ -612,17 +925,24 @@ impl<'a> CCFGDataMap<'a> {
 
     fn update_data_graph_by_call(&mut self, call_node: SSARef, cfg: &mut CCFG) {
         radeco_trace!("CCFGBuilder::update_data_graph_by_call {:?}", call_node);
+        let ret_val = self.calls.get(&call_node).map(|b| b.ret);
         let ret_reg_name_opt = ret_value_string(self.rfn);
-        if ret_reg_name_opt.is_none() {
+        if ret_val.is_none() && ret_reg_name_opt.is_none() {
             return;
         }
-        let ret_reg_name = ret_reg_name_opt.unwrap();
         let reg_map = utils::call_rets(call_node, self.ssa);
         for (idx, (node, _)) in reg_map.into_iter() {
             let name = self.ssa.regfile.get_name(idx).unwrap_or("mem").to_string();
-            if name == ret_reg_name {
-                // TODO add type
-                let cfg_node = cfg.var("tmp", None);
+            let is_ret = match ret_val {
+                Some(ret) => ret == Some(node),
+                None => ret_reg_name_opt.as_ref() == Some(&name),
+            };
+            if is_ret {
+                let ty = self
+                    .width_of(node)
+                    .and_then(|w| Self::fixed_ty(w, false))
+                    .filter(|_| self.render.c_types);
+                let cfg_node = cfg.var("tmp", ty);
                 self.var_map.insert(node, cfg_node);
             } else {
                 self.add_regvar(node, cfg);
@@ -637,7 +957,7 @@ impl<'a> CCFGDataMap<'a> {
                 let cfg_node = if let Some(s) = strings.get(&val) {
                     cfg.constant(&format!("\"{}\"", s), None)
                 } else {
-                    cfg.constant(&format!("0x{:x}", val), None)
+                    cfg.constant(&self.render_const(node, val), None)
                 };
                 self.const_nodes.insert(node);
                 self.var_map.insert(node, cfg_node);
@@ -647,6 +967,24 @@ impl<'a> CCFGDataMap<'a> {
         }
     }
 
+    fn render_const(&self, node: SSARef, val: u64) -> String {
+        let width = self
+            .ssa
+            .node_data(node)
+            .ok()
+            .and_then(|nd| nd.vt.width().get_width())
+            .unwrap_or(64);
+        let rendered = self.render.const_format.render(val, width, "0x");
+        let label = self.ssa.flags(&node).or_else(|| {
+            radeco_containers::section_containing(self.render.sections, val)
+                .and_then(|s| s.name.clone())
+        });
+        match label {
+            Some(ref name) if !name.is_empty() => format!("{} /* {} */", rendered, name),
+            _ => rendered,
+        }
+    }
+
     fn prepare_regs(&mut self, cfg: &mut CCFG) {
         for walk_node in self.ssa.inorder_walk() {
             if self.ssa.is_action(walk_node) {
@@ -673,15 +1011,22 @@ impl<'a> CCFGDataMap<'a> {
 mod test {
     use crate::backend::lang_c::c_ast;
     use crate::backend::lang_c::c_cfg;
-    use crate::backend::lang_c::c_cfg_builder::{CCFGBuilder, CCFGDataMap, SSARef, CCFG};
-    use crate::frontend::radeco_containers::RadecoFunction;
+    use crate::backend::lang_c::c_cfg_builder::{
+        recover_c_cfg_with, CCFGBuilder, CCFGDataMap, RenderOptions, ResolvedCall, SSARef, CCFG,
+    };
+    use crate::frontend::radeco_containers::{
+        flag_names, parse_annotations, BindingType, CallContextInfo, RadecoFunction, VarBinding,
+    };
     use crate::frontend::radeco_source::SourceErr;
-    use crate::middle::ir::MOpcode;
+    use crate::middle::ir::{ConstFormat, MOpcode, WidthSpec};
     use crate::middle::ir_reader;
+    use crate::middle::ir_writer;
     use crate::middle::regfile::SubRegisterFile;
-    use crate::middle::ssa::ssa_traits::{SSAWalk, SSA};
+    use crate::middle::ssa::ssa_traits::{SSAWalk, ValueInfo, SSA};
+    use crate::middle::ssa::ssastorage::NodeData;
     use crate::middle::ssa::utils;
-    use r2papi::structs::LRegInfo;
+    use petgraph::graph::NodeIndex;
+    use r2papi::structs::{LFlagInfo, LRegInfo};
     use serde_json;
     use std::collections::HashMap;
     use std::fs::File;
@@ -831,7 +1176,7 @@ mod test {
                     let ret = if let Some(s) = strings.get(&tmp_val) {
                         format!("\"{}\"", s)
                     } else {
-                        format!("0x{:x}", tmp_val)
+                        datamap.render_const(node, tmp_val)
                     };
                     Some(ret)
                 } else {
@@ -976,9 +1321,10 @@ mod test {
 
     fn load(name: &str) -> RadecoFunction {
         let ssa = {
-            let regfile = Arc::new(SubRegisterFile::new(
-                &register_profile().expect("Unable to load register profile"),
-            ));
+            let regfile = Arc::new(
+                SubRegisterFile::new(&register_profile().expect("Unable to load register profile"))
+                    .unwrap(),
+            );
             let mut f = File::open(name).expect("file not found");
             let mut ir_str = String::new();
             f.read_to_string(&mut ir_str)
@@ -1009,10 +1355,286 @@ mod test {
             let rfn = load("./test_files/bin1_main_ssa");
             let dummy_map = HashMap::new();
             let mut builder = CCFGBuilder::new(&rfn, &dummy_map);
-            let data_graph = CCFGDataMap::recover_data(&rfn, &mut builder.cfg, &dummy_map);
+            let data_graph = CCFGDataMap::recover_data(
+                &rfn,
+                &mut builder.cfg,
+                &dummy_map,
+                RenderOptions::default(),
+            );
             builder.datamap = data_graph;
             CCFGBuilderVerifier::verify(&mut builder)
                 .expect(&format!("CCFGBuilder verification failed {}", file));
         }
     }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const NARROW_CONST_SSA_TXT: &str = "\
+define-fun sym.narrow(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #xffffffffffffffff;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %3;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn consts_rendered_at_node_width() {
+        let regfile = Arc::new(
+            SubRegisterFile::new(&register_profile().expect("Unable to load register profile"))
+                .unwrap(),
+        );
+        let mut rfn = RadecoFunction::default();
+        *rfn.ssa_mut() = ir_reader::parse_il(NARROW_CONST_SSA_TXT, regfile);
+        let c = *rfn.ssa().constants().keys().next().unwrap();
+        rfn.ssa_mut().g[c] = NodeData::Op(
+            MOpcode::OpConst(u64::max_value()),
+            ValueInfo::new_scalar(WidthSpec::new_known(8)),
+        );
+
+        let strings = HashMap::new();
+        for &(format, expected) in &[(ConstFormat::Hex, "0xff"), (ConstFormat::Signed, "-1")] {
+            let mut cfg = c_cfg::CCFG::new(rfn.name.as_ref());
+            let options = RenderOptions {
+                const_format: format,
+                sections: &[],
+                c_types: false,
+                calls: &[],
+            };
+            let datamap = CCFGDataMap::recover_data(&rfn, &mut cfg, &strings, options);
+            assert_eq!(
+                cfg.constant_of(datamap.var_map[&c]),
+                Some(expected.to_owned())
+            );
+        }
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const WIDEN_SSA_TXT: &str = "\
+define-fun sym.widen(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown32 = Load(%3, %1);
+        [@0x001000.0002] %5: $Unknown64 = ZeroExt64(%4);
+        [@0x001000.0003] %6: $Unknown0 = Store(%3, %2, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %6;
+}
+";
+
+    #[test]
+    fn zero_extend_renders_as_cast() {
+        let regfile = Arc::new(
+            SubRegisterFile::new(&register_profile().expect("Unable to load register profile"))
+                .unwrap(),
+        );
+        let mut rfn = RadecoFunction::default();
+        *rfn.ssa_mut() = ir_reader::parse_il(WIDEN_SSA_TXT, regfile);
+        let options = RenderOptions {
+            c_types: true,
+            ..RenderOptions::default()
+        };
+        let cfg = recover_c_cfg_with(&rfn, &HashMap::new(), &HashMap::new(), options);
+        let code = c_cfg::ctrl_flow_struct::structure_and_convert(cfg)
+            .expect("Structuring failed")
+            .print();
+        assert!(
+            code.contains("*(uint64_t *)(rsi) = ((uint64_t)*(uint32_t *)(rdi))"),
+            "{}",
+            code
+        );
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLER_SSA_TXT: &str = "\
+define-fun sym.caller(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown64 = %1 + #x1;
+        [@0x001000.0002] (%5: $Unknown64 = $rax, %6: $Unknown64 = $rcx, %7: $Unknown0 = $mem) = CALL #x2000($rdi=%2, $rsi=%4, $mem=%3);
+        [@0x001000.0003] %8: $Unknown0 = Store(%7, %1, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %8;
+}
+";
+
+    #[test]
+    fn resolved_call_args() {
+        let regfile = Arc::new(
+            SubRegisterFile::new(&register_profile().expect("Unable to load register profile"))
+                .unwrap(),
+        );
+        let mut caller = RadecoFunction::default();
+        *caller.ssa_mut() = ir_reader::parse_il(CALLER_SSA_TXT, regfile.clone());
+        let ssa = caller.ssa();
+        let call = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpCall))
+            .unwrap();
+        let info = utils::call_info(call, ssa).unwrap();
+        let passed = |reg: &str| info.register_args[regfile.register_id_by_name(reg).unwrap()];
+
+        // `sym.callee(a, b)` returning in rax, with the callee's argument nodes being arbitrary.
+        let ridx = |reg: &str| Some(regfile.register_id_by_name(reg).unwrap().to_u8() as u64);
+        let mut callee = RadecoFunction::default();
+        let (a, b, ret) = (NodeIndex::new(1), NodeIndex::new(2), NodeIndex::new(3));
+        let params = vec![
+            (BindingType::RegisterArgument(0), a, "rdi"),
+            (BindingType::RegisterArgument(1), b, "rsi"),
+            (BindingType::Return, ret, "rax"),
+        ];
+        for (btype, idx, reg) in params {
+            let binding = VarBinding::new(btype, String::new(), None, idx, ridx(reg));
+            callee.bindings_mut().push(binding);
+        }
+        let context = CallContextInfo {
+            map: vec![(passed("rsi"), b), (passed("rdi"), a), (call, ret)],
+            csite_node: call,
+            csite: 0x1002,
+        };
+
+        let fname_map = vec![(0x2000, "sym.callee".to_owned())]
+            .into_iter()
+            .collect();
+        let decompile = |calls: &[ResolvedCall]| {
+            let options = RenderOptions {
+                calls,
+                ..RenderOptions::default()
+            };
+            let cfg = recover_c_cfg_with(&caller, &fname_map, &HashMap::new(), options);
+            c_cfg::ctrl_flow_struct::structure_and_convert(cfg)
+                .expect("Structuring failed")
+                .print()
+        };
+
+        let resolved = decompile(&[ResolvedCall {
+            context: &context,
+            callee: &callee,
+        }]);
+        assert!(
+            resolved.contains("tmp = sym.callee(rsi, (rdi + 0x1));"),
+            "{}",
+            resolved
+        );
+        assert!(!resolved.contains("clobbers"), "{}", resolved);
+
+        let unresolved = decompile(&[]);
+        assert!(
+            unresolved.contains("// unresolved call, clobbers rax, rcx"),
+            "{}",
+            unresolved
+        );
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const FLAGGED_SSA_TXT: &str = "\
+define-fun fcn.00001000(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown0 = Store(%2, %1, #x4006b4);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %3;
+}
+";
+
+    #[test]
+    fn flags_in_output() {
+        let regfile = Arc::new(
+            SubRegisterFile::new(&register_profile().expect("Unable to load register profile"))
+                .unwrap(),
+        );
+        let mut rfn = RadecoFunction::default();
+        rfn.offset = 0x1000;
+        *rfn.ssa_mut() = ir_reader::parse_il(FLAGGED_SSA_TXT, regfile);
+        let flags: Vec<LFlagInfo> = serde_json::from_str(
+            r#"[{"offset":4196020,"name":"section..rodata","size":0},
+                {"offset":4196020,"name":"str.hello","size":6},
+                {"offset":4096,"name":"fcn.00001000","size":4},
+                {"offset":4096,"name":"sym.main","size":4}]"#,
+        )
+        .unwrap();
+        rfn.annotate_flags(&flag_names(&flags));
+
+        let cfg = recover_c_cfg_with(
+            &rfn,
+            &HashMap::new(),
+            &HashMap::new(),
+            RenderOptions::default(),
+        );
+        let code = c_cfg::ctrl_flow_struct::structure_and_convert(cfg)
+            .expect("Structuring failed")
+            .print();
+        assert!(code.starts_with("fn sym.main ("), "{}", code);
+        assert!(code.contains("0x4006b4 /* str.hello */"), "{}", code);
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const ANNOTATED_SSA_TXT: &str = "\
+define-fun sym.annotated(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        [@0x001004.0001] %4: $Unknown0 = Store(%2, %1, %3);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %4;
+}
+";
+
+    #[test]
+    fn annotations_in_output() {
+        let regfile = Arc::new(
+            SubRegisterFile::new(&register_profile().expect("Unable to load register profile"))
+                .unwrap(),
+        );
+        let mut rfn = RadecoFunction::default();
+        rfn.offset = 0x1000;
+        *rfn.ssa_mut() = ir_reader::parse_il(ANNOTATED_SSA_TXT, regfile);
+        // Neither 0x1002 nor 0x2000 has an operation.
+        let annotations = parse_annotations(
+            r#"{"0x1000": "next one", "4100": "saved here", "0x1002": "lost", "0x2000": "other"}"#,
+        )
+        .unwrap();
+        assert_eq!(rfn.apply_annotations(annotations), 2);
+
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, Some(rfn.name.to_string()), rfn.ssa()).unwrap();
+        assert!(il.contains(" + #x1; // next one"), "{}", il);
+        assert!(il.contains("); // saved here"), "{}", il);
+
+        let cfg = recover_c_cfg_with(
+            &rfn,
+            &HashMap::new(),
+            &HashMap::new(),
+            RenderOptions::default(),
+        );
+        let code = c_cfg::ctrl_flow_struct::structure_and_convert(cfg)
+            .expect("Structuring failed")
+            .print();
+        // The addition is folded into the store, which carries both comments.
+        assert!(code.contains("// next one; saved here"), "{}", code);
+        assert!(!code.contains("lost"), "{}", code);
+    }
 }
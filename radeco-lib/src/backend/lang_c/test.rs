@@ -4,20 +4,14 @@ use super::c_cfg_builder;
 use crate::frontend::radeco_containers::RadecoFunction;
 use crate::middle::ir_reader;
 use crate::middle::regfile::SubRegisterFile;
-use serde_json;
+use crate::utils::test_support::register_file;
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
 
-const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
-
 lazy_static! {
-    static ref REGISTER_FILE: Arc<SubRegisterFile> = {
-        let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
-        let reg_profile = serde_json::from_str(&*s).unwrap();
-        Arc::new(SubRegisterFile::new(&reg_profile))
-    };
+    static ref REGISTER_FILE: Arc<SubRegisterFile> = register_file();
 }
 
 fn run_ssa_file<P: AsRef<Path>>(file: P) -> Result<CAST, &'static str> {
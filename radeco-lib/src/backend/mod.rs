@@ -12,3 +12,7 @@ pub mod x86 {
     pub mod x86_idioms;
 }
 pub mod ctrl_flow_struct;
+pub mod esil_emit;
+pub mod interp;
+pub mod smt;
+pub mod structure;
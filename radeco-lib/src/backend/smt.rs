@@ -0,0 +1,490 @@
+//! Translates the SSA of a loop-free path to SMT-LIB2, to reason about it with an SMT solver.
+//!
+//! Every value defined on the path is a bit-vector named after its node (e.g. `v12`), whose sort
+//! is given by the width of its `ValueInfo`. Memory states are arrays from addresses to bytes, with
+//! little-endian loads and stores. The registers of the entry state are declared under their own
+//! names (e.g. `rdi`), and the ones of the exit state, if the path reaches it, are defined with an
+//! `_out` suffix (e.g. `rax_out`). A phi takes the operand flowing in from the previous block of
+//! the path, and the branches taken by the path are asserted.
+//!
+//! Calls and the operations without a bit-vector counterpart are declared as fresh symbols, left
+//! for the solver to choose, with a warning. The output ends after the path, so that the
+//! assertions of interest and `(check-sat)` can be appended to it.
+
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+
+use petgraph::graph::NodeIndex;
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+const FALSE_EDGE: u8 = 0;
+const TRUE_EDGE: u8 = 1;
+
+/// Paths from the entry to the exit of the function made of at most `max_blocks` blocks,
+/// counting the entry and the exit nodes, which don't go through any block twice.
+pub fn paths(ssa: &SSAStorage, max_blocks: usize) -> Vec<Vec<NodeIndex>> {
+    let (entry, exit) = match (ssa.entry_node(), ssa.exit_node()) {
+        (Some(entry), Some(exit)) => (entry, exit),
+        _ => return Vec::new(),
+    };
+    let mut paths = Vec::new();
+    let mut path = vec![entry];
+    extend_paths(ssa, exit, max_blocks, &mut path, &mut paths);
+    paths
+}
+
+fn extend_paths(
+    ssa: &SSAStorage,
+    exit: NodeIndex,
+    max_blocks: usize,
+    path: &mut Vec<NodeIndex>,
+    paths: &mut Vec<Vec<NodeIndex>>,
+) {
+    let last = path[path.len() - 1];
+    if last == exit {
+        paths.push(path.clone());
+        return;
+    }
+    if path.len() >= max_blocks {
+        return;
+    }
+    let mut succs = ssa.succs_of(last);
+    succs.sort();
+    succs.dedup();
+    for succ in succs {
+        if path.contains(&succ) {
+            continue;
+        }
+        path.push(succ);
+        extend_paths(ssa, exit, max_blocks, path, paths);
+        path.pop();
+    }
+}
+
+/// Writes the SMT-LIB2 translation of `path`, a sequence of blocks each followed by one of its
+/// successors, see `paths`. A path made of a single block translates that block alone, its phis
+/// being fresh symbols.
+pub fn emit_path<O: Write>(output: O, ssa: &SSAStorage, path: &[NodeIndex]) -> fmt::Result {
+    SmtWriter::new(output, ssa, path).emit()
+}
+
+struct SmtWriter<'a, O: Write> {
+    output: O,
+    ssa: &'a SSAStorage,
+    path: &'a [NodeIndex],
+    names: HashMap<NodeIndex, String>,
+    // Width of addresses and of the values whose width is unknown
+    word_size: u16,
+}
+
+impl<'a, O: Write> SmtWriter<'a, O> {
+    fn new(output: O, ssa: &'a SSAStorage, path: &'a [NodeIndex]) -> SmtWriter<'a, O> {
+        SmtWriter {
+            output: output,
+            ssa: ssa,
+            path: path,
+            names: HashMap::new(),
+            word_size: ssa.regfile.word_size().unwrap_or(64),
+        }
+    }
+
+    fn emit(&mut self) -> fmt::Result {
+        writeln!(self.output, "(set-logic QF_ABV)")?;
+        if let Some(entry) = self.ssa.entry_node() {
+            for (name, node) in self.register_state(entry) {
+                let sort = self.sort(node);
+                writeln!(self.output, "(declare-const {} {})", name, sort)?;
+                self.names.insert(node, name);
+            }
+        }
+
+        for (i, &block) in self.path.iter().enumerate() {
+            for node in self.ssa.phis_in(block) {
+                self.define(node)?;
+            }
+            for node in self.ssa.exprs_in(block) {
+                self.operand(node)?;
+            }
+            if let Some(&next) = self.path.get(i + 1) {
+                self.emit_branch(block, next)?;
+            }
+        }
+
+        let exit = self.ssa.exit_node();
+        if let (Some(exit), Some(&last)) = (exit, self.path.last()) {
+            if exit == last {
+                for (name, node) in self.register_state(exit) {
+                    let value = self.operand(node)?;
+                    let sort = self.sort(node);
+                    writeln!(
+                        self.output,
+                        "(define-fun {}_out () {} {})",
+                        name, sort, value
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Values of the register state of `block`, by register name.
+    fn register_state(&self, block: NodeIndex) -> Vec<(String, NodeIndex)> {
+        let state = match self.ssa.registers_in(block) {
+            Some(state) => state,
+            None => return Vec::new(),
+        };
+        let regfile = &self.ssa.regfile;
+        utils::register_state_info(state, self.ssa)
+            .into_iter()
+            .map(|(id, (node, _))| (regfile.get_name(id).unwrap_or("mem").to_owned(), node))
+            .collect()
+    }
+
+    /// Asserts the condition of going from `block` to `next`, if there is one.
+    fn emit_branch(&mut self, block: NodeIndex, next: NodeIndex) -> fmt::Result {
+        let selector = match self.ssa.selector_in(block) {
+            Some(selector) => selector,
+            None => return Ok(()),
+        };
+        let edges = self
+            .ssa
+            .outgoing_edges(block)
+            .into_iter()
+            .filter(|&(edge, _)| self.ssa.g.edge_endpoints(edge).map(|(_, t)| t) == Some(next))
+            .map(|(_, ety)| ety)
+            .collect::<Vec<_>>();
+        let taken = match &*edges {
+            &[TRUE_EDGE] => true,
+            &[FALSE_EDGE] => false,
+            _ => return Ok(()),
+        };
+        let cond = self.operand(selector)?;
+        let width = self.width(selector);
+        if taken {
+            writeln!(self.output, "(assert {})", is_nonzero(&cond, width))
+        } else {
+            writeln!(self.output, "(assert (= {} {}))", cond, literal(0, width))
+        }
+    }
+
+    /// Width of `node`, `0` for memory.
+    fn width(&self, node: NodeIndex) -> u16 {
+        self.ssa
+            .node_data(node)
+            .ok()
+            .and_then(|nd| nd.vt.width().get_width())
+            .unwrap_or(self.word_size)
+    }
+
+    fn sort(&self, node: NodeIndex) -> String {
+        match self.width(node) {
+            0 => format!("(Array (_ BitVec {}) (_ BitVec 8))", self.word_size),
+            w => format!("(_ BitVec {})", w),
+        }
+    }
+
+    /// Expression standing for the value `node`, defining it first if needed.
+    fn operand(&mut self, node: NodeIndex) -> Result<String, fmt::Error> {
+        match self.ssa.constant(node) {
+            Some(value) => Ok(literal(value, self.width(node))),
+            None => self.define(node),
+        }
+    }
+
+    fn define(&mut self, node: NodeIndex) -> Result<String, fmt::Error> {
+        if let Some(name) = self.names.get(&node) {
+            return Ok(name.clone());
+        }
+        let expr = if self.ssa.is_phi(node) {
+            self.phi_expr(node)?
+        } else if let Some(opcode) = self.ssa.opcode(node) {
+            let expr = self.op_expr(node, &opcode)?;
+            if expr.is_none() {
+                radeco_warn!("smt|{:?} ({:?}) is unconstrained", node, opcode);
+            }
+            expr
+        } else {
+            // Comments, e.g. the values returned by a call.
+            None
+        };
+        let name = format!("v{}", node.index());
+        let sort = self.sort(node);
+        match expr {
+            Some(expr) => writeln!(self.output, "(define-fun {} () {} {})", name, sort, expr)?,
+            None => writeln!(self.output, "(declare-const {} {})", name, sort)?,
+        }
+        self.names.insert(node, name.clone());
+        Ok(name)
+    }
+
+    /// The operand of `phi` flowing in from the block before it on the path, if any.
+    fn phi_expr(&mut self, phi: NodeIndex) -> Result<Option<String>, fmt::Error> {
+        let block = self.ssa.block_for(phi);
+        let pos = self.path.iter().position(|&b| Some(b) == block);
+        let pred = match pos {
+            Some(pos) if pos > 0 => self.path[pos - 1],
+            _ => return Ok(None),
+        };
+        let source = utils::phi_sources(phi, self.ssa)
+            .into_iter()
+            .find(|&(_, ref preds)| preds.contains(&pred))
+            .map(|(op, _)| op);
+        match source {
+            Some(op) => {
+                let expr = self.operand(op)?;
+                Ok(Some(resize(&expr, self.width(op), self.width(phi))))
+            }
+            None => {
+                radeco_warn!("smt|No operand of {:?} flows in from {:?}", phi, pred);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Translates the operation `node`, `None` if it has no bit-vector counterpart.
+    fn op_expr(&mut self, node: NodeIndex, opcode: &MOpcode) -> Result<Option<String>, fmt::Error> {
+        let ssa = self.ssa;
+        let operands = ssa.operands_of(node);
+        let w = self.width(node);
+        let mut ops = Vec::new();
+        for &op in &operands {
+            ops.push((self.operand(op)?, self.width(op)));
+        }
+        // Operand `i` as wide as `width`.
+        let op = |i: usize, width: u16| resize(&ops[i].0, ops[i].1, width);
+        let binary = |f: &str| format!("({} {} {})", f, op(0, w), op(1, w));
+        let compare = |f: &str| {
+            let width = ops[0].1.max(ops[1].1);
+            let cond = format!("({} {} {})", f, op(0, width), op(1, width));
+            from_bool(&cond, w)
+        };
+        let arity = match *opcode {
+            MOpcode::OpNot
+            | MOpcode::OpMov
+            | MOpcode::OpNarrow(_)
+            | MOpcode::OpSignExt(_)
+            | MOpcode::OpZeroExt(_)
+            | MOpcode::OpByteSwap(_) => 1,
+            MOpcode::OpLoad => 2,
+            MOpcode::OpITE | MOpcode::OpStore => 3,
+            _ => 2,
+        };
+        if ops.len() != arity || (w == 0 && *opcode != MOpcode::OpStore) {
+            return Ok(None);
+        }
+
+        let expr = match *opcode {
+            MOpcode::OpAdd => binary("bvadd"),
+            MOpcode::OpSub => binary("bvsub"),
+            MOpcode::OpMul => binary("bvmul"),
+            MOpcode::OpDiv => binary("bvudiv"),
+            MOpcode::OpMod => binary("bvurem"),
+            MOpcode::OpAnd => binary("bvand"),
+            MOpcode::OpOr => binary("bvor"),
+            MOpcode::OpXor => binary("bvxor"),
+            MOpcode::OpLsl => binary("bvshl"),
+            MOpcode::OpLsr => binary("bvlshr"),
+            MOpcode::OpEq => compare("="),
+            MOpcode::OpLt => compare("bvult"),
+            MOpcode::OpGt => compare("bvugt"),
+            MOpcode::OpRol | MOpcode::OpRor => {
+                let amount = match ssa.constant(operands[1]) {
+                    Some(amount) => amount % u64::from(w),
+                    None => return Ok(None),
+                };
+                let f = if *opcode == MOpcode::OpRol {
+                    "rotate_left"
+                } else {
+                    "rotate_right"
+                };
+                format!("((_ {} {}) {})", f, amount, op(0, w))
+            }
+            MOpcode::OpNot => format!("(bvnot {})", op(0, w)),
+            MOpcode::OpMov | MOpcode::OpNarrow(_) | MOpcode::OpZeroExt(_) => op(0, w),
+            MOpcode::OpSignExt(_) if w >= ops[0].1 => {
+                format!("((_ sign_extend {}) {})", w - ops[0].1, ops[0].0)
+            }
+            MOpcode::OpSignExt(_) => op(0, w),
+            MOpcode::OpByteSwap(_) if w % 8 == 0 => {
+                let value = op(0, w);
+                // The lowest byte becomes the highest one.
+                let bytes = (0..w / 8)
+                    .map(|i| format!("((_ extract {} {}) {})", 8 * i + 7, 8 * i, value))
+                    .collect::<Vec<_>>();
+                concat(&bytes)
+            }
+            MOpcode::OpITE => {
+                let cond = is_nonzero(&ops[0].0, ops[0].1);
+                format!("(ite {} {} {})", cond, op(1, w), op(2, w))
+            }
+            MOpcode::OpLoad if w % 8 == 0 => {
+                let address = op(1, self.word_size);
+                let bytes = (0..w / 8)
+                    .rev()
+                    .map(|i| {
+                        let byte_address = self.offset(&address, i);
+                        format!("(select {} {})", ops[0].0, byte_address)
+                    })
+                    .collect::<Vec<_>>();
+                concat(&bytes)
+            }
+            MOpcode::OpStore if ops[2].1 % 8 == 0 && ops[2].1 > 0 => {
+                let address = op(1, self.word_size);
+                let mut memory = ops[0].0.clone();
+                for i in 0..ops[2].1 / 8 {
+                    let byte = format!("((_ extract {} {}) {})", 8 * i + 7, 8 * i, ops[2].0);
+                    memory = format!("(store {} {} {})", memory, self.offset(&address, i), byte);
+                }
+                memory
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(expr))
+    }
+
+    /// Address `offset` bytes after `address`.
+    fn offset(&self, address: &str, offset: u16) -> String {
+        if offset == 0 {
+            address.to_owned()
+        } else {
+            let offset = literal(u64::from(offset), self.word_size);
+            format!("(bvadd {} {})", address, offset)
+        }
+    }
+}
+
+fn literal(value: u64, width: u16) -> String {
+    let value = if width < 64 {
+        value & ((1 << width) - 1)
+    } else {
+        value
+    };
+    format!("(_ bv{} {})", value, width)
+}
+
+/// `expr` of `from` bits, zero extended or truncated to `to` bits.
+fn resize(expr: &str, from: u16, to: u16) -> String {
+    if from == to || from == 0 || to == 0 {
+        expr.to_owned()
+    } else if from < to {
+        format!("((_ zero_extend {}) {})", to - from, expr)
+    } else {
+        format!("((_ extract {} 0) {})", to - 1, expr)
+    }
+}
+
+fn concat(parts: &[String]) -> String {
+    if parts.len() == 1 {
+        parts[0].clone()
+    } else {
+        format!("(concat {})", parts.join(" "))
+    }
+}
+
+/// The boolean `cond` as a bit-vector of `width` bits.
+fn from_bool(cond: &str, width: u16) -> String {
+    format!("(ite {} {} {})", cond, literal(1, width), literal(0, width))
+}
+
+/// Whether the bit-vector `expr` of `width` bits isn't zero.
+fn is_nonzero(expr: &str, width: u16) -> String {
+    format!("(not (= {} {}))", expr, literal(0, width))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::test_support::load_ssa;
+    use std::io::Write as IoWrite;
+    use std::process::{Command, Stdio};
+
+    // rax = (rdi + rsi) * 2 if it is 0x10, rdi - rsi otherwise
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const ARITH_SSA_TXT: &str = "\
+define-fun sym.arith(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown64 = %1 + %2;
+        [@0x001004.0001] %5: $Unknown64 = %4 * #x2;
+        [@0x001004.0002] %6: $Unknown1 = %5 == #x10;
+        JMP IF %6 0x001010.0000 ELSE 0x001008.0000
+    bb_0x001008.0000(sz 0x4):
+        [@0x001008.0001] %7: $Unknown64 = %1 - %2;
+        JMP 0x001010.0000
+    bb_0x001010.0000(sz 0x1):
+        %8: $Unknown64 = Phi(%5, %7);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %8;
+        $mem = %3;
+}
+";
+
+    /// Checks `smt` with z3, if it is installed.
+    fn check_sat(smt: &str) -> Option<String> {
+        let mut z3 = Command::new("z3")
+            .args(&["-smt2", "-in"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        z3.stdin.take()?.write_all(smt.as_bytes()).ok()?;
+        let output = z3.wait_with_output().ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    }
+
+    #[test]
+    fn arithmetic_paths() {
+        let ssa = load_ssa(ARITH_SSA_TXT);
+        let all = paths(&ssa, 8);
+        assert_eq!(all.len(), 2);
+        // entry, 0x1000, 0x1010 and exit at least
+        assert!(paths(&ssa, 3).is_empty());
+
+        let emitted = all
+            .iter()
+            .map(|path| {
+                let mut smt = String::new();
+                emit_path(&mut smt, &ssa, path).unwrap();
+                smt
+            })
+            .collect::<Vec<_>>();
+        for smt in &emitted {
+            assert!(smt.starts_with("(set-logic QF_ABV)\n"));
+            assert!(smt.contains("(declare-const rdi (_ BitVec 64))"));
+            assert!(smt.contains("(declare-const mem (Array (_ BitVec 64) (_ BitVec 8)))"));
+            assert!(smt.contains("(bvmul "));
+            assert!(smt.contains("(define-fun rax_out () (_ BitVec 64) "));
+            assert!(smt.contains("(define-fun mem_out () (Array (_ BitVec 64) (_ BitVec 8)) mem)"));
+            assert_eq!(smt.matches('(').count(), smt.matches(')').count());
+        }
+        let taken = emitted
+            .iter()
+            .position(|smt| smt.contains("(assert (not (= "))
+            .unwrap();
+        assert!(!emitted[taken].contains("(bvsub "));
+        assert!(emitted[1 - taken].contains("(bvsub "));
+        assert!(emitted[1 - taken].contains("(assert (= "));
+
+        // Only the path through the taken branch returns 0x10 with rdi = 3 and rsi = 5.
+        let model = "(assert (= rdi (_ bv3 64)))\n(assert (= rsi (_ bv5 64)))\n";
+        for (i, smt) in emitted.iter().enumerate() {
+            let query = format!(
+                "{}{}(assert (= rax_out (_ bv16 64)))\n(check-sat)\n",
+                smt, model
+            );
+            if let Some(res) = check_sat(&query) {
+                assert_eq!(res, if i == taken { "sat" } else { "unsat" });
+            }
+        }
+    }
+}
@@ -0,0 +1,502 @@
+//! Recovers structured control flow from the CFG of the SSA.
+//!
+//! The blocks are walked in dominator order and arranged into sequences, two-way conditionals
+//! and loops, following the structural analysis of Cifuentes' "Reverse Compilation Techniques":
+//!
+//!  * the natural loops found by `LoopForest` become `While` loops when their header is the only
+//!    block leaving them, and endless `Loop`s exited with `Break` otherwise;
+//!  * a conditional block is closed by its follow, the last block (in reverse postorder) it
+//!    immediately dominates and which is reached by more than one forward edge.
+//!
+//! Control flow which does not fit in these constructs, e.g. the second entry of an irreducible
+//! loop, is kept as a `Goto` to a `Label` placed in front of its target.
+
+use crate::analysis::loops::LoopForest;
+use crate::middle::ssa::cfg_traits::CFG;
+
+use std::collections::{HashMap, HashSet};
+
+/// A structured representation of the control flow of a function, over its basic blocks `B`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StructuredAst<B> {
+    /// The instructions of a basic block.
+    Block(B),
+    Seq(Vec<StructuredAst<B>>),
+    /// Branches on the selector of `cond`, or on its negation if `negated` is set. The block
+    /// itself precedes the conditional.
+    If {
+        cond: B,
+        negated: bool,
+        then: Box<StructuredAst<B>>,
+        els: Option<Box<StructuredAst<B>>>,
+    },
+    /// Executes `header`, then `body` as long as the selector of `header` (or its negation if
+    /// `negated` is set) holds.
+    While {
+        header: B,
+        negated: bool,
+        body: Box<StructuredAst<B>>,
+    },
+    /// Endless loop, left through a `Break`.
+    Loop(Box<StructuredAst<B>>),
+    /// Leaves the innermost loop.
+    Break,
+    /// Goes on with the next iteration of the innermost loop.
+    Continue,
+    /// Jumps to the `Label` of a block.
+    Goto(B),
+    Label(B),
+}
+
+struct LoopContext<B> {
+    header: B,
+    body: HashSet<B>,
+    exit: Option<B>,
+}
+
+struct Structurer<'a, T: CFG> {
+    cfg: &'a T,
+    forest: LoopForest<T::ActionRef>,
+    rpo_index: HashMap<T::ActionRef, usize>,
+    exit: Option<T::ActionRef>,
+    emitted: HashSet<T::ActionRef>,
+    loops: Vec<LoopContext<T::ActionRef>>,
+}
+
+/// Structures the blocks of `cfg` reachable from its entry node.
+pub fn structure<T: CFG>(cfg: &T) -> StructuredAst<T::ActionRef> {
+    let forest = LoopForest::new(cfg);
+    let rpo_index = forest
+        .reverse_postorder()
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| (b, i))
+        .collect();
+    let mut structurer = Structurer {
+        cfg: cfg,
+        forest: forest,
+        rpo_index: rpo_index,
+        exit: cfg.exit_node(),
+        emitted: HashSet::new(),
+        loops: Vec::new(),
+    };
+
+    let ast = match cfg.entry_node() {
+        Some(entry) => StructuredAst::Seq(structurer.region(entry, None)),
+        None => StructuredAst::Seq(Vec::new()),
+    };
+
+    let mut targets = HashSet::new();
+    goto_targets(&ast, &mut targets);
+    add_labels(ast, &targets)
+}
+
+impl<'a, T: CFG> Structurer<'a, T> {
+    /// Structures the blocks from `start` up to, but excluding, `stop`.
+    fn region(
+        &mut self,
+        start: T::ActionRef,
+        stop: Option<T::ActionRef>,
+    ) -> Vec<StructuredAst<T::ActionRef>> {
+        let mut seq = Vec::new();
+        let mut current = Some(start);
+        while let Some(block) = current {
+            if Some(block) == stop || Some(block) == self.exit {
+                break;
+            }
+            if let Some(jump) = self.loop_jump(block) {
+                seq.push(jump);
+                break;
+            }
+            if self.emitted.contains(&block) {
+                seq.push(StructuredAst::Goto(block));
+                break;
+            }
+            if self.is_loop_header(block) {
+                let (ast, exit) = self.structure_loop(block);
+                seq.push(ast);
+                current = exit;
+                continue;
+            }
+
+            self.emitted.insert(block);
+            seq.push(StructuredAst::Block(block));
+            current = if let Some(ci) = self.cfg.conditional_blocks(block) {
+                let follow = self.follow(block);
+                let then = self.region(ci.true_side, follow);
+                let els = self.region(ci.false_side, follow);
+                seq.push(conditional(block, then, els));
+                follow
+            } else {
+                self.cfg.unconditional_block(block)
+            };
+        }
+        seq
+    }
+
+    /// The jump leaving `block` for a block of the innermost loop which is not structured
+    /// in place: its header, once visited, or its exit.
+    fn loop_jump(&self, block: T::ActionRef) -> Option<StructuredAst<T::ActionRef>> {
+        let ctx = self.loops.last()?;
+        if block == ctx.header && self.emitted.contains(&block) {
+            Some(StructuredAst::Continue)
+        } else if ctx.body.contains(&block) {
+            None
+        } else if Some(block) == ctx.exit {
+            Some(StructuredAst::Break)
+        } else {
+            Some(StructuredAst::Goto(block))
+        }
+    }
+
+    fn is_loop_header(&self, block: T::ActionRef) -> bool {
+        !self.loops.iter().any(|ctx| ctx.header == block)
+            && self.forest.loops().iter().any(|l| l.header == block)
+    }
+
+    /// Structures the loop headed by `header`. Returns the loop and the block following it.
+    fn structure_loop(
+        &mut self,
+        header: T::ActionRef,
+    ) -> (StructuredAst<T::ActionRef>, Option<T::ActionRef>) {
+        let body = self
+            .forest
+            .loops()
+            .iter()
+            .find(|l| l.header == header)
+            .map(|l| l.body.clone())
+            .unwrap_or_default();
+
+        // The loop is pre-checked if its header is the only block leaving it.
+        let precheck = self.cfg.conditional_blocks(header).and_then(|ci| {
+            let leaves_body = |b: &T::ActionRef| !body.contains(b);
+            match (leaves_body(&ci.true_side), leaves_body(&ci.false_side)) {
+                (false, true) => Some((ci.true_side, ci.false_side, false)),
+                (true, false) => Some((ci.false_side, ci.true_side, true)),
+                _ => None,
+            }
+        });
+        let leaves_elsewhere = body
+            .iter()
+            .filter(|&&b| b != header)
+            .any(|&b| self.cfg.succs_of(b).iter().any(|s| !body.contains(s)));
+
+        match precheck {
+            Some((first, exit, negated)) if !leaves_elsewhere => {
+                self.emitted.insert(header);
+                self.loops.push(LoopContext {
+                    header: header,
+                    body: body,
+                    exit: Some(exit),
+                });
+                let mut seq = self.region(first, None);
+                self.loops.pop();
+                if seq.last() == Some(&StructuredAst::Continue) {
+                    seq.pop();
+                }
+                let ast = StructuredAst::While {
+                    header: header,
+                    negated: negated,
+                    body: Box::new(StructuredAst::Seq(seq)),
+                };
+                (ast, Some(exit))
+            }
+            _ => {
+                // Leave the loop for its first exit in reverse postorder, the other exits are
+                // reached through gotos.
+                let exit = body
+                    .iter()
+                    .flat_map(|&b| self.cfg.succs_of(b))
+                    .filter(|s| !body.contains(s) && self.rpo_index.contains_key(s))
+                    .min_by_key(|s| self.rpo_index[s]);
+                self.loops.push(LoopContext {
+                    header: header,
+                    body: body,
+                    exit: exit,
+                });
+                let mut seq = self.region(header, None);
+                self.loops.pop();
+                if seq.last() == Some(&StructuredAst::Continue) {
+                    seq.pop();
+                }
+                (StructuredAst::Loop(Box::new(StructuredAst::Seq(seq))), exit)
+            }
+        }
+    }
+
+    /// The block where the branches of the conditional `block` join, if any.
+    fn follow(&self, block: T::ActionRef) -> Option<T::ActionRef> {
+        let in_loop = |b: &T::ActionRef| self.loops.last().map_or(true, |ctx| ctx.body.contains(b));
+        self.forest
+            .reverse_postorder()
+            .iter()
+            .rev()
+            .find(|&&n| {
+                n != block
+                    && self.forest.idom(n) == Some(block)
+                    && in_loop(&n)
+                    && self.forward_preds(n) > 1
+            })
+            .cloned()
+    }
+
+    /// Number of edges to `block` which are not back edges.
+    fn forward_preds(&self, block: T::ActionRef) -> usize {
+        self.cfg
+            .preds_of(block)
+            .into_iter()
+            .filter(|p| self.rpo_index.contains_key(p) && !self.forest.dominates(block, *p))
+            .count()
+    }
+}
+
+fn conditional<B>(
+    cond: B,
+    then: Vec<StructuredAst<B>>,
+    els: Vec<StructuredAst<B>>,
+) -> StructuredAst<B> {
+    let (negated, then, els) = if then.is_empty() {
+        (true, els, Vec::new())
+    } else {
+        (false, then, els)
+    };
+    StructuredAst::If {
+        cond: cond,
+        negated: negated,
+        then: Box::new(StructuredAst::Seq(then)),
+        els: if els.is_empty() {
+            None
+        } else {
+            Some(Box::new(StructuredAst::Seq(els)))
+        },
+    }
+}
+
+fn goto_targets<B: Copy + Eq + std::hash::Hash>(ast: &StructuredAst<B>, targets: &mut HashSet<B>) {
+    match *ast {
+        StructuredAst::Goto(b) => {
+            targets.insert(b);
+        }
+        StructuredAst::Seq(ref seq) => {
+            for ast in seq {
+                goto_targets(ast, targets);
+            }
+        }
+        StructuredAst::If {
+            ref then, ref els, ..
+        } => {
+            goto_targets(then, targets);
+            if let Some(ref els) = *els {
+                goto_targets(els, targets);
+            }
+        }
+        StructuredAst::While { ref body, .. } | StructuredAst::Loop(ref body) => {
+            goto_targets(body, targets)
+        }
+        _ => {}
+    }
+}
+
+/// Places a `Label` in front of every block (or loop headed by a block) in `targets`.
+fn add_labels<B: Copy + Eq + std::hash::Hash>(
+    ast: StructuredAst<B>,
+    targets: &HashSet<B>,
+) -> StructuredAst<B> {
+    let label = |ast: &StructuredAst<B>| match *ast {
+        StructuredAst::Block(b) | StructuredAst::While { header: b, .. }
+            if targets.contains(&b) =>
+        {
+            Some(StructuredAst::Label(b))
+        }
+        _ => None,
+    };
+    match ast {
+        StructuredAst::Seq(seq) => {
+            let mut labeled = Vec::with_capacity(seq.len());
+            for ast in seq {
+                labeled.extend(label(&ast));
+                labeled.push(add_labels(ast, targets));
+            }
+            StructuredAst::Seq(labeled)
+        }
+        StructuredAst::If {
+            cond,
+            negated,
+            then,
+            els,
+        } => StructuredAst::If {
+            cond: cond,
+            negated: negated,
+            then: Box::new(add_labels(*then, targets)),
+            els: els.map(|els| Box::new(add_labels(*els, targets))),
+        },
+        StructuredAst::While {
+            header,
+            negated,
+            body,
+        } => StructuredAst::While {
+            header: header,
+            negated: negated,
+            body: Box::new(add_labels(*body, targets)),
+        },
+        StructuredAst::Loop(body) => StructuredAst::Loop(Box::new(add_labels(*body, targets))),
+        ast => ast,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StructuredAst::*;
+    use super::*;
+    use crate::middle::ir::MAddress;
+    use crate::middle::ssa::cfg_traits::CFGMod;
+    use crate::middle::ssa::ssastorage::SSAStorage;
+    use petgraph::graph::NodeIndex;
+
+    const FALSE: u8 = 0;
+    const TRUE: u8 = 1;
+    const UNCOND: u8 = 2;
+
+    /// Builds a CFG with a block per address, the first one being the entry, and the given edges
+    /// between the blocks at these addresses.
+    fn cfg(addresses: &[u64], edges: &[(u64, u64, u8)]) -> (SSAStorage, HashMap<u64, NodeIndex>) {
+        let mut ssa = SSAStorage::new();
+        let blocks = addresses
+            .iter()
+            .map(|&a| (a, ssa.insert_block(MAddress::new(a, 0)).unwrap()))
+            .collect::<HashMap<_, _>>();
+        ssa.set_entry_node(blocks[&addresses[0]]);
+        for &(src, dst, index) in edges {
+            ssa.insert_control_edge(blocks[&src], blocks[&dst], index);
+        }
+        (ssa, blocks)
+    }
+
+    fn seq<B>(asts: Vec<StructuredAst<B>>) -> Box<StructuredAst<B>> {
+        Box::new(Seq(asts))
+    }
+
+    #[test]
+    fn if_else() {
+        // if (0x0) { 0x10 } else { 0x20 } 0x30
+        let (ssa, b) = cfg(
+            &[0x0, 0x10, 0x20, 0x30],
+            &[
+                (0x0, 0x10, TRUE),
+                (0x0, 0x20, FALSE),
+                (0x10, 0x30, UNCOND),
+                (0x20, 0x30, UNCOND),
+            ],
+        );
+        let expected = Seq(vec![
+            Block(b[&0x0]),
+            If {
+                cond: b[&0x0],
+                negated: false,
+                then: seq(vec![Block(b[&0x10])]),
+                els: Some(seq(vec![Block(b[&0x20])])),
+            },
+            Block(b[&0x30]),
+        ]);
+        assert_eq!(structure(&ssa), expected);
+    }
+
+    #[test]
+    fn if_without_then() {
+        // if (!0x0) { 0x20 } 0x30
+        let (ssa, b) = cfg(
+            &[0x0, 0x20, 0x30],
+            &[(0x0, 0x30, TRUE), (0x0, 0x20, FALSE), (0x20, 0x30, UNCOND)],
+        );
+        let expected = Seq(vec![
+            Block(b[&0x0]),
+            If {
+                cond: b[&0x0],
+                negated: true,
+                then: seq(vec![Block(b[&0x20])]),
+                els: None,
+            },
+            Block(b[&0x30]),
+        ]);
+        assert_eq!(structure(&ssa), expected);
+    }
+
+    #[test]
+    fn while_loop() {
+        // 0x0; while (0x10) { 0x20 } 0x30
+        let (ssa, b) = cfg(
+            &[0x0, 0x10, 0x20, 0x30],
+            &[
+                (0x0, 0x10, UNCOND),
+                (0x10, 0x20, TRUE),
+                (0x10, 0x30, FALSE),
+                (0x20, 0x10, UNCOND),
+            ],
+        );
+        let expected = Seq(vec![
+            Block(b[&0x0]),
+            While {
+                header: b[&0x10],
+                negated: false,
+                body: seq(vec![Block(b[&0x20])]),
+            },
+            Block(b[&0x30]),
+        ]);
+        assert_eq!(structure(&ssa), expected);
+    }
+
+    #[test]
+    fn loop_with_break() {
+        // 0x0; loop { 0x10; if (0x20) break; } 0x30
+        let (ssa, b) = cfg(
+            &[0x0, 0x10, 0x20, 0x30],
+            &[
+                (0x0, 0x10, UNCOND),
+                (0x10, 0x20, UNCOND),
+                (0x20, 0x30, TRUE),
+                (0x20, 0x10, FALSE),
+            ],
+        );
+        let expected = Seq(vec![
+            Block(b[&0x0]),
+            Loop(seq(vec![
+                Block(b[&0x10]),
+                Block(b[&0x20]),
+                If {
+                    cond: b[&0x20],
+                    negated: false,
+                    then: seq(vec![Break]),
+                    els: Some(seq(vec![Continue])),
+                },
+            ])),
+            Block(b[&0x30]),
+        ]);
+        assert_eq!(structure(&ssa), expected);
+    }
+
+    #[test]
+    fn irreducible_falls_back_to_goto() {
+        // The cycle between 0x10 and 0x20 can be entered through either block.
+        let (ssa, b) = cfg(
+            &[0x0, 0x10, 0x20],
+            &[
+                (0x0, 0x10, FALSE),
+                (0x0, 0x20, TRUE),
+                (0x10, 0x20, UNCOND),
+                (0x20, 0x10, UNCOND),
+            ],
+        );
+        let expected = Seq(vec![
+            Block(b[&0x0]),
+            If {
+                cond: b[&0x0],
+                negated: true,
+                then: seq(vec![Label(b[&0x10]), Block(b[&0x10])]),
+                els: None,
+            },
+            Block(b[&0x20]),
+            Goto(b[&0x10]),
+        ]);
+        assert_eq!(structure(&ssa), expected);
+    }
+}
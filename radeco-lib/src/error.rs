@@ -0,0 +1,78 @@
+//! Errors reported by the loading, analysis and decompilation entry points of radeco-lib.
+//!
+//! Most of the library still assumes well-formed input, but the errors an embedder can expect
+//! from the outside world (a failing `Source`, malformed ESIL, a function which doesn't exist) are
+//! returned as a `RadecoError` rather than panicking.
+
+use crate::frontend::radeco_source::SourceErr;
+use crate::middle::regfile::RegFileError;
+
+use std::{error, fmt};
+
+/// ESIL of an instruction which couldn't be lifted to SSA.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstructError {
+    /// Address of the instruction
+    pub address: u64,
+    pub esil: String,
+    /// What was wrong with the ESIL
+    pub reason: String,
+}
+
+impl fmt::Display for ConstructError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unable to lift `{}` at {:#x}: {}",
+            self.esil, self.address, self.reason
+        )
+    }
+}
+
+impl error::Error for ConstructError {}
+
+#[derive(Debug)]
+pub enum RadecoError {
+    /// The `Source` failed to provide some information.
+    Source(SourceErr),
+    /// The register profile is inconsistent.
+    RegisterProfile(RegFileError),
+    /// The SSA of a function couldn't be constructed.
+    Construction(ConstructError),
+    /// No function has the given name.
+    MissingFunction(String),
+    /// An analysis or the decompilation of a function failed.
+    Analysis(String),
+}
+
+impl fmt::Display for RadecoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &RadecoError::Source(ref e) => write!(f, "{}", e),
+            &RadecoError::RegisterProfile(ref e) => write!(f, "{}", e),
+            &RadecoError::Construction(ref e) => write!(f, "{}", e),
+            &RadecoError::MissingFunction(ref name) => write!(f, "{} is not found", name),
+            &RadecoError::Analysis(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl error::Error for RadecoError {}
+
+impl From<SourceErr> for RadecoError {
+    fn from(e: SourceErr) -> RadecoError {
+        RadecoError::Source(e)
+    }
+}
+
+impl From<RegFileError> for RadecoError {
+    fn from(e: RegFileError) -> RadecoError {
+        RadecoError::RegisterProfile(e)
+    }
+}
+
+impl From<ConstructError> for RadecoError {
+    fn from(e: ConstructError) -> RadecoError {
+        RadecoError::Construction(e)
+    }
+}
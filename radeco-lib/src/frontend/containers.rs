@@ -3,6 +3,7 @@
 #![allow(deprecated)]
 #![deprecated(since="0.2.0", note="Replace with `radeco_containers`")]
 
+use crate::error::RadecoError;
 use crate::frontend::bindings::{Binding, LocalInfo, RBind, RBindings, RadecoBindings};
 
 use crate::frontend::source::Source;
@@ -117,14 +118,17 @@ impl<B: RBindings> RadecoFunction<B> {
         }
     }
 
-    pub fn construct(reg_profile: &LRegInfo, insts: Vec<LOpInfo>) -> RadecoFunction<B> {
+    pub fn construct(
+        reg_profile: &LRegInfo,
+        insts: Vec<LOpInfo>,
+    ) -> Result<RadecoFunction<B>, RadecoError> {
         let mut rfn = RadecoFunction::new();
         {
-            let regfile = SubRegisterFile::new(reg_profile);
+            let regfile = SubRegisterFile::new(reg_profile)?;
             let mut constructor = SSAConstruct::new(&mut rfn.ssa, &regfile);
-            constructor.run(insts.as_slice());
+            constructor.run(insts.as_slice())?;
         }
-        rfn
+        Ok(rfn)
     }
 }
 
@@ -135,9 +139,9 @@ fn ssa_single_fn(
     reg_info: &LRegInfo,
     instructions: Vec<LOpInfo>,
     offset: u64,
-) -> DefaultFnTy {
+) -> Result<DefaultFnTy, RadecoError> {
     radeco_trace!("Construction SSA for: {:?}", f.name);
-    let mut rfn = RadecoFunction::construct(reg_info, instructions);
+    let mut rfn = RadecoFunction::construct(reg_info, instructions)?;
     rfn.name = if let Some(name) = f.name.as_ref() {
         name.clone()
     } else {
@@ -172,7 +176,7 @@ fn ssa_single_fn(
             .into_iter()
             .collect();
     }
-    rfn
+    Ok(rfn)
 }
 
 // This function fixes up the call information.
@@ -388,7 +392,9 @@ impl<'a, T: 'a + Source> From<&'a mut T> for RadecoModule<'a, DefaultFnTy> {
         });
         let mut rmod = RadecoModule::default();
         let mut handles = Vec::new();
-        rmod.regfile = Some(SubRegisterFile::new(&reg_info));
+        rmod.regfile = SubRegisterFile::new(&reg_info)
+            .map_err(|_e| radeco_err!("{}", _e))
+            .ok();
         let (tx, rx) = sync::mpsc::channel();
         let fns = source.functions().unwrap_or_else(|_e| {
             radeco_err!("{:?}", _e);
@@ -416,7 +422,13 @@ impl<'a, T: 'a + Source> From<&'a mut T> for RadecoModule<'a, DefaultFnTy> {
             let handle = thread::Builder::new()
                 .name(f.name.as_ref().unwrap().to_owned())
                 .spawn(move || {
-                    let mut rfn = ssa_single_fn(&f, &reg_info, instructions, offset);
+                    let mut rfn = match ssa_single_fn(&f, &reg_info, instructions, offset) {
+                        Ok(rfn) => rfn,
+                        Err(_e) => {
+                            radeco_err!("{}", _e);
+                            return false;
+                        }
+                    };
                     // Attach additional information as necessay from `FunctionInfo`.
                     // {
                     //     // Add all defined registers to bindings.
@@ -451,17 +463,21 @@ impl<'a, T: 'a + Source> From<&'a mut T> for RadecoModule<'a, DefaultFnTy> {
                         radeco_err!("function name not found");
                         "Unknown".to_string()
                     });
-                    if tx.send((offset, func_name, rfn)).is_err() {
+                    let sent = tx.send((offset, func_name, rfn)).is_ok();
+                    if !sent {
                         radeco_err!("send failed");
-                    };
+                    }
+                    sent
                 });
             handles.push(handle.expect(""));
         }
 
         let mut success = 0;
         for h in handles {
+            // Only the functions which were sent are received.
             match h.join() {
-                Ok(_) => success += 1,
+                Ok(true) => success += 1,
+                Ok(false) => {}
                 Err(_e) => radeco_warn!("{:?}", _e),
             }
         }
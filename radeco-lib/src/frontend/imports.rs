@@ -1,16 +1,19 @@
 //! Defines structs and methods to deal with imports and dynamic linking
 
-use crate::frontend::radeco_containers::RadecoFunction;
+use crate::frontend::radeco_containers::{FunctionRef, RadecoFunction};
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cell::RefCell;
 
 use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ImportInfo {
     pub plt: u64,
     pub name: Cow<'static, str>,
     pub rfn: Arc<RefCell<RadecoFunction>>,
+    /// The function exported by another module of the project this import is linked to
+    pub resolved: Option<FunctionRef>,
 }
 
 impl ImportInfo {
@@ -21,6 +24,7 @@ impl ImportInfo {
             plt: plt,
             name: name,
             rfn: Arc::new(RefCell::new(rfn)),
+            resolved: None,
         }
     }
 }
@@ -1,13 +1,22 @@
 //! Implements some low-level analysis as a part of frontend
 
-use crate::frontend::radeco_containers::{CallContextInfo, CallGraph, RadecoFunction, RadecoModule};
-use crate::middle::ir::MOpcode;
-use crate::middle::ssa::ssa_traits::{NodeType, SSAWalk, SSA};
+use crate::analysis::flag_fold::width_of;
+use crate::frontend::radeco_containers::{
+    CallContextInfo, CallGraph, FunctionRef, ProjectCallGraph, RadecoFunction, RadecoModule,
+};
+use crate::middle::dot::{self, GraphMl};
+use crate::middle::ir::{MOpcode, WidthSpec};
+use crate::middle::regfile::RegisterId;
+use crate::middle::ssa::ssa_traits::{NodeType, SSAWalk, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+use petgraph::algo::tarjan_scc;
 use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
 use petgraph::Direction;
 use r2papi::structs::FunctionInfo;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 /// Converts call graph information from `Source`, represented in FunctionInfo,
 /// into an actual graph with links.
 pub fn load_call_graph(finfos: &[FunctionInfo], rmod: &RadecoModule) -> CallGraph {
@@ -40,6 +49,16 @@ pub fn load_call_graph(finfos: &[FunctionInfo], rmod: &RadecoModule) -> CallGrap
                 }
             }
         }
+        // Tail calls are jumps for the `Source`, see `find_tail_calls`.
+        if let (Some(&cn), Some(rfn)) = (fnode, rmod.functions.get(&offset)) {
+            for (&csite, target) in rfn.tail_calls() {
+                if let Some(&tn) = node_map.get(target) {
+                    let mut cctx = CallContextInfo::default();
+                    cctx.csite = csite;
+                    cg.add_edge(cn, tn, cctx);
+                }
+            }
+        }
     }
 
     // Initial callsite analysis to fix up CallContextInfo on the edges.
@@ -61,8 +80,46 @@ pub fn load_call_graph(finfos: &[FunctionInfo], rmod: &RadecoModule) -> CallGrap
     cg
 }
 
+/// Marks the jumps to the start of another function, one of `Source::functions()` or an import,
+/// as tail calls of the function they are in, see `RadecoFunction::tail_calls`. Only the
+/// unconditional jumps to a constant address are considered, and a jump to the start of its own
+/// function is left as a loop.
+pub fn find_tail_calls(rmod: &mut RadecoModule, pc: &str) {
+    let starts = rmod
+        .functions
+        .keys()
+        .chain(rmod.imports.keys())
+        .cloned()
+        .collect::<HashSet<_>>();
+    for rfn in rmod.functions.values_mut() {
+        let offset = rfn.offset;
+        let tail_calls: BTreeMap<u64, u64> = rfn
+            .instructions()
+            .iter()
+            .filter_map(|op| Some((op.offset?, jump_target(op.esil.as_ref()?, pc)?)))
+            .filter(|&(_, target)| target != offset && starts.contains(&target))
+            .collect();
+        rfn.set_tail_calls(tail_calls);
+    }
+}
+
+// Target of a jump lifted from `target,pc,=`, with a constant `target`.
+fn jump_target(esil: &str, pc: &str) -> Option<u64> {
+    let tokens = esil.split(',').collect::<Vec<_>>();
+    match tokens.as_slice() {
+        [target, reg, "="] if *reg == pc => {
+            if target.starts_with("0x") {
+                u64::from_str_radix(&target[2..], 16).ok()
+            } else {
+                target.parse().ok()
+            }
+        }
+        _ => None,
+    }
+}
+
 /// Iterates through nodes in SSA for rfn and initializes the inital CallContextInfo
-fn analyze_callsite_initial(rfn: &RadecoFunction) -> HashMap<u64, CallContextInfo> {
+pub fn analyze_callsite_initial(rfn: &RadecoFunction) -> HashMap<u64, CallContextInfo> {
     let mut cctxs = HashMap::new();
     let ssa = rfn.ssa();
     for node in ssa.inorder_walk() {
@@ -140,11 +197,21 @@ pub fn init_call_ctx(rmod: &mut RadecoModule) {
             if let Some((callee_cgid, args)) = callee_info {
                 // Access the actual callsite in rfn.
                 if let Some(mut cctx) = csites.remove(&csite) {
-                    cctx.map = cctx
-                        .map
-                        .iter()
-                        .map(|&(k, _)| k)
-                        .zip(args.into_iter().map(|v| v.idx))
+                    // Map the value of the register of every argument at the callsite, and the
+                    // call itself for the return value, to the binding of the callee. Arguments
+                    // the callee doesn't read have no binding, see `RadecoFunction::mark_args`.
+                    let csite_node = cctx.csite_node;
+                    let reg_args = utils::call_info(csite_node, rfn.ssa()).map(|i| i.register_args);
+                    cctx.map = args
+                        .into_iter()
+                        .filter_map(|binding| {
+                            if binding.btype.is_return() {
+                                return Some((csite_node, binding.idx));
+                            }
+                            let regid = RegisterId::from_u8(binding.ridx? as u8);
+                            let &value = reg_args.as_ref()?.get(regid)?;
+                            Some((value, binding.idx))
+                        })
                         .collect();
                     // Update callsite information in the callgraph.
                     rmod.callgraph.update_edge(rfn.cgid(), callee_cgid, cctx);
@@ -153,3 +220,432 @@ pub fn init_call_ctx(rmod: &mut RadecoModule) {
         }
     }
 }
+
+/// Infers a best-effort signature for every function in `rmod`, i.e. the type of each of its
+/// argument bindings. The width of an argument is the widest value passed in its register over all
+/// the callsites of the function, or the width of the register at entry if there are none. An
+/// argument used as the address of an `OpLoad`/`OpStore` is assumed to be a pointer.
+pub fn infer_signatures(rmod: &mut RadecoModule) {
+    let cg_nodes: HashMap<u64, NodeIndex> = rmod
+        .callgraph
+        .node_indices()
+        .map(|n| (rmod.callgraph[n], n))
+        .collect();
+
+    let mut signatures = HashMap::new();
+    for (&offset, rfn) in &rmod.functions {
+        // (SSA of the caller, call node) for every callsite of `rfn`
+        let callsites = cg_nodes
+            .get(&offset)
+            .map(|&n| {
+                rmod.callgraph
+                    .edges_directed(n, Direction::Incoming)
+                    .filter_map(|e| {
+                        let caller = rmod.functions.get(&rmod.callgraph[e.source()])?;
+                        Some((caller.ssa(), e.weight().csite_node))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let ssa = rfn.ssa();
+        let signature = rfn
+            .bindings()
+            .iter()
+            .filter(|b| b.btype.is_argument())
+            .map(|arg| {
+                let passed = arg.ridx.and_then(|ridx| {
+                    callsites
+                        .iter()
+                        .filter_map(|&(caller_ssa, call)| {
+                            let value = caller_ssa
+                                .sparse_operands_of(call)
+                                .into_iter()
+                                .find(|&(i, _)| i as u64 == ridx + 1)?
+                                .1;
+                            passed_width(caller_ssa, value)
+                        })
+                        .max()
+                });
+                let width = passed
+                    .or_else(|| width_of(ssa, arg.idx))
+                    .map_or(WidthSpec::Unknown, WidthSpec::Known);
+                if is_address(ssa, arg.idx) {
+                    ValueInfo::new_reference(width)
+                } else {
+                    ValueInfo::new_scalar(width)
+                }
+            })
+            .collect::<Vec<_>>();
+        signatures.insert(offset, signature);
+    }
+
+    for (offset, signature) in signatures {
+        if let Some(rfn) = rmod.functions.get_mut(&offset) {
+            *rfn.signature_mut() = signature;
+        }
+    }
+}
+
+/// Width of the argument `value`, looking through zero and sign extensions.
+fn passed_width(ssa: &SSAStorage, value: NodeIndex) -> Option<u16> {
+    match ssa.opcode(value) {
+        Some(MOpcode::OpZeroExt(_)) | Some(MOpcode::OpSignExt(_)) => ssa
+            .operands_of(value)
+            .first()
+            .and_then(|&v| width_of(ssa, v)),
+        _ => width_of(ssa, value),
+    }
+}
+
+/// Whether `node` is used as the address of a memory access.
+fn is_address(ssa: &SSAStorage, node: NodeIndex) -> bool {
+    ssa.uses_of(node)
+        .into_iter()
+        .any(|user| match ssa.opcode(user) {
+            Some(MOpcode::OpLoad) | Some(MOpcode::OpStore) => ssa
+                .sparse_operands_of(user)
+                .iter()
+                .any(|&(i, operand)| i == 1 && operand == node),
+            _ => false,
+        })
+}
+
+/// Links the imports of every module to the functions exported, under the same name, by the
+/// other modules. The argument and return bindings of the exported function are copied over to
+/// the import, so that `init_call_ctx` maps the callsites into the context of the actual function.
+///
+/// Returns the call graph spanning all the modules.
+pub fn link_modules(modules: &mut [RadecoModule]) -> ProjectCallGraph {
+    // Exported function name -> first module (in loading order) exporting it.
+    let mut exports = HashMap::new();
+    for (i, rmod) in modules.iter().enumerate() {
+        for export in rmod.exports() {
+            if let (Some(name), Some(vaddr)) = (&export.name, export.vaddr) {
+                exports.entry(name.clone()).or_insert(FunctionRef {
+                    module: i,
+                    offset: vaddr,
+                });
+            }
+        }
+    }
+
+    let mut links = Vec::new();
+    for (i, rmod) in modules.iter().enumerate() {
+        for (&plt, ifn) in &rmod.imports {
+            match exports.get(&*ifn.name) {
+                Some(target) if target.module != i => {
+                    let bindings = modules[target.module]
+                        .functions
+                        .get(&target.offset)
+                        .map(|rfn| {
+                            rfn.bindings()
+                                .iter()
+                                .filter(|x| x.btype.is_argument() || x.btype.is_return())
+                                .cloned()
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+                    links.push((i, plt, *target, bindings));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut relinked = Vec::new();
+    for (i, plt, target, bindings) in links {
+        radeco_trace!("link_modules|{}:{:#x} -> {:?}", i, plt, target);
+        if let Some(ifn) = modules[i].imports.get_mut(&plt) {
+            ifn.resolved = Some(target);
+            if !bindings.is_empty() {
+                *ifn.rfn.borrow_mut().bindings_mut() = bindings;
+                relinked.push(i);
+            }
+        }
+    }
+    relinked.dedup();
+    for i in relinked {
+        init_call_ctx(&mut modules[i]);
+    }
+
+    // Merge all the module-level call graphs and add the edges across modules.
+    let mut cg = ProjectCallGraph::new();
+    let mut node_map = HashMap::new();
+    {
+        let mut node = |cg: &mut ProjectCallGraph, fref: FunctionRef| {
+            *node_map.entry(fref).or_insert_with(|| cg.add_node(fref))
+        };
+        for (i, rmod) in modules.iter().enumerate() {
+            for edge in rmod.callgraph.raw_edges() {
+                let caller = FunctionRef {
+                    module: i,
+                    offset: rmod.callgraph[edge.source()],
+                };
+                let callee = FunctionRef {
+                    module: i,
+                    offset: rmod.callgraph[edge.target()],
+                };
+                let (src, dst) = (node(&mut cg, caller), node(&mut cg, callee));
+                cg.add_edge(src, dst, edge.weight.csite);
+            }
+            for (&plt, ifn) in &rmod.imports {
+                if let Some(target) = ifn.resolved {
+                    let stub = FunctionRef {
+                        module: i,
+                        offset: plt,
+                    };
+                    let (src, dst) = (node(&mut cg, stub), node(&mut cg, target));
+                    cg.add_edge(src, dst, plt);
+                }
+            }
+        }
+    }
+
+    cg
+}
+
+/// Text report of the call graph of `rmod`. Every function, sorted by address, is followed by
+/// the functions it calls with the number of callsites calling them:
+///
+/// ```text
+/// 0x00001000 sym.fact (recursive)
+///     -> 0x00001000 sym.fact x1
+///     -> 0x00002000 sym.imp.printf x2
+/// ```
+///
+/// The functions part of a cycle of calls, including a function calling itself, are marked as
+/// recursive.
+pub fn callgraph_report(rmod: &RadecoModule) -> String {
+    let cg = &rmod.callgraph;
+    let mut recursive = HashSet::new();
+    for scc in tarjan_scc(cg) {
+        if scc.len() > 1 || cg.find_edge(scc[0], scc[0]).is_some() {
+            recursive.extend(scc.into_iter().map(|n| cg[n]));
+        }
+    }
+
+    // Function -> callee -> number of callsites.
+    let mut calls = rmod
+        .functions
+        .keys()
+        .map(|&offset| (offset, BTreeMap::new()))
+        .collect::<BTreeMap<_, _>>();
+    for node in cg.node_indices() {
+        let callees = calls.entry(cg[node]).or_insert_with(BTreeMap::new);
+        for er in cg.edges_directed(node, Direction::Outgoing) {
+            *callees.entry(cg[er.target()]).or_insert(0) += 1;
+        }
+    }
+
+    let mut report = Vec::new();
+    for (offset, callees) in calls {
+        let marker = if recursive.contains(&offset) {
+            " (recursive)"
+        } else {
+            ""
+        };
+        let name = rmod.name_at(offset);
+        report.push(format!("{:#010x} {}{}", offset, name, marker));
+        for (callee, count) in callees {
+            report.push(format!(
+                "    -> {:#010x} {} x{}",
+                callee,
+                rmod.name_at(callee),
+                count
+            ));
+        }
+    }
+    report.join("\n")
+}
+
+// The call graph of the module, with a node for every function and an edge for every callsite.
+impl GraphMl for RadecoModule {
+    fn node_keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[("name", "string"), ("address", "string")]
+    }
+
+    fn edge_keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[("callsite", "string")]
+    }
+
+    fn graphml_nodes(&self) -> Vec<(usize, Vec<Option<String>>)> {
+        let cg = &self.callgraph;
+        cg.node_indices()
+            .map(|n| {
+                let name = self.name_at(cg[n]);
+                (n.index(), vec![Some(name), Some(format!("{:#x}", cg[n]))])
+            })
+            .collect()
+    }
+
+    fn graphml_edges(&self) -> Vec<(usize, usize, Vec<Option<String>>)> {
+        self.callgraph
+            .edge_references()
+            .map(|er| {
+                let csite = format!("{:#x}", er.weight().csite);
+                (er.source().index(), er.target().index(), vec![Some(csite)])
+            })
+            .collect()
+    }
+}
+
+/// The call graph of `rmod` in GraphML, see `dot::emit_graphml`.
+pub fn callgraph_graphml(rmod: &RadecoModule) -> String {
+    dot::emit_graphml(rmod)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::radeco_containers::{BindingType, VarBinding};
+    use crate::utils::test_support::{entry_reg, load_at, register_file};
+
+    // Reads a 32 bit value from the pointer in rsi
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLEE_SSA_TXT: &str = "\
+define-fun sym.callee(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x002000.0000(sz 0x4):
+        [@0x002000.0001] %4: $Unknown32 = Load(%3, %2);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $mem = %3;
+}
+";
+
+    /// A function calling `sym.callee` at `addr` with a `width` bits value in rdi
+    fn caller_il(addr: u64, width: u16) -> String {
+        format!(
+            "\
+define-fun sym.caller{w}(unknown) -> unknown {{
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_{a:#08x}.0000(sz 0x8):
+        [@{a:#08x}.0001] %4: $Unknown{w} = Narrow{w}(%1);
+        [@{a:#08x}.0002] %5: $Unknown64 = ZeroExt64(%4);
+        [@{a:#08x}.0003] (%6: $Unknown0 = $mem) = CALL #x2000($rdi=%5, $rsi=%2, $mem=%3);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %6;
+}}
+",
+            a = addr,
+            w = width
+        )
+    }
+
+    #[test]
+    fn widest_argument_wins() {
+        let regfile = register_file();
+
+        let mut callee = load_at(CALLEE_SSA_TXT, 0x2000, &regfile);
+        for (i, reg) in ["rdi", "rsi"].iter().enumerate() {
+            let idx = entry_reg(callee.ssa(), reg);
+            let ridx = regfile.register_id_by_name(reg).unwrap().to_u8() as u64;
+            callee.bindings_mut().push(VarBinding::new(
+                BindingType::RegisterArgument(i),
+                String::new(),
+                Some(reg.to_string()),
+                idx,
+                Some(ridx),
+            ));
+        }
+
+        let mut rmod = RadecoModule::default();
+        let callee_id = rmod.callgraph.add_node(callee.offset);
+        rmod.functions.insert(callee.offset, callee);
+        for &(addr, width) in &[(0x1000, 8), (0x1100, 32)] {
+            let caller = load_at(&caller_il(addr, width), addr, &regfile);
+            let call = caller
+                .ssa()
+                .inorder_walk()
+                .find(|&n| caller.ssa().opcode(n) == Some(MOpcode::OpCall))
+                .unwrap();
+            let caller_id = rmod.callgraph.add_node(addr);
+            let cctx = CallContextInfo {
+                map: Vec::new(),
+                csite_node: call,
+                csite: addr + 3,
+            };
+            rmod.callgraph.add_edge(caller_id, callee_id, cctx);
+            rmod.functions.insert(addr, caller);
+        }
+
+        infer_signatures(&mut rmod);
+
+        let signature = rmod.functions[&0x2000].signature();
+        assert_eq!(signature.len(), 2);
+        assert_eq!(signature[0], ValueInfo::new_scalar(WidthSpec::Known(32)));
+        assert_eq!(signature[1], ValueInfo::new_reference(WidthSpec::Known(64)));
+        // Nobody calls the callers and they have no argument bindings.
+        assert!(rmod.functions[&0x1000].signature().is_empty());
+    }
+
+    #[test]
+    fn self_recursive_function_in_report() {
+        let mut rmod = RadecoModule::default();
+        for &(offset, name) in &[(0x1000, "main"), (0x2000, "sym.fact"), (0x3000, "sym.leaf")] {
+            let mut rfn = RadecoFunction::default();
+            rfn.offset = offset;
+            rfn.name = name.into();
+            rmod.functions.insert(offset, rfn);
+        }
+        let main = rmod.callgraph.add_node(0x1000);
+        let fact = rmod.callgraph.add_node(0x2000);
+        let leaf = rmod.callgraph.add_node(0x3000);
+        for &(caller, callee, csite) in &[
+            (main, fact, 0x1004),
+            (main, fact, 0x1010),
+            (fact, fact, 0x2008),
+            (fact, leaf, 0x200c),
+        ] {
+            let mut cctx = CallContextInfo::default();
+            cctx.csite = csite;
+            rmod.callgraph.add_edge(caller, callee, cctx);
+        }
+
+        let report = callgraph_report(&rmod);
+        assert_eq!(
+            report.lines().collect::<Vec<_>>(),
+            vec![
+                "0x00001000 main",
+                "    -> 0x00002000 sym.fact x2",
+                "0x00002000 sym.fact (recursive)",
+                "    -> 0x00002000 sym.fact x1",
+                "    -> 0x00003000 sym.leaf x1",
+                "0x00003000 sym.leaf",
+            ]
+        );
+    }
+
+    #[test]
+    fn callgraph_graphml_is_escaped() {
+        let mut rmod = RadecoModule::default();
+        for &(offset, name) in &[(0x1000, "main"), (0x2000, "sym.operator<<")] {
+            let mut rfn = RadecoFunction::default();
+            rfn.offset = offset;
+            rfn.name = name.into();
+            rmod.functions.insert(offset, rfn);
+        }
+        let main = rmod.callgraph.add_node(0x1000);
+        let op = rmod.callgraph.add_node(0x2000);
+        let mut cctx = CallContextInfo::default();
+        cctx.csite = 0x1004;
+        rmod.callgraph.add_edge(main, op, cctx);
+
+        let graphml = callgraph_graphml(&rmod);
+        assert_eq!(dot::count_xml_elements(&graphml, "node"), Ok(2));
+        assert_eq!(dot::count_xml_elements(&graphml, "edge"), Ok(1));
+        assert!(graphml.contains("<data key=\"node_name\">sym.operator&lt;&lt;</data>"));
+        assert!(graphml.contains("<data key=\"edge_callsite\">0x1004</data>"));
+    }
+}
@@ -29,37 +29,56 @@
 //!
 //! For more examples of loading, check the `examples/` directory of this project.
 
+use crate::analysis::alias::AliasSets;
+use crate::analysis::functions::liveness;
+use crate::analysis::globals::GlobalVar;
+use crate::analysis::loops::LoopForest;
+use crate::analysis::vrange::ValueRanges;
+use crate::error::{ConstructError, RadecoError};
 use crate::frontend::imports::ImportInfo;
 use crate::frontend::llanalyzer;
-use crate::frontend::radeco_source::Source;
+use crate::frontend::radeco_source::{Source, SourceErr};
 use crate::frontend::ssaconstructor::SSAConstruct;
 
-use crate::middle::regfile::{RegisterUsage, SubRegisterFile};
-use crate::middle::ssa::cfg_traits::CFG;
-use crate::middle::ssa::ssa_traits::{NodeType, SSA};
+use crate::middle::ir::{MAddress, MOpcode};
+use crate::middle::phiplacement::{self, UNCOND_EDGE};
+use crate::middle::regfile::{RegisterId, RegisterUsage, SubRegisterFile};
+use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+use crate::middle::ssa::ssa_traits::{NodeType, SSAExtra, ValueInfo, SSA};
+use crate::middle::ssa::ssadot;
 
 use crate::middle::ssa::ssastorage::SSAStorage;
+use petgraph::algo::tarjan_scc;
 use petgraph::Direction;
 
-use petgraph::graph::{Graph, NodeIndex};
+use petgraph::graph::{EdgeIndex, Graph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use r2papi::api_trait::R2Api;
 use r2papi::structs::{
-    LCCInfo, LEntryInfo, LExportInfo, LOpInfo, LRelocInfo, LSectionInfo, LStringInfo, LSymbolInfo,
-    LSymbolType, LVarInfo,
+    LCCInfo, LEntryInfo, LExportInfo, LFlagInfo, LOpInfo, LRelocInfo, LSectionInfo, LStringInfo,
+    LSymbolInfo, LSymbolType, LVarInfo,
 };
 
 use r2pipe::r2::R2;
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::{de, Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cell::RefCell;
-use std::cmp::Ordering;
+use std::cmp::{self, Ordering};
 use std::collections::btree_map;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error;
 use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::ops::Range;
+use std::path::Path;
 use std::rc::Rc;
 use std::slice;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::vec;
 
 // use cpuprofiler::PROFILER;
 
@@ -123,7 +142,7 @@ pub mod loader_defaults {
                 .map(|import| import.name.unwrap())
                 .collect::<Vec<_>>();
 
-            let functions = src.functions().expect("failed to get functions");
+            let functions = src.functions_paged().expect("failed to get functions");
 
             functions
                 .into_iter()
@@ -156,13 +175,28 @@ pub mod loader_defaults {
 }
 
 /// Top level container used to hold all analysis
+#[derive(Serialize, Deserialize)]
 pub struct RadecoProject {
     /// Map of loaded modules
     modules: Vec<RadecoModule>,
     /// Register/Arch information for loaded project
     reginfo: Arc<SubRegisterFile>,
+    /// Call graph spanning all the loaded modules
+    callgraph: ProjectCallGraph,
 }
 
+/// Identifies a function within a `RadecoProject` by the index of its module and its offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct FunctionRef {
+    pub module: usize,
+    pub offset: u64,
+}
+
+// Graph where every node is a function of any module of the project. Edges are labeled by the
+// callsite; the edges linking an import stub to the function exported by another module are
+// labeled by the PLT address of the import.
+pub type ProjectCallGraph = Graph<FunctionRef, u64>;
+
 // Graph where every node is an Address (function start address) and edges are labeled
 // by the `callsite`, i.e., the actual location of the call.
 pub type CallGraph = Graph<u64, CallContextInfo>;
@@ -178,7 +212,7 @@ impl CGInfo for CallGraph {
     fn callers<'a>(&'a self, idx: NodeIndex) -> Box<dyn Iterator<Item = (u64, NodeIndex)> + 'a> {
         box self
             .edges_directed(idx, Direction::Incoming)
-            .map(|er| (er.weight().csite, er.target()))
+            .map(|er| (er.weight().csite, er.source()))
     }
 
     // Return (callsite, call target)
@@ -189,7 +223,7 @@ impl CGInfo for CallGraph {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 /// Container to store information about a single loaded binary or library.
 pub struct RadecoModule {
     /// Human-readable name for the  module
@@ -197,6 +231,7 @@ pub struct RadecoModule {
     // Information from the loader
     symbols: Vec<LSymbolInfo>,
     strings: Vec<LStringInfo>,
+    flags: Vec<LFlagInfo>,
     sections: Arc<Vec<LSectionInfo>>,
     // Map from PLT entry address to `ImportInfo` for an import
     pub imports: HashMap<u64, ImportInfo>,
@@ -210,7 +245,18 @@ pub struct RadecoModule {
     /// Map of functions loaded
     pub functions: BTreeMap<u64, RadecoFunction>,
     /// Source used to load this module
+    #[serde(skip)]
     pub source: Option<Rc<dyn Source>>,
+    /// Whether the SSA of the functions was constructed assuming their calling conventions
+    assume_cc: bool,
+    /// Whether the SSA of the functions was constructed keeping the reads of the program counter
+    keep_pc: bool,
+    /// Offsets of the functions whose SSA is stale and has to be reconstructed
+    dirty: HashSet<u64>,
+    /// Register profiles overriding the one of the source for ranges of addresses
+    register_profiles: Vec<(Range<u64>, Arc<SubRegisterFile>)>,
+    /// Global variables recovered by `GlobalVars`, by address
+    globals: BTreeMap<u64, GlobalVar>,
 }
 
 impl fmt::Debug for RadecoModule {
@@ -228,7 +274,7 @@ pub enum FunctionType {
     Import(u16),
 }
 
-#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum BindingType {
     // Arguments - ith argument
     RegisterArgument(usize),
@@ -237,6 +283,8 @@ pub enum BindingType {
     RegisterLocal(String, i64),
     // Stack offset (from "SP")
     StackLocal(usize),
+    // Global variable - address
+    Global(u64),
     // Return
     Return,
     // Unknown
@@ -270,9 +318,16 @@ impl BindingType {
             _ => false,
         }
     }
+
+    pub fn is_global(&self) -> bool {
+        match *self {
+            BindingType::Global(_) => true,
+            _ => false,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct VarBinding {
     pub btype: BindingType,
     name: Cow<'static, str>,
@@ -351,7 +406,7 @@ impl VarBinding {
 pub type VarBindings = Vec<VarBinding>;
 
 /// The type of this function.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FunctionKind {
     Local,
     Imported,
@@ -364,7 +419,33 @@ impl Default for FunctionKind {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+/// The effects of a basic block on the registers and memory, see
+/// `RadecoFunction::block_effects`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockEffect {
+    /// Registers written by the block
+    pub defines: BTreeSet<String>,
+    /// Registers read by the block before it writes them
+    pub uses: BTreeSet<String>,
+    /// It is `true` if the block stores to memory
+    pub writes_memory: bool,
+}
+
+/// A basic block of a function, see `RadecoFunction::basic_blocks`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlockInfo {
+    /// Address of the first instruction of the block
+    pub start: MAddress,
+    /// Size of the block in bytes, 0 for the blocks without instructions (e.g. the entry block)
+    pub size: u64,
+    /// Starting addresses of the successors, with the type of the edge to each of them: 0 when
+    /// the condition ending the block is false, 1 when it is true, and `UNCOND_EDGE` otherwise
+    pub succs: Vec<(MAddress, u8)>,
+    /// It is `true` if the block flows out of the function, to the exit node
+    pub exits: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 /// Container to store information about identified function.
 /// Used as a basic unit in intra-functional analysis.
 pub struct RadecoFunction {
@@ -392,10 +473,43 @@ pub struct RadecoFunction {
     // XXX Should it be separated from RadecoFunction?
     // NodeIndex is SSAStorage's NodeIndex
     binding_map: HashMap<NodeIndex, VarBindings>,
+    /// Inferred types of the arguments, in the order of the argument bindings
+    signature: Vec<ValueInfo>,
     /// Calling convention of this function
     pub callconv: Option<LCCInfo>,
+    /// It is `true` if `callconv` was supplied by the user, see `set_calling_convention`
+    #[serde(default)]
+    custom_callconv: bool,
+    /// Registers clobbered by this function, when supplied by the user along with `callconv`
+    #[serde(default)]
+    clobbers: Option<Vec<String>>,
     /// Register usage of this function
     pub regusage: RegisterUsage,
+    /// Register usage of the callee at each callsite, when known. Calls without one are assumed
+    /// to read and clobber every register and memory.
+    callee_usage: HashMap<u64, RegisterUsage>,
+    /// Disassembly of the instruction each node was lifted from, if the SSA was constructed from
+    /// the instructions of the function
+    disasm: Option<HashMap<NodeIndex, String>>,
+    /// Ranges of the values of the SSA, once computed by the engine
+    value_ranges: Option<ValueRanges>,
+    /// Locations the loads and stores of the SSA may access, see `analysis::alias`
+    alias_sets: Option<AliasSets>,
+    /// Loads of stack slots which may be read before being written, see `analysis::uninit`
+    uninit_reads: Vec<NodeIndex>,
+    /// ESIL tokens which could not be lifted, with the address of their instruction
+    unsupported: Vec<(u64, String)>,
+    /// Constants written to a register during the construction of the SSA, with the address of
+    /// the write and the name of the register. Constants don't belong to any block of the SSA.
+    const_writes: Vec<(NodeIndex, MAddress, String)>,
+    /// Jumps to the start of another function, by the address of the jump, along with the address
+    /// of the function. These are lifted as a call followed by a return.
+    #[serde(default)]
+    tail_calls: BTreeMap<u64, u64>,
+    /// It is `true` if the construction or the analysis of the SSA ran out of time, leaving the
+    /// SSA partial or unsimplified
+    #[serde(default)]
+    timed_out: bool,
     /// Name of the calling convention of this function (e.g. amd64, ms, arm64, etc.)
     // see https://github.com/radare/radare2/tree/9e08da0fa6b6c36edf04db72d22e065ccc90d381/libr/anal/d
     pub callconv_name: String,
@@ -404,15 +518,26 @@ pub struct RadecoFunction {
     pub kind: FunctionKind,
 }
 
+/// Name of the first module of a project loaded from a `Source` without a path, see
+/// `ProjectLoader::source`.
+pub const SOURCE_MODULE_NAME: &str = "main";
+
 #[derive(Default)]
 /// Top-level loader used to initialize a `RadecoProject`
 pub struct ProjectLoader<'a> {
     load_libs: bool,
     path: Cow<'static, str>,
+    // Additional modules to load, each one with an optional source
+    extra_modules: Vec<(Cow<'static, str>, Option<Rc<dyn Source>>)>,
     load_library_path: Option<Cow<'static, str>>,
     filter_modules: Option<fn(&RadecoModule) -> bool>,
     source: Option<Rc<dyn Source>>,
     mloader: Option<ModuleLoader<'a>>,
+    max_function_insts: Option<usize>,
+    fn_timeout: Option<Duration>,
+    max_workers: Option<usize>,
+    assume_cc: bool,
+    keep_pc: bool,
 }
 
 impl<'a> ProjectLoader<'a> {
@@ -420,10 +545,16 @@ impl<'a> ProjectLoader<'a> {
         ProjectLoader {
             load_libs: false,
             path: Cow::from(""),
+            extra_modules: Vec::new(),
             load_library_path: None,
             filter_modules: None,
             source: None,
             mloader: None,
+            max_function_insts: None,
+            fn_timeout: None,
+            max_workers: None,
+            assume_cc: false,
+            keep_pc: false,
         }
     }
     // TODO:
@@ -444,14 +575,34 @@ impl<'a> ProjectLoader<'a> {
         self
     }
 
-    /// Setup and configure `ModuleLoader` to use
+    /// Load the binary at `path` as an additional module of the project. Imports of each module
+    /// are linked to the exports of the others, so that the call graph of the project spans
+    /// all of them.
+    pub fn add_module<T: AsRef<str>>(mut self, path: T) -> ProjectLoader<'a> {
+        self.extra_modules.push((Cow::from(path.as_ref().to_owned()), None));
+        self
+    }
+
+    /// Same as `add_module`, but loads the module named `name` from `source`.
+    pub fn add_module_source<T: AsRef<str>>(
+        mut self,
+        name: T,
+        source: Rc<dyn Source>,
+    ) -> ProjectLoader<'a> {
+        self.extra_modules.push((Cow::from(name.as_ref().to_owned()), Some(source)));
+        self
+    }
+
+    /// Setup and configure `ModuleLoader` to use. If the `ModuleLoader` has a source set, it is
+    /// used for every module.
     pub fn module_loader<'b: 'a>(mut self, mloader: ModuleLoader<'b>) -> ProjectLoader<'a> {
         self.mloader = Some(mloader);
         self
     }
 
     /// Set the source to use for loading. This is propagated to every `ModuleLoader`
-    /// unless it is reconfigured.
+    /// unless it is reconfigured. The module loaded from it is named after the path if one is
+    /// set, and `SOURCE_MODULE_NAME` otherwise.
     pub fn source(mut self, source: Rc<dyn Source>) -> ProjectLoader<'a> {
         self.source = Some(source);
         self
@@ -465,22 +616,73 @@ impl<'a> ProjectLoader<'a> {
         self
     }
 
+    /// Skip the functions with more than `max` instructions in every module, see
+    /// `ModuleLoader::max_function_insts`.
+    pub fn max_function_insts(mut self, max: usize) -> ProjectLoader<'a> {
+        self.max_function_insts = Some(max);
+        self
+    }
+
+    /// Give up on the construction of the SSA of a function after `budget`, in every module, see
+    /// `ModuleLoader::fn_timeout`.
+    pub fn fn_timeout(mut self, budget: Duration) -> ProjectLoader<'a> {
+        self.fn_timeout = Some(budget);
+        self
+    }
+
+    /// Load every module with at most `n` threads, see `ModuleLoader::max_workers`.
+    pub fn max_workers(mut self, n: usize) -> ProjectLoader<'a> {
+        self.max_workers = Some(n);
+        self
+    }
+
+    /// Construct the SSA of the functions of every module assuming their calling conventions,
+    /// see `ModuleLoader::assume_cc`.
+    pub fn assume_cc(mut self) -> ProjectLoader<'a> {
+        self.assume_cc = true;
+        self
+    }
+
+    /// Keep the reads of the program counter in the SSA of the functions of every module, see
+    /// `ModuleLoader::keep_pc`.
+    pub fn keep_pc(mut self) -> ProjectLoader<'a> {
+        self.keep_pc = true;
+        self
+    }
+
     /// Filter loading of `RadecoModules` based on `f`
     pub fn filter_modules(mut self, f: fn(&RadecoModule) -> bool) -> ProjectLoader<'a> {
         self.filter_modules = Some(f);
         self
     }
 
+    fn open_r2(path: &str) -> Result<Rc<dyn Source>, SourceErr> {
+        let mut r2 = R2::new(Some(path)).map_err(|_| SourceErr::SrcErr("Unable to open r2"))?;
+        let _ = r2.raw("e bin.minstr=1".to_string());
+        //New r2 process is launched thus it needs to analyze
+        r2.analyze_all();
+        let r2w = Rc::new(RefCell::new(r2));
+        Ok(Rc::new(r2w))
+    }
+
     /// Kick everything off based on the config/defaults
-    pub fn load(mut self) -> RadecoProject {
+    pub fn load(self) -> RadecoProject {
+        match self.load_project(false) {
+            Ok(rp) => rp,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Same as `load`, but reports the errors of the `Source`, and the functions whose SSA can't
+    /// be constructed, instead of going on, see `ModuleLoader::try_load`.
+    pub fn try_load(self) -> Result<RadecoProject, RadecoError> {
+        self.load_project(true)
+    }
+
+    fn load_project(mut self, strict: bool) -> Result<RadecoProject, RadecoError> {
         if self.source.is_none() {
             // Load r2 source.
-            let mut r2 = R2::new(Some(&self.path)).expect("Unable to open r2");
-            let _ = r2.raw("e bin.minstr=1".to_string());
-            //New r2 process is launched thus it needs to analyze
-            r2.analyze_all();
-            let r2w = Rc::new(RefCell::new(r2));
-            self.source = Some(Rc::new(r2w));
+            self.source = Some(ProjectLoader::open_r2(&self.path)?);
         };
 
         let source = self.source.as_ref().unwrap();
@@ -488,41 +690,73 @@ impl<'a> ProjectLoader<'a> {
         // TODO: Load more arch specific information from the source
 
         if self.mloader.is_none() {
+            // The source is not set here as it differs for every module.
             self.mloader = Some(
                 ModuleLoader::default()
-                    .source(Rc::clone(source))
                     .build_ssa()
                     .build_callgraph()
                     .load_datarefs()
                     .load_locals()
                     .parallel()
                     // .assume_cc()
-                    .stub_imports(),
+                    .stub_imports()
+                    .annotate_flags(),
             );
         }
+        if let Some(max) = self.max_function_insts {
+            self.mloader.as_mut().unwrap().max_function_insts = Some(max);
+        }
+        if let Some(budget) = self.fn_timeout {
+            self.mloader.as_mut().unwrap().fn_timeout = Some(budget);
+        }
+        if let Some(n) = self.max_workers {
+            let mloader = self.mloader.as_mut().unwrap();
+            mloader.parallel = true;
+            mloader.max_workers = Some(n);
+        }
+        if self.assume_cc {
+            self.mloader.as_mut().unwrap().assume_cc = true;
+        }
+        if self.keep_pc {
+            self.mloader.as_mut().unwrap().keep_pc = true;
+        }
 
         let mut mod_map = Vec::new();
 
         {
             let mod_loader = self.mloader.as_mut().unwrap();
-            // TODO: Set name correctly
-            mod_map.push(mod_loader.load(Rc::clone(source)));
+            let mut rmod = mod_loader.load_module(Rc::clone(source), strict)?;
+            rmod.name = if self.path.is_empty() {
+                Cow::from(SOURCE_MODULE_NAME)
+            } else {
+                self.path.clone()
+            };
+            mod_map.push(rmod);
+
+            for (name, msource) in self.extra_modules.drain(..) {
+                let msource = match msource {
+                    Some(msource) => msource,
+                    None => ProjectLoader::open_r2(&name)?,
+                };
+                let mut rmod = mod_loader.load_module(msource, strict)?;
+                rmod.name = name;
+                mod_map.push(rmod);
+            }
         }
 
+        let callgraph = llanalyzer::link_modules(&mut mod_map);
+
         // Clear out irrelevant fields in self and move it into project loader
         // XXX: Do when needed!
         // self.mod_loader = None;
-        let regfile = SubRegisterFile::new(
-            &source
-                .register_profile()
-                .expect("Unable to load register profile"),
-        );
+        let regfile = SubRegisterFile::new(&source.register_profile()?)?;
 
-        RadecoProject {
+        Ok(RadecoProject {
             modules: mod_map,
             // XXX
             reginfo: Arc::new(regfile),
-        }
+            callgraph: callgraph,
+        })
     }
 }
 
@@ -591,6 +825,12 @@ pub struct FunctionIterMut<'f> {
     iter: btree_map::IterMut<'f, u64, RadecoFunction>,
 }
 
+/// Iterator over the functions of a module, callees first. See `RadecoModule::iter_topo`.
+pub struct TopoFunctionIter<'f> {
+    module: &'f RadecoModule,
+    order: vec::IntoIter<u64>,
+}
+
 impl<'f> Iterator for FunctionIter<'f> {
     type Item = ZippedFunction<'f>;
     fn next(&mut self) -> Option<ZippedFunction<'f>> {
@@ -605,6 +845,18 @@ impl<'f> Iterator for FunctionIter<'f> {
     }
 }
 
+impl<'f> Iterator for TopoFunctionIter<'f> {
+    type Item = ZippedFunction<'f>;
+    fn next(&mut self) -> Option<ZippedFunction<'f>> {
+        let offset = self.order.next()?;
+        let rfn = self.module.functions.get_key_value(&offset)?;
+        Some(ZippedFunction {
+            module: self.module,
+            function: rfn,
+        })
+    }
+}
+
 impl<'f> Iterator for FunctionIterMut<'f> {
     type Item = ZippedFunctionMut<'f>;
     fn next(&mut self) -> Option<ZippedFunctionMut<'f>> {
@@ -616,6 +868,14 @@ impl<'f> Iterator for FunctionIterMut<'f> {
     }
 }
 
+/// Runs `op` in `pool` if set, in the global pool of rayon otherwise.
+fn in_pool<R: Send, F: FnOnce() -> R + Send>(pool: Option<&ThreadPool>, op: F) -> R {
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
 #[derive(Default)]
 /// Module-level loader used to construct a `RadecoModule`
 pub struct ModuleLoader<'a> {
@@ -627,8 +887,17 @@ pub struct ModuleLoader<'a> {
     load_datarefs: bool,
     load_locals: bool,
     parallel: bool,
+    max_workers: Option<usize>,
     assume_cc: bool,
+    keep_pc: bool,
     stub_imports: bool,
+    only_reachable: bool,
+    include_indirect: bool,
+    max_function_insts: Option<usize>,
+    max_function_bytes: Option<u64>,
+    fn_timeout: Option<Duration>,
+    annotate_flags: bool,
+    register_profiles: Vec<(Range<u64>, Arc<SubRegisterFile>)>,
 }
 
 impl<'a> ModuleLoader<'a> {
@@ -673,18 +942,41 @@ impl<'a> ModuleLoader<'a> {
 
     /// Executes parallelizable functions in parallel. Uses `num_thread` number
     /// of threads. Defaults to 8 if `None`.
+    ///
+    /// The instructions of the functions are also disassembled in parallel if the source serves
+    /// concurrent requests, see `Source::as_concurrent`.
     pub fn parallel(mut self) -> ModuleLoader<'a> {
         self.parallel = true;
         self
     }
 
+    /// Same as `parallel`, but runs the disassembly and the construction of the SSA in a pool of
+    /// `n` threads rather than in the global pool of rayon, which has as many threads as there
+    /// are CPUs. The loaded module doesn't depend on the number of threads.
+    pub fn max_workers(mut self, n: usize) -> ModuleLoader<'a> {
+        self.parallel = true;
+        self.max_workers = Some(n);
+        self
+    }
+
     /// Assume calling convention information in regfile to be true. This is used for setting up
     /// bindings for arguments and return values for functions.
+    ///
+    /// A call then only reads the arguments of the calling convention and only writes its return
+    /// value, which makes for a much smaller SSA. By default, every call conservatively reads and
+    /// clobbers every register, which is still correct for callees not following the convention.
     pub fn assume_cc(mut self) -> ModuleLoader<'a> {
         self.assume_cc = true;
         self
     }
 
+    /// Keep the reads of the program counter in the SSA, rather than replacing them with the
+    /// address of the next instruction, see `SSAConstruct::construct`.
+    pub fn keep_pc(mut self) -> ModuleLoader<'a> {
+        self.keep_pc = true;
+        self
+    }
+
     /// Create blank, stub entries for imported functions.
     /// Required for load-libs, auto set when load_libs is true for the project loader.
     pub fn stub_imports(mut self) -> ModuleLoader<'a> {
@@ -692,6 +984,64 @@ impl<'a> ModuleLoader<'a> {
         self
     }
 
+    /// Only keep the functions reachable from the entrypoints and exports of the module, see
+    /// `RadecoModule::reachable_functions`. The others are dropped before the SSA is built.
+    /// Needs support from `Source`
+    pub fn only_reachable(mut self) -> ModuleLoader<'a> {
+        self.only_reachable = true;
+        self
+    }
+
+    /// With `only_reachable`, also keep the functions whose address is referenced by a reachable
+    /// function, as they may be called indirectly.
+    pub fn include_indirect(mut self) -> ModuleLoader<'a> {
+        self.include_indirect = true;
+        self
+    }
+
+    /// Skip the functions with more than `max` instructions. Their instructions are not loaded,
+    /// so they are left as stubs in the callgraph with an empty SSA. If the `Source` knows the
+    /// number of instructions of a function (see `Source::instruction_count`), a function over
+    /// the limit isn't disassembled either.
+    pub fn max_function_insts(mut self, max: usize) -> ModuleLoader<'a> {
+        self.max_function_insts = Some(max);
+        self
+    }
+
+    /// Skip the functions larger than `max` bytes, in the same way as `max_function_insts`.
+    pub fn max_function_bytes(mut self, max: u64) -> ModuleLoader<'a> {
+        self.max_function_bytes = Some(max);
+        self
+    }
+
+    /// Give up on the construction of the SSA of a function once `budget` has elapsed since its
+    /// instructions started loading. The instructions left are not lifted and the function is
+    /// recorded as timed out (see `RadecoFunction::timed_out`), so that a single function can't
+    /// stall the loading of the whole module.
+    pub fn fn_timeout(mut self, budget: Duration) -> ModuleLoader<'a> {
+        self.fn_timeout = Some(budget);
+        self
+    }
+
+    /// Annotates the SSA of the functions with the flags of the `Source`, see
+    /// `RadecoModule::annotate_flags`.
+    pub fn annotate_flags(mut self) -> ModuleLoader<'a> {
+        self.annotate_flags = true;
+        self
+    }
+
+    /// Use `regfile` instead of the register profile of the `Source` for the functions starting
+    /// in `range`, e.g. for the Thumb code of an ARM binary. If ranges overlap, the one added
+    /// first is used.
+    pub fn register_profile_for(
+        mut self,
+        range: Range<u64>,
+        regfile: SubRegisterFile,
+    ) -> ModuleLoader<'a> {
+        self.register_profiles.push((range, Arc::new(regfile)));
+        self
+    }
+
     fn init_fn_bindings(rfn: &mut RadecoFunction, sub_reg_f: &SubRegisterFile) {
         // Setup binding information for functions based on reg_p. Note that this essential
         // marks the "potential" arguments without worrying about if they're ever used. Future
@@ -725,7 +1075,8 @@ impl<'a> ModuleLoader<'a> {
                         vb.idx = *entry_state
                             .iter()
                             .find(|&&ridx| {
-                                if let Ok(NodeType::Comment(ref s)) =
+                                if let Ok(NodeType::Comment(ref s))
+                                | Ok(NodeType::Argument(ref s)) =
                                     rfn.ssa().node_data(ridx).map(|n| n.nt)
                                 {
                                     if s == reg.1 {
@@ -743,7 +1094,8 @@ impl<'a> ModuleLoader<'a> {
                         vb.idx = *exit_state
                             .iter()
                             .find(|&&ridx| {
-                                if let Ok(NodeType::Comment(ref s)) =
+                                if let Ok(NodeType::Comment(ref s))
+                                | Ok(NodeType::Argument(ref s)) =
                                     rfn.ssa().node_data(ridx).map(|n| n.nt)
                                 {
                                     if s == reg.1 {
@@ -778,7 +1130,28 @@ impl<'a> ModuleLoader<'a> {
     }
 
     /// Kick everything off and load module information based on config and defaults
+    ///
+    /// The offending instruction of a function with malformed ESIL is lifted as an
+    /// `OpUnsupported` and recorded in `RadecoFunction::unsupported`, use `try_load` to get an
+    /// error instead.
     pub fn load(&mut self, src: Rc<dyn Source>) -> RadecoModule {
+        match self.load_module(src, false) {
+            Ok(rmod) => rmod,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Same as `load`, but fails if the `Source` lacks the register profile or if the SSA of a
+    /// function can't be constructed.
+    pub fn try_load(&mut self, src: Rc<dyn Source>) -> Result<RadecoModule, RadecoError> {
+        self.load_module(src, true)
+    }
+
+    fn load_module(
+        &mut self,
+        src: Rc<dyn Source>,
+        strict: bool,
+    ) -> Result<RadecoModule, RadecoError> {
         let source = if self.source.is_some() {
             self.source.as_ref().unwrap()
         } else {
@@ -793,6 +1166,9 @@ impl<'a> ModuleLoader<'a> {
         floader.source = Some(Rc::clone(source));
 
         let mut rmod = RadecoModule::default();
+        rmod.assume_cc = self.assume_cc;
+        rmod.keep_pc = self.keep_pc;
+        rmod.register_profiles = self.register_profiles.clone();
 
         // Fill in module level information from the `Source`
         match source.symbols() {
@@ -857,6 +1233,11 @@ impl<'a> ModuleLoader<'a> {
             Err(_e) => radeco_warn!(_e),
         }
 
+        match source.flags() {
+            Ok(flags) => rmod.flags = flags,
+            Err(_e) => radeco_warn!(_e),
+        }
+
         let mut flresult = floader.load(&rmod);
         flresult.functions = if self.filter.is_some() {
             let filter_fn = self.filter.as_ref().unwrap();
@@ -871,11 +1252,90 @@ impl<'a> ModuleLoader<'a> {
 
         rmod.functions = flresult.functions;
 
-        // Load instructions into functions
-        for rfn in rmod.functions.values_mut() {
+        // Load instructions into functions. A source made from a partial dump may not have the
+        // instructions of every function, these are left empty.
+        let available = source
+            .available_functions()
+            .map(|offsets| offsets.into_iter().collect::<HashSet<_>>());
+        let mut wanted = Vec::new();
+        for rfn in rmod.functions.values() {
             if let FunctionKind::Local = rfn.kind {
-                rfn.instructions = source.disassemble_function(&rfn.name).unwrap_or(Vec::new());
+                if available.as_ref().map_or(false, |a| !a.contains(&rfn.offset)) {
+                    radeco_warn!("No instructions for {} at {:#X}", rfn.name, rfn.offset);
+                    continue;
+                }
+                if self.max_function_bytes.map_or(false, |max| rfn.size > max) {
+                    radeco_warn!(
+                        "Skipping {} at {:#X}: {} bytes",
+                        rfn.name,
+                        rfn.offset,
+                        rfn.size
+                    );
+                    continue;
+                }
+                // The instructions are counted again once disassembled, for the sources which
+                // don't know the count beforehand.
+                if let Some(max) = self.max_function_insts {
+                    match source.instruction_count(rfn.offset) {
+                        Some(count) if count > max => {
+                            radeco_warn!(
+                                "Skipping {} at {:#X}: {} instructions",
+                                rfn.name,
+                                rfn.offset,
+                                count
+                            );
+                            continue;
+                        }
+                        _ => (),
+                    }
+                }
+                wanted.push((rfn.offset, rfn.name.to_string()));
+            }
+        }
+        let disassemble = |source: &dyn Source, &(offset, ref name): &(u64, String)| {
+            let load_start = Instant::now();
+            let instructions = source.disassemble_function(name).unwrap_or(Vec::new());
+            (offset, instructions, load_start.elapsed())
+        };
+        let pool = match self.max_workers {
+            Some(n) => match ThreadPoolBuilder::new().num_threads(n).build() {
+                Ok(pool) => Some(pool),
+                Err(_e) => {
+                    radeco_warn!("Unable to start {} workers: {}", n, _e);
+                    None
+                }
+            },
+            None => None,
+        };
+        // Only a source serving concurrent requests is worth disassembling from several threads.
+        let loaded: Vec<(u64, Vec<LOpInfo>, Duration)> = match source.as_concurrent() {
+            Some(csource) if self.parallel => in_pool(pool.as_ref(), || {
+                wanted.par_iter().map(|f| disassemble(csource, f)).collect()
+            }),
+            _ => wanted.iter().map(|f| disassemble(&**source, f)).collect(),
+        };
+
+        // Time spent loading the instructions of each function, which counts towards its budget.
+        let mut load_times = HashMap::new();
+        for (offset, instructions, elapsed) in loaded {
+            load_times.insert(offset, elapsed);
+            let rfn = match rmod.functions.get_mut(&offset) {
+                Some(rfn) => rfn,
+                None => continue,
+            };
+            if self
+                .max_function_insts
+                .map_or(false, |max| instructions.len() > max)
+            {
+                radeco_warn!(
+                    "Skipping {} at {:#X}: {} instructions",
+                    rfn.name,
+                    rfn.offset,
+                    instructions.len()
+                );
+                continue;
             }
+            rfn.instructions = instructions;
         }
 
         // Load calling conventions for all functions and imports
@@ -890,45 +1350,110 @@ impl<'a> ModuleLoader<'a> {
             }
         }
 
+        let reg_p = source.register_profile()?;
+        if let Some(pc) = reg_p.alias_info.iter().find(|a| a.role_str == "PC") {
+            llanalyzer::find_tail_calls(&mut rmod, &pc.reg);
+        }
+
+        // Load optional information. These need support from `Source` for analysis
+        let aux_info = if self.build_callgraph
+            || self.load_datarefs
+            || self.load_locals
+            || self.only_reachable
+        {
+            match source.functions_paged() {
+                Ok(info) => info,
+                Err(_e) => {
+                    radeco_warn!(_e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        if self.only_reachable {
+            // The callsites can't be analyzed yet, but the edges are enough to find the
+            // reachable functions. The callgraph is built again once the SSA is available.
+            rmod.callgraph = llanalyzer::load_call_graph(aux_info.as_slice(), &rmod);
+            for info in &aux_info {
+                if let Some(rfn) = info.offset.and_then(|o| rmod.functions.get_mut(&o)) {
+                    rfn.datarefs = info.datarefs.clone().unwrap_or_default();
+                }
+            }
+            let roots = rmod.entry_roots();
+            if roots.is_empty() {
+                radeco_warn!("No entrypoint or export to find the reachable functions from");
+            } else {
+                let reachable = rmod.reachable_functions(&roots, self.include_indirect);
+                rmod.functions.retain(|offset, _| reachable.contains(offset));
+            }
+            rmod.callgraph = CallGraph::new();
+        }
+
         // Optionally construct the SSA.
-        let reg_p = source
-            .register_profile()
-            .expect("Unable to load register profile");
-        let sub_reg_f = SubRegisterFile::new(&reg_p);
+        let profiles = self.register_profiles.as_slice();
         if self.build_ssa {
-            if self.parallel {
-                let ascc = self.assume_cc;
-                rmod.functions.par_iter_mut().for_each(|(_, rfn)| {
-                    SSAConstruct::<SSAStorage>::construct(rfn, &reg_p, ascc, true);
+            let fn_timeout = self.fn_timeout;
+            let replace_pc = !self.keep_pc;
+            let construct = |rfn: &mut RadecoFunction, assume_cc: bool| {
+                let deadline = fn_timeout.map(|budget| {
+                    let loaded = load_times.get(&rfn.offset).cloned().unwrap_or_default();
+                    Instant::now() + budget.checked_sub(loaded).unwrap_or_default()
                 });
+                match SSAConstruct::<SSAStorage>::construct_until(
+                    rfn, &reg_p, profiles, assume_cc, replace_pc, deadline,
+                ) {
+                    Ok(true) => {
+                        radeco_warn!("Timed out constructing {} at {:#X}", rfn.name, rfn.offset);
+                        rfn.set_timed_out(true);
+                        None
+                    }
+                    Ok(false) => None,
+                    Err(e) => {
+                        radeco_warn!("{} in {}", e, rfn.name);
+                        Some(e)
+                    }
+                }
+            };
+            let errors: Vec<ConstructError> = if self.parallel {
+                let ascc = self.assume_cc;
+                let functions = &mut rmod.functions;
+                in_pool(pool.as_ref(), || {
+                    functions
+                        .par_iter_mut()
+                        .filter_map(|(_, rfn)| construct(rfn, ascc))
+                        .collect()
+                })
             } else {
-                for rfn in rmod.functions.values_mut() {
-                    SSAConstruct::<SSAStorage>::construct(rfn, &reg_p, self.assume_cc, true);
+                rmod.functions
+                    .values_mut()
+                    .filter_map(|rfn| construct(rfn, self.assume_cc))
+                    .collect()
+            };
+            if strict {
+                if let Some(e) = errors.into_iter().min_by_key(|e| e.address) {
+                    return Err(e.into());
                 }
             }
         }
 
         if self.stub_imports {
             for ifn in rmod.imports.values_mut() {
-                SSAConstruct::<SSAStorage>::construct(
+                let constructed = SSAConstruct::<SSAStorage>::construct(
                     &mut ifn.rfn.borrow_mut(),
                     &reg_p,
+                    profiles,
                     self.assume_cc,
-                    true,
+                    !self.keep_pc,
                 );
+                if let Err(_e) = constructed {
+                    radeco_warn!(_e);
+                }
             }
         }
 
-        // Load optional information. These need support from `Source` for analysis
         if self.build_callgraph || self.load_datarefs || self.load_locals {
-            let aux_info = match source.functions() {
-                Ok(info) => info,
-                Err(_e) => {
-                    radeco_warn!(_e);
-                    Vec::new()
-                }
-            };
-
             if self.build_callgraph {
                 rmod.callgraph = llanalyzer::load_call_graph(aux_info.as_slice(), &rmod);
                 // Iterate through nodes and associate nodes with the correct functions
@@ -957,20 +1482,15 @@ impl<'a> ModuleLoader<'a> {
             if self.load_locals {
                 for info in &aux_info {
                     if let Some(rfn) = rmod.functions.get_mut(&info.offset.unwrap()) {
-                        let locals_res = self.source.as_ref().map(|s| s.locals_of(rfn.offset));
-                        let mut locals = match locals_res {
-                            Some(Ok(_locals)) => _locals
+                        let mut locals = match source.locals_of(rfn.offset) {
+                            Ok(_locals) => _locals
                                 .into_iter()
                                 .map(|l| VarBinding::local(l))
                                 .collect::<Vec<_>>(),
-                            Some(Err(_e)) => {
+                            Err(_e) => {
                                 radeco_warn!("{:?}", _e);
                                 Vec::new()
                             }
-                            None => {
-                                radeco_warn!("Source is not found");
-                                Vec::new()
-                            }
                         };
                         rfn.bindings_mut().append(&mut locals);
                     }
@@ -979,25 +1499,34 @@ impl<'a> ModuleLoader<'a> {
         }
 
         if self.build_callgraph && self.assume_cc {
+            // Bindings refer to the registers of the profile the SSA was constructed with.
             for rfn in rmod.functions.values_mut() {
+                let sub_reg_f = Arc::clone(&rfn.ssa().regfile);
                 ModuleLoader::init_fn_bindings(rfn, &sub_reg_f);
+                // Only keep the arguments that are read, so that the callsites are mapped to them.
+                rfn.mark_args();
             }
             // Do the same for imports.
             for ifn in rmod.imports.values_mut() {
-                ModuleLoader::init_fn_bindings(&mut ifn.rfn.borrow_mut(), &sub_reg_f);
+                let ifn = &mut *ifn.rfn.borrow_mut();
+                let sub_reg_f = Arc::clone(&ifn.ssa().regfile);
+                ModuleLoader::init_fn_bindings(ifn, &sub_reg_f);
             }
 
             llanalyzer::init_call_ctx(&mut rmod);
+            llanalyzer::infer_signatures(&mut rmod);
         }
 
         for rfn in rmod.functions.values_mut() {
             rfn.mark_locals();
-            rfn.mark_args();
+        }
+        if self.annotate_flags {
+            rmod.annotate_flags();
         }
         // Set source
         rmod.source = Some(Rc::clone(&source));
 
-        rmod
+        Ok(rmod)
     }
 
     /// Setup a function loader for the module
@@ -1019,6 +1548,7 @@ impl<'a> ModuleLoader<'a> {
 pub struct FunctionLoader<'a> {
     source: Option<Rc<dyn Source>>,
     strategies: Vec<&'a dyn PredicatedLoader>,
+    plugins: Vec<&'a dyn FunctionLoaderPlugin>,
 }
 
 pub trait PredicatedLoader {
@@ -1051,6 +1581,37 @@ where
     }
 }
 
+/// Adjusts the functions identified by the strategies of a `FunctionLoader`, beyond what the
+/// `Source` reports, e.g. to add the functions starting with a prologue it doesn't recognize, or
+/// to merge tail-called fragments into their function.
+///
+/// Plugins run once all the strategies have run, in the order they were registered with
+/// `FunctionLoader::plugin`. Each plugin is given the functions as left by the previous one, so
+/// the last plugin to touch a function has the final say on it.
+pub trait FunctionLoaderPlugin {
+    /// Adds, removes or changes the identified `functions`, keyed by their offset.
+    fn adjust(
+        &self,
+        source: Option<&Rc<dyn Source>>,
+        functions: &mut BTreeMap<u64, RadecoFunction>,
+        rmod: &RadecoModule,
+    );
+}
+
+impl<T> FunctionLoaderPlugin for T
+where
+    T: Fn(Option<&Rc<dyn Source>>, &mut BTreeMap<u64, RadecoFunction>, &RadecoModule),
+{
+    fn adjust(
+        &self,
+        source: Option<&Rc<dyn Source>>,
+        functions: &mut BTreeMap<u64, RadecoFunction>,
+        rmod: &RadecoModule,
+    ) {
+        self(source, functions, rmod)
+    }
+}
+
 #[derive(Default, Clone)]
 /// Results from `FunctionLoader`
 pub struct FLResult {
@@ -1067,9 +1628,16 @@ impl<'a> FunctionLoader<'a> {
         self
     }
 
+    /// Add a plugin adjusting the identified functions, see `FunctionLoaderPlugin`
+    pub fn plugin<'b: 'a>(mut self, plugin: &'b dyn FunctionLoaderPlugin) -> FunctionLoader<'a> {
+        self.plugins.push(plugin);
+        self
+    }
+
     /// Kick everything off and breakdown a radeco module into functions
     pub fn load(&mut self, rmod: &RadecoModule) -> FLResult {
-        self.strategies
+        let mut result = self
+            .strategies
             .iter()
             .fold(FLResult::default(), |mut acc, f| {
                 if f.predicate(&acc) {
@@ -1078,7 +1646,16 @@ impl<'a> FunctionLoader<'a> {
                     acc.functions.extend(fl.functions.into_iter());
                 }
                 acc
-            })
+            });
+        for plugin in &self.plugins {
+            let before = result.functions.len();
+            plugin.adjust(self.source.as_ref(), &mut result.functions, rmod);
+            let after = result.functions.len();
+            if after > before {
+                result.new += (after - before) as u32;
+            }
+        }
+        result
     }
 
     /// Include default strategies to identify functions in the loaded binary
@@ -1094,6 +1671,7 @@ impl RadecoProject {
         RadecoProject {
             modules: Vec::new(),
             reginfo: Arc::new(SubRegisterFile::default()),
+            callgraph: ProjectCallGraph::new(),
         }
     }
 
@@ -1101,6 +1679,11 @@ impl RadecoProject {
         &self.reginfo
     }
 
+    /// Returns the call graph spanning all the modules of the project.
+    pub fn callgraph(&self) -> &ProjectCallGraph {
+        &self.callgraph
+    }
+
     pub fn nth_module(&self, idx: usize) -> Option<&RadecoModule> {
         if self.modules.len() > idx {
             Some(&self.modules[idx])
@@ -1129,6 +1712,130 @@ impl RadecoProject {
             iter: self.modules.iter_mut(),
         }
     }
+
+    /// Saves the modules of the project to `path`, with the SSA, the bindings and the call graph
+    /// of their functions, so that the project can be reopened with `RadecoProject::load` without
+    /// loading and analyzing the binary again.
+    ///
+    /// The results of the analyses of the functions (e.g. their register usage and value ranges)
+    /// are saved along with their SSA. The sources of the modules are not saved.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ProjectFileError> {
+        // Every function usually holds its own copy of the same register profile, so the profiles
+        // are compared by value and only the distinct ones are written.
+        let mut regfiles: Vec<serde_json::Value> = Vec::new();
+        let mut ssa_regfiles = Vec::new();
+        for rmod in &self.modules {
+            let mut indices = Vec::new();
+            for rfn in rmod.functions.values() {
+                let regfile = serde_json::to_value(&*rfn.ssa().regfile)?;
+                let idx = match regfiles.iter().position(|r| *r == regfile) {
+                    Some(idx) => idx,
+                    None => {
+                        regfiles.push(regfile);
+                        regfiles.len() - 1
+                    }
+                };
+                indices.push(idx);
+            }
+            ssa_regfiles.push(indices);
+        }
+
+        let file = ProjectFile {
+            version: PROJECT_FORMAT_VERSION,
+            regfiles,
+            ssa_regfiles,
+            project: self,
+        };
+        serde_json::to_writer(BufWriter::new(File::create(path)?), &file)?;
+        Ok(())
+    }
+
+    /// Loads a project saved by `RadecoProject::save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<RadecoProject, ProjectFileError> {
+        let data = fs::read_to_string(path)?;
+        // Check the version alone first, the rest of the file is not expected to match the types
+        // of another version.
+        let header: ProjectFileVersion = serde_json::from_str(&data)?;
+        if header.version != PROJECT_FORMAT_VERSION {
+            return Err(ProjectFileError::Version(header.version));
+        }
+
+        let file: ProjectFile<SubRegisterFile, RadecoProject> = serde_json::from_str(&data)?;
+        for regfile in &file.regfiles {
+            regfile
+                .validate()
+                .map_err(|e| ProjectFileError::Format(de::Error::custom(e)))?;
+        }
+        let regfiles = file.regfiles.into_iter().map(Arc::new).collect::<Vec<_>>();
+        let mut project = file.project;
+        for (rmod, indices) in project.modules.iter_mut().zip(file.ssa_regfiles) {
+            for (rfn, idx) in rmod.functions.values_mut().zip(indices) {
+                let regfile = regfiles.get(idx).ok_or_else(|| {
+                    ProjectFileError::Format(de::Error::custom("invalid register profile index"))
+                })?;
+                rfn.ssa_mut().regfile = Arc::clone(regfile);
+            }
+        }
+        Ok(project)
+    }
+}
+
+/// Version of the format written by `RadecoProject::save`. It has to be bumped whenever one of the
+/// saved types changes.
+pub const PROJECT_FORMAT_VERSION: u32 = 3;
+
+/// Errors of `RadecoProject::save` and `RadecoProject::load`.
+#[derive(Debug)]
+pub enum ProjectFileError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// The project was saved with another version of the format.
+    Version(u32),
+}
+
+impl fmt::Display for ProjectFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ProjectFileError::Io(ref e) => write!(f, "{}", e),
+            &ProjectFileError::Format(ref e) => write!(f, "Invalid project file: {}", e),
+            &ProjectFileError::Version(version) => write!(
+                f,
+                "Project file has format version {}, expected version {}",
+                version, PROJECT_FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl error::Error for ProjectFileError {}
+
+impl From<io::Error> for ProjectFileError {
+    fn from(e: io::Error) -> ProjectFileError {
+        ProjectFileError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ProjectFileError {
+    fn from(e: serde_json::Error) -> ProjectFileError {
+        ProjectFileError::Format(e)
+    }
+}
+
+/// On-disk layout of a `RadecoProject`. The register files of the SSA aren't part of the project,
+/// they are stored once in `regfiles` instead.
+#[derive(Serialize, Deserialize)]
+struct ProjectFile<R, P> {
+    version: u32,
+    regfiles: Vec<R>,
+    /// For every module, the index in `regfiles` of the register file of each function, in the
+    /// order of their offsets
+    ssa_regfiles: Vec<Vec<usize>>,
+    project: P,
+}
+
+#[derive(Deserialize)]
+struct ProjectFileVersion {
+    version: u32,
 }
 
 impl RadecoModule {
@@ -1158,11 +1865,114 @@ impl RadecoModule {
             rfn.name = From::from(String::from(new_name));
         }
         if let Some(ref mut src) = self.source {
-            let _ = src.raw(format!("afn {} {:x}", new_name, offset));
+            // The source re-analyzes the function, so its instructions may have changed.
+            if src.raw(format!("afn {} {:x}", new_name, offset)).is_ok() {
+                self.mark_dirty(offset);
+            }
         }
         old_name
     }
 
+    /// Marks the function at `offset` as stale, e.g. after the source re-analyzed it. Its SSA is
+    /// discarded and reconstructed by the next call to `rebuild_dirty`.
+    pub fn mark_dirty(&mut self, offset: u64) {
+        if let Some(rfn) = self.functions.get_mut(&offset) {
+            rfn.invalidate_ssa();
+            self.dirty.insert(offset);
+        }
+    }
+
+    /// Overrides the calling convention of the function at `offset`, see
+    /// `RadecoFunction::set_calling_convention`. The callers of the function are marked dirty, so
+    /// that their calls are modeled again after `rebuild_dirty`.
+    pub fn set_calling_convention(
+        &mut self,
+        offset: u64,
+        callconv: LCCInfo,
+        clobbers: Option<Vec<String>>,
+    ) -> bool {
+        match self.functions.get_mut(&offset) {
+            Some(rfn) => {
+                rfn.set_calling_convention(callconv);
+                if let Some(clobbers) = clobbers {
+                    rfn.set_clobbers(clobbers);
+                }
+            }
+            None => return false,
+        }
+        let callers = self
+            .callgraph
+            .node_indices()
+            .filter(|&n| self.callgraph[n] == offset)
+            .flat_map(|n| self.callgraph.callers(n))
+            .map(|(_, caller)| self.callgraph[caller])
+            .collect::<Vec<_>>();
+        for caller in callers {
+            self.mark_dirty(caller);
+        }
+        true
+    }
+
+    /// Returns `true` if the SSA of the function at `offset` has to be reconstructed.
+    pub fn is_dirty(&self, offset: u64) -> bool {
+        self.dirty.contains(&offset)
+    }
+
+    /// Reloads the instructions of every function marked dirty and reconstructs its SSA. The
+    /// bindings of the functions and the call contexts of the module are updated to refer to the
+    /// new nodes.
+    pub fn rebuild_dirty(&mut self) {
+        if self.dirty.is_empty() {
+            return;
+        }
+        let source = match self.source {
+            Some(ref source) => Rc::clone(source),
+            None => {
+                radeco_warn!("Unable to rebuild SSA of {}: no source", self.name);
+                return;
+            }
+        };
+        let reg_p = match source.register_profile() {
+            Ok(reg_p) => reg_p,
+            Err(_e) => {
+                radeco_warn!(_e);
+                return;
+            }
+        };
+
+        for offset in self.dirty.drain() {
+            let rfn = match self.functions.get_mut(&offset) {
+                Some(rfn) => rfn,
+                None => continue,
+            };
+            if let FunctionKind::Local = rfn.kind {
+                match source
+                    .disassemble_function(&rfn.name)
+                    .or_else(|_| source.instructions_at(offset))
+                {
+                    Ok(instructions) => rfn.instructions = instructions,
+                    Err(_e) => radeco_warn!("{:?}", _e),
+                }
+            }
+            let constructed = SSAConstruct::<SSAStorage>::construct(
+                rfn,
+                &reg_p,
+                &self.register_profiles,
+                self.assume_cc,
+                !self.keep_pc,
+            );
+            if let Err(_e) = constructed {
+                radeco_warn!(_e);
+            }
+            rfn.set_timed_out(false);
+            rfn.rebind_registers();
+            rfn.mark_locals();
+            rfn.mark_args();
+        }
+
+        llanalyzer::init_call_ctx(self);
+    }
+
     pub fn iter<'a>(&'a self) -> FunctionIter<'a> {
         FunctionIter {
             module: &self,
@@ -1176,14 +1986,69 @@ impl RadecoModule {
         }
     }
 
-    pub fn sections(&self) -> &Arc<Vec<LSectionInfo>> {
-        &self.sections
-    }
-
+    /// Same as `iter`, but in reverse topological order of the callgraph: the callees of a
+    /// function are yielded before it, so that interprocedural analyses can use what was found
+    /// about them. The functions of a cycle of calls are yielded by address, and the functions
+    /// missing from the callgraph come last.
+    pub fn iter_topo<'a>(&'a self) -> TopoFunctionIter<'a> {
+        let mut order = Vec::with_capacity(self.functions.len());
+        let mut seen = HashSet::new();
+        // `tarjan_scc` returns the SCCs in reverse topological order.
+        for scc in tarjan_scc(&self.callgraph) {
+            let mut offsets = scc
+                .into_iter()
+                .map(|n| self.callgraph[n])
+                .filter(|o| self.functions.contains_key(o))
+                .collect::<Vec<_>>();
+            offsets.sort();
+            order.extend(offsets.into_iter().filter(|&o| seen.insert(o)));
+        }
+        order.extend(self.functions.keys().filter(|&&o| seen.insert(o)));
+        TopoFunctionIter {
+            module: self,
+            order: order.into_iter(),
+        }
+    }
+
+    pub fn sections(&self) -> &Arc<Vec<LSectionInfo>> {
+        &self.sections
+    }
+
+    /// Returns the section containing `address`, see [`section_containing`].
+    pub fn section_of(&self, address: u64) -> Option<&LSectionInfo> {
+        section_containing(&self.sections, address)
+    }
+
     pub fn strings(&self) -> &Vec<LStringInfo> {
         &self.strings
     }
 
+    pub fn flags(&self) -> &Vec<LFlagInfo> {
+        &self.flags
+    }
+
+    /// Returns the global variables of this module, by address, once recovered by `GlobalVars`.
+    pub fn globals(&self) -> &BTreeMap<u64, GlobalVar> {
+        &self.globals
+    }
+
+    pub fn set_globals(&mut self, globals: BTreeMap<u64, GlobalVar>) {
+        self.globals = globals;
+    }
+
+    /// Annotates the SSA of every function with the names of the flags of this module, see
+    /// `RadecoFunction::annotate_flags`.
+    pub fn annotate_flags(&mut self) {
+        let names = flag_names(&self.flags);
+        for rfn in self.functions.values_mut() {
+            rfn.annotate_flags(&names);
+        }
+    }
+
+    pub fn exports(&self) -> &Vec<LExportInfo> {
+        &self.exports
+    }
+
     pub fn callees_of(&self, rfn: &RadecoFunction) -> Vec<(u64, NodeIndex)> {
         // TODO More efficient implementation
         let csite_nodes = rfn
@@ -1194,6 +2059,206 @@ impl RadecoModule {
             .flat_map(|cn| self.callgraph.callees(cn).collect::<Vec<_>>())
             .collect::<Vec<_>>()
     }
+
+    /// Returns the callsites calling the function at `offset`, along with the name of the
+    /// function each call is made from. Sorted by callsite.
+    pub fn callers_by_name(&self, offset: u64) -> Vec<(u64, String)> {
+        self.calls_by_name(offset, Direction::Incoming)
+    }
+
+    /// Returns the callsites of the function at `offset`, along with the name of the function or
+    /// import each of them calls. Sorted by callsite.
+    pub fn callees_by_name(&self, offset: u64) -> Vec<(u64, String)> {
+        self.calls_by_name(offset, Direction::Outgoing)
+    }
+
+    fn calls_by_name(&self, offset: u64, dir: Direction) -> Vec<(u64, String)> {
+        let idx = match self
+            .callgraph
+            .node_indices()
+            .find(|&n| self.callgraph[n] == offset)
+        {
+            Some(idx) => idx,
+            None => return Vec::new(),
+        };
+        let mut calls = self
+            .callgraph
+            .edges_directed(idx, dir)
+            .map(|er| {
+                let other = match dir {
+                    Direction::Incoming => er.source(),
+                    Direction::Outgoing => er.target(),
+                };
+                (er.weight().csite, self.name_at(self.callgraph[other]))
+            })
+            .collect::<Vec<_>>();
+        calls.sort();
+        calls
+    }
+
+    /// Name of the function or import at `offset`, falling back to the address itself.
+    pub fn name_at(&self, offset: u64) -> String {
+        if let Some(rfn) = self.functions.get(&offset) {
+            rfn.name.to_string()
+        } else if let Some(import) = self.imports.get(&offset) {
+            import.name.to_string()
+        } else {
+            format!("{:#x}", offset)
+        }
+    }
+
+    /// Returns the references to `address` made by the functions of this module, sorted by the
+    /// address they are made from, see `RadecoFunction::xrefs_to`. When `address` is a function,
+    /// the calls to it recorded in the callgraph are listed as well.
+    pub fn xrefs_to(&self, address: u64) -> Vec<Xref> {
+        let mut xrefs = self
+            .functions
+            .values()
+            .flat_map(|rfn| {
+                rfn.xrefs_to(address)
+                    .into_iter()
+                    .map(move |(from, kind)| Xref {
+                        from,
+                        kind,
+                        function: rfn.name.to_string(),
+                    })
+            })
+            .collect::<Vec<_>>();
+        let calls = xrefs
+            .iter()
+            .filter(|x| x.kind == XrefKind::Code)
+            .map(|x| x.from)
+            .collect::<HashSet<_>>();
+        for (csite, function) in self.callers_by_name(address) {
+            if !calls.contains(&csite) {
+                xrefs.push(Xref {
+                    from: csite,
+                    kind: XrefKind::Code,
+                    function,
+                });
+            }
+        }
+        xrefs.sort();
+        xrefs
+    }
+
+    /// Addresses the execution of the module can start from, i.e. its entrypoints and the
+    /// functions it exports.
+    pub fn entry_roots(&self) -> Vec<u64> {
+        let mut roots = self
+            .entrypoint
+            .iter()
+            .filter_map(|e| e.vaddr)
+            .chain(self.exports.iter().filter_map(|e| e.vaddr))
+            .collect::<Vec<_>>();
+        roots.sort();
+        roots.dedup();
+        roots
+    }
+
+    /// Returns the offsets of the functions reachable from `roots` through the callgraph.
+    ///
+    /// The callgraph only has edges for direct calls. If `indirect` is set, a function whose
+    /// address is referenced by a reachable function (see `RadecoFunction::datarefs`) is assumed
+    /// to be called indirectly, and is reachable as well.
+    pub fn reachable_functions(&self, roots: &[u64], indirect: bool) -> HashSet<u64> {
+        let cg_nodes: HashMap<u64, NodeIndex> = self
+            .callgraph
+            .node_indices()
+            .map(|n| (self.callgraph[n], n))
+            .collect();
+
+        let mut reachable = HashSet::new();
+        let mut worklist = roots.to_vec();
+        while let Some(offset) = worklist.pop() {
+            if !reachable.insert(offset) {
+                continue;
+            }
+            if let Some(&n) = cg_nodes.get(&offset) {
+                worklist.extend(self.callgraph.neighbors(n).map(|c| self.callgraph[c]));
+            }
+            if indirect {
+                if let Some(rfn) = self.functions.get(&offset) {
+                    worklist.extend(
+                        rfn.datarefs
+                            .iter()
+                            .filter(|a| self.functions.contains_key(*a)),
+                    );
+                }
+            }
+        }
+        reachable
+    }
+}
+
+/// Returns the section of `sections` whose virtual address range contains `address`. If several
+/// sections overlap (e.g. a section and the segment it belongs to), the smallest one is picked.
+pub fn section_containing(sections: &[LSectionInfo], address: u64) -> Option<&LSectionInfo> {
+    sections
+        .iter()
+        .filter_map(|s| {
+            let base = s.vaddr?;
+            let size = s.vsize.or(s.size)?;
+            if base <= address && address - base < size {
+                Some((size, s))
+            } else {
+                None
+            }
+        })
+        .min_by_key(|&(size, _)| size)
+        .map(|(_, s)| s)
+}
+
+/// Returns the name of the flag to use for each offset of `flags`. When several flags are at the
+/// same offset, the one of the most specific namespace is picked: the flags of radare2 naming
+/// regions of the binary (e.g. `section.` or `segment.`) are the least specific, followed by the
+/// generated ones (e.g. `fcn.` or `loc.`), then by the deepest namespace (`sym.imp.puts` over
+/// `sym.puts`). Flags at 0 are ignored, as they are only used to name empty regions.
+pub fn flag_names(flags: &[LFlagInfo]) -> HashMap<u64, String> {
+    let mut names: HashMap<u64, &str> = HashMap::new();
+    for flag in flags.iter().filter(|f| f.offset != 0 && !f.name.is_empty()) {
+        let name = names.entry(flag.offset).or_insert(flag.name.as_str());
+        if flag_rank(&flag.name) > flag_rank(*name) {
+            *name = flag.name.as_str();
+        }
+    }
+    names
+        .into_iter()
+        .map(|(offset, name)| (offset, name.to_owned()))
+        .collect()
+}
+
+/// Parses the annotations of `RadecoFunction::apply_annotations` from a JSON object mapping the
+/// addresses, in hex (`0x` prefixed) or decimal, to the comments, e.g.
+/// `{"0x401000": "checks the key"}`.
+pub fn parse_annotations(json: &str) -> Result<HashMap<u64, String>, String> {
+    let raw: HashMap<String, String> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    raw.into_iter()
+        .map(|(addr, comment)| {
+            let parsed = if addr.starts_with("0x") {
+                u64::from_str_radix(&addr[2..], 16)
+            } else {
+                addr.parse::<u64>()
+            };
+            parsed
+                .map(|address| (address, comment))
+                .map_err(|_| format!("Invalid address {}", addr))
+        })
+        .collect()
+}
+
+/// Specificity of the name of a flag, see `flag_names`. Ties are broken by name.
+fn flag_rank(name: &str) -> (u8, usize, cmp::Reverse<&str>) {
+    const REGIONS: &[&str] = &["section.", "section_end.", "segment.", "map."];
+    const GENERATED: &[&str] = &["fcn.", "loc.", "entry", "case.", "switch."];
+    let namespace = if REGIONS.iter().any(|p| name.starts_with(p)) {
+        0
+    } else if GENERATED.iter().any(|p| name.starts_with(p)) {
+        1
+    } else {
+        2
+    };
+    (namespace, name.matches('.').count(), cmp::Reverse(name))
 }
 
 impl RadecoFunction {
@@ -1205,14 +2270,60 @@ impl RadecoFunction {
         self.instructions.as_slice()
     }
 
+    /// Returns the SSA of this function, walked through the traits of `middle::ssa::prelude`.
     pub fn ssa(&self) -> &SSAStorage {
         &self.ssa
     }
 
+    /// Returns the SSA of this function to modify it, e.g. from a pass written outside of this
+    /// crate. The rules keeping it consistent are listed in `middle::ssa::prelude`; the facts
+    /// indexed by node stored in this function (e.g. `alias_sets`) aren't updated along.
     pub fn ssa_mut(&mut self) -> &mut SSAStorage {
         &mut self.ssa
     }
 
+    /// Returns the control flow graph of this function in Graphviz dot, without the values
+    /// computed by its blocks, see `ssadot::emit_cfg_dot`.
+    pub fn cfg_dot(&self) -> String {
+        ssadot::emit_cfg_dot(&self.ssa)
+    }
+
+    /// Returns the basic blocks of this function ordered by address. The exit node, which has no
+    /// address, is not one of them: the blocks flowing to it have `exits` set instead.
+    pub fn basic_blocks(&self) -> Vec<BasicBlockInfo> {
+        let exit = self.ssa.exit_node();
+        let mut blocks = self
+            .ssa
+            .blocks()
+            .into_iter()
+            .filter_map(|block| {
+                let start = self.ssa.starting_address(block)?;
+                let mut succs = Vec::new();
+                let mut exits = false;
+                for (edge, kind) in self.ssa.outgoing_edges(block) {
+                    let target = match self.ssa.g.edge_endpoints(edge) {
+                        Some((_, target)) => target,
+                        None => continue,
+                    };
+                    if Some(target) == exit {
+                        exits = true;
+                    } else if let Some(addr) = self.ssa.starting_address(target) {
+                        succs.push((addr, kind));
+                    }
+                }
+                succs.sort();
+                Some(BasicBlockInfo {
+                    start,
+                    size: self.ssa.block_size(block).unwrap_or(0),
+                    succs,
+                    exits,
+                })
+            })
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|b| b.start);
+        blocks
+    }
+
     /// Returns the id in the call graph for this function.
     pub fn cgid(&self) -> NodeIndex {
         self.cgid
@@ -1226,6 +2337,533 @@ impl RadecoFunction {
         &mut self.bindings
     }
 
+    pub fn signature(&self) -> &[ValueInfo] {
+        &self.signature
+    }
+
+    pub fn signature_mut(&mut self) -> &mut Vec<ValueInfo> {
+        &mut self.signature
+    }
+
+    pub fn callee_usage(&self, csite: u64) -> Option<&RegisterUsage> {
+        self.callee_usage.get(&csite)
+    }
+
+    pub fn set_callee_usage(&mut self, csite: u64, usage: RegisterUsage) {
+        self.callee_usage.insert(csite, usage);
+    }
+
+    pub fn disasm(&self) -> Option<&HashMap<NodeIndex, String>> {
+        self.disasm.as_ref()
+    }
+
+    /// Returns the disassembly of the instruction `node` was lifted from.
+    pub fn disasm_of(&self, node: NodeIndex) -> Option<&str> {
+        self.disasm.as_ref()?.get(&node).map(|s| s.as_str())
+    }
+
+    pub fn set_disasm(&mut self, disasm: HashMap<NodeIndex, String>) {
+        self.disasm = Some(disasm);
+    }
+
+    /// Returns the ESIL tokens which could not be lifted, with the address of their instruction.
+    pub fn unsupported(&self) -> &[(u64, String)] {
+        &self.unsupported
+    }
+
+    pub fn set_unsupported(&mut self, unsupported: Vec<(u64, String)>) {
+        self.unsupported = unsupported;
+    }
+
+    /// Returns the constants written to a register, with the address of the write and the name
+    /// of the register.
+    pub fn const_writes(&self) -> &[(NodeIndex, MAddress, String)] {
+        &self.const_writes
+    }
+
+    pub fn set_const_writes(&mut self, const_writes: Vec<(NodeIndex, MAddress, String)>) {
+        self.const_writes = const_writes;
+    }
+
+    /// Returns the tail calls of this function, from the address of the jump to the address of
+    /// the function it jumps to.
+    pub fn tail_calls(&self) -> &BTreeMap<u64, u64> {
+        &self.tail_calls
+    }
+
+    pub fn set_tail_calls(&mut self, tail_calls: BTreeMap<u64, u64>) {
+        self.tail_calls = tail_calls;
+    }
+
+    /// Returns `true` if the SSA construction or the engine gave up on this function, see
+    /// `ModuleLoader::fn_timeout` and `RadecoEngine::fn_timeout`.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    pub fn set_timed_out(&mut self, timed_out: bool) {
+        self.timed_out = timed_out;
+    }
+
+    /// Overrides the calling convention of this function, e.g. for hand-written code which
+    /// doesn't follow the one of the platform. Unlike the ones loaded from the `Source`, it is
+    /// trusted by `Inferer` to model the calls to this function.
+    pub fn set_calling_convention(&mut self, callconv: LCCInfo) {
+        self.callconv = Some(callconv);
+        self.custom_callconv = true;
+    }
+
+    /// Returns `true` if the calling convention of this function was supplied by the user.
+    pub fn has_custom_callconv(&self) -> bool {
+        self.custom_callconv
+    }
+
+    /// Sets the registers clobbered by this function. Along with a calling convention supplied
+    /// by the user, every other register apart from the return one is assumed to be preserved.
+    pub fn set_clobbers(&mut self, clobbers: Vec<String>) {
+        self.clobbers = Some(clobbers);
+    }
+
+    pub fn clobbers(&self) -> Option<&[String]> {
+        self.clobbers.as_ref().map(|c| c.as_slice())
+    }
+
+    pub fn value_ranges(&self) -> Option<&ValueRanges> {
+        self.value_ranges.as_ref()
+    }
+
+    pub fn set_value_ranges(&mut self, ranges: ValueRanges) {
+        self.value_ranges = Some(ranges);
+    }
+
+    pub fn alias_sets(&self) -> Option<&AliasSets> {
+        self.alias_sets.as_ref()
+    }
+
+    pub fn set_alias_sets(&mut self, sets: AliasSets) {
+        self.alias_sets = Some(sets);
+    }
+
+    /// Returns the `OpLoad`s which may read an uninitialized local, once computed by the engine.
+    pub fn uninit_reads(&self) -> &[NodeIndex] {
+        &self.uninit_reads
+    }
+
+    pub fn set_uninit_reads(&mut self, loads: Vec<NodeIndex>) {
+        self.uninit_reads = loads;
+    }
+
+    /// Returns the starting addresses of the blocks of the function, but the entry block, sorted.
+    fn block_starts(&self) -> Vec<(u64, NodeIndex)> {
+        let entry = self.ssa.entry_node();
+        let mut starts = self
+            .ssa
+            .blocks()
+            .into_iter()
+            .filter(|&b| Some(b) != entry)
+            .filter_map(|b| self.ssa.starting_address(b).map(|start| (start.address, b)))
+            .collect::<Vec<_>>();
+        starts.sort_by_key(|&(start, b)| (start, b.index()));
+        starts
+    }
+
+    /// Like `PhiPlacer::block_of`, the block containing an address is the last one starting at
+    /// or before it.
+    fn block_at(starts: &[(u64, NodeIndex)], addr: u64) -> Option<NodeIndex> {
+        starts
+            .iter()
+            .rev()
+            .find(|&&(start, _)| start <= addr)
+            .map(|&(_, b)| b)
+    }
+
+    /// Summarizes the effects of the basic block `block`: the registers it defines, the
+    /// registers it reads as they are on entry to the block, and whether it writes memory.
+    ///
+    /// As in `reg_value_at`, registers are found through the register annotations of the SSA,
+    /// only considering the nodes as wide as the annotated register, and constants written to
+    /// registers through `const_writes`. The uses are the registers held by the operands of the
+    /// block defined outside of it, e.g. by its phis or by the entry of the function. The `mem`
+    /// pseudo-register is left out of both: the block writes memory iff it contains an
+    /// `OpStore`.
+    pub fn block_effects(&self, block: NodeIndex) -> BlockEffect {
+        let ssa = &self.ssa;
+        let annotated = |node: NodeIndex| {
+            let node_width = ssa
+                .node_data(node)
+                .ok()
+                .and_then(|nd| nd.vt.width().get_width());
+            ssa.registers(node)
+                .into_iter()
+                .filter(|r| r != "mem")
+                .filter(move |r| {
+                    let width = ssa
+                        .regfile
+                        .register_id_by_name(r)
+                        .and_then(|id| ssa.regfile.get_width(id));
+                    width.map_or(true, |w| node_width == Some(w as u16))
+                })
+        };
+
+        let exprs = ssa.exprs_in(block);
+        let inside = exprs.iter().cloned().collect::<HashSet<_>>();
+        let mut effect = BlockEffect::default();
+        for &expr in &exprs {
+            effect.defines.extend(annotated(expr));
+            if ssa.opcode(expr) == Some(MOpcode::OpStore) {
+                effect.writes_memory = true;
+            }
+            for op in ssa.operands_of(expr) {
+                if !inside.contains(&op) && ssa.constant(op).is_none() {
+                    effect.uses.extend(annotated(op));
+                }
+            }
+        }
+
+        let starts = self.block_starts();
+        effect.defines.extend(
+            self.const_writes
+                .iter()
+                .filter(|&&(_, a, _)| RadecoFunction::block_at(&starts, a.address) == Some(block))
+                .map(|&(_, _, ref r)| r.clone()),
+        );
+        effect
+    }
+
+    /// Returns the node holding the value of the register `reg` (a whole register, e.g. `rax`)
+    /// when the instruction at `addr` is reached, before it is executed.
+    ///
+    /// This is the last definition of `reg` before `addr` in the block containing `addr`, else
+    /// the phi merging `reg` at the start of that block, else the definition reaching the block
+    /// through its dominators, down to the value `reg` had on entry to the function. Definitions
+    /// are found through the register annotations of the SSA (`SSA::registers`); since reads
+    /// of a sub-register are annotated too, only nodes as wide as `reg` are considered.
+    /// Constants, which don't belong to any block, are located through `const_writes`.
+    pub fn reg_value_at(&self, reg: &str, addr: u64) -> Option<NodeIndex> {
+        let ssa = &self.ssa;
+        let width = ssa
+            .regfile
+            .register_id_by_name(reg)
+            .and_then(|id| ssa.regfile.get_width(id));
+        let defines = |node: NodeIndex| {
+            let node_width = ssa
+                .node_data(node)
+                .ok()
+                .and_then(|nd| nd.vt.width().get_width());
+            ssa.registers(node).iter().any(|r| r == reg)
+                && width.map_or(true, |w| node_width == Some(w as u16))
+        };
+
+        let entry = ssa.entry_node()?;
+        let starts = self.block_starts();
+        let block_at = |addr: u64| RadecoFunction::block_at(&starts, addr);
+
+        let mut block = block_at(addr)?;
+        let dominators = LoopForest::new(ssa);
+        let mut before = Some(addr);
+        while block != entry {
+            let exprs = ssa
+                .exprs_in(block)
+                .into_iter()
+                .filter(|&n| defines(n))
+                .filter_map(|n| ssa.address(n).map(|a| (a, n)));
+            let consts = self
+                .const_writes
+                .iter()
+                .filter(|&&(_, a, ref r)| r == reg && block_at(a.address) == Some(block))
+                .map(|&(n, a, _)| (a, n));
+            let def = exprs
+                .chain(consts)
+                .filter(|&(a, _)| before.map_or(true, |addr| a.address < addr))
+                .max_by_key(|&(a, n)| (a, n.index()))
+                .map(|(_, n)| n)
+                .or_else(|| ssa.phis_in(block).into_iter().find(|&n| defines(n)));
+            if def.is_some() {
+                return def;
+            }
+            // Everything in the dominators precedes `addr`.
+            block = dominators.idom(block)?;
+            before = None;
+        }
+
+        let regs = ssa.registers_in(entry)?;
+        ssa.operands_of(regs)
+            .into_iter()
+            .find(|&n| ssa.name_of(n).as_ref().map(|s| s.as_str()) == Some(reg))
+    }
+
+    /// Returns whether the value `from` may flow into the value `to`, i.e. whether `to` is
+    /// reachable from `from` through the def-use chains of the SSA, phis included.
+    ///
+    /// Memory is approximated conservatively through the memory chain: a value stored to memory
+    /// flows into every later memory state, and thus into every value loaded from it, whatever
+    /// the address. Similarly, the arguments of a call flow into all the values it returns.
+    pub fn flows_to(&self, from: NodeIndex, to: NodeIndex) -> bool {
+        let ssa = &self.ssa;
+        let mut seen = HashSet::new();
+        let mut worklist = vec![from];
+        while let Some(node) = worklist.pop() {
+            if node == to {
+                return true;
+            }
+            if !seen.insert(node) {
+                continue;
+            }
+            worklist.extend(
+                ssa.uses_of(node)
+                    .into_iter()
+                    .filter(|&u| ssa.is_expr(u) || ssa.is_phi(u) || ssa.is_comment(u)),
+            );
+        }
+        false
+    }
+
+    /// Returns the inputs of the function the value `to` may be derived from, as approximated by
+    /// `flows_to`. These are the values of the registers and of the memory on entry to the
+    /// function (and the undefined values) reaching `to`, sorted by node. Constants are not
+    /// reported.
+    pub fn taint_sources(&self, to: NodeIndex) -> Vec<NodeIndex> {
+        let ssa = &self.ssa;
+        let mut seen = HashSet::new();
+        let mut sources = Vec::new();
+        let mut worklist = vec![to];
+        while let Some(node) = worklist.pop() {
+            if !seen.insert(node) {
+                continue;
+            }
+            let operands = ssa.operands_of(node);
+            if operands.is_empty() {
+                if !ssa.is_constant(node) {
+                    sources.push(node);
+                }
+                continue;
+            }
+            worklist.extend(operands);
+        }
+        sources.sort();
+        sources
+    }
+
+    /// Returns the placeholder blocks standing for the unknown targets of the indirect jumps of
+    /// this function (see `phiplacement::is_unexplored`), along with the selector of each jump,
+    /// i.e. the value jumped to. Sorted by address of the placeholder.
+    pub fn unresolved_targets(&self) -> Vec<(MAddress, NodeIndex)> {
+        let ssa = &self.ssa;
+        let mut unresolved = ssa
+            .blocks()
+            .into_iter()
+            .filter_map(|b| {
+                let selector = ssa.selector_in(b)?;
+                let placeholder = self.placeholder_of(b)?.1;
+                Some((ssa.starting_address(placeholder)?, selector))
+            })
+            .collect::<Vec<_>>();
+        unresolved.sort();
+        unresolved
+    }
+
+    /// Replaces the placeholder of the indirect jump to `selector`, see `unresolved_targets`, by
+    /// an edge to the block starting at every address of `targets`. Returns `false`, and leaves
+    /// the jump unresolved, if there is no such jump or if a target doesn't start a block.
+    pub fn resolve_indirect(&mut self, selector: NodeIndex, targets: Vec<u64>) -> bool {
+        let block = match self
+            .ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| self.ssa.selector_in(b) == Some(selector))
+        {
+            Some(block) => block,
+            None => return false,
+        };
+        let (edge, placeholder) = match self.placeholder_of(block) {
+            Some(placeholder) => placeholder,
+            None => return false,
+        };
+        let starts = self
+            .ssa
+            .blocks()
+            .into_iter()
+            .filter_map(|b| {
+                let start = self.ssa.starting_address(b)?;
+                if start.offset == 0 {
+                    Some((start.address, b))
+                } else {
+                    None
+                }
+            })
+            .collect::<HashMap<_, _>>();
+        let mut succs = Vec::new();
+        for target in &targets {
+            match starts.get(target) {
+                Some(&succ) if !succs.contains(&succ) => succs.push(succ),
+                Some(_) => {}
+                None => return false,
+            }
+        }
+
+        for succ in succs {
+            self.ssa.insert_control_edge(block, succ, UNCOND_EDGE);
+        }
+        self.ssa.remove_control_edge(edge);
+        self.ssa.remove_block(placeholder);
+        if self.value_ranges.is_some() {
+            self.value_ranges = Some(ValueRanges::new(&self.ssa));
+        }
+        true
+    }
+
+    /// The edge from `block` to the placeholder of the targets of its indirect jump, if it ends
+    /// with one, along with the placeholder.
+    fn placeholder_of(&self, block: NodeIndex) -> Option<(EdgeIndex, NodeIndex)> {
+        let ssa = &self.ssa;
+        let edge = match ssa.outgoing_edges(block).as_slice() {
+            &[(edge, _)] => edge,
+            _ => return None,
+        };
+        let placeholder = ssa.g.edge_endpoints(edge)?.1;
+        let start = ssa.starting_address(placeholder)?;
+        if ssa.exit_node() == Some(placeholder) || !phiplacement::is_unexplored(start) {
+            return None;
+        }
+        Some((edge, placeholder))
+    }
+
+    /// Returns the instructions of this function referencing `address`, i.e. the operations using
+    /// it as a constant, sorted by address. Uses as the target of a call or a jump are code
+    /// references, any other use, such as the address of a load or a store, is a data reference.
+    pub fn xrefs_to(&self, address: u64) -> Vec<(u64, XrefKind)> {
+        let ssa = &self.ssa;
+        let mut xrefs = Vec::new();
+        for (node, value) in ssa.constants() {
+            if value != address {
+                continue;
+            }
+            for user in ssa.uses_of(node) {
+                let from = match ssa.address(user) {
+                    Some(addr) if ssa.is_expr(user) => addr.address,
+                    _ => continue,
+                };
+                let kind = match ssa.opcode(user) {
+                    Some(MOpcode::OpCall) if ssa.sparse_operands_of(user).contains(&(0, node)) => {
+                        XrefKind::Code
+                    }
+                    Some(MOpcode::OpJmp) | Some(MOpcode::OpCJmp) => XrefKind::Code,
+                    _ => XrefKind::Data,
+                };
+                xrefs.push((from, kind));
+            }
+        }
+        xrefs.sort();
+        xrefs.dedup();
+        xrefs
+    }
+
+    /// Annotates the SSA with the flags of `names` (see `flag_names`): the constants whose value is
+    /// the offset of a flag, the basic blocks starting at one, and the entry node with the flag at
+    /// the offset of the function. The annotations are retrieved with `SSAExtra::flags`.
+    pub fn annotate_flags(&mut self, names: &HashMap<u64, String>) {
+        let ssa = &mut self.ssa;
+        let mut flagged = ssa
+            .constants()
+            .iter()
+            .filter_map(|(&node, value)| Some((node, names.get(value)?)))
+            .collect::<Vec<_>>();
+        if let Some(entry) = ssa.entry_node() {
+            flagged.extend(names.get(&self.offset).map(|name| (entry, name)));
+            let blocks = ssa.blocks().into_iter().filter(|&b| b != entry);
+            flagged.extend(blocks.filter_map(|b| {
+                let start = ssa.starting_address(b)?;
+                Some((b, names.get(&start.address)?))
+            }));
+        }
+        for (node, name) in flagged {
+            ssa.add_flag(&node, name.clone());
+        }
+    }
+
+    /// Attaches the user comments of `annotations`, by address, to the SSA (see
+    /// `SSAExtra::set_comment`), for `ir_writer` and the C backend to render them inline. Every
+    /// comment goes to the first operation lifted from the instruction at its address. The
+    /// addresses outside of this function are ignored, and the ones inside it without any
+    /// operation, e.g. removed by the analyses, are skipped with a warning. Returns the number of
+    /// comments attached.
+    pub fn apply_annotations(&mut self, annotations: HashMap<u64, String>) -> usize {
+        let ssa = &mut self.ssa;
+        let mut first: HashMap<u64, (MAddress, NodeIndex)> = HashMap::new();
+        for node in ssa.values().into_iter().filter(|&n| ssa.is_expr(n)) {
+            let addr = match ssa.address(node) {
+                Some(addr) if annotations.contains_key(&addr.address) => addr,
+                _ => continue,
+            };
+            let entry = first.entry(addr.address).or_insert((addr, node));
+            if (addr, node) < *entry {
+                *entry = (addr, node);
+            }
+        }
+        let mut applied = 0;
+        for (address, comment) in annotations {
+            match first.get(&address) {
+                Some(&(_, node)) => {
+                    ssa.set_comment(&node, comment);
+                    applied += 1;
+                }
+                None if address.wrapping_sub(self.offset) < self.size => {
+                    radeco_warn!(
+                        "No operation at {:#x} to annotate in {}",
+                        address,
+                        self.name
+                    );
+                }
+                None => {}
+            }
+        }
+        applied
+    }
+
+    /// Discards the SSA of this function, as well as the nodes its bindings refer to. The SSA
+    /// has to be reconstructed before the function is analyzed again.
+    pub fn invalidate_ssa(&mut self) {
+        let regfile = Arc::clone(&self.ssa.regfile);
+        self.ssa = SSAStorage::new();
+        self.ssa.regfile = regfile;
+        self.binding_map.clear();
+        self.disasm = None;
+        self.unsupported.clear();
+        self.const_writes.clear();
+        for binding in &mut self.bindings {
+            binding.idx = NodeIndex::end();
+        }
+    }
+
+    /// Points the register bindings back at the register state of the entry (for arguments)
+    /// or exit (for returns) of the SSA.
+    fn rebind_registers(&mut self) {
+        let (entry_state, exit_state) = {
+            let ssa = &self.ssa;
+            let state = |node: Option<NodeIndex>| {
+                node.and_then(|n| ssa.registers_in(n))
+                    .map(|regs| ssa.sparse_operands_of(regs))
+                    .unwrap_or_default()
+            };
+            (state(ssa.entry_node()), state(ssa.exit_node()))
+        };
+        for binding in &mut self.bindings {
+            let state = if binding.btype.is_argument() {
+                &entry_state
+            } else if binding.btype.is_return() {
+                &exit_state
+            } else {
+                continue;
+            };
+            if let Some(ridx) = binding.ridx {
+                binding.idx = state
+                    .iter()
+                    .find(|&&(i, _)| i as u64 == ridx)
+                    .map_or(NodeIndex::end(), |&(_, node)| node);
+            }
+        }
+    }
+
     pub fn call_sites(&self, call_graph: &CallGraph) -> Vec<CallContextInfo> {
         call_graph
             .edges_directed(self.cgid, Direction::Outgoing)
@@ -1309,8 +2947,20 @@ impl RadecoFunction {
         }
     }
 
+    /// Drops the register argument bindings of the registers that aren't live at the entry of the
+    /// function, i.e. that are written before being read.
     pub fn mark_args(&mut self) {
-        // TODO
+        let live = match liveness::live_in(self) {
+            Some(live) => live,
+            None => return,
+        };
+        self.bindings
+            .retain(|binding| match (&binding.btype, binding.ridx) {
+                (BindingType::RegisterArgument(_), Some(ridx)) => {
+                    live.contains(&RegisterId::from_u8(ridx as u8))
+                }
+                _ => true,
+            });
     }
 
     pub fn mark_locals(&mut self) {
@@ -1331,6 +2981,22 @@ impl RadecoFunction {
         }
     }
 
+    /// Binds `node`, an address computation, to the local variable `binding`. The binding is
+    /// also added to the function's bindings if it is not already known.
+    pub fn bind_local(&mut self, node: NodeIndex, binding: VarBinding) {
+        let known = self
+            .bindings
+            .iter()
+            .any(|vb| vb.btype == binding.btype && vb.name == binding.name);
+        if !known {
+            self.bindings.push(binding.clone());
+        }
+        let bindings = self.binding_map.entry(node).or_insert_with(Vec::new);
+        if !bindings.iter().any(|vb| vb.btype == binding.btype) {
+            bindings.push(binding);
+        }
+    }
+
     pub fn local_at(&self, node: NodeIndex, forward: bool) -> Option<VarBindings> {
         let next = self.ssa.replaced_map.get(&node).cloned();
         if next.is_some() && forward {
@@ -1341,7 +3007,7 @@ impl RadecoFunction {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct CallContextInfo {
     /// NodeIndex mapping from a node in the caller's context to a node in callee's context
     pub map: Vec<(NodeIndex, NodeIndex)>,
@@ -1351,8 +3017,1010 @@ pub struct CallContextInfo {
     pub csite: u64,
 }
 
+/// Kind of a reference to an address, see `RadecoModule::xrefs_to`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum XrefKind {
+    /// The address is the target of a call or a jump
+    Code,
+    /// The address is loaded from, stored to or otherwise used as a value
+    Data,
+}
+
+/// Reference to an address made by an instruction of a function.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Xref {
+    /// Address of the instruction making the reference
+    pub from: u64,
+    pub kind: XrefKind,
+    /// Name of the function containing the instruction
+    pub function: String,
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::analysis::engine::RadecoEngine;
+    use crate::frontend::radeco_source::{FileSource, SourceErr};
+    use crate::middle::ir::MOpcode;
+    use crate::middle::ir_writer;
+    use crate::middle::ssa::ssa_traits::SSAWalk;
+    use crate::utils::test_support::{entry_reg, load, load_at, reg_profile, register_file};
+    use r2papi::structs::{FunctionInfo, LImportInfo, LRegInfo};
+    use serde_json;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::thread;
+
+    #[test]
+    fn rebuild_dirty_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source = FileSource::open(path.to_str().unwrap());
+        let mut rp = ProjectLoader::new().source(Rc::new(source)).load();
+        let rmod = rp.nth_module_mut(0).unwrap();
+        let main = 0x40059D;
+        let nodes = rmod.function(main).unwrap().ssa().g.node_count();
+        assert!(nodes > 0);
+
+        rmod.mark_dirty(main);
+        assert!(rmod.is_dirty(main));
+        assert_eq!(rmod.function(main).unwrap().ssa().g.node_count(), 0);
+
+        rmod.rebuild_dirty();
+        assert!(!rmod.is_dirty(main));
+        let rfn = rmod.function(main).unwrap();
+        assert_eq!(rfn.ssa().g.node_count(), nodes);
+        assert!(!rfn.instructions().is_empty());
+        for binding in rfn.bindings().iter().filter(|b| b.ridx.is_some()) {
+            assert!(rfn.ssa().node_data(binding.idx).is_ok());
+        }
+    }
+
+    #[test]
+    fn save_load_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source = FileSource::open(path.to_str().unwrap());
+        let mut rp = ProjectLoader::new().source(Rc::new(source)).load();
+        let main = 0x40059D;
+        let engine = RadecoEngine::new(10);
+        engine.report_func(rp.nth_module_mut(0).unwrap().function_mut(main).unwrap());
+
+        let saved = env::temp_dir().join(format!("radeco_save_load_test_{}", process::id()));
+        rp.save(&saved).unwrap();
+        let loaded = RadecoProject::load(&saved);
+        fs::remove_file(&saved).unwrap();
+        let loaded = loaded.unwrap();
+
+        let emit = |rp: &RadecoProject| {
+            let mut out = Vec::new();
+            for rmod in rp.iter().map(|zm| zm.module) {
+                for rfn in rmod.functions.values() {
+                    let mut il = String::new();
+                    ir_writer::emit_il(&mut il, Some(rfn.name.to_string()), rfn.ssa()).unwrap();
+                    let bindings = rfn
+                        .bindings()
+                        .iter()
+                        .map(|b| (b.btype(), b.idx, b.ridx))
+                        .collect::<Vec<_>>();
+                    out.push((il, bindings));
+                }
+                out.push((format!("{:?}", rmod.callgraph), Vec::new()));
+            }
+            out
+        };
+        assert_eq!(emit(&rp), emit(&loaded));
+        // The register files are restored.
+        let rfn = loaded.nth_module(0).and_then(|rmod| rmod.function(main));
+        let regfile = &rfn.unwrap().ssa().regfile;
+        assert_eq!(regfile.whole_names, rp.regfile().whole_names);
+        // So are the results of the analyses.
+        let original = rp.nth_module(0).and_then(|rmod| rmod.function(main));
+        let (original, restored) = (original.unwrap(), rfn.unwrap());
+        let ranges = |rfn: &RadecoFunction| {
+            let ranges = rfn.value_ranges().expect("No value ranges");
+            rfn.ssa()
+                .inorder_walk()
+                .map(|n| ranges.range_of(n))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ranges(original), ranges(restored));
+        assert_eq!(
+            format!("{:?}", original.regusage),
+            format!("{:?}", restored.regusage)
+        );
+        assert_eq!(original.uninit_reads(), restored.uninit_reads());
+
+        // Projects saved with another version of the format are rejected.
+        fs::write(&saved, r#"{"version": 0}"#).unwrap();
+        let loaded = RadecoProject::load(&saved);
+        fs::remove_file(&saved).unwrap();
+        match loaded {
+            Err(ProjectFileError::Version(0)) => {}
+            _ => panic!("Expected a version mismatch"),
+        }
+    }
+
+    #[test]
+    fn assume_cc_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let main = 0x40059D;
+        let call_operands = |loader: ProjectLoader| {
+            let source = FileSource::open(path.to_str().unwrap());
+            let rp = loader.source(Rc::new(source)).load();
+            let ssa = rp.nth_module(0).unwrap().function(main).unwrap().ssa();
+            ssa.inorder_walk()
+                .into_iter()
+                .filter(|&node| ssa.opcode(node) == Some(MOpcode::OpCall))
+                .map(|node| ssa.operands_of(node).len())
+                .collect::<Vec<_>>()
+        };
+
+        // Without the calling conventions every call reads every register, with them only the
+        // arguments.
+        let conservative = call_operands(ProjectLoader::new());
+        let assumed = call_operands(ProjectLoader::new().assume_cc());
+        assert!(!conservative.is_empty());
+        assert_eq!(conservative.len(), assumed.len());
+        for (c, a) in conservative.iter().zip(assumed.iter()) {
+            assert!(a < c, "{} operands with assume_cc, {} without", a, c);
+        }
+    }
+
+    #[test]
+    fn only_reachable_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source: Rc<dyn Source> = Rc::new(FileSource::open(path.to_str().unwrap()));
+        let entry0 = 0x4004B0;
+        let main = 0x40059D;
+        let register_tm_clones = 0x400510;
+
+        let rmod = ModuleLoader::default()
+            .build_ssa()
+            .only_reachable()
+            .load(Rc::clone(&source));
+        assert!(rmod.function(entry0).unwrap().ssa().g.node_count() > 0);
+        // `entry0` only passes the address of `main` to `__libc_start_main`.
+        assert!(rmod.function(main).is_none());
+        assert!(rmod.function(register_tm_clones).is_none());
+
+        let rmod = ModuleLoader::default()
+            .build_ssa()
+            .only_reachable()
+            .include_indirect()
+            .load(Rc::clone(&source));
+        assert!(rmod.function(main).unwrap().ssa().g.node_count() > 0);
+        assert!(rmod.function(register_tm_clones).is_none());
+    }
+
+    #[test]
+    fn max_workers_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source: Rc<dyn Source> = Rc::new(FileSource::open(path.to_str().unwrap()));
+        let il_with = |n| {
+            let rmod = ModuleLoader::default()
+                .build_ssa()
+                .max_workers(n)
+                .load(Rc::clone(&source));
+            rmod.functions
+                .values()
+                .map(|rfn| {
+                    let mut il = String::new();
+                    ir_writer::emit_il(&mut il, Some(rfn.name.to_string()), rfn.ssa()).unwrap();
+                    il
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let single = il_with(1);
+        assert!(single.len() > 1);
+        assert_eq!(single, il_with(4));
+    }
+
+    #[test]
+    fn max_function_size_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source: Rc<dyn Source> = Rc::new(FileSource::open(path.to_str().unwrap()));
+        // `main` has 44 instructions over 147 bytes, `sym.__libc_csu_init` 34 over 101 bytes.
+        let main = 0x40059D;
+        let csu_init = 0x400630;
+
+        let rmod = ModuleLoader::default()
+            .build_ssa()
+            .max_function_insts(40)
+            .load(Rc::clone(&source));
+        assert!(rmod.function(main).unwrap().instructions().is_empty());
+        assert_eq!(rmod.function(csu_init).unwrap().instructions().len(), 34);
+
+        let rmod = ModuleLoader::default()
+            .build_ssa()
+            .max_function_bytes(100)
+            .load(Rc::clone(&source));
+        assert!(rmod.function(main).unwrap().instructions().is_empty());
+        assert!(rmod.function(csu_init).unwrap().instructions().is_empty());
+        assert_eq!(rmod.function(0x4004B0).unwrap().instructions().len(), 11);
+    }
+
+    /// `FileSource` recording the names of the functions it disassembles.
+    struct RecordingSource {
+        inner: FileSource,
+        disassembled: RefCell<Vec<String>>,
+    }
+
+    impl Source for RecordingSource {
+        fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+            self.inner.functions()
+        }
+        fn instructions_at(&self, addr: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+            self.inner.instructions_at(addr)
+        }
+        fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+            self.inner.register_profile()
+        }
+        fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+            self.inner.flags()
+        }
+        fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+            self.inner.sections()
+        }
+        fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+            self.inner.symbols()
+        }
+        fn instruction_count(&self, start_addr: u64) -> Option<usize> {
+            self.inner.instruction_count(start_addr)
+        }
+        fn imports(&self) -> Result<Vec<LImportInfo>, SourceErr> {
+            self.inner.imports()
+        }
+        fn exports(&self) -> Result<Vec<LExportInfo>, SourceErr> {
+            self.inner.exports()
+        }
+        fn relocs(&self) -> Result<Vec<LRelocInfo>, SourceErr> {
+            self.inner.relocs()
+        }
+        fn libraries(&self) -> Result<Vec<String>, SourceErr> {
+            self.inner.libraries()
+        }
+        fn entrypoint(&self) -> Result<Vec<LEntryInfo>, SourceErr> {
+            self.inner.entrypoint()
+        }
+        fn strings(&self, data_only: bool) -> Result<Vec<LStringInfo>, SourceErr> {
+            self.inner.strings(data_only)
+        }
+        fn locals_of(&self, start_addr: u64) -> Result<Vec<LVarInfo>, SourceErr> {
+            self.inner.locals_of(start_addr)
+        }
+        fn cc_info_of(&self, start_addr: u64) -> Result<LCCInfo, SourceErr> {
+            self.inner.cc_info_of(start_addr)
+        }
+        fn disassemble_function(&self, name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
+            self.disassembled.borrow_mut().push(name.to_owned());
+            self.inner.disassemble_function(name)
+        }
+    }
+
+    #[test]
+    fn max_function_insts_skips_disassembly() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source = Rc::new(RecordingSource {
+            inner: FileSource::open(path.to_str().unwrap()),
+            disassembled: RefCell::new(Vec::new()),
+        });
+
+        let rmod = ModuleLoader::default()
+            .build_ssa()
+            .max_function_insts(40)
+            .load(source.clone());
+        // `main` is skipped from its 44 instructions reported by the source, before being
+        // disassembled.
+        assert!(rmod.function(0x40059D).unwrap().instructions().is_empty());
+        let disassembled = source.disassembled.borrow();
+        assert!(!disassembled.iter().any(|name| name == "main"));
+        assert!(disassembled
+            .iter()
+            .any(|name| name == "sym.__libc_csu_init"));
+    }
+
+    /// `FileSource` taking `delay` to disassemble the function named `slow`.
+    struct SlowSource {
+        inner: FileSource,
+        slow: &'static str,
+        delay: Duration,
+    }
+
+    impl Source for SlowSource {
+        fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+            self.inner.functions()
+        }
+        fn instructions_at(&self, addr: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+            self.inner.instructions_at(addr)
+        }
+        fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+            self.inner.register_profile()
+        }
+        fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+            self.inner.flags()
+        }
+        fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+            self.inner.sections()
+        }
+        fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+            self.inner.symbols()
+        }
+        fn imports(&self) -> Result<Vec<LImportInfo>, SourceErr> {
+            self.inner.imports()
+        }
+        fn exports(&self) -> Result<Vec<LExportInfo>, SourceErr> {
+            self.inner.exports()
+        }
+        fn relocs(&self) -> Result<Vec<LRelocInfo>, SourceErr> {
+            self.inner.relocs()
+        }
+        fn libraries(&self) -> Result<Vec<String>, SourceErr> {
+            self.inner.libraries()
+        }
+        fn entrypoint(&self) -> Result<Vec<LEntryInfo>, SourceErr> {
+            self.inner.entrypoint()
+        }
+        fn strings(&self, data_only: bool) -> Result<Vec<LStringInfo>, SourceErr> {
+            self.inner.strings(data_only)
+        }
+        fn locals_of(&self, start_addr: u64) -> Result<Vec<LVarInfo>, SourceErr> {
+            self.inner.locals_of(start_addr)
+        }
+        fn cc_info_of(&self, start_addr: u64) -> Result<LCCInfo, SourceErr> {
+            self.inner.cc_info_of(start_addr)
+        }
+        fn disassemble_function(&self, name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
+            if name == self.slow {
+                thread::sleep(self.delay);
+            }
+            self.inner.disassemble_function(name)
+        }
+    }
+
+    #[test]
+    fn fn_timeout_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source: Rc<dyn Source> = Rc::new(SlowSource {
+            inner: FileSource::open(path.to_str().unwrap()),
+            slow: "main",
+            delay: Duration::from_millis(600),
+        });
+        let main = 0x40059D;
+        let csu_init = 0x400630;
+
+        let mut rmod = ModuleLoader::default()
+            .build_ssa()
+            .fn_timeout(Duration::from_millis(300))
+            .load(Rc::clone(&source));
+        // Loading the instructions of `main` used up its budget, none of them is lifted.
+        let rfn = rmod.function(main).unwrap();
+        assert!(rfn.timed_out());
+        assert_eq!(rfn.instructions().len(), 44);
+        assert!(rfn.disasm().unwrap().is_empty());
+        // The other functions are loaded as usual.
+        for (_, rfn) in rmod.functions.iter().filter(|(&offset, _)| offset != main) {
+            assert!(!rfn.timed_out(), "{} timed out", rfn.name);
+        }
+        let blocks = rmod.function(csu_init).unwrap().ssa().blocks().len();
+        assert!(blocks > 2);
+
+        // The engine moves on from `main` without analyzing it.
+        let engine = RadecoEngine::new(10).fn_timeout(Duration::from_secs(60));
+        let report = engine.report_func(rmod.functions.get_mut(&main).unwrap());
+        assert!(report.is_none());
+        let report = engine
+            .report_func(rmod.functions.get_mut(&csu_init).unwrap())
+            .unwrap();
+        assert!(!report.timed_out);
+
+        // An exhausted budget stops the engine before the first analyzer.
+        let rfn = rmod.functions.get_mut(&csu_init).unwrap();
+        let report = RadecoEngine::new(10)
+            .fn_timeout(Duration::from_secs(0))
+            .report_func(rfn)
+            .unwrap();
+        assert!(report.timed_out);
+        assert!(report.passes.iter().all(|p| p.iterations == 0));
+        assert!(rfn.timed_out());
+    }
+
+    /// `FileSource` listing its functions `page_size` at a time. Every request of functions is
+    /// recorded, `None` being a request of the whole list.
+    struct PagedSource {
+        inner: FileSource,
+        page_size: usize,
+        requests: RefCell<Vec<Option<(usize, usize)>>>,
+    }
+
+    impl Source for PagedSource {
+        fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+            self.requests.borrow_mut().push(None);
+            self.inner.functions()
+        }
+        fn function_page_size(&self) -> Option<usize> {
+            Some(self.page_size)
+        }
+        fn functions_range(
+            &self,
+            offset: usize,
+            count: usize,
+        ) -> Result<Vec<FunctionInfo>, SourceErr> {
+            self.requests.borrow_mut().push(Some((offset, count)));
+            let functions = self.inner.functions()?;
+            Ok(functions.into_iter().skip(offset).take(count).collect())
+        }
+        fn instructions_at(&self, addr: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+            self.inner.instructions_at(addr)
+        }
+        fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+            self.inner.register_profile()
+        }
+        fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+            self.inner.flags()
+        }
+        fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+            self.inner.sections()
+        }
+        fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+            self.inner.symbols()
+        }
+        fn imports(&self) -> Result<Vec<LImportInfo>, SourceErr> {
+            self.inner.imports()
+        }
+        fn exports(&self) -> Result<Vec<LExportInfo>, SourceErr> {
+            self.inner.exports()
+        }
+        fn relocs(&self) -> Result<Vec<LRelocInfo>, SourceErr> {
+            self.inner.relocs()
+        }
+        fn libraries(&self) -> Result<Vec<String>, SourceErr> {
+            self.inner.libraries()
+        }
+        fn entrypoint(&self) -> Result<Vec<LEntryInfo>, SourceErr> {
+            self.inner.entrypoint()
+        }
+        fn strings(&self, data_only: bool) -> Result<Vec<LStringInfo>, SourceErr> {
+            self.inner.strings(data_only)
+        }
+        fn locals_of(&self, start_addr: u64) -> Result<Vec<LVarInfo>, SourceErr> {
+            self.inner.locals_of(start_addr)
+        }
+        fn cc_info_of(&self, start_addr: u64) -> Result<LCCInfo, SourceErr> {
+            self.inner.cc_info_of(start_addr)
+        }
+        fn disassemble_function(&self, name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
+            self.inner.disassemble_function(name)
+        }
+    }
+
+    #[test]
+    fn paged_functions_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let path = path.to_str().unwrap();
+        let paged = Rc::new(PagedSource {
+            inner: FileSource::open(path),
+            page_size: 4,
+            requests: RefCell::new(Vec::new()),
+        });
+        let rmod = ModuleLoader::default().load(Rc::clone(&paged) as Rc<dyn Source>);
+        // bin1 has 14 functions, the last page is the first one which is not full.
+        assert_eq!(
+            *paged.requests.borrow(),
+            vec![Some((0, 4)), Some((4, 4)), Some((8, 4)), Some((12, 4))]
+        );
+
+        let expected = ModuleLoader::default().load(Rc::new(FileSource::open(path)));
+        assert_eq!(
+            rmod.functions.keys().collect::<Vec<_>>(),
+            expected.functions.keys().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn function_loader_plugins() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source: Rc<dyn Source> = Rc::new(FileSource::open(path.to_str().unwrap()));
+
+        fn add_synthetic(
+            _: Option<&Rc<dyn Source>>,
+            functions: &mut BTreeMap<u64, RadecoFunction>,
+            _: &RadecoModule,
+        ) {
+            let mut rfn = RadecoFunction::default();
+            rfn.name = Cow::from("fcn.synthetic");
+            rfn.offset = 0x400700;
+            rfn.size = 0x10;
+            functions.insert(rfn.offset, rfn);
+        }
+        fn rename_synthetic(
+            _: Option<&Rc<dyn Source>>,
+            functions: &mut BTreeMap<u64, RadecoFunction>,
+            _: &RadecoModule,
+        ) {
+            if let Some(rfn) = functions.get_mut(&0x400700) {
+                rfn.name = Cow::from("fcn.renamed");
+            }
+        }
+
+        let rmod = ModuleLoader::default()
+            .function_loader(
+                FunctionLoader::default()
+                    .include_defaults()
+                    .plugin(&add_synthetic),
+            )
+            .load(Rc::clone(&source));
+        assert_eq!(rmod.function(0x400700).unwrap().name, "fcn.synthetic");
+        // The functions reported by the source are kept.
+        assert!(rmod.function(0x40059D).is_some());
+
+        // The plugins registered later see the functions added by the earlier ones.
+        let rmod = ModuleLoader::default()
+            .function_loader(
+                FunctionLoader::default()
+                    .include_defaults()
+                    .plugin(&add_synthetic)
+                    .plugin(&rename_synthetic),
+            )
+            .load(Rc::clone(&source));
+        assert_eq!(rmod.function(0x400700).unwrap().name, "fcn.renamed");
+    }
+
+    #[test]
+    fn callers_and_callees() {
+        let mut rmod = RadecoModule::default();
+        for &(offset, name) in &[(0x1000, "main"), (0x2000, "helper")] {
+            let mut rfn = RadecoFunction::default();
+            rfn.offset = offset;
+            rfn.name = Cow::from(name);
+            rmod.functions.insert(offset, rfn);
+        }
+        rmod.imports.insert(
+            0x400,
+            ImportInfo::new_stub(0x400, Cow::from("sym.imp.puts")),
+        );
+
+        let call = |csite| CallContextInfo {
+            csite: csite,
+            ..CallContextInfo::default()
+        };
+        let main = rmod.callgraph.add_node(0x1000);
+        let helper = rmod.callgraph.add_node(0x2000);
+        let puts = rmod.callgraph.add_node(0x400);
+        rmod.callgraph.add_edge(main, helper, call(0x1010));
+        rmod.callgraph.add_edge(main, puts, call(0x1008));
+        rmod.callgraph.add_edge(helper, puts, call(0x2004));
+
+        assert_eq!(
+            rmod.callees_by_name(0x1000),
+            vec![
+                (0x1008, "sym.imp.puts".to_owned()),
+                (0x1010, "helper".to_owned())
+            ]
+        );
+        assert_eq!(
+            rmod.callers_by_name(0x2000),
+            vec![(0x1010, "main".to_owned())]
+        );
+        assert_eq!(
+            rmod.callers_by_name(0x400),
+            vec![(0x1008, "main".to_owned()), (0x2004, "helper".to_owned())]
+        );
+        assert!(rmod.callers_by_name(0x1000).is_empty());
+        assert!(rmod.callees_by_name(0x400).is_empty());
+    }
+
+    #[test]
+    fn iter_topo_test() {
+        let mut rmod = RadecoModule::default();
+        for &offset in &[0x1000, 0x2000, 0x3000] {
+            let mut rfn = RadecoFunction::default();
+            rfn.offset = offset;
+            rmod.functions.insert(offset, rfn);
+        }
+        // main -> helper -> leaf, added in the order of the map.
+        let main = rmod.callgraph.add_node(0x1000);
+        let helper = rmod.callgraph.add_node(0x2000);
+        let leaf = rmod.callgraph.add_node(0x3000);
+        rmod.callgraph
+            .add_edge(main, helper, CallContextInfo::default());
+        rmod.callgraph
+            .add_edge(helper, leaf, CallContextInfo::default());
+
+        let order = |rmod: &RadecoModule| {
+            rmod.iter_topo()
+                .map(|zf| *zf.function.0)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(&rmod), vec![0x3000, 0x2000, 0x1000]);
+        // `iter` is unchanged.
+        let offsets = rmod.iter().map(|zf| *zf.function.0).collect::<Vec<_>>();
+        assert_eq!(offsets, vec![0x1000, 0x2000, 0x3000]);
+
+        // A cycle is yielded by address, after its callees.
+        rmod.callgraph
+            .add_edge(leaf, helper, CallContextInfo::default());
+        assert_eq!(order(&rmod), vec![0x2000, 0x3000, 0x1000]);
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const XREF_MAIN_SSA_TXT: &str = "\
+define-fun main(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %2: $Unknown64 = Load(%1, #x601040);
+        [@0x001008.0001] (%3: $Unknown0 = $mem) = CALL #x2000($rdi=%2, $mem=%1);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %3;
+}
+";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const XREF_HELPER_SSA_TXT: &str = "\
+define-fun helper(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x002000.0000(sz 0x8):
+        [@0x002004.0001] %3: $Unknown0 = Store(%2, #x601040, %1);
+        RETURN
+    exit-node:
+    final-register-state:
+        $mem = %3;
+}
+";
+
+    #[test]
+    fn tail_call_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source: Rc<dyn Source> = Rc::new(FileSource::open(path.to_str().unwrap()));
+        // Both paths of `entry1.init` end in a jump to `sym.register_tm_clones`.
+        let init = 0x400570;
+        let register_tm_clones = 0x400510;
+        let jumps = [0x400590, 0x400598];
+
+        let rmod = ModuleLoader::default()
+            .build_ssa()
+            .build_callgraph()
+            .load(source);
+        let rfn = rmod.function(init).unwrap();
+        let tail_calls = jumps
+            .iter()
+            .map(|&j| (j, register_tm_clones))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(rfn.tail_calls(), &tail_calls);
+        let callees = rmod
+            .callgraph
+            .callees(rfn.cgid())
+            .map(|(csite, n)| (csite, rmod.callgraph[n]))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(callees, tail_calls);
+
+        // Each jump is a call returning to the caller of `entry1.init`.
+        let ssa = rfn.ssa();
+        for &jump in &jumps {
+            let call = ssa
+                .inorder_walk()
+                .into_iter()
+                .find(|&n| {
+                    ssa.opcode(n) == Some(MOpcode::OpCall)
+                        && ssa.address(n).map(|a| a.address) == Some(jump)
+                })
+                .unwrap();
+            let block = ssa.block_for(call).unwrap();
+            assert_eq!(ssa.unconditional_block(block), ssa.exit_node());
+        }
+        // The jumps don't add the start of `sym.register_tm_clones` as a block.
+        let target = MAddress::new(register_tm_clones, 0);
+        assert!(ssa
+            .blocks()
+            .into_iter()
+            .all(|b| ssa.starting_address(b) != Some(target)));
+    }
+
+    #[test]
+    fn xrefs_test() {
+        let regfile = register_file();
+        let mut rmod = RadecoModule::default();
+        for &(offset, name, il) in &[
+            (0x1000, "main", XREF_MAIN_SSA_TXT),
+            (0x2000, "helper", XREF_HELPER_SSA_TXT),
+        ] {
+            let mut rfn = load_at(il, offset, &regfile);
+            rfn.name = Cow::from(name);
+            rmod.functions.insert(offset, rfn);
+        }
+        let main = rmod.callgraph.add_node(0x1000);
+        let helper = rmod.callgraph.add_node(0x2000);
+        let cctx = CallContextInfo {
+            csite: 0x1008,
+            ..CallContextInfo::default()
+        };
+        rmod.callgraph.add_edge(main, helper, cctx);
+
+        let xref = |from, kind, function: &str| Xref {
+            from,
+            kind,
+            function: function.to_owned(),
+        };
+        assert_eq!(
+            rmod.xrefs_to(0x601040),
+            vec![
+                xref(0x1000, XrefKind::Data, "main"),
+                xref(0x2004, XrefKind::Data, "helper")
+            ]
+        );
+        // The call is listed once, both as a constant target and as a callgraph edge.
+        assert_eq!(
+            rmod.xrefs_to(0x2000),
+            vec![xref(0x1008, XrefKind::Code, "main")]
+        );
+        assert!(rmod.xrefs_to(0x1000).is_empty());
+    }
+
+    #[test]
+    fn reg_value_at_test() {
+        let reg_profile = reg_profile();
+        // mov eax, 1; je 0x1010; mov eax, 2; jmp 0x1010; mov rbx, rax; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"1,rax,=","offset":4096,"opcode":"mov eax, 1","type":"mov","size":5},
+                {"esil":"zf,?{,0x1010,rip,=,}","offset":4101,"opcode":"je 0x1010","type":"cjmp","size":2},
+                {"esil":"2,rax,=","offset":4103,"opcode":"mov eax, 2","type":"mov","size":5},
+                {"esil":"0x1010,rip,=","offset":4108,"opcode":"jmp 0x1010","type":"jmp","size":4},
+                {"esil":"rax,rbx,=","offset":4112,"opcode":"mov rbx, rax","type":"mov","size":3},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4115,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.offset = 0x1000;
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let rax_at = |addr| rfn.reg_value_at("rax", addr).expect("No value for rax");
+        // The constants are moved into rax.
+        let const_at = |addr| {
+            let mov = rax_at(addr);
+            assert_eq!(ssa.opcode(mov), Some(MOpcode::OpMov));
+            ssa.constant(ssa.operands_of(mov)[0])
+        };
+        assert_eq!(ssa.argument(rax_at(0x1000)), Some("rax".to_owned()));
+        assert_eq!(const_at(0x1005), Some(1));
+        assert_eq!(const_at(0x1007), Some(1));
+        assert_eq!(const_at(0x100c), Some(2));
+        // Both definitions flow into 0x1010.
+        let phi = rax_at(0x1010);
+        assert!(ssa.is_phi(phi));
+        assert_eq!(rax_at(0x1013), phi);
+        assert!(rfn.reg_value_at("rax", 0xfff).is_none());
+    }
+
+    #[test]
+    fn block_effects_test() {
+        let reg_profile = reg_profile();
+        // mov eax, 1; mov dword [rdi], esi; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"1,rax,=","offset":4096,"opcode":"mov eax, 1","type":"mov","size":5},
+                {"esil":"esi,rdi,=[4]","offset":4101,"opcode":"mov dword [rdi], esi","type":"mov","size":2},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4103,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.offset = 0x1000;
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let block = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| {
+                ssa.exprs_in(b)
+                    .into_iter()
+                    .any(|n| ssa.opcode(n) == Some(MOpcode::OpStore))
+            })
+            .expect("No block with the store");
+        let effect = rfn.block_effects(block);
+        assert!(effect.defines.contains("rax"));
+        assert!(effect.uses.contains("rdi"));
+        assert!(effect.uses.contains("rsi"));
+        assert!(!effect.uses.contains("rax"));
+        assert!(!effect.defines.contains("mem") && !effect.uses.contains("mem"));
+        assert!(effect.writes_memory);
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const FLOW_SSA_TXT: &str = "\
+define-fun sym.flow(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %4: $Unknown64 = Load(%3, %1);
+        [@0x001000.0002] %5: $Unknown64 = %4 + #x1;
+        [@0x001000.0003] %6: $Unknown0 = Store(%3, %2, %5);
+        [@0x001000.0004] %7: $Unknown64 = %2 + #x8;
+        [@0x001000.0005] %8: $Unknown64 = Load(%6, %7);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %8;
+        $rbx = %7;
+        $mem = %6;
+}
+";
+
+    #[test]
+    fn flows_to_test() {
+        let rfn = load(FLOW_SSA_TXT);
+
+        let ssa = rfn.ssa();
+        // Nodes of `opcode`, in the order of their address
+        let op = |opcode: MOpcode| {
+            let mut nodes = ssa
+                .inorder_walk()
+                .filter(|&n| ssa.opcode(n) == Some(opcode.clone()))
+                .collect::<Vec<_>>();
+            nodes.sort_by_key(|&n| ssa.address(n));
+            nodes
+        };
+        let (load, reload) = (op(MOpcode::OpLoad)[0], op(MOpcode::OpLoad)[1]);
+        let (add, store) = (op(MOpcode::OpAdd)[0], op(MOpcode::OpStore)[0]);
+        let offset = op(MOpcode::OpAdd)[1];
+        let (rdi, rsi, mem) = (
+            entry_reg(ssa, "rdi"),
+            entry_reg(ssa, "rsi"),
+            entry_reg(ssa, "mem"),
+        );
+
+        assert!(rfn.flows_to(load, add));
+        assert!(rfn.flows_to(load, store));
+        // Through the memory chain, whatever address is loaded from.
+        assert!(rfn.flows_to(load, reload));
+        assert!(rfn.flows_to(rdi, reload));
+        assert!(!rfn.flows_to(add, load));
+        assert!(!rfn.flows_to(load, offset));
+        assert!(!rfn.flows_to(rdi, offset));
+
+        let mut sources = vec![rdi, mem];
+        sources.sort();
+        assert_eq!(rfn.taint_sources(add), sources);
+        assert_eq!(rfn.taint_sources(offset), vec![rsi]);
+    }
+
+    #[test]
+    fn resolve_indirect_test() {
+        let reg_profile = reg_profile();
+        // je 0x1010; jb 0x1008; jmp rdi; ret; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"zf,?{,0x1010,rip,=,}","offset":4096,"opcode":"je 0x1010","type":"cjmp","size":2},
+                {"esil":"cf,?{,0x1008,rip,=,}","offset":4098,"opcode":"jb 0x1008","type":"cjmp","size":2},
+                {"esil":"rdi,rip,=","offset":4100,"opcode":"jmp rdi","type":"rjmp","size":2},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4104,"opcode":"ret","type":"ret","size":1},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4112,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.offset = 0x1000;
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let block_at = |rfn: &RadecoFunction, addr| {
+            let ssa = rfn.ssa();
+            ssa.blocks()
+                .into_iter()
+                .find(|&b| ssa.starting_address(b) == Some(MAddress::new(addr, 0)))
+                .unwrap()
+        };
+        let jump = block_at(&rfn, 0x1004);
+        let unresolved = rfn.unresolved_targets();
+        assert_eq!(unresolved.len(), 1);
+        let (placeholder, selector) = unresolved[0];
+        assert!(phiplacement::is_unexplored(placeholder));
+        assert_eq!(rfn.ssa().selector_in(jump), Some(selector));
+
+        // 0x1009 doesn't start a block.
+        assert!(!rfn.resolve_indirect(selector, vec![0x1008, 0x1009]));
+        assert_eq!(rfn.unresolved_targets(), unresolved);
+
+        assert!(rfn.resolve_indirect(selector, vec![0x1008, 0x1010, 0x1008]));
+        assert!(rfn.unresolved_targets().is_empty());
+        let mut succs = rfn.ssa().succs_of(jump);
+        succs.sort();
+        let mut targets = vec![block_at(&rfn, 0x1008), block_at(&rfn, 0x1010)];
+        targets.sort();
+        assert_eq!(succs, targets);
+        assert!(rfn
+            .ssa()
+            .blocks()
+            .into_iter()
+            .all(|b| rfn.ssa().starting_address(b) != Some(placeholder)));
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const BRANCH_SSA_TXT: &str = "\
+define-fun sym.branch(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown1 = %1 == %2;
+        JMP IF %4 0x001010.0000 ELSE 0x001008.0000
+    bb_0x001008.0000(sz 0x8):
+        [@0x001008.0001] %5: $Unknown64 = %1 - %2;
+        JMP 0x001010.0000
+    bb_0x001010.0000(sz 0x1):
+        %6: $Unknown64 = Phi(%1, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $mem = %3;
+}
+";
+
+    #[test]
+    fn cfg_dot_test() {
+        let rfn = load(BRANCH_SSA_TXT);
+
+        let dot = rfn.cfg_dot();
+        let lines = dot.lines().collect::<Vec<_>>();
+        let count = |pat: &str| lines.iter().filter(|l| l.contains(pat)).count();
+        // entry, 0x1000, 0x1008 and 0x1010, then the exit node
+        assert_eq!(count("shape=box"), 4);
+        assert_eq!(count("shape=doublecircle"), 1);
+        assert_eq!(count(" -> "), 5);
+        assert_eq!(count("label=\"T\""), 1);
+        assert_eq!(count("label=\"F\""), 1);
+        assert_eq!(count("label=\"U\""), 3);
+        assert_eq!(count("label=\"0x1000-0x1008\""), 1);
+        assert_eq!(count("label=\"0x1008-0x1010\""), 1);
+        // No value is drawn.
+        assert!(!dot.contains("Phi"));
+    }
+
+    #[test]
+    fn basic_blocks_test() {
+        let rfn = load(BRANCH_SSA_TXT);
+
+        let addr = |address| MAddress::new(address, 0);
+        let blocks = rfn.basic_blocks();
+        let starts = blocks.iter().map(|b| b.start).collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![addr(0), addr(0x1000), addr(0x1008), addr(0x1010)]
+        );
+        assert_eq!(blocks[0].succs, vec![(addr(0x1000), UNCOND_EDGE)]);
+        assert_eq!(blocks[1].size, 8);
+        assert_eq!(blocks[1].succs, vec![(addr(0x1008), 0), (addr(0x1010), 1)]);
+        assert_eq!(blocks[2].size, 8);
+        assert_eq!(blocks[2].succs, vec![(addr(0x1010), UNCOND_EDGE)]);
+        assert_eq!(blocks[3].size, 1);
+        assert!(blocks[3].succs.is_empty());
+        // Only the last block returns.
+        assert_eq!(
+            blocks.iter().map(|b| b.exits).collect::<Vec<_>>(),
+            vec![false, false, false, true]
+        );
+    }
+
     #[test]
     fn test_fn_loader() {
         // let ld = |x: &FLResult, y: &RadecoModule| -> FLResult { unimplemented!() };
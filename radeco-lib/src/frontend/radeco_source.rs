@@ -6,15 +6,22 @@ use std::cell::RefCell;
 use std::error::Error;
 use std::fmt;
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::frontend::radeco_containers::section_containing;
 
 use r2papi::api_trait::R2Api;
 use r2papi::structs::{
     FunctionInfo, LCCInfo, LEntryInfo, LExportInfo, LFlagInfo, LFunctionInfo, LImportInfo, LOpInfo,
     LRegInfo, LRelocInfo, LSectionInfo, LStringInfo, LSymbolInfo, LVarInfo,
 };
+use r2pipe::r2::R2;
 
 #[derive(Debug)]
 pub enum SourceErr {
@@ -84,11 +91,54 @@ pub trait Source {
     fn raw(&self, _cmd: String) -> Result<String, SourceErr> {
         unimplemented!()
     }
+    /// Read `len` raw bytes of the binary mapped at `addr`.
+    ///
+    /// The analyses reading memory skip what they can't read, so the sources without the bytes
+    /// of the binary fail instead of panicking.
+    fn read_bytes(&self, _addr: u64, _len: usize) -> Result<Vec<u8>, SourceErr> {
+        Err(SourceErr::SrcErr(
+            "read_bytes is not supported by this source",
+        ))
+    }
 
     fn send(&self, _: String) -> Result<(), SourceErr> {
         Ok(())
     }
 
+    /// Offsets of the functions whose instructions this source can provide, or `None` if it can
+    /// provide the instructions of any function.
+    fn available_functions(&self) -> Option<Vec<u64>> {
+        None
+    }
+
+    /// Number of instructions of the function at `start_addr`, as recorded in its metadata, or
+    /// `None` if it can't be known without disassembling the function.
+    fn instruction_count(&self, _start_addr: u64) -> Option<usize> {
+        None
+    }
+
+    /// Number of functions to request at once from `functions_range` when listing all of them,
+    /// or `None` if the whole list is better requested at once from `functions`.
+    fn function_page_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// This source, if it can serve requests from several threads at once (see
+    /// `PooledR2Source`), or `None` if the requests have to be issued one at a time.
+    fn as_concurrent(&self) -> Option<&(dyn Source + Sync)> {
+        None
+    }
+
+    /// At most `count` functions, starting from the `offset`-th one listed by `functions`.
+    fn functions_range(&self, offset: usize, count: usize) -> Result<Vec<FunctionInfo>, SourceErr> {
+        Ok(self
+            .functions()?
+            .into_iter()
+            .skip(offset)
+            .take(count)
+            .collect())
+    }
+
     // Non essential / functions with default implementation.
     fn function_at(&self, address: u64) -> Result<FunctionInfo, SourceErr> {
         for f in self.functions()? {
@@ -110,6 +160,23 @@ pub trait Source {
         Err(SourceErr::SrcErr("No known function"))
     }
 
+    /// All the functions, requested a page at a time if the source has a `function_page_size`.
+    fn functions_paged(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+        let page_size = match self.function_page_size() {
+            Some(page_size) if page_size > 0 => page_size,
+            _ => return self.functions(),
+        };
+        let mut functions = Vec::new();
+        loop {
+            let page = self.functions_range(functions.len(), page_size)?;
+            let last = page.len() < page_size;
+            functions.extend(page);
+            if last {
+                return Ok(functions);
+            }
+        }
+    }
+
     fn instructions_at_fn(&self, fn_name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
         let fn_ = self.function_named(fn_name)?;
         if let Some(offset) = fn_.offset {
@@ -128,15 +195,12 @@ pub trait Source {
         Err(SourceErr::SrcErr("No known flag at offset"))
     }
 
+    /// Returns the section containing `address`, the smallest one if it is also contained in a
+    /// segment, see [`section_containing`].
     fn section_of(&self, address: u64) -> Result<LSectionInfo, SourceErr> {
-        for s in self.sections()? {
-            let addr = s.vaddr.expect("Invalid section");
-            let size = s.size.expect("Invalid section size");
-            if address >= addr && address < addr + size {
-                return Ok(s);
-            }
-        }
-        Err(SourceErr::SrcErr("No known section at addr"))
+        section_containing(&self.sections()?, address)
+            .cloned()
+            .ok_or(SourceErr::SrcErr("No known section at addr"))
     }
 }
 
@@ -155,14 +219,66 @@ pub trait Source {
 // sense to have some sort of cached information so that concurrent reads can occur. This should
 // be invalidated whenever some information is exported back to radare or some analysis is run on
 // r2.
+//
+// `PooledR2Source` serves several threads, with one r2 process each.
 pub type WrappedR2Api<R> = Rc<RefCell<R>>;
 
+/// Number of functions requested at once from r2, see `Source::function_page_size`.
+pub const R2_FUNCTION_PAGE_SIZE: usize = 256;
+
+/// `ninstr` of the function at `offset` in `json`, a list of functions as printed by `aflj`.
+/// r2papi's `FunctionInfo` doesn't keep it.
+fn ninstr_of(json: &str, offset: u64) -> Option<usize> {
+    let functions: Vec<serde_json::Value> = serde_json::from_str(json).ok()?;
+    let function = functions
+        .iter()
+        .find(|f| f["offset"].as_u64() == Some(offset))?;
+    function["ninstr"].as_u64().map(|n| n as usize)
+}
+
 // Implementation of `Source` trait for R2.
 impl<R: R2Api> Source for WrappedR2Api<R> {
     fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
         Ok(self.try_borrow_mut()?.fn_list()?)
     }
 
+    fn function_page_size(&self) -> Option<usize> {
+        Some(R2_FUNCTION_PAGE_SIZE)
+    }
+
+    fn instruction_count(&self, start_addr: u64) -> Option<usize> {
+        let json = self
+            .try_borrow_mut()
+            .ok()?
+            .raw(format!("afij @ {}", start_addr));
+        ninstr_of(&json, start_addr)
+    }
+
+    // `aflq` lists the offset of every function, one per line, of which `~:` keeps the lines of
+    // the page. The information of the functions in the page is then requested in a single
+    // command, as the concatenation of their `afij`.
+    fn functions_range(&self, offset: usize, count: usize) -> Result<Vec<FunctionInfo>, SourceErr> {
+        let mut r2 = self.try_borrow_mut()?;
+        let offsets = r2.raw(format!("aflq~:{}..{}", offset, offset + count));
+        let cmd = offsets
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .take(count)
+            .map(|off| format!("afij @ {}", off))
+            .collect::<Vec<_>>()
+            .join(";");
+        if cmd.is_empty() {
+            return Ok(Vec::new());
+        }
+        let json = r2.raw(cmd);
+        let mut functions = Vec::new();
+        for info in serde_json::Deserializer::from_str(&json).into_iter::<Vec<FunctionInfo>>() {
+            functions.extend(info?);
+        }
+        Ok(functions)
+    }
+
     fn instructions_at(&self, address: u64) -> Result<Vec<LOpInfo>, SourceErr> {
         if let Ok(fn_info) = self.try_borrow_mut()?.function(&format!("{}", address)) {
             fn_info
@@ -241,12 +357,424 @@ impl<R: R2Api> Source for WrappedR2Api<R> {
         Ok(self.try_borrow_mut()?.raw(cmd))
     }
 
+    fn read_bytes(&self, addr: u64, len: usize) -> Result<Vec<u8>, SourceErr> {
+        let hex = self
+            .try_borrow_mut()?
+            .raw(format!("p8 {} @ {:#x}", len, addr));
+        let bytes = decode_hex(&hex)?;
+        if bytes.len() != len {
+            return Err(SourceErr::SrcErr("Short read from r2"));
+        }
+        Ok(bytes)
+    }
+
     fn send(&self, s: String) -> Result<(), SourceErr> {
         let _ = self.try_borrow_mut()?.raw(s);
         Ok(())
     }
 }
 
+/// Decodes the hex string printed by r2's `p8`.
+fn decode_hex(hex: &str) -> Result<Vec<u8>, SourceErr> {
+    let hex = hex.trim();
+    if !hex.is_ascii() || hex.len() % 2 != 0 {
+        return Err(SourceErr::SrcErr("Invalid hex string"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(SourceErr::from))
+        .collect()
+}
+
+/// Default number of reconnects a `ReconnectingSource` attempts before giving up.
+pub const DEFAULT_RECONNECTS: u32 = 3;
+
+/// Source over a connection that may drop, e.g. to a remote r2 over TCP/HTTP.
+///
+/// When a request fails with a transport error (`SourceErr::OtherErr`), the connection is
+/// re-established through `connect` and the request is retried, up to `retries` times with an
+/// exponential backoff. `connect` must re-issue any setup (e.g. analysis) the source relies on.
+pub struct ReconnectingSource<S, F>
+where
+    S: Source,
+    F: Fn() -> Result<S, SourceErr>,
+{
+    source: RefCell<Option<S>>,
+    connect: F,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl<S, F> ReconnectingSource<S, F>
+where
+    S: Source,
+    F: Fn() -> Result<S, SourceErr>,
+{
+    /// Wraps the already connected `source`, using `connect` to reconnect.
+    pub fn new(source: S, connect: F, retries: u32) -> ReconnectingSource<S, F> {
+        ReconnectingSource {
+            source: RefCell::new(Some(source)),
+            connect,
+            retries,
+            backoff: Duration::from_millis(100),
+        }
+    }
+
+    /// Delay before the first reconnect; it doubles on every following attempt.
+    pub fn backoff(mut self, backoff: Duration) -> ReconnectingSource<S, F> {
+        self.backoff = backoff;
+        self
+    }
+
+    fn with_retry<T, G>(&self, op: G) -> Result<T, SourceErr>
+    where
+        G: Fn(&S) -> Result<T, SourceErr>,
+    {
+        let mut delay = self.backoff;
+        for _ in 0..self.retries {
+            match self.source.borrow().as_ref().map(&op) {
+                Some(Err(SourceErr::OtherErr(_e))) => {
+                    radeco_warn!("Lost connection to source: {}", _e);
+                }
+                Some(res) => return res,
+                None => {}
+            }
+            thread::sleep(delay);
+            delay *= 2;
+            *self.source.borrow_mut() = (self.connect)().ok();
+        }
+        match *self.source.borrow() {
+            Some(ref source) => op(source),
+            None => Err(SourceErr::SrcErr("Unable to reconnect to source")),
+        }
+    }
+}
+
+impl<S, F> Source for ReconnectingSource<S, F>
+where
+    S: Source,
+    F: Fn() -> Result<S, SourceErr>,
+{
+    fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+        self.with_retry(|s| s.functions())
+    }
+
+    fn function_page_size(&self) -> Option<usize> {
+        self.source
+            .borrow()
+            .as_ref()
+            .and_then(|s| s.function_page_size())
+    }
+
+    fn functions_range(&self, offset: usize, count: usize) -> Result<Vec<FunctionInfo>, SourceErr> {
+        self.with_retry(|s| s.functions_range(offset, count))
+    }
+
+    fn instructions_at(&self, address: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.with_retry(|s| s.instructions_at(address))
+    }
+
+    fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+        self.with_retry(|s| s.register_profile())
+    }
+
+    fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+        self.with_retry(|s| s.flags())
+    }
+
+    fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+        self.with_retry(|s| s.sections())
+    }
+
+    fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+        self.with_retry(|s| s.symbols())
+    }
+
+    fn imports(&self) -> Result<Vec<LImportInfo>, SourceErr> {
+        self.with_retry(|s| s.imports())
+    }
+
+    fn exports(&self) -> Result<Vec<LExportInfo>, SourceErr> {
+        self.with_retry(|s| s.exports())
+    }
+
+    fn relocs(&self) -> Result<Vec<LRelocInfo>, SourceErr> {
+        self.with_retry(|s| s.relocs())
+    }
+
+    fn libraries(&self) -> Result<Vec<String>, SourceErr> {
+        self.with_retry(|s| s.libraries())
+    }
+
+    fn entrypoint(&self) -> Result<Vec<LEntryInfo>, SourceErr> {
+        self.with_retry(|s| s.entrypoint())
+    }
+
+    fn disassemble_function(&self, name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.with_retry(|s| s.disassemble_function(name))
+    }
+
+    fn disassemble_n_bytes(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.with_retry(|s| s.disassemble_n_bytes(n, at))
+    }
+
+    fn disassemble_n_insts(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.with_retry(|s| s.disassemble_n_insts(n, at))
+    }
+
+    fn locals_of(&self, start_addr: u64) -> Result<Vec<LVarInfo>, SourceErr> {
+        self.with_retry(|s| s.locals_of(start_addr))
+    }
+
+    fn cc_info_of(&self, start_addr: u64) -> Result<LCCInfo, SourceErr> {
+        self.with_retry(|s| s.cc_info_of(start_addr))
+    }
+
+    fn strings(&self, data_only: bool) -> Result<Vec<LStringInfo>, SourceErr> {
+        self.with_retry(|s| s.strings(data_only))
+    }
+
+    fn raw(&self, cmd: String) -> Result<String, SourceErr> {
+        self.with_retry(|s| s.raw(cmd.clone()))
+    }
+
+    fn read_bytes(&self, addr: u64, len: usize) -> Result<Vec<u8>, SourceErr> {
+        self.with_retry(|s| s.read_bytes(addr, len))
+    }
+
+    fn send(&self, cmd: String) -> Result<(), SourceErr> {
+        self.with_retry(|s| s.send(cmd.clone()))
+    }
+
+    fn available_functions(&self) -> Option<Vec<u64>> {
+        self.source
+            .borrow()
+            .as_ref()
+            .and_then(|s| s.available_functions())
+    }
+
+    fn instruction_count(&self, start_addr: u64) -> Option<usize> {
+        self.source
+            .borrow()
+            .as_ref()
+            .and_then(|s| s.instruction_count(start_addr))
+    }
+}
+
+/// Source over a pool of independent r2 processes, each with its own pipe to the same binary.
+///
+/// Unlike `WrappedR2Api`, it can serve requests from several threads at once, e.g. the
+/// disassembly of the functions by a `ModuleLoader::parallel`. Every request checks out an idle
+/// connection for its duration, waiting for one to be returned to the pool if all of them are
+/// busy. The connections are closed when the pool is dropped.
+pub struct PooledR2Source {
+    idle: Mutex<Vec<R2>>,
+    returned: Condvar,
+    // Number of connections of the pool, idle or checked out.
+    size: AtomicUsize,
+}
+
+// Connection checked out of a `PooledR2Source`. It goes back to the pool when dropped, even if
+// the request panicked.
+struct Checkout<'a> {
+    pool: &'a PooledR2Source,
+    conn: Option<WrappedR2Api<R2>>,
+}
+
+impl<'a> Drop for Checkout<'a> {
+    fn drop(&mut self) {
+        let conn = match self.conn.take() {
+            Some(conn) => conn,
+            None => return,
+        };
+        // A request panicking with the lock held is no reason to leak the connection.
+        let mut idle = self.pool.idle.lock().unwrap_or_else(|e| e.into_inner());
+        match Rc::try_unwrap(conn) {
+            Ok(r2) => idle.push(r2.into_inner()),
+            // The request kept the connection, it is lost to the pool.
+            Err(_) => {
+                radeco_warn!("r2 connection kept by a request, the pool shrinks");
+                self.pool.size.fetch_sub(1, atomic::Ordering::SeqCst);
+            }
+        }
+        self.pool.returned.notify_all();
+    }
+}
+
+impl PooledR2Source {
+    /// Spawns `size` (at least one) r2 processes on the binary at `path`, analyzing it in each.
+    pub fn open(path: &str, size: usize) -> Result<PooledR2Source, SourceErr> {
+        let mut connections = Vec::new();
+        for _ in 0..size.max(1) {
+            let mut r2 = R2::new(Some(path)).map_err(|_| SourceErr::SrcErr("Unable to open r2"))?;
+            let _ = r2.raw("e bin.minstr=1".to_string());
+            r2.analyze_all();
+            connections.push(r2);
+        }
+        Ok(PooledR2Source {
+            size: AtomicUsize::new(connections.len()),
+            idle: Mutex::new(connections),
+            returned: Condvar::new(),
+        })
+    }
+
+    fn with_connection<T, G>(&self, op: G) -> Result<T, SourceErr>
+    where
+        G: FnOnce(&WrappedR2Api<R2>) -> Result<T, SourceErr>,
+    {
+        let r2 = {
+            let mut idle = self
+                .idle
+                .lock()
+                .map_err(|_| SourceErr::SrcErr("r2 pool poisoned"))?;
+            loop {
+                if let Some(r2) = idle.pop() {
+                    break r2;
+                }
+                if self.size.load(atomic::Ordering::SeqCst) == 0 {
+                    return Err(SourceErr::SrcErr("r2 pool is empty"));
+                }
+                idle = self
+                    .returned
+                    .wait(idle)
+                    .map_err(|_| SourceErr::SrcErr("r2 pool poisoned"))?;
+            }
+        };
+        let checkout = Checkout {
+            pool: self,
+            conn: Some(Rc::new(RefCell::new(r2))),
+        };
+        match checkout.conn {
+            Some(ref conn) => op(conn),
+            None => unreachable!(),
+        }
+    }
+}
+
+impl Drop for PooledR2Source {
+    fn drop(&mut self) {
+        if let Ok(idle) = self.idle.get_mut() {
+            for r2 in idle.iter_mut() {
+                r2.close();
+            }
+        }
+    }
+}
+
+impl Source for PooledR2Source {
+    fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+        self.with_connection(|r2| r2.functions())
+    }
+
+    fn function_page_size(&self) -> Option<usize> {
+        Some(R2_FUNCTION_PAGE_SIZE)
+    }
+
+    fn as_concurrent(&self) -> Option<&(dyn Source + Sync)> {
+        Some(self)
+    }
+
+    fn instruction_count(&self, start_addr: u64) -> Option<usize> {
+        self.with_connection(|r2| Ok(r2.instruction_count(start_addr)))
+            .ok()?
+    }
+
+    fn functions_range(&self, offset: usize, count: usize) -> Result<Vec<FunctionInfo>, SourceErr> {
+        self.with_connection(|r2| r2.functions_range(offset, count))
+    }
+
+    fn instructions_at(&self, address: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.with_connection(|r2| r2.instructions_at(address))
+    }
+
+    fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+        self.with_connection(|r2| r2.register_profile())
+    }
+
+    fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+        self.with_connection(|r2| r2.flags())
+    }
+
+    fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+        self.with_connection(|r2| r2.sections())
+    }
+
+    fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+        self.with_connection(|r2| r2.symbols())
+    }
+
+    fn imports(&self) -> Result<Vec<LImportInfo>, SourceErr> {
+        self.with_connection(|r2| r2.imports())
+    }
+
+    fn exports(&self) -> Result<Vec<LExportInfo>, SourceErr> {
+        self.with_connection(|r2| r2.exports())
+    }
+
+    fn relocs(&self) -> Result<Vec<LRelocInfo>, SourceErr> {
+        self.with_connection(|r2| r2.relocs())
+    }
+
+    fn libraries(&self) -> Result<Vec<String>, SourceErr> {
+        self.with_connection(|r2| r2.libraries())
+    }
+
+    fn entrypoint(&self) -> Result<Vec<LEntryInfo>, SourceErr> {
+        self.with_connection(|r2| r2.entrypoint())
+    }
+
+    fn disassemble_function(&self, name: &str) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.with_connection(|r2| r2.disassemble_function(name))
+    }
+
+    fn disassemble_n_bytes(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.with_connection(|r2| r2.disassemble_n_bytes(n, at))
+    }
+
+    fn disassemble_n_insts(&self, n: u64, at: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.with_connection(|r2| r2.disassemble_n_insts(n, at))
+    }
+
+    fn locals_of(&self, start_addr: u64) -> Result<Vec<LVarInfo>, SourceErr> {
+        self.with_connection(|r2| r2.locals_of(start_addr))
+    }
+
+    fn cc_info_of(&self, start_addr: u64) -> Result<LCCInfo, SourceErr> {
+        self.with_connection(|r2| r2.cc_info_of(start_addr))
+    }
+
+    fn strings(&self, data_only: bool) -> Result<Vec<LStringInfo>, SourceErr> {
+        self.with_connection(|r2| r2.strings(data_only))
+    }
+
+    fn raw(&self, cmd: String) -> Result<String, SourceErr> {
+        self.with_connection(|r2| r2.raw(cmd))
+    }
+
+    fn read_bytes(&self, addr: u64, len: usize) -> Result<Vec<u8>, SourceErr> {
+        self.with_connection(|r2| r2.read_bytes(addr, len))
+    }
+
+    // Sent to every connection, once all of them are idle, as the commands sent to r2 usually
+    // change its state.
+    fn send(&self, cmd: String) -> Result<(), SourceErr> {
+        let mut idle = self
+            .idle
+            .lock()
+            .map_err(|_| SourceErr::SrcErr("r2 pool poisoned"))?;
+        while idle.len() < self.size.load(atomic::Ordering::SeqCst) {
+            idle = self
+                .returned
+                .wait(idle)
+                .map_err(|_| SourceErr::SrcErr("r2 pool poisoned"))?;
+        }
+        for r2 in idle.iter_mut() {
+            let _ = r2.raw(cmd.clone());
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 /// File source is used to load information from json files.
 /// The files in the directory must be of the form "<base_name>_<suffix>.json". This means that the
@@ -297,6 +825,7 @@ mod suffix {
     pub const LOCAL: &'static str = "locals";
     pub const CCINFO: &'static str = "ccinfo";
     pub const ENTRY: &'static str = "entrypoint";
+    pub const BYTES: &'static str = "bytes";
 }
 
 impl FileSource {
@@ -309,6 +838,70 @@ impl FileSource {
             base_name: base_name.to_owned(),
         }
     }
+
+    /// Returns the sorted offsets of the functions whose instructions were dumped into `dir`,
+    /// i.e. the addresses for which `instructions_at` can succeed.
+    pub fn available_functions(&self) -> Vec<u64> {
+        self.dumped_offsets(suffix::INSTRUCTIONS)
+    }
+
+    /// Returns the sorted offsets of the files dumped as "<base_name>_<suffix>_<offset>.json".
+    fn dumped_offsets(&self, suffix: &str) -> Vec<u64> {
+        let prefix = format!("{}_{}_", self.base_name, suffix);
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_e) => {
+                radeco_warn!("{:?}", _e);
+                return Vec::new();
+            }
+        };
+        let mut offsets = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|name| {
+                if !name.starts_with(&*prefix) || !name.ends_with(".json") {
+                    return None;
+                }
+                let hex = &name[prefix.len()..name.len() - ".json".len()];
+                u64::from_str_radix(hex.trim_start_matches("0x"), 16).ok()
+            })
+            .collect::<Vec<_>>();
+        offsets.sort();
+        offsets
+    }
+
+    /// Looks for `len` bytes at `addr` in the chunks dumped as "<base_name>_bytes_<offset>.json".
+    fn dumped_bytes(&self, addr: u64, len: usize) -> Option<Vec<u8>> {
+        for start in self.dumped_offsets(suffix::BYTES) {
+            if start > addr {
+                break;
+            }
+            let suffix = format!("{}_{:#X}", suffix::BYTES, start);
+            let bytes: Vec<u8> = match self.read_file(&suffix) {
+                Ok(json_str) => serde_json::from_str(&json_str).ok()?,
+                Err(_) => continue,
+            };
+            let offset = (addr - start) as usize;
+            if offset + len <= bytes.len() {
+                return Some(bytes[offset..offset + len].to_vec());
+            }
+        }
+        None
+    }
+
+    /// Reads `len` bytes at `addr` from the side file "<base_name>.bin", which holds the raw
+    /// binary. Virtual addresses are mapped to file offsets through the dumped sections.
+    fn image_bytes(&self, path: &Path, addr: u64, len: usize) -> Result<Vec<u8>, SourceErr> {
+        let section = self.section_of(addr)?;
+        let (paddr, vaddr) = match (section.paddr, section.vaddr) {
+            (Some(paddr), Some(vaddr)) => (paddr, vaddr),
+            _ => return Err(SourceErr::SrcErr("Invalid section")),
+        };
+        let mut f = File::open(path)?;
+        f.seek(SeekFrom::Start(paddr + addr - vaddr))?;
+        let mut bytes = vec![0; len];
+        f.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
 }
 
 impl Source for FileSource {
@@ -396,6 +989,26 @@ impl Source for FileSource {
     fn raw(&self, _cmd: String) -> Result<String, SourceErr> {
         Err(SourceErr::SrcErr("`Source::raw` is not implemented"))
     }
+
+    fn read_bytes(&self, addr: u64, len: usize) -> Result<Vec<u8>, SourceErr> {
+        if let Some(bytes) = self.dumped_bytes(addr, len) {
+            return Ok(bytes);
+        }
+        let mut path = PathBuf::from(&self.dir);
+        path.push(&format!("{}.bin", self.base_name));
+        if !path.exists() {
+            return Err(SourceErr::SrcErr("No known bytes at addr"));
+        }
+        self.image_bytes(&path, addr, len)
+    }
+
+    fn available_functions(&self) -> Option<Vec<u64>> {
+        Some(FileSource::available_functions(self))
+    }
+
+    fn instruction_count(&self, start_addr: u64) -> Option<usize> {
+        ninstr_of(&self.read_file(suffix::FUNCTION_INFO).ok()?, start_addr)
+    }
 }
 
 impl<R: R2Api> From<WrappedR2Api<R>> for FileSource {
@@ -414,7 +1027,10 @@ impl<R: R2Api> From<WrappedR2Api<R>> for FileSource {
         {
             let fns = r2.functions().expect("Failed to load function info");
             {
-                let json_str = serde_json::to_string(&fns).expect("Failed to encode to json");
+                // `aflj` is dumped as is, as `FunctionInfo` drops `ninstr`.
+                let json_str = r2
+                    .raw("aflj".to_owned())
+                    .expect("Failed to load function info");
                 fsource.write_file(suffix::FUNCTION_INFO, &json_str);
             }
 
@@ -483,6 +1099,24 @@ impl<R: R2Api> From<WrappedR2Api<R>> for FileSource {
                 let sections = r2.sections().expect("Failed to load section map");
                 let json_str = serde_json::to_string(&sections).expect("Failed to encode to json");
                 fsource.write_file(suffix::SECTION, &json_str);
+
+                // Code is already dumped as instructions, only the data is needed.
+                for s in sections {
+                    let executable = s.flags.as_ref().map_or(false, |f| f.contains('x'));
+                    let (vaddr, size) = match (s.vaddr, s.size) {
+                        (Some(vaddr), Some(size)) if vaddr != 0 && size != 0 => (vaddr, size),
+                        _ => continue,
+                    };
+                    if executable {
+                        continue;
+                    }
+                    let bytes = r2
+                        .read_bytes(vaddr, size as usize)
+                        .expect("Failed to load section bytes");
+                    let json_str = serde_json::to_string(&bytes).expect("Failed to encode to json");
+                    let suffix = format!("{}_{:#X}", suffix::BYTES, vaddr);
+                    fsource.write_file(&suffix, &json_str);
+                }
             }
 
             {
@@ -547,8 +1181,33 @@ mod test {
 
     use crate::frontend::radeco_containers::*;
     use crate::frontend::radeco_source::*;
+    use crate::utils::test_support::StubSource;
+    use std::cell::Cell;
     use std::rc::Rc;
 
+    // Needs r2 to be installed.
+    #[test]
+    #[cfg(feature = "r2_tests")]
+    fn pooled_r2_source_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("ex-bins/simple2");
+        let path = path.to_str().unwrap();
+        let source = PooledR2Source::open(path, 2).expect("Unable to open r2");
+        assert!(source.as_concurrent().is_some());
+        let rproj = ProjectLoader::new()
+            .path(path)
+            .source(Rc::new(source))
+            .load();
+        for m in rproj.iter() {
+            let main = m
+                .module
+                .iter()
+                .find(|rfn| rfn.function.1.name.ends_with("main"))
+                .expect("No main");
+            assert!(!main.function.1.instructions().is_empty());
+        }
+    }
+
     #[test]
     fn file_source_test() {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -556,4 +1215,113 @@ mod test {
         let source = FileSource::open(path.to_str().unwrap());
         ProjectLoader::new().source(Rc::new(source)).load();
     }
+
+    #[test]
+    fn available_functions_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source = FileSource::open(path.to_str().unwrap());
+        let offsets = source.available_functions();
+        assert_eq!(offsets.len(), 14);
+        assert_eq!(offsets.first(), Some(&0x400438));
+        assert_eq!(offsets.last(), Some(&0x4006A4));
+        assert!(offsets.contains(&0x40059D));
+        for offset in offsets {
+            assert!(source.instructions_at(offset).is_ok());
+        }
+
+        // Nothing was dumped under another name.
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin2");
+        assert!(FileSource::open(path.to_str().unwrap())
+            .available_functions()
+            .is_empty());
+    }
+
+    #[test]
+    fn cross_module_link_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let bin1 = FileSource::open(path.to_str().unwrap());
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/libc_filesource/libc");
+        let libc = FileSource::open(path.to_str().unwrap());
+
+        let rp = ProjectLoader::new()
+            .source(Rc::new(bin1))
+            .add_module_source("libc.so.6", Rc::new(libc))
+            .load();
+
+        let libc_mod = rp.nth_module(1).expect("libc is not loaded");
+        assert_eq!(libc_mod.name(), "libc.so.6");
+
+        // `puts@plt` in bin1 is linked to `sym.puts` in libc.
+        let stub = FunctionRef {
+            module: 0,
+            offset: 0x400470,
+        };
+        let puts = FunctionRef {
+            module: 1,
+            offset: 0x1000,
+        };
+        let bin1_mod = rp.nth_module(0).unwrap();
+        assert_eq!(bin1_mod.name(), SOURCE_MODULE_NAME);
+        assert_eq!(bin1_mod.imports[&stub.offset].resolved, Some(puts));
+
+        let cg = rp.callgraph();
+        let node = |fref| cg.node_indices().find(|&n| cg[n] == fref).unwrap();
+        let edge = cg
+            .find_edge(node(stub), node(puts))
+            .expect("No cross-module edge");
+        assert_eq!(cg[edge], stub.offset);
+    }
+
+    #[test]
+    fn read_bytes_test() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("test_files/bin1_filesource/bin1");
+        let source = FileSource::open(path.to_str().unwrap());
+        // `_IO_stdin_used` at the start of .rodata, followed by the strings.
+        assert_eq!(source.read_bytes(0x4006B0, 4).unwrap(), vec![1, 0, 2, 0]);
+        assert_eq!(
+            source.read_bytes(0x4006B4, 8).unwrap(),
+            b"%d %d %d".to_vec()
+        );
+        assert_eq!(source.read_bytes(0x4006C3, 7).unwrap(), b"Success".to_vec());
+        // Past the end of the dumped chunk.
+        assert!(source.read_bytes(0x4006CB, 8).is_err());
+        assert!(source.read_bytes(0x400000, 4).is_err());
+
+        assert_eq!(
+            decode_hex("7f454c46\n").unwrap(),
+            vec![0x7f, b'E', b'L', b'F']
+        );
+        assert!(decode_hex("7f4").is_err());
+    }
+
+    #[test]
+    fn reconnect_test() {
+        let connects = Rc::new(Cell::new(0));
+        let connects_ = connects.clone();
+        let source = ReconnectingSource::new(
+            StubSource::new().broken_for(1),
+            move || {
+                connects_.set(connects_.get() + 1);
+                Ok(StubSource::new())
+            },
+            DEFAULT_RECONNECTS,
+        )
+        .backoff(Duration::from_millis(0));
+        assert!(source.functions().is_ok());
+        assert_eq!(connects.get(), 1);
+
+        // Gives up once the retries are exhausted.
+        let source = ReconnectingSource::new(
+            StubSource::new().broken_for(1),
+            || Ok(StubSource::new().broken_for(1)),
+            2,
+        )
+        .backoff(Duration::from_millis(0));
+        assert!(source.functions().is_err());
+    }
 }
@@ -10,11 +10,14 @@
 // For example: "zf,?{,0x80,rip,=,}" is a valid esil statement as it does not
 // have any
 // instructions after "}" in the same instruction.
+// The only exception is an explicit else, e.g. "zf,?{,1,rax,=,}{,2,rax,=,}",
+// whose true and false blocks both join at the next instruction.
 
 use esil::lexer::{Token, Tokenizer};
 
-use esil::parser::{Parse, Parser};
+use esil::parser::{Parse, Parser, ParserError};
 // use frontend::instruction_analyzer::{InstructionAnalyzer, X86_CS_IA, IOperand};
+use crate::error::ConstructError;
 use crate::frontend::radeco_containers::RadecoFunction;
 
 use crate::middle::ir::{self, MAddress, MOpcode};
@@ -27,7 +30,10 @@ use r2papi::structs::{LOpInfo, LRegInfo};
 
 // use regex::Regex;
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::sync::Arc;
+use std::time::Instant;
 use std::{cmp, fmt, u64};
 
 pub type VarId = usize;
@@ -36,6 +42,40 @@ const FALSE_EDGE: u8 = 0;
 const TRUE_EDGE: u8 = 1;
 const UNCOND_EDGE: u8 = 2;
 
+/// Values of the ESIL internal vars `old`, `old_` and `cur`, i.e. the operands and the result of
+/// the last operation setting them. The flags (`$z`, `$c`, ...) are computed from these.
+#[derive(Clone, Copy, Debug)]
+struct EsilVars<V> {
+    old: V,
+    old_: Option<V>,
+    cur: V,
+}
+
+/// Region of memory an address points to, as far as it is known while constructing the SSA. Each
+/// one is a pseudo-variable holding the last memory state which may have changed it, see
+/// `SSAConstruct::split_memory`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Region {
+    /// Relative to the stack pointer on entry
+    Stack,
+    /// At a constant address
+    Global,
+}
+
+const REGIONS: [Region; 2] = [Region::Stack, Region::Global];
+
+/// A `rep`-prefixed string instruction of x86, lifted as a single intrinsic rather than as the
+/// loop of its ESIL, see `string_op`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StringOp {
+    /// `rep movs`, lifted as `OpRepMovs`
+    Movs,
+    /// `rep stos`, lifted as `OpRepStos`
+    Stos,
+    /// `repne scas`, lifted as `OpRepScas`
+    Scas,
+}
+
 pub struct SSAConstruct<'a, T>
 where
     T: 'a
@@ -54,12 +94,44 @@ where
     // Used to keep track of esil if-else. The reference to the ITE node and the address of this
     // instruction.
     nesting: Vec<(T::ValueRef, MAddress)>,
+    // Ends of the true blocks of the ifs with an explicit else in this instruction, which go on
+    // with the next instruction along with the false blocks.
+    branch_ends: Vec<MAddress>,
+    // Set if the instruction is a conditional assignment (see `is_conditional_assignment`),
+    // which is lifted as a select on the condition, once known, rather than as a branch.
+    selecting: bool,
+    select_cond: Option<T::ValueRef>,
     // Used to keep track of the offset within an instruction.
     instruction_offset: u64,
     needs_new_block: bool,
     mem_id: u64,
     assume_cc: bool,
     replace_pc: bool,
+    // Set if the instruction being lifted computes addresses from its own address rather than
+    // from the next one, see `pc_value`.
+    self_relative: bool,
+    // Constants computed from the program counter, which operations with another constant are
+    // folded into, see `fold_pc_relative`.
+    pc_relative: HashSet<T::ValueRef>,
+    // Disassembly of the instruction each value was lifted from.
+    disasm: HashMap<T::ValueRef, String>,
+    // ESIL internal vars, as set by the last assignment or comparison.
+    esil_vars: Option<EsilVars<T::ValueRef>>,
+    // Operands of the last operation which didn't set the ESIL internal vars.
+    last_pop: (Option<T::ValueRef>, Option<T::ValueRef>),
+    // ESIL tokens which could not be lifted, with the address of their instruction.
+    unsupported: Vec<(u64, String)>,
+    // Set if the loads only go through the stores which may change the region they read from.
+    split_memory: bool,
+    // Region of the values known to be addresses in one of them.
+    regions: HashMap<T::ValueRef, Region>,
+    // Constants written to a register, with the address of the write and the register name.
+    const_writes: Vec<(T::ValueRef, MAddress, String)>,
+    // Addresses of the jumps lifted as a call followed by a return, see `tail_calls`.
+    tail_calls: HashSet<u64>,
+    // Instant after which the instructions left are not lifted, checked between instructions.
+    deadline: Option<Instant>,
+    timed_out: bool,
 }
 
 impl<'a, T> SSAConstruct<'a, T>
@@ -80,11 +152,26 @@ where
             regfile: regfile,
             intermediates: Vec::new(),
             nesting: Vec::new(),
+            branch_ends: Vec::new(),
+            selecting: false,
+            select_cond: None,
             instruction_offset: 0,
             needs_new_block: true,
             mem_id: 0,
             assume_cc: false,
             replace_pc: true,
+            self_relative: false,
+            pc_relative: HashSet::new(),
+            disasm: HashMap::new(),
+            esil_vars: None,
+            last_pop: (None, None),
+            unsupported: Vec::new(),
+            const_writes: Vec::new(),
+            split_memory: false,
+            regions: HashMap::new(),
+            tail_calls: HashSet::new(),
+            deadline: None,
+            timed_out: false,
         };
 
         // Add all the registers to the variable list.
@@ -97,14 +184,160 @@ where
     }
 
     // Helper wrapper.
-    pub fn construct(rfn: &mut RadecoFunction, ri: &LRegInfo, assume_cc: bool, replace_pc: bool) {
+    /// Constructs the SSA of `rfn`. The register profile used is the one of the first range in
+    /// `profiles` containing the start address of the function, or `ri` if there is none.
+    ///
+    /// Fails on malformed ESIL, see `run`.
+    pub fn construct(
+        rfn: &mut RadecoFunction,
+        ri: &LRegInfo,
+        profiles: &[(Range<u64>, Arc<SubRegisterFile>)],
+        assume_cc: bool,
+        replace_pc: bool,
+    ) -> Result<(), ConstructError> {
+        SSAConstruct::<T>::construct_until(rfn, ri, profiles, assume_cc, replace_pc, None)
+            .map(|_| ())
+    }
+
+    /// Same as `construct`, but the instructions left once `deadline` is past are not lifted.
+    /// Returns `true` if that happened, see `timed_out`.
+    pub fn construct_until(
+        rfn: &mut RadecoFunction,
+        ri: &LRegInfo,
+        profiles: &[(Range<u64>, Arc<SubRegisterFile>)],
+        assume_cc: bool,
+        replace_pc: bool,
+        deadline: Option<Instant>,
+    ) -> Result<bool, ConstructError> {
         let instructions = rfn.instructions().to_vec();
-        let regfile = Arc::new(SubRegisterFile::new(ri));
+        let tail_calls = rfn.tail_calls().keys().cloned().collect::<Vec<_>>();
+        let profile = profiles
+            .iter()
+            .find(|(range, _)| range.contains(&rfn.offset));
+        let regfile = match profile {
+            Some((_, rf)) => Arc::clone(rf),
+            None => Arc::new(SubRegisterFile::new(ri).map_err(|e| ConstructError {
+                address: rfn.offset,
+                esil: String::new(),
+                reason: e.to_string(),
+            })?),
+        };
         rfn.ssa_mut().regfile = regfile.clone();
-        let mut constr = SSAConstruct::new(rfn.ssa_mut(), &regfile);
-        constr.assume_cc = assume_cc;
-        constr.replace_pc = replace_pc;
-        constr.run(instructions.as_slice());
+        let (result, disasm, unsupported, const_writes, timed_out) = {
+            let mut constr = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            constr.assume_cc = assume_cc;
+            constr.replace_pc = replace_pc;
+            constr.deadline = deadline;
+            constr.tail_calls(tail_calls);
+            let result = constr.run(instructions.as_slice());
+            (
+                result,
+                constr.disasm,
+                constr.unsupported,
+                constr.const_writes,
+                constr.timed_out,
+            )
+        };
+        rfn.set_disasm(disasm);
+        rfn.set_unsupported(unsupported);
+        rfn.set_const_writes(const_writes);
+        result.map(|_| timed_out)
+    }
+
+    /// Returns the disassembly of the instruction every value was lifted from, as of the last
+    /// call to `run`.
+    pub fn disasm(&self) -> &HashMap<T::ValueRef, String> {
+        &self.disasm
+    }
+
+    /// Returns the ESIL tokens which could not be lifted during the last call to `run`, along
+    /// with the address of the instruction they belong to.
+    pub fn unsupported(&self) -> &[(u64, String)] {
+        &self.unsupported
+    }
+
+    /// Returns `true` if the last call to `run` stopped lifting the instructions at the deadline.
+    /// The SSA is still complete, but only covers the instructions before it.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// Lifts the jumps at `addresses` as tail calls, i.e. as a call followed by a return, rather
+    /// than as a jump. Must be called before `run`.
+    pub fn tail_calls<I: IntoIterator<Item = u64>>(&mut self, addresses: I) {
+        self.tail_calls.extend(addresses);
+    }
+
+    /// Models the stack and the memory at constant addresses as regions of their own, whose
+    /// loads only go through the stores which may change them. The stores still go through all
+    /// the previous ones, and so does the `mem` of the register states. Must be called before
+    /// `run`.
+    pub fn split_memory(&mut self) {
+        if !self.split_memory {
+            self.split_memory = true;
+            self.phiplacer
+                .add_variables(vec![*MEM_VALUEINFO; REGIONS.len()]);
+        }
+    }
+
+    // Pseudo-variable of `region`, which comes after the one of the whole memory.
+    fn region_id(&self, region: Region) -> u64 {
+        let index = REGIONS.iter().position(|&r| r == region).unwrap_or(0);
+        self.mem_id() + 1 + index as u64
+    }
+
+    fn region_of(&self, address: &T::ValueRef) -> Option<Region> {
+        if !self.split_memory {
+            None
+        } else if self.phiplacer.is_constant(address) {
+            Some(Region::Global)
+        } else {
+            self.regions.get(address).cloned()
+        }
+    }
+
+    // Memory state which a load from `address` goes through.
+    fn memory_for_load(&mut self, address: &mut MAddress, target: &T::ValueRef) -> T::ValueRef {
+        let variable = match self.region_of(target) {
+            Some(region) => self.region_id(region),
+            None => self.mem_id(),
+        };
+        self.read_memory(address, variable)
+    }
+
+    // Memory state `variable` at `address`. The memory states are added by `init_blocks`, a
+    // state which is missing reads as undefined.
+    fn read_memory(&mut self, address: &mut MAddress, variable: u64) -> T::ValueRef {
+        match self.phiplacer.read_variable(address, variable) {
+            Ok(state) => state,
+            Err(_e) => {
+                radeco_err!("Cannot read the memory state {}: {}", variable, _e);
+                self.phiplacer.add_undefined(*address, *MEM_VALUEINFO)
+            }
+        }
+    }
+
+    // Makes `state` the memory after a store to `target`, or after a call if it is `None`.
+    fn write_memory(
+        &mut self,
+        address: MAddress,
+        target: Option<&T::ValueRef>,
+        state: T::ValueRef,
+    ) {
+        let mut ids = vec![self.mem_id()];
+        if self.split_memory {
+            let region = target.and_then(|t| self.region_of(t));
+            for &r in REGIONS.iter() {
+                if region.map_or(true, |region| region == r) {
+                    ids.push(self.region_id(r));
+                }
+            }
+        }
+        for id in ids {
+            if let Err(_e) = self.phiplacer.write_variable(address, id, state) {
+                radeco_err!("Cannot write the memory state {}: {}", id, _e);
+            }
+        }
     }
 
     fn set_mem_id(&mut self, id: u64) {
@@ -141,10 +374,21 @@ where
                     && length.is_some()
                 {
                     // PC is a constant value at given address
-                    let value = address.address + length.unwrap();
-                    self.phiplacer.add_const(address, value, None)
+                    let value = self.pc_value(address.address, length.unwrap());
+                    let pc = self.phiplacer.add_const(address, value, None);
+                    self.pc_relative.insert(pc);
+                    pc
                 } else {
-                    self.phiplacer.read_register(address, name)
+                    let value = self.phiplacer.read_register(address, name);
+                    // The stack pointer read through a phi, whose operands aren't known yet
+                    // while the block isn't sealed, still points to the stack.
+                    if self.split_memory
+                        && self.regfile.alias_info.get("SP") == Some(name)
+                        && self.phiplacer.is_phi(&value)
+                    {
+                        self.regions.insert(value, Region::Stack);
+                    }
+                    value
                 }
             }
             // We arrive at this case only when we have popped an operand that we have pushed
@@ -162,6 +406,7 @@ where
                 let value = address.address;
                 self.phiplacer.add_const(address, value, None)
             }
+            ref flag if flag.is_flag() => self.process_flag(flag, address),
             _ => panic!(
                 "SSAConstruct Error: Found something other than a Var as an operand to an \
                  instruction!"
@@ -170,6 +415,162 @@ where
         Some(ret)
     }
 
+    // Value of the program counter as read by the instruction at `address`, `length` bytes long.
+    // As when ESIL is emulated, this is the address of the next instruction, except for the
+    // AArch64 `adr` and `adrp`, which compute the address relative to their own (`adrp` to its
+    // page).
+    fn pc_value(&self, address: u64, length: u64) -> u64 {
+        if self.self_relative {
+            address
+        } else {
+            address.wrapping_add(length)
+        }
+    }
+
+    // Folds an operation between two constants, one of them computed from the program counter,
+    // into the absolute address. Covers the `rip + disp` of a x86-64 `lea` as well as the `add`
+    // following an AArch64 `adrp`, so that the address can be cross referenced.
+    fn fold_pc_relative(
+        &self,
+        token: &Token,
+        lhs: &Option<T::ValueRef>,
+        rhs: &Option<T::ValueRef>,
+        width: u16,
+    ) -> Option<u64> {
+        let (lhs, rhs) = (lhs.as_ref()?, rhs.as_ref()?);
+        if !self.pc_relative.contains(lhs) && !self.pc_relative.contains(rhs) {
+            return None;
+        }
+        let (a, b) = (self.phiplacer.constant(lhs)?, self.phiplacer.constant(rhs)?);
+        let value = match *token {
+            Token::EAdd => a.wrapping_add(b),
+            Token::ESub => a.wrapping_sub(b),
+            Token::EAnd => a & b,
+            _ => return None,
+        };
+        if width > 0 && width < 64 {
+            Some(value & ((1 << width) - 1))
+        } else {
+            Some(value)
+        }
+    }
+
+    // Computes an ESIL flag from the internal vars set by the last operation, as the parser would
+    // have done in its expansion.
+    fn process_flag(&mut self, flag: &Token, address: &mut MAddress) -> T::ValueRef {
+        let vars = match self.esil_vars {
+            Some(vars) => vars,
+            None => {
+                radeco_warn!("Flag {:?} used before any operation set it", flag);
+                return self.phiplacer.add_undefined(*address, scalar!(1));
+            }
+        };
+        let lastsz = cmp::max(self.phiplacer.operand_width(&vars.old), 1);
+        let msb = u64::from(lastsz) - 1;
+        match *flag {
+            Token::IZero(_) => {
+                let cur = self.mask_to(address, vars.cur, lastsz);
+                let zero = self.phiplacer.add_const(address, 0, None);
+                self.flag_op(MOpcode::OpEq, address, 1, &[cur, zero])
+            }
+            // Carry out of bit `n`.
+            Token::ICarry(n) => {
+                let bits = u16::from(n) + 1;
+                let cur = self.mask_to(address, vars.cur, bits);
+                let old = self.mask_to(address, vars.old, bits);
+                self.flag_op(MOpcode::OpLt, address, 1, &[cur, old])
+            }
+            // Borrow from bit `n`.
+            Token::IBorrow(n) => {
+                let bits = u16::from(n);
+                let old = self.mask_to(address, vars.old, bits);
+                let cur = self.mask_to(address, vars.cur, bits);
+                self.flag_op(MOpcode::OpLt, address, 1, &[old, cur])
+            }
+            Token::ISign(_) => {
+                let shift = self.phiplacer.add_const(address, msb, None);
+                let sign = self.flag_op(MOpcode::OpLsr, address, lastsz, &[vars.cur, shift]);
+                self.flag_op(MOpcode::OpNarrow(1), address, 1, &[sign])
+            }
+            // Set if the lowest byte of the result has an even number of bits set, see:
+            //   - https://graphics.stanford.edu/~seander/bithacks.html#ParityWith64Bits
+            Token::IParity(_) => {
+                let mut parity = self.mask_to(address, vars.cur, 8);
+                for (op, c) in vec![
+                    (MOpcode::OpMul, 0x0101_0101_0101_0101),
+                    (MOpcode::OpAnd, 0x8040_2010_0804_0201),
+                    (MOpcode::OpMod, 0x1FF),
+                    (MOpcode::OpAnd, 1),
+                    (MOpcode::OpXor, 1),
+                ] {
+                    let c = self.phiplacer.add_const(address, c, Some(scalar!(64)));
+                    parity = self.flag_op(op, address, 64, &[parity, c]);
+                }
+                self.flag_op(MOpcode::OpNarrow(1), address, 1, &[parity])
+            }
+            // of = ((~old ^ old_) & (cur ^ old)) >> (lastsz - 1)
+            Token::IOverflow(_) => {
+                let old_ = match vars.old_ {
+                    Some(old_) => old_,
+                    None => self.phiplacer.add_const(address, 0, None),
+                };
+                let not_old = self.flag_op(MOpcode::OpNot, address, lastsz, &[vars.old]);
+                let lhs = self.flag_op(MOpcode::OpXor, address, lastsz, &[not_old, old_]);
+                let rhs = self.flag_op(MOpcode::OpXor, address, lastsz, &[vars.cur, vars.old]);
+                let both = self.flag_op(MOpcode::OpAnd, address, lastsz, &[lhs, rhs]);
+                let shift = self.phiplacer.add_const(address, msb, None);
+                let of = self.flag_op(MOpcode::OpLsr, address, lastsz, &[both, shift]);
+                self.flag_op(MOpcode::OpNarrow(1), address, 1, &[of])
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Keeps the lowest `bits` bits of `value`, if it is any wider.
+    fn mask_to(&mut self, address: &mut MAddress, value: T::ValueRef, bits: u16) -> T::ValueRef {
+        let width = self.phiplacer.operand_width(&value);
+        if bits >= width {
+            return value;
+        }
+        let mask = self.phiplacer.add_const(address, (1 << bits) - 1, None);
+        self.flag_op(MOpcode::OpAnd, address, width, &[value, mask])
+    }
+
+    // Fits `value` to the `bits` written by a store, narrowing or zero extending it.
+    fn fit_to(&mut self, address: &mut MAddress, value: T::ValueRef, bits: u16) -> T::ValueRef {
+        let width = self.phiplacer.operand_width(&value);
+        if width == bits {
+            return value;
+        }
+        if let Some(c) = self.phiplacer.constant(&value) {
+            return self.phiplacer.add_sized_const(c, bits);
+        }
+        let op = if width > bits {
+            MOpcode::OpNarrow(bits)
+        } else {
+            MOpcode::OpZeroExt(bits)
+        };
+        let vt = ValueInfo::new_unresolved(ir::WidthSpec::from(bits));
+        let cast = self.phiplacer.add_op(&op, address, vt);
+        self.phiplacer.op_use(&cast, 0, &value);
+        self.phiplacer.propagate_reginfo(&cast);
+        cast
+    }
+
+    fn flag_op(
+        &mut self,
+        op: MOpcode,
+        address: &mut MAddress,
+        width: u16,
+        operands: &[T::ValueRef],
+    ) -> T::ValueRef {
+        let op_node = self.phiplacer.add_op(&op, address, scalar!(width));
+        for (i, operand) in operands.iter().enumerate() {
+            self.phiplacer.op_use(&op_node, i as u8, operand);
+        }
+        op_node
+    }
+
     fn process_out(&mut self, result: Option<T::ValueRef>, _: MAddress) -> Option<Token> {
         // NB 1: Process out is defined for any operation, not only equal as before.
         // Hence, here we should give result a new entry in the "intermediates" table
@@ -204,8 +605,21 @@ where
         let mut lhs = self.process_in(&operands[0], address, Some(op_length));
         let mut rhs = self.process_in(&operands[1], address, Some(op_length));
 
-        self.phiplacer
-            .narrow_const_operand(address, &mut lhs, &mut rhs);
+        self.phiplacer.narrow_const_operand(&mut lhs, &mut rhs);
+
+        // Mirror the parser in keeping track of the ESIL internal vars. Comparisons set them once
+        // their result is known, below. Assigning a flag doesn't change them.
+        if !token.should_set_vars() {
+            self.last_pop = (lhs, rhs);
+        } else if !token.updates_result() && !operands[1].as_ref().map_or(false, Token::is_flag) {
+            if let (Some(old), Some(cur)) = (self.last_pop.0, rhs) {
+                self.esil_vars = Some(EsilVars {
+                    old: old,
+                    old_: self.last_pop.1,
+                    cur: cur,
+                });
+            }
+        }
 
         // Check if the two operands are of compatible sizes for compare
         let lhs_size = lhs.map_or(0, |i| self.phiplacer.operand_width(&i));
@@ -213,6 +627,19 @@ where
 
         let result_size = cmp::max(lhs_size, rhs_size);
 
+        if let Some(value) = self.fold_pc_relative(token, &lhs, &rhs, result_size) {
+            let folded = self.phiplacer.add_const(address, value, None);
+            self.pc_relative.insert(folded);
+            if token.should_set_vars() {
+                self.esil_vars = Some(EsilVars {
+                    old: lhs.expect("lhs cannot be `None`"),
+                    old_: rhs,
+                    cur: folded,
+                });
+            }
+            return Some(folded);
+        }
+
         // Get the radeco Opcode and the output width.
         let (op, vt) = match *token {
             Token::ECmp => {
@@ -244,7 +671,7 @@ where
                         // determine are the ones where the rhs is a constant.
                         if let Some(Token::EConstant(target)) = operands[1] {
                             // Direct/known CF tranfer
-                            let target_addr = MAddress::new(target, 0);
+                            let target_addr = MAddress::segmented(address.segment, target, 0);
                             self.phiplacer.add_block(
                                 target_addr,
                                 Some(*address),
@@ -265,21 +692,40 @@ where
                                 );
                             }
                         }
-                    } else {
-                        // We are writing into a register.
+                    } else if let Some(cond) = self.select_cond {
+                        // `name = cond ? rhs : name`. The value is written first, so that it is
+                        // read back as wide as the register.
+                        let old = self.phiplacer.read_register(address, name);
                         self.phiplacer.write_register(
                             address,
                             name,
                             rhs.expect("rhs for EEq cannot be `None`"),
                         );
+                        let new = self.phiplacer.read_register(address, name);
+                        let vt = ValueInfo::new_scalar(ir::WidthSpec::Known(
+                            self.phiplacer.operand_width(&old),
+                        ));
+                        let select = self.phiplacer.add_select(address, vt, &cond, &new, &old);
+                        self.phiplacer.write_register(address, name, select);
+                    } else {
+                        // We are writing into a register.
+                        let value = rhs.expect("rhs for EEq cannot be `None`");
+                        // The page of an `adrp` is usually computed by r2 already, it is still
+                        // relative to the program counter for the `add` pairing with it.
+                        if self.self_relative && self.phiplacer.is_constant(&value) {
+                            self.pc_relative.insert(value);
+                        }
+                        self.phiplacer.write_register(address, name, value);
                     }
                 } else {
                     // This means that we're performing a memory write. So we need to emit an
                     // OpStore operation.
                     radeco_trace!("Memory Write");
-                    let op_node = self
-                        .phiplacer
-                        .add_op(&MOpcode::OpStore, address, *MEM_VALUEINFO);
+                    let op_node = self.phiplacer.add_op(
+                        &MOpcode::OpStore,
+                        address,
+                        store_valueinfo(rhs_size),
+                    );
                     self.phiplacer.op_use(
                         &op_node,
                         0,
@@ -296,6 +742,10 @@ where
                 return None;
             }
             // Returns None.
+            Token::EIf if self.selecting => {
+                self.select_cond = lhs;
+                return None;
+            }
             Token::EIf => {
                 // Create a new block for true.
                 // The next instruction must be a part of the true block, unless we see an "{"
@@ -309,7 +759,10 @@ where
                 // represented throught the control flow edges of the block to which ITE belongs
                 // to. For clarity, we will add comments to show the same.
                 // Hence: 0 -> compare statement. 1 -> T. 2 -> F.
-                let true_address = MAddress::new(address.address, address.offset + 1);
+                let true_address = MAddress {
+                    offset: address.offset + 1,
+                    ..*address
+                };
                 let _true_block =
                     self.phiplacer
                         .add_block(true_address, Some(*address), Some(TRUE_EDGE));
@@ -375,26 +828,26 @@ where
                 MOpcode::OpMod,
                 ValueInfo::new_scalar(ir::WidthSpec::from(result_size)),
             ),
-            Token::EPoke(_) => {
-                // TODO: rhs has to be cast to size 'n' if it's size is not already n.
+            Token::EPoke(n) => {
+                let value = rhs.expect("rhs cannot be `None`");
+                let value = self.fit_to(address, value, n as u16);
                 let mem_id = self.mem_id();
-                let mem = self.phiplacer.read_variable(address, mem_id);
-                let op_node = self
-                    .phiplacer
-                    .add_op(&MOpcode::OpStore, address, scalar!(0));
+                let mem = self.read_memory(address, mem_id);
+                let op_node =
+                    self.phiplacer
+                        .add_op(&MOpcode::OpStore, address, store_valueinfo(n as u16));
 
                 self.phiplacer.op_use(&op_node, 0, &mem);
                 self.phiplacer
                     .op_use(&op_node, 1, lhs.as_ref().expect("lhs cannot be `None`"));
-                self.phiplacer
-                    .op_use(&op_node, 2, rhs.as_ref().expect("rhs cannot be `None`"));
+                self.phiplacer.op_use(&op_node, 2, &value);
 
-                self.phiplacer
-                    .write_variable(*address, self.mem_id, op_node);
+                self.write_memory(*address, lhs.as_ref(), op_node);
                 return None;
             }
             Token::EPeek(n) => {
-                let mem = self.phiplacer.read_variable(address, self.mem_id);
+                let target = lhs.expect("lhs cannot be `None`");
+                let mem = self.memory_for_load(address, &target);
                 let op_node = self.phiplacer.add_op(
                     &MOpcode::OpLoad,
                     address,
@@ -402,23 +855,43 @@ where
                 );
 
                 self.phiplacer.op_use(&op_node, 0, &mem);
-                self.phiplacer
-                    .op_use(&op_node, 1, lhs.as_ref().expect("lhs cannot be `None`"));
+                self.phiplacer.op_use(&op_node, 1, &target);
                 return Some(op_node);
             }
-            Token::EPop => unreachable!(),
-            Token::EGoto => unimplemented!(),
-            Token::EBreak => unimplemented!(),
+            Token::EElse => {
+                // The false block of the innermost if starts here, rather than at the next
+                // instruction.
+                let (op_node, if_address) = match self.nesting.pop() {
+                    Some(if_) => if_,
+                    None => {
+                        radeco_warn!("Else without an if at {}", address);
+                        return None;
+                    }
+                };
+                self.branch_ends.push(*address);
+                let false_address = MAddress {
+                    offset: address.offset + 1,
+                    ..*address
+                };
+                self.phiplacer
+                    .add_block(false_address, Some(if_address), Some(FALSE_EDGE));
+                let false_comment = self.phiplacer.add_comment(
+                    if_address,
+                    scalar!(0),
+                    format!("F: {}", false_address),
+                );
+                self.phiplacer.op_use(&op_node, 2, &false_comment);
+                return None;
+            }
             Token::EEndIf | Token::ENop => {
+                self.select_cond = None;
                 return None;
             }
-            // Anything else is considered invalid. Log this as a warning and move on.
-            // We may not
-            // want to panic here as we can still achieve a reasonable decompilation
-            // missing just
+            // Anything else is not supported. Log this as a warning and move on. We may not
+            // want to panic here as we can still achieve a reasonable decompilation missing just
             // one or two instructions.
             _ => {
-                unimplemented!();
+                return self.process_unsupported(token, address, &[lhs, rhs]);
             }
         };
 
@@ -450,9 +923,40 @@ where
                 }
                 cmp::Ordering::Equal => (lhs.expect(""), rhs.expect("")),
             };
+            // A commutative operation takes its constant operand second, so that `1 + x` and
+            // `x + 1` are built alike whatever the order they were pushed on the ESIL stack.
+            let (first, second) = if op.is_commutative()
+                && self.phiplacer.is_constant(&lhs)
+                && !self.phiplacer.is_constant(&rhs)
+            {
+                (rhs, lhs)
+            } else {
+                (lhs, rhs)
+            };
             let op_node_ = self.phiplacer.add_op(&op, address, vt);
-            self.phiplacer.op_use(&op_node_, 0, &lhs);
-            self.phiplacer.op_use(&op_node_, 1, &rhs);
+            self.phiplacer.op_use(&op_node_, 0, &first);
+            self.phiplacer.op_use(&op_node_, 1, &second);
+            // An offset from an address is in the same region.
+            if let Token::EAdd | Token::ESub = *token {
+                let base = if self.phiplacer.is_constant(&second) {
+                    Some(first)
+                } else if self.phiplacer.is_constant(&first) {
+                    Some(second)
+                } else {
+                    None
+                };
+                if let Some(region) = base.and_then(|b| self.region_of(&b)) {
+                    self.regions.insert(op_node_, region);
+                }
+            }
+            // The ESIL vars keep the order of the stack, the flags are computed from it.
+            if token.should_set_vars() {
+                self.esil_vars = Some(EsilVars {
+                    old: lhs,
+                    old_: Some(rhs),
+                    cur: op_node_,
+                });
+            }
             Some(op_node_)
         } else {
             // There is only one operand, that is lhs. No need for cast.
@@ -462,6 +966,25 @@ where
         }
     }
 
+    // Stands in for a token which cannot be lifted, so that the rest of the function still is.
+    // The `OpUnsupported` node has side effects, hence it is kept around for the later passes.
+    fn process_unsupported(
+        &mut self,
+        token: &Token,
+        address: &mut MAddress,
+        operands: &[Option<T::ValueRef>],
+    ) -> Option<T::ValueRef> {
+        radeco_warn!("Unsupported ESIL token {:?} at {}", token, address);
+        let name = format!("{:?}", token);
+        let opcode = MOpcode::OpUnsupported(name.clone());
+        let op_node = self.phiplacer.add_op(&opcode, address, scalar!(0));
+        for (i, operand) in operands.iter().flatten().enumerate() {
+            self.phiplacer.op_use(&op_node, i as u8, operand);
+        }
+        self.unsupported.push((address.address, name));
+        None
+    }
+
     fn init_blocks(&mut self) {
         // Create a start block with all registers as variables defined in this block.
         // Seal this block as the start block cannot have any more successors.
@@ -477,12 +1000,19 @@ where
                 .whole_registers
                 .get(i)
                 .expect("This cannot be `None`");
-            // Name the newly created nodes with register names.
+            // The entry value of every register is an argument named after the register.
             let argnode = self
                 .phiplacer
-                .add_comment(start_address, *reg, name.clone());
-            self.phiplacer
-                .write_variable(start_address, i as u64, argnode);
+                .add_argument(start_address, *reg, name.clone());
+            if self.regfile.alias_info.get("SP") == Some(name) {
+                self.regions.insert(argnode, Region::Stack);
+            }
+            if let Err(_e) = self
+                .phiplacer
+                .write_variable(start_address, i as u64, argnode)
+            {
+                radeco_err!("Cannot write the argument {}: {}", name, _e);
+            }
         }
 
         {
@@ -492,9 +1022,7 @@ where
             let mem_comment =
                 self.phiplacer
                     .add_comment(start_address, *MEM_VALUEINFO, "mem".to_owned());
-            let mem_id = self.mem_id();
-            self.phiplacer
-                .write_variable(start_address, mem_id, mem_comment);
+            self.write_memory(start_address, None, mem_comment);
         }
 
         self.phiplacer.sync_register_state(start_block);
@@ -509,19 +1037,15 @@ where
     // ESIL is received, it merely takes this vector of ESIL strings and transforms
     // it into its SSA
     // form.
-    pub fn run(&mut self, op_info: &[LOpInfo]) {
-        let mut p = Parser::init(
-            Some(
-                self.regfile
-                    .named_registers
-                    .iter()
-                    .map(|(n, v)| (n.clone(), v.width as u64))
-                    .collect(),
-            ),
-            Some(64),
-        );
+    /// Fails on the first instruction whose ESIL is malformed, e.g. an operator without enough
+    /// operands. Such an instruction is lifted as an `OpUnsupported` (see `unsupported`) and the
+    /// instructions after it are still lifted, so the SSA covers the whole function anyway.
+    pub fn run(&mut self, op_info: &[LOpInfo]) -> Result<(), ConstructError> {
+        let mut p = self.new_parser();
 
         let mut current_address = MAddress::new(0, 0);
+        let mut error = None;
+        self.timed_out = false;
         self.init_blocks();
         for op in op_info {
             if op.esil.is_none() {
@@ -530,6 +1054,13 @@ where
 
             let offset = op.offset.unwrap_or(0);
 
+            // Give up between instructions, the SSA is closed off below as for the last one.
+            if self.deadline.map_or(false, |d| Instant::now() >= d) {
+                radeco_warn!("SSA construction timed out at {:#x}", offset);
+                self.timed_out = true;
+                break;
+            }
+
             // Get ESIL string
             let esil_str = if let Some(ref esil_str_) = op.esil {
                 esil_str_
@@ -547,6 +1078,10 @@ where
                 self.phiplacer.add_block(next_address, None, None);
             }
 
+            // The false block of an explicit else ends with the previous instruction.
+            if !self.branch_ends.is_empty() {
+                self.branch_ends.push(current_address);
+            }
             current_address.offset = 0;
             self.phiplacer.maybe_add_edge(current_address, next_address);
             current_address = next_address;
@@ -566,16 +1101,53 @@ where
                     .add_block(current_address, Some(src_address), Some(FALSE_EDGE));
                 self.phiplacer.op_use(src_node, 2, &false_comment);
             }
+            // Both blocks of an explicit else join at this instruction, unless they jump
+            // elsewhere.
+            if !self.branch_ends.is_empty() {
+                self.phiplacer.add_block(current_address, None, None);
+                for end in self.branch_ends.drain(..) {
+                    self.phiplacer.maybe_add_edge(end, current_address);
+                }
+            }
 
             radeco_trace!("ssa_construct_esil|{}|{:?}", current_address, esil_str);
 
+            let pc = self.regfile.alias_info.get("PC").map_or("", String::as_str);
+            self.self_relative = op
+                .opcode
+                .as_ref()
+                .and_then(|s| s.split_whitespace().next())
+                .map_or(false, |m| m == "adr" || m == "adrp");
+            self.selecting = is_conditional_assignment(esil_str, pc, self.regfile);
+            self.select_cond = None;
+
+            if let Some((kind, width)) = op.opcode.as_ref().and_then(|s| string_op(s)) {
+                if self.process_string_op(kind, width, &mut current_address) {
+                    continue;
+                }
+            }
+
+            // A trap (e.g. `ud2`) ends its block, which has no successor: nothing after it is
+            // executed. A conditional one is left to the unknown ESIL below.
+            if esil_str.split(",").any(|x| x == "TRAP") && !esil_str.contains("?{") {
+                self.phiplacer.add_trap(current_address);
+                self.phiplacer
+                    .add_op(&MOpcode::OpTrap, &mut current_address, scalar!(0));
+                self.needs_new_block = true;
+                continue;
+            }
+
             // Handle call separately.
             // NOTE: This is a hack.
             {
-                // also handle unknown ESIL this way
+                // also handle unknown ESIL this way, as an `OpUnsupported` clobbering everything
                 let overrides = &["GOTO", "TRAP", "$", "TODO", "REPEAT"];
-                let opt_call_ty = if esil_str.split(",").any(|x| overrides.contains(&x)) {
+                let tail_call = self.tail_calls.contains(&offset);
+                let unknown_esil = esil_str.split(",").any(|x| overrides.contains(&x));
+                let opt_call_ty = if unknown_esil {
                     Some(Cow::Owned(format!("ESIL: {}", esil_str)))
+                } else if tail_call {
+                    Some(Cow::Owned("call".to_owned()))
                 } else if let Some(ref ty) = op.optype {
                     if ty == "call" || ty == "ucall" {
                         Some(Cow::Borrowed(ty))
@@ -598,14 +1170,32 @@ where
                         reference!()
                     };
 
-                    let call_operand = self.phiplacer.add_comment(
-                        current_address,
-                        value_type,
-                        op.opcode.clone().unwrap_or(unknown_str),
-                    );
+                    // An indirect call through a register takes the value of that register as
+                    // its target, so that it can be turned into a direct call once the value is
+                    // known. See `analysis::functions::devirtualize`.
+                    let target_reg = if &*call_ty == "ucall" {
+                        indirect_target(esil_str, pc)
+                            .filter(|r| self.regfile.get_subregister(r).is_some())
+                    } else {
+                        None
+                    };
+
+                    let call_operand = if let Some(reg) = target_reg {
+                        self.phiplacer.read_register(&mut current_address, reg)
+                    } else {
+                        self.phiplacer.add_comment(
+                            current_address,
+                            value_type,
+                            op.opcode.clone().unwrap_or(unknown_str),
+                        )
+                    };
 
                     let opcode = if is_real_call {
                         MOpcode::OpCall
+                    } else if unknown_esil {
+                        radeco_warn!("Unsupported ESIL {:?} at {}", esil_str, current_address);
+                        self.unsupported.push((offset, esil_str.clone()));
+                        MOpcode::OpUnsupported(esil_str.clone())
                     } else {
                         MOpcode::OpCustom(call_ty.into_owned())
                     };
@@ -649,7 +1239,7 @@ where
 
                     // Assume every function call reads from and writes to memory.
                     let mem_id = self.mem_id();
-                    let mem_node = self.phiplacer.read_variable(&mut current_address, mem_id);
+                    let mem_node = self.read_memory(&mut current_address, mem_id);
                     self.phiplacer
                         .op_use(&op_call, (mem_id + 1) as u8, &mem_node);
                     let new_mem_comment = format!("{}@{}", "mem", current_address);
@@ -658,8 +1248,7 @@ where
                         *MEM_VALUEINFO,
                         new_mem_comment,
                     );
-                    self.phiplacer
-                        .write_variable(current_address, mem_id, comment_node);
+                    self.write_memory(current_address, None, comment_node);
                     self.phiplacer.op_use(&comment_node, mem_id as u8, &op_call);
 
                     // If we're using CC, we assume that we know the register that corresponds to
@@ -688,6 +1277,11 @@ where
                     }
 
                     self.phiplacer.op_use(&op_call, 0, &call_operand);
+                    // The callee of a tail call returns to the caller of this function.
+                    if tail_call {
+                        self.phiplacer.add_return(current_address, UNCOND_EDGE);
+                        self.needs_new_block = true;
+                    }
                     continue;
                 }
             }
@@ -722,12 +1316,13 @@ where
             }
             */
 
+            let mut failure = None;
             loop {
                 let token_opt = match p.parse::<_, Tokenizer>(esil_str) {
                     Ok(token_opt_) => token_opt_,
-                    Err(_err) => {
-                        radeco_err!("{}", _err.to_string());
-                        continue;
+                    Err(err) => {
+                        failure = Some(err.to_string());
+                        break;
                     }
                 };
 
@@ -735,11 +1330,23 @@ where
                     radeco_trace!("ssa_construct_token|{}|{:?}", current_address, token);
                     let (lhs, rhs) = match p.fetch_operands(token) {
                         Ok(operands_opt) => operands_opt,
-                        Err(_err) => {
-                            radeco_err!("{}", _err.to_string());
+                        Err(ParserError::Unimplemented) => {
+                            self.process_unsupported(token, &mut current_address, &[]);
+                            current_address.offset += 1;
                             continue;
                         }
+                        Err(err) => {
+                            failure = Some(err.to_string());
+                            break;
+                        }
                     };
+                    let invalid = self
+                        .invalid_operand(&lhs)
+                        .or_else(|| self.invalid_operand(&rhs));
+                    if invalid.is_some() {
+                        failure = invalid;
+                        break;
+                    }
 
                     // Determine what to do with the operands and get the result.
                     let result = self.process_op(
@@ -756,12 +1363,156 @@ where
                     break;
                 }
             }
+            if let Some(reason) = failure {
+                let err = ConstructError {
+                    address: offset,
+                    esil: esil_str.clone(),
+                    reason: reason,
+                };
+                self.skip_malformed(&err, &mut current_address);
+                error = error.or(Some(err));
+                // The parser is left halfway through the instruction.
+                p = self.new_parser();
+            }
         }
         // BUG: The last block may not have the biggest address, which means current_address
         // may be not in the last basic block
         // self.phiplacer.add_edge(current_address, MAddress::new(u64::MAX, 0), UNCOND_EDGE);
         self.phiplacer.gather_exits();
         self.phiplacer.finish(op_info);
+
+        let opcodes = op_info
+            .iter()
+            .filter_map(|op| Some((op.offset?, op.opcode.as_ref()?)))
+            .collect::<HashMap<_, _>>();
+        self.disasm = self
+            .phiplacer
+            .value_addresses()
+            .into_iter()
+            .filter_map(|(node, addr)| Some((node, opcodes.get(&addr.address)?.to_string())))
+            .collect();
+        self.const_writes = self.phiplacer.const_writes();
+
+        error.map_or(Ok(()), Err)
+    }
+
+    fn new_parser(&self) -> Parser {
+        let mut p = Parser::init(
+            Some(
+                self.regfile
+                    .named_registers
+                    .iter()
+                    .map(|(n, v)| (n.clone(), v.width as u64))
+                    .collect(),
+            ),
+            Some(self.phiplacer.word_size() as u64),
+        );
+        // Flags are computed from the operations tracked in `process_op`.
+        p.pass_flags = true;
+        p
+    }
+
+    /// Marks the rest of the malformed instruction `err` with an `OpUnsupported` of its ESIL. What
+    /// was lifted of it is kept.
+    fn skip_malformed(&mut self, err: &ConstructError, address: &mut MAddress) {
+        radeco_warn!("{}", err);
+        let opcode = MOpcode::OpUnsupported(err.esil.clone());
+        self.phiplacer.add_op(&opcode, address, scalar!(0));
+        self.unsupported.push((err.address, err.esil.clone()));
+        address.offset += 1;
+    }
+
+    /// Lifts the string instruction `kind`, on elements of `width` bits, as an intrinsic. The
+    /// pointers and the count are updated as the instruction leaves them, assuming the direction
+    /// flag is clear. Returns `false` if the registers of the instruction are not known, in which
+    /// case its ESIL is lifted instead.
+    fn process_string_op(&mut self, kind: StringOp, width: u16, address: &mut MAddress) -> bool {
+        let word = self.phiplacer.word_size();
+        let (di, si, cx) = match word {
+            64 => ("rdi", "rsi", "rcx"),
+            32 => ("edi", "esi", "ecx"),
+            _ => return false,
+        };
+        let acc = match width {
+            8 => "al",
+            16 => "ax",
+            32 => "eax",
+            _ => "rax",
+        };
+        let known = [di, si, cx, acc, "zf"]
+            .iter()
+            .all(|r| self.regfile.get_subregister(r).is_some());
+        if !known {
+            return false;
+        }
+
+        let dst = self.phiplacer.read_register(address, di);
+        let src = match kind {
+            StringOp::Movs => self.phiplacer.read_register(address, si),
+            StringOp::Stos | StringOp::Scas => self.phiplacer.read_register(address, acc),
+        };
+        let count = self.phiplacer.read_register(address, cx);
+        let mem = self.memory_for_load(address, &dst);
+        let (opcode, vt) = match kind {
+            StringOp::Movs => (MOpcode::OpRepMovs(width), *MEM_VALUEINFO),
+            StringOp::Stos => (MOpcode::OpRepStos(width), *MEM_VALUEINFO),
+            StringOp::Scas => (MOpcode::OpRepScas(width), scalar!(word)),
+        };
+        let string_op = self.phiplacer.add_op(&opcode, address, vt);
+        for (i, operand) in [mem, dst, src, count].iter().enumerate() {
+            self.phiplacer.op_use(&string_op, i as u8, operand);
+        }
+
+        // Number of elements gone through
+        let elements = if kind == StringOp::Scas {
+            string_op
+        } else {
+            self.write_memory(*address, Some(&dst), string_op);
+            count
+        };
+        let size = self
+            .phiplacer
+            .add_const(address, u64::from(width / 8), Some(scalar!(word)));
+        let bytes = self.flag_op(MOpcode::OpMul, address, word, &[elements, size]);
+        let next_dst = self.flag_op(MOpcode::OpAdd, address, word, &[dst, bytes]);
+        self.phiplacer.write_register(address, di, next_dst);
+        if kind == StringOp::Movs {
+            let next_src = self.flag_op(MOpcode::OpAdd, address, word, &[src, bytes]);
+            self.phiplacer.write_register(address, si, next_src);
+        }
+        let left = if kind == StringOp::Scas {
+            self.flag_op(MOpcode::OpSub, address, word, &[count, string_op])
+        } else {
+            self.phiplacer.add_const(address, 0, Some(scalar!(word)))
+        };
+        self.phiplacer.write_register(address, cx, left);
+
+        // `zf` tells whether the last element read is the one looked for.
+        if kind == StringOp::Scas {
+            let last = self.flag_op(MOpcode::OpSub, address, word, &[next_dst, size]);
+            let load = self
+                .phiplacer
+                .add_op(&MOpcode::OpLoad, address, scalar!(width));
+            self.phiplacer.op_use(&load, 0, &mem);
+            self.phiplacer.op_use(&load, 1, &last);
+            let found = self.flag_op(MOpcode::OpEq, address, 1, &[load, src]);
+            self.phiplacer.write_register(address, "zf", found);
+        }
+        true
+    }
+
+    /// Tells why `operand` can't be processed by `process_in`, if it can't.
+    fn invalid_operand(&self, operand: &Option<Token>) -> Option<String> {
+        match *operand {
+            None
+            | Some(Token::ERegister(_))
+            | Some(Token::EIdentifier(_))
+            | Some(Token::EConstant(_))
+            | Some(Token::EAddress) => None,
+            Some(Token::EEntry(id, _)) if id < self.intermediates.len() => None,
+            Some(ref flag) if flag.is_flag() => None,
+            Some(ref token) => Some(format!("{:?} is not an operand", token)),
+        }
     }
 
     #[allow(dead_code)]
@@ -773,6 +1524,8 @@ where
         disp: i64,
         addr: &mut MAddress,
     ) -> T::ValueRef {
+        // The address is computed at the native word size.
+        let vt = ValueInfo::new_scalar(ir::WidthSpec::Known(self.phiplacer.word_size()));
         let base_node = if let Some(ref reg) = *base {
             self.process_in(&Some(Token::ERegister(reg.clone())), addr, None)
         } else {
@@ -785,8 +1538,6 @@ where
             let reg_node = self
                 .process_in(&Some(Token::ERegister(reg.clone())), addr, None)
                 .expect("Invalid op");
-            // TODO: s/64/default op size/
-            let vt = ValueInfo::new_scalar(ir::WidthSpec::Known(64));
             let mult_node = self.phiplacer.add_op(&MOpcode::OpMul, addr, vt);
             // Make a node for the scale
             let scale_node = self.phiplacer.add_const(addr, scale as u64, None);
@@ -809,7 +1560,6 @@ where
             res = match (res, *nd) {
                 (Some(ref o1), Some(ref o2)) => {
                     // Join by a '+'
-                    let vt = ValueInfo::new_scalar(ir::WidthSpec::Known(64));
                     let add_node = self.phiplacer.add_op(&MOpcode::OpAdd, addr, vt);
                     self.phiplacer.op_use(&add_node, 0, o1);
                     self.phiplacer.op_use(&add_node, 1, o2);
@@ -926,23 +1676,25 @@ mod test {
     use crate::analysis::analyzer::{all, FuncAnalyzer};
     use crate::analysis::dce::DCE;
     use crate::analysis::sccp::SCCP;
+    use crate::frontend::llanalyzer;
     use crate::middle::dot;
     use crate::middle::ir_writer;
+    use crate::middle::ssa::cfg_traits::CFG;
+    use crate::middle::ssa::ssa_traits::{SSAWalk, SSA};
+    use crate::middle::ssa::ssastorage::SSAStorage;
+    use crate::middle::ssa::utils;
+    use crate::utils::test_support::{self, reg_profile};
     use r2papi::structs::{LFunctionInfo, LRegInfo};
     use serde_json;
     use std::fs::File;
     use std::io::prelude::*;
     use std::sync::Arc;
-
-    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+    use std::time::Duration;
 
     fn before_test(reg_profile: &mut LRegInfo, instructions: &mut LFunctionInfo, from: &str) {
         // Enable for debugging only.
         // enable_logging!();
-        let mut register_profile = File::open(REGISTER_PROFILE).unwrap();
-        let mut s = String::new();
-        register_profile.read_to_string(&mut s).unwrap();
-        *reg_profile = serde_json::from_str(&*s).unwrap();
+        *reg_profile = test_support::reg_profile();
         let mut instruction_file = File::open(from).unwrap();
         let mut s = String::new();
         instruction_file.read_to_string(&mut s).unwrap();
@@ -961,9 +1713,11 @@ mod test {
         let mut rfn = RadecoFunction::default();
 
         {
-            let regfile = SubRegisterFile::new(&reg_profile);
+            let regfile = SubRegisterFile::new(&reg_profile).unwrap();
             let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor
+                .run(instructions.ops.unwrap().as_slice())
+                .unwrap();
         }
 
         let mut dce = DCE::new();
@@ -986,9 +1740,11 @@ mod test {
         let mut rfn = RadecoFunction::default();
 
         {
-            let regfile = SubRegisterFile::new(&reg_profile);
+            let regfile = SubRegisterFile::new(&reg_profile).unwrap();
             let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor
+                .run(instructions.ops.unwrap().as_slice())
+                .unwrap();
         }
 
         let mut dce = DCE::new();
@@ -1004,6 +1760,352 @@ mod test {
         f.write_all(tmp.as_bytes()).expect("Write failed!");
     }
 
+    #[test]
+    fn store_carries_access_width() {
+        let reg_profile = reg_profile();
+        // mov dword [rdi], esi; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"esi,rdi,=[4]","offset":4096,"opcode":"mov dword [rdi], esi","type":"mov","size":2},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4098,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+
+        {
+            let regfile = Arc::new(SubRegisterFile::new(&reg_profile).unwrap());
+            rfn.ssa_mut().regfile = regfile.clone();
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &*regfile);
+            constructor.run(ops.as_slice()).unwrap();
+        }
+
+        let ssa = rfn.ssa();
+        let store = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpStore))
+            .expect("No store emitted");
+        assert_eq!(
+            ssa.node_data(store).unwrap().vt.width(),
+            &ir::WidthSpec::Known(32)
+        );
+    }
+
+    #[test]
+    fn nodes_map_to_disasm() {
+        let reg_profile = reg_profile();
+        // mov eax, 1; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"1,rax,=","offset":4096,"opcode":"mov eax, 1","type":"mov","size":5},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4101,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let mov = ssa
+            .inorder_walk()
+            .find(|&n| ssa.address(n).map(|a| a.address) == Some(0x1000))
+            .expect("Nothing lifted from the mov");
+        assert_eq!(rfn.disasm_of(mov), Some("mov eax, 1"));
+
+        let mut il = String::new();
+        ir_writer::emit_il_with_disasm(&mut il, None, ssa, rfn.disasm().unwrap()).unwrap();
+        assert!(il.contains("; // mov eax, 1"));
+    }
+
+    #[test]
+    fn zero_flag_compares_with_zero() {
+        let reg_profile = reg_profile();
+        // cmp rbx, rax; je 0x1010; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"rax,rbx,==,$z,zf,=,$b64,cf,=,$p,pf,=,$s,sf,=,$o,of,=","offset":4096,"opcode":"cmp rbx, rax","type":"cmp","size":3},
+                {"esil":"zf,?{,0x1010,rip,=,}","offset":4099,"opcode":"je 0x1010","type":"cjmp","size":2},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4101,"opcode":"ret","type":"ret","size":1},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4112,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let zf = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpEq))
+            .expect("No zero comparison emitted for `$z`");
+        let ops = ssa.operands_of(zf);
+        assert_eq!(ssa.opcode(ops[0]), Some(MOpcode::OpSub));
+        assert_eq!(ssa.constant_value(ops[1]), Some(0));
+    }
+
+    #[test]
+    fn unsupported_esil_is_kept() {
+        let reg_profile = reg_profile();
+        // mov eax, 1; int3; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"1,rax,=,STACK","offset":4096,"opcode":"mov eax, 1","type":"mov","size":5},
+                {"esil":"BREAK","offset":4101,"opcode":"int3","type":"trap","size":1},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4102,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+        assert_eq!(
+            rfn.unsupported(),
+            &[(0x1000, "EDump".to_owned()), (0x1005, "EBreak".to_owned())]
+        );
+
+        DCE::new().analyze(&mut rfn, Some(all));
+        let ssa = rfn.ssa();
+        let unsupported = ssa
+            .inorder_walk()
+            .filter_map(|n| match ssa.opcode(n) {
+                Some(MOpcode::OpUnsupported(name)) => Some(name),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(unsupported, vec!["EDump", "EBreak"]);
+    }
+
+    #[test]
+    fn trap_ends_block() {
+        let reg_profile = reg_profile();
+        // mov eax, 1; ud2; ret
+        let ops = r#"[{"esil":"1,rax,=","offset":4096,"opcode":"mov eax, 1","type":"mov","size":5},
+                {"esil":"TRAP","offset":4101,"opcode":"ud2","type":"trap","size":2},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4103,"opcode":"ret","type":"ret","size":1}]"#;
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = serde_json::from_str(ops).unwrap();
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+        assert!(rfn.unsupported().is_empty());
+
+        DCE::new().analyze(&mut rfn, Some(all));
+        let ssa = rfn.ssa();
+        let trap = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpTrap))
+            .expect("No trap emitted for `ud2`");
+        // Neither the `ret` nor the exit node follows the trap.
+        let block = ssa.block_for(trap).unwrap();
+        assert!(ssa.succs_of(block).is_empty());
+        // Hence the write of rax never reaches the exit.
+        assert_eq!(exit_constant(ops, &reg_profile, "rax"), None);
+    }
+
+    #[test]
+    fn indirect_call_is_a_call() {
+        let reg_profile = reg_profile();
+        // call rax; ret
+        let ops = r#"[{"esil":"rip,8,rsp,-=,rsp,=[8],rax,rip,=","offset":4096,"opcode":"call rax","type":"ucall","size":2},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4098,"opcode":"ret","type":"ret","size":1}]"#;
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = serde_json::from_str(ops).unwrap();
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let call = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpCall))
+            .expect("No call emitted for `call rax`");
+        let target = utils::call_info(call, ssa).unwrap().target;
+        assert_eq!(target, test_support::entry_reg(ssa, "rax"));
+        assert!(llanalyzer::analyze_callsite_initial(&rfn).contains_key(&0x1000));
+
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, None, ssa).unwrap();
+        assert!(il.contains("= CALL ("));
+    }
+
+    #[test]
+    fn explicit_else() {
+        let reg_profile = reg_profile();
+        // rax = zf ? 1 : 2; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"zf,?{,1,rax,=,}{,2,rax,=,}","offset":4096,"opcode":"sel","type":"mov","size":3},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4099,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let branch = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(0x1000, 0)))
+            .expect("No block for the instruction");
+        let sides = ssa.succs_of(branch);
+        assert_eq!(sides.len(), 2);
+        // Both sides are blocks of the instruction, and both go on with the `ret`.
+        let join = MAddress::new(0x1003, 0);
+        for &side in &sides {
+            assert_eq!(ssa.starting_address(side).unwrap().address, 0x1000);
+            let succs = ssa.succs_of(side);
+            assert_eq!(succs.len(), 1);
+            assert_eq!(ssa.starting_address(succs[0]), Some(join));
+        }
+        assert_ne!(sides[0], sides[1]);
+
+        // rax is merged at the join.
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let rax = ssa.regfile.register_id_by_name("rax").unwrap();
+        let (value, _) = utils::register_state_info(exit, ssa)[rax];
+        assert!(ssa.is_phi(value));
+        // Each side moves its constant into rax.
+        let mut merged = ssa
+            .operands_of(value)
+            .into_iter()
+            .filter_map(|op| ssa.operands_of(op).first().and_then(|&c| ssa.constant(c)))
+            .collect::<Vec<_>>();
+        merged.sort();
+        assert_eq!(merged, vec![1, 2]);
+    }
+
+    #[test]
+    fn malformed_esil_is_an_error() {
+        let reg_profile = reg_profile();
+        // The addition at 0x1005 lacks an operand.
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"1,rax,+=","offset":4096,"opcode":"add rax, 1","type":"add","size":5},
+                {"esil":"rax,+","offset":4101,"opcode":"invalid","type":"add","size":3},
+                {"esil":"2,rbx,+=","offset":4104,"opcode":"add rbx, 2","type":"add","size":5}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        let err = SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ConstructError {
+                address: 0x1005,
+                esil: "rax,+".to_owned(),
+                reason: "Insufficient operands!".to_owned(),
+            }
+        );
+
+        // The malformed instruction is kept as unsupported, and the instructions around it are
+        // still lifted.
+        assert_eq!(rfn.unsupported(), &[(0x1005, "rax,+".to_owned())]);
+        let ssa = rfn.ssa();
+        assert!(ssa.entry_node().is_some() && ssa.exit_node().is_some());
+        let addresses = ssa
+            .inorder_walk()
+            .filter_map(|n| ssa.address(n).map(|a| a.address))
+            .collect::<Vec<_>>();
+        assert!(addresses.contains(&0x1000));
+        assert!(addresses.contains(&0x1008));
+        assert!(ssa
+            .inorder_walk()
+            .any(|n| ssa.opcode(n) == Some(MOpcode::OpUnsupported("rax,+".to_owned()))));
+    }
+
+    #[test]
+    fn split_block_redefines_intermediates() {
+        let reg_profile = reg_profile();
+        // The zero flag set at 0x1003 is computed from the 32-bit addition at 0x1000, whose result
+        // is zero extended into rax. The loop back to 0x1003 splits the block in between.
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"1,eax,+=","offset":4096,"opcode":"add eax, 1","type":"add","size":3},
+                {"esil":"$z,zf,=,1,eax,+=","offset":4099,"opcode":"step","type":"add","size":2},
+                {"esil":"zf,?{,0x1003,rip,=,}","offset":4101,"opcode":"je 0x1003","type":"cjmp","size":2},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4103,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let block_at = |addr| {
+            ssa.blocks()
+                .into_iter()
+                .find(|&b| ssa.starting_address(b) == Some(MAddress::new(addr, 0)))
+                .unwrap()
+        };
+        let (upper, lower) = (block_at(0x1000), block_at(0x1003));
+        for node in ssa.exprs_in(lower) {
+            for operand in ssa.operands_of(node) {
+                assert_ne!(ssa.block_for(operand), Some(upper));
+            }
+        }
+
+        let zf = ssa
+            .exprs_in(lower)
+            .into_iter()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpEq))
+            .expect("No zero comparison emitted for `$z`");
+        let add = ssa.operands_of(zf)[0];
+        assert_eq!(ssa.opcode(add), Some(MOpcode::OpAdd));
+        assert_eq!(ssa.block_for(add), Some(lower));
+        // eax is read again from the phi of rax.
+        assert!(ssa.operands_of(add).into_iter().any(|n| {
+            ssa.opcode(n) == Some(MOpcode::OpNarrow(32)) && ssa.is_phi(ssa.operands_of(n)[0])
+        }));
+    }
+
+    #[test]
+    fn dup_reuses_node() {
+        let reg_profile = reg_profile();
+        // rcx = (rax + rbx) * (rax + rbx); ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"rbx,rax,+,DUP,*,rcx,=","offset":4096,"opcode":"square","type":"mul","size":4},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4100,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+        assert!(rfn.unsupported().is_empty());
+
+        let ssa = rfn.ssa();
+        let mul = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpMul))
+            .expect("No multiplication emitted");
+        let ops = ssa.operands_of(mul);
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[0], ops[1]);
+        assert_eq!(ssa.opcode(ops[0]), Some(MOpcode::OpAdd));
+    }
+
+    #[test]
+    fn emit_is_deterministic() {
+        let mut reg_profile = Default::default();
+        let mut instructions: LFunctionInfo = Default::default();
+        before_test(
+            &mut reg_profile,
+            &mut instructions,
+            "test_files/ct1_instructions.json",
+        );
+        let ops = instructions.ops.unwrap();
+
+        // Every `HashMap` gets its own random seed, so constructing twice in the same process is
+        // enough to catch an order leaking out of one.
+        let emit = || {
+            let mut rfn = RadecoFunction::default();
+            {
+                let regfile = Arc::new(SubRegisterFile::new(&reg_profile).unwrap());
+                rfn.ssa_mut().regfile = regfile.clone();
+                let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &*regfile);
+                constructor.run(ops.as_slice()).unwrap();
+            }
+            let mut il = String::new();
+            ir_writer::emit_il(&mut il, Some("main".to_owned()), rfn.ssa()).unwrap();
+            (il, dot::emit_dot(rfn.ssa()))
+        };
+
+        let (il, dot) = emit();
+        for _ in 0..4 {
+            let (il_again, dot_again) = emit();
+            assert_eq!(il, il_again);
+            assert_eq!(dot, dot_again);
+        }
+    }
+
     #[test]
     fn ssa_bfs_walk() {
         let mut reg_profile = Default::default();
@@ -1016,10 +2118,12 @@ mod test {
         let mut rfn = RadecoFunction::default();
 
         {
-            let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+            let regfile = Arc::new(SubRegisterFile::new(&reg_profile).unwrap());
             rfn.ssa_mut().regfile = regfile.clone();
             let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &*regfile);
-            constructor.run(instructions.ops.unwrap().as_slice());
+            constructor
+                .run(instructions.ops.unwrap().as_slice())
+                .unwrap();
         }
 
         let mut dce = DCE::new();
@@ -1040,9 +2144,609 @@ mod test {
         ir_writer::emit_il(&mut il, Some("main".to_owned()), rfn.ssa()).unwrap();
         println!("{}", il);
     }
+
+    // Minimal profiles, standing in for the Thumb and the AArch64 parts of a binary.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const THUMB_PROFILE: &str = r#"{
+        "alias_info": [{"role": 0, "role_str": "PC", "reg": "pc"}],
+        "reg_info": [
+            {"type": 0, "type_str": "gpr", "name": "r0", "size": 32, "offset": 0},
+            {"type": 0, "type_str": "gpr", "name": "r1", "size": 32, "offset": 32},
+            {"type": 0, "type_str": "gpr", "name": "pc", "size": 32, "offset": 64}
+        ]
+    }"#;
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const AARCH64_PROFILE: &str = r#"{
+        "alias_info": [{"role": 0, "role_str": "PC", "reg": "pc"}],
+        "reg_info": [
+            {"type": 0, "type_str": "gpr", "name": "x0", "size": 64, "offset": 0},
+            {"type": 0, "type_str": "gpr", "name": "w0", "size": 32, "offset": 0},
+            {"type": 0, "type_str": "gpr", "name": "pc", "size": 64, "offset": 64}
+        ]
+    }"#;
+
+    #[test]
+    fn profile_by_address_range() {
+        let mut x86_profile = Default::default();
+        let mut instructions = Default::default();
+        before_test(
+            &mut x86_profile,
+            &mut instructions,
+            "test_files/tiny_sccp_test_instructions.json",
+        );
+        let x86 = SubRegisterFile::new(&x86_profile).unwrap();
+        let thumb: LRegInfo = serde_json::from_str(THUMB_PROFILE).unwrap();
+        let aarch64: LRegInfo = serde_json::from_str(AARCH64_PROFILE).unwrap();
+        let profiles = [
+            (
+                0x1000..0x2000,
+                Arc::new(SubRegisterFile::new(&thumb).unwrap()),
+            ),
+            (
+                0x2000..0x3000,
+                Arc::new(SubRegisterFile::new(&aarch64).unwrap()),
+            ),
+        ];
+
+        let cases = [
+            (0x1000, vec!["r0", "r1", "pc"]),
+            (0x2ffe, vec!["x0", "pc"]),
+            // Outside of the ranges, the global profile is used.
+            (0x3000, x86.whole_names.iter().map(|s| s.as_str()).collect()),
+        ];
+        for (offset, expected) in cases.iter() {
+            let mut rfn = RadecoFunction::default();
+            rfn.offset = *offset;
+            SSAConstruct::<SSAStorage>::construct(&mut rfn, &x86_profile, &profiles, false, true)
+                .unwrap();
+            assert_eq!(&rfn.ssa().regfile.whole_names, expected);
+
+            // The entry node defines exactly the registers of the profile.
+            let ssa = rfn.ssa();
+            let regs = ssa.registers_in(ssa.entry_node().unwrap()).unwrap();
+            let names = ssa
+                .operands_of(regs)
+                .into_iter()
+                .filter_map(|n| ssa.argument(n))
+                .collect::<Vec<_>>();
+            assert_eq!(&names, expected);
+        }
+    }
+
+    #[test]
+    fn default_width_from_profile() {
+        let thumb: LRegInfo = serde_json::from_str(THUMB_PROFILE).unwrap();
+        assert_eq!(SubRegisterFile::new(&thumb).unwrap().word_size(), Some(32));
+
+        // movs r0, 1; adds r1, r1, r0
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"1,r0,=","offset":4096,"opcode":"movs r0, 1","type":"mov","size":2},
+                {"esil":"r0,r1,+,r1,=","offset":4098,"opcode":"adds r1, r1, r0","type":"add","size":2}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.offset = 0x1000;
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &thumb, &[], false, true).unwrap();
+
+        // Constants and operations without an explicit width take the width of the program
+        // counter rather than 64 bits.
+        let ssa = rfn.ssa();
+        let width = |n| ssa.node_data(n).unwrap().vt.width().get_width();
+        let consts = ssa.constants();
+        assert!(!consts.is_empty());
+        for &node in consts.keys() {
+            assert_eq!(width(node), Some(32));
+        }
+        let add = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpAdd))
+            .expect("No addition emitted");
+        assert_eq!(width(add), Some(32));
+    }
+
+    // Constructs `ops` and returns the constant held by `reg` on exit, if any. The constant is
+    // moved into the register.
+    fn exit_constant(ops: &str, reg_profile: &LRegInfo, reg: &str) -> Option<u64> {
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = serde_json::from_str(ops).unwrap();
+        rfn.offset = rfn.instructions[0].offset.unwrap();
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, reg_profile, &[], false, true).unwrap();
+        let ssa = rfn.ssa();
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let id = ssa.regfile.register_id_by_name(reg).unwrap();
+        let (value, _) = utils::register_state_info(exit, ssa)[id];
+        if ssa.opcode(value) != Some(MOpcode::OpMov) {
+            return None;
+        }
+        ssa.constant(ssa.operands_of(value)[0])
+    }
+
+    #[test]
+    fn pc_relative_address() {
+        let x86 = reg_profile();
+
+        // lea rax, [rip + 0x2000]: relative to the next instruction.
+        let lea = r#"[{"esil":"0x2000,rip,+,rax,=","offset":4096,"opcode":"lea rax, [rip + 0x2000]","type":"lea","size":7},
+                      {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4103,"opcode":"ret","type":"ret","size":1}]"#;
+        assert_eq!(exit_constant(lea, &x86, "rax"), Some(0x3007));
+
+        // adrp x0, 0x3000: relative to its own page, even at the end of it.
+        let aarch64: LRegInfo = serde_json::from_str(AARCH64_PROFILE).unwrap();
+        let adrp = r#"[{"esil":"0xfffffffffffff000,pc,&,0x1000,+,x0,=","offset":12284,"opcode":"adrp x0, 0x3000","type":"lea","size":4},
+                       {"esil":"lr,pc,=","offset":12288,"opcode":"ret","type":"ret","size":4}]"#;
+        assert_eq!(exit_constant(adrp, &aarch64, "x0"), Some(0x3000));
+    }
+
+    #[test]
+    fn split_memory_regions() {
+        let reg_profile = reg_profile();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile).unwrap());
+
+        // mov [rsp - 8], rax; mov rbx, [0x601040]; mov rcx, [rsp - 8]
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"rax,8,rsp,-,=[8]","offset":4096,"opcode":"mov","type":"mov","size":5},
+                {"esil":"0x601040,[8],rbx,=","offset":4101,"opcode":"mov","type":"mov","size":8},
+                {"esil":"8,rsp,-,[8],rcx,=","offset":4109,"opcode":"mov","type":"mov","size":5},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4114,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+
+        // Returns the memory states the loads from the global and from the stack go through,
+        // along with the store.
+        let construct = |split: bool| {
+            let mut rfn = RadecoFunction::default();
+            rfn.ssa_mut().regfile = regfile.clone();
+            {
+                let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &*regfile);
+                if split {
+                    constructor.split_memory();
+                }
+                constructor.run(&ops).unwrap();
+            }
+            let ssa = rfn.ssa();
+            let find = |opcode: MOpcode| {
+                ssa.inorder_walk()
+                    .filter(|&n| ssa.opcode(n) == Some(opcode.clone()))
+                    .collect::<Vec<_>>()
+            };
+            let store = find(MOpcode::OpStore)[0];
+            let (global, stack): (Vec<_>, Vec<_>) = find(MOpcode::OpLoad)
+                .into_iter()
+                .partition(|&n| ssa.constant(ssa.operands_of(n)[1]).is_some());
+            let mem = |load| ssa.operands_of(load)[0];
+            (mem(global[0]), mem(stack[0]), store)
+        };
+
+        let (global, stack, store) = construct(false);
+        assert_eq!(global, store);
+        assert_eq!(stack, store);
+
+        // The store to the stack is not on the chain of the global load.
+        let (global, stack, store) = construct(true);
+        assert_ne!(global, store);
+        assert_eq!(stack, store);
+    }
+
+    #[test]
+    fn memory_access_width() {
+        let reg_profile = reg_profile();
+
+        for &(bytes, bits, constant) in &[(1, 8, 0x34), (4, 32, 0x1234), (8, 64, 0x1234)] {
+            // Loads from `[rax]` and stores `rcx` and a constant to `[rdx]`.
+            let ops: Vec<LOpInfo> = serde_json::from_str(&format!(
+                r#"[{{"esil":"rax,[{0}],rbx,=","offset":4096,"opcode":"mov","type":"mov","size":3}},
+                    {{"esil":"rcx,rdx,=[{0}]","offset":4099,"opcode":"mov","type":"mov","size":3}},
+                    {{"esil":"0x1234,rdx,=[{0}]","offset":4102,"opcode":"mov","type":"mov","size":3}},
+                    {{"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4105,"opcode":"ret","type":"ret","size":1}}]"#,
+                bytes
+            ))
+            .unwrap();
+            let mut rfn = RadecoFunction::default();
+            rfn.instructions = ops;
+            SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true)
+                .unwrap();
+            let ssa = rfn.ssa();
+            let width = |n| ssa.node_data(n).unwrap().vt.width().get_width();
+
+            let loads = ssa
+                .inorder_walk()
+                .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpLoad))
+                .collect::<Vec<_>>();
+            assert_eq!(loads.len(), 1);
+            assert_eq!(width(loads[0]), Some(bits));
+
+            let stores = ssa
+                .inorder_walk()
+                .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpStore))
+                .collect::<Vec<_>>();
+            assert_eq!(stores.len(), 2);
+            for &store in &stores {
+                assert_eq!(width(store), Some(bits));
+                // The stored value is as wide as the access.
+                let value = ssa.operands_of(store)[2];
+                assert_eq!(width(value), Some(bits));
+            }
+            // The constant is truncated to the access.
+            let constants = stores
+                .iter()
+                .filter_map(|&n| ssa.constant(ssa.operands_of(n)[2]))
+                .collect::<Vec<_>>();
+            assert_eq!(constants, vec![constant]);
+        }
+    }
+
+    #[test]
+    fn write_clears_upper() {
+        let reg_profile = reg_profile();
+        let regfile = SubRegisterFile::new(&reg_profile).unwrap();
+        assert!(regfile.get_subregister("eax").unwrap().clears_upper);
+        assert!(!regfile.get_subregister("ax").unwrap().clears_upper);
+        assert!(!regfile.get_subregister("al").unwrap().clears_upper);
+
+        // Constructs `esil` followed by a return, and returns the function with the value of
+        // `rax` on exit.
+        let construct = |esil: &str| {
+            let ops: Vec<LOpInfo> = serde_json::from_str(&format!(
+                r#"[{{"esil":"{}","offset":4096,"opcode":"mov","type":"mov","size":3}},
+                    {{"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4099,"opcode":"ret","type":"ret","size":1}}]"#,
+                esil
+            ))
+            .unwrap();
+            let mut rfn = RadecoFunction::default();
+            rfn.instructions = ops;
+            SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true)
+                .unwrap();
+            let ssa = rfn.ssa();
+            let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+            let rax = ssa.regfile.register_id_by_name("rax").unwrap();
+            let (value, _) = utils::register_state_info(exit, ssa)[rax];
+            (rfn, value)
+        };
+
+        // mov eax, ebx: `rax` is `ebx` zero extended.
+        let (rfn, rax) = construct("ebx,eax,=");
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(rax), Some(MOpcode::OpZeroExt(64)));
+        let ebx = ssa.operands_of(rax)[0];
+        assert_eq!(ssa.opcode(ebx), Some(MOpcode::OpNarrow(32)));
+
+        // mov ax, bx: the upper bits of `rax` are merged back.
+        let (rfn, rax) = construct("bx,ax,=");
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(rax), Some(MOpcode::OpOr));
+        assert!(ssa
+            .operands_of(rax)
+            .into_iter()
+            .any(|n| ssa.opcode(n) == Some(MOpcode::OpAnd)));
+    }
+
+    #[test]
+    fn entry_values_are_arguments() {
+        let reg_profile = reg_profile();
+        // mov rax, rdi; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"rdi,rax,=","offset":4096,"opcode":"mov rax, rdi","type":"mov","size":3},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4099,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let entry = ssa.entry_node().unwrap();
+        let in_entry = ssa
+            .values()
+            .into_iter()
+            .filter(|&n| ssa.block_for(n) == Some(entry))
+            .collect::<Vec<_>>();
+        let args = in_entry
+            .iter()
+            .filter_map(|&n| ssa.argument(n))
+            .collect::<Vec<_>>();
+        assert_eq!(args, ssa.regfile.whole_names);
+        // Only the memory is left a comment.
+        let comments = in_entry
+            .iter()
+            .filter_map(|&n| ssa.comment(n))
+            .collect::<Vec<_>>();
+        assert_eq!(comments, vec!["mem".to_owned()]);
+
+        // The register name annotation is kept.
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let rax = ssa.regfile.register_id_by_name("rax").unwrap();
+        let (value, _) = utils::register_state_info(exit, ssa)[rax];
+        let rdi = ssa.operands_of(value)[0];
+        assert!(ssa.is_argument(rdi));
+        assert_eq!(ssa.registers(rdi), vec!["rdi".to_owned()]);
+    }
+
+    #[test]
+    fn rep_movs_is_intrinsic() {
+        let reg_profile = reg_profile();
+        // rep movsb byte [rdi], byte ptr [rsi]; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"rcx,!,?{,BREAK,},rsi,[1],rdi,=[1],df,?{,1,rdi,-=,1,rsi,-=,},df,!,?{,1,rdi,+=,1,rsi,+=,},rcx,--=,rcx,?{,4096,GOTO,}","offset":4096,"opcode":"rep movsb byte [rdi], byte ptr [rsi]","type":"mov","size":2},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4098,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let movs = ssa
+            .inorder_walk()
+            .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpRepMovs(8)))
+            .collect::<Vec<_>>();
+        assert_eq!(movs.len(), 1);
+        let operands = ssa.operands_of(movs[0]);
+        assert_eq!(operands.len(), 4);
+        assert_eq!(ssa.comment(operands[0]), Some("mem".to_owned()));
+        let regs = operands[1..]
+            .iter()
+            .map(|&n| ssa.argument(n))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            regs,
+            vec![
+                Some("rdi".to_owned()),
+                Some("rsi".to_owned()),
+                Some("rcx".to_owned())
+            ]
+        );
+        // The loop of the ESIL is not lifted.
+        assert!(ssa.inorder_walk().all(|n| match ssa.opcode(n) {
+            Some(MOpcode::OpCustom(_)) => false,
+            _ => true,
+        }));
+
+        // The copy is the memory at the exit, and 0 is moved into rcx.
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let state = utils::register_state_info(exit, ssa);
+        let rcx = ssa.regfile.register_id_by_name("rcx").unwrap();
+        assert_eq!(ssa.opcode(state[rcx].0), Some(MOpcode::OpMov));
+        assert_eq!(ssa.constant(ssa.operands_of(state[rcx].0)[0]), Some(0));
+        let mem = ssa.regfile.mem_id();
+        assert_eq!(state[mem].0, movs[0]);
+    }
+
+    #[test]
+    fn constant_as_wide_as_operand() {
+        let reg_profile = reg_profile();
+        // cmp al, 5
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"5,al,==","offset":4096,"opcode":"cmp al, 5","type":"cmp","size":2}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true).unwrap();
+
+        let ssa = rfn.ssa();
+        let sub = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpSub))
+            .expect("No subtraction emitted for `cmp`");
+        assert_eq!(ssa.node_data(sub).unwrap().vt.width().get_width(), Some(8));
+        let five = ssa
+            .operands_of(sub)
+            .into_iter()
+            .find(|&n| ssa.constant(n) == Some(5))
+            .expect("The constant is not an operand of the subtraction");
+        assert_eq!(ssa.node_data(five).unwrap().vt.width().get_width(), Some(8));
+        // Neither the constant nor al are cast to the width of the other.
+        assert!(ssa.inorder_walk().all(|n| match ssa.opcode(n) {
+            Some(MOpcode::OpZeroExt(_)) => false,
+            Some(MOpcode::OpNarrow(_)) => ssa.constant(ssa.operands_of(n)[0]).is_none(),
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn conditional_move_is_select() {
+        let reg_profile = reg_profile();
+
+        // Constructs `cmp rbx, rax`, `esil` and a return.
+        let construct = |esil: &str| {
+            let ops: Vec<LOpInfo> = serde_json::from_str(&format!(
+                r#"[{{"esil":"rax,rbx,==,$z,zf,=","offset":4096,"opcode":"cmp rbx, rax","type":"cmp","size":3}},
+                    {{"esil":"{}","offset":4099,"opcode":"cmove rax, rbx","type":"cmov","size":4}},
+                    {{"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4103,"opcode":"ret","type":"ret","size":1}}]"#,
+                esil
+            ))
+            .unwrap();
+            let mut rfn = RadecoFunction::default();
+            rfn.instructions = ops;
+            SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true)
+                .unwrap();
+            rfn
+        };
+
+        let rfn = construct("zf,?{,rbx,rax,=,}");
+        let ssa = rfn.ssa();
+        let select = ssa
+            .inorder_walk()
+            .find(|&n| ssa.opcode(n) == Some(MOpcode::OpITE))
+            .expect("No select emitted for the conditional move");
+        assert_eq!(ssa.operands_of(select).len(), 3);
+        // No branch is taken: the blocks are the ones of an unconditional move.
+        let blocks = construct("rbx,rax,=").ssa().blocks().len();
+        assert_eq!(ssa.blocks().len(), blocks);
+
+        let exit = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let rax = ssa.regfile.register_id_by_name("rax").unwrap();
+        let (value, _) = utils::register_state_info(exit, ssa)[rax];
+        assert!(value == select || ssa.operands_of(value) == vec![select]);
+    }
+
+    #[test]
+    fn commutative_operands_canonicalized() {
+        let reg_profile = reg_profile();
+
+        // Constructs `esil` followed by a return, and returns the operands of its `opcode` node.
+        let operands = |esil: &str, opcode: MOpcode| {
+            let ops: Vec<LOpInfo> = serde_json::from_str(&format!(
+                r#"[{{"esil":"{}","offset":4096,"opcode":"","type":"add","size":4}},
+                    {{"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4100,"opcode":"ret","type":"ret","size":1}}]"#,
+                esil
+            ))
+            .unwrap();
+            let mut rfn = RadecoFunction::default();
+            rfn.instructions = ops;
+            SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, &[], false, true)
+                .unwrap();
+            let ssa = rfn.ssa();
+            let node = ssa
+                .inorder_walk()
+                .find(|&n| ssa.opcode(n) == Some(opcode.clone()))
+                .expect("Operation not lifted");
+            ssa.operands_of(node)
+                .into_iter()
+                .map(|n| match ssa.constant(n) {
+                    Some(c) => format!("#{}", c),
+                    None => ssa.registers(n).join(","),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let commutative = vec![
+            ("+", MOpcode::OpAdd),
+            ("*", MOpcode::OpMul),
+            ("^", MOpcode::OpXor),
+        ];
+        for (esil, opcode) in commutative {
+            let one_x = operands(&format!("1,rbx,{},rax,=", esil), opcode.clone());
+            let x_one = operands(&format!("rbx,1,{},rax,=", esil), opcode);
+            assert_eq!(one_x, x_one);
+            assert_eq!(one_x, vec!["rbx".to_owned(), "#1".to_owned()]);
+        }
+        // `1 - x` and `x - 1` are left apart.
+        let one_x = operands("1,rbx,-,rax,=", MOpcode::OpSub);
+        let x_one = operands("rbx,1,-,rax,=", MOpcode::OpSub);
+        assert_ne!(one_x, x_one);
+    }
+
+    #[test]
+    fn expired_deadline_lifts_nothing() {
+        let reg_profile = reg_profile();
+        // mov eax, 1; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"esil":"1,rax,=","offset":4096,"opcode":"mov eax, 1","type":"mov","size":5},
+                {"esil":"rsp,[8],rip,=,8,rsp,+=","offset":4101,"opcode":"ret","type":"ret","size":1}]"#,
+        )
+        .unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = ops;
+        let timed_out = SSAConstruct::<SSAStorage>::construct_until(
+            &mut rfn,
+            &reg_profile,
+            &[],
+            false,
+            true,
+            Some(Instant::now()),
+        )
+        .unwrap();
+        assert!(timed_out);
+
+        // The SSA is still well-formed, with the entry and the exit only.
+        let ssa = rfn.ssa();
+        assert!(ssa.entry_node().is_some() && ssa.exit_node().is_some());
+        assert!(ssa
+            .inorder_walk()
+            .all(|n| ssa.address(n).map(|a| a.address) != Some(0x1000)));
+
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = serde_json::from_str(
+            r#"[{"esil":"1,rax,=","offset":4096,"opcode":"mov eax, 1","type":"mov","size":5}]"#,
+        )
+        .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(3600);
+        assert!(!SSAConstruct::<SSAStorage>::construct_until(
+            &mut rfn,
+            &reg_profile,
+            &[],
+            false,
+            true,
+            Some(deadline),
+        )
+        .unwrap());
+    }
+}
+
+/// Returns `true` if `esil` ends with a condition whose body only assigns a register other than
+/// `pc`, e.g. `zf,?{,rbx,rax,=,}` for `cmove rax, rbx`.
+fn is_conditional_assignment(esil: &str, pc: &str, regfile: &SubRegisterFile) -> bool {
+    let tokens = esil.split(',').collect::<Vec<_>>();
+    let body = match tokens.iter().position(|&t| t == "?{") {
+        Some(start) if tokens.last() == Some(&"}") => &tokens[start + 1..tokens.len() - 1],
+        _ => return false,
+    };
+    let (dst, value) = match body.split_last() {
+        Some((&"=", rest)) => match rest.split_last() {
+            Some((&dst, value)) => (dst, value),
+            None => return false,
+        },
+        _ => return false,
+    };
+    let flow = ["GOTO", "BREAK", "LOOP", "TODO"];
+    dst != pc
+        && regfile.get_subregister(dst).is_some()
+        && !value.is_empty()
+        && value
+            .iter()
+            .all(|t| !t.contains(|c| c == '=' || c == '{' || c == '}') && !flow.contains(t))
+}
+
+/// Returns the operand `esil` assigns to `pc`, e.g. `rax` for `rip,8,rsp,-=,rsp,=[8],rax,rip,=`
+/// (`call rax`).
+fn indirect_target<'e>(esil: &'e str, pc: &str) -> Option<&'e str> {
+    let tokens = esil.split(',').collect::<Vec<_>>();
+    tokens
+        .windows(3)
+        .rev()
+        .find(|w| w[1] == pc && w[2] == "=")
+        .map(|w| w[0])
+}
+
+/// Returns the string operation of the instruction `disasm` and the width of its elements, if it
+/// is one of `rep movs`, `rep stos` or `repne scas`, e.g. `rep movsb byte [rdi], byte ptr [rsi]`.
+fn string_op(disasm: &str) -> Option<(StringOp, u16)> {
+    let mut words = disasm.split_whitespace();
+    let prefix = words.next()?;
+    let mnemonic = words.next()?;
+    if mnemonic.len() != 5 || !mnemonic.is_ascii() {
+        return None;
+    }
+    let kind = match (prefix, &mnemonic[..4]) {
+        ("rep", "movs") => StringOp::Movs,
+        ("rep", "stos") => StringOp::Stos,
+        ("repne", "scas") | ("repnz", "scas") => StringOp::Scas,
+        _ => return None,
+    };
+    let width = match &mnemonic[4..] {
+        "b" => 8,
+        "w" => 16,
+        "d" => 32,
+        "q" => 64,
+        _ => return None,
+    };
+    Some((kind, width))
 }
 
 lazy_static! {
     /// A `ValueInfo` for `{mem}` comments
     static ref MEM_VALUEINFO: ValueInfo = scalar!(0);
 }
+
+/// A `ValueInfo` for an `OpStore` writing `width` bits to memory. Falls back to `MEM_VALUEINFO`
+/// if the access size is not known.
+fn store_valueinfo(width: u16) -> ValueInfo {
+    if [8, 16, 32, 64, 128].contains(&width) {
+        scalar!(width)
+    } else {
+        *MEM_VALUEINFO
+    }
+}
@@ -45,6 +45,7 @@
 
 extern crate petgraph;
 extern crate regex;
+extern crate serde;
 extern crate serde_json;
 
 #[macro_use]
@@ -86,4 +87,5 @@ pub mod middle;
 pub mod analysis;
 
 pub mod backend;
+pub mod error;
 pub mod frontend;
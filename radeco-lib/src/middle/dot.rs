@@ -5,10 +5,11 @@
 // This file may not be copied, modified, or distributed
 // except according to those terms.
 
-//! Graph visualization traits and functions to emit dot code.
+//! Graph visualization traits and functions to emit dot code, as well as GraphML for external
+//! graph tools (e.g. Gephi or Cytoscape).
 
 use std::cmp::Eq;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::hash::Hash;
 
@@ -126,13 +127,35 @@ pub trait GraphDot {
 }
 
 pub fn emit_dot<T: GraphDot>(g: &T) -> String {
+    emit_dot_filtered(g, |_| true)
+}
+
+/// Like `emit_dot`, but only emits the nodes in one of `clusters`, along with the edges between
+/// them.
+pub fn emit_dot_clusters<T: GraphDot>(g: &T, clusters: &[usize]) -> String {
+    emit_dot_filtered(g, |n| {
+        g.node_cluster(n).map_or(false, |c| clusters.contains(&c))
+    })
+}
+
+fn emit_dot_filtered<T, F>(g: &T, keep: F) -> String
+where
+    T: GraphDot,
+    F: Fn(&T::NodeIndex) -> bool,
+{
     let mut result = String::new();
     result.push_str(&*g.configure());
 
     // Node configurations
+    let nodes = g.nodes();
+    let total = nodes.len();
+    let nodes = nodes.into_iter().filter(|n| keep(n)).collect::<Vec<_>>();
+    // Finding the ends of an edge is slow, only do it if some node was left out.
+    let partial = nodes.len() < total;
     {
-        let nodes = g.nodes();
-        let mut clustermap = HashMap::<T::NodeIndex, Vec<T::NodeIndex>>::new();
+        // Clusters and the nodes in them are emitted by index, so that the output is the same
+        // on every run.
+        let mut clustermap = BTreeMap::<usize, Vec<T::NodeIndex>>::new();
 
         for i in &nodes {
             let block = g.node_cluster(i).unwrap_or_else(|| {
@@ -140,13 +163,14 @@ pub fn emit_dot<T: GraphDot>(g: &T) -> String {
                 0
             });
             clustermap
-                .entry(T::node_index_new(block))
+                .entry(T::node_index_new(block).to_index())
                 .or_insert_with(Vec::new)
                 .push(i.clone());
         }
 
-        for (k, v) in &clustermap {
-            result.push_str(&*format!("subgraph cluster_{} {{\n", k.to_index()));
+        for (k, v) in &mut clustermap {
+            v.sort_by_key(|n| n.to_index());
+            result.push_str(&*format!("subgraph cluster_{} {{\n", k));
             result.push_str("style=filled;\n");
             result.push_str("fillcolor=gray;\n");
             result.push_str("rankdir=TB;\n");
@@ -162,9 +186,175 @@ pub fn emit_dot<T: GraphDot>(g: &T) -> String {
         if g.edge_skip(&edge_i) {
             continue;
         }
+        if partial && !(keep(&g.edge_source(&edge_i)) && keep(&g.edge_target(&edge_i))) {
+            continue;
+        }
         result.push_str(g.edge_attrs(&edge_i).bake());
     }
 
     result.push_str("\n}\n");
     result
 }
+
+/// This trait enables graphs to be exported to GraphML from implementors.
+pub trait GraphMl {
+    /// Names and GraphML types (e.g. `string` or `int`) of the attributes of the nodes.
+    fn node_keys(&self) -> &'static [(&'static str, &'static str)];
+    /// Names and GraphML types of the attributes of the edges.
+    fn edge_keys(&self) -> &'static [(&'static str, &'static str)];
+
+    /// Nodes, by index, along with the values of their attributes in the order of `node_keys`.
+    /// Attributes which don't apply to a node are `None`.
+    fn graphml_nodes(&self) -> Vec<(usize, Vec<Option<String>>)>;
+    /// Edges, by the indices of their source and target, along with the values of their
+    /// attributes in the order of `edge_keys`.
+    fn graphml_edges(&self) -> Vec<(usize, usize, Vec<Option<String>>)>;
+}
+
+/// Escapes the characters of `s` which can't appear as is in XML text or attribute values.
+pub fn xml_escape(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => r.push_str("&amp;"),
+            '<' => r.push_str("&lt;"),
+            '>' => r.push_str("&gt;"),
+            '"' => r.push_str("&quot;"),
+            '\'' => r.push_str("&apos;"),
+            _ => r.push(c),
+        }
+    }
+    r
+}
+
+/// Pushes the `<data>` elements of the attributes of a node or an edge (`domain`) to `result`.
+fn push_graphml_data(
+    result: &mut String,
+    domain: &str,
+    keys: &[(&str, &str)],
+    values: &[Option<String>],
+) {
+    for (&(name, _), value) in keys.iter().zip(values) {
+        if let Some(ref value) = *value {
+            result.push_str(&format!(
+                "      <data key=\"{}_{}\">{}</data>\n",
+                domain,
+                name,
+                xml_escape(value)
+            ));
+        }
+    }
+}
+
+/// Emits `g` as a GraphML document holding a single directed graph.
+pub fn emit_graphml<T: GraphMl>(g: &T) -> String {
+    emit_graphml_graphs(&[("G".to_owned(), g)])
+}
+
+/// Emits a GraphML document holding a directed graph for each of `graphs`, by id. The ids of the
+/// nodes are prefixed with the index of their graph, since they must be unique in the document.
+pub fn emit_graphml_graphs<T: GraphMl>(graphs: &[(String, &T)]) -> String {
+    let mut result = String::new();
+    result.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    result.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    if let Some(&(_, g)) = graphs.first() {
+        for &(domain, keys) in &[("node", g.node_keys()), ("edge", g.edge_keys())] {
+            for &(name, ty) in keys {
+                result.push_str(&format!(
+                    "  <key id=\"{0}_{1}\" for=\"{0}\" attr.name=\"{1}\" attr.type=\"{2}\"/>\n",
+                    domain, name, ty
+                ));
+            }
+        }
+    }
+
+    for (i, &(ref id, g)) in graphs.iter().enumerate() {
+        let prefix = if graphs.len() > 1 {
+            format!("g{}", i)
+        } else {
+            String::new()
+        };
+        result.push_str(&format!(
+            "  <graph id=\"{}\" edgedefault=\"directed\">\n",
+            xml_escape(id)
+        ));
+        for (n, values) in g.graphml_nodes() {
+            result.push_str(&format!("    <node id=\"{}n{}\">\n", prefix, n));
+            push_graphml_data(&mut result, "node", g.node_keys(), &values);
+            result.push_str("    </node>\n");
+        }
+        for (source, target, values) in g.graphml_edges() {
+            result.push_str(&format!(
+                "    <edge source=\"{0}n{1}\" target=\"{0}n{2}\">\n",
+                prefix, source, target
+            ));
+            push_graphml_data(&mut result, "edge", g.edge_keys(), &values);
+            result.push_str("    </edge>\n");
+        }
+        result.push_str("  </graph>\n");
+    }
+    result.push_str("</graphml>\n");
+    result
+}
+
+/// Checks that the elements of the XML document `xml` are properly nested and that its markup
+/// characters are escaped, returning the number of elements named `name`.
+#[cfg(test)]
+pub fn count_xml_elements(xml: &str, name: &str) -> Result<usize, String> {
+    fn check_escapes(text: &str) -> Result<(), String> {
+        for (i, _) in text.match_indices('&') {
+            let entity = &text[i..];
+            if !["&amp;", "&lt;", "&gt;", "&quot;", "&apos;"]
+                .iter()
+                .any(|e| entity.starts_with(e))
+            {
+                return Err(format!("Unescaped `&` in {:?}", text));
+            }
+        }
+        Ok(())
+    }
+
+    let mut open = Vec::new();
+    let mut count = 0;
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let text = &rest[..start];
+        if text.contains('>') {
+            return Err(format!("Unescaped `>` in {:?}", text));
+        }
+        check_escapes(text)?;
+        let end = rest[start..]
+            .find('>')
+            .ok_or_else(|| "Unterminated tag".to_owned())?;
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        if tag.contains('<') || tag.matches('"').count() % 2 != 0 {
+            return Err(format!("Malformed tag {:?}", tag));
+        }
+        check_escapes(tag)?;
+        if tag.starts_with('?') {
+            continue;
+        }
+        if tag.starts_with('/') {
+            match open.pop() {
+                Some(elem) if elem == &tag[1..] => continue,
+                elem => return Err(format!("{:?} closed by {:?}", elem, tag)),
+            }
+        }
+        let elem = tag
+            .trim_end_matches('/')
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| "Empty tag".to_owned())?;
+        if elem == name {
+            count += 1;
+        }
+        if !tag.ends_with('/') {
+            open.push(elem);
+        }
+    }
+    if !rest.trim().is_empty() || !open.is_empty() {
+        return Err(format!("Unclosed elements {:?}", open));
+    }
+    Ok(count)
+}
@@ -9,13 +9,14 @@
 //! Shared by the SSA representation and the intermediate representation of the
 //! ESIL parser.
 
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::fmt;
 
 pub type Address = u64;
 
 /// Used to describe the width of an operand/operation in a SSA Node.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum WidthSpec {
     /// Takes whatever width the other operation takes
     Adaptive,
@@ -48,23 +49,42 @@ impl From<u16> for WidthSpec {
     }
 }
 
-#[derive(Clone, Default, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    Clone, Default, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
 // New address struct
+//
+// `segment` is the segment or overlay of the address, 0 unless the binary has overlapping
+// regions. Addresses are ordered by segment first, so that the blocks of a segment are
+// contiguous.
 pub struct MAddress {
+    pub segment: u16,
     pub address: u64,
     pub offset: u64,
 }
 
 impl MAddress {
     pub fn new(address: u64, offset: u64) -> MAddress {
+        MAddress::segmented(0, address, offset)
+    }
+
+    /// Returns the address `address.offset` in the segment or overlay `segment`.
+    pub fn segmented(segment: u16, address: u64, offset: u64) -> MAddress {
         MAddress {
+            segment: segment,
             address: address,
             offset: offset,
         }
     }
 
+    /// Returns the segment or overlay of the address, 0 if the binary isn't segmented.
+    pub fn segment(&self) -> u16 {
+        self.segment
+    }
+
     pub fn invalid_address() -> MAddress {
         MAddress {
+            segment: 0,
             address: u64::max_value(),
             offset: u64::max_value(),
         }
@@ -73,6 +93,10 @@ impl MAddress {
 
 impl fmt::UpperHex for MAddress {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The segment is left out of the addresses of a binary which isn't segmented.
+        if self.segment != 0 {
+            write!(f, "{:04X}:", self.segment)?;
+        }
         write!(f, "{:#08X}.{:04X}", self.address, self.offset)
     }
 }
@@ -85,25 +109,25 @@ impl fmt::Display for MAddress {
 
 impl From<u64> for MAddress {
     fn from(other: u64) -> MAddress {
-        MAddress {
-            address: other,
-            offset: 0,
-        }
+        MAddress::new(other, 0)
     }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum MArity {
     Binary,
+    Quaternary,
     Ternary,
     Unary,
     Zero,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MOpcode {
     OpAdd,
     OpAnd,
+    // Reverse the order of the bytes of the value, which is `width` bits wide
+    OpByteSwap(u16),
     OpCJmp,
     OpCall,
     OpConst(u64),
@@ -111,7 +135,7 @@ pub enum MOpcode {
     OpDiv,
     OpEq,
     OpGt,
-    // If - Then - Else
+    // If - Then - Else, the second operand if the first one holds and the third one otherwise
     OpITE,
     OpIf,
     OpInvalid,
@@ -131,10 +155,26 @@ pub enum MOpcode {
     OpRol,
     // Rotate Shift Right
     OpRor,
+    // `rep movs` with elements of `width` bits: copies the number of elements given by the
+    // fourth operand from the third operand to the second one, in the memory state of the first
+    // operand. It is the memory state after the copy.
+    OpRepMovs(u16),
+    // `repne scas` with elements of `width` bits: the number of elements read from the second
+    // operand, up to the fourth operand, until one equal to the third operand, in the memory state
+    // of the first operand.
+    OpRepScas(u16),
+    // `rep stos` with elements of `width` bits: fills the number of elements given by the fourth
+    // operand at the second operand with the third operand, in the memory state of the first
+    // operand. It is the memory state after the fill.
+    OpRepStos(u16),
     // Sign Extend to width
     OpSignExt(u16),
     OpStore,
     OpSub,
+    // A trap, e.g. `ud2`: the execution never goes past it
+    OpTrap,
+    // ESIL which couldn't be lifted, e.g. `TODO`
+    OpUnsupported(String),
     OpXor,
     // Zero Extend to width
     OpZeroExt(u16),
@@ -169,24 +209,26 @@ impl MOpcode {
         }
     }
 
+    /// Returns true if executing the operation has an effect beyond computing its result, in which
+    /// case it must be kept even if the result is never used.
     pub fn has_sideeffects(&self) -> bool {
         match *self {
             MOpcode::OpStore
+            | MOpcode::OpRepMovs(_)
+            | MOpcode::OpRepStos(_)
             | MOpcode::OpJmp
             | MOpcode::OpCJmp
             | MOpcode::OpCall
-            | MOpcode::OpITE => true,
+            | MOpcode::OpCustom(_)
+            | MOpcode::OpTrap
+            | MOpcode::OpUnsupported(_) => true,
             _ => false,
         }
     }
 
     pub fn allowed_in_ssa(&self) -> bool {
         match *self {
-            MOpcode::OpCJmp
-            | MOpcode::OpITE
-            | MOpcode::OpInvalid
-            | MOpcode::OpJmp
-            | MOpcode::OpNop => false,
+            MOpcode::OpCJmp | MOpcode::OpInvalid | MOpcode::OpJmp | MOpcode::OpNop => false,
             _ => true,
         }
     }
@@ -195,6 +237,7 @@ impl MOpcode {
         match *self {
             MOpcode::OpAdd => (Cow::from("OpAdd"), MArity::Binary),
             MOpcode::OpAnd => (Cow::from("OpAnd"), MArity::Binary),
+            MOpcode::OpByteSwap(_) => (Cow::from("OpByteSwap"), MArity::Unary),
             MOpcode::OpCJmp => (Cow::from("OpJmpIf"), MArity::Binary),
             MOpcode::OpCall => (Cow::from("OpCall"), MArity::Unary),
             MOpcode::OpConst(c) => (Cow::from(format!("OpCost({})", c)), MArity::Zero),
@@ -219,9 +262,16 @@ impl MOpcode {
             MOpcode::OpOr => (Cow::from("OpOr"), MArity::Binary),
             MOpcode::OpRol => (Cow::from("OpRol"), MArity::Binary),
             MOpcode::OpRor => (Cow::from("OpRor"), MArity::Binary),
+            MOpcode::OpRepMovs(_) => (Cow::from("OpRepMovs"), MArity::Quaternary),
+            MOpcode::OpRepScas(_) => (Cow::from("OpRepScas"), MArity::Quaternary),
+            MOpcode::OpRepStos(_) => (Cow::from("OpRepStos"), MArity::Quaternary),
             MOpcode::OpSignExt(_) => (Cow::from("OpSignExt"), MArity::Unary),
             MOpcode::OpStore => (Cow::from("OpStore"), MArity::Ternary),
             MOpcode::OpSub => (Cow::from("OpSub"), MArity::Binary),
+            MOpcode::OpTrap => (Cow::from("OpTrap"), MArity::Zero),
+            MOpcode::OpUnsupported(ref s) => {
+                (Cow::from(format!("OpUnsupported({})", s)), MArity::Zero)
+            }
             MOpcode::OpXor => (Cow::from("OpXor"), MArity::Binary),
             MOpcode::OpZeroExt(_) => (Cow::from("OpZeroExt"), MArity::Unary),
         }
@@ -260,6 +310,12 @@ impl MOpcode {
             MOpcode::OpSub => 28,
             MOpcode::OpXor => 29,
             MOpcode::OpZeroExt(_) => 30,
+            MOpcode::OpByteSwap(_) => 31,
+            MOpcode::OpRepMovs(_) => 32,
+            MOpcode::OpRepScas(_) => 33,
+            MOpcode::OpRepStos(_) => 34,
+            MOpcode::OpTrap => 35,
+            MOpcode::OpUnsupported(_) => 36,
         }
     }
 
@@ -270,6 +326,8 @@ impl MOpcode {
         let lhs = Wrapping(lhs);
         let rhs = Wrapping(rhs);
         Some(match self {
+            // Division by zero has no value.
+            OpDiv | OpMod if rhs.0 == 0 => return None,
             OpAdd => (lhs + rhs).0,
             OpSub => (lhs - rhs).0,
             OpMul => (lhs * rhs).0,
@@ -294,6 +352,7 @@ impl MOpcode {
 
         Some(match self {
             OpNot => !val,
+            OpByteSwap(width) => byte_swap(val, *width),
             _ => return None,
         })
     }
@@ -304,3 +363,63 @@ impl fmt::Display for MOpcode {
         write!(f, "{}", self.to_string())
     }
 }
+
+/// How constants are rendered by the IR writer and the decompiler. Constants are always rendered
+/// at the width of their node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConstFormat {
+    /// Unsigned hexadecimal. This is the only form `ir_reader` accepts.
+    Hex,
+    /// Unsigned decimal.
+    Decimal,
+    /// Signed decimal, i.e. negative if the high bit is set.
+    Signed,
+}
+
+impl Default for ConstFormat {
+    fn default() -> ConstFormat {
+        ConstFormat::Hex
+    }
+}
+
+impl ConstFormat {
+    /// Renders `value` as a constant of `width` bits, putting `hex_prefix` in front of
+    /// hexadecimal numbers.
+    pub fn render(self, value: u64, width: u16, hex_prefix: &str) -> String {
+        let value = truncate_const(value, width);
+        match self {
+            ConstFormat::Hex => format!("{}{:x}", hex_prefix, value),
+            ConstFormat::Decimal => value.to_string(),
+            ConstFormat::Signed => signed_const(value, width).to_string(),
+        }
+    }
+}
+
+/// Keeps the lowest `width` bits of `value`. Constants are stored as `u64` whatever the width of
+/// their node.
+pub fn truncate_const(value: u64, width: u16) -> u64 {
+    if width == 0 || width >= 64 {
+        value
+    } else {
+        value & ((1 << width) - 1)
+    }
+}
+
+/// Reverses the order of the bytes in the lowest `width` bits of `value`.
+pub fn byte_swap(value: u64, width: u16) -> u64 {
+    if width == 0 || width >= 64 {
+        value.swap_bytes()
+    } else {
+        truncate_const(value, width).swap_bytes() >> (64 - u32::from(width))
+    }
+}
+
+/// Interprets the lowest `width` bits of `value` as a two's complement number.
+pub fn signed_const(value: u64, width: u16) -> i64 {
+    if width == 0 || width >= 64 {
+        value as i64
+    } else {
+        let shift = 64 - u32::from(width);
+        ((value << shift) as i64) >> shift
+    }
+}
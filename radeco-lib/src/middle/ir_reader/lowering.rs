@@ -5,8 +5,9 @@ use crate::middle::ir;
 use crate::middle::ir::MOpcode as IrOpcode;
 use crate::middle::regfile::RegisterId;
 use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+use crate::middle::ssa::graph_traits::Graph;
 use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
-use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::ssastorage::{EdgeData, SSAStorage};
 
 use std::collections::HashMap;
 use std::error;
@@ -45,7 +46,7 @@ struct LowerSsa<'a> {
     blocks: HashMap<ir::MAddress, SSABlock>,
     values: HashMap<sast::ValueRef, SSAValue>,
     fw_ref_values: HashMap<sast::ValueRef, SSAValue>,
-    phi_operands: Vec<(SSAValue, Vec<sast::Operand>)>,
+    phi_operands: Vec<(SSAValue, Vec<sast::PhiOperand>)>,
 }
 
 impl<'a> LowerSsa<'a> {
@@ -95,10 +96,7 @@ impl<'a> LowerSsa<'a> {
         self.lower_final_reg_state(sfn.final_reg_state)?;
 
         for (phi, sops) in mem::replace(&mut self.phi_operands, Vec::new()) {
-            for sop in sops.into_iter().rev() {
-                let op = self.lower_operand(sop)?;
-                self.ssa.phi_use(phi, op);
-            }
+            self.lower_phi_operands(phi, sops)?;
         }
 
         if !self.fw_ref_values.is_empty() {
@@ -119,7 +117,12 @@ impl<'a> LowerSsa<'a> {
         let regstate = self.ssa.registers_in(self.entry_node).ok_or(SsaError)?;
         for (sast::NewValue(vr, ty), sreg) in sregstate {
             let regid = self.index_of_reg(&sreg)?;
-            let val = self.ssa.insert_comment(lower_valueinfo(ty), sreg.0).ok_or(SsaError)?;
+            let val = if sreg.0 == "mem" {
+                self.ssa.insert_comment(lower_valueinfo(ty), sreg.0)
+            } else {
+                self.ssa.insert_argument(lower_valueinfo(ty), sreg.0)
+            }
+            .ok_or(SsaError)?;
             self.ssa.op_use(regstate, regid.to_u8(), val);
             self.insert_new_value(vr, val)?;
         }
@@ -298,6 +301,34 @@ impl<'a> LowerSsa<'a> {
         Ok(())
     }
 
+    /// Adds the operands of `phi`. An operand flowing in from several
+    /// predecessors is used once per predecessor, at the index of that
+    /// predecessor in `CFG::ordered_preds_of`, as `utils::phi_sources` expects.
+    fn lower_phi_operands(&mut self, phi: SSAValue, sops: Vec<sast::PhiOperand>) -> Result<()> {
+        let block = self.ssa.block_for(phi).ok_or(SsaError)?;
+        let preds = self.ssa.ordered_preds_of(block);
+        for sop in sops {
+            let op = self.lower_operand(sop.value)?;
+            if sop.preds.is_empty() {
+                self.ssa.phi_use(phi, op);
+                continue;
+            }
+            for addr in sop.preds {
+                let index = self
+                    .blocks
+                    .get(&addr)
+                    .and_then(|b| preds.iter().position(|p| p == b))
+                    .ok_or_else(|| {
+                        LoweringError::InvalidAst(format!("no predecessor at {} for a phi", addr))
+                    })?;
+                self.ssa
+                    .insert_edge(phi, op, EdgeData::Data(index as u8))
+                    .ok_or(SsaError)?;
+            }
+        }
+        Ok(())
+    }
+
     fn block_at(&mut self, at: ir::MAddress) -> Result<SSABlock> {
         use std::collections::hash_map::Entry;
         // can't use `or_insert_with` because `ssa.insert_block` may fail
@@ -350,6 +381,7 @@ fn lower_resize_op(srst: sast::ResizeType, sws: sast::WidthSpec) -> IrOpcode {
         sast::ResizeType::Narrow => IrOpcode::OpNarrow(sws.0),
         sast::ResizeType::SignExt => IrOpcode::OpSignExt(sws.0),
         sast::ResizeType::ZeroExt => IrOpcode::OpZeroExt(sws.0),
+        sast::ResizeType::ByteSwap => IrOpcode::OpByteSwap(sws.0),
     }
 }
 
@@ -40,11 +40,19 @@ pub enum Terminator {
 
 #[derive(Debug)]
 pub enum Operation {
-    Phi(NewValue, Vec<Operand>),
+    Phi(NewValue, Vec<PhiOperand>),
     Assign(Option<ir::MAddress>, NewValue, Expr),
     Call(Option<ir::MAddress>, Vec<CallRet>, Operand, Vec<CallArg>),
 }
 
+/// An operand of a phi, with the predecessor blocks it flows in from. Without
+/// any predecessor, the operand is added after the previous ones.
+#[derive(Debug)]
+pub struct PhiOperand {
+    pub value: Operand,
+    pub preds: Vec<ir::MAddress>,
+}
+
 #[derive(Debug)]
 pub struct CallRet {
     pub value: NewValue,
@@ -72,6 +80,7 @@ pub enum ResizeType {
     Narrow,
     SignExt,
     ZeroExt,
+    ByteSwap,
 }
 
 #[derive(Debug)]
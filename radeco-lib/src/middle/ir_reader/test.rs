@@ -1,5 +1,6 @@
 use crate::middle::ir_writer;
 use crate::middle::regfile::SubRegisterFile;
+use crate::utils::test_support::register_file;
 use serde_json;
 use std::fs::File;
 use std::io::Read;
@@ -86,14 +87,88 @@ define-fun sym.foo(unknown) -> unknown {
         $mem = %34;
 }
 ";
-const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const DIAMOND_SSA_TXT: &str = "\
+define-fun sym.diamond(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown1 = %1 == #x0;
+        JMP IF %3 0x001004.0000 ELSE 0x001008.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] %4: $Unknown64 = %1 + #x1;
+        JMP 0x00100C.0000
+    bb_0x001008.0000(sz 0x4):
+        [@0x001008.0001] %5: $Unknown64 = %1 - #x1;
+        JMP 0x00100C.0000
+    bb_0x00100C.0000(sz 0x1):
+        %6: $Unknown64 = Phi(%4, %5);
+        %7: $Unknown64 = Phi();
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $rdi = %7;
+        $mem = %2;
+}
+";
+
+// Two blocks at the same address, in different segments or overlays.
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SEGMENTED_SSA_TXT: &str = "\
+define-fun sym.overlay(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        JMP 0001:0x001000.0000
+    bb_0001:0x001000.0000(sz 0x4):
+        [@0001:0x001000.0001] %4: $Unknown64 = %3 - #x1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $mem = %2;
+}
+";
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const NARROW_CONST_SSA_TXT: &str = "\
+define-fun sym.narrow(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #xffffffffffffffff;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %3;
+        $mem = %2;
+}
+";
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SECTION_CONSTS_SSA_TXT: &str = "\
+define-fun sym.labels(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x400410;
+        [@0x001000.0002] %4: $Unknown64 = %3 + #x10;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %4;
+        $mem = %2;
+}
+";
 
 lazy_static! {
-    static ref REGISTER_FILE: Arc<SubRegisterFile> = {
-        let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
-        let reg_profile = serde_json::from_str(&*s).unwrap();
-        Arc::new(SubRegisterFile::new(&reg_profile))
-    };
+    static ref REGISTER_FILE: Arc<SubRegisterFile> = register_file();
 }
 
 #[test]
@@ -134,16 +209,16 @@ fn check_ssa_parse() {
         ssa.g[x1],
         NodeData::Op(OpConst(1), ValueInfo::new_scalar(WidthSpec::new_known(64)))
     );
-    let cmt_rdi = v1_ops[1];
+    let arg_rdi = v1_ops[1];
     assert_eq!(
-        ssa.g[cmt_rdi],
-        NodeData::Comment(
+        ssa.g[arg_rdi],
+        NodeData::Argument(
             ValueInfo::new_unresolved(WidthSpec::new_known(64)),
             "rdi".to_owned()
         )
     );
     let rdi_i = ssa.regfile.register_id_by_name("rdi").unwrap().to_usize();
-    assert_eq!(cmt_rdi, ers[RegisterId::from_usize(rdi_i)].0);
+    assert_eq!(arg_rdi, ers[RegisterId::from_usize(rdi_i)].0);
 }
 
 #[test]
@@ -161,6 +236,104 @@ fn roundtrip_loopy_main() {
     roundtrip_file("sym.main".to_owned(), "test_files/loopy_main_ssa");
 }
 
+#[test]
+fn emit_phi_sources_diamond() {
+    let parsed = super::parse_il(DIAMOND_SSA_TXT, REGISTER_FILE.clone());
+    let mut emitted = String::new();
+    ir_writer::emit_il(&mut emitted, Some("sym.diamond".to_owned()), &parsed).unwrap();
+    let phis = emitted
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| l.contains("Phi("))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        phis,
+        vec![
+            "%6: $Unknown64 = Phi(%4 @ 0x001004.0000, %5 @ 0x001008.0000);",
+            "%7: $Unknown64 = Phi();",
+        ]
+    );
+    // annotated phis must be readable again
+    roundtrip("sym.diamond".to_owned(), &emitted);
+}
+
+#[test]
+fn lift_segmented_blocks() {
+    use crate::middle::ir::MAddress;
+    use crate::middle::ssa::cfg_traits::CFG;
+
+    let ssa = super::parse_il(SEGMENTED_SSA_TXT, REGISTER_FILE.clone());
+    let mut starts = ssa
+        .blocks()
+        .into_iter()
+        .filter_map(|b| ssa.starting_address(b))
+        .filter(|a| a.address == 0x1000)
+        .collect::<Vec<_>>();
+    starts.sort();
+    assert_eq!(
+        starts,
+        vec![MAddress::new(0x1000, 0), MAddress::segmented(1, 0x1000, 0)]
+    );
+    roundtrip("sym.overlay".to_owned(), SEGMENTED_SSA_TXT);
+}
+
+#[test]
+fn emit_narrow_const() {
+    use crate::middle::ir::{ConstFormat, MOpcode, WidthSpec};
+    use crate::middle::ssa::ssa_traits::{ValueInfo, SSA};
+    use crate::middle::ssa::ssastorage::NodeData;
+
+    let mut ssa = super::parse_il(NARROW_CONST_SSA_TXT, REGISTER_FILE.clone());
+    let c = *ssa.constants().keys().next().unwrap();
+    ssa.g[c] = NodeData::Op(
+        MOpcode::OpConst(u64::max_value()),
+        ValueInfo::new_scalar(WidthSpec::new_known(8)),
+    );
+    assert_eq!(ssa.constant_value(c), Some(u64::max_value()));
+
+    let mut emitted = String::new();
+    ir_writer::emit_il(&mut emitted, None, &ssa).unwrap();
+    assert!(emitted.contains("= %1 + #xff;"));
+    let mut emitted = String::new();
+    ir_writer::emit_il_with_consts(&mut emitted, None, &ssa, ConstFormat::Signed).unwrap();
+    assert!(emitted.contains("= %1 + -1;"));
+}
+
+#[test]
+fn emit_section_labels() {
+    use r2papi::structs::LSectionInfo;
+
+    // `.text` lies within the segment, the tightest one must be picked.
+    let sections: Vec<LSectionInfo> = serde_json::from_str(
+        r#"[{"flags":"-r-x","name":"LOAD0","paddr":0,"size":4096,"vaddr":4194304,"vsize":4096},
+            {"flags":"-r-x","name":".text","paddr":1024,"size":512,"vaddr":4195328,"vsize":512}]"#,
+    )
+    .unwrap();
+    let ssa = super::parse_il(SECTION_CONSTS_SSA_TXT, REGISTER_FILE.clone());
+    let mut emitted = String::new();
+    ir_writer::emit_il_with_sections(&mut emitted, None, &ssa, &sections).unwrap();
+    assert!(emitted.contains("= %1 + #x400410 /* .text */;"));
+    assert!(emitted.contains("= %3 + #x10;"));
+}
+
+#[test]
+fn emit_single_block() {
+    use crate::middle::ssa::utils;
+
+    let ssa = super::parse_il(DIAMOND_SSA_TXT, REGISTER_FILE.clone());
+    let block = utils::block_at(&ssa, 0x1006).unwrap();
+    assert_eq!(utils::block_at(&ssa, 0x100d), None);
+
+    let mut emitted = String::new();
+    ir_writer::emit_il_block(&mut emitted, &ssa, block, &[]).unwrap();
+    let lines = emitted.lines().map(|l| l.trim()).collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "bb_0x001004.0000(sz 0x4):");
+    assert!(lines[1].starts_with("[@0x001004.0001]"));
+    assert!(lines[1].ends_with("+ #x1;"));
+    assert_eq!(lines[2], "JMP 0x00100C.0000");
+}
+
 fn roundtrip_file<P: AsRef<Path>>(fn_name: String, file_path: P) {
     let ssa_txt = {
         let mut ssa_txt_file = File::open(file_path).expect("Error opening file");
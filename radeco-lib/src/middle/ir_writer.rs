@@ -6,13 +6,14 @@
 //! The text based
 //! representation is inspired from (and probably similar) LLVM IR.
 
-use crate::frontend::radeco_containers::RadecoFunction;
-use crate::middle::ir::MOpcode;
+use crate::frontend::radeco_containers::{self, RadecoFunction};
+use crate::middle::ir::{ConstFormat, MOpcode};
 use crate::middle::ssa::cfg_traits::CFG;
-use crate::middle::ssa::ssa_traits::{SSAWalk, ValueInfo, SSA};
+use crate::middle::ssa::ssa_traits::{SSAExtra, SSAWalk, ValueInfo, SSA};
 use crate::middle::ssa::ssastorage::{NodeData, SSAStorage};
 use crate::middle::ssa::utils;
 use petgraph::graph::NodeIndex;
+use r2papi::structs::LSectionInfo;
 
 use std::collections::HashMap;
 use std::fmt;
@@ -45,10 +46,81 @@ macro_rules! log_emit_err {
     }
 }
 
+/// Emits `ssa` as text, which `ir_reader::parse_il` parses back. The operations carrying a comment
+/// (see `RadecoFunction::apply_annotations`) are followed by it, e.g. `// checks the key`, in
+/// which case the output can't be parsed back.
 pub fn emit_il<O: Write>(output: O, fn_name: Option<String>, ssa: &SSAStorage) -> fmt::Result {
     IRWriter::new(output, ssa).emit_il(fn_name)
 }
 
+/// Like `emit_il`, but every operation is followed by a comment with the disassembly of the
+/// instruction it was lifted from, if it is in `disasm`. The output can't be parsed back.
+pub fn emit_il_with_disasm<O: Write>(
+    output: O,
+    fn_name: Option<String>,
+    ssa: &SSAStorage,
+    disasm: &HashMap<NodeIndex, String>,
+) -> fmt::Result {
+    let mut writer = IRWriter::new(output, ssa);
+    writer.disasm = Some(disasm);
+    writer.emit_il(fn_name)
+}
+
+/// Like `emit_il`, but constants are rendered in the given format. The output can only be parsed
+/// back with `ConstFormat::Hex`.
+pub fn emit_il_with_consts<O: Write>(
+    output: O,
+    fn_name: Option<String>,
+    ssa: &SSAStorage,
+    const_format: ConstFormat,
+) -> fmt::Result {
+    let mut writer = IRWriter::new(output, ssa);
+    writer.const_format = const_format;
+    writer.emit_il(fn_name)
+}
+
+/// Like `emit_il`, but constants pointing into one of `sections` are followed by a comment with
+/// the name of the section, e.g. `#x601040 /* .data */`, or with the name of their flag if they
+/// were annotated with one (see `RadecoFunction::annotate_flags`). The output can't be parsed
+/// back.
+pub fn emit_il_with_sections<O: Write>(
+    output: O,
+    fn_name: Option<String>,
+    ssa: &SSAStorage,
+    sections: &[LSectionInfo],
+) -> fmt::Result {
+    let mut writer = IRWriter::new(output, ssa);
+    writer.sections = sections;
+    writer.flags = true;
+    writer.emit_il(fn_name)
+}
+
+/// Like `emit_il_with_sections`, but only emits the basic block `block`. Values defined outside of
+/// it are numbered as they are first used. The output can't be parsed back.
+pub fn emit_il_block<O: Write>(
+    output: O,
+    ssa: &SSAStorage,
+    block: NodeIndex,
+    sections: &[LSectionInfo],
+) -> fmt::Result {
+    let mut writer = IRWriter::new(output, ssa);
+    writer.sections = sections;
+    writer.flags = true;
+    writer.emit_block(block)
+}
+
+/// Like `emit_il`, but the values annotated with a variable (see `analysis::variables`) are named
+/// after it, e.g. `v3`, instead of being numbered. The output can't be parsed back.
+pub fn emit_il_with_variables<O: Write>(
+    output: O,
+    fn_name: Option<String>,
+    ssa: &SSAStorage,
+) -> fmt::Result {
+    let mut writer = IRWriter::new(output, ssa);
+    writer.variables = true;
+    writer.emit_il(fn_name)
+}
+
 // TODO: expose width
 pub fn pretty_print_function_proto(rfn: &RadecoFunction) -> String {
     let args = rfn
@@ -72,6 +144,11 @@ struct IRWriter<'a, O: Write> {
     seen: HashMap<NodeIndex, u64>,
     ctr: u64,
     output: O,
+    disasm: Option<&'a HashMap<NodeIndex, String>>,
+    const_format: ConstFormat,
+    sections: &'a [LSectionInfo],
+    flags: bool,
+    variables: bool,
 }
 
 impl<'a, O: Write> IRWriter<'a, O> {
@@ -81,6 +158,11 @@ impl<'a, O: Write> IRWriter<'a, O> {
             seen: HashMap::new(),
             ctr: 0,
             output,
+            disasm: None,
+            const_format: ConstFormat::default(),
+            sections: &[],
+            flags: false,
+            variables: false,
         }
     }
 
@@ -100,31 +182,7 @@ impl<'a, O: Write> IRWriter<'a, O> {
                 continue;
             }
             match self.ssa.g[node] {
-                NodeData::Op(ref opcode, vt) => {
-                    self.indent(2)?;
-                    if let Some(address) = self.ssa.address(node) {
-                        write!(self.output, "[@{}] ", address)?;
-                    }
-                    match opcode {
-                        MOpcode::OpConst(_) => {
-                            radeco_err!("found const");
-                        }
-                        MOpcode::OpCall => self.emit_call(node)?,
-                        _ => {
-                            self.emit_new_value(node, vt)?;
-                            self.emit_operation(opcode, &self.ssa.operands_of(node))?;
-                        }
-                    };
-                    writeln!(self.output, ";")?;
-                }
-                NodeData::Phi(vt, _) => {
-                    self.indent(2)?;
-                    self.emit_new_value(node, vt)?;
-                    write!(self.output, "Phi(")?;
-                    let operands = self.ssa.operands_of(node);
-                    self.emit_operand_list(&operands)?;
-                    writeln!(self.output, ");")?;
-                }
+                NodeData::Op(..) | NodeData::Phi(..) => self.emit_value(node)?,
                 NodeData::BasicBlock(addr, sz) => {
                     if let Some(prev_block) = last {
                         // end previous block
@@ -174,6 +232,61 @@ impl<'a, O: Write> IRWriter<'a, O> {
         Ok(())
     }
 
+    /// Emits only the operations and phis of `block`, followed by its jump.
+    fn emit_block(mut self, block: NodeIndex) -> fmt::Result {
+        match self.ssa.g[block] {
+            NodeData::BasicBlock(addr, sz) => {
+                self.indent(1)?;
+                writeln!(self.output, "bb_{}(sz {:#x}):", addr, sz)?;
+            }
+            ref n => return log_emit_err!(self, "Not a basic block: {:?}", n),
+        }
+        for node in self.ssa.inorder_walk() {
+            if node != block && self.ssa.block_for(node) == Some(block) {
+                self.emit_value(node)?;
+            }
+        }
+        self.indent(2)?;
+        self.emit_jump(block)
+    }
+
+    fn emit_value(&mut self, node: NodeIndex) -> fmt::Result {
+        match self.ssa.g[node] {
+            NodeData::Op(ref opcode, vt) => {
+                self.indent(2)?;
+                if let Some(address) = self.ssa.address(node) {
+                    write!(self.output, "[@{}] ", address)?;
+                }
+                match opcode {
+                    MOpcode::OpConst(_) => {
+                        radeco_err!("found const");
+                    }
+                    MOpcode::OpCall => self.emit_call(node)?,
+                    _ => {
+                        self.emit_new_value(node, vt)?;
+                        self.emit_operation(opcode, &self.ssa.operands_of(node))?;
+                    }
+                };
+                write!(self.output, ";")?;
+                if let Some(asm) = self.disasm.and_then(|d| d.get(&node)) {
+                    write!(self.output, " // {}", asm)?;
+                }
+                if let Some(comment) = self.ssa.comments(&node) {
+                    write!(self.output, " // {}", comment)?;
+                }
+                writeln!(self.output)
+            }
+            NodeData::Phi(vt, _) => {
+                self.indent(2)?;
+                self.emit_new_value(node, vt)?;
+                write!(self.output, "Phi(")?;
+                self.emit_phi_operands(node)?;
+                writeln!(self.output, ");")
+            }
+            ref n => log_emit_err!(self, "Not a value: {:?}", n),
+        }
+    }
+
     fn emit_entry_regstate(&mut self, entry_regstate: NodeIndex) -> fmt::Result {
         self.indent(1)?;
         writeln!(self.output, "entry-register-state:")?;
@@ -260,6 +373,36 @@ impl<'a, O: Write> IRWriter<'a, O> {
                 write!(self.output, ")")?;
                 Ok(())
             }
+            OpByteSwap(wd) => {
+                write!(self.output, "ByteSwap{}(", wd)?;
+                self.emit_opt_operand(operands.get(0).cloned())?;
+                write!(self.output, ")")?;
+                Ok(())
+            }
+            OpRepMovs(wd) | OpRepScas(wd) | OpRepStos(wd) => {
+                let name = match opcode {
+                    OpRepMovs(_) => "RepMovs",
+                    OpRepScas(_) => "RepScas",
+                    _ => "RepStos",
+                };
+                write!(self.output, "{}{}(", name, wd)?;
+                self.emit_operand_list(operands)?;
+                write!(self.output, ")")?;
+                Ok(())
+            }
+            OpTrap => {
+                write!(self.output, "Trap()")?;
+                Ok(())
+            }
+            OpUnsupported(ref esil) => {
+                write!(self.output, "Unsupported({:?}", esil)?;
+                if !operands.is_empty() {
+                    write!(self.output, ", ")?;
+                    self.emit_operand_list(operands)?;
+                }
+                write!(self.output, ")")?;
+                Ok(())
+            }
             _ => {
                 radeco_warn!("unknown opcode: {:?}", opcode);
                 write!(self.output, "{}(", opcode)?;
@@ -289,6 +432,32 @@ impl<'a, O: Write> IRWriter<'a, O> {
         Ok(())
     }
 
+    /// Emits the operands of `phi`, each tagged with the predecessor blocks it
+    /// flows in from, e.g. `%2 @ 0x0006A0.0000, %22 @ 0x0006C7.0000`. The
+    /// operands are ordered by their first predecessor, which, unlike their
+    /// node indices, `ir_reader` keeps.
+    fn emit_phi_operands(&mut self, phi: NodeIndex) -> fmt::Result {
+        let mut sources = utils::phi_sources(phi, self.ssa);
+        let ssa = self.ssa;
+        sources.sort_by_key(|&(operand, ref preds)| {
+            let first = preds.first().and_then(|&b| ssa.starting_address(b));
+            (first.is_none(), first, operand.index())
+        });
+        emit_list!(self.output, sources, |(operand, preds)| {
+            self.emit_operand(operand)?;
+            if !preds.is_empty() {
+                write!(self.output, " @ ")?;
+                for (i, pred) in preds.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(self.output, "|")?;
+                    }
+                    self.emit_jump_tgt(pred)?;
+                }
+            }
+        });
+        Ok(())
+    }
+
     fn emit_call(&mut self, call_node: NodeIndex) -> fmt::Result {
         if let Some(call_info) = utils::call_info(call_node, self.ssa) {
             let ret_regs = utils::call_rets(call_node, self.ssa);
@@ -390,22 +559,51 @@ impl<'a, O: Write> IRWriter<'a, O> {
 
     fn emit_operand(&mut self, operand: NodeIndex) -> fmt::Result {
         match self.ssa.g[operand] {
-            NodeData::Op(MOpcode::OpConst(c), _) => write!(self.output, "#x{:x}", c),
-            _ => {
-                let idx = self.value(operand);
-                write!(self.output, "%{}", idx)
+            NodeData::Op(MOpcode::OpConst(c), vt) => {
+                let width = vt.width().get_width().unwrap_or(64);
+                let rendered = self.const_format.render(c, width, "#x");
+                self.output.write_str(&rendered)?;
+                let flag = if self.flags {
+                    self.ssa.flags(&operand)
+                } else {
+                    None
+                };
+                let label = flag.or_else(|| {
+                    radeco_containers::section_containing(self.sections, c)
+                        .and_then(|s| s.name.clone())
+                });
+                match label {
+                    Some(ref name) if !name.is_empty() => write!(self.output, " /* {} */", name),
+                    _ => Ok(()),
+                }
             }
+            _ => self.emit_name(operand),
         }
     }
 
     fn emit_new_value(&mut self, node: NodeIndex, vt: ValueInfo) -> fmt::Result {
-        let idx = self.value(node);
-        write!(self.output, "%{}: ", idx)?;
+        self.emit_name(node)?;
+        write!(self.output, ": ")?;
         self.emit_valueinfo(vt)?;
         write!(self.output, " = ")?;
         Ok(())
     }
 
+    fn emit_name(&mut self, node: NodeIndex) -> fmt::Result {
+        let variable = if self.variables {
+            self.ssa.variable(&node)
+        } else {
+            None
+        };
+        match variable {
+            Some(name) => self.output.write_str(&name),
+            None => {
+                let idx = self.value(node);
+                write!(self.output, "%{}", idx)
+            }
+        }
+    }
+
     fn value(&mut self, node: NodeIndex) -> u64 {
         use std::collections::hash_map::Entry;
         match self.seen.entry(node) {
@@ -15,6 +15,7 @@ pub mod ssa {
     pub mod ssa_traits;
     pub mod error;
     pub mod memoryssa;
+    pub mod prelude;
     pub mod ssadot;
     pub mod ssastorage;
     pub mod utils;
@@ -14,16 +14,33 @@ use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::u64;
 
 use crate::middle::ir::{self, MAddress, MOpcode};
-use crate::middle::ssa::graph_traits::{ConditionInfo, EdgeInfo, Graph};
+use crate::middle::ssa::graph_traits::{EdgeInfo, Graph};
 use crate::middle::ssa::ssa_traits::{SSAExtra, SSAMod, ValueInfo};
 use crate::r2papi::structs::LOpInfo;
 
-use crate::middle::regfile::{RegisterId, SubRegisterFile};
+use crate::middle::regfile::{RegFileError, RegisterId, SubRegisterFile};
 use crate::middle::ssa::ssa_traits::{NodeData, NodeType};
 
 pub type VarId = u64;
 
-const UNCOND_EDGE: u8 = 2;
+/// Type of the unconditional control flow edges.
+pub const UNCOND_EDGE: u8 = 2;
+
+/// Address of the block standing for the unknown targets of the first indirect jump, see
+/// `add_indirect_cf`. The blocks of the next ones count down from there.
+pub const UNEXPLORED_ADDR: u64 = u64::MAX - 1;
+
+/// Number of nested reads of a variable through the predecessors of blocks after which the phi
+/// of the block is left without operands until the reads in progress are done, see
+/// `read_variable_recursive`. It keeps the long chains of blocks of big functions from
+/// overflowing the stack.
+pub const MAX_READ_DEPTH: usize = 128;
+
+/// Returns `true` if `address` is the one of a block standing for the unknown targets of an
+/// indirect jump, rather than of an instruction.
+pub fn is_unexplored(address: MAddress) -> bool {
+    address.address > UNEXPLORED_ADDR - u64::from(u32::MAX)
+}
 
 pub struct PhiPlacer<'a, T>
 where
@@ -35,17 +52,34 @@ where
             CFEdgeRef = <T as Graph>::GraphEdgeRef,
         >,
 {
+    // Constants by value and requested width, which is `None` for the ones as wide as a word
+    // by default, so that every constant is added once.
+    consts: HashMap<(u64, Option<u16>), T::ValueRef>,
     current_def: Vec<BTreeMap<MAddress, T::ValueRef>>,
-    incomplete_phis: HashMap<MAddress, HashMap<VarId, T::ValueRef>>,
+    // Phis of sealed blocks whose operands are read once no read is in progress, with their
+    // block and variable, see `MAX_READ_DEPTH`.
+    deferred_phis: Vec<(T::ActionRef, VarId, T::ValueRef)>,
+    // Number of reads in progress through the predecessors of blocks.
+    depth: usize,
+    // Writes of values which don't belong to any block (constants), which are the only ones
+    // whose address is not recorded in `index_to_addr`.
+    const_writes: Vec<(MAddress, VarId, T::ValueRef)>,
+    incomplete_phis: HashMap<MAddress, BTreeMap<VarId, T::ValueRef>>,
     incomplete_propagation: HashSet<T::ValueRef>,
     outputs: HashMap<T::ValueRef, VarId>,
+    // `OpITE`s selecting a value, which unlike the ones ending a block are kept.
+    selects: HashSet<T::ValueRef>,
     pub blocks: BTreeMap<MAddress, T::ActionRef>,
     pub index_to_addr: HashMap<T::ValueRef, MAddress>,
     pub variable_types: Vec<ValueInfo>,
     regfile: &'a SubRegisterFile,
     sealed_blocks: HashSet<T::ActionRef>,
     ssa: &'a mut T,
+    // Blocks ending with a trap, which have no successor, not even the exit node.
+    trap_blocks: HashSet<T::ActionRef>,
     unexplored_addr: u64,
+    // Width of the values whose width is unknown, the native word size of the architecture
+    word_size: u16,
 }
 
 impl<'a, T> PhiPlacer<'a, T>
@@ -62,19 +96,32 @@ where
     pub fn new(ssa: &'a mut T, regfile: &'a SubRegisterFile) -> PhiPlacer<'a, T> {
         PhiPlacer {
             blocks: BTreeMap::new(),
+            consts: HashMap::new(),
+            const_writes: Vec::new(),
             current_def: Vec::new(),
+            deferred_phis: Vec::new(),
+            depth: 0,
             incomplete_phis: HashMap::new(),
             incomplete_propagation: HashSet::new(),
             index_to_addr: HashMap::new(),
             outputs: HashMap::new(),
             regfile: regfile,
             sealed_blocks: HashSet::new(),
+            selects: HashSet::new(),
             ssa: ssa,
-            unexplored_addr: u64::max_value() - 1,
+            trap_blocks: HashSet::new(),
+            unexplored_addr: UNEXPLORED_ADDR,
             variable_types: Vec::new(),
+            word_size: regfile.word_size().unwrap_or(64),
         }
     }
 
+    /// Native word size of the architecture, see `SubRegisterFile::word_size`. It is the default
+    /// width of the values whose width is not known, e.g. constants.
+    pub fn word_size(&self) -> u16 {
+        self.word_size
+    }
+
     /// Add a new variable that the phiplacer should know of.
     /// This information is required to place phi-s. Note that the
     /// phis are generated only for variables defined in this list.
@@ -88,12 +135,39 @@ where
         self.variable_types.extend(variable_types);
     }
 
+    /// Index of `variable` in `variable_types` and `current_def`. Every register of the
+    /// `SubRegisterFile` has to be added first, see `add_variables`.
+    fn var_index(&self, variable: VarId) -> Result<usize, RegFileError> {
+        let count = self.variable_types.len();
+        if variable as usize >= count {
+            return Err(RegFileError::OutOfRange {
+                id: variable as usize,
+                count,
+            });
+        }
+        Ok(variable as usize)
+    }
+
     /// Write/associate a value/node with a defined variable. Usually called when the
     /// expression has an assignment.
     ///
     /// It is up to the caller function to ensure that the sizes are compatible, and insert
     /// appropriate width operations if necessary.
-    pub fn write_variable(&mut self, address: MAddress, variable: VarId, value: T::ValueRef) {
+    ///
+    /// Fails if `variable` was not added with `add_variables`.
+    pub fn write_variable(
+        &mut self,
+        address: MAddress,
+        variable: VarId,
+        value: T::ValueRef,
+    ) -> Result<(), RegFileError> {
+        self.var_index(variable)?;
+        self.write_var(address, variable, value);
+        Ok(())
+    }
+
+    // `write_variable` of a variable known to be added.
+    fn write_var(&mut self, address: MAddress, variable: VarId, value: T::ValueRef) {
         radeco_trace!(
             "phip_write_var|{:?}|{} ({:?})|{}",
             value,
@@ -107,6 +181,9 @@ where
             self.ssa.set_register(value, rname.clone());
         }
 
+        if !self.index_to_addr.contains_key(&value) {
+            self.const_writes.push((address, variable, value));
+        }
         self.current_def[variable as usize].insert(address, value);
         self.outputs.insert(value, variable);
 
@@ -144,12 +221,31 @@ where
         }
     }
 
-    pub fn read_variable(&mut self, address: &mut MAddress, variable: VarId) -> T::ValueRef {
+    /// Value of `variable` at `address`, placing the phis it needs. Fails if `variable` was not
+    /// added with `add_variables`.
+    pub fn read_variable(
+        &mut self,
+        address: &mut MAddress,
+        variable: VarId,
+    ) -> Result<T::ValueRef, RegFileError> {
+        self.var_index(variable)?;
+        Ok(self.read_var(address, variable))
+    }
+
+    // `read_variable` of a variable known to be added.
+    fn read_var(&mut self, address: &mut MAddress, variable: VarId) -> T::ValueRef {
         radeco_trace!("Entering read_variable, variable: {:?}", variable);
-        let v = match self.current_def_in_block(variable, *address).cloned() {
+        let mut v = match self.current_def_in_block(variable, *address).cloned() {
             Some(var) => var,
             None => self.read_variable_recursive(variable, address),
         };
+        if self.depth == 0 && !self.deferred_phis.is_empty() {
+            self.complete_deferred_phis();
+            // The value read may be one of the phis, which turned out to be trivial.
+            if let Some(&def) = self.current_def_in_block(variable, *address) {
+                v = def;
+            }
+        }
         radeco_trace!("Exiting read_variable, return: {:?}", v);
         v
     }
@@ -164,14 +260,25 @@ where
         let val = if self.sealed_blocks.contains(&block) {
             let preds = self.ssa.preds_of(block);
             //assert!(preds.len() > 0);
-            if preds.len() == 1 {
+            if self.depth >= MAX_READ_DEPTH {
+                // Too deep to read through the predecessors now: add the phi, whose operands
+                // are read once the reads in progress are done. It is removed then if trivial.
+                radeco_warn!("phip_rvr|deferring the phi of {} at {}", variable, address);
+                let val = self.add_phi(address, valtype);
+                self.write_var(*address, variable, val);
+                self.deferred_phis.push((block, variable, val));
+                val
+            } else if preds.len() == 1 {
                 // Optimize the common case of one predecessor: No phi needed
                 let mut p_address = self.addr_of(&preds[0]);
-                self.read_variable(&mut p_address, variable)
+                self.depth += 1;
+                let val = self.read_var(&mut p_address, variable);
+                self.depth -= 1;
+                val
             } else {
                 // Break potential cycles with operandless phi
                 let val = self.add_phi(address, valtype);
-                self.write_variable(*address, variable, val);
+                self.write_var(*address, variable, val);
                 self.add_phi_operands(block, variable, val)
             }
         } else {
@@ -202,7 +309,7 @@ where
                 panic!()
             }
         };
-        self.write_variable(*address, variable, val);
+        self.write_var(*address, variable, val);
         radeco_trace!("Exiting read_variabel_recursive");
         val
     }
@@ -281,52 +388,29 @@ where
             // phi) and
             //   this is used to provide the def for this use.
 
-            // Copy all the outgoing CF edges.
-            let invalid_edge = self
-                .ssa
-                .invalid_edge()
-                .expect("Invalid Edge is not defined");
-            let conditional_branches =
-                if let Some(branches) = self.ssa.conditional_edges(upper_block) {
-                    branches
-                } else {
-                    ConditionInfo::new(invalid_edge, invalid_edge)
-                };
-            let outgoing = [
-                conditional_branches.false_side,
-                conditional_branches.true_side,
-                self.ssa
-                    .unconditional_edge(upper_block)
-                    .unwrap_or(invalid_edge),
-            ];
-            for (i, edge) in outgoing.iter().enumerate() {
-                if *edge
-                    != self
-                        .ssa
-                        .invalid_edge()
-                        .expect("Invalid Edge is not defined")
-                {
-                    let target = self
-                        .ssa
-                        .edge_info(*edge)
-                        .unwrap_or_else(|| {
-                            radeco_err!("Less-endpoints edge");
-                            EdgeInfo::new(
-                                self.ssa.invalid_action().unwrap(),
-                                self.ssa.invalid_action().unwrap(),
-                            )
-                        })
-                        .target;
-                    if lower_block != target {
-                        radeco_trace!(
-                            "ADD BLOCK: phip_add_edge|{:?} --{}--> {:?}",
-                            lower_block,
-                            i,
-                            target
-                        );
-                        self.ssa.insert_control_edge(lower_block, target, i as u8);
-                        self.ssa.remove_control_edge(*edge);
-                    }
+            // Copy all the outgoing CF edges. A conditional jump splitting its own block only
+            // has its true edge yet, which has to be moved along with the others.
+            for (edge, i) in self.ssa.outgoing_edges(upper_block) {
+                let target = self
+                    .ssa
+                    .edge_info(edge)
+                    .unwrap_or_else(|| {
+                        radeco_err!("Less-endpoints edge");
+                        EdgeInfo::new(
+                            self.ssa.invalid_action().unwrap(),
+                            self.ssa.invalid_action().unwrap(),
+                        )
+                    })
+                    .target;
+                if lower_block != target {
+                    radeco_trace!(
+                        "ADD BLOCK: phip_add_edge|{:?} --{}--> {:?}",
+                        lower_block,
+                        i,
+                        target
+                    );
+                    self.ssa.insert_control_edge(lower_block, target, i);
+                    self.ssa.remove_control_edge(edge);
                 }
             }
 
@@ -341,8 +425,15 @@ where
             self.blocks.insert(at, lower_block);
 
             // For there is no assignment to addr_to_index, the original code will
-            // skip the loop below, causing losing necessary phi functions
-            for (ni, addr) in self.index_to_addr.clone() {
+            // skip the loop below, causing losing necessary phi functions.
+            // Walk the nodes in address order, so that the walk stops at the end of the lower
+            // block and the phis are placed in the same order on every run.
+            let mut moved = self.value_addresses();
+            moved.sort_by_key(|&(_, addr)| addr);
+            // Values of the upper block which aren't register residences, e.g. the `OpZeroExt`
+            // of a 32-bit register, are redefined in the lower block once for all their uses.
+            let mut redefined = HashMap::new();
+            for (ni, addr) in moved {
                 if addr < at {
                     continue;
                 }
@@ -358,45 +449,25 @@ where
                 for operand_ in &operands {
                     let (i, operand) = *operand_;
                     // If operand is const, it cannot belong to any block.
-                    match self.ssa.opcode(operand) {
-                        Some(MOpcode::OpConst(_)) => {
-                            continue;
-                        }
-                        _ => {}
-                    }
-                    let operand_addr = self.index_to_addr[&operand];
-                    let operand_block = self.block_of(operand_addr).unwrap_or_else(|| {
-                        radeco_err!("Block not found @ {:?}", operand_addr);
-                        self.ssa.invalid_action().unwrap()
-                    });
-                    if operand_block == upper_block {
-                        // Since the current block is not sealed, we can add an incomplete phi and
-                        // modify the constructed SSA to take this phi as operand rather than the
-                        // node.
-                        // This can be summarized in the following operations:
-                        //   - Remove the edge from the operand to ni.
-                        //   - Add a new "incomplete" phi associated with the lower block.
-                        //   - Connect the edge.
-                        // The above two steps can easily be accomplished by doing a read_variable
-                        // and adding an operand edge.
-                        // BUG: Not all operands are the register residences.
-                        // For example, OpZeroExt for 32-bit register. If we want to add a 32-bit register
-                        // with a constant, our algorithm will add an Opwiden(64) node for the register,
-                        // then add the OpZeroExt node with an Opconst. In this situation, OpZeroExt
-                        // node isn't a residence for any register.
-                        if !self.outputs.contains_key(&operand) {
-                            continue;
-                        }
-                        self.ssa.op_unuse(ni, operand);
-                        let output_varid = self.outputs[&operand];
-                        let mut at_ = at;
-                        // BUG: if current define is ni itself, this code may cause ni use itself
-                        // as operand. So we have to do some check.
-                        // A tricky way to solve the bug is to call read_variable_recursive, rather
-                        // than read_variable, this will force the code generate a phi node.
-                        let replacement_phi = self.read_variable_recursive(output_varid, &mut at_);
-                        self.ssa.op_use(ni, i, replacement_phi);
+                    if !self.defined_in(operand, upper_block) {
+                        continue;
                     }
+                    // Since the current block is not sealed, we can add an incomplete phi and
+                    // modify the constructed SSA to take this phi as operand rather than the
+                    // node.
+                    // This can be summarized in the following operations:
+                    //   - Add a new "incomplete" phi associated with the lower block.
+                    //   - Remove the edge from the operand to ni.
+                    //   - Connect the edge.
+                    // The above steps can easily be accomplished by doing a read_variable
+                    // and adding an operand edge.
+                    let replacement =
+                        match self.redefine_in(operand, at, upper_block, &mut redefined) {
+                            Some(replacement) => replacement,
+                            None => continue,
+                        };
+                    self.ssa.op_unuse(ni, operand);
+                    self.ssa.op_use(ni, i, replacement);
                 }
             }
         } else {
@@ -405,6 +476,60 @@ where
         lower_block
     }
 
+    /// Returns whether `value` was added to `block`.
+    fn defined_in(&self, value: T::ValueRef, block: T::ActionRef) -> bool {
+        self.index_to_addr
+            .get(&value)
+            .and_then(|&addr| self.block_of(addr))
+            .map_or(false, |b| b == block)
+    }
+
+    /// Defines again `value`, of the upper part of a block split at `at`, at the start of the
+    /// lower part. The registers are read again, through phis, and the values computed from them
+    /// are redefined along with their operands of the upper part. Returns `None` if `value`
+    /// can't be redefined, e.g. an undefined value.
+    fn redefine_in(
+        &mut self,
+        value: T::ValueRef,
+        at: MAddress,
+        upper_block: T::ActionRef,
+        redefined: &mut HashMap<T::ValueRef, T::ValueRef>,
+    ) -> Option<T::ValueRef> {
+        if let Some(&output_varid) = self.outputs.get(&value) {
+            let mut at_ = at;
+            // BUG: if current define is ni itself, this code may cause ni use itself
+            // as operand. So we have to do some check.
+            // A tricky way to solve the bug is to call read_variable_recursive, rather
+            // than read_variable, this will force the code generate a phi node.
+            return Some(self.read_variable_recursive(output_varid, &mut at_));
+        }
+        if let Some(&redefinition) = redefined.get(&value) {
+            return Some(redefinition);
+        }
+        let opcode = self.ssa.opcode(value)?;
+        // The `OpITE`s ending a block are removed once it is known.
+        if opcode == MOpcode::OpITE && !self.selects.contains(&value) {
+            return None;
+        }
+        let vt = self.ssa.node_data(value).ok()?.vt;
+        let mut at_ = at;
+        let redefinition = self.add_op(&opcode, &mut at_, vt);
+        if opcode == MOpcode::OpITE {
+            self.selects.insert(redefinition);
+        }
+        for (i, operand) in self.ssa.sparse_operands_of(value) {
+            let operand = if self.defined_in(operand, upper_block) {
+                self.redefine_in(operand, at, upper_block, redefined)
+                    .unwrap_or(operand)
+            } else {
+                operand
+            };
+            self.ssa.op_use(redefinition, i, operand);
+        }
+        redefined.insert(value, redefinition);
+        Some(redefinition)
+    }
+
     // Function to add an indirect control flow transfer
     pub fn add_indirect_cf(
         &mut self,
@@ -447,6 +572,16 @@ where
             .insert_control_edge(source_block, exit_node, edge_type);
     }
 
+    /// Ends the block of `current_addr` with a trap: the execution doesn't go past it, so the
+    /// block neither falls through to the next one nor reaches the exit node.
+    pub fn add_trap(&mut self, current_addr: MAddress) {
+        let block = self.block_of(current_addr).unwrap_or_else(|| {
+            radeco_err!("Block not found @ {:?}", current_addr);
+            self.ssa.invalid_action().unwrap()
+        });
+        self.trap_blocks.insert(block);
+    }
+
     pub fn add_edge(&mut self, source: MAddress, target: MAddress, cftype: u8) {
         let source_block = self.block_of(source).unwrap_or_else(|| {
             radeco_err!("Block not found @ {:?}", source);
@@ -473,7 +608,9 @@ where
             radeco_err!("Block not found @ {:?}", source);
             self.ssa.invalid_action().unwrap()
         });
-        if self.ssa.outgoing_edges(source_block).is_empty() {
+        if self.ssa.outgoing_edges(source_block).is_empty()
+            && !self.trap_blocks.contains(&source_block)
+        {
             let target_block = self.block_of(target).unwrap_or_else(|| {
                 radeco_err!("Block not found @ {:?}", target);
                 self.ssa.invalid_action().unwrap()
@@ -533,10 +670,13 @@ where
         radeco_trace!("Entering add_phi_operands, phi: {:?}", phi);
         // Determine operands from predecessors
         let _baddr = self.addr_of(&block);
-        for pred in self.ssa.preds_of(block) {
+        self.depth += 1;
+        // The operands are added in the order of `CFG::ordered_preds_of`, which tells which
+        // predecessor each of them flows in from.
+        for pred in self.ssa.ordered_preds_of(block) {
             let mut p_addr = self.addr_of(&pred);
             radeco_trace!("phip_add_phi_operands|cur:{}|pred:{}", _baddr, p_addr);
-            let datasource = self.read_variable(&mut p_addr, variable);
+            let datasource = self.read_var(&mut p_addr, variable);
             radeco_trace!("datasource: {:?}", datasource);
             self.ssa.phi_use(phi, datasource);
             radeco_trace!("done with phi_use, phi: {:?}, ds: {:?}", phi, datasource);
@@ -544,13 +684,44 @@ where
                 self.propagate_reginfo(&phi);
             }
         }
+        self.depth -= 1;
 
         let v = self.try_remove_trivial_phi(phi);
         radeco_trace!("Exiting add phi operands");
         v
     }
 
+    /// Reads the operands of the phis left without any by `read_variable_recursive`, which may
+    /// leave more of them.
+    fn complete_deferred_phis(&mut self) {
+        while let Some((block, variable, phi)) = self.deferred_phis.pop() {
+            self.add_phi_operands(block, variable, phi);
+        }
+    }
+
+    /// Replaces `phi` if it merges a single value, as well as the phis using it which become
+    /// trivial in turn. Returns the value replacing `phi`, or `phi` itself.
     fn try_remove_trivial_phi(&mut self, phi: T::ValueRef) -> T::ValueRef {
+        let (same, mut users) = self.remove_trivial_phi(phi);
+        // Try to remove all phi users, which might have become trivial. They are kept in a
+        // worklist rather than recursed into, as long chains of them are common.
+        while let Some(use_) = users.pop() {
+            match self.ssa.node_data(use_) {
+                Ok(NodeData {
+                    vt: _,
+                    nt: NodeType::Phi,
+                }) => {
+                    users.extend(self.remove_trivial_phi(use_).1);
+                }
+                _ => {}
+            }
+        }
+        same
+    }
+
+    /// Replaces `phi` if it is trivial. Returns the value replacing it, or `phi` itself, and the
+    /// users of `phi` which have to be checked again.
+    fn remove_trivial_phi(&mut self, phi: T::ValueRef) -> (T::ValueRef, Vec<T::ValueRef>) {
         radeco_trace!("Entering try_remove_trivial_phi, phi: {:?}", phi);
         let undef = self
             .ssa
@@ -566,7 +737,7 @@ where
             if same != undef {
                 // The phi merges at least two values: not trivial
                 radeco_trace!("Exiting try_remove_trivial_phi, return: {:?}", phi);
-                return phi;
+                return (phi, Vec::new());
             }
             same = op
         }
@@ -626,20 +797,8 @@ where
             }
         }
 
-        // Try to recursively remove all phi users, which might have become trivial
-        for use_ in users {
-            match self.ssa.node_data(use_) {
-                Ok(NodeData {
-                    vt: _,
-                    nt: NodeType::Phi,
-                }) => {
-                    self.try_remove_trivial_phi(use_);
-                }
-                _ => {}
-            }
-        }
         radeco_trace!("Exiting(2) try_remove_trivial_phi, ret: {:?}", same);
-        same
+        (same, users)
     }
 
     pub fn add_dynamic(&mut self) -> T::ActionRef {
@@ -649,16 +808,19 @@ where
         });
         let dyn_addr = MAddress::new(u64::MAX, 0);
         self.blocks.insert(dyn_addr, action);
-        self.incomplete_phis.insert(dyn_addr, HashMap::new());
+        self.incomplete_phis.insert(dyn_addr, BTreeMap::new());
         self.sync_register_state(action);
         action
     }
 
     pub fn sync_register_state(&mut self, block: T::ActionRef) {
         let rs = registers_in_err!(self.ssa, block, self.ssa.invalid_value().unwrap());
-        for var in 0..self.variable_types.len() {
+        // The variables added after the memory are internal to the construction of the SSA, e.g.
+        // its memory regions, and aren't part of the register state.
+        let state_len = self.regfile.mem_id().to_usize() + 1;
+        for var in 0..self.variable_types.len().min(state_len) {
             let mut addr = self.addr_of(&block);
-            let val = self.read_variable(&mut addr, var as u64);
+            let val = self.read_var(&mut addr, var as u64);
             self.ssa.op_use(rs, var as u8, val);
         }
     }
@@ -699,35 +861,52 @@ where
         vt_option: Option<ValueInfo>,
     ) -> T::ValueRef {
         if vt_option.is_none() {
-            return self.ssa.insert_const(value, None).unwrap_or_else(|| {
-                radeco_err!("Cannot insert new constants");
-                self.ssa.invalid_value().unwrap()
-            });
+            return self.const_node(value, None);
         }
         let vt = vt_option.unwrap();
-        let width = vt.width().get_width().unwrap_or(64);
+        let width = vt.width().get_width().unwrap_or(self.word_size);
         if width < 64 {
-            let val: u64 = value & (1 << (width) - 1);
-            let const_node = self.ssa.insert_const(val, Some(width)).unwrap_or_else(|| {
-                radeco_err!("Cannot insert new constants");
-                self.ssa.invalid_value().unwrap()
-            });
+            let val: u64 = value & ((1 << width) - 1);
+            let const_node = self.const_node(val, Some(width));
             let opcode = MOpcode::OpNarrow(width as u16);
             let narrow_node = self.add_op(&opcode, address, vt);
             self.op_use(&narrow_node, 0, &const_node);
             narrow_node
         } else {
-            let const_node = self
-                .ssa
-                .insert_const(value, Some(width))
-                .unwrap_or_else(|| {
-                    radeco_err!("Cannot insert new constants");
-                    self.ssa.invalid_value().unwrap()
-                });
-            const_node
+            self.const_node(value, vt.width().get_width())
         }
     }
 
+    /// Returns the constant `value` truncated to `width` bits, as wide as `width`.
+    pub fn add_sized_const(&mut self, value: u64, width: u16) -> T::ValueRef {
+        let value = if width < 64 {
+            value & ((1 << width) - 1)
+        } else {
+            value
+        };
+        self.const_node(value, Some(width))
+    }
+
+    // Returns the constant `value` as wide as `width`, or as a word if it is `None`. The node is
+    // only added the first time, the constants are shared by all their users.
+    fn const_node(&mut self, value: u64, width: Option<u16>) -> T::ValueRef {
+        if let Some(&node) = self.consts.get(&(value, width)) {
+            // It may have been removed since.
+            if self.ssa.constant(node) == Some(value) {
+                return node;
+            }
+        }
+        let node = self
+            .ssa
+            .insert_const(value, Some(width.unwrap_or(self.word_size)))
+            .unwrap_or_else(|| {
+                radeco_err!("Cannot insert new constants");
+                self.ssa.invalid_value().unwrap()
+            });
+        self.consts.insert((value, width), node);
+        node
+    }
+
     pub fn add_undefined(&mut self, address: MAddress, vt: ValueInfo) -> T::ValueRef {
         let i = self.ssa.insert_undefined(vt).unwrap_or_else(|| {
             radeco_err!("Cannot insert new undefined nodes");
@@ -761,6 +940,16 @@ where
         i
     }
 
+    /// Adds the value of the register `reg` on entry of the function.
+    pub fn add_argument(&mut self, address: MAddress, vt: ValueInfo, reg: String) -> T::ValueRef {
+        let i = self.ssa.insert_argument(vt, reg).unwrap_or_else(|| {
+            radeco_err!("Cannot insert new arguments");
+            self.ssa.invalid_value().unwrap()
+        });
+        self.index_to_addr.insert(i, address);
+        i
+    }
+
     // TODO: Add a more convenient method to add an opcode and operands to it.
     // Something like the previous verified_add_op.
 
@@ -774,6 +963,24 @@ where
         i
     }
 
+    /// Adds an `OpITE` selecting `then` if `cond` holds and `otherwise` if it doesn't, without
+    /// any control flow.
+    pub fn add_select(
+        &mut self,
+        address: &mut MAddress,
+        vt: ValueInfo,
+        cond: &T::ValueRef,
+        then: &T::ValueRef,
+        otherwise: &T::ValueRef,
+    ) -> T::ValueRef {
+        let i = self.add_op(&MOpcode::OpITE, address, vt);
+        self.op_use(&i, 0, cond);
+        self.op_use(&i, 1, then);
+        self.op_use(&i, 2, otherwise);
+        self.selects.insert(i);
+        i
+    }
+
     pub fn set_address(&mut self, node: &T::ValueRef, address: MAddress) {
         self.index_to_addr.insert(*node, address);
     }
@@ -791,7 +998,14 @@ where
             }
         };
         let id = info.base;
-        let mut value = self.read_variable(address, id);
+        let mut value = match self.read_variable(address, id) {
+            Ok(value) => value,
+            Err(_e) => {
+                radeco_warn!("phip_read_reg|{}: {}", var, _e);
+                let vi = ValueInfo::new_scalar(ir::WidthSpec::Unknown);
+                return self.add_undefined(*address, vi);
+            }
+        };
 
         let width = self.operand_width(&value);
 
@@ -834,10 +1048,26 @@ where
         };
         let id = info.base;
 
-        let vt = self.variable_types[id as usize];
-        let width = vt.width().get_width().unwrap_or(64);
+        let vt = match self.var_index(id) {
+            Ok(index) => self.variable_types[index],
+            Err(_e) => {
+                radeco_warn!("phip_write_reg|{}: {}", var, _e);
+                return;
+            }
+        };
+        let width = vt.width().get_width().unwrap_or(self.word_size);
+
+        // The whole register is written, with the value of the subregister zero extended.
+        let clears_upper = info.clears_upper && info.width < width as u64;
+        if clears_upper && self.operand_width(&value) as u64 > info.width {
+            let sub_width = info.width as u16;
+            let sub_vt = ValueInfo::new_scalar(ir::WidthSpec::Known(sub_width));
+            let narrow_node = self.add_op(&MOpcode::OpNarrow(sub_width), address, sub_vt);
+            self.op_use(&narrow_node, 0, &value);
+            value = narrow_node;
+        }
 
-        if info.width >= width as u64 {
+        if info.width >= width as u64 || clears_upper {
             // Register width should be corresponding with its residence's width.
             value = match width.cmp(&self.operand_width(&value)) {
                 Ordering::Equal => {
@@ -858,7 +1088,7 @@ where
                     width_node
                 }
             };
-            self.write_variable(*address, id, value);
+            self.write_var(*address, id, value);
             self.ssa.set_register(
                 value,
                 self.regfile
@@ -893,11 +1123,11 @@ where
         let maskval: u64 = ((!((!1u64) << (info.width - 1))) << info.shift) ^ fullval;
 
         if maskval == 0 {
-            self.write_variable(*address, id, value);
+            self.write_var(*address, id, value);
             return;
         }
 
-        let mut ov = self.read_variable(address, id);
+        let mut ov = self.read_var(address, id);
         let maskvalue_node = self.add_const(address, maskval, Some(vt));
 
         let op_and = self.add_op(&MOpcode::OpAnd, address, vt);
@@ -910,7 +1140,7 @@ where
         self.op_use(&op_or, 0, &value);
         self.op_use(&op_or, 1, &ov);
         value = op_or;
-        self.write_variable(*address, id, value);
+        self.write_var(*address, id, value);
         self.propagate_reginfo(&value);
     }
 
@@ -920,14 +1150,26 @@ where
 
     pub fn operand_width(&self, node: &T::ValueRef) -> u16 {
         match self.ssa.node_data(*node) {
-            Ok(x) => x.vt.width().get_width().unwrap_or(64),
+            Ok(x) => x.vt.width().get_width().unwrap_or(self.word_size),
             Err(_e) => {
                 radeco_err!("{:?}", _e);
-                64
+                self.word_size
             }
         }
     }
 
+    pub fn is_constant(&self, node: &T::ValueRef) -> bool {
+        self.ssa.constant(*node).is_some()
+    }
+
+    pub fn is_phi(&self, node: &T::ValueRef) -> bool {
+        self.ssa.is_phi(*node)
+    }
+
+    pub fn constant(&self, node: &T::ValueRef) -> Option<u64> {
+        self.ssa.constant(*node)
+    }
+
     fn new_block(&mut self, bb: MAddress) -> T::ActionRef {
         if let Some(b) = self.blocks.get(&bb) {
             *b
@@ -936,54 +1178,45 @@ where
                 radeco_err!("Cannot insert new blocks");
                 self.ssa.invalid_action().unwrap()
             });
-            self.incomplete_phis.insert(bb, HashMap::new());
+            self.incomplete_phis.insert(bb, BTreeMap::new());
             block
         }
     }
 
-    // For constant, we should narrow it rather widening another OpCode
+    /// Materializes the constant operand of an operation as wide as the other operand, if it is
+    /// narrower, rather than widening the other one. E.g. the `5` of `cmp al, 5` is an 8-bit
+    /// constant. The constants are shared by width, see `add_sized_const`.
     pub fn narrow_const_operand(
         &mut self,
-        address: &mut MAddress,
         lhs: &mut Option<T::ValueRef>,
         rhs: &mut Option<T::ValueRef>,
     ) {
-        if lhs.is_none() || rhs.is_none() {
-            return;
-        }
-
-        let lhs_size = lhs.map_or(0, |i| self.operand_width(&i));
-        let rhs_size = rhs.map_or(0, |i| self.operand_width(&i));
+        let (lhs_node, rhs_node) = match (*lhs, *rhs) {
+            (Some(l), Some(r)) => (l, r),
+            _ => return,
+        };
 
         // Narrowing will happen only if there is one constant and one normal opcode.
-        if lhs.map_or(false, |i| self.ssa.constant(i).is_some())
-            ^ rhs.map_or(false, |i| self.ssa.constant(i).is_some())
-        {
-            let (victim, victim_size) = if lhs_size < 64 {
-                (rhs, lhs_size)
-            } else {
-                (lhs, rhs_size)
+        let (victim, value, width) =
+            match (self.ssa.constant(lhs_node), self.ssa.constant(rhs_node)) {
+                (Some(value), None) => (lhs, value, self.operand_width(&rhs_node)),
+                (None, Some(value)) => (rhs, value, self.operand_width(&lhs_node)),
+                _ => return,
             };
-            if victim_size < 64 {
-                let victim_node = victim.unwrap_or_else(|| {
-                    radeco_err!("Wrong victim");
-                    self.ssa.invalid_value().unwrap()
-                });
-                let vtype = ValueInfo::new_unresolved(ir::WidthSpec::from(victim_size));
-                let opc = MOpcode::OpNarrow(victim_size as u16);
-                let node = self.add_op(&opc, address, vtype);
-                self.op_use(&node, 0, &victim_node);
-                *victim = Some(node);
-            }
+        let victim_size = victim.map_or(0, |i| self.operand_width(&i));
+        if width > 0 && width < victim_size {
+            *victim = Some(self.add_sized_const(value, width));
         }
     }
 
     // Determine which block address should belong to.
     // This basically translates to finding the greatest address that is less that
-    // `address` in self.blocks. This is fairly simple as self.blocks is sorted.
+    // `address` in self.blocks, within the segment of `address`. This is fairly simple as
+    // self.blocks is sorted, segment first.
     pub fn block_of(&self, address: MAddress) -> Option<T::ActionRef> {
+        let segment_start = MAddress::segmented(address.segment(), 0, 0);
         self.blocks
-            .range((Included(&MAddress::new(0, 0)), Included(&address)))
+            .range((Included(&segment_start), Included(&address)))
             .last()
             .map(|(_, &b)| b)
     }
@@ -1051,7 +1284,7 @@ where
         let blocks = self.ssa.blocks();
         let exit_node = exit_node_err!(self.ssa);
         for block in blocks {
-            if self.ssa.succs_of(block).len() == 0 {
+            if self.ssa.succs_of(block).len() == 0 && !self.trap_blocks.contains(&block) {
                 self.ssa.insert_control_edge(block, exit_node, UNCOND_EDGE);
             }
         }
@@ -1059,6 +1292,28 @@ where
 
     // Performs SSA finish operation such as assigning the blocks in the final
     // graph, sealing blocks, running basic dead code elimination etc.
+    /// Returns the address of every value still in the SSA.
+    pub fn value_addresses(&self) -> Vec<(T::ValueRef, MAddress)> {
+        self.ssa
+            .values()
+            .into_iter()
+            .filter_map(|node| self.index_to_addr.get(&node).map(|&addr| (node, addr)))
+            .collect()
+    }
+
+    /// Returns the constants written to a register, along with the address of the write and the
+    /// name of the register. Unlike other values, constants are not placed in any block.
+    pub fn const_writes(&self) -> Vec<(T::ValueRef, MAddress, String)> {
+        self.const_writes
+            .iter()
+            .filter(|&&(_, _, value)| self.ssa.node_data(value).is_ok())
+            .filter_map(|&(addr, variable, value)| {
+                let name = self.regfile.whole_names.get(variable as usize)?;
+                Some((value, addr, name.clone()))
+            })
+            .collect()
+    }
+
     pub fn finish(&mut self, ops: &[LOpInfo]) {
         // Iterate through blocks and seal them. Also associate nodes with their
         // respective blocks.
@@ -1105,6 +1360,7 @@ where
         for block in wl {
             self.seal_block(block);
         }
+        self.complete_deferred_phis();
 
         for node in &self.ssa.values() {
             if let Some(addr) = self.index_to_addr.get(node).cloned() {
@@ -1112,6 +1368,9 @@ where
                 // Mark selector.
                 if let Ok(ndata) = self.ssa.node_data(*node) {
                     if let NodeType::Op(MOpcode::OpITE) = ndata.nt {
+                        if self.selects.contains(node) {
+                            continue;
+                        }
                         let block = self.block_of(addr);
                         if let Some(cond_node) = self.ssa.operands_of(*node).get(0) {
                             self.ssa.set_selector(*cond_node, block.unwrap());
@@ -1124,18 +1383,31 @@ where
             }
         }
 
-        // Associate basic block with correct block sizes.
+        // Associate basic block with correct block sizes. Overlapping instruction streams may
+        // be disassembled at interleaved offsets, so the ops are walked in order of offsets.
+        let mut ops = ops
+            .iter()
+            .map(|op| (op.offset.unwrap_or(0), op.size.unwrap_or(0)))
+            .collect::<Vec<_>>();
+        ops.sort();
         for opn in ops.windows(2) {
-            let op1 = &opn[0];
-            let op2 = &opn[1];
-            let off1 = MAddress::new(op1.offset.unwrap_or(0), 0);
-            let off2 = MAddress::new(op2.offset.unwrap_or(0), 0);
+            let (offset1, size1) = opn[0];
+            let (offset2, _) = opn[1];
+            if offset1.saturating_add(size1) > offset2 {
+                radeco_warn!(
+                    "Instruction at {:#x} overlaps the one at {:#x}",
+                    offset1,
+                    offset2
+                );
+            }
+            let off1 = MAddress::new(offset1, 0);
+            let off2 = MAddress::new(offset2, 0);
 
             match (self.block_of(off1), self.block_of(off2)) {
                 (Some(b1), Some(b2)) if b1 == b2 => { /* Nothing to do */ }
                 (Some(b1), Some(_)) => {
                     if let Some(start) = self.ssa.starting_address(b1) {
-                        let size = off1.address - start.address;
+                        let size = off1.address.saturating_sub(start.address);
                         self.ssa.set_block_size(b1, size);
                     }
                 }
@@ -1144,3 +1416,153 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ssa::cfg_traits::CFG;
+    use crate::middle::ssa::ssa_traits::SSA;
+    use crate::middle::ssa::ssastorage::SSAStorage;
+    use crate::utils::test_support::reg_profile;
+
+    #[test]
+    fn constants_are_shared() {
+        let reg_profile = reg_profile();
+        let regfile = SubRegisterFile::new(&reg_profile).unwrap();
+        let mut ssa = SSAStorage::new();
+        let mut phip = PhiPlacer::new(&mut ssa, &regfile);
+        let mut addr = MAddress::new(0x1000, 0);
+        let vt = |width| Some(ValueInfo::new_scalar(ir::WidthSpec::Known(width)));
+
+        let c = phip.add_const(&mut addr, 0x10, vt(64));
+        assert_eq!(phip.add_const(&mut addr, 0x10, vt(64)), c);
+        assert_ne!(phip.add_const(&mut addr, 0x11, vt(64)), c);
+        // The constants of an unknown width are distinct from the ones of a known width.
+        let word = phip.add_const(&mut addr, 0x10, None);
+        assert_ne!(word, c);
+        assert_eq!(phip.add_const(&mut addr, 0x10, None), word);
+
+        // Narrower constants are narrowed from the same 32-bit constant.
+        let n1 = phip.add_const(&mut addr, 0x10, vt(32));
+        let n2 = phip.add_const(&mut addr, 0x10, vt(32));
+        assert_ne!(n1, n2);
+        let narrowed = phip.ssa.operands_of(n1);
+        assert_eq!(narrowed, phip.ssa.operands_of(n2));
+        assert_eq!(phip.ssa.constant(narrowed[0]), Some(0x10));
+    }
+
+    #[test]
+    fn segmented_blocks_are_distinct() {
+        let reg_profile = reg_profile();
+        let regfile = SubRegisterFile::new(&reg_profile).unwrap();
+        let mut ssa = SSAStorage::new();
+        let mut phip = PhiPlacer::new(&mut ssa, &regfile);
+        let (low, high) = (MAddress::new(0x1000, 0), MAddress::segmented(1, 0x1000, 0));
+
+        let low_block = phip.add_block(low, None, None);
+        let high_block = phip.add_block(high, Some(low), Some(UNCOND_EDGE));
+        assert_ne!(low_block, high_block);
+        assert_eq!(phip.block_of(MAddress::new(0x1004, 0)), Some(low_block));
+        assert_eq!(
+            phip.block_of(MAddress::segmented(1, 0x1004, 0)),
+            Some(high_block)
+        );
+        // The first block of a segment doesn't extend the last one of the previous segment.
+        assert_eq!(phip.block_of(MAddress::segmented(1, 0xfff, 0)), None);
+
+        // Jumping back into the first segment splits its block, and only it.
+        let split = phip.add_block(MAddress::new(0x1002, 0), Some(high), Some(UNCOND_EDGE));
+        assert_eq!(phip.block_of(MAddress::new(0x1004, 0)), Some(split));
+        assert_eq!(phip.block_of(MAddress::new(0x1000, 0)), Some(low_block));
+        assert_eq!(
+            phip.block_of(MAddress::segmented(1, 0x1004, 0)),
+            Some(high_block)
+        );
+        assert_eq!(phip.ssa.starting_address(high_block), Some(high));
+        assert_eq!(phip.ssa.starting_address(high_block).unwrap().segment(), 1);
+    }
+
+    #[test]
+    fn overlapping_ops_sizes() {
+        let reg_profile = reg_profile();
+        let regfile = SubRegisterFile::new(&reg_profile).unwrap();
+        let mut ssa = SSAStorage::new();
+        let mut phip = PhiPlacer::new(&mut ssa, &regfile);
+        let (entry, first, second) = (
+            MAddress::new(0, 0),
+            MAddress::new(0x1000, 0),
+            MAddress::new(0x1008, 0),
+        );
+        let entry_block = phip.add_block(entry, None, None);
+        phip.mark_entry_node(&entry_block);
+        let first_block = phip.add_block(first, Some(entry), Some(UNCOND_EDGE));
+        let second_block = phip.add_block(second, Some(first), Some(UNCOND_EDGE));
+
+        // Out of order, and the instruction at 0x1002 overlaps the one at 0x1000.
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[{"offset":4096,"size":4},
+                {"offset":4108,"size":1},
+                {"offset":4098,"size":4},
+                {"offset":4104,"size":4}]"#,
+        )
+        .unwrap();
+        phip.finish(&ops);
+
+        assert_eq!(phip.ssa.block_size(first_block), Some(2));
+        for block in vec![entry_block, first_block, second_block] {
+            assert!(phip.ssa.block_size(block).map_or(true, |size| size < 0x10));
+        }
+    }
+
+    #[test]
+    fn long_chain_of_blocks() {
+        let reg_profile = reg_profile();
+        let regfile = SubRegisterFile::new(&reg_profile).unwrap();
+        let mut ssa = SSAStorage::new();
+        let mut phip = PhiPlacer::new(&mut ssa, &regfile);
+        let vt = ValueInfo::new_scalar(ir::WidthSpec::Known(64));
+        phip.add_variables(vec![vt]);
+
+        let entry = MAddress::new(0, 0);
+        let entry_block = phip.add_block(entry, None, None);
+        phip.mark_entry_node(&entry_block);
+        let arg = phip.add_argument(entry, vt, "rax".to_owned());
+        phip.write_variable(entry, 0, arg).unwrap();
+
+        // Many more blocks than `MAX_READ_DEPTH`, each one following the previous one.
+        let mut blocks = vec![entry_block];
+        let mut last = entry;
+        for i in 1..(MAX_READ_DEPTH as u64 * 16) {
+            let at = MAddress::new(0x1000 + i * 4, 0);
+            blocks.push(phip.add_block(at, Some(last), Some(UNCOND_EDGE)));
+            last = at;
+        }
+        for block in blocks {
+            phip.seal_block(block);
+        }
+
+        // The phis deferred on the way merge the entry value only, and are removed.
+        assert_eq!(phip.read_variable(&mut last, 0).unwrap(), arg);
+        assert!(phip.deferred_phis.is_empty());
+        assert!(phip.ssa.values().iter().all(|&v| !phip.ssa.is_phi(v)));
+    }
+
+    #[test]
+    fn unknown_variable_is_an_error() {
+        let reg_profile = reg_profile();
+        let regfile = SubRegisterFile::new(&reg_profile).unwrap();
+        let mut ssa = SSAStorage::new();
+        let mut phip = PhiPlacer::new(&mut ssa, &regfile);
+        let vt = ValueInfo::new_scalar(ir::WidthSpec::Known(64));
+        phip.add_variables(vec![vt]);
+
+        let mut entry = MAddress::new(0, 0);
+        let entry_block = phip.add_block(entry, None, None);
+        phip.mark_entry_node(&entry_block);
+        let arg = phip.add_argument(entry, vt, "rax".to_owned());
+
+        let err = RegFileError::OutOfRange { id: 1, count: 1 };
+        assert_eq!(phip.write_variable(entry, 1, arg), Err(err.clone()));
+        assert_eq!(phip.read_variable(&mut entry, 1), Err(err));
+    }
+}
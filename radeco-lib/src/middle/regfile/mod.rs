@@ -20,15 +20,20 @@ use crate::middle::ir;
 use crate::middle::ssa::ssa_traits::ValueInfo;
 
 use r2papi::structs::{LCCInfo, LRegInfo};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::convert::From;
+use std::{error, fmt};
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct SubRegister {
     pub base: u64,
     pub shift: u64,
     pub width: u64,
+    /// Whether a write to this register zeroes the bits of the whole register above it, instead
+    /// of preserving them.
+    pub clears_upper: bool,
 }
 
 impl SubRegister {
@@ -37,17 +42,73 @@ impl SubRegister {
             base: base,
             shift: shift,
             width: width,
+            clears_upper: false,
         }
     }
 }
 
+/// An inconsistency of a register profile or of a `SubRegisterFile`, or an access to a register
+/// which doesn't exist.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegFileError {
+    /// The register `name` overlaps the whole register `whole` without being contained in it.
+    PartialOverlap { name: String, whole: String },
+    /// There are more whole registers than a `RegisterId` can identify.
+    TooManyRegisters(usize),
+    /// `whole_names` and `whole_registers` don't have the same length.
+    NameCountMismatch { names: usize, registers: usize },
+    /// The role `role` is an alias of `reg`, which is not a register of the profile.
+    UnknownAlias { role: String, reg: String },
+    /// The register `name` is part of a whole register which doesn't exist.
+    UnknownWholeRegister { name: String, base: u64 },
+    /// `id` doesn't identify any of the `count` whole registers.
+    OutOfRange { id: usize, count: usize },
+}
+
+impl fmt::Display for RegFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &RegFileError::PartialOverlap {
+                ref name,
+                ref whole,
+            } => {
+                write!(f, "Register {} partially overlaps {}", name, whole)
+            }
+            &RegFileError::TooManyRegisters(count) => write!(
+                f,
+                "{} whole registers, at most {} are supported",
+                count,
+                u8::max_value()
+            ),
+            &RegFileError::NameCountMismatch { names, registers } => {
+                write!(f, "{} names for {} whole registers", names, registers)
+            }
+            &RegFileError::UnknownAlias { ref role, ref reg } => {
+                write!(f, "{} is an alias of the unknown register {}", role, reg)
+            }
+            &RegFileError::UnknownWholeRegister { ref name, base } => write!(
+                f,
+                "Register {} is part of the whole register {}, which doesn't exist",
+                name, base
+            ),
+            &RegFileError::OutOfRange { id, count } => write!(
+                f,
+                "Register id {} is out of range, there are {} whole registers",
+                id, count
+            ),
+        }
+    }
+}
+
+impl error::Error for RegFileError {}
+
 /// A structure containing information about whole and partial registers of a platform.
 /// Upon creation it builds a vector of `ValueType`s representing whole registers
 /// to be added to a `PhiPlacer`.
 ///
 /// It can then translate accesses to partial registers to accesses of whole registers.
 /// Shifts and masks are added automatically.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct SubRegisterFile {
     /// `ValueType`s of whole registers ready to be added to a `PhiPlacer`.
     /// The index within `PhiPlacer` to the first register is needed
@@ -82,7 +143,10 @@ impl Iterator for RegisterIter {
 
 impl SubRegisterFile {
     /// Creates a new SubRegisterFile based on a provided register profile.
-    pub fn new(reg_info: &LRegInfo) -> SubRegisterFile {
+    ///
+    /// Fails if the profile is inconsistent, see `validate`, or if a register partially overlaps
+    /// another one.
+    pub fn new(reg_info: &LRegInfo) -> Result<SubRegisterFile, RegFileError> {
         let mut aliases: HashMap<String, String> = HashMap::new();
         for reg in &reg_info.alias_info {
             aliases.insert(reg.role_str.clone(), reg.reg.clone());
@@ -115,6 +179,7 @@ impl SubRegisterFile {
             }
         });
 
+        let zero_extends = zero_extends_32(reg_info);
         let mut current = SubRegister::new(0, 0, 0);
         let mut whole: Vec<ValueInfo> = Vec::new();
         let mut names: Vec<String> = Vec::new();
@@ -130,24 +195,67 @@ impl SubRegisterFile {
                     current.width as u16,
                 )));
                 names.push(name.clone());
-            } else {
-                let ev_until = ev.width + ev.shift;
-                assert!(ev_until <= cur_until);
+            } else if ev.width + ev.shift > cur_until {
+                return Err(RegFileError::PartialOverlap {
+                    name: name.clone(),
+                    whole: names.last().cloned().unwrap_or_default(),
+                });
             }
 
-            let subreg =
+            let mut subreg =
                 SubRegister::new(whole.len() as u64 - 1, ev.shift - current.shift, ev.width);
+            // Narrower writes (e.g. `ax`, `al`) preserve the upper bits.
+            subreg.clears_upper = zero_extends
+                && reg_info.reg_info[ev.base as usize].type_str == "gpr"
+                && subreg.shift == 0
+                && subreg.width == 32
+                && current.width == 64;
 
             slices.insert(name.clone(), subreg);
         }
 
-        SubRegisterFile {
+        let regfile = SubRegisterFile {
             whole_registers: whole,
             named_registers: slices,
             whole_names: names,
             alias_info: aliases,
             type_info: types,
+        };
+        regfile.validate()?;
+        Ok(regfile)
+    }
+
+    /// Checks that there is a name for every whole register, that they can all be identified by
+    /// a `RegisterId`, and that every alias and every subregister refers to a known register.
+    pub fn validate(&self) -> Result<(), RegFileError> {
+        let count = self.whole_registers.len();
+        if self.whole_names.len() != count {
+            return Err(RegFileError::NameCountMismatch {
+                names: self.whole_names.len(),
+                registers: count,
+            });
+        }
+        // `mem_id` comes after the last whole register.
+        if count > u8::max_value() as usize {
+            return Err(RegFileError::TooManyRegisters(count));
+        }
+        for (role, reg) in &self.alias_info {
+            if !self.type_info.contains_key(reg) && !self.named_registers.contains_key(reg) {
+                return Err(RegFileError::UnknownAlias {
+                    role: role.clone(),
+                    reg: reg.clone(),
+                });
+            }
+        }
+        for (name, subreg) in &self.named_registers {
+            if subreg.base as usize >= count {
+                return Err(RegFileError::UnknownWholeRegister {
+                    name: name.clone(),
+                    base: subreg.base,
+                });
+            }
         }
+        Ok(())
     }
 
     // API for Sub Reigster.
@@ -155,6 +263,19 @@ impl SubRegisterFile {
         self.named_registers.get(name).cloned()
     }
 
+    /// Sets whether a write to the register `name` zeroes the bits of the whole register above
+    /// it, overriding the rule derived from the register profile. Returns `false` if there is no
+    /// register `name`.
+    pub fn set_clears_upper(&mut self, name: &str, clears_upper: bool) -> bool {
+        match self.named_registers.get_mut(name) {
+            Some(subreg) => {
+                subreg.clears_upper = clears_upper;
+                true
+            }
+            None => false,
+        }
+    }
+
     // Get id for a register named `reg`
     pub fn register_id_by_name(&self, reg: &str) -> Option<RegisterId> {
         self.named_registers
@@ -171,6 +292,17 @@ impl SubRegisterFile {
         }
     }
 
+    /// Native word size of the architecture, in bits. This is the width of the program counter,
+    /// or of the stack pointer if the profile doesn't name one.
+    pub fn word_size(&self) -> Option<u16> {
+        ["PC", "SP"]
+            .iter()
+            .filter_map(|alias| self.register_id_by_alias(alias))
+            .filter_map(|id| self.get_width(id))
+            .map(|width| width as u16)
+            .next()
+    }
+
     // API for whole register.
 
     // Get information by id.
@@ -190,16 +322,32 @@ impl SubRegisterFile {
         }
     }
 
+    /// `ValueInfo` of the whole register `id`.
+    pub fn whole_register(&self, id: RegisterId) -> Result<&ValueInfo, RegFileError> {
+        self.whole_registers
+            .get(id.to_usize())
+            .ok_or_else(|| self.out_of_range(id))
+    }
+
+    /// Name of the whole register `id`. Unlike `get_name`, the error tells how many registers
+    /// there are.
+    pub fn whole_name(&self, id: RegisterId) -> Result<&str, RegFileError> {
+        self.get_name(id).ok_or_else(|| self.out_of_range(id))
+    }
+
+    fn out_of_range(&self, id: RegisterId) -> RegFileError {
+        RegFileError::OutOfRange {
+            id: id.to_usize(),
+            count: self.whole_registers.len(),
+        }
+    }
+
     // Get information by other way.
+    // `alias_info` maps the roles to the registers.
     pub fn get_name_by_alias(&self, alias: &String) -> Option<&str> {
-        for id in 0..self.whole_names.len() {
-            if let Some(name) = self.get_name(RegisterId::from_usize(id)) {
-                if self.alias_info.get(name) == Some(alias) {
-                    return Some(name);
-                }
-            }
-        }
-        None
+        let rname = self.alias_info.get(alias)?;
+        self.register_id_by_name(rname)
+            .and_then(|id| self.get_name(id))
     }
 
     pub fn iter_args(&self) -> RegisterIter {
@@ -265,6 +413,37 @@ impl SubRegisterFile {
         Some(ret)
     }
 
+    /// Same as `r2callconv_to_register_usage`, for a calling convention supplied by the user.
+    /// If `clobbers` is given, every whole register it doesn't list, apart from the return
+    /// register, is preserved; otherwise the preserved registers are the ones of `callconv_name`.
+    pub fn custom_callconv_to_register_usage(
+        &self,
+        callconv: &LCCInfo,
+        callconv_name: &str,
+        clobbers: Option<&[String]>,
+    ) -> Option<RegisterUsage> {
+        let clobbers = match clobbers {
+            Some(clobbers) => clobbers,
+            None => return self.r2callconv_to_register_usage(callconv, callconv_name),
+        };
+        // no preserved register so far
+        let mut ret = self.r2callconv_to_register_usage(callconv, "")?;
+        let mut clobbered = clobbers
+            .iter()
+            .map(|regname| self.register_id_by_name(regname))
+            .collect::<Option<Vec<_>>>()?;
+        if let Some(ref regname) = callconv.ret {
+            clobbered.push(self.register_id_by_name(regname)?);
+        }
+        for reg_id in (0..self.whole_registers.len()).map(RegisterId::from_usize) {
+            if !clobbered.contains(&reg_id) {
+                ret.set_preserved(reg_id);
+            }
+        }
+
+        Some(ret)
+    }
+
     /// Creates an empty `RegisterMap`.
     pub fn new_register_map<V>(&self) -> RegisterMap<V> {
         RegisterMap::with_register_count(self.whole_registers.len() + 1)
@@ -288,6 +467,26 @@ impl RegisterId {
 }
 
 // TODO: if r2 ever starts keeping track of preserved registers, use that instead of this
+/// Returns `true` if `reg_info` is the profile of x86-64 or AArch64, on which writing the lower
+/// 32 bits of a 64-bit general purpose register (e.g. `eax`, `w0`) zeroes its upper 32 bits.
+///
+/// The profile doesn't name its architecture, which is recognized from the program counter: `rip`
+/// on x86-64, and `pc` along with the `w0` view of `x0` on AArch64. The other architectures have
+/// to call `SubRegisterFile::set_clears_upper` for the registers behaving this way.
+fn zero_extends_32(reg_info: &LRegInfo) -> bool {
+    let has_reg = |name: &str| reg_info.reg_info.iter().any(|r| r.name == name);
+    let pc = reg_info
+        .alias_info
+        .iter()
+        .find(|a| a.role_str == "PC")
+        .map(|a| &*a.reg);
+    match pc {
+        Some("rip") => true,
+        Some("pc") => has_reg("x0") && has_reg("w0"),
+        _ => false,
+    }
+}
+
 /// For a given named calling convention, return the set of registers it
 /// preserves across calls (are callee-saved).
 ///
@@ -319,3 +518,109 @@ fn callconv_name_to_preserved_list(cc_name: &str) -> &'static [&'static str] {
         _ => &[],
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+
+    // `rsp` is not part of the profile.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const UNKNOWN_ALIAS_PROFILE: &str = r#"{
+        "alias_info": [
+            {"role": 0, "role_str": "PC", "reg": "rip"},
+            {"role": 1, "role_str": "SP", "reg": "rsp"}
+        ],
+        "reg_info": [
+            {"type": 0, "type_str": "gpr", "name": "rax", "size": 64, "offset": 0},
+            {"type": 0, "type_str": "gpr", "name": "rip", "size": 64, "offset": 64}
+        ]
+    }"#;
+
+    // `ah` straddles `rax` and `rbx`.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const OVERLAP_PROFILE: &str = r#"{
+        "alias_info": [],
+        "reg_info": [
+            {"type": 0, "type_str": "gpr", "name": "rax", "size": 64, "offset": 0},
+            {"type": 0, "type_str": "gpr", "name": "ah", "size": 16, "offset": 56},
+            {"type": 0, "type_str": "gpr", "name": "rbx", "size": 64, "offset": 64}
+        ]
+    }"#;
+
+    #[test]
+    fn inconsistent_profile() {
+        let reg_info: LRegInfo = serde_json::from_str(UNKNOWN_ALIAS_PROFILE).unwrap();
+        let err = SubRegisterFile::new(&reg_info).unwrap_err();
+        assert_eq!(
+            err,
+            RegFileError::UnknownAlias {
+                role: "SP".to_owned(),
+                reg: "rsp".to_owned(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "SP is an alias of the unknown register rsp"
+        );
+
+        let reg_info: LRegInfo = serde_json::from_str(OVERLAP_PROFILE).unwrap();
+        assert_eq!(
+            SubRegisterFile::new(&reg_info).unwrap_err(),
+            RegFileError::PartialOverlap {
+                name: "ah".to_owned(),
+                whole: "rax".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn clears_upper_by_arch() {
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        const AARCH64_PROFILE: &str = r#"{
+            "alias_info": [{"role": 0, "role_str": "PC", "reg": "pc"}],
+            "reg_info": [
+                {"type": 0, "type_str": "gpr", "name": "x0", "size": 64, "offset": 0},
+                {"type": 0, "type_str": "gpr", "name": "w0", "size": 32, "offset": 0},
+                {"type": 0, "type_str": "gpr", "name": "pc", "size": 64, "offset": 64}
+            ]
+        }"#;
+        let aarch64: LRegInfo = serde_json::from_str(AARCH64_PROFILE).unwrap();
+        let regfile = SubRegisterFile::new(&aarch64).unwrap();
+        assert!(regfile.get_subregister("w0").unwrap().clears_upper);
+
+        // Another 64-bit architecture, with the same layout but without `w0`.
+        let other = AARCH64_PROFILE.replace("\"w0\"", "\"a0\"");
+        let other: LRegInfo = serde_json::from_str(&other).unwrap();
+        let mut regfile = SubRegisterFile::new(&other).unwrap();
+        assert!(!regfile.get_subregister("a0").unwrap().clears_upper);
+        assert!(regfile.set_clears_upper("a0", true));
+        assert!(regfile.get_subregister("a0").unwrap().clears_upper);
+        assert!(!regfile.set_clears_upper("w0", true));
+    }
+
+    #[test]
+    fn whole_register_out_of_range() {
+        let mut reg_info: LRegInfo = serde_json::from_str(UNKNOWN_ALIAS_PROFILE).unwrap();
+        reg_info.alias_info.pop();
+        let regfile = SubRegisterFile::new(&reg_info).unwrap();
+        assert_eq!(regfile.whole_name(RegisterId::from_usize(1)), Ok("rip"));
+        assert!(regfile.whole_register(RegisterId::from_usize(1)).is_ok());
+        let err = regfile.whole_name(regfile.mem_id()).unwrap_err();
+        assert_eq!(err, RegFileError::OutOfRange { id: 2, count: 2 });
+        assert_eq!(
+            err.to_string(),
+            "Register id 2 is out of range, there are 2 whole registers"
+        );
+
+        let mut inconsistent = regfile.clone();
+        inconsistent.whole_names.pop();
+        assert_eq!(
+            inconsistent.validate(),
+            Err(RegFileError::NameCountMismatch {
+                names: 1,
+                registers: 2,
+            })
+        );
+    }
+}
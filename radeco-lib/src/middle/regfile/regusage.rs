@@ -1,5 +1,6 @@
 use super::RegisterId;
 use fixedbitset::FixedBitSet;
+use serde::{Deserialize, Serialize};
 
 /// The set of registers (possibly including the memory "register") that a
 /// function reads and/or preserves.
@@ -10,11 +11,13 @@ use fixedbitset::FixedBitSet;
 /// **Implementation note:** This stores which registers a function *ignores*
 /// instead of what it reads. This is so the `Default` implementation of
 /// "reads and clobbers everything" is safe to assign to unanalyzed functions.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RegisterUsage {
     /// Registers that are *not* parameters
+    #[serde(with = "bitset")]
     ignores: FixedBitSet,
     /// Callee-saved registers
+    #[serde(with = "bitset")]
     preserves: FixedBitSet,
 }
 
@@ -79,3 +82,29 @@ impl RegisterUsage {
         self.preserves.set_range(.., false)
     }
 }
+
+/// (De)serializes a `FixedBitSet` as its length and the indices of the bits it sets, as the length
+/// tells the immutable instances apart.
+mod bitset {
+    use fixedbitset::FixedBitSet;
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::{Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(set: &FixedBitSet, serializer: S) -> Result<S::Ok, S::Error> {
+        (set.len(), set.ones().collect::<Vec<_>>()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<FixedBitSet, D::Error> {
+        let (len, ones) = <(usize, Vec<usize>)>::deserialize(deserializer)?;
+        let mut set = FixedBitSet::with_capacity(len);
+        for i in ones {
+            if i >= len {
+                return Err(de::Error::custom("register index out of range"));
+            }
+            set.insert(i);
+        }
+        Ok(set)
+    }
+}
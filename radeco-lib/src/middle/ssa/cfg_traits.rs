@@ -37,6 +37,7 @@
 //!  Note: Reference in the docs refers to any type that is used to index
 //!  nodes and edges in the graph and not necessarily __pointers__.
 
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::hash::Hash;
 
@@ -69,6 +70,20 @@ pub trait CFG: Graph {
     /// Reference to immediate successors of block
     fn succs_of(&self, node: Self::ActionRef) -> Vec<Self::ActionRef>;
 
+    /// Predecessors of `block` in order of starting addresses, each one once. The `i`-th operand
+    /// of a phi of `block` flows in from the `i`-th of them.
+    fn ordered_preds_of(&self, block: Self::ActionRef) -> Vec<Self::ActionRef> {
+        let mut preds = self.preds_of(block);
+        preds.sort_by_key(|&pred| self.starting_address(pred));
+        let mut ordered = Vec::with_capacity(preds.len());
+        for pred in preds {
+            if !ordered.contains(&pred) {
+                ordered.push(pred);
+            }
+        }
+        ordered
+    }
+
     /// Reference to the next block in the natural flow of the CFG
     fn unconditional_block(&self, i: Self::ActionRef) -> Option<Self::ActionRef>;
 
@@ -102,6 +117,49 @@ pub trait CFG: Graph {
 
     /// Reference that represents an Invalid control flow edge.
     fn invalid_edge(&self) -> Option<Self::CFEdgeRef>;
+
+    ///////////////////////////////////////////////////////////////////////////
+    //// Traversals
+    ///////////////////////////////////////////////////////////////////////////
+
+    /// Blocks and actions reachable from the entry node, in postorder: every one of them comes
+    /// after its successors, except along the edges closing a loop. Blocks which can't be reached
+    /// from the entry node are left out. The exit node and the dynamic actions are visited as any
+    /// other action when they are reached, and having no successors, they come before every block
+    /// flowing into them.
+    fn postorder(&self) -> Vec<Self::ActionRef> {
+        let entry = match self.entry_node() {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+        let mut postorder = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![(entry, self.succs_of(entry), 0)];
+        visited.insert(entry);
+
+        while let Some(&mut (block, ref succs, ref mut next)) = stack.last_mut() {
+            if let Some(&succ) = succs.get(*next) {
+                *next += 1;
+                if visited.insert(succ) {
+                    stack.push((succ, self.succs_of(succ), 0));
+                }
+            } else {
+                postorder.push(block);
+                stack.pop();
+            }
+        }
+        postorder
+    }
+
+    /// Blocks and actions reachable from the entry node, in reverse postorder: every one of them
+    /// comes before its successors, except along the edges closing a loop. This is the order in
+    /// which forward dataflow analyses converge the fastest. See `postorder` for the blocks left
+    /// out.
+    fn reverse_postorder(&self) -> Vec<Self::ActionRef> {
+        let mut order = self.postorder();
+        order.reverse();
+        order
+    }
 }
 
 /// Provides __mutators__ to the underlying storage
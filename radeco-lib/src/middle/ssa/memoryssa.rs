@@ -249,7 +249,7 @@ where
                         }
                     }
 
-                    NodeType::Comment(reg) => {
+                    NodeType::Comment(reg) | NodeType::Argument(reg) => {
                         if self.check_local(reg.clone()) {
                             self.local_nodes.insert(*node);
                         }
@@ -0,0 +1,40 @@
+//! The traits and types needed to walk and modify the SSA of a function, e.g. from a pass
+//! written outside of this crate: `use radeco_lib::middle::ssa::prelude::*;`.
+//!
+//! The SSA of a `RadecoFunction` is reached through `RadecoFunction::ssa` and
+//! `RadecoFunction::ssa_mut`. The methods a pass needs are split over a few traits:
+//!
+//!  * `SSA`: `operands_of`, `uses_of`, `node_data`, `opcode`, `constant`, `registers`,
+//!    `exprs_in`, `block_for`, `address`;
+//!  * `SSAMod`: `insert_op`, `insert_const`, `op_use`, `op_unuse`, `insert_into_block`,
+//!    `replace_value`, `remove_value`;
+//!  * `SSAExtra`: the comments, flags and variables attached to the values;
+//!  * `CFG` and `Graph`: the blocks and the edges between them, the entry and exit nodes.
+//!
+//! The SSA stays consistent as long as a pass sticks to the following rules:
+//!
+//!  * A new operation is only visible once it is placed in a block with `insert_into_block`,
+//!    usually at the address of the value it is computed for. Its operands are set with `op_use`,
+//!    in order from index 0, e.g. the memory state, the address and the value of an `OpStore`.
+//!  * A value is replaced with `replace_value`, which moves its uses, selector and register
+//!    annotations to the replacement and removes it. `remove_value` is only for the values nothing
+//!    uses anymore, or whose users are removed too.
+//!  * The operations changing the width of a register value (e.g. `OpNarrow`, `OpZeroExt`) are
+//!    annotated with the registers of their operand through `utils::propagate_registers`, as the
+//!    later analyses locate the registers through the annotations.
+//!  * Constants are shared by the whole function: they are created with `insert_const` and never
+//!    placed in a block, nor replaced.
+//!
+//! `verify` checks the control flow and the number and widths of the operands of every operation,
+//! and is worth running after a pass under development.
+
+pub use crate::middle::ir::{MAddress, MOpcode, WidthSpec};
+pub use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+pub use crate::middle::ssa::graph_traits::Graph;
+pub use crate::middle::ssa::ssa_traits::{
+    NodeData, NodeType, SSAExtra, SSAMod, SSAWalk, ValueInfo, SSA,
+};
+pub use crate::middle::ssa::ssastorage::SSAStorage;
+pub use crate::middle::ssa::utils::propagate_registers;
+pub use crate::middle::ssa::verifier::verify;
+pub use petgraph::graph::NodeIndex;
@@ -27,6 +27,7 @@ use std::hash::Hash;
 
 use super::cfg_traits::{CFGMod, CFG};
 use crate::middle::ir;
+use serde::{Deserialize, Serialize};
 
 #[macro_export]
 macro_rules! entry_node_err {
@@ -61,7 +62,7 @@ macro_rules! registers_in_err {
     };
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 /// Defines the high level `type` of value for a node. It is only used to differentiate between
 /// references and non-reference (scalar) types.
 pub enum ValueType {
@@ -75,7 +76,7 @@ pub enum ValueType {
     Invalid,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 /// Information for a `ValueRef`
 pub struct ValueInfo {
     /// `Type` of value
@@ -174,6 +175,8 @@ pub enum NodeType {
     Undefined,
     /// Generic comment, used to represent other data
     Comment(String),
+    /// Value of the named register on entry of the function.
+    Argument(String),
 }
 
 // Implement display helper for NodeData to make it a little nicer to read prefix notation.
@@ -183,6 +186,7 @@ impl fmt::Display for NodeType {
             &NodeType::Op(ref op) => format!("{}", op),
             &NodeType::Phi => "Phi".to_owned(),
             &NodeType::Comment(ref s) => s.clone(),
+            &NodeType::Argument(ref s) => format!("${}", s),
             // Don't care about these
             _ => String::new(),
         };
@@ -220,6 +224,9 @@ pub trait SSA: CFG {
     /// Returns true if the node is a comment node
     fn is_comment(&self, i: Self::ValueRef) -> bool;
 
+    /// Returns true if the node is the value of a register on entry of the function.
+    fn is_argument(&self, i: Self::ValueRef) -> bool;
+
     fn is_constant(&self, exi: Self::ValueRef) -> bool;
     fn constant_value(&self, exi: Self::ValueRef) -> Option<u64>;
 
@@ -265,6 +272,9 @@ pub trait SSA: CFG {
     /// Get comment information, as a pack of get_node_data on a Comment data.
     fn comment(&self, i: Self::ValueRef) -> Option<String>;
 
+    /// Get the register an Argument node is the entry value of.
+    fn argument(&self, i: Self::ValueRef) -> Option<String>;
+
     /// Get OpCode information, as a pack of get_node_data on a Comment data.
     fn opcode(&self, i: Self::ValueRef) -> Option<ir::MOpcode>;
 
@@ -286,6 +296,9 @@ pub trait SSAMod: SSA + CFGMod {
     /// Set the node as selector for the control edges away from the specified basic block
     fn set_selector(&mut self, node: Self::ValueRef, block: Self::ActionRef);
 
+    /// Change the opcode of an operation node, keeping its operands and uses
+    fn set_opcode(&mut self, node: Self::ValueRef, opc: ir::MOpcode);
+
     /// Insert a new operation node.
     fn insert_op(
         &mut self,
@@ -306,10 +319,14 @@ pub trait SSAMod: SSA + CFGMod {
     /// Add a new comment node
     fn insert_comment(&mut self, vt: ValueInfo, msg: String) -> Option<Self::ValueRef>;
 
+    /// Add a new argument node, the value of register `reg` on entry of the function
+    fn insert_argument(&mut self, vt: ValueInfo, reg: String) -> Option<Self::ValueRef>;
+
     /// Associate a node with index n with a block
     fn insert_into_block(&mut self, node: Self::ValueRef, block: Self::ActionRef, _: ir::MAddress);
 
-    /// Add a data source to a phi node.
+    /// Add a data source to a phi node, flowing in from the predecessor following the ones of
+    /// the operands already added, see `CFG::ordered_preds_of`.
     fn phi_use(&mut self, phi: Self::ValueRef, node: Self::ValueRef);
 
     /// Remove a data source from a phi node.
@@ -344,6 +361,7 @@ pub trait SSAExtra: SSA {
     fn set_color(&mut self, _: &Self::ValueRef, _: u8) {}
     fn set_comment(&mut self, _: &Self::ValueRef, _: String) {}
     fn add_flag(&mut self, _: &Self::ValueRef, _: String) {}
+    fn set_variable(&mut self, _: &Self::ValueRef, _: String) {}
     fn is_marked(&self, _: &Self::ValueRef) -> bool {
         false
     }
@@ -356,6 +374,11 @@ pub trait SSAExtra: SSA {
         None
     }
 
+    /// Name of the variable the value belongs to, see `analysis::variables`.
+    fn variable(&self, _: &Self::ValueRef) -> Option<String> {
+        None
+    }
+
     fn addr(&self, _: &Self::ValueRef) -> Option<String> {
         None
     }
@@ -369,4 +392,12 @@ pub trait SSAWalk<I: Iterator<Item = <Self as SSA>::ValueRef>>: SSA {
     fn bfs_walk(&self) -> I;
     fn inorder_walk(&self) -> I;
     fn dfs_walk(&self) -> I;
+    /// Walks the blocks in reverse postorder (see `CFG::reverse_postorder`), each followed by its
+    /// phis and expressions, in the order of their addresses. Forward dataflow analyses converge
+    /// the fastest in this order.
+    fn reverse_postorder_walk(&self) -> I;
+    /// Walks the nodes of `reverse_postorder_walk` backwards: the blocks in postorder, each
+    /// preceded by its phis and expressions, last ones first. This is the order of choice for
+    /// backward dataflow analyses.
+    fn postorder_walk(&self) -> I;
 }
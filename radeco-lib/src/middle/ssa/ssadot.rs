@@ -5,16 +5,19 @@
 // This file may not be copied, modified, or distributed
 // except according to those terms.
 
-//! Implements the `GraphDot` trait for `SSAStorage`
+//! Implements the `GraphDot` and `GraphMl` traits for `SSAStorage`, and emits the bare control
+//! flow graph of an `SSAStorage` in Dot.
 
 use petgraph::graph;
 use petgraph::graph::NodeIndex;
 use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use petgraph::EdgeDirection;
 
 use super::ssa_traits::{SSAExtra, SSA};
 use super::ssastorage::{EdgeData, NodeData, SSAStorage};
-use crate::middle::dot::{DotAttrBlock, GraphDot};
+use crate::middle::dot::{DotAttrBlock, GraphDot, GraphMl};
 use crate::middle::ir::MOpcode;
+use crate::middle::phiplacement;
 use crate::middle::ssa::cfg_traits::CFG;
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -103,7 +106,7 @@ impl GraphDot for SSAStorage {
             EdgeData::Data(_) => {
                 // Skip data edges from registers to registerstates.
                 if let NodeData::RegisterState = self.g[edge.source()] {
-                    self.comment(edge.target()).is_some()
+                    self.comment(edge.target()).is_some() || self.is_argument(edge.target())
                 } else {
                     false
                 }
@@ -230,7 +233,7 @@ impl GraphDot for SSAStorage {
                 );
                 attrs
             }
-            NodeData::Comment(_, ref msg) => vec![
+            NodeData::Comment(_, ref msg) | NodeData::Argument(_, ref msg) => vec![
                 (
                     "label".to_string(),
                     format!("\"{}\"", msg.replace("\"", "\\\"")),
@@ -279,3 +282,219 @@ impl GraphDot for SSAStorage {
         DotAttrBlock::Hybrid(prefix, attr)
     }
 }
+
+///////////////////////////////////////////////////////////////////////////////
+//// Implementation of GraphMl to export SSAStorage.
+///////////////////////////////////////////////////////////////////////////////
+
+// Every node and edge of the graph is exported, apart from the placeholders of the removed ones.
+// The edges have the same direction as in the graph, e.g. from an operation to its operands.
+impl GraphMl for SSAStorage {
+    fn node_keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("kind", "string"),
+            ("opcode", "string"),
+            ("width", "int"),
+            ("address", "string"),
+            ("register", "string"),
+            ("label", "string"),
+        ]
+    }
+
+    fn edge_keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[("kind", "string"), ("operand", "int"), ("flow", "string")]
+    }
+
+    fn graphml_nodes(&self) -> Vec<(usize, Vec<Option<String>>)> {
+        let mut nodes = Vec::new();
+        for i in self.g.node_indices() {
+            let (kind, opcode, vi, label) = match self.g[i] {
+                NodeData::Op(ref opc, vi) => {
+                    let label = match *opc {
+                        MOpcode::OpConst(c) => Some(format!("{:#x}", c)),
+                        _ => None,
+                    };
+                    ("op", Some(opc.to_string().into_owned()), Some(vi), label)
+                }
+                NodeData::Phi(vi, _) => ("phi", None, Some(vi), None),
+                NodeData::Comment(vi, ref msg) => ("comment", None, Some(vi), Some(msg.clone())),
+                NodeData::Argument(vi, ref reg) => ("argument", None, Some(vi), Some(reg.clone())),
+                NodeData::Undefined(vi) => ("undefined", None, Some(vi), None),
+                NodeData::BasicBlock(..) => ("block", None, None, None),
+                NodeData::DynamicAction => ("action", None, None, None),
+                NodeData::RegisterState => ("register_state", None, None, None),
+                NodeData::Removed | NodeData::Unreachable => continue,
+            };
+            let (width, address, registers) = match self.g[i] {
+                NodeData::BasicBlock(addr, _) => (None, Some(format!("{}", addr)), Vec::new()),
+                _ if vi.is_some() => (
+                    vi.and_then(|vi| vi.width().get_width()),
+                    self.addr(&i),
+                    self.registers(i),
+                ),
+                _ => (None, None, Vec::new()),
+            };
+            let register = if registers.is_empty() {
+                None
+            } else {
+                Some(registers.join(","))
+            };
+            nodes.push((
+                i.index(),
+                vec![
+                    Some(kind.to_owned()),
+                    opcode,
+                    width.map(|w| w.to_string()),
+                    address,
+                    register,
+                    label,
+                ],
+            ));
+        }
+        nodes
+    }
+
+    fn graphml_edges(&self) -> Vec<(usize, usize, Vec<Option<String>>)> {
+        let removed = |n: NodeIndex| match self.g[n] {
+            NodeData::Removed | NodeData::Unreachable => true,
+            _ => false,
+        };
+        let mut edges = Vec::new();
+        for edge in self.g.edge_references() {
+            if removed(edge.source()) || removed(edge.target()) {
+                continue;
+            }
+            let (kind, operand, flow) = match *edge.weight() {
+                EdgeData::Control(i) => {
+                    let flow = match i {
+                        0 => "false",
+                        1 => "true",
+                        _ => "unconditional",
+                    };
+                    ("control", None, Some(flow.to_owned()))
+                }
+                EdgeData::Data(i) => ("data", Some(i.to_string()), None),
+                EdgeData::ContainedInBB(_) => ("contained_in", None, None),
+                EdgeData::RegisterInfo => ("register_info", None, None),
+                EdgeData::Selector => ("selector", None, None),
+                EdgeData::RegisterState => ("register_state", None, None),
+                EdgeData::ReplacedBy => ("replaced_by", None, None),
+            };
+            edges.push((
+                edge.source().index(),
+                edge.target().index(),
+                vec![Some(kind.to_owned()), operand, flow],
+            ));
+        }
+        edges
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+//// Emit Dot for the control flow graph of SSAStorage.
+///////////////////////////////////////////////////////////////////////////////
+
+/// Emits only the basic blocks of `ssa` and the control flow edges between them, like `agf` in
+/// radare2. Blocks are labeled by the range of addresses they span, end excluded, and edges by
+/// whether they are taken when the branch is true (T), false (F) or unconditionally (U). The exit
+/// node and the blocks standing for the unknown targets of indirect jumps are drawn apart.
+pub fn emit_cfg_dot(ssa: &SSAStorage) -> String {
+    let mut result = String::new();
+    result.push_str(
+        "digraph cfg {\ngraph [fontsize=12 fontname=\"Verdana\" rankdir=TB;]\n\
+         node [fontname=\"Verdana\"];\n",
+    );
+    let exit = ssa.exit_node();
+    let mut blocks = ssa.blocks();
+    blocks.extend(exit);
+    blocks.sort();
+
+    for &block in &blocks {
+        let attrs = match ssa.starting_address(block) {
+            _ if Some(block) == exit => "shape=doublecircle label=\"exit\"".to_owned(),
+            Some(addr) if phiplacement::is_unexplored(addr) => {
+                "shape=box style=dashed label=\"indirect\"".to_owned()
+            }
+            Some(addr) => {
+                let size = ssa.block_size(block).unwrap_or(0);
+                let label = if size == 0 {
+                    format!("{:#x}", addr.address)
+                } else {
+                    format!("{:#x}-{:#x}", addr.address, addr.address + size)
+                };
+                format!("shape=box label=\"{}\"", label)
+            }
+            None => continue,
+        };
+        result.push_str(&format!("n{} [{}];\n", block.index(), attrs));
+    }
+
+    for &block in &blocks {
+        let mut edges = ssa
+            .g
+            .edges_directed(block, EdgeDirection::Outgoing)
+            .filter_map(|edge| match *edge.weight() {
+                EdgeData::Control(i) if blocks.contains(&edge.target()) => Some((i, edge.target())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        edges.sort();
+        for (i, target) in edges {
+            let (color, label) = match i {
+                0 => ("red", "F"),
+                1 => ("green", "T"),
+                _ => ("blue", "U"),
+            };
+            result.push_str(&format!(
+                "n{} -> n{} [color={} label=\"{}\"];\n",
+                block.index(),
+                target.index(),
+                color,
+                label
+            ));
+        }
+    }
+
+    result.push_str("}\n");
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::dot::{count_xml_elements, emit_graphml};
+    use crate::utils::test_support::load_ssa;
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const BRANCH_SSA_TXT: &str = "\
+define-fun sym.branch(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %4: $Unknown1 = %1 < %2;
+        JMP IF %4 0x001006.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x2):
+        RETURN
+    bb_0x001006.0000(sz 0x2):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %1;
+        $mem = %3;
+}
+";
+
+    #[test]
+    fn graphml_is_well_formed() {
+        let ssa = load_ssa(BRANCH_SSA_TXT);
+
+        let graphml = emit_graphml(&ssa);
+        assert_eq!(count_xml_elements(&graphml, "node"), Ok(ssa.g.node_count()));
+        assert_eq!(count_xml_elements(&graphml, "edge"), Ok(ssa.g.edge_count()));
+        assert!(graphml.contains("<data key=\"node_opcode\">OpLt</data>"));
+        assert!(graphml.contains("<data key=\"edge_flow\">true</data>"));
+        assert!(graphml.contains("<data key=\"node_register\">rdi"));
+    }
+}
@@ -13,6 +13,7 @@ use petgraph::graph::{EdgeIndex, NodeIndex};
 use petgraph::stable_graph::StableDiGraph;
 use petgraph::visit::{EdgeRef, IntoEdgeReferences};
 use petgraph::EdgeDirection;
+use serde::{Deserialize, Serialize};
 use std::cmp::{Ordering, PartialEq, PartialOrd};
 use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::fmt::{self, Debug};
@@ -30,12 +31,14 @@ use utils::logger;
 
 /// Structure that represents data that maybe associated with an node in the
 /// SSA
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AdditionalData {
     comments: Option<String>,
     flag: Option<String>,
     mark: bool,
     color: Option<u8>,
+    #[serde(default)]
+    variable: Option<String>,
 }
 
 impl AdditionalData {
@@ -45,6 +48,7 @@ impl AdditionalData {
             flag: None,
             mark: false,
             color: None,
+            variable: None,
         }
     }
 }
@@ -56,6 +60,7 @@ impl default::Default for AdditionalData {
             flag: None,
             mark: false,
             color: None,
+            variable: None,
         }
     }
 }
@@ -64,12 +69,12 @@ pub type AssociatedData = HashMap<NodeIndex, AdditionalData>;
 
 /// Both actions and values are represented using this same enum.
 ///
-/// Value nodes are `Op`, `Phi`, `Comment`, `Undefined` and `Removed`.
+/// Value nodes are `Op`, `Phi`, `Comment`, `Argument`, `Undefined` and `Removed`.
 /// Action nodes are `Unreachable`, `BasicBlock`, `DynamicAction`
 /// `RegisterState` is neither.
 /// Value nodes have a `ValueInfo` that can be extracted with
 /// `SSA::get_node_data`
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum NodeData {
     /// Represents on operation.
     Op(MOpcode, ValueInfo),
@@ -77,6 +82,8 @@ pub enum NodeData {
     Phi(ValueInfo, String),
     /// Represents an undefined node with a comment.
     Comment(ValueInfo, String),
+    /// Represents the value of the named register on entry of the function.
+    Argument(ValueInfo, String),
     /// Represents an undefined node without comment.
     Undefined(ValueInfo),
     /// Placeholder for value nodes.
@@ -99,7 +106,8 @@ impl NodeData {
         match *self {
             NodeData::Op(_, ref mut vif)
             | NodeData::Phi(ref mut vif, _)
-            | NodeData::Comment(ref mut vif, _) => *vif = vi,
+            | NodeData::Comment(ref mut vif, _)
+            | NodeData::Argument(ref mut vif, _) => *vif = vi,
             _ => {}
         }
     }
@@ -108,7 +116,8 @@ impl NodeData {
         match self {
             &NodeData::Op(_, ref vif)
             | &NodeData::Phi(ref vif, _)
-            | &NodeData::Comment(ref vif, _) => Some(vif),
+            | &NodeData::Comment(ref vif, _)
+            | &NodeData::Argument(ref vif, _) => Some(vif),
             _ => None,
         }
     }
@@ -117,7 +126,8 @@ impl NodeData {
         match *self {
             NodeData::Op(_, ref mut vif)
             | NodeData::Phi(ref mut vif, _)
-            | NodeData::Comment(ref mut vif, _) => Some(vif),
+            | NodeData::Comment(ref mut vif, _)
+            | NodeData::Argument(ref mut vif, _) => Some(vif),
             _ => None,
         }
     }
@@ -130,6 +140,7 @@ impl fmt::Display for NodeData {
             NodeData::Op(ref op, _) => format!("{}", op),
             NodeData::Phi(_, _) => "Phi".to_owned(),
             NodeData::Comment(_, ref s) => s.clone(),
+            NodeData::Argument(_, ref s) => format!("${}", s),
             // Don't care about these
             _ => String::new(),
         };
@@ -138,7 +149,7 @@ impl fmt::Display for NodeData {
 }
 
 /// Edge type for the SSAStorage-internal petgraph.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum EdgeData {
     /// Edge from action to action. Represents control flow. The number is
     /// used to distinguish true branch, false branch, etc.
@@ -165,13 +176,16 @@ pub enum EdgeData {
 }
 
 /// A petgraph based SSA storage.
-#[derive(Debug, Clone)]
+///
+/// The register file is not serialized, it has to be set again after deserializing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SSAStorage {
     pub g: StableDiGraph<NodeData, EdgeData>,
     entry_node: NodeIndex,
     exit_node: NodeIndex,
     pub assoc_data: AssociatedData,
     pub replaced_map: HashMap<NodeIndex, NodeIndex>,
+    #[serde(skip)]
     pub regfile: Arc<SubRegisterFile>,
 }
 
@@ -209,6 +223,12 @@ impl SSAStorage {
             })
             .collect::<HashMap<_, _>>()
     }
+
+    /// Returns the register an entry value stands for: the register of an `Argument`, or the
+    /// comment of the other values of the entry register state (e.g. `mem`).
+    pub fn name_of(&self, i: NodeIndex) -> Option<String> {
+        self.argument(i).or_else(|| self.comment(i))
+    }
 }
 
 /// //////////////////////////////////////////////////////////////////////////
@@ -281,7 +301,8 @@ impl Graph for SSAStorage {
                         self.op_use(othernode, d, j);
                     }
                     Some(&NodeData::Phi(_, _)) => {
-                        self.phi_use(othernode, j);
+                        // The index tells the predecessor the operand flows in from.
+                        self.insert_edge(othernode, j, EdgeData::Data(d));
                     }
                     _ => {}
                 }
@@ -775,6 +796,13 @@ impl SSA for SSAStorage {
         }
     }
 
+    fn is_argument(&self, exi: Self::ValueRef) -> bool {
+        match self.g.node_weight(exi) {
+            Some(&NodeData::Argument(_, _)) => true,
+            _ => false,
+        }
+    }
+
     fn is_constant(&self, exi: Self::ValueRef) -> bool {
         match self.g.node_weight(exi) {
             Some(&NodeData::Op(MOpcode::OpConst(_), _)) => true,
@@ -894,7 +922,13 @@ impl SSA for SSAStorage {
     fn operands_of(&self, exi: Self::ValueRef) -> Vec<Self::ValueRef> {
         let mut args = self.sparse_operands_of(exi);
         args.sort_by(|a, b| a.0.cmp(&b.0));
-        args.iter().map(|a| a.1).collect()
+        let mut operands = args.iter().map(|a| a.1).collect::<Vec<_>>();
+        // A value flowing into a phi from several predecessors is a single operand.
+        if self.is_phi(exi) {
+            let mut seen = HashSet::new();
+            operands.retain(|&op| seen.insert(op));
+        }
+        operands
     }
 
     fn sparse_operands_of(&self, exi: Self::ValueRef) -> Vec<(u8, Self::ValueRef)> {
@@ -939,6 +973,10 @@ impl SSA for SSAStorage {
                 vt: vt,
                 nt: TNodeType::Comment(s.clone()),
             }),
+            Some(&NodeData::Argument(vt, ref s)) => Ok(TNodeData {
+                vt: vt,
+                nt: TNodeType::Argument(s.clone()),
+            }),
             Some(&NodeData::Undefined(vt)) => Ok(TNodeData {
                 vt: vt,
                 nt: TNodeType::Undefined,
@@ -972,10 +1010,19 @@ impl SSA for SSAStorage {
         }
     }
 
+    fn argument(&self, i: Self::ValueRef) -> Option<String> {
+        match self.g.node_weight(i) {
+            Some(&NodeData::Argument(_, ref reg)) => Some(reg.clone()),
+            _ => None,
+        }
+    }
+
     fn registers(&self, i: Self::ValueRef) -> Vec<String> {
         let mut regs = Vec::new();
         // Self-loop in RadecoIL is not welcomed ;D
-        // Thus, Comment Node in entry_node will not have a RegisterInfo edge pointing to itself.
+        // Thus, the entry values (Argument nodes, or the `mem` Comment node) will not have a
+        // RegisterInfo edge pointing to themselves.
+        regs.extend(self.argument(i));
         if let Some(s) = self.comment(i) {
             if self.block_for(i) == self.entry_node() {
                 regs.push(s);
@@ -988,7 +1035,7 @@ impl SSA for SSAStorage {
         while let Some((edge, othernode)) = walk.next(&self.g) {
             match self.g.edge_weight(edge) {
                 Some(&EdgeData::RegisterInfo) => {
-                    if let Some(regname) = self.comment(othernode) {
+                    if let Some(regname) = self.name_of(othernode) {
                         regs.push(regname);
                     }
                 }
@@ -1036,7 +1083,7 @@ impl SSAMod for SSAStorage {
         let reg_state = registers_in_err!(self, self.entry_node);
         let operands = self.operands_of(reg_state);
         for op in operands {
-            if Some(regname.clone()) == self.comment(op) {
+            if Some(&regname) == self.name_of(op).as_ref() {
                 self.insert_edge(i, op, EdgeData::RegisterInfo);
                 break;
             }
@@ -1047,6 +1094,12 @@ impl SSAMod for SSAStorage {
         self.insert_edge(block, node, EdgeData::Selector);
     }
 
+    fn set_opcode(&mut self, node: Self::ValueRef, opc: MOpcode) {
+        if let Some(&mut NodeData::Op(ref mut op, _)) = self.g.node_weight_mut(node) {
+            *op = opc;
+        }
+    }
+
     fn insert_op(&mut self, opc: MOpcode, vt: ValueInfo, _: Option<u64>) -> Option<Self::ValueRef> {
         Some(
             self.insert_node(NodeData::Op(opc, vt))
@@ -1072,12 +1125,22 @@ impl SSAMod for SSAStorage {
         self.insert_node(NodeData::Comment(vt, msg))
     }
 
+    fn insert_argument(&mut self, vt: ValueInfo, reg: String) -> Option<Self::ValueRef> {
+        self.insert_node(NodeData::Argument(vt, reg))
+    }
+
     fn insert_into_block(&mut self, node: Self::ValueRef, block: Self::ActionRef, at: MAddress) {
         self.insert_edge(node, block, EdgeData::ContainedInBB(at));
     }
 
     fn phi_use(&mut self, phi: Self::ValueRef, node: Self::ValueRef) {
-        self.insert_edge(phi, node, EdgeData::Data(0));
+        let index = self
+            .sparse_operands_of(phi)
+            .into_iter()
+            .map(|(i, _)| i.saturating_add(1))
+            .max()
+            .unwrap_or(0);
+        self.insert_edge(phi, node, EdgeData::Data(index));
     }
 
     fn phi_unuse(&mut self, phi: Self::ValueRef, node: Self::ValueRef) {
@@ -1193,6 +1256,14 @@ impl SSAExtra for SSAStorage {
         data.flag = Some(f);
     }
 
+    fn set_variable(&mut self, i: &Self::ValueRef, name: String) {
+        let data = self
+            .assoc_data
+            .entry(*i)
+            .or_insert_with(AdditionalData::new);
+        data.variable = Some(name);
+    }
+
     fn is_marked(&self, i: &Self::ValueRef) -> bool {
         self.assoc_data
             .get(i)
@@ -1222,6 +1293,12 @@ impl SSAExtra for SSAStorage {
     fn flags(&self, i: &Self::ValueRef) -> Option<String> {
         self.assoc_data.get(i).and_then(|data| data.flag.clone())
     }
+
+    fn variable(&self, i: &Self::ValueRef) -> Option<String> {
+        self.assoc_data
+            .get(i)
+            .and_then(|data| data.variable.clone())
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -1332,24 +1409,7 @@ impl SSAWalk<Walker> for SSAStorage {
                     continue;
                 }
                 visited.insert(*block);
-                let mut nodes = Vec::new();
-                nodes.push(*block);
-                let mut exprs = self
-                    .exprs_in(*block)
-                    .iter()
-                    .chain(self.phis_in(*block).iter())
-                    .rev()
-                    .cloned()
-                    .collect::<Vec<NodeIndex>>();
-
-                exprs.sort_by(|x, y| {
-                    let addr_x = self.address(*x).expect("No address information found");
-                    let addr_y = self.address(*y).expect("No address information found");
-                    addr_x.cmp(&addr_y)
-                });
-                for expr in &exprs {
-                    nodes.push(*expr);
-                }
+                let nodes = self.block_and_values(*block);
                 for outedge in self.outgoing_edges(*block) {
                     let target = self
                         .edge_info(outedge.0)
@@ -1361,10 +1421,12 @@ impl SSAWalk<Walker> for SSAStorage {
                     let key = InorderKey::new(addr, target);
                     explorer.push(key);
                 }
-                blocks.push((key.address, nodes));
+                blocks.push((key.address, *block, nodes));
             }
-            blocks.sort_by(|x, y| x.0.cmp(&y.0));
-            walker.nodes = blocks.into_iter().flat_map(|(_, x)| x).collect();
+            // The exit node comes after the blocks of every segment.
+            let exit = self.exit_node();
+            blocks.sort_by_key(|&(address, block, _)| (Some(block) == exit, address));
+            walker.nodes = blocks.into_iter().flat_map(|(_, _, x)| x).collect();
         }
         walker
     }
@@ -1372,6 +1434,45 @@ impl SSAWalk<Walker> for SSAStorage {
     fn dfs_walk(&self) -> Walker {
         unimplemented!()
     }
+
+    fn reverse_postorder_walk(&self) -> Walker {
+        Walker {
+            nodes: self
+                .reverse_postorder()
+                .into_iter()
+                .flat_map(|block| self.block_and_values(block))
+                .collect(),
+        }
+    }
+
+    fn postorder_walk(&self) -> Walker {
+        Walker {
+            nodes: self.reverse_postorder_walk().rev().collect(),
+        }
+    }
+}
+
+impl SSAStorage {
+    /// `block` followed by its phis and expressions, in the order of their addresses.
+    fn block_and_values(&self, block: NodeIndex) -> Vec<NodeIndex> {
+        let mut exprs = self
+            .exprs_in(block)
+            .iter()
+            .chain(self.phis_in(block).iter())
+            .cloned()
+            .collect::<Vec<NodeIndex>>();
+
+        // Nodes at the same address are ordered by index, so that the walk doesn't depend on the
+        // order in which the edges were added.
+        exprs.sort_by(|x, y| {
+            let addr_x = self.address(*x).expect("No address information found");
+            let addr_y = self.address(*y).expect("No address information found");
+            (addr_x, x.index()).cmp(&(addr_y, y.index()))
+        });
+        let mut nodes = vec![block];
+        nodes.extend(exprs);
+        nodes
+    }
 }
 
 // Iterators for `SSAStorage`
@@ -1391,3 +1492,44 @@ impl DoubleEndedIterator for Walker {
         self.nodes.pop_back()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diamond_reverse_postorder() {
+        // 0x0: if (cond) { 0x10 } else { 0x20 } 0x30: return; 0x40 is unreachable.
+        let mut ssa = SSAStorage::new();
+        let blocks = [0x0, 0x10, 0x20, 0x30, 0x40]
+            .iter()
+            .map(|&a| ssa.insert_block(MAddress::new(a, 0)).unwrap())
+            .collect::<Vec<_>>();
+        let (header, then, els, join, unreachable) =
+            (blocks[0], blocks[1], blocks[2], blocks[3], blocks[4]);
+        let exit = ssa.insert_dynamic().unwrap();
+        ssa.set_entry_node(header);
+        ssa.set_exit_node(exit);
+        ssa.insert_control_edge(header, then, 1);
+        ssa.insert_control_edge(header, els, 0);
+        ssa.insert_control_edge(then, join, 2);
+        ssa.insert_control_edge(els, join, 2);
+        ssa.insert_control_edge(join, exit, 2);
+        ssa.insert_control_edge(unreachable, join, 2);
+
+        let rpo = ssa.reverse_postorder();
+        let pos = |block| rpo.iter().position(|&b| b == block).unwrap();
+        assert_eq!(rpo.len(), 5);
+        assert_eq!(pos(header), 0);
+        assert!(pos(then) < pos(join) && pos(els) < pos(join));
+        assert_eq!(pos(join), 3);
+        assert_eq!(pos(exit), 4);
+        assert!(!rpo.contains(&unreachable));
+
+        let mut postorder = ssa.postorder();
+        postorder.reverse();
+        assert_eq!(postorder, rpo);
+        // Without any value, the walks visit the blocks only.
+        assert_eq!(ssa.reverse_postorder_walk().collect::<Vec<_>>(), rpo);
+    }
+}
@@ -1,7 +1,9 @@
 //! A few utility functions for working with an [`SSAStorage`].
 
 use crate::middle::regfile::{RegisterId, RegisterMap};
-use crate::middle::ssa::ssa_traits::ValueInfo;
+use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
 use crate::middle::ssa::ssastorage::{EdgeData, SSAStorage};
 
 use petgraph::prelude::*;
@@ -63,3 +65,292 @@ pub fn register_state_info(
     }
     ret
 }
+
+/// Pairs every operand of a phi node with the predecessor blocks (of the block
+/// containing the phi) along which that operand flows in.
+///
+/// The index of an operand tells its predecessor in `CFG::ordered_preds_of`, as
+/// the operands are added in that order. Operands whose index has no matching
+/// predecessor, e.g. after an edge was removed, are returned with an empty list.
+/// Predecessors are sorted by address.
+pub fn phi_sources(phi: NodeIndex, ssa: &SSAStorage) -> Vec<(NodeIndex, Vec<NodeIndex>)> {
+    let mut sources: Vec<(NodeIndex, Vec<NodeIndex>)> = ssa
+        .operands_of(phi)
+        .into_iter()
+        .map(|op| (op, Vec::new()))
+        .collect();
+    let preds = match ssa.block_for(phi) {
+        Some(block) => ssa.ordered_preds_of(block),
+        None => return sources,
+    };
+
+    for (index, op) in ssa.sparse_operands_of(phi) {
+        let pred = match preds.get(index as usize) {
+            Some(&pred) => pred,
+            None => continue,
+        };
+        if let Some(source) = sources.iter_mut().find(|s| s.0 == op) {
+            source.1.push(pred);
+        }
+    }
+
+    for source in &mut sources {
+        source.1.sort_by_key(|&b| ssa.starting_address(b));
+    }
+    sources
+}
+
+/// Returns the basic block containing the address `addr`, other than the entry block. If blocks
+/// overlap, the one starting last is picked.
+pub fn block_at(ssa: &SSAStorage, addr: u64) -> Option<NodeIndex> {
+    let entry = ssa.entry_node()?;
+    ssa.blocks()
+        .into_iter()
+        .filter(|&b| b != entry)
+        .filter_map(|b| Some((ssa.starting_address(b)?.address, ssa.block_size(b)?, b)))
+        // An empty block still holds its starting address.
+        .filter(|&(start, size, _)| start <= addr && addr - start < size.max(1))
+        .max_by_key(|&(start, _, b)| (start, b.index()))
+        .map(|(_, _, b)| b)
+}
+
+/// Annotates `node` with the registers of its first operand, as `PhiPlacer::propagate_reginfo`
+/// does while the SSA is constructed. Call it after inserting an operation which changes the
+/// width of a register value (e.g. `OpNarrow` or `OpZeroExt`), so that the new node still holds
+/// the register for the analyses relying on the annotations, see `SSA::registers`.
+pub fn propagate_registers(ssa: &mut SSAStorage, node: NodeIndex) {
+    let first = match ssa.operands_of(node).first() {
+        Some(&first) => first,
+        None => return,
+    };
+    let annotated = ssa.registers(node);
+    for reg in ssa.registers(first) {
+        if !annotated.contains(&reg) {
+            ssa.set_register(node, reg);
+        }
+    }
+}
+
+/// Merges every block with a single successor with that successor, if the block is its only
+/// predecessor. The nodes of the successor are moved to the block, its phis (which are trivial,
+/// having a single predecessor) are replaced by their operand and its register state is dropped.
+/// Returns the number of blocks removed.
+pub fn merge_linear_blocks(ssa: &mut SSAStorage) -> usize {
+    let mut merged = 0;
+    while let Some((block, succ)) = next_linear_pair(ssa) {
+        merge_blocks(ssa, block, succ);
+        merged += 1;
+    }
+    merged
+}
+
+/// Finds a block and its only successor, of which it is the only predecessor.
+fn next_linear_pair(ssa: &SSAStorage) -> Option<(NodeIndex, NodeIndex)> {
+    let entry = ssa.entry_node();
+    let exit = ssa.exit_node();
+    ssa.blocks().into_iter().find_map(|block| {
+        if Some(block) == entry || Some(block) == exit || ssa.outgoing_edges(block).len() != 1 {
+            return None;
+        }
+        let succ = *ssa.succs_of(block).first()?;
+        let mergeable = succ != block
+            && Some(succ) != exit
+            && ssa.is_block(succ)
+            && ssa.incoming_edges(succ).len() == 1
+            && ssa
+                .phis_in(succ)
+                .into_iter()
+                .all(|phi| trivial_phi(ssa, phi).is_some());
+        if mergeable {
+            Some((block, succ))
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns the only value, other than itself, `phi` merges.
+fn trivial_phi(ssa: &SSAStorage, phi: NodeIndex) -> Option<NodeIndex> {
+    let mut operands = ssa.operands_of(phi);
+    operands.retain(|&op| op != phi);
+    operands.dedup();
+    match operands.as_slice() {
+        [op] => Some(*op),
+        _ => None,
+    }
+}
+
+fn merge_blocks(ssa: &mut SSAStorage, block: NodeIndex, succ: NodeIndex) {
+    for phi in ssa.phis_in(succ) {
+        if let Some(op) = trivial_phi(ssa, phi) {
+            ssa.replace_value(phi, op);
+        }
+    }
+
+    // The register state of `succ` is removed along with it.
+    let regstate = ssa.registers_in(succ);
+    let contained = ssa
+        .g
+        .edges_directed(succ, Incoming)
+        .filter_map(|e| match *e.weight() {
+            EdgeData::ContainedInBB(addr) if Some(e.source()) != regstate => {
+                Some((e.id(), e.source(), addr))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for (edge, node, addr) in contained {
+        ssa.g.remove_edge(edge);
+        ssa.insert_into_block(node, block, addr);
+    }
+    if let Some(selector) = ssa.selector_in(succ) {
+        ssa.remove_edges_between(succ, selector);
+        ssa.set_selector(selector, block);
+    }
+
+    let outgoing = ssa.outgoing_edges(succ);
+    for (edge, index) in outgoing {
+        let target = ssa.g.edge_endpoints(edge).map(|(_, t)| t);
+        if let Some(target) = target {
+            ssa.insert_control_edge(block, target, index);
+        }
+    }
+    ssa.remove_edges_between(block, succ);
+
+    if let (Some(start), Some(succ_start), Some(succ_size)) = (
+        ssa.starting_address(block),
+        ssa.starting_address(succ),
+        ssa.block_size(succ),
+    ) {
+        let size = succ_start.address + succ_size - start.address;
+        ssa.set_block_size(block, size);
+    }
+    ssa.remove_block(succ);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir::{MOpcode, WidthSpec};
+    use crate::utils::test_support::load_ssa;
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const LINEAR_SSA_TXT: &str = "\
+define-fun sym.linear(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        JMP 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        %4: $Unknown64 = Phi(%3);
+        [@0x001004.0001] %5: $Unknown64 = %4 + #x2;
+        JMP 0x001008.0000
+    bb_0x001008.0000(sz 0x4):
+        [@0x001008.0001] %6: $Unknown64 = %5 + #x3;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %6;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn merges_straight_line() {
+        let mut ssa = load_ssa(LINEAR_SSA_TXT);
+
+        assert_eq!(merge_linear_blocks(&mut ssa), 2);
+
+        let entry = ssa.entry_node().unwrap();
+        let blocks = ssa
+            .blocks()
+            .into_iter()
+            .filter(|&b| b != entry)
+            .collect::<Vec<_>>();
+        assert_eq!(blocks.len(), 1);
+        let block = blocks[0];
+        assert_eq!(ssa.block_size(block), Some(0xc));
+        assert_eq!(ssa.succs_of(block), vec![ssa.exit_node().unwrap()]);
+        assert!(ssa.phis_in(block).is_empty());
+        let adds = ssa.exprs_in(block);
+        assert_eq!(adds.len(), 3);
+        assert!(adds.iter().all(|&n| ssa.opcode(n) == Some(MOpcode::OpAdd)));
+        // The phi was replaced by the value it merged.
+        assert_eq!(ssa.operands_of(adds[1])[0], adds[0]);
+    }
+
+    // Both operands of the phi are defined in the entry block.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const ENTRY_PHI_SSA_TXT: &str = "\
+define-fun sym.entry_phi(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown0 = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %4: $Unknown1 = %1 == #x0;
+        JMP IF %4 0x001004.0000 ELSE 0x001008.0000
+    bb_0x001004.0000(sz 0x4):
+        JMP 0x00100C.0000
+    bb_0x001008.0000(sz 0x4):
+        JMP 0x00100C.0000
+    bb_0x00100C.0000(sz 0x1):
+        %5: $Unknown64 = Phi(%1, %2);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %5;
+        $mem = %3;
+}
+";
+
+    #[test]
+    fn phi_sources_from_entry() {
+        let ssa = load_ssa(ENTRY_PHI_SSA_TXT);
+        let phi = ssa
+            .blocks()
+            .into_iter()
+            .flat_map(|b| ssa.phis_in(b))
+            .next()
+            .unwrap();
+        let sources = phi_sources(phi, &ssa)
+            .into_iter()
+            .map(|(op, preds)| {
+                let starts = preds
+                    .into_iter()
+                    .map(|b| ssa.starting_address(b).unwrap().address)
+                    .collect::<Vec<_>>();
+                (ssa.argument(op).unwrap(), starts)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sources,
+            vec![
+                ("rdi".to_owned(), vec![0x1004]),
+                ("rsi".to_owned(), vec![0x1008]),
+            ]
+        );
+    }
+
+    #[test]
+    fn narrowing_keeps_register() {
+        let mut ssa = load_ssa(LINEAR_SSA_TXT);
+        let regs = ssa.registers_in(ssa.entry_node().unwrap()).unwrap();
+        let rdi = ssa
+            .operands_of(regs)
+            .into_iter()
+            .find(|&n| ssa.argument(n) == Some("rdi".to_owned()))
+            .unwrap();
+
+        let vt = ValueInfo::new_scalar(WidthSpec::new_known(32));
+        let narrow = ssa.insert_op(MOpcode::OpNarrow(32), vt, None).unwrap();
+        ssa.op_use(narrow, 0, rdi);
+        propagate_registers(&mut ssa, narrow);
+        assert_eq!(ssa.registers(narrow), vec!["rdi".to_owned()]);
+        // Propagating again doesn't duplicate the annotation.
+        propagate_registers(&mut ssa, narrow);
+        assert_eq!(ssa.registers(narrow), vec!["rdi".to_owned()]);
+    }
+}
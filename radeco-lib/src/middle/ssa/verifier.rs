@@ -210,10 +210,12 @@ impl Verify for SSAStorage {
                         MArity::Unary => 1,
                         MArity::Binary => 2,
                         MArity::Ternary => 3,
+                        MArity::Quaternary => 4,
                     };
 
                     if opcode != MOpcode::OpCall
                         && opcode.idx() != MOpcode::OpCustom(String::new()).idx()
+                        && opcode.idx() != MOpcode::OpUnsupported(String::new()).idx()
                     {
                         check!(op_len == n, SSAErr::WrongNumOperands(*exi, n, op_len));
                     }
@@ -241,11 +243,23 @@ impl Verify for SSAStorage {
                             check!(opw < w0, SSAErr::IncompatibleWidth(*exi, opw, w0));
                             check!(w == w0, SSAErr::IncompatibleWidth(*exi, w, w0));
                         }
+                        MOpcode::OpByteSwap(w0) => {
+                            let opw = self
+                                .node_data(operands[0])
+                                .map(|vi| vi.vt.width().get_width().unwrap_or(64))
+                                .unwrap();
+                            check!(opw == w0, SSAErr::IncompatibleWidth(*exi, opw, w0));
+                            check!(w == w0, SSAErr::IncompatibleWidth(*exi, w, w0));
+                        }
                         MOpcode::OpEq | MOpcode::OpGt | MOpcode::OpLt => {
                             check!(w == 1, SSAErr::IncompatibleWidth(*exi, 1, w));
                         }
                         // TODO: Width of OpStore and OpLoad now is not certain.
                         MOpcode::OpCall | MOpcode::OpStore | MOpcode::OpLoad => {}
+                        // Their operands are the memory, addresses, a count and an element.
+                        MOpcode::OpRepMovs(_) | MOpcode::OpRepScas(_) | MOpcode::OpRepStos(_) => {}
+                        // Like a call, it reads every register and the memory.
+                        MOpcode::OpUnsupported(_) => {}
                         _ => {
                             // All operands to an expr must have the same width.
                             let w0 = self
@@ -0,0 +1,106 @@
+//! Golden tests of the IL.
+//!
+//! A function of a `FileSource` fixture under `test_files` is run through SSA construction, SCCP
+//! and DCE, and the IL emitted for it is compared against the one checked in as
+//! `test_files/golden/<base name>_<function>.il`. Any change of the output, e.g. of the phis
+//! placed, makes the test fail.
+//!
+//! Run the tests with `RADECO_UPDATE_GOLDEN` set to write the current IL to the golden files
+//! instead, once the change is known to be right. This is also how the golden file of a new test
+//! is written, to be committed along with the test: a missing golden file fails the test.
+
+use crate::analysis::analyzer::{all, FuncAnalyzer};
+use crate::analysis::dce::DCE;
+use crate::analysis::sccp::SCCP;
+use crate::frontend::radeco_containers::ModuleLoader;
+use crate::frontend::radeco_source::FileSource;
+use crate::middle::ir_writer;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Environment variable to set to regenerate the golden files.
+pub const UPDATE_GOLDEN: &str = "RADECO_UPDATE_GOLDEN";
+
+fn test_files() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files")
+}
+
+/// Returns the IL of `function` in `fixture`, the path of a `FileSource` relative to
+/// `test_files`, once constructed and simplified by SCCP and DCE.
+pub fn pipeline_il(fixture: &str, function: &str) -> String {
+    let path = test_files().join(fixture);
+    let source = FileSource::open(path.to_str().unwrap());
+    let mut rmod = ModuleLoader::default().build_ssa().load(Rc::new(source));
+    let rfn = rmod
+        .functions
+        .values_mut()
+        .find(|rfn| rfn.name == function)
+        .unwrap_or_else(|| panic!("{} is not found in {}", function, fixture));
+
+    DCE::new().analyze(rfn, Some(all));
+    SCCP::new().analyze(rfn, Some(all));
+    DCE::new().analyze(rfn, Some(all));
+
+    let mut il = String::new();
+    ir_writer::emit_il(&mut il, Some(function.to_owned()), rfn.ssa()).unwrap();
+    il
+}
+
+/// Compares the IL of `function` in `fixture` (see `pipeline_il`) against its golden file, or
+/// writes it there if `UPDATE_GOLDEN` is set.
+///
+/// Panics if the IL differs, or if the golden file is missing.
+pub fn check_golden(fixture: &str, function: &str) {
+    let il = pipeline_il(fixture, function);
+    let base_name = Path::new(fixture).file_name().unwrap().to_str().unwrap();
+    let dir = test_files().join("golden");
+    let path = dir.join(format!("{}_{}.il", base_name, function));
+
+    if env::var_os(UPDATE_GOLDEN).is_some() {
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, &il).unwrap();
+        return;
+    }
+    let golden = fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "Cannot read the golden file {} ({}), rerun with {} set to write it",
+            path.display(),
+            e,
+            UPDATE_GOLDEN
+        )
+    });
+    pretty_assertions::assert_eq!(
+        golden,
+        il,
+        "The IL of {} differs from {}, rerun with {} set if this is expected",
+        function,
+        path.display(),
+        UPDATE_GOLDEN
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A loop, whose header merges the sum and the counter.
+    #[test]
+    fn loop_golden() {
+        check_golden("tiny_filesource/tiny", "sym.count");
+    }
+
+    // A diamond, whose join selects the greatest of the arguments.
+    #[test]
+    fn diamond_golden() {
+        check_golden("tiny_filesource/tiny", "sym.max");
+    }
+
+    // A call guarded by a flag in memory.
+    #[test]
+    fn dtors_golden() {
+        check_golden("bin1_filesource/bin1", "sym.__do_global_dtors_aux");
+    }
+}
@@ -11,6 +11,11 @@
 #[macro_use]
 pub mod logger;
 
+#[cfg(test)]
+pub mod golden;
+#[cfg(test)]
+pub mod test_support;
+
 //use std::io::prelude::*;
 //use std::fs;
 //use std::fs::File;
@@ -0,0 +1,177 @@
+//! Helpers shared by the unit tests: the x86-64 register file of the IL fixtures, loading a
+//! function from its IL, locating the registers of the entry state, and a `Source` serving fixed
+//! data.
+
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::frontend::radeco_source::{Source, SourceErr};
+use crate::middle::ir_reader;
+use crate::middle::regfile::SubRegisterFile;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+use r2papi::structs::{FunctionInfo, LFlagInfo, LOpInfo, LRegInfo, LSectionInfo, LSymbolInfo};
+
+use std::cell::Cell;
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+/// Register profile the IL fixtures are written against.
+pub const REGISTER_PROFILE: &str = "test_files/x86_register_profile.json";
+
+/// Reads the x86-64 register profile.
+pub fn reg_profile() -> LRegInfo {
+    let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+    serde_json::from_str(&*s).unwrap()
+}
+
+/// Builds the register file of the x86-64 register profile.
+pub fn register_file() -> Arc<SubRegisterFile> {
+    Arc::new(SubRegisterFile::new(&reg_profile()).unwrap())
+}
+
+/// Parses the IL of a function into its SSA.
+pub fn load_ssa(il: &str) -> SSAStorage {
+    ir_reader::parse_il(il, register_file())
+}
+
+/// Parses the IL of a function into a new `RadecoFunction`.
+pub fn load(il: &str) -> RadecoFunction {
+    let mut rfn = RadecoFunction::default();
+    *rfn.ssa_mut() = load_ssa(il);
+    rfn
+}
+
+/// Parses the IL of a function at `offset` against `regfile`, for the tests building a module out
+/// of several functions sharing a register file.
+pub fn load_at(il: &str, offset: u64, regfile: &Arc<SubRegisterFile>) -> RadecoFunction {
+    let mut rfn = RadecoFunction::default();
+    rfn.offset = offset;
+    *rfn.ssa_mut() = ir_reader::parse_il(il, regfile.clone());
+    rfn
+}
+
+/// Returns the value of the register `name` in the entry register state.
+pub fn entry_reg(ssa: &SSAStorage, name: &str) -> NodeIndex {
+    let regs = ssa.registers_in(ssa.entry_node().unwrap()).unwrap();
+    ssa.operands_of(regs)
+        .into_iter()
+        .find(|&n| ssa.name_of(n).as_ref().map(|s| s.as_str()) == Some(name))
+        .unwrap()
+}
+
+/// `Source` serving the functions, flags, sections and symbols it is given as JSON, the way
+/// radare2 would print them, and the bytes of the chunks of memory it holds. Anything it is not
+/// given is empty.
+#[derive(Default)]
+pub struct StubSource {
+    functions: Option<&'static str>,
+    flags: Option<&'static str>,
+    sections: Option<&'static str>,
+    symbols: Option<&'static str>,
+    memory: Vec<(u64, Vec<u8>)>,
+    broken: Cell<u32>,
+}
+
+impl StubSource {
+    pub fn new() -> StubSource {
+        StubSource::default()
+    }
+
+    pub fn with_functions(mut self, json: &'static str) -> StubSource {
+        self.functions = Some(json);
+        self
+    }
+
+    pub fn with_flags(mut self, json: &'static str) -> StubSource {
+        self.flags = Some(json);
+        self
+    }
+
+    pub fn with_sections(mut self, json: &'static str) -> StubSource {
+        self.sections = Some(json);
+        self
+    }
+
+    pub fn with_symbols(mut self, json: &'static str) -> StubSource {
+        self.symbols = Some(json);
+        self
+    }
+
+    /// Maps `bytes` at `addr`.
+    pub fn with_memory(mut self, addr: u64, bytes: Vec<u8>) -> StubSource {
+        self.memory.push((addr, bytes));
+        self
+    }
+
+    /// Fails the first `requests` requests as if the connection was broken.
+    pub fn broken_for(self, requests: u32) -> StubSource {
+        self.broken.set(requests);
+        self
+    }
+
+    fn request(&self) -> Result<(), SourceErr> {
+        if self.broken.get() > 0 {
+            self.broken.set(self.broken.get() - 1);
+            let err = io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe");
+            Err(SourceErr::from(err))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn parse_or_empty<T: serde::de::DeserializeOwned>(json: Option<&str>) -> Result<Vec<T>, SourceErr> {
+    match json {
+        Some(json) => Ok(serde_json::from_str(json)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+impl Source for StubSource {
+    fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+        self.request()?;
+        parse_or_empty(self.functions)
+    }
+
+    fn instructions_at(&self, _: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+        self.request()?;
+        Ok(Vec::new())
+    }
+
+    fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+        self.request()?;
+        Ok(LRegInfo::default())
+    }
+
+    fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+        self.request()?;
+        parse_or_empty(self.flags)
+    }
+
+    fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+        self.request()?;
+        parse_or_empty(self.sections)
+    }
+
+    fn symbols(&self) -> Result<Vec<LSymbolInfo>, SourceErr> {
+        self.request()?;
+        parse_or_empty(self.symbols)
+    }
+
+    fn read_bytes(&self, addr: u64, len: usize) -> Result<Vec<u8>, SourceErr> {
+        self.request()?;
+        self.memory
+            .iter()
+            .filter(|&&(start, _)| start <= addr)
+            .filter_map(|&(start, ref bytes)| {
+                let offset = (addr - start) as usize;
+                bytes.get(offset..offset + len)
+            })
+            .next()
+            .map(|b| b.to_vec())
+            .ok_or(SourceErr::SrcErr("No known bytes at addr"))
+    }
+}
@@ -1,9 +1,44 @@
 use clap::{App, Arg};
+use std::collections::HashMap;
+use std::fs;
 use std::process;
+use std::time::Duration;
 
+use radeco_lib::analysis::functions::signatures::SignatureDb;
+use radeco_lib::frontend::radeco_containers::parse_annotations;
+use radeco_lib::frontend::radeco_source::DEFAULT_RECONNECTS;
+
+use super::core::ConstructOptions;
 use super::MAX_ITERATIONS;
 
-pub fn parse_args() -> (Option<String>, Option<String>, bool, bool, bool, u32) {
+/// The options radeco was started with.
+pub struct CliArgs {
+    /// Binary, or r2 tcp/http server, to load
+    pub bin: Option<String>,
+    /// Command to run in batch mode
+    pub command: Option<String>,
+    /// Print a separator after the output of every command
+    pub append: bool,
+    /// Decompile the whole binary, or run `command`, and exit
+    pub batch: bool,
+    pub no_highlight: bool,
+    /// Print the time spent in every analysis at the end of batch mode
+    pub profile: bool,
+    /// Max number of iterations of the engine
+    pub max_iterations: u32,
+    /// Number of reconnects to a dropped r2 tcp/http server
+    pub reconnects: u32,
+    /// Skip the functions with more instructions than this
+    pub max_insts: Option<usize>,
+    /// Give up on the functions taking longer than this to analyze
+    pub fn_timeout: Option<Duration>,
+    pub signatures: SignatureDb,
+    /// Comments to show in the IR and the output, by address
+    pub annotations: HashMap<u64, String>,
+    pub construct: ConstructOptions,
+}
+
+pub fn parse_args() -> CliArgs {
     let vs = env!("VERSION_STR");
     let matches = App::new("radeco")
         .version(vs)
@@ -24,6 +59,50 @@ pub fn parse_args() -> (Option<String>, Option<String>, bool, bool, bool, u32) {
                 .required(false)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("reconnects")
+                .help("Number of reconnects to a dropped r2 tcp/http server")
+                .short("r")
+                .long("reconnects")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-function-insts")
+                .help("Skip the functions with more instructions than this")
+                .long("max-function-insts")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fn-timeout")
+                .help("Give up on the functions taking longer than this many seconds to analyze")
+                .long("fn-timeout")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .help("Number of threads loading the functions")
+                .short("j")
+                .long("jobs")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("signatures")
+                .help("JSON file with the return types of more library functions")
+                .long("signatures")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("annotations")
+                .help("JSON file mapping addresses to comments to show in the IR and the output")
+                .long("annotations")
+                .required(false)
+                .takes_value(true),
+        )
         .arg(Arg::from_usage(
             "-a --append 'Append separator to the end of every output.'",
         ))
@@ -31,10 +110,22 @@ pub fn parse_args() -> (Option<String>, Option<String>, bool, bool, bool, u32) {
         .arg(Arg::from_usage(
             "-l --no-highlight 'Disable syntax highlight on output'",
         ))
+        .arg(Arg::from_usage(
+            "-p --profile 'Print the time spent in every analysis at the end of batch mode'",
+        ))
+        .arg(Arg::from_usage(
+            "--assume-cc 'Assume that calls follow the calling convention, for a smaller but less \
+             conservative SSA'",
+        ))
+        .arg(Arg::from_usage(
+            "--no-replace-pc 'Keep the reads of the program counter instead of replacing them \
+             with addresses'",
+        ))
         .get_matches();
     let is_append = matches.is_present("append");
     let is_batch = matches.is_present("batch");
     let no_highlight = matches.is_present("no-highlight");
+    let is_profile = matches.is_present("profile");
     let bin = matches.value_of("BIN").map(|s| s.to_string());
     let command = matches.value_of("command").map(|s| s.to_string());
 
@@ -46,6 +137,10 @@ pub fn parse_args() -> (Option<String>, Option<String>, bool, bool, bool, u32) {
         eprintln!("Passed a command in interactive mode");
         process::exit(0);
     }
+    if is_profile && !is_batch {
+        eprintln!("Profiling is only available in batch mode");
+        process::exit(0);
+    }
     let max_it = match matches.value_of("max-iterations") {
         Some(s) => {
             // TODO -> Implement error management.
@@ -65,5 +160,96 @@ pub fn parse_args() -> (Option<String>, Option<String>, bool, bool, bool, u32) {
         process::exit(0);
     }
 
-    (bin, command, is_append, is_batch, no_highlight, max_it)
+    let reconnects = match matches.value_of("reconnects") {
+        Some(s) => match u32::from_str_radix(s.trim(), 10) {
+            Ok(reconnects) => reconnects,
+            Err(_) => {
+                eprintln!("reconnects must be a decimal number");
+                process::exit(0);
+            }
+        },
+        None => DEFAULT_RECONNECTS,
+    };
+
+    let max_insts = match matches.value_of("max-function-insts") {
+        Some(s) => match usize::from_str_radix(s.trim(), 10) {
+            Ok(max_insts) => Some(max_insts),
+            Err(_) => {
+                eprintln!("max-function-insts must be a decimal number");
+                process::exit(0);
+            }
+        },
+        None => None,
+    };
+
+    let fn_timeout = match matches.value_of("fn-timeout") {
+        Some(s) => match s.trim().parse::<f64>() {
+            Ok(secs) if secs > 0.0 && secs.is_finite() => Some(Duration::from_secs_f64(secs)),
+            _ => {
+                eprintln!("fn-timeout must be a positive number of seconds");
+                process::exit(0);
+            }
+        },
+        None => None,
+    };
+
+    let max_workers = match matches.value_of("jobs") {
+        Some(s) => match usize::from_str_radix(s.trim(), 10) {
+            Ok(jobs) if jobs > 0 => Some(jobs),
+            _ => {
+                eprintln!("jobs must be a positive decimal number");
+                process::exit(0);
+            }
+        },
+        None => None,
+    };
+
+    let construct = ConstructOptions {
+        assume_cc: matches.is_present("assume-cc"),
+        replace_pc: !matches.is_present("no-replace-pc"),
+        max_workers,
+    };
+
+    let mut signatures = SignatureDb::default();
+    if let Some(path) = matches.value_of("signatures") {
+        let loaded = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| signatures.load(&json).map_err(|e| e.to_string()));
+        if let Err(e) = loaded {
+            eprintln!("Cannot load the signatures from {}: {}", path, e);
+            process::exit(0);
+        }
+    }
+
+    let annotations = match matches.value_of("annotations") {
+        Some(path) => {
+            let loaded = fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| parse_annotations(&json));
+            match loaded {
+                Ok(annotations) => annotations,
+                Err(e) => {
+                    eprintln!("Cannot load the annotations from {}: {}", path, e);
+                    process::exit(0);
+                }
+            }
+        }
+        None => HashMap::new(),
+    };
+
+    CliArgs {
+        bin,
+        command,
+        append: is_append,
+        batch: is_batch,
+        no_highlight,
+        profile: is_profile,
+        max_iterations: max_it,
+        reconnects,
+        max_insts,
+        fn_timeout,
+        signatures,
+        annotations,
+        construct,
+    }
 }
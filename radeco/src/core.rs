@@ -1,21 +1,39 @@
 use base64;
+use r2papi::api_trait::R2Api;
+use r2papi::structs::{LCCInfo, LSectionInfo};
 use r2pipe::{R2Pipe, R2};
-use radeco_lib::analysis::engine::{Engine, RadecoEngine};
+use radeco_lib::analysis::analyzer::{self, AnalyzerInfo, AnalyzerKind};
+use radeco_lib::analysis::engine::{ConvergenceReport, Engine, Profile, RadecoEngine};
+use radeco_lib::analysis::functions::signatures::SignatureDb;
+use radeco_lib::backend::interp;
 use radeco_lib::backend::lang_c::c_cfg::ctrl_flow_struct;
 use radeco_lib::backend::lang_c::c_cfg::CCFGVerifier;
-use radeco_lib::backend::lang_c::c_cfg_builder;
+use radeco_lib::backend::lang_c::c_cfg_builder::{self, RenderOptions, ResolvedCall};
+use radeco_lib::backend::smt;
+use radeco_lib::error::RadecoError;
+use radeco_lib::frontend::llanalyzer;
 use radeco_lib::frontend::radeco_containers::*;
+use radeco_lib::frontend::radeco_source::{ReconnectingSource, Source, SourceErr};
 use radeco_lib::middle::dot;
 use radeco_lib::middle::ir_writer;
+use radeco_lib::middle::ssa::cfg_traits::CFG;
 use radeco_lib::middle::ssa::ssastorage::SSAStorage;
+use radeco_lib::middle::ssa::utils;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
 use std::panic;
 use std::rc::Rc;
 use std::str;
+use std::sync::Arc;
+use std::time::Duration;
 
 thread_local!(
     pub static PROJ: RefCell<Option<RadecoProject>> = RefCell::new(None);
+    // Signatures of the library functions, see `--signatures`.
+    pub static SIGNATURES: RefCell<SignatureDb> = RefCell::new(SignatureDb::default());
+    // Comments of the user by address, see `--annotations`.
+    pub static ANNOTATIONS: RefCell<HashMap<u64, String>> = RefCell::new(HashMap::new());
 );
 
 pub fn fn_list(proj: &RadecoProject) -> Vec<String> {
@@ -71,32 +89,408 @@ pub fn fn_rename(old_f: &str, new_f: &str, proj: &mut RadecoProject) {
     }
 }
 
-pub fn analyze(rfn: &mut RadecoFunction, max_it: u32) {
-    let engine = RadecoEngine::new(max_it);
-    engine.run_func(rfn);
+/// Overrides the calling convention of the function `name` with `spec`, a list of
+/// `args=<reg>,...`, `ret=<reg>` and `clobber=<reg>,...` in any order. The module of the function
+/// is analyzed again, so that the calls to it follow the new calling convention.
+pub fn set_cc(
+    name: &str,
+    spec: &[&str],
+    proj: &mut RadecoProject,
+    max_it: u32,
+    fn_timeout: Option<Duration>,
+) -> Result<(), String> {
+    let regs = |list: &str| {
+        list.split(',')
+            .filter(|r| !r.is_empty())
+            .map(|r| r.to_owned())
+            .collect::<Vec<_>>()
+    };
+    let mut callconv = LCCInfo::default();
+    let mut clobbers = None;
+    for term in spec {
+        let mut kv = term.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some("args"), Some(list)) => callconv.args = Some(regs(list)),
+            (Some("ret"), Some(reg)) => callconv.ret = Some(reg.to_owned()),
+            (Some("clobber"), Some(list)) => clobbers = Some(regs(list)),
+            _ => return Err(format!("Invalid calling convention {}", term)),
+        }
+    }
+
+    let regfile = proj.regfile().clone();
+    let unknown = callconv
+        .args
+        .iter()
+        .flatten()
+        .chain(&callconv.ret)
+        .chain(clobbers.iter().flatten())
+        .find(|reg| regfile.register_id_by_name(reg).is_none());
+    if let Some(reg) = unknown {
+        return Err(format!("Unknown register {}", reg));
+    }
+
+    let mut found = false;
+    for xy in proj.iter_mut() {
+        let module = xy.module;
+        let off = module
+            .iter()
+            .find(|rfn| rfn.function.1.name == name)
+            .map(|rfn| rfn.function.1.offset);
+
+        if let Some(off) = off {
+            module.set_calling_convention(off, callconv.clone(), clobbers.clone());
+            module.rebuild_dirty();
+            engine(max_it, fn_timeout).run_module(module, &*regfile);
+            found = true;
+        }
+    }
+
+    if found {
+        Ok(())
+    } else {
+        Err(format!("{} is not found", name))
+    }
+}
+
+/// Lists the calls made to the function `name`, one `<callsite> <caller>` per line.
+pub fn callers(name: &str, proj: &RadecoProject) -> Result<String, String> {
+    calls(name, proj, RadecoModule::callers_by_name)
+}
+
+/// Lists the calls made by the function `name`, one `<callsite> <callee>` per line.
+pub fn callees(name: &str, proj: &RadecoProject) -> Result<String, String> {
+    calls(name, proj, RadecoModule::callees_by_name)
+}
+
+fn calls<F>(name: &str, proj: &RadecoProject, f: F) -> Result<String, String>
+where
+    F: Fn(&RadecoModule, u64) -> Vec<(u64, String)>,
+{
+    let (rmod, offset) = proj
+        .iter()
+        .map(|i| i.module)
+        .filter_map(|rmod| {
+            rmod.functions
+                .values()
+                .find(|rfn| rfn.name == name)
+                .map(|rfn| (rmod, rfn.offset))
+        })
+        .next()
+        .ok_or(format!("{} is not found", name))?;
+    Ok(f(rmod, offset)
+        .into_iter()
+        .map(|(csite, name)| format!("{:#x} {}", csite, name))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Text report of the call graph of every module, see `llanalyzer::callgraph_report`.
+pub fn callgraph_report(proj: &RadecoProject) -> String {
+    proj.iter()
+        .map(|i| llanalyzer::callgraph_report(i.module))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The call graph of every module in GraphML, as a graph named after the module.
+pub fn callgraph_graphml(proj: &RadecoProject) -> String {
+    let graphs = proj
+        .iter()
+        .map(|i| (i.module.name().to_owned(), i.module))
+        .collect::<Vec<_>>();
+    dot::emit_graphml_graphs(&graphs)
+}
+
+/// Lists the sections of every module, one `<name> <vaddr> <size> <perms>` per line.
+pub fn sections(proj: &RadecoProject) -> String {
+    proj.iter()
+        .map(|i| i.module)
+        .flat_map(|rmod| rmod.sections().iter())
+        .map(|s| {
+            format!(
+                "{:<24} {:#010x} {:#8x} {}",
+                s.name.as_ref().map(|n| n.as_str()).unwrap_or(""),
+                s.vaddr.unwrap_or(0),
+                s.vsize.or(s.size).unwrap_or(0),
+                s.flags.as_ref().map(|f| f.as_str()).unwrap_or("")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses an address given in hex (`0x` prefixed) or decimal.
+fn parse_addr(addr: &str) -> Result<u64, String> {
+    let parsed = if addr.starts_with("0x") {
+        u64::from_str_radix(&addr[2..], 16)
+    } else {
+        addr.parse::<u64>()
+    };
+    parsed.map_err(|_| format!("Invalid address {}", addr))
+}
+
+/// Names the section containing `addr`, given in hex (`0x` prefixed) or decimal.
+pub fn section_of(addr: &str, proj: &RadecoProject) -> Result<String, String> {
+    let addr = parse_addr(addr)?;
+    proj.iter()
+        .map(|i| i.module)
+        .filter_map(|rmod| rmod.section_of(addr))
+        .next()
+        .and_then(|s| s.name.clone())
+        .ok_or(format!("{:#x} is not in any section", addr))
+}
+
+/// Lists the references to `addr`, given in hex (`0x` prefixed) or decimal, one
+/// `<from> <code|data> <function>` per line. The calls to a function at `addr` are listed as code
+/// references.
+pub fn xrefs(addr: &str, proj: &RadecoProject) -> Result<String, String> {
+    let addr = parse_addr(addr)?;
+    let xrefs = proj
+        .iter()
+        .flat_map(|i| i.module.xrefs_to(addr))
+        .map(|x| {
+            let kind = match x.kind {
+                XrefKind::Code => "code",
+                XrefKind::Data => "data",
+            };
+            format!("{:#x} {} {}", x.from, kind, x.function)
+        })
+        .collect::<Vec<_>>();
+    if xrefs.is_empty() {
+        return Err(format!("No references to {:#x}", addr));
+    }
+    Ok(xrefs.join("\n"))
+}
+
+/// Sections of the project, used to label the addresses in the output.
+pub fn project_sections(proj: &RadecoProject) -> Arc<Vec<LSectionInfo>> {
+    proj.iter()
+        .map(|i| i.module.sections().clone())
+        .next()
+        .unwrap_or_default()
+}
+
+// Engine running at most `max_it` iterations, for at most `fn_timeout` on every function.
+fn engine(max_it: u32, fn_timeout: Option<Duration>) -> RadecoEngine {
+    let signatures = SIGNATURES.with(|sigs| sigs.borrow().clone());
+    let engine = RadecoEngine::new(max_it).signatures(signatures);
+    match fn_timeout {
+        Some(budget) => engine.fn_timeout(budget),
+        None => engine,
+    }
+}
+
+// Attaches the comments of `--annotations` to the analyzed SSA of `rfn`.
+fn annotate(rfn: &mut RadecoFunction) {
+    ANNOTATIONS.with(|notes| {
+        let notes = notes.borrow();
+        if !notes.is_empty() {
+            rfn.apply_annotations(notes.clone());
+        }
+    });
+}
+
+pub fn analyze(
+    rfn: &mut RadecoFunction,
+    max_it: u32,
+    fn_timeout: Option<Duration>,
+) -> Option<ConvergenceReport> {
+    let engine = engine(max_it, fn_timeout);
+    let report = engine.report_func(rfn);
+    if let Some(warning) = report.as_ref().and_then(|r| r.cap_warning(&rfn.name)) {
+        eprintln!("  [!] {}", warning);
+    }
+    annotate(rfn);
+    report
 }
 
-pub fn analyze_all_functions<'a>(proj: &'a mut RadecoProject, max_it: u32) {
+/// Runs the analysis named `pass` on `rfn`, after the ones it requires. Lists the analyses run,
+/// one `<name> <changes>` per line.
+pub fn analyze_pass(rfn: &mut RadecoFunction, pass: &str, max_it: u32) -> Result<String, String> {
+    let kind = analyzer::kind_by_name(pass).ok_or(format!("{} is not an analysis", pass))?;
+    let passes = engine(max_it, None)
+        .run_pass(rfn, kind)
+        .map_err(|e| e.to_string())?;
+    Ok(passes
+        .iter()
+        .map(|stats| format!("{} {}", analysis_name(stats.kind), stats.changes))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Lists the analyses, one `<name> <function|module> <required analyses>` per line.
+pub fn analyses() -> String {
+    let func = analyzer::all_func_analyzers()
+        .into_iter()
+        .chain(analyzer::final_func_analyzers())
+        .map(|kind| (kind, "function"));
+    let module = analyzer::all_module_analyzers()
+        .into_iter()
+        .map(|kind| (kind, "module"));
+    func.chain(module)
+        .map(|(kind, scope)| {
+            let info: &'static AnalyzerInfo = From::from(kind);
+            let requires = info
+                .requires
+                .iter()
+                .map(|&dep| analysis_name(dep))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("{:16} {:8} {}", info.name, scope, requires)
+                .trim_end()
+                .to_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn analysis_name(kind: AnalyzerKind) -> &'static str {
+    let info: &'static AnalyzerInfo = From::from(kind);
+    info.name
+}
+
+/// Reconstructs the SSA of the functions which went stale, e.g. after being renamed.
+pub fn rebuild_dirty(proj: &mut RadecoProject) {
+    for xy in proj.iter_mut() {
+        xy.module.rebuild_dirty();
+    }
+}
+
+/// Analyzes every function of the project, returning the time spent in each of them and in each
+/// analyzer.
+pub fn analyze_all_functions<'a>(
+    proj: &'a mut RadecoProject,
+    max_it: u32,
+    fn_timeout: Option<Duration>,
+) -> Profile {
+    rebuild_dirty(proj);
     let rfns = proj
         .iter_mut()
         .map(|i| i.module)
         .flat_map(|rmod| rmod.functions.values_mut());
+    let mut profile = Profile::new();
     for rfn in rfns {
-        analyze(rfn, max_it);
+        if let Some(report) = analyze(rfn, max_it, fn_timeout) {
+            profile.record(&rfn.name, &report);
+        }
     }
+    profile
 }
 
-pub fn emit_ir(rfn: &RadecoFunction) -> String {
+pub fn emit_ir(rfn: &RadecoFunction, sections: &[LSectionInfo]) -> String {
     eprintln!("  [*] Writing out IR");
     let mut res = String::new();
-    ir_writer::emit_il(&mut res, Some(rfn.name.to_string()), rfn.ssa()).unwrap();
+    ir_writer::emit_il_with_sections(&mut res, Some(rfn.name.to_string()), rfn.ssa(), sections)
+        .unwrap();
     res
 }
 
+/// Longest paths, in blocks, translated by `emit_smt` by default.
+pub const SMT_MAX_BLOCKS: usize = 16;
+
+/// Emits the SMT-LIB2 translation of every loop-free path of `rfn` made of at most `max_blocks`
+/// blocks, see `smt::paths`. The paths are separated by `(reset)`.
+pub fn emit_smt(rfn: &RadecoFunction, max_blocks: Option<&str>) -> Result<String, String> {
+    let max_blocks = match max_blocks.map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) => n,
+        Some(Err(_)) => return Err("max_blocks must be a decimal number".to_owned()),
+        None => SMT_MAX_BLOCKS,
+    };
+    let ssa = rfn.ssa();
+    let mut res = String::new();
+    for (i, path) in smt::paths(ssa, max_blocks).iter().enumerate() {
+        if i > 0 {
+            res.push_str("(reset)\n");
+        }
+        let blocks = path
+            .iter()
+            .filter_map(|&b| ssa.starting_address(b))
+            .map(|addr| format!("{}", addr))
+            .collect::<Vec<_>>();
+        writeln!(res, "; path {}: {}", i, blocks.join(" ")).unwrap();
+        smt::emit_path(&mut res, ssa, path).unwrap();
+    }
+    Ok(res)
+}
+
+/// Blocks run by `emulate` before giving up, by default.
+pub const EMULATE_MAX_STEPS: usize = 10000;
+
+/// Runs `rfn` with the registers given in `spec` as `<reg>=<value>`, the others being 0, and
+/// lists the registers once it returned. `steps=<n>` sets the number of blocks run before giving
+/// up, see `interp::Interpreter`.
+pub fn emulate(rfn: &RadecoFunction, spec: &[&str]) -> Result<String, String> {
+    let regfile = &rfn.ssa().regfile;
+    let mut registers = HashMap::new();
+    let mut max_steps = EMULATE_MAX_STEPS;
+    for term in spec {
+        let mut kv = term.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some("steps"), Some(n)) => {
+                max_steps = n
+                    .parse::<usize>()
+                    .map_err(|_| "steps must be a decimal number".to_owned())?;
+            }
+            (Some(reg), Some(value)) if regfile.whole_names.iter().any(|r| r == reg) => {
+                registers.insert(reg.to_owned(), parse_addr(value)?);
+            }
+            (Some(reg), Some(_)) => return Err(format!("Unknown register {}", reg)),
+            _ => return Err(format!("Invalid register value {}", term)),
+        }
+    }
+
+    let mut interp = interp::Interpreter::new(rfn.ssa(), max_steps);
+    let emulation = interp.run(&registers).map_err(|e| e.to_string())?;
+    let mut res = String::new();
+    for (reg, value) in emulation.registers {
+        match value {
+            Some(value) => writeln!(res, "{} = {:#x}", reg, value).unwrap(),
+            None => writeln!(res, "{} = ?", reg).unwrap(),
+        }
+    }
+    write!(res, "Returned after {} blocks", emulation.steps).unwrap();
+    Ok(res)
+}
+
 pub fn emit_dot(ssa: &SSAStorage) -> String {
     dot::emit_dot(ssa)
 }
 
+pub fn emit_graphml(ssa: &SSAStorage) -> String {
+    dot::emit_graphml(ssa)
+}
+
+/// Emits the IR of the basic block of `rfn` containing `addr`, given as for `section_of`.
+pub fn emit_ir_block(
+    rfn: &RadecoFunction,
+    addr: &str,
+    sections: &[LSectionInfo],
+) -> Result<String, String> {
+    let addr = parse_addr(addr)?;
+    let block = utils::block_at(rfn.ssa(), addr)
+        .ok_or_else(|| format!("{:#x} is not inside {}", addr, rfn.name))?;
+    let mut res = String::new();
+    ir_writer::emit_il_block(&mut res, rfn.ssa(), block, sections).unwrap();
+    Ok(res)
+}
+
+/// Emits the graph of the basic block of `rfn` containing `addr`, along with its predecessors
+/// and successors.
+pub fn emit_dot_block(rfn: &RadecoFunction, addr: &str) -> Result<String, String> {
+    let addr = parse_addr(addr)?;
+    let ssa = rfn.ssa();
+    let block = utils::block_at(ssa, addr)
+        .ok_or_else(|| format!("{:#x} is not inside {}", addr, rfn.name))?;
+    let clusters = ssa
+        .preds_of(block)
+        .into_iter()
+        .chain(ssa.succs_of(block))
+        .chain(Some(block))
+        .map(|b| b.index())
+        .collect::<Vec<_>>();
+    Ok(dot::emit_dot_clusters(ssa, &clusters))
+}
+
 pub fn decompile_all_functions<'a>(proj: &'a RadecoProject) -> String {
     let mut decompiled_funcs = Vec::new();
     let funcs = fn_list(&proj);
@@ -113,14 +507,15 @@ pub fn decompile_all_functions<'a>(proj: &'a RadecoProject) -> String {
     decompiled_funcs.join("\n")
 }
 
-pub fn decompile<'a>(name: &str, proj: &'a RadecoProject) -> Result<String, String> {
+pub fn decompile<'a>(name: &str, proj: &'a RadecoProject) -> Result<String, RadecoError> {
     if let Some(rfn) = get_function(name, &proj) {
         let rmod = proj.iter().map(|i| i.module).next().unwrap();
         let func_name_map = func_names(&rmod);
         let strings = strings(&rmod);
-        decompile_priv(rfn, &func_name_map, &strings)
+        let calls = c_cfg_builder::resolve_calls(rmod, rfn);
+        decompile_priv(rfn, &func_name_map, &strings, rmod.sections(), &calls)
     } else {
-        Err(format!("{} is not found.", name))
+        Err(RadecoError::MissingFunction(name.to_string()))
     }
 }
 
@@ -128,12 +523,34 @@ fn decompile_priv(
     rfn: &RadecoFunction,
     func_name_map: &HashMap<u64, String>,
     strings: &HashMap<u64, String>,
-) -> Result<String, String> {
-    let c_cfg_result =
-        panic::catch_unwind(|| c_cfg_builder::recover_c_cfg(rfn, func_name_map, strings));
+    sections: &[LSectionInfo],
+    calls: &[ResolvedCall],
+) -> Result<String, RadecoError> {
+    let unsupported = unsupported_count(rfn);
+    if unsupported > 0 {
+        eprintln!(
+            "  [!] {} decompiled with {} unsupported instructions",
+            rfn.name, unsupported
+        );
+    }
+    if rfn.timed_out() {
+        eprintln!(
+            "  [!] {} timed out, its decompilation may be partial",
+            rfn.name
+        );
+    }
+    let options = RenderOptions {
+        sections,
+        calls,
+        ..RenderOptions::default()
+    };
+    let c_cfg_result = panic::catch_unwind(|| {
+        c_cfg_builder::recover_c_cfg_with(rfn, func_name_map, strings, options)
+    });
 
     if c_cfg_result.is_err() {
-        return Err("Failed to recover C control flow graph".to_string());
+        let msg = "Failed to recover C control flow graph".to_string();
+        return Err(RadecoError::Analysis(msg));
     };
 
     let c_cfg = c_cfg_result.unwrap();
@@ -145,7 +562,7 @@ fn decompile_priv(
     let result = panic::catch_unwind(|| {
         ctrl_flow_struct::structure_and_convert(c_cfg.clone())
             .map(|s| s.print())
-            .map_err(|e| e.to_string())
+            .map_err(|e| RadecoError::Analysis(e.to_string()))
     });
     match result {
         Ok(code) => code,
@@ -156,34 +573,181 @@ fn decompile_priv(
     }
 }
 
-pub fn load_proj_by_path(path: &str, max_it: u32) -> RadecoProject {
-    let mut p = ProjectLoader::new().path(path).load();
+/// Number of instructions of `rfn` containing ESIL which could not be lifted.
+pub fn unsupported_count(rfn: &RadecoFunction) -> usize {
+    rfn.unsupported()
+        .iter()
+        .map(|&(addr, _)| addr)
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// How the SSA of the functions is constructed when loading a project.
+///
+/// The defaults are conservative: every call reads and clobbers every register and the reads of
+/// the program counter are replaced by the address of the next instruction. Assuming the calling
+/// conventions makes for a much smaller SSA, and so a more readable output, but is wrong for the
+/// callees which don't follow them.
+#[derive(Clone, Copy, Debug)]
+pub struct ConstructOptions {
+    /// Calls only read the arguments and write the return value of the calling convention
+    pub assume_cc: bool,
+    /// Reads of the program counter are replaced by the address of the next instruction
+    pub replace_pc: bool,
+    /// Number of threads constructing the SSA, as many as there are CPUs if `None`
+    pub max_workers: Option<usize>,
+}
+
+impl Default for ConstructOptions {
+    fn default() -> ConstructOptions {
+        ConstructOptions {
+            assume_cc: false,
+            replace_pc: true,
+            max_workers: None,
+        }
+    }
+}
+
+// Loader of the projects, skipping the functions with more than `max_insts` instructions and
+// giving up on the ones taking longer than `fn_timeout`.
+fn project_loader<'a>(
+    max_insts: Option<usize>,
+    fn_timeout: Option<Duration>,
+    construct: ConstructOptions,
+) -> ProjectLoader<'a> {
+    let mut loader = ProjectLoader::new();
+    if let Some(max) = max_insts {
+        loader = loader.max_function_insts(max);
+    }
+    if let Some(budget) = fn_timeout {
+        loader = loader.fn_timeout(budget);
+    }
+    if construct.assume_cc {
+        loader = loader.assume_cc();
+    }
+    if !construct.replace_pc {
+        loader = loader.keep_pc();
+    }
+    if let Some(n) = construct.max_workers {
+        loader = loader.max_workers(n);
+    }
+    loader
+}
+
+pub fn load_proj_by_path(
+    path: &str,
+    max_it: u32,
+    max_insts: Option<usize>,
+    fn_timeout: Option<Duration>,
+    construct: ConstructOptions,
+) -> RadecoProject {
+    let mut p = project_loader(max_insts, fn_timeout, construct)
+        .path(path)
+        .load();
     let regfile = p.regfile().clone();
     for xy in p.iter_mut() {
-        let engine = RadecoEngine::new(max_it);
+        let engine = engine(max_it, fn_timeout);
         engine.run_module(xy.module, &*regfile.clone());
+        xy.module.functions.values_mut().for_each(annotate);
     }
     p
 }
 
-pub fn load_proj_tcp(url: &str, max_it: u32) -> Result<RadecoProject, &'static str> {
+/// Opens a project saved by `save_proj`.
+pub fn open_proj(path: &str) -> Result<RadecoProject, String> {
+    RadecoProject::load(path).map_err(|e| format!("Unable to open {}: {}", path, e))
+}
+
+/// Saves the project, with the results of the analyses, to `path`.
+pub fn save_proj(path: &str, proj: &RadecoProject) -> Result<(), String> {
+    proj.save(path)
+        .map_err(|e| format!("Unable to save to {}: {}", path, e))
+}
+
+pub fn load_proj_tcp(
+    url: &str,
+    max_it: u32,
+    reconnects: u32,
+    max_insts: Option<usize>,
+    fn_timeout: Option<Duration>,
+    construct: ConstructOptions,
+) -> Result<RadecoProject, &'static str> {
     let r2p = R2Pipe::tcp(url).map_err(|_| "Unable to connect to r2pipe")?;
-    Ok(load_project_by_r2pipe(r2p, max_it))
+    let url = url.to_string();
+    let reconnect = move || {
+        let r2p =
+            R2Pipe::tcp(&url).map_err(|_| SourceErr::SrcErr("Unable to connect to r2pipe"))?;
+        Ok(reopen_r2(r2p))
+    };
+    let source =
+        ReconnectingSource::new(Rc::new(RefCell::new(R2::from(r2p))), reconnect, reconnects);
+    Ok(load_project_by_source(
+        Rc::new(source),
+        max_it,
+        max_insts,
+        fn_timeout,
+        construct,
+    ))
 }
 
-pub fn load_proj_http(url: &str, max_it: u32) -> Result<RadecoProject, &'static str> {
+pub fn load_proj_http(
+    url: &str,
+    max_it: u32,
+    reconnects: u32,
+    max_insts: Option<usize>,
+    fn_timeout: Option<Duration>,
+    construct: ConstructOptions,
+) -> Result<RadecoProject, &'static str> {
     let r2p = R2Pipe::http(url);
-    Ok(load_project_by_r2pipe(r2p, max_it))
+    let url = url.to_string();
+    let reconnect = move || Ok(reopen_r2(R2Pipe::http(&url)));
+    let source =
+        ReconnectingSource::new(Rc::new(RefCell::new(R2::from(r2p))), reconnect, reconnects);
+    Ok(load_project_by_source(
+        Rc::new(source),
+        max_it,
+        max_insts,
+        fn_timeout,
+        construct,
+    ))
 }
 
-pub fn load_project_by_r2pipe(r2p: R2Pipe, max_it: u32) -> RadecoProject {
+// The server may have been restarted while we were disconnected, so redo the setup done when
+// radeco spawns r2 itself.
+fn reopen_r2(r2p: R2Pipe) -> Rc<RefCell<R2>> {
+    let mut r2 = R2::from(r2p);
+    let _ = r2.raw("e bin.minstr=1".to_string());
+    r2.analyze_all();
+    Rc::new(RefCell::new(r2))
+}
+
+pub fn load_project_by_r2pipe(
+    r2p: R2Pipe,
+    max_it: u32,
+    max_insts: Option<usize>,
+    fn_timeout: Option<Duration>,
+    construct: ConstructOptions,
+) -> RadecoProject {
     let r2 = R2::from(r2p);
     let r2w = Rc::new(RefCell::new(r2));
-    let mut p = ProjectLoader::new().source(Rc::new(r2w)).load();
+    load_project_by_source(Rc::new(r2w), max_it, max_insts, fn_timeout, construct)
+}
+
+fn load_project_by_source(
+    source: Rc<dyn Source>,
+    max_it: u32,
+    max_insts: Option<usize>,
+    fn_timeout: Option<Duration>,
+    construct: ConstructOptions,
+) -> RadecoProject {
+    let mut p = project_loader(max_insts, fn_timeout, construct)
+        .source(source)
+        .load();
     let regfile = p.regfile().clone();
     for xy in p.iter_mut() {
-        let engine = RadecoEngine::new(max_it);
+        let engine = engine(max_it, fn_timeout);
         engine.run_module(xy.module, &*regfile.clone());
+        xy.module.functions.values_mut().for_each(annotate);
     }
     p
 }
@@ -25,6 +25,7 @@ use rustyline::validate::Validator;
 use rustyline::{CompletionType, Config, Context, EditMode, Editor, Helper};
 use std::fs;
 use std::process;
+use std::time::Duration;
 
 mod scheme {
     pub const HTTP: &'static str = "http://";
@@ -80,13 +81,26 @@ impl Completer for Completes {
         let cmds = vec![
             command::HELP,
             command::LOAD,
+            command::OPEN,
+            command::SAVE,
             command::CONNECT,
             command::FNLIST,
             command::ANALYZE,
+            command::ANALYSES,
             command::DOT,
+            command::CFG,
             command::IR,
+            command::SMT,
+            command::EMULATE,
             command::DECOMPILE,
             command::FUNC_RENAME,
+            command::SET_CC,
+            command::CALLERS,
+            command::CALLEES,
+            command::CG_REPORT,
+            command::SECTIONS,
+            command::SECTION_OF,
+            command::XREFS,
             command::QUIT,
         ];
 
@@ -130,7 +144,7 @@ impl Completer for Completes {
                 }
             });
         }
-        if line.starts_with("load") {
+        if line.starts_with(command::LOAD) || line.starts_with(command::OPEN) {
             match self.file_completer.complete(line, _pos, ctx) {
                 Ok((n, ss)) => {
                     let mut completed_lines = ss
@@ -158,7 +172,23 @@ const SEP: &'static str = "END";
 fn main() {
     #[cfg(feature = "trace_log")]
     env_logger::init();
-    let (arg, cmd_opt, is_append_mode, is_batch_mode, no_highlight, max_it) = cli::parse_args();
+    let cli::CliArgs {
+        bin: arg,
+        command: cmd_opt,
+        append: is_append_mode,
+        batch: is_batch_mode,
+        no_highlight,
+        profile: is_profile_mode,
+        max_iterations: max_it,
+        reconnects,
+        max_insts,
+        fn_timeout,
+        signatures,
+        annotations,
+        construct,
+    } = cli::parse_args();
+    core::SIGNATURES.with(|sigs| *sigs.borrow_mut() = signatures);
+    core::ANNOTATIONS.with(|notes| *notes.borrow_mut() = annotations);
     let config = Config::builder()
         .auto_add_history(true)
         .history_ignore_space(true)
@@ -170,18 +200,32 @@ fn main() {
     core::PROJ.with(move |proj| {
         use r2pipe::R2Pipe;
         let proj_result = match arg {
-            Some(ref s) if scheme::is_http(s) => {
-                core::load_proj_http(&s[scheme::HTTP.len()..], max_it)
-                    .map_err(|e| Some(e.to_string()))
-            }
-            Some(ref s) if scheme::is_tcp(s) => {
-                core::load_proj_tcp(&s[scheme::TCP.len()..], max_it)
-                    .map_err(|e| Some(e.to_string()))
-            }
-            Some(ref s) if is_file(s) => Ok(core::load_proj_by_path(s, max_it)),
+            Some(ref s) if scheme::is_http(s) => core::load_proj_http(
+                &s[scheme::HTTP.len()..],
+                max_it,
+                reconnects,
+                max_insts,
+                fn_timeout,
+                construct,
+            )
+            .map_err(|e| Some(e.to_string())),
+            Some(ref s) if scheme::is_tcp(s) => core::load_proj_tcp(
+                &s[scheme::TCP.len()..],
+                max_it,
+                reconnects,
+                max_insts,
+                fn_timeout,
+                construct,
+            )
+            .map_err(|e| Some(e.to_string())),
+            Some(ref s) if is_file(s) => Ok(core::load_proj_by_path(
+                s, max_it, max_insts, fn_timeout, construct,
+            )),
             Some(s) => Err(Some(format!("Invalid argument {}", s))),
             None => match R2Pipe::open() {
-                Ok(r2p) => Ok(core::load_project_by_r2pipe(r2p, max_it)),
+                Ok(r2p) => Ok(core::load_project_by_r2pipe(
+                    r2p, max_it, max_insts, fn_timeout, construct,
+                )),
                 Err(_) => Err(None),
             },
         };
@@ -201,18 +245,29 @@ fn main() {
             // If a command is specified by the user run it,
             // otherwise decompile all functions.
             if let Some(command) = cmd_opt {
-                cmd(command, no_highlight, max_it);
+                cmd(
+                    command,
+                    no_highlight,
+                    max_it,
+                    reconnects,
+                    max_insts,
+                    fn_timeout,
+                    construct,
+                );
             } else {
                 let mut proj_ = proj_opt.borrow_mut();
                 let proj = proj_.as_mut().unwrap();
 
-                core::analyze_all_functions(proj, max_it);
+                let profile = core::analyze_all_functions(proj, max_it, fn_timeout);
                 let decompiled = core::decompile_all_functions(proj);
                 if no_highlight {
                     println!("{}", decompiled);
                 } else {
                     highlighting::print_highlighted(&decompiled);
                 }
+                if is_profile_mode {
+                    eprintln!("{}", profile.table());
+                }
             }
 
             process::exit(0);
@@ -223,7 +278,15 @@ fn main() {
         let readline = rl.readline(PROMPT);
         match readline {
             Ok(line) => {
-                cmd(line, !no_highlight, max_it);
+                cmd(
+                    line,
+                    !no_highlight,
+                    max_it,
+                    reconnects,
+                    max_insts,
+                    fn_timeout,
+                    construct,
+                );
                 if is_append_mode {
                     println!("{}", SEP);
                 }
@@ -241,13 +304,26 @@ fn main() {
 mod command {
     pub const HELP: &'static str = "help";
     pub const LOAD: &'static str = "load";
+    pub const OPEN: &'static str = "open";
+    pub const SAVE: &'static str = "save";
     pub const CONNECT: &'static str = "connect";
     pub const FNLIST: &'static str = "fn_list";
     pub const ANALYZE: &'static str = "analyze";
+    pub const ANALYSES: &'static str = "analyses";
     pub const DOT: &'static str = "dot";
+    pub const CFG: &'static str = "cfg";
     pub const IR: &'static str = "ir";
+    pub const SMT: &'static str = "smt";
+    pub const EMULATE: &'static str = "emulate";
     pub const DECOMPILE: &'static str = "decompile";
     pub const FUNC_RENAME: &'static str = "fn_rn";
+    pub const SET_CC: &'static str = "set_cc";
+    pub const CALLERS: &'static str = "callers";
+    pub const CALLEES: &'static str = "callees";
+    pub const CG_REPORT: &'static str = "cg_report";
+    pub const SECTIONS: &'static str = "sections";
+    pub const SECTION_OF: &'static str = "section_of";
+    pub const XREFS: &'static str = "xrefs";
     pub const QUIT: &'static str = "quit";
 
     pub fn help() {
@@ -258,9 +334,20 @@ mod command {
             format!("{} path", LOAD),
             width = width
         );
+        println!(
+            "{:width$}    Open project saved with {}",
+            format!("{} path", OPEN),
+            SAVE,
+            width = width
+        );
+        println!(
+            "{:width$}    Save project with the results of the analyses",
+            format!("{} path", SAVE),
+            width = width
+        );
         println!(
             "{:width$}    Connect to radare2 server",
-            format!("{} (http|tcp)://<url>", CONNECT),
+            format!("{} (http|tcp)://<url> [reconnects]", CONNECT),
             width = width
         );
         println!("{:width$}    Show function list", FNLIST, width = width);
@@ -270,13 +357,43 @@ mod command {
             width = width
         );
         println!(
-            "{:width$}    Emit IR of <func>",
-            format!("{} <func>", IR),
+            "{:width$}    Run the analysis <name> on <func>, after the ones it requires",
+            format!("{} <func> --pass <name>", ANALYZE),
+            width = width
+        );
+        println!(
+            "{:width$}    List the analyses, with the ones they require",
+            ANALYSES,
+            width = width
+        );
+        println!(
+            "{:width$}    Emit IR of <func>, or of its block at <addr>",
+            format!("{} <func> [@ <addr>]", IR),
+            width = width
+        );
+        println!(
+            "{:width$}    Emit graph of the IR of <func> in GraphML",
+            format!("{} <func> --graphml", IR),
+            width = width
+        );
+        println!(
+            "{:width$}    Emit graph of the IR in Graphviz dot, or of its block at <addr>",
+            format!("{} <func> [@ <addr>]", DOT),
+            width = width
+        );
+        println!(
+            "{:width$}    Emit the control flow graph of <func> in Graphviz dot",
+            format!("{} <func>", CFG),
+            width = width
+        );
+        println!(
+            "{:width$}    Emit the loop-free paths of <func> in SMT-LIB2",
+            format!("{} <func> [max_blocks]", SMT),
             width = width
         );
         println!(
-            "{:width$}    Emit graph of the IR in Graphviz dot",
-            format!("{} <func>", DOT),
+            "{:width$}    Run <func> from the given register values, until it returns",
+            format!("{} <func> [<reg>=<value>...] [steps=<n>]", EMULATE),
             width = width
         );
         println!(
@@ -289,19 +406,64 @@ mod command {
             format!("{} <old_name> <new_name>", FUNC_RENAME),
             width = width
         );
+        println!(
+            "{:width$}    Set the calling convention of <func>",
+            format!("{} <func> args=<regs> ret=<reg> clobber=<regs>", SET_CC),
+            width = width
+        );
+        println!(
+            "{:width$}    List the calls made to <func>",
+            format!("{} <func>", CALLERS),
+            width = width
+        );
+        println!(
+            "{:width$}    List the calls made by <func>",
+            format!("{} <func>", CALLEES),
+            width = width
+        );
+        println!(
+            "{:width$}    Show the callees of every function, with their number of calls",
+            CG_REPORT,
+            width = width
+        );
+        println!(
+            "{:width$}    Emit the call graph of every module in GraphML",
+            format!("{} --graphml", CG_REPORT),
+            width = width
+        );
+        println!("{:width$}    List sections", SECTIONS, width = width);
+        println!(
+            "{:width$}    Show the section containing <addr>",
+            format!("{} <addr>", SECTION_OF),
+            width = width
+        );
+        println!(
+            "{:width$}    List the code and data references to <addr>",
+            format!("{} <addr>", XREFS),
+            width = width
+        );
         println!("{:width$}    Quit interactive prompt", QUIT, width = width);
     }
 
     /// Returns true if `cmd` requires a function as parameter.
     pub fn requires_func(cmd: &str) -> bool {
         match cmd {
-            ANALYZE | DOT | IR | DECOMPILE | FUNC_RENAME => true,
+            ANALYZE | DOT | CFG | IR | SMT | EMULATE | DECOMPILE | FUNC_RENAME | SET_CC
+            | CALLERS | CALLEES => true,
             _ => false,
         }
     }
 }
 
-fn cmd(line: String, highlight: bool, max_it: u32) {
+fn cmd(
+    line: String,
+    highlight: bool,
+    max_it: u32,
+    reconnects: u32,
+    max_insts: Option<usize>,
+    fn_timeout: Option<Duration>,
+    construct: core::ConstructOptions,
+) {
     if line.is_empty() {
         return;
     }
@@ -310,6 +472,8 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
     let op1 = terms.next();
     let op2 = terms.next();
     let op3 = terms.next();
+    let op4 = terms.next();
+    let rest = terms.collect::<Vec<_>>();
 
     core::PROJ.with(|proj_opt| {
         match (op1, op2, op3) {
@@ -317,20 +481,55 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
                 command::help();
                 return;
             }
+            (Some(command::ANALYSES), _, _) => {
+                println!("{}", core::analyses());
+                return;
+            }
             (Some(command::LOAD), Some(path), _) => {
                 if is_file(path) {
-                    *proj_opt.borrow_mut() = Some(core::load_proj_by_path(path, max_it));
+                    *proj_opt.borrow_mut() = Some(core::load_proj_by_path(
+                        path, max_it, max_insts, fn_timeout, construct,
+                    ));
                     return;
                 } else {
                     println!("{} is not found.", path);
                     return;
                 }
             }
-            (Some(command::CONNECT), Some(url), _) => {
+            (Some(command::OPEN), Some(path), _) => {
+                match core::open_proj(path) {
+                    Ok(p) => *proj_opt.borrow_mut() = Some(p),
+                    Err(err) => println!("{}", err),
+                }
+                return;
+            }
+            (Some(command::CONNECT), Some(url), n) => {
+                let reconnects = match n.map(|n| n.parse::<u32>()) {
+                    Some(Ok(n)) => n,
+                    Some(Err(_)) => {
+                        println!("reconnects must be a decimal number");
+                        return;
+                    }
+                    None => reconnects,
+                };
                 let p_opt = if scheme::is_http(&url) {
-                    core::load_proj_http(&url[scheme::HTTP.len()..], max_it)
+                    core::load_proj_http(
+                        &url[scheme::HTTP.len()..],
+                        max_it,
+                        reconnects,
+                        max_insts,
+                        fn_timeout,
+                        construct,
+                    )
                 } else if scheme::is_tcp(&url) {
-                    core::load_proj_tcp(&url[scheme::TCP.len()..], max_it)
+                    core::load_proj_tcp(
+                        &url[scheme::TCP.len()..],
+                        max_it,
+                        reconnects,
+                        max_insts,
+                        fn_timeout,
+                        construct,
+                    )
                 } else {
                     Err("Invalid url")
                 };
@@ -353,17 +552,42 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
         let proj = proj_.as_mut().unwrap();
         match (op1, op2, op3) {
             (Some(command::ANALYZE), Some("*"), _) => {
-                core::analyze_all_functions(proj, max_it);
+                core::analyze_all_functions(proj, max_it, fn_timeout);
+            }
+            (Some(command::SAVE), Some(path), _) => {
+                if let Err(err) = core::save_proj(path, &proj) {
+                    println!("{}", err);
+                }
             }
             (Some(command::FNLIST), _, _) => {
                 let funcs = core::fn_list(&proj);
                 println!("{}", funcs.join("\n"));
             }
-            // TODO Show list of dependency information of analyses
-            // TODO Add command for individual analyses
+            (Some(command::ANALYZE), Some(f), Some("--pass")) => {
+                core::rebuild_dirty(proj);
+                if let Some(rfn) = core::get_function_mut(f, proj) {
+                    match core::analyze_pass(rfn, op4.unwrap_or(""), max_it) {
+                        Ok(res) => println!("{}", res),
+                        Err(err) => println!("{}", err),
+                    }
+                } else {
+                    println!("{} is not found", f);
+                }
+            }
             (Some(command::ANALYZE), Some(f), _) => {
+                core::rebuild_dirty(proj);
                 if let Some(rfn) = core::get_function_mut(f, proj) {
-                    core::analyze(rfn, max_it);
+                    core::analyze(rfn, max_it, fn_timeout);
+                } else {
+                    println!("{} is not found", f);
+                }
+            }
+            (Some(command::DOT), Some(f), Some("@")) => {
+                if let Some(rfn) = core::get_function(f, &proj) {
+                    match core::emit_dot_block(rfn, op4.unwrap_or("")) {
+                        Ok(res) => println!("{}", res),
+                        Err(err) => println!("{}", err),
+                    }
                 } else {
                     println!("{} is not found", f);
                 }
@@ -375,9 +599,60 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
                     println!("{} is not found", f);
                 }
             }
+            (Some(command::CFG), Some(f), _) => {
+                if let Some(rfn) = core::get_function(f, &proj) {
+                    println!("{}", rfn.cfg_dot());
+                } else {
+                    println!("{} is not found", f);
+                }
+            }
+            (Some(command::IR), Some(f), Some("@")) => {
+                if let Some(rfn) = core::get_function(f, &proj) {
+                    let sections = core::project_sections(&proj);
+                    match core::emit_ir_block(rfn, op4.unwrap_or(""), &sections) {
+                        Ok(res) => println!("{}", res),
+                        Err(err) => println!("{}", err),
+                    }
+                } else {
+                    println!("{} is not found", f);
+                }
+            }
+            (Some(command::IR), Some(f), Some("--graphml")) => {
+                if let Some(rfn) = core::get_function(f, &proj) {
+                    println!("{}", core::emit_graphml(rfn.ssa()));
+                } else {
+                    println!("{} is not found", f);
+                }
+            }
             (Some(command::IR), Some(f), _) => {
                 if let Some(rfn) = core::get_function(f, &proj) {
-                    println!("{}", core::emit_ir(rfn));
+                    let sections = core::project_sections(&proj);
+                    println!("{}", core::emit_ir(rfn, &sections));
+                } else {
+                    println!("{} is not found", f);
+                }
+            }
+            (Some(command::SMT), Some(f), n) => {
+                if let Some(rfn) = core::get_function(f, &proj) {
+                    match core::emit_smt(rfn, n) {
+                        Ok(res) => println!("{}", res),
+                        Err(err) => println!("{}", err),
+                    }
+                } else {
+                    println!("{} is not found", f);
+                }
+            }
+            (Some(command::EMULATE), Some(f), _) => {
+                if let Some(rfn) = core::get_function(f, &proj) {
+                    let spec = op3
+                        .into_iter()
+                        .chain(op4)
+                        .chain(rest.iter().cloned())
+                        .collect::<Vec<_>>();
+                    match core::emulate(rfn, &spec) {
+                        Ok(res) => println!("{}", res),
+                        Err(err) => println!("{}", err),
+                    }
                 } else {
                     println!("{} is not found", f);
                 }
@@ -403,6 +678,41 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
             (Some(command::FUNC_RENAME), Some(old_f), Some(new_f)) => {
                 core::fn_rename(old_f, new_f, proj);
             }
+            (Some(command::SET_CC), Some(f), _) => {
+                let spec = op3
+                    .into_iter()
+                    .chain(op4)
+                    .chain(rest.iter().cloned())
+                    .collect::<Vec<_>>();
+                if let Err(err) = core::set_cc(f, &spec, proj, max_it, fn_timeout) {
+                    println!("{}", err);
+                }
+            }
+            (Some(command::CALLERS), Some(f), _) => match core::callers(f, &proj) {
+                Ok(res) => println!("{}", res),
+                Err(err) => println!("{}", err),
+            },
+            (Some(command::CALLEES), Some(f), _) => match core::callees(f, &proj) {
+                Ok(res) => println!("{}", res),
+                Err(err) => println!("{}", err),
+            },
+            (Some(command::CG_REPORT), Some("--graphml"), _) => {
+                println!("{}", core::callgraph_graphml(&proj));
+            }
+            (Some(command::CG_REPORT), _, _) => {
+                println!("{}", core::callgraph_report(&proj));
+            }
+            (Some(command::SECTIONS), _, _) => {
+                println!("{}", core::sections(&proj));
+            }
+            (Some(command::SECTION_OF), Some(addr), _) => match core::section_of(addr, &proj) {
+                Ok(res) => println!("{}", res),
+                Err(err) => println!("{}", err),
+            },
+            (Some(command::XREFS), Some(addr), _) => match core::xrefs(addr, &proj) {
+                Ok(res) => println!("{}", res),
+                Err(err) => println!("{}", err),
+            },
             _ => {
                 println!(
                     "Invalid command {} {}",